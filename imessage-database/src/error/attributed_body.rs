@@ -0,0 +1,25 @@
+/*!
+ Errors that can happen when parsing an archived `NSAttributedString`.
+*/
+
+use std::fmt::{Display, Formatter, Result};
+
+use crate::error::{plist::PlistParseError, typedstream::TypedStreamError};
+
+/// Errors that can happen when parsing an archived `NSAttributedString`
+#[derive(Debug)]
+pub enum AttributedBodyError {
+    /// The data is a `typedstream` that could not be decoded
+    TypedStreamError(TypedStreamError),
+    /// The data is an `NSKeyedArchiver` plist that could not be decoded
+    KeyedArchiveError(PlistParseError),
+}
+
+impl Display for AttributedBodyError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            AttributedBodyError::TypedStreamError(why) => write!(fmt, "{why}"),
+            AttributedBodyError::KeyedArchiveError(why) => write!(fmt, "{why}"),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+/*!
+ Errors that can happen when decrypting an encrypted iOS or iPadOS backup.
+*/
+
+use std::{
+    fmt::{Display, Formatter, Result},
+    io::Error as IoError,
+    path::PathBuf,
+};
+
+use crate::error::plist::PlistParseError;
+
+/// Errors that can happen when decrypting an encrypted backup
+#[derive(Debug)]
+pub enum BackupError {
+    /// `Manifest.plist` could not be read
+    InvalidManifest(plist::Error),
+    /// `Manifest.plist` is missing a key needed to decrypt the backup
+    MissingKey(String),
+    /// The keybag in `Manifest.plist` could not be read
+    InvalidKeybag,
+    /// The password did not unlock the keybag
+    WrongPassword,
+    /// A file is protected with a class key the keybag does not hold
+    MissingClassKey(u32),
+    /// A file or key could not be decrypted
+    CannotDecrypt(String),
+    /// The metadata stored for a file in `Manifest.db` could not be read
+    InvalidFile(String, PlistParseError),
+    /// The decrypted `Manifest.db` could not be queried
+    ManifestError(rusqlite::Error),
+    /// A file in the backup could not be read
+    CannotRead(IoError),
+    /// A decrypted file could not be written
+    CannotWrite(IoError, PathBuf),
+}
+
+impl Display for BackupError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            BackupError::InvalidManifest(why) => {
+                write!(fmt, "Unable to read backup manifest: {why}")
+            }
+            BackupError::MissingKey(key) => {
+                write!(fmt, "Backup manifest does not contain {key}!")
+            }
+            BackupError::InvalidKeybag => write!(fmt, "Backup keybag is invalid!"),
+            BackupError::WrongPassword => write!(fmt, "Incorrect backup password!"),
+            BackupError::MissingClassKey(class) => {
+                write!(
+                    fmt,
+                    "Backup keybag has no key for protection class {class}!"
+                )
+            }
+            BackupError::CannotDecrypt(name) => write!(fmt, "Unable to decrypt {name}!"),
+            BackupError::InvalidFile(file_id, why) => {
+                write!(
+                    fmt,
+                    "Unable to read metadata for backup file {file_id}: {why}"
+                )
+            }
+            BackupError::ManifestError(why) => {
+                write!(fmt, "Unable to read decrypted backup manifest: {why}")
+            }
+            BackupError::CannotRead(why) => write!(fmt, "Unable to read backup file: {why}"),
+            BackupError::CannotWrite(why, path) => {
+                write!(fmt, "Unable to write decrypted file {path:?}: {why}")
+            }
+        }
+    }
+}
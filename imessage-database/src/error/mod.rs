@@ -3,10 +3,14 @@
 */
 
 pub mod attachment;
+pub mod attributed_body;
+#[cfg(feature = "encrypted-backups")]
+pub mod backup;
 pub mod handwriting;
 pub mod message;
 pub mod plist;
 pub mod query_context;
+pub mod sms_backup;
 pub mod streamtyped;
 pub mod table;
 pub mod typedstream;
@@ -19,6 +19,7 @@ pub enum PlistParseError {
     InvalidEditedMessage(String),
     StreamTypedError(StreamTypedError),
     HandwritingError(HandwritingError),
+    InvalidPlist(plist::Error),
 }
 
 impl Display for PlistParseError {
@@ -48,6 +49,7 @@ impl Display for PlistParseError {
             }
             PlistParseError::StreamTypedError(why) => write!(fmt, "{why}"),
             PlistParseError::HandwritingError(why) => write!(fmt, "{why}"),
+            PlistParseError::InvalidPlist(why) => write!(fmt, "Unable to read plist data: {why}"),
         }
     }
 }
@@ -4,10 +4,15 @@
 
 use std::fmt::{Display, Formatter, Result};
 
+use crate::util::query_context::ATTACHMENT_TYPES;
+
 /// Errors that can happen when parsing query context data
 #[derive(Debug)]
 pub enum QueryContextError {
     InvalidDate(String),
+    InvalidService(String),
+    InvalidAttachmentType(String),
+    InvalidGuid(String),
 }
 
 impl Display for QueryContextError {
@@ -17,6 +22,19 @@ impl Display for QueryContextError {
                 fmt,
                 "Invalid date provided: {date}! Must be in format YYYY-MM-DD."
             ),
+            QueryContextError::InvalidService(service) => write!(
+                fmt,
+                "Invalid service provided: {service}! Must be one of <imessage, sms>."
+            ),
+            QueryContextError::InvalidAttachmentType(attachment_type) => write!(
+                fmt,
+                "Invalid attachment type provided: {attachment_type}! Must be one of <{}>.",
+                ATTACHMENT_TYPES.join(", ")
+            ),
+            QueryContextError::InvalidGuid(guid) => write!(
+                fmt,
+                "Invalid message GUID provided: {guid}! Must only contain letters, numbers, and dashes."
+            ),
         }
     }
 }
@@ -0,0 +1,37 @@
+/*!
+ Errors that can happen when reading an Android SMS Backup & Restore file.
+*/
+
+use std::{
+    fmt::{Display, Formatter, Result},
+    io::Error as IoError,
+    path::PathBuf,
+};
+
+/// Errors that can happen when reading an SMS Backup & Restore file
+#[derive(Debug)]
+pub enum SmsBackupError {
+    /// The backup file could not be opened
+    CannotRead(IoError),
+    /// The backup file is not valid XML
+    InvalidXml(quick_xml::Error),
+    /// The converted messages could not be written to the database
+    CannotWrite(rusqlite::Error),
+    /// An attachment could not be written to disk
+    CannotWriteAttachment(IoError, PathBuf),
+}
+
+impl Display for SmsBackupError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            SmsBackupError::CannotRead(why) => write!(fmt, "Unable to read SMS backup: {why}"),
+            SmsBackupError::InvalidXml(why) => write!(fmt, "Unable to parse SMS backup: {why}"),
+            SmsBackupError::CannotWrite(why) => {
+                write!(fmt, "Unable to write SMS backup messages: {why}")
+            }
+            SmsBackupError::CannotWriteAttachment(why, path) => {
+                write!(fmt, "Unable to write SMS backup attachment {path:?}: {why}")
+            }
+        }
+    }
+}
@@ -4,6 +4,8 @@
 
 use std::fmt::{Display, Formatter, Result};
 
+use crate::error::sms_backup::SmsBackupError;
+
 /// Errors that can happen when extracting data from a `SQLite` table
 #[derive(Debug)]
 pub enum TableError {
@@ -12,8 +14,11 @@ pub enum TableError {
     Chat(rusqlite::Error),
     Handle(rusqlite::Error),
     Messages(rusqlite::Error),
+    Contacts(rusqlite::Error),
     CannotConnect(String),
     CannotRead(std::io::Error),
+    Merge(rusqlite::Error),
+    SmsBackup(SmsBackupError),
 }
 
 impl Display for TableError {
@@ -24,8 +29,11 @@ impl Display for TableError {
             TableError::Chat(why) => write!(fmt, "Failed to parse chat row: {why}"),
             TableError::Handle(why) => write!(fmt, "Failed to parse handle row: {why}"),
             TableError::Messages(why) => write!(fmt, "Failed to parse messages row: {why}"),
+            TableError::Contacts(why) => write!(fmt, "Failed to read contacts database: {why}"),
             TableError::CannotConnect(why) => write!(fmt, "{why}"),
             TableError::CannotRead(why) => write!(fmt, "{why}"),
+            TableError::Merge(why) => write!(fmt, "Failed to merge databases: {why}"),
+            TableError::SmsBackup(why) => write!(fmt, "{why}"),
         }
     }
 }
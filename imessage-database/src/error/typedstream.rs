@@ -4,18 +4,23 @@
 
 use std::{
     array::TryFromSliceError,
-    fmt::{Display, Formatter, Result}, str::Utf8Error,
+    fmt::{Display, Formatter, Result},
+    str::Utf8Error,
 };
 
 /// Errors that can happen when parsing `typedstream` data
+///
+/// Each `usize` is the byte offset in the stream where the problem was found.
 #[derive(Debug)]
 pub enum TypedStreamError {
+    /// Reading up to the first offset would go past the end of a stream of the second length
     OutOfBounds(usize, usize),
     InvalidHeader,
     SliceError(TryFromSliceError),
-    StringParseError(Utf8Error),
-    InvalidArray,
-    InvalidPointer(u8),
+    StringParseError(usize, Utf8Error),
+    InvalidArray(usize),
+    InvalidPointer(usize, u8),
+    MissingText,
 }
 
 impl Display for TypedStreamError {
@@ -28,9 +33,16 @@ impl Display for TypedStreamError {
             TypedStreamError::SliceError(why) => {
                 write!(fmt, "Unable to slice source stream: {why}")
             }
-            TypedStreamError::StringParseError(why) => write!(fmt, "Failed to parse string: {why}"),
-            TypedStreamError::InvalidArray => write!(fmt, "Failed to parse array data"),
-            TypedStreamError::InvalidPointer(why) => write!(fmt, "Failed to parse pointer: {why}"),
+            TypedStreamError::StringParseError(idx, why) => {
+                write!(fmt, "Failed to parse string at index {idx:x}: {why}")
+            }
+            TypedStreamError::InvalidArray(idx) => {
+                write!(fmt, "Failed to parse array data at index {idx:x}")
+            }
+            TypedStreamError::InvalidPointer(idx, why) => {
+                write!(fmt, "Failed to parse pointer at index {idx:x}: {why}")
+            }
+            TypedStreamError::MissingText => write!(fmt, "typedstream does not contain a string!"),
         }
     }
 }
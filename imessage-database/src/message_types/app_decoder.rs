@@ -0,0 +1,360 @@
+/*!
+ Decoders that summarize the messages sent by specific apps.
+
+ Every app fills the fields of an [`AppMessage`] differently, so a generic rendering can only list them.
+ An [`AppDecoder`] knows what the fields mean for one app, like the game in a GamePigeon message or
+ the restaurant in an OpenTable reservation, and describes the message in a sentence.
+
+ Decoders are found by the app's bundle ID in an [`AppDecoders`] registry. To support a new app,
+ implement [`AppDecoder`] and [`register`](AppDecoders::register) it.
+*/
+
+use crate::message_types::app::AppMessage;
+
+/// A description of an app message
+#[derive(Debug, PartialEq, Eq)]
+pub struct AppSummary {
+    /// The name of the app, i.e. `GamePigeon`
+    pub app: String,
+    /// A sentence describing the message, i.e. `Reservation at Nopa`
+    pub description: String,
+    /// More information about the message, one line per item
+    pub details: Vec<String>,
+}
+
+/// Summarizes the messages sent by one app
+pub trait AppDecoder: Sync {
+    /// The bundle ID of the app's iMessage extension, or a prefix shared by all of the app's extensions
+    fn bundle_id(&self) -> &str;
+    /// Describe a message sent by the app, if it holds enough data to do so
+    fn summarize(&self, balloon: &AppMessage) -> Option<AppSummary>;
+}
+
+/// The decoders for the apps this crate knows about
+pub static DEFAULT_DECODERS: &[&dyn AppDecoder] = &[
+    &ApplePayDecoder,
+    &GamePigeonDecoder,
+    &OpenTableDecoder,
+    &ShazamDecoder,
+    &PollsDecoder,
+];
+
+/// A registry of [`AppDecoder`]s, keyed on bundle ID
+pub struct AppDecoders<'a> {
+    /// The decoders to try, in order
+    decoders: Vec<&'a dyn AppDecoder>,
+}
+
+impl Default for AppDecoders<'_> {
+    fn default() -> Self {
+        Self {
+            decoders: DEFAULT_DECODERS.to_vec(),
+        }
+    }
+}
+
+impl<'a> AppDecoders<'a> {
+    /// Add a decoder, which is used before any decoder already registered for the same app
+    pub fn register(&mut self, decoder: &'a dyn AppDecoder) {
+        self.decoders.insert(0, decoder);
+    }
+
+    /// Describe a message sent by the app with the given bundle ID, if there is a decoder for it
+    pub fn summarize(&self, bundle_id: &str, balloon: &AppMessage) -> Option<AppSummary> {
+        summarize_with(&self.decoders, bundle_id, balloon)
+    }
+}
+
+/// Describe a message sent by the app with the given bundle ID using the [`DEFAULT_DECODERS`]
+pub fn summarize_app(bundle_id: &str, balloon: &AppMessage) -> Option<AppSummary> {
+    summarize_with(DEFAULT_DECODERS, bundle_id, balloon)
+}
+
+/// Find the decoder for a bundle ID and use it to describe a message
+fn summarize_with(
+    decoders: &[&dyn AppDecoder],
+    bundle_id: &str,
+    balloon: &AppMessage,
+) -> Option<AppSummary> {
+    decoders
+        .iter()
+        .find(|decoder| bundle_id.starts_with(decoder.bundle_id()))
+        .and_then(|decoder| decoder.summarize(balloon))
+}
+
+/// Collect the fields that are present, splitting multiline fields into their lines
+fn lines(fields: &[Option<&str>]) -> Vec<String> {
+    fields
+        .iter()
+        .flatten()
+        .flat_map(|field| field.lines())
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Apple Pay payments and requests
+pub struct ApplePayDecoder;
+
+impl AppDecoder for ApplePayDecoder {
+    fn bundle_id(&self) -> &str {
+        "com.apple.PassbookUIService.PeerPaymentMessagesExtension"
+    }
+
+    fn summarize(&self, balloon: &AppMessage) -> Option<AppSummary> {
+        // The sentence Apple shows in the conversation list, i.e. `Sent $265 with Apple Pay.`
+        let description = balloon.ldtext.or(balloon.subcaption)?;
+        Some(AppSummary {
+            app: balloon.app_name.unwrap_or("Apple Pay").to_string(),
+            description: description.to_string(),
+            details: lines(&[balloon.caption]),
+        })
+    }
+}
+
+/// GamePigeon games
+pub struct GamePigeonDecoder;
+
+impl AppDecoder for GamePigeonDecoder {
+    fn bundle_id(&self) -> &str {
+        "com.gamerdelights.gamepigeon"
+    }
+
+    fn summarize(&self, balloon: &AppMessage) -> Option<AppSummary> {
+        // The name of the game is stored as the summary text
+        let game = balloon.ldtext?;
+        Some(AppSummary {
+            app: String::from("GamePigeon"),
+            description: format!("Game of {game}"),
+            details: lines(&[balloon.caption, balloon.subcaption]),
+        })
+    }
+}
+
+/// OpenTable reservations
+pub struct OpenTableDecoder;
+
+impl AppDecoder for OpenTableDecoder {
+    fn bundle_id(&self) -> &str {
+        "com.contextoptional.OpenTable"
+    }
+
+    fn summarize(&self, balloon: &AppMessage) -> Option<AppSummary> {
+        let restaurant = balloon.title?;
+        Some(AppSummary {
+            app: String::from("OpenTable"),
+            description: format!("Reservation at {restaurant}"),
+            details: lines(&[balloon.subtitle, balloon.caption, balloon.subcaption]),
+        })
+    }
+}
+
+/// Songs shared from Shazam
+pub struct ShazamDecoder;
+
+impl AppDecoder for ShazamDecoder {
+    fn bundle_id(&self) -> &str {
+        "com.shazam.Shazam"
+    }
+
+    fn summarize(&self, balloon: &AppMessage) -> Option<AppSummary> {
+        let song = balloon.title.or(balloon.caption)?;
+        let description = match balloon.subtitle.or(balloon.subcaption) {
+            Some(artist) => format!("{song} by {artist}"),
+            None => song.to_string(),
+        };
+        Some(AppSummary {
+            app: String::from("Shazam"),
+            description,
+            details: vec![],
+        })
+    }
+}
+
+/// Polls created in a conversation
+pub struct PollsDecoder;
+
+impl AppDecoder for PollsDecoder {
+    fn bundle_id(&self) -> &str {
+        "com.apple.messages.Polls"
+    }
+
+    fn summarize(&self, balloon: &AppMessage) -> Option<AppSummary> {
+        let question = balloon.caption.or(balloon.title).or(balloon.ldtext)?;
+        Some(AppSummary {
+            app: balloon.app_name.unwrap_or("Polls").to_string(),
+            description: format!("Poll: {question}"),
+            details: lines(&[balloon.subcaption, balloon.trailing_caption]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::File};
+
+    use plist::Value;
+
+    use crate::{
+        message_types::{
+            app::AppMessage,
+            app_decoder::{summarize_app, AppDecoder, AppDecoders, AppSummary},
+            variants::BalloonProvider,
+        },
+        util::plist::parse_plist,
+    };
+
+    fn read_payload(name: &str) -> Value {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join(format!("test_data/app_message/{name}.plist"));
+        let plist = Value::from_reader(File::open(plist_path).unwrap()).unwrap();
+        parse_plist(&plist).unwrap()
+    }
+
+    fn blank() -> AppMessage<'static> {
+        AppMessage {
+            image: None,
+            url: None,
+            title: None,
+            subtitle: None,
+            caption: None,
+            subcaption: None,
+            trailing_caption: None,
+            trailing_subcaption: None,
+            app_name: None,
+            ldtext: None,
+        }
+    }
+
+    #[test]
+    fn can_summarize_apple_pay() {
+        let payload = read_payload("Sent265");
+        let balloon = AppMessage::from_map(&payload).unwrap();
+
+        let expected = AppSummary {
+            app: "Apple\u{a0}Pay".to_string(),
+            description: "Sent $265 with Apple\u{a0}Pay.".to_string(),
+            details: vec!["Apple\u{a0}Cash".to_string()],
+        };
+        assert_eq!(
+            summarize_app(
+                "com.apple.PassbookUIService.PeerPaymentMessagesExtension",
+                &balloon
+            ),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn can_summarize_game_pigeon() {
+        let payload = read_payload("Game");
+        let balloon = AppMessage::from_map(&payload).unwrap();
+
+        let expected = AppSummary {
+            app: "GamePigeon".to_string(),
+            description: "Game of Dots & Boxes".to_string(),
+            details: vec!["Your move.".to_string()],
+        };
+        assert_eq!(
+            summarize_app(
+                "com.gamerdelights.gamepigeon.ext.MessagesExtension",
+                &balloon
+            ),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn can_summarize_opentable() {
+        let payload = read_payload("OpenTableInvited");
+        let balloon = AppMessage::from_map(&payload).unwrap();
+
+        let expected = AppSummary {
+            app: "OpenTable".to_string(),
+            description: "Reservation at Rusty Grill - Boise".to_string(),
+            details: vec![
+                "Reservation Confirmed".to_string(),
+                "Table for 4 people".to_string(),
+                "Sunday, October 17 at 7:45 PM".to_string(),
+                "You're invited! Tap to accept.".to_string(),
+            ],
+        };
+        assert_eq!(
+            summarize_app("com.contextoptional.OpenTable.Messages", &balloon),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn can_summarize_shazam() {
+        let mut balloon = blank();
+        balloon.title = Some("Song");
+        balloon.subtitle = Some("Artist");
+
+        let summary = summarize_app("com.shazam.Shazam.ShazamMessages", &balloon).unwrap();
+        assert_eq!(summary.description, "Song by Artist");
+    }
+
+    #[test]
+    fn can_summarize_poll() {
+        let mut balloon = blank();
+        balloon.caption = Some("Pizza or tacos?");
+        balloon.subcaption = Some("3 votes");
+
+        let summary = summarize_app("com.apple.messages.Polls", &balloon).unwrap();
+        assert_eq!(summary.description, "Poll: Pizza or tacos?");
+        assert_eq!(summary.details, vec!["3 votes"]);
+    }
+
+    #[test]
+    fn cant_summarize_unknown_app() {
+        let mut balloon = blank();
+        balloon.caption = Some("Caption");
+
+        assert_eq!(summarize_app("com.example.app", &balloon), None);
+    }
+
+    #[test]
+    fn cant_summarize_missing_data() {
+        assert_eq!(
+            summarize_app("com.gamerdelights.gamepigeon.ext", &blank()),
+            None
+        );
+    }
+
+    #[test]
+    fn can_register_decoder() {
+        struct Custom;
+
+        impl AppDecoder for Custom {
+            fn bundle_id(&self) -> &str {
+                "com.example"
+            }
+
+            fn summarize(&self, balloon: &AppMessage) -> Option<AppSummary> {
+                Some(AppSummary {
+                    app: "Example".to_string(),
+                    description: balloon.caption?.to_uppercase(),
+                    details: vec![],
+                })
+            }
+        }
+
+        let mut balloon = blank();
+        balloon.caption = Some("hello");
+
+        let mut decoders = AppDecoders::default();
+        assert_eq!(decoders.summarize("com.example.app", &balloon), None);
+
+        decoders.register(&Custom);
+        assert_eq!(
+            decoders
+                .summarize("com.example.app", &balloon)
+                .map(|summary| summary.description),
+            Some("HELLO".to_string())
+        );
+    }
+}
@@ -0,0 +1,167 @@
+/*!
+ Apple Pay and Apple Cash transactions sent in a conversation.
+
+ These are [`AppMessage`]s sent by `com.apple.PassbookUIService.PeerPaymentMessagesExtension`. The message text
+ is only an attachment placeholder, so the transaction is read from the sentence Apple stores in the balloon,
+ i.e. `Sent $265 with Apple Pay.`
+*/
+
+use std::fmt::{Display, Formatter, Result};
+
+use crate::message_types::app::AppMessage;
+
+/// Which way money moved in an Apple Pay transaction
+#[derive(Debug, PartialEq, Eq)]
+pub enum PaymentDirection {
+    /// Money was sent to the other person
+    Sent,
+    /// Money was requested from the other person
+    Requested,
+    /// Money was received from the other person
+    Received,
+}
+
+/// An Apple Pay transaction
+#[derive(Debug, PartialEq, Eq)]
+pub struct ApplePayMessage<'a> {
+    /// Which way the money moved, if the transaction is a single payment or request
+    pub direction: Option<PaymentDirection>,
+    /// The amount of money, including its currency symbol, i.e. `$25`
+    pub amount: Option<&'a str>,
+    /// The sentence Apple uses to describe the transaction
+    pub description: &'a str,
+}
+
+impl<'a> ApplePayMessage<'a> {
+    /// Read a transaction from an Apple Pay balloon, if it describes one
+    pub fn from_balloon(balloon: &AppMessage<'a>) -> Option<Self> {
+        let description = balloon.ldtext.or(balloon.subcaption)?.trim();
+        if description.is_empty() {
+            return None;
+        }
+
+        // Apple separates the words in amounts with non-breaking spaces, which are whitespace too
+        let mut words = description.split_whitespace();
+        let direction = match words.next() {
+            Some("Sent") => Some(PaymentDirection::Sent),
+            Some("Requested") => Some(PaymentDirection::Requested),
+            Some("Received") => Some(PaymentDirection::Received),
+            _ => None,
+        };
+        let amount = direction
+            .as_ref()
+            .and_then(|_| words.next())
+            .filter(|word| word.chars().any(|c| c.is_ascii_digit()));
+
+        Some(Self {
+            direction,
+            amount,
+            description,
+        })
+    }
+}
+
+impl Display for ApplePayMessage<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match (&self.direction, self.amount) {
+            (Some(PaymentDirection::Sent), Some(amount)) => write!(fmt, "Sent {amount}"),
+            (Some(PaymentDirection::Requested), Some(amount)) => write!(fmt, "Requested {amount}"),
+            (Some(PaymentDirection::Received), Some(amount)) => write!(fmt, "Received {amount}"),
+            // Recurring payments and other transactions are described as Apple wrote them
+            _ => write!(fmt, "{}", self.description.trim_end_matches('.')),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::File};
+
+    use plist::Value;
+
+    use crate::{
+        message_types::{
+            app::AppMessage,
+            apple_pay::{ApplePayMessage, PaymentDirection},
+            variants::BalloonProvider,
+        },
+        util::plist::parse_plist,
+    };
+
+    fn read_balloon(name: &str) -> Value {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join(format!("test_data/app_message/{name}.plist"));
+        let plist = Value::from_reader(File::open(plist_path).unwrap()).unwrap();
+        parse_plist(&plist).unwrap()
+    }
+
+    fn blank() -> AppMessage<'static> {
+        AppMessage {
+            image: None,
+            url: None,
+            title: None,
+            subtitle: None,
+            caption: None,
+            subcaption: None,
+            trailing_caption: None,
+            trailing_subcaption: None,
+            app_name: None,
+            ldtext: None,
+        }
+    }
+
+    #[test]
+    fn can_parse_sent() {
+        let payload = read_balloon("Sent265");
+        let balloon = AppMessage::from_map(&payload).unwrap();
+        let payment = ApplePayMessage::from_balloon(&balloon).unwrap();
+
+        assert_eq!(payment.direction, Some(PaymentDirection::Sent));
+        assert_eq!(payment.amount, Some("$265"));
+        assert_eq!(payment.to_string(), "Sent $265");
+    }
+
+    #[test]
+    fn can_parse_recurring() {
+        let payload = read_balloon("ApplePayRecurring");
+        let balloon = AppMessage::from_map(&payload).unwrap();
+        let payment = ApplePayMessage::from_balloon(&balloon).unwrap();
+
+        assert_eq!(payment.direction, None);
+        assert_eq!(payment.amount, None);
+        assert_eq!(
+            payment.to_string(),
+            "Sending you $1 weekly starting Nov 18, 2023"
+        );
+    }
+
+    #[test]
+    fn can_parse_requested() {
+        let mut balloon = blank();
+        balloon.ldtext = Some("Requested\u{a0}€10 with Apple\u{a0}Pay.");
+        let payment = ApplePayMessage::from_balloon(&balloon).unwrap();
+
+        assert_eq!(payment.direction, Some(PaymentDirection::Requested));
+        assert_eq!(payment.to_string(), "Requested €10");
+    }
+
+    #[test]
+    fn can_parse_received_from_subcaption() {
+        let mut balloon = blank();
+        balloon.subcaption = Some("Received $5.50");
+        let payment = ApplePayMessage::from_balloon(&balloon).unwrap();
+
+        assert_eq!(payment.to_string(), "Received $5.50");
+    }
+
+    #[test]
+    fn cant_parse_empty() {
+        assert_eq!(ApplePayMessage::from_balloon(&blank()), None);
+
+        let mut balloon = blank();
+        balloon.ldtext = Some(" ");
+        assert_eq!(ApplePayMessage::from_balloon(&balloon), None);
+    }
+}
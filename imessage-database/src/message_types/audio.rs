@@ -0,0 +1,325 @@
+/*!
+ These are audio attachments, like [audio messages](https://support.apple.com/guide/iphone/send-and-receive-audio-messages-iph2e42d3117/ios), which are sent as `CAF` files, and other `M4A` or `MP4` audio files.
+*/
+
+/// The number of bars in a waveform
+pub const WAVEFORM_BARS: usize = 40;
+
+/// Represents the length, and where feasible the shape, of an audio attachment
+#[derive(Debug, PartialEq, Default)]
+pub struct AudioMetadata {
+    /// The length of the audio in seconds
+    pub duration: f64,
+    /// The relative loudness of each part of the audio, from `0.0` to `1.0`
+    ///
+    /// Audio messages are compressed, so this is estimated from the size of each compressed packet,
+    /// which grows with the amount of sound in it. It is empty if the file does not list packet sizes.
+    pub waveform: Vec<f64>,
+}
+
+impl AudioMetadata {
+    /// Format the duration as `m:ss`, or `h:mm:ss` for audio that is an hour or longer
+    pub fn format_duration(&self) -> String {
+        let total = self.duration.round() as u64;
+        let (hours, minutes, seconds) = (total / 3600, (total % 3600) / 60, total % 60);
+        match hours {
+            0 => format!("{minutes}:{seconds:02}"),
+            _ => format!("{hours}:{minutes:02}:{seconds:02}"),
+        }
+    }
+}
+
+/// Parse the duration and waveform of `CAF`, `M4A`, or `MP4` audio data
+pub fn parse_audio_metadata(data: &[u8]) -> Option<AudioMetadata> {
+    match data.get(0..4)? {
+        b"caff" => parse_caf(data),
+        _ if data.get(4..8)? == b"ftyp" => parse_mp4(data),
+        _ => None,
+    }
+}
+
+/// Read a big endian `u32` at `offset`
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Read a big endian `u64` at `offset`
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Parse a [Core Audio Format](https://developer.apple.com/library/archive/documentation/MusicAudio/Reference/CAFSpec/CAF_spec/CAF_spec.html) file
+fn parse_caf(data: &[u8]) -> Option<AudioMetadata> {
+    let mut sample_rate = None;
+    let mut bytes_per_packet = 0;
+    let mut frames_per_packet = 0;
+    let mut audio_bytes = None;
+    let mut packet_table = None;
+
+    // The file header is followed by chunks, each with a type and a 64-bit size
+    let mut idx = 8;
+    while idx + 12 <= data.len() {
+        let kind = &data[idx..idx + 4];
+        let size = read_u64(data, idx + 4)?;
+        let start = idx + 12;
+        // The audio data chunk may have a size of -1 if it runs to the end of the file
+        let end = match size {
+            u64::MAX => data.len(),
+            size => start
+                .checked_add(usize::try_from(size).ok()?)?
+                .min(data.len()),
+        };
+        let chunk = &data[start..end];
+
+        match kind {
+            b"desc" => {
+                sample_rate = Some(f64::from_be_bytes(chunk.get(0..8)?.try_into().ok()?));
+                bytes_per_packet = read_u32(chunk, 16)?;
+                frames_per_packet = read_u32(chunk, 20)?;
+            }
+            // The data starts with a 4 byte edit count
+            b"data" => audio_bytes = Some(chunk.len().saturating_sub(4) as u64),
+            b"pakt" => packet_table = Some(chunk),
+            _ => {}
+        }
+        idx = end;
+    }
+
+    let sample_rate = sample_rate.filter(|rate| *rate > 0.0)?;
+    let mut metadata = AudioMetadata::default();
+
+    if let Some(table) = packet_table {
+        let packets = read_u64(table, 0)?;
+        let valid_frames = read_u64(table, 8)?;
+        metadata.duration = valid_frames as f64 / sample_rate;
+
+        // Variable sized packets list their sizes, which make a rough waveform
+        if bytes_per_packet == 0 {
+            let sizes = read_packet_sizes(table.get(24..)?, packets, frames_per_packet == 0);
+            metadata.waveform = waveform(&sizes);
+        }
+    }
+
+    // Packets of a constant size do not need a table
+    if metadata.duration == 0.0 && bytes_per_packet > 0 {
+        let packets = audio_bytes? / u64::from(bytes_per_packet);
+        metadata.duration = (packets * u64::from(frames_per_packet)) as f64 / sample_rate;
+    }
+
+    Some(metadata)
+}
+
+/// Read the packet sizes from a `CAF` packet table, which stores each as a variable length integer
+///
+/// If the packets do not have a constant number of frames, each size is followed by a frame count.
+fn read_packet_sizes(table: &[u8], packets: u64, has_frame_counts: bool) -> Vec<u64> {
+    let mut values = table.iter();
+    let mut read_integer = || -> Option<u64> {
+        let mut value: u64 = 0;
+        loop {
+            let byte = values.next()?;
+            value = (value << 7) | u64::from(byte & 0x7F);
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    };
+
+    let mut sizes = vec![];
+    while (sizes.len() as u64) < packets {
+        let Some(size) = read_integer() else {
+            break;
+        };
+        if has_frame_counts && read_integer().is_none() {
+            break;
+        }
+        sizes.push(size);
+    }
+    sizes
+}
+
+/// Build a waveform from packet sizes, averaging them into bars scaled to the largest one
+fn waveform(sizes: &[u64]) -> Vec<f64> {
+    if sizes.is_empty() {
+        return vec![];
+    }
+    let count = WAVEFORM_BARS.min(sizes.len());
+    let bars: Vec<f64> = (0..count)
+        .map(|bar| {
+            let start = bar * sizes.len() / count;
+            let end = (bar + 1) * sizes.len() / count;
+            let bucket = &sizes[start..end];
+            bucket.iter().sum::<u64>() as f64 / bucket.len() as f64
+        })
+        .collect();
+    let loudest = bars.iter().cloned().fold(0.0, f64::max);
+    match loudest > 0.0 {
+        true => bars.iter().map(|bar| bar / loudest).collect(),
+        false => vec![],
+    }
+}
+
+/// Parse an `MP4` file, i.e. `M4A` audio, by reading the duration from its movie header
+fn parse_mp4(data: &[u8]) -> Option<AudioMetadata> {
+    let moov = find_box(data, b"moov")?;
+    let mvhd = find_box(moov, b"mvhd")?;
+    let (timescale, duration) = match mvhd.first()? {
+        // Version 1 headers use 64-bit times
+        1 => (read_u32(mvhd, 20)?, read_u64(mvhd, 24)?),
+        _ => (read_u32(mvhd, 12)?, u64::from(read_u32(mvhd, 16)?)),
+    };
+    if timescale == 0 {
+        return None;
+    }
+    Some(AudioMetadata {
+        duration: duration as f64 / f64::from(timescale),
+        waveform: vec![],
+    })
+}
+
+/// Find the contents of the first `MP4` box of a type
+fn find_box<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut idx = 0;
+    while idx + 8 <= data.len() {
+        let (header, size) = match read_u32(data, idx)? {
+            // The size is stored as a 64-bit integer after the type
+            1 => (16, usize::try_from(read_u64(data, idx + 8)?).ok()?),
+            // The box runs to the end of the data
+            0 => (8, data.len() - idx),
+            size => (8, size as usize),
+        };
+        if size < header {
+            return None;
+        }
+        let end = idx.checked_add(size)?.min(data.len());
+        if data.get(idx + 4..idx + 8)? == kind {
+            return data.get(idx + header..end);
+        }
+        idx = end;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message_types::audio::{
+        parse_audio_metadata, read_packet_sizes, waveform, AudioMetadata, WAVEFORM_BARS,
+    };
+
+    /// Build a `CAF` chunk
+    fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = kind.to_vec();
+        chunk.extend_from_slice(&(data.len() as u64).to_be_bytes());
+        chunk.extend_from_slice(data);
+        chunk
+    }
+
+    /// Build a `CAF` audio description
+    fn description(sample_rate: f64, bytes_per_packet: u32, frames_per_packet: u32) -> Vec<u8> {
+        let mut desc = sample_rate.to_be_bytes().to_vec();
+        desc.extend_from_slice(b"opus");
+        desc.extend_from_slice(&0u32.to_be_bytes());
+        desc.extend_from_slice(&bytes_per_packet.to_be_bytes());
+        desc.extend_from_slice(&frames_per_packet.to_be_bytes());
+        desc.extend_from_slice(&1u32.to_be_bytes());
+        desc.extend_from_slice(&0u32.to_be_bytes());
+        desc
+    }
+
+    /// Build an `MP4` box
+    fn mp4_box(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut mp4_box = ((data.len() + 8) as u32).to_be_bytes().to_vec();
+        mp4_box.extend_from_slice(kind);
+        mp4_box.extend_from_slice(data);
+        mp4_box
+    }
+
+    #[test]
+    fn can_parse_caf_with_packet_table() {
+        // Four packets of 960 frames at 48 kHz, with sizes 10, 20, 200, and 40
+        let mut table = 4u64.to_be_bytes().to_vec();
+        table.extend_from_slice(&3840u64.to_be_bytes());
+        table.extend_from_slice(&0u32.to_be_bytes());
+        table.extend_from_slice(&0u32.to_be_bytes());
+        table.extend_from_slice(&[10, 20, 0x81, 0x48, 40]);
+
+        let mut data = b"caff\0\x01\0\0".to_vec();
+        data.extend_from_slice(&chunk(b"desc", &description(48000.0, 0, 960)));
+        data.extend_from_slice(&chunk(b"pakt", &table));
+        data.extend_from_slice(&chunk(b"data", &[0; 16]));
+
+        assert_eq!(
+            parse_audio_metadata(&data),
+            Some(AudioMetadata {
+                duration: 0.08,
+                waveform: vec![0.05, 0.1, 1.0, 0.2],
+            })
+        );
+    }
+
+    #[test]
+    fn can_parse_caf_with_constant_packets() {
+        // 16,000 bytes of 2 byte packets with 1 frame each at 8 kHz
+        let mut data = b"caff\0\x01\0\0".to_vec();
+        data.extend_from_slice(&chunk(b"desc", &description(8000.0, 2, 1)));
+        data.extend_from_slice(&chunk(b"data", &[0; 16004]));
+
+        let metadata = parse_audio_metadata(&data).unwrap();
+        assert_eq!(metadata.duration, 1.0);
+        assert!(metadata.waveform.is_empty());
+    }
+
+    #[test]
+    fn can_parse_m4a() {
+        // 90 seconds at a timescale of 44.1 kHz
+        let mut mvhd = vec![0; 4];
+        mvhd.extend_from_slice(&0u32.to_be_bytes());
+        mvhd.extend_from_slice(&0u32.to_be_bytes());
+        mvhd.extend_from_slice(&44100u32.to_be_bytes());
+        mvhd.extend_from_slice(&(44100u32 * 90).to_be_bytes());
+
+        let mut data = mp4_box(b"ftyp", b"M4A \0\0\0\0");
+        data.extend_from_slice(&mp4_box(b"moov", &mp4_box(b"mvhd", &mvhd)));
+
+        let metadata = parse_audio_metadata(&data).unwrap();
+        assert_eq!(metadata.format_duration(), "1:30");
+    }
+
+    #[test]
+    fn cant_parse_other_data() {
+        assert_eq!(parse_audio_metadata(b"RIFF\0\0\0\0WAVE"), None);
+    }
+
+    #[test]
+    fn can_read_frame_counts() {
+        assert_eq!(read_packet_sizes(&[5, 1, 6, 1, 7], 3, true), vec![5, 6]);
+    }
+
+    #[test]
+    fn can_build_waveform_buckets() {
+        let sizes: Vec<u64> = (1..=80).collect();
+        let waveform = waveform(&sizes);
+
+        assert_eq!(waveform.len(), WAVEFORM_BARS);
+        assert_eq!(waveform.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn can_format_duration() {
+        let short = AudioMetadata {
+            duration: 7.4,
+            waveform: vec![],
+        };
+        assert_eq!(short.format_duration(), "0:07");
+
+        let long = AudioMetadata {
+            duration: 3725.0,
+            waveform: vec![],
+        };
+        assert_eq!(long.format_duration(), "1:02:05");
+    }
+}
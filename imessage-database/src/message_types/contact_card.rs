@@ -0,0 +1,219 @@
+/*!
+ These are contact cards shared in a conversation, which are sent as [vCard](https://datatracker.ietf.org/doc/html/rfc6350) (`.vcf`) attachments.
+*/
+
+/// Represents a single contact parsed from a vCard file
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct ContactCard {
+    /// The display name of the contact
+    pub name: Option<String>,
+    /// The company the contact works for
+    pub organization: Option<String>,
+    /// The contact's phone numbers
+    pub phone_numbers: Vec<String>,
+    /// The contact's email addresses
+    pub emails: Vec<String>,
+}
+
+impl ContactCard {
+    /// Get the best available name for the contact, falling back to its first phone number or email
+    pub fn display_name(&self) -> Option<&str> {
+        self.name
+            .as_deref()
+            .or(self.organization.as_deref())
+            .or(self.phone_numbers.first().map(String::as_str))
+            .or(self.emails.first().map(String::as_str))
+    }
+
+    /// Get the organization, phone numbers, and emails of the contact, leaving out the one used as its name
+    pub fn details(&self) -> Vec<&str> {
+        let name = self.display_name();
+        self.organization
+            .iter()
+            .chain(&self.phone_numbers)
+            .chain(&self.emails)
+            .map(String::as_str)
+            .filter(|detail| Some(*detail) != name)
+            .collect()
+    }
+
+    /// Determine if the card has no data to show
+    pub fn is_empty(&self) -> bool {
+        self.display_name().is_none()
+    }
+}
+
+/// Parse the contacts from the contents of a vCard file
+///
+/// Only the name, organization, phone numbers, and email addresses are read; photos and other
+/// properties are ignored. Contacts without any of these are left out.
+pub fn parse_contact_cards(data: &str) -> Vec<ContactCard> {
+    let mut cards = vec![];
+    let mut current: Option<ContactCard> = None;
+    // Structured name, used when there is no formatted name
+    let mut structured_name: Option<String> = None;
+
+    for line in unfold(data) {
+        let Some((property, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Drop the parameters, i.e. `;type=CELL`, and any group, i.e. `item1.`
+        let name = property.split(';').next().unwrap_or_default();
+        let name = name.rsplit('.').next().unwrap_or_default().to_uppercase();
+
+        if name == "BEGIN" && value.eq_ignore_ascii_case("VCARD") {
+            current = Some(ContactCard::default());
+            structured_name = None;
+            continue;
+        }
+        if name == "END" && value.eq_ignore_ascii_case("VCARD") {
+            if let Some(mut card) = current.take() {
+                if card.name.is_none() {
+                    card.name = structured_name.take();
+                }
+                if !card.is_empty() {
+                    cards.push(card);
+                }
+            }
+            continue;
+        }
+
+        // Properties outside of a card are ignored
+        let Some(card) = current.as_mut() else {
+            continue;
+        };
+        match name.as_str() {
+            "FN" => card.name = non_empty(unescape(value)),
+            "N" => structured_name = format_structured_name(value),
+            "ORG" => card.organization = value.split(';').next().map(unescape).and_then(non_empty),
+            "TEL" => {
+                // vCard 4.0 stores phone numbers as `tel:` URIs
+                let number = value.strip_prefix("tel:").unwrap_or(value);
+                card.phone_numbers.extend(non_empty(unescape(number)));
+            }
+            "EMAIL" => card.emails.extend(non_empty(unescape(value))),
+            _ => {}
+        }
+    }
+
+    cards
+}
+
+/// Join lines that were folded onto the next line, which start with a space or tab
+fn unfold(data: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in data.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(previous)) => previous.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Build a name from the `N` property, which is `Family;Given;Additional;Prefix;Suffix`
+fn format_structured_name(value: &str) -> Option<String> {
+    let parts: Vec<String> = value.split(';').map(unescape).collect();
+    let order = [3, 1, 2, 0, 4];
+    let name = order
+        .iter()
+        .filter_map(|&idx| parts.get(idx))
+        .filter(|part| !part.is_empty())
+        .cloned()
+        .collect::<Vec<String>>()
+        .join(" ");
+    non_empty(name)
+}
+
+/// Replace the escape sequences used in vCard values
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            _ => out.push(c),
+        }
+    }
+    out.trim().to_string()
+}
+
+/// Discard empty values
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message_types::contact_card::{parse_contact_cards, ContactCard};
+
+    #[test]
+    fn can_parse_contact_card() {
+        let data = "BEGIN:VCARD\r\nVERSION:3.0\r\nN:Doe;Jane;;;\r\nFN:Jane Doe\r\nORG:Example\\, Inc.;\r\nitem1.TEL;type=CELL;type=pref:+1 (555) 867-5309\r\nEMAIL;type=INTERNET:jane@example.com\r\nPHOTO;ENCODING=b:AAAA\r\n BBBB\r\nEND:VCARD\r\n";
+        let expected = vec![ContactCard {
+            name: Some("Jane Doe".to_string()),
+            organization: Some("Example, Inc.".to_string()),
+            phone_numbers: vec!["+1 (555) 867-5309".to_string()],
+            emails: vec!["jane@example.com".to_string()],
+        }];
+
+        assert_eq!(parse_contact_cards(data), expected);
+    }
+
+    #[test]
+    fn can_parse_structured_name() {
+        let data = "BEGIN:VCARD\nVERSION:4.0\nN:Doe;Jane;Q.;Dr.;\nTEL;VALUE=uri:tel:+15558675309\nEND:VCARD\n";
+        let expected = vec![ContactCard {
+            name: Some("Dr. Jane Q. Doe".to_string()),
+            organization: None,
+            phone_numbers: vec!["+15558675309".to_string()],
+            emails: vec![],
+        }];
+
+        assert_eq!(parse_contact_cards(data), expected);
+    }
+
+    #[test]
+    fn can_parse_folded_lines() {
+        let data = "BEGIN:VCARD\nFN:Jane\n  Doe\nEND:VCARD\n";
+
+        assert_eq!(
+            parse_contact_cards(data)[0].name.as_deref(),
+            Some("Jane Doe")
+        );
+    }
+
+    #[test]
+    fn can_parse_multiple_cards() {
+        let data =
+            "BEGIN:VCARD\nFN:Jane Doe\nEND:VCARD\nBEGIN:VCARD\nEMAIL:john@example.com\nEND:VCARD\n";
+        let cards = parse_contact_cards(data);
+
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[1].display_name(), Some("john@example.com"));
+    }
+
+    #[test]
+    fn can_get_details_without_name() {
+        let card = ContactCard {
+            name: None,
+            organization: None,
+            phone_numbers: vec!["+15558675309".to_string(), "+15555550123".to_string()],
+            emails: vec!["jane@example.com".to_string()],
+        };
+
+        assert_eq!(card.display_name(), Some("+15558675309"));
+        assert_eq!(card.details(), vec!["+15555550123", "jane@example.com"]);
+    }
+
+    #[test]
+    fn cant_parse_empty_card() {
+        let data = "BEGIN:VCARD\nVERSION:3.0\nEND:VCARD\n";
+
+        assert!(parse_contact_cards(data).is_empty());
+    }
+}
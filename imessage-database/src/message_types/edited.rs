@@ -4,16 +4,18 @@
  The main data type used to represent these types of messages is [`EditedMessage`].
 */
 
+use log::debug;
+
 use plist::Value;
 
 use crate::{
     error::plist::PlistParseError,
     message_types::variants::BalloonProvider,
     util::{
+        attributed_body::AttributedBody,
         dates::TIMESTAMP_FACTOR,
         plist::{extract_array_key, extract_bytes_key, extract_dictionary, extract_int_key},
-        streamtyped::parse,
-        typedstream::parser::TypedStreamReader,
+        streamtyped,
     },
 };
 
@@ -130,20 +132,15 @@ impl<'a> BalloonProvider<'a> for EditedMessage {
 
                     let timestamp = extract_int_key(message_data, "d")? * TIMESTAMP_FACTOR;
 
-                    let typedstream = extract_bytes_key(message_data, "t")?;
-
-                    let mut parser = TypedStreamReader::from(typedstream);
-                    let text = match parser
-                        .parse()
-                        .ok()
-                        .as_ref()
-                        .and_then(|items| items.first())
-                        .and_then(|item| item.as_nsstring())
-                        .map(String::from)
-                    {
-                        Some(text) => text,
-                        None => parse(typedstream.to_vec())
-                            .map_err(PlistParseError::StreamTypedError)?,
+                    let body = extract_bytes_key(message_data, "t")?;
+
+                    let text = match AttributedBody::parse(body) {
+                        Ok(parsed) => parsed.text,
+                        Err(why) => {
+                            debug!("Falling back to the legacy parser for an edit: {why}");
+                            streamtyped::parse(body.to_vec())
+                                .map_err(PlistParseError::StreamTypedError)?
+                        }
                     };
 
                     let guid = message_data
@@ -184,6 +181,16 @@ impl EditedMessage {
         }
     }
 
+    /// A message whose only part was unsent
+    pub(crate) fn unsent() -> Self {
+        EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Unsent,
+                edit_history: vec![],
+            }],
+        }
+    }
+
     /// Gets the edited message data for the given message part index
     pub fn part(&self, index: usize) -> Option<&EditedMessagePart> {
         self.parts.get(index)
@@ -0,0 +1,117 @@
+/*!
+ These are the link previews that iMessage generates when sending a link to join a FaceTime call.
+
+ The preview usually holds no metadata beyond the link itself, so it would otherwise display as an empty bubble.
+*/
+
+use plist::Value;
+
+use crate::{
+    error::plist::PlistParseError,
+    message_types::{url::URLMessage, variants::BalloonProvider},
+};
+
+/// The start of every link that joins a FaceTime call
+const FACETIME_LINK_PREFIX: &str = "https://facetime.apple.com/join";
+
+/// This struct is not documented by Apple, but represents messages displayed as
+/// `com.apple.messages.URLBalloonProvider` but for FaceTime call links
+#[derive(Debug, PartialEq, Eq)]
+pub struct FaceTimeMessage<'a> {
+    /// The link used to join the call
+    pub url: &'a str,
+    /// The name given to the call, if it has one
+    pub title: Option<&'a str>,
+}
+
+impl<'a> BalloonProvider<'a> for FaceTimeMessage<'a> {
+    fn from_map(payload: &'a Value) -> Result<Self, PlistParseError> {
+        let balloon = URLMessage::from_map(payload)?;
+        let url = balloon
+            .get_url()
+            .filter(|url| is_facetime_link(url))
+            .ok_or(PlistParseError::WrongMessageType)?;
+
+        Ok(Self {
+            url,
+            // Links without a name use the app's name as their title
+            title: balloon.title.filter(|title| *title != "FaceTime"),
+        })
+    }
+}
+
+/// Determine if a URL is a link to join a FaceTime call
+pub fn is_facetime_link(url: &str) -> bool {
+    url.starts_with(FACETIME_LINK_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use crate::message_types::{
+        facetime::{is_facetime_link, FaceTimeMessage},
+        variants::BalloonProvider,
+    };
+
+    fn payload(url: &str, title: Option<&str>) -> Value {
+        let mut nested_url = Dictionary::new();
+        nested_url.insert("URL".to_string(), Value::String(url.to_string()));
+
+        let mut metadata = Dictionary::new();
+        metadata.insert("URL".to_string(), Value::Dictionary(nested_url));
+        if let Some(title) = title {
+            metadata.insert("title".to_string(), Value::String(title.to_string()));
+        }
+
+        let mut root = Dictionary::new();
+        root.insert("richLinkMetadata".to_string(), Value::Dictionary(metadata));
+        Value::Dictionary(root)
+    }
+
+    #[test]
+    fn can_parse_facetime_link() {
+        let link = payload(
+            "https://facetime.apple.com/join#v=1&p=AbCdEf&k=GhIjKl",
+            None,
+        );
+
+        let expected = FaceTimeMessage {
+            url: "https://facetime.apple.com/join#v=1&p=AbCdEf&k=GhIjKl",
+            title: None,
+        };
+        assert_eq!(FaceTimeMessage::from_map(&link).unwrap(), expected);
+    }
+
+    #[test]
+    fn can_parse_named_facetime_link() {
+        let named = payload(
+            "https://facetime.apple.com/join#v=1&p=AbCdEf&k=GhIjKl",
+            Some("Book Club"),
+        );
+        assert_eq!(
+            FaceTimeMessage::from_map(&named).unwrap().title,
+            Some("Book Club")
+        );
+
+        let unnamed = payload(
+            "https://facetime.apple.com/join#v=1&p=AbCdEf&k=GhIjKl",
+            Some("FaceTime"),
+        );
+        assert_eq!(FaceTimeMessage::from_map(&unnamed).unwrap().title, None);
+    }
+
+    #[test]
+    fn cant_parse_other_link() {
+        let link = payload("https://chrissardegna.com", Some("FaceTime"));
+        assert!(FaceTimeMessage::from_map(&link).is_err());
+    }
+
+    #[test]
+    fn can_detect_facetime_link() {
+        assert!(is_facetime_link(
+            "https://facetime.apple.com/join#v=1&p=AbCdEf"
+        ));
+        assert!(!is_facetime_link("https://apple.com/facetime"));
+    }
+}
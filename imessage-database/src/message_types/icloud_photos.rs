@@ -0,0 +1,158 @@
+/*!
+ These are the link previews that iMessage generates when sharing photos through iCloud, either as an
+ [iCloud link](https://support.apple.com/guide/photos/share-photos-using-icloud-links-phta7c62e8b8/mac) or as an
+ invitation to a [Shared Album](https://support.apple.com/guide/iphone/shared-albums-iph37b4d5f1f/ios).
+
+ The previews usually hold little metadata beyond the link itself, so they would otherwise display as empty bubbles.
+*/
+
+use plist::Value;
+
+use crate::{
+    error::plist::PlistParseError,
+    message_types::{url::URLMessage, variants::BalloonProvider},
+};
+
+/// The start of every link to a Shared Album
+const SHARED_ALBUM_PREFIX: &str = "https://www.icloud.com/sharedalbum/";
+/// The starts of links to photos shared with an iCloud link
+const PHOTO_LINK_PREFIXES: [&str; 2] = [
+    "https://share.icloud.com/photos/",
+    "https://www.icloud.com/photos/",
+];
+
+/// The kinds of iCloud photo links
+#[derive(Debug, PartialEq, Eq)]
+pub enum ICloudPhotosKind {
+    /// An invitation to subscribe to a Shared Album
+    SharedAlbum,
+    /// A link to a collection of photos stored in iCloud
+    PhotoLink,
+}
+
+/// This struct is not documented by Apple, but represents messages displayed as
+/// `com.apple.messages.URLBalloonProvider` but for photos shared through iCloud
+#[derive(Debug, PartialEq, Eq)]
+pub struct ICloudPhotosMessage<'a> {
+    /// The kind of iCloud photo link
+    pub kind: ICloudPhotosKind,
+    /// The link used to view the photos
+    pub url: &'a str,
+    /// The name of the album, if it has one
+    pub title: Option<&'a str>,
+    /// A description of the shared items, i.e. `12 Photos`
+    pub summary: Option<&'a str>,
+}
+
+impl<'a> BalloonProvider<'a> for ICloudPhotosMessage<'a> {
+    fn from_map(payload: &'a Value) -> Result<Self, PlistParseError> {
+        let balloon = URLMessage::from_map(payload)?;
+        let url = balloon.get_url().ok_or(PlistParseError::WrongMessageType)?;
+        let kind = get_icloud_photos_kind(url).ok_or(PlistParseError::WrongMessageType)?;
+
+        Ok(Self {
+            kind,
+            url,
+            // Links without a name use the service's name as their title
+            title: balloon
+                .title
+                .filter(|title| !matches!(*title, "iCloud" | "iCloud Photos")),
+            summary: balloon.summary,
+        })
+    }
+}
+
+/// Determine the kind of iCloud photo link a URL points to, if it points to one
+pub fn get_icloud_photos_kind(url: &str) -> Option<ICloudPhotosKind> {
+    if url.starts_with(SHARED_ALBUM_PREFIX) {
+        return Some(ICloudPhotosKind::SharedAlbum);
+    }
+    if PHOTO_LINK_PREFIXES
+        .iter()
+        .any(|prefix| url.starts_with(prefix))
+    {
+        return Some(ICloudPhotosKind::PhotoLink);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use crate::message_types::{
+        icloud_photos::{get_icloud_photos_kind, ICloudPhotosKind, ICloudPhotosMessage},
+        variants::BalloonProvider,
+    };
+
+    fn payload(url: &str, title: Option<&str>, summary: Option<&str>) -> Value {
+        let mut nested_url = Dictionary::new();
+        nested_url.insert("URL".to_string(), Value::String(url.to_string()));
+
+        let mut metadata = Dictionary::new();
+        metadata.insert("URL".to_string(), Value::Dictionary(nested_url));
+        if let Some(title) = title {
+            metadata.insert("title".to_string(), Value::String(title.to_string()));
+        }
+        if let Some(summary) = summary {
+            metadata.insert("summary".to_string(), Value::String(summary.to_string()));
+        }
+
+        let mut root = Dictionary::new();
+        root.insert("richLinkMetadata".to_string(), Value::Dictionary(metadata));
+        Value::Dictionary(root)
+    }
+
+    #[test]
+    fn can_parse_shared_album() {
+        let album = payload(
+            "https://www.icloud.com/sharedalbum/#B0aGWZuqDGKvJx",
+            Some("Ski Trip"),
+            None,
+        );
+
+        let expected = ICloudPhotosMessage {
+            kind: ICloudPhotosKind::SharedAlbum,
+            url: "https://www.icloud.com/sharedalbum/#B0aGWZuqDGKvJx",
+            title: Some("Ski Trip"),
+            summary: None,
+        };
+        assert_eq!(ICloudPhotosMessage::from_map(&album).unwrap(), expected);
+    }
+
+    #[test]
+    fn can_parse_photo_link() {
+        let link = payload(
+            "https://share.icloud.com/photos/0a1B2c3D4e5F6g7H8i9J",
+            Some("iCloud Photos"),
+            Some("12 Photos"),
+        );
+
+        let expected = ICloudPhotosMessage {
+            kind: ICloudPhotosKind::PhotoLink,
+            url: "https://share.icloud.com/photos/0a1B2c3D4e5F6g7H8i9J",
+            title: None,
+            summary: Some("12 Photos"),
+        };
+        assert_eq!(ICloudPhotosMessage::from_map(&link).unwrap(), expected);
+    }
+
+    #[test]
+    fn cant_parse_other_link() {
+        let link = payload("https://www.icloud.com/freeform/REDACTED", None, None);
+        assert!(ICloudPhotosMessage::from_map(&link).is_err());
+    }
+
+    #[test]
+    fn can_get_icloud_photos_kind() {
+        assert_eq!(
+            get_icloud_photos_kind("https://www.icloud.com/photos/#0a1B2c3D4e5F6g7H8i9J"),
+            Some(ICloudPhotosKind::PhotoLink)
+        );
+        assert_eq!(
+            get_icloud_photos_kind("https://www.icloud.com/sharedalbum/#B0aGWZuqDGKvJx"),
+            Some(ICloudPhotosKind::SharedAlbum)
+        );
+        assert_eq!(get_icloud_photos_kind("https://www.icloud.com/"), None);
+    }
+}
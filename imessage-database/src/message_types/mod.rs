@@ -3,11 +3,17 @@
 */
 
 pub mod app;
+pub mod app_decoder;
 pub mod app_store;
+pub mod apple_pay;
+pub mod audio;
 pub mod collaboration;
+pub mod contact_card;
 pub mod edited;
 pub mod expressives;
+pub mod facetime;
 pub mod handwriting;
+pub mod icloud_photos;
 pub mod music;
 pub mod placemark;
 pub mod sticker;
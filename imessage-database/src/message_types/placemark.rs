@@ -128,6 +128,34 @@ impl<'a> PlacemarkMessage<'a> {
     pub fn get_url(&self) -> Option<&str> {
         self.url.or(self.original_url)
     }
+
+    /// Get the latitude and longitude of the location, if the Maps URL contains them
+    ///
+    /// Maps links store the coordinates in the `ll` query parameter, i.e. `ll=33.450858,-118.508212`
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        [self.url, self.original_url]
+            .into_iter()
+            .flatten()
+            .find_map(parse_coordinates)
+    }
+}
+
+/// Read the `ll` query parameter from a Maps URL
+fn parse_coordinates(url: &str) -> Option<(f64, f64)> {
+    let (_, query) = url.split_once('?')?;
+    let value = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("ll="))?
+        .replace("%2C", ",");
+    let (latitude, longitude) = value.split_once(',')?;
+    let latitude: f64 = latitude.trim().parse().ok()?;
+    let longitude: f64 = longitude.trim().parse().ok()?;
+
+    if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) {
+        Some((latitude, longitude))
+    } else {
+        None
+    }
 }
 
 #[cfg(test)]
@@ -203,4 +231,36 @@ mod tests {
 
         assert_eq!(placemark, expected);
     }
+
+    #[test]
+    fn can_get_coordinates() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/shared_placemark/SharedPlacemark.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_plist(&plist).unwrap();
+
+        let balloon = PlacemarkMessage::from_map(&parsed).unwrap();
+        assert_eq!(balloon.coordinates(), Some((33.450858, -118.508212)));
+    }
+
+    #[test]
+    fn cant_get_invalid_coordinates() {
+        let mut balloon = PlacemarkMessage {
+            url: Some("https://maps.apple.com/?q=Cherry%20Cove"),
+            original_url: None,
+            place_name: None,
+            placemark: Placemark::default(),
+        };
+        assert_eq!(balloon.coordinates(), None);
+
+        balloon.url = Some("https://maps.apple.com/?ll=123.4,5.6");
+        assert_eq!(balloon.coordinates(), None);
+
+        balloon.url = Some("https://maps.apple.com/?ll=abc");
+        balloon.original_url = Some("https://maps.apple.com/?ll=1.5%2C-2.25");
+        assert_eq!(balloon.coordinates(), Some((1.5, -2.25)));
+    }
 }
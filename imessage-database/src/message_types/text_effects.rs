@@ -2,6 +2,12 @@
  Effects that can alter the appearance of message text.
 */
 
+use std::io::Cursor;
+
+use plist::Value;
+
+use crate::util::plist::{get_string_from_dict, parse_plist};
+
 /// Text effect container
 ///
 /// Message text may contain any number of traditional styles or one animation.
@@ -33,6 +39,100 @@ pub enum TextEffect<'a> {
     ///
     /// The embedded data contains the unit that the range represents.
     Conversion(Unit),
+    /// Data that Messages recognized in the text without adding a link, like an address
+    ///
+    /// The embedded data contains the kind of data the range represents.
+    Detected(DataDetector),
+}
+
+/// Kinds of data recognized by [data detectors](https://developer.apple.com/documentation/foundation/nsdatadetector)
+///
+/// A message's [`typedstream`](crate::util::typedstream) contains an archived `DDScannerResult` under the key `__kIMDataDetectedAttributeName`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DataDetector {
+    /// A street address
+    Address,
+    /// A phone number
+    PhoneNumber,
+    /// An email address
+    Email,
+    /// A web address
+    Url,
+    /// A flight number
+    Flight,
+    /// A shipment tracking number
+    TrackingNumber,
+    /// A type of data not currently supported
+    Unknown(String),
+}
+
+impl DataDetector {
+    /// Get the kind of data from the archived `DDScannerResult` stored under the `__kIMDataDetectedAttributeName` key
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        let plist = Value::from_reader(Cursor::new(payload)).ok()?;
+        let result = parse_plist(&plist).ok()?;
+        Some(Self::from_type(get_string_from_dict(&result, "T")?))
+    }
+
+    /// Get the kind of data from the type name of a `DDScannerResult`, i.e. `PhoneNumber`
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::message_types::text_effects::DataDetector;
+    ///
+    /// let detector = DataDetector::from_type("FullAddress"); // DataDetector::Address
+    /// ```
+    pub fn from_type(name: &str) -> Self {
+        match name {
+            "PhoneNumber" => Self::PhoneNumber,
+            "Email" | "EmailAddress" | "MailAddress" => Self::Email,
+            "HttpURL" | "WebURL" | "Link" | "URL" => Self::Url,
+            "FlightInformation" | "FlightNumber" => Self::Flight,
+            "TrackingNumber" | "ShipmentTrackingNumber" => Self::TrackingNumber,
+            _ if name.ends_with("Address") => Self::Address,
+            _ => Self::Unknown(name.to_string()),
+        }
+    }
+
+    /// Build a link that opens the recognized `text`, if this kind of data has one
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::message_types::text_effects::DataDetector;
+    ///
+    /// let link = DataDetector::PhoneNumber.link("(555) 867-5309"); // Some("tel:5558675309")
+    /// ```
+    pub fn link(&self, text: &str) -> Option<String> {
+        let text = text.trim();
+        match self {
+            Self::Address => Some(format!("https://maps.apple.com/?q={}", encode_query(text))),
+            Self::PhoneNumber => Some(format!(
+                "tel:{}",
+                text.chars()
+                    .filter(|c| c.is_ascii_digit() || *c == '+')
+                    .collect::<String>()
+            )),
+            Self::Email => Some(format!("mailto:{text}")),
+            Self::Url if text.contains("://") => Some(text.to_string()),
+            Self::Url => Some(format!("https://{text}")),
+            Self::Flight | Self::TrackingNumber | Self::Unknown(_) => None,
+        }
+    }
+}
+
+/// Percent-encode the characters that are not safe in a URL query
+fn encode_query(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
 }
 
 /// Unit conversion text effect container
@@ -115,3 +215,45 @@ impl Animation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::message_types::text_effects::DataDetector;
+
+    #[test]
+    fn can_get_data_detector_type() {
+        assert_eq!(
+            DataDetector::from_type("PhoneNumber"),
+            DataDetector::PhoneNumber
+        );
+        assert_eq!(
+            DataDetector::from_type("FullAddress"),
+            DataDetector::Address
+        );
+        assert_eq!(
+            DataDetector::from_type("AuthCode"),
+            DataDetector::Unknown("AuthCode".to_string())
+        );
+    }
+
+    #[test]
+    fn can_link_address() {
+        assert_eq!(
+            DataDetector::Address.link("1 Infinite Loop, Cupertino"),
+            Some("https://maps.apple.com/?q=1%20Infinite%20Loop%2C%20Cupertino".to_string())
+        );
+    }
+
+    #[test]
+    fn can_link_phone_number() {
+        assert_eq!(
+            DataDetector::PhoneNumber.link("+1 (555) 867-5309"),
+            Some("tel:+15558675309".to_string())
+        );
+    }
+
+    #[test]
+    fn cant_link_flight() {
+        assert_eq!(DataDetector::Flight.link("UA 123"), None);
+    }
+}
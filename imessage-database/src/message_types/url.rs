@@ -11,6 +11,8 @@ use crate::{
     message_types::{
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
+        facetime::FaceTimeMessage,
+        icloud_photos::ICloudPhotosMessage,
         music::MusicMessage,
         placemark::PlacemarkMessage,
         variants::{BalloonProvider, URLOverride},
@@ -75,6 +77,12 @@ impl<'a> URLMessage<'a> {
         if let Ok(balloon) = PlacemarkMessage::from_map(payload) {
             return Ok(URLOverride::SharedPlacemark(balloon));
         }
+        if let Ok(balloon) = FaceTimeMessage::from_map(payload) {
+            return Ok(URLOverride::FaceTime(balloon));
+        }
+        if let Ok(balloon) = ICloudPhotosMessage::from_map(payload) {
+            return Ok(URLOverride::ICloudPhotos(balloon));
+        }
         if let Ok(balloon) = URLMessage::from_map(payload) {
             return Ok(URLOverride::Normal(balloon));
         }
@@ -127,7 +135,7 @@ impl<'a> URLMessage<'a> {
     }
 
     /// Get the redirected URL from a URL message, falling back to the original URL, if it exists
-    pub fn get_url(&self) -> Option<&str> {
+    pub fn get_url(&self) -> Option<&'a str> {
         self.url.or(self.original_url)
     }
 }
@@ -9,8 +9,9 @@ use plist::Value;
 use crate::{
     error::plist::PlistParseError,
     message_types::{
-        app_store::AppStoreMessage, collaboration::CollaborationMessage, music::MusicMessage,
-        placemark::PlacemarkMessage, url::URLMessage,
+        app_store::AppStoreMessage, collaboration::CollaborationMessage, facetime::FaceTimeMessage,
+        icloud_photos::ICloudPhotosMessage, music::MusicMessage, placemark::PlacemarkMessage,
+        url::URLMessage,
     },
 };
 
@@ -84,6 +85,8 @@ pub enum CustomBalloon<'a> {
     URL,
     /// Handwritten animated messages
     Handwriting,
+    /// [Digital Touch](https://support.apple.com/guide/iphone/send-a-digital-touch-effect-iph3ce5b32b7/ios) sketches and effects
+    DigitalTouch,
     /// Apple Pay (one of Sent, Requested, Received)
     ApplePay,
     /// Fitness.app messages
@@ -112,21 +115,37 @@ pub enum URLOverride<'a> {
     Collaboration(CollaborationMessage<'a>),
     /// [`Placemark`](crate::message_types::placemark) messages
     SharedPlacemark(PlacemarkMessage<'a>),
+    /// [`FaceTime`](crate::message_types::facetime) call links
+    FaceTime(FaceTimeMessage<'a>),
+    /// [`iCloud Photos`](crate::message_types::icloud_photos) links and Shared Album invitations
+    ICloudPhotos(ICloudPhotosMessage<'a>),
 }
 
 /// Announcement Message Types
 ///
 /// Announcements are messages sent to a thread for actions that are not balloons, i.e.
 /// updating the name of the group or changing the group photo
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum Announcement<'a> {
     /// Someone changed the name of the group
     NameChange(&'a str),
     /// Someone updated the group photo
     PhotoChange,
+    /// Someone removed the group photo
+    PhotoRemoved,
+    /// Someone added a participant to the group; the `i32` is the added participant's handle ID
+    ParticipantAdded(i32),
+    /// Someone removed a participant from the group; the `i32` is the removed participant's handle ID
+    ParticipantRemoved(i32),
+    /// Someone left the group
+    ParticipantLeft,
     /// All parts of the message were unsent
     FullyUnsent,
-    /// Types that may occur in the future, i.e. someone leaving or joining a group
+    /// Someone kept an audio message that would otherwise have expired
+    AudioMessageKept,
+    /// A system message whose `item_type` is not understood yet; the `i32` is the `item_type`
+    UnknownEvent(i32),
+    /// Types that may occur in the future
     Unknown(&'a i32),
 }
 
@@ -3,8 +3,8 @@
 */
 
 use rusqlite::{Connection, Error, Result, Row, Statement};
-use sha1::{Digest, Sha1};
 use std::{
+    collections::HashMap,
     fs::File,
     io::Read,
     path::{Path, PathBuf},
@@ -12,15 +12,22 @@ use std::{
 
 use crate::{
     error::{attachment::AttachmentError, table::TableError},
-    message_types::sticker::{get_sticker_effect, StickerEffect},
+    message_types::{
+        audio::{parse_audio_metadata, AudioMetadata},
+        contact_card::{parse_contact_cards, ContactCard},
+        sticker::{get_sticker_effect, StickerEffect},
+    },
     tables::{
-        messages::Message,
-        table::{Table, ATTACHMENT},
+        messages::{message::MESSAGE_SERVICE, Message},
+        table::{
+            Table, ATTACHMENT, CHAT, CHAT_MESSAGE_JOIN, MESSAGE, MESSAGE_ATTACHMENT_JOIN, ORPHANED,
+        },
     },
     util::{
+        backup::{backup_file, ATTACHMENT_DOMAIN},
         dates::TIMESTAMP_FACTOR,
         dirs::home,
-        output::{done_processing, processing},
+        merge::BACKUP_ROOT,
         platform::Platform,
         query_context::QueryContext,
         size::format_file_size,
@@ -29,6 +36,8 @@ use crate::{
 
 /// The default root directory for iMessage attachment data
 pub const DEFAULT_ATTACHMENT_ROOT: &str = "~/Library/Messages/Attachments";
+/// The number of conversations listed in the diagnostic for attachments that were not located
+const MISSING_CHAT_LIMIT: usize = 10;
 
 /// Represents the [MIME type](https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_Types) of a message's attachment data
 ///
@@ -44,6 +53,20 @@ pub enum MediaType<'a> {
     Unknown,
 }
 
+impl MediaType<'_> {
+    /// Get the top-level category of the media, i.e. `image` for `image/jpeg`, if it is a known one
+    pub fn category(&self) -> Option<&'static str> {
+        match self {
+            MediaType::Image(_) => Some("image"),
+            MediaType::Video(_) => Some("video"),
+            MediaType::Audio(_) => Some("audio"),
+            MediaType::Text(_) => Some("text"),
+            MediaType::Application(_) => Some("application"),
+            MediaType::Other(_) | MediaType::Unknown => None,
+        }
+    }
+}
+
 /// Represents a single row in the `attachment` table.
 #[derive(Debug)]
 pub struct Attachment {
@@ -60,9 +83,38 @@ pub struct Attachment {
     pub total_bytes: u64,
     /// `true` if the attachment was a sticker, else `false`
     pub is_sticker: bool,
+    /// Nonzero if Messages hides the attachment from the conversation, i.e. because it was deleted
     pub hide_attachment: i32,
     /// Auxiliary data to denote that an attachment has been copied
     pub copied_path: Option<PathBuf>,
+    /// The root of the iOS backup the file is in, if the attachment was [merged](crate::util::merge) from one
+    pub backup_root: Option<PathBuf>,
+}
+
+/// Diagnostic data about the `attachment` table
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct AttachmentDiagnostic {
+    /// The number of rows in the table
+    pub total_attachments: usize,
+    /// The number of bytes of attachment data the table refers to
+    pub total_bytes: u64,
+    /// The number of bytes of attachment data found on disk
+    pub bytes_on_disk: u64,
+    /// The number of attachments whose files were not found
+    pub missing_files: usize,
+    /// The number of attachments without a path, which are included in `missing_files`
+    pub no_path_provided: usize,
+    /// The conversations with the most files that were not found, with their number of missing files
+    pub most_missing_chats: Vec<(String, usize)>,
+    /// The number of attachments joined to a message or attachment that no longer exists
+    pub dangling_joins: usize,
+}
+
+impl AttachmentDiagnostic {
+    /// The number of files with a path that were not found, which were most likely offloaded to iCloud
+    pub fn offloaded_files(&self) -> usize {
+        self.missing_files.saturating_sub(self.no_path_provided)
+    }
 }
 
 impl Table for Attachment {
@@ -77,6 +129,10 @@ impl Table for Attachment {
             is_sticker: row.get("is_sticker").unwrap_or(false),
             hide_attachment: row.get("hide_attachment").unwrap_or(0),
             copied_path: None,
+            backup_root: row
+                .get::<_, Option<String>>(BACKUP_ROOT)
+                .unwrap_or(None)
+                .map(PathBuf::from),
         })
     }
 
@@ -121,6 +177,21 @@ impl Attachment {
         Ok(out_l)
     }
 
+    /// Get the attachment with the given GUID, if it exists
+    ///
+    /// Attachments that do not belong to a message, like group chat photos, are referenced by their GUID.
+    pub fn from_guid(db: &Connection, guid: &str) -> Result<Option<Attachment>, TableError> {
+        let mut statement = db
+            .prepare(&format!("SELECT * FROM {ATTACHMENT} WHERE guid = ?1"))
+            .map_err(TableError::Attachment)?;
+
+        let mut iter = statement
+            .query_map([guid], |row| Ok(Attachment::from_row(row)))
+            .map_err(TableError::Attachment)?;
+
+        iter.next().map(Attachment::extract).transpose()
+    }
+
     /// Get the media type of an attachment
     pub fn mime_type(&'_ self) -> MediaType<'_> {
         match &self.mime_type {
@@ -203,6 +274,66 @@ impl Attachment {
         Ok(Some(StickerEffect::default()))
     }
 
+    /// Read the duration, and where feasible the waveform, of an audio attachment
+    ///
+    /// Returns `None` if the attachment is not audio or its format is not supported.
+    ///
+    /// `db_path` is the path to the root of the backup directory.
+    /// This is the same path used by [`get_connection()`](crate::tables::table::get_connection).
+    pub fn get_audio_metadata(
+        &self,
+        platform: &Platform,
+        db_path: &Path,
+        custom_attachment_root: Option<&str>,
+    ) -> Result<Option<AudioMetadata>, AttachmentError> {
+        if !matches!(self.mime_type(), MediaType::Audio(_)) {
+            return Ok(None);
+        }
+
+        Ok(self
+            .as_bytes(platform, db_path, custom_attachment_root)?
+            .and_then(|data| parse_audio_metadata(&data)))
+    }
+
+    /// `true` if Messages hides the attachment from the conversation, else `false`
+    pub fn is_hidden(&self) -> bool {
+        self.hide_attachment != 0
+    }
+
+    /// Determine if the attachment is a [vCard](https://datatracker.ietf.org/doc/html/rfc6350) contact card
+    pub fn is_contact_card(&self) -> bool {
+        match self.mime_type() {
+            MediaType::Text(subtype) => {
+                subtype.eq_ignore_ascii_case("vcard") || subtype.eq_ignore_ascii_case("x-vcard")
+            }
+            _ => self
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("vcf")),
+        }
+    }
+
+    /// Parse the contacts stored in a contact card attachment
+    ///
+    /// Returns an empty list if the attachment is not a contact card.
+    ///
+    /// `db_path` is the path to the root of the backup directory.
+    /// This is the same path used by [`get_connection()`](crate::tables::table::get_connection).
+    pub fn get_contact_cards(
+        &self,
+        platform: &Platform,
+        db_path: &Path,
+        custom_attachment_root: Option<&str>,
+    ) -> Result<Vec<ContactCard>, AttachmentError> {
+        if !self.is_contact_card() {
+            return Ok(vec![]);
+        }
+
+        match self.as_bytes(platform, db_path, custom_attachment_root)? {
+            Some(data) => Ok(parse_contact_cards(&String::from_utf8_lossy(&data))),
+            None => Ok(vec![]),
+        }
+    }
+
     /// Get the path to an attachment, if it exists
     pub fn path(&self) -> Option<&Path> {
         match &self.filename {
@@ -249,20 +380,33 @@ impl Attachment {
             let mut statement = format!("SELECT SUM(total_bytes) FROM {ATTACHMENT} a");
 
             if context.has_filters() {
-                statement.push_str(" WHERE ");
+                let mut filters = vec![];
                 if let Some(start) = context.start {
-                    statement.push_str(&format!(
+                    filters.push(format!(
                         "    a.created_date >= {}",
                         start / TIMESTAMP_FACTOR
                     ));
                 }
                 if let Some(end) = context.end {
-                    if context.start.is_some() {
-                        statement.push_str(" AND ");
-                    }
-                    statement
-                        .push_str(&format!("    a.created_date <= {}", end / TIMESTAMP_FACTOR));
+                    filters.push(format!("    a.created_date < {}", end / TIMESTAMP_FACTOR));
+                }
+                if let Some(chat_ids) = &context.selected_chat_ids {
+                    let chat_ids: Vec<String> = chat_ids.iter().map(i32::to_string).collect();
+                    filters.push(format!(
+                        "    a.ROWID IN (SELECT j.attachment_id FROM {MESSAGE_ATTACHMENT_JOIN} j JOIN {CHAT_MESSAGE_JOIN} c ON j.message_id = c.message_id WHERE c.chat_id IN ({}))",
+                        chat_ids.join(", ")
+                    ));
                 }
+                if let Some(attachment_types) = context.attachment_type_filter("a") {
+                    filters.push(format!("    {attachment_types}"));
+                }
+                if let Some(service) = context.service {
+                    filters.push(format!(
+                        "    a.ROWID IN (SELECT j.attachment_id FROM {MESSAGE_ATTACHMENT_JOIN} j JOIN {MESSAGE} m ON j.message_id = m.ROWID LEFT JOIN {CHAT_MESSAGE_JOIN} c ON c.message_id = m.ROWID WHERE {MESSAGE_SERVICE} = '{service}')"
+                    ));
+                }
+                statement.push_str(" WHERE ");
+                statement.push_str(&filters.join(" AND "));
             }
 
             db.prepare(&statement).map_err(TableError::Attachment)?
@@ -293,25 +437,79 @@ impl Attachment {
         db_path: &Path,
         custom_attachment_root: Option<&str>,
     ) -> Option<String> {
-        if let Some(mut path_str) = self.filename.clone() {
-            // Apply custom attachment path
-            if let Some(custom_attachment_path) = custom_attachment_root {
-                path_str = path_str.replace(DEFAULT_ATTACHMENT_ROOT, custom_attachment_path);
+        self.resolve_path(
+            self.filename.as_deref()?,
+            platform,
+            db_path,
+            custom_attachment_root,
+        )
+    }
+
+    /// Get the path to the video of a Live Photo, if this attachment is the still image of one
+    ///
+    /// Messages does not store the video of a Live Photo as its own attachment. Instead, it is saved next
+    /// to the still image, with the same name and a `MOV` extension, i.e. `IMG_0001.MOV` for `IMG_0001.HEIC`.
+    ///
+    /// This only returns a path if the video exists on disk.
+    pub fn live_photo_path(
+        &self,
+        platform: &Platform,
+        db_path: &Path,
+        custom_attachment_root: Option<&str>,
+    ) -> Option<String> {
+        if self.is_sticker || !matches!(self.mime_type(), MediaType::Image(_)) {
+            return None;
+        }
+        let filename = self.filename.as_deref()?;
+        let extension = Path::new(filename).extension()?.to_str()?;
+        let stem = filename.get(..filename.len() - extension.len())?;
+
+        ["MOV", "mov"]
+            .iter()
+            .filter_map(|video_extension| {
+                self.resolve_path(
+                    &format!("{stem}{video_extension}"),
+                    platform,
+                    db_path,
+                    custom_attachment_root,
+                )
+            })
+            .find(|path| Path::new(path).exists())
+    }
+
+    /// Resolve a path from the `filename` column to the location of the file on disk
+    ///
+    /// Attachments merged from an iOS backup are found in that backup, whatever the platform of the merged database.
+    fn resolve_path(
+        &self,
+        path: &str,
+        platform: &Platform,
+        db_path: &Path,
+        custom_attachment_root: Option<&str>,
+    ) -> Option<String> {
+        let (platform, db_path) = match &self.backup_root {
+            Some(backup_root) => (&Platform::iOS, backup_root.as_path()),
+            None => (platform, db_path),
+        };
+        // Apply custom attachment path
+        let path_str = match custom_attachment_root {
+            Some(custom_attachment_path) => {
+                path.replace(DEFAULT_ATTACHMENT_ROOT, custom_attachment_path)
             }
-            return match platform {
-                Platform::macOS => Some(Attachment::gen_macos_attachment(&path_str)),
-                Platform::iOS => Attachment::gen_ios_attachment(&path_str, db_path),
-            };
+            None => path.to_string(),
+        };
+        match platform {
+            Platform::macOS => Some(Attachment::gen_macos_attachment(&path_str)),
+            Platform::iOS => Attachment::gen_ios_attachment(&path_str, db_path),
         }
-        None
     }
 
     /// Emit diagnostic data for the Attachments table
     ///
     /// This is defined outside of [`Diagnostic`](crate::tables::table::Diagnostic) because it requires additional data.
     ///
-    /// Get the number of attachments that are missing from the filesystem
-    /// or are missing one of the following columns:
+    /// Get the number of attachments that are missing from the filesystem, listing the conversations
+    /// with the most attachments that were likely offloaded to iCloud, or are missing one of the following columns:
     ///
     /// - `ck_server_change_token_blob`
     /// - `sr_ck_server_change_token_blob`
@@ -325,7 +523,7 @@ impl Attachment {
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
-    /// Attachment::run_diagnostic(&conn, &db_path, &Platform::macOS);
+    /// let diagnostic = Attachment::run_diagnostic(&conn, &db_path, &Platform::macOS);
     /// ```
     ///
     /// `db_path` is the path to the root of the backup directory.
@@ -334,47 +532,57 @@ impl Attachment {
         db: &Connection,
         db_path: &Path,
         platform: &Platform,
-    ) -> Result<(), TableError> {
-        processing();
+    ) -> Result<AttachmentDiagnostic, TableError> {
         let mut total_attachments = 0;
         let mut null_attachments = 0;
         let mut size_on_disk: u64 = 0;
+        let mut offloaded_by_chat: HashMap<String, usize> = HashMap::new();
         let mut statement_paths = db
-            .prepare(&format!("SELECT filename FROM {ATTACHMENT}"))
+            .prepare(&format!(
+                "SELECT
+                     a.*,
+                     COALESCE(NULLIF(c.display_name, ''), c.chat_identifier) AS chat_name
+                 FROM {ATTACHMENT} as a
+                 LEFT JOIN {MESSAGE_ATTACHMENT_JOIN} as j ON j.attachment_id = a.ROWID
+                 LEFT JOIN {CHAT_MESSAGE_JOIN} as cmj ON cmj.message_id = j.message_id
+                 LEFT JOIN {CHAT} as c ON c.ROWID = cmj.chat_id
+                 GROUP BY a.ROWID"
+            ))
             .map_err(TableError::Attachment)?;
         let paths = statement_paths
-            .query_map([], |r| Ok(r.get(0)))
+            .query_map([], |r| {
+                Ok((
+                    Attachment::from_row(r)?,
+                    r.get::<_, Option<String>>("chat_name")?,
+                ))
+            })
             .map_err(TableError::Attachment)?;
 
         let missing_files = paths
             .filter_map(Result::ok)
-            .filter(|path: &Result<String, Error>| {
+            .filter(|(attachment, chat): &(Attachment, Option<String>)| {
                 // Keep track of the number of attachments in the table
                 total_attachments += 1;
-                if let Ok(filepath) = path {
-                    match platform {
-                        Platform::macOS => {
-                            let path = Attachment::gen_macos_attachment(filepath);
-                            let file = Path::new(&path);
+                if attachment.filename.is_some() {
+                    // Files with a path but nothing on disk were most likely offloaded to iCloud
+                    let missing = match attachment.resolved_attachment_path(platform, db_path, None)
+                    {
+                        Some(parsed_path) => {
+                            let file = Path::new(&parsed_path);
                             if let Ok(metadata) = file.metadata() {
                                 size_on_disk += metadata.len();
                             }
                             !file.exists()
                         }
-                        Platform::iOS => {
-                            if let Some(parsed_path) =
-                                Attachment::gen_ios_attachment(filepath, db_path)
-                            {
-                                let file = Path::new(&parsed_path);
-                                if let Ok(metadata) = file.metadata() {
-                                    size_on_disk += metadata.len();
-                                }
-                                return !file.exists();
-                            }
-                            // This hits if the attachment path doesn't get generated
-                            true
-                        }
+                        // This hits if the attachment path doesn't get generated
+                        None => true,
+                    };
+                    if missing {
+                        *offloaded_by_chat
+                            .entry(chat.clone().unwrap_or_else(|| ORPHANED.to_string()))
+                            .or_default() += 1;
                     }
+                    missing
                 } else {
                     // This hits if there is no path provided for the current attachment
                     null_attachments += 1;
@@ -386,32 +594,30 @@ impl Attachment {
         let total_bytes =
             Attachment::get_total_attachment_bytes(db, &QueryContext::default()).unwrap_or(0);
 
-        done_processing();
-
-        if total_attachments > 0 {
-            println!("\rAttachment diagnostic data:");
-            println!("    Total attachments: {total_attachments}");
-            println!(
-                "        Data referenced in table: {}",
-                format_file_size(total_bytes)
-            );
-            println!(
-                "        Data present on disk: {}",
-                format_file_size(size_on_disk)
-            );
-            if missing_files > 0 && total_attachments > 0 {
-                println!(
-                    "    Missing files: {missing_files:?} ({:.0}%)",
-                    (missing_files as f64 / total_attachments as f64) * 100f64
-                );
-                println!("        No path provided: {null_attachments}");
-                println!(
-                    "        No file located: {}",
-                    missing_files.saturating_sub(null_attachments)
-                );
-            }
-        }
-        Ok(())
+        Ok(AttachmentDiagnostic {
+            total_attachments,
+            total_bytes,
+            bytes_on_disk: size_on_disk,
+            missing_files,
+            no_path_provided: null_attachments,
+            most_missing_chats: most_missing(offloaded_by_chat, MISSING_CHAT_LIMIT),
+            dangling_joins: Attachment::count_dangling_joins(db)?,
+        })
+    }
+
+    /// Count the rows in the message to attachment join table whose message or attachment was deleted
+    fn count_dangling_joins(db: &Connection) -> Result<usize, TableError> {
+        db.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} as j
+                 LEFT JOIN {MESSAGE} as m ON m.ROWID = j.message_id
+                 LEFT JOIN {ATTACHMENT} as a ON a.ROWID = j.attachment_id
+                 WHERE m.ROWID IS NULL OR a.ROWID IS NULL"
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .map_err(TableError::Attachment)
     }
 
     /// Generate a macOS path for an attachment
@@ -423,26 +629,40 @@ impl Attachment {
     }
 
     /// Generate an iOS path for an attachment
+    ///
+    /// The file is looked up in the backup's `Manifest.db`, like the Messages database, if the backup has one.
     fn gen_ios_attachment(file_path: &str, db_path: &Path) -> Option<String> {
         let input = file_path.get(2..)?;
-        let filename = format!(
-            "{:x}",
-            Sha1::digest(format!("MediaDomain-{input}").as_bytes())
-        );
-        let directory = filename.get(0..2)?;
+        let path = backup_file(db_path, ATTACHMENT_DOMAIN, input)?;
 
-        Some(format!("{}/{directory}/{filename}", db_path.display()))
+        Some(path.display().to_string())
     }
 }
 
+/// Sort conversations by their number of missing attachments, keeping the first `limit`
+fn most_missing(counts: HashMap<String, usize>, limit: usize) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(a_chat, a_count), (b_chat, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_chat.cmp(b_chat))
+    });
+    counts.truncate(limit);
+    counts
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        tables::attachment::{Attachment, MediaType, DEFAULT_ATTACHMENT_ROOT},
-        util::platform::Platform,
+        tables::attachment::{most_missing, Attachment, MediaType, DEFAULT_ATTACHMENT_ROOT},
+        util::{backup::MANIFEST_DB, platform::Platform, query_context::QueryContext},
     };
 
-    use std::path::{Path, PathBuf};
+    use rusqlite::Connection;
+    use std::{
+        collections::HashMap,
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+        path::{Path, PathBuf},
+    };
 
     fn sample_attachment() -> Attachment {
         Attachment {
@@ -455,9 +675,70 @@ mod tests {
             is_sticker: false,
             hide_attachment: 0,
             copied_path: None,
+            backup_root: None,
         }
     }
 
+    #[test]
+    fn can_get_from_guid() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, guid TEXT, filename TEXT, mime_type TEXT);",
+            "INSERT INTO attachment VALUES (7, 'at_0_F1C3E7D9-1234', '~/Library/Messages/Attachments/a/b/GroupPhotoImage.jpeg', 'image/jpeg');",
+        ))
+        .unwrap();
+
+        let attachment = Attachment::from_guid(&db, "at_0_F1C3E7D9-1234")
+            .unwrap()
+            .unwrap();
+        assert_eq!(attachment.rowid, 7);
+        assert_eq!(attachment.extension(), Some("jpeg"));
+        assert!(Attachment::from_guid(&db, "missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn can_get_total_attachment_bytes_by_chat_service() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY, service TEXT);",
+            "CREATE TABLE chat (ROWID INTEGER PRIMARY KEY, service_name TEXT);",
+            "CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);",
+            "CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, total_bytes INTEGER);",
+            "CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);",
+            // Message 2 does not store its service, so it uses the service of its chat
+            "INSERT INTO message VALUES (1, 'iMessage'), (2, NULL);",
+            "INSERT INTO chat VALUES (1, 'SMS');",
+            "INSERT INTO chat_message_join VALUES (1, 1), (1, 2);",
+            "INSERT INTO attachment VALUES (5, 100), (6, 20);",
+            "INSERT INTO message_attachment_join VALUES (1, 5), (2, 6);",
+        ))
+        .unwrap();
+
+        let mut context = QueryContext::default();
+        context.set_service("sms").unwrap();
+        assert_eq!(
+            Attachment::get_total_attachment_bytes(&db, &context).unwrap(),
+            20
+        );
+    }
+
+    #[test]
+    fn can_count_dangling_joins() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY);",
+            "CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY);",
+            "CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);",
+            "INSERT INTO message VALUES (1), (2);",
+            "INSERT INTO attachment VALUES (5), (6);",
+            // Message 3 and attachment 7 were deleted
+            "INSERT INTO message_attachment_join VALUES (1, 5), (2, 6), (3, 5), (1, 7);",
+        ))
+        .unwrap();
+
+        assert_eq!(Attachment::count_dangling_joins(&db).unwrap(), 2);
+    }
+
     #[test]
     fn can_get_path() {
         let attachment = sample_attachment();
@@ -511,6 +792,19 @@ mod tests {
         assert_eq!(attachment.mime_type(), MediaType::Unknown);
     }
 
+    #[test]
+    fn can_get_mime_type_category() {
+        let mut attachment = sample_attachment();
+        assert_eq!(attachment.mime_type().category(), Some("image"));
+
+        attachment.mime_type = None;
+        attachment.uti = Some("com.apple.coreaudio-format".to_string());
+        assert_eq!(attachment.mime_type().category(), Some("audio"));
+
+        attachment.mime_type = Some("fake/bloop".to_string());
+        assert_eq!(attachment.mime_type().category(), None);
+    }
+
     #[test]
     fn can_get_filename() {
         let attachment = sample_attachment();
@@ -590,6 +884,35 @@ mod tests {
             .ends_with("c~d.png"));
     }
 
+    #[test]
+    fn can_get_live_photo_path() {
+        let db_path = PathBuf::from("fake_root");
+        let dir = temp_dir().join("imessage-database-live-photo");
+        create_dir_all(&dir).unwrap();
+        write(dir.join("IMG_0001.HEIC"), "still").unwrap();
+        write(dir.join("IMG_0001.MOV"), "video").unwrap();
+
+        let mut attachment = sample_attachment();
+        attachment.filename = Some(dir.join("IMG_0001.HEIC").display().to_string());
+        attachment.mime_type = Some("image/heic".to_string());
+
+        assert_eq!(
+            attachment.live_photo_path(&Platform::macOS, &db_path, None),
+            Some(dir.join("IMG_0001.MOV").display().to_string())
+        );
+    }
+
+    #[test]
+    fn cant_get_live_photo_path_missing_video() {
+        let db_path = PathBuf::from("fake_root");
+        let attachment = sample_attachment();
+
+        assert_eq!(
+            attachment.live_photo_path(&Platform::macOS, &db_path, None),
+            None
+        );
+    }
+
     #[test]
     fn can_get_resolved_path_ios() {
         let db_path = PathBuf::from("fake_root");
@@ -601,6 +924,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_get_resolved_path_ios_manifest() {
+        let db_path = temp_dir().join("imessage-database-attachment-manifest");
+        let _ = remove_dir_all(&db_path);
+        create_dir_all(&db_path).unwrap();
+        let db = Connection::open(db_path.join(MANIFEST_DB)).unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE Files (fileID TEXT PRIMARY KEY, domain TEXT, relativePath TEXT);",
+            "INSERT INTO Files VALUES ('cd4567', 'MediaDomain', 'Library/SMS/Attachments/a/b/c.png');",
+        ))
+        .unwrap();
+
+        let mut attachment = sample_attachment();
+        attachment.filename = Some("~/Library/SMS/Attachments/a/b/c.png".to_string());
+
+        // Files listed in the manifest are not stored under the name generated from their path
+        assert_eq!(
+            attachment.resolved_attachment_path(&Platform::iOS, &db_path, None),
+            Some(db_path.join("cd").join("cd4567").display().to_string())
+        );
+    }
+
     #[test]
     fn can_get_resolved_path_ios_custom() {
         let db_path = PathBuf::from("fake_root");
@@ -614,6 +959,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_get_resolved_path_merged_ios() {
+        let db_path = PathBuf::from("merged_root");
+        let mut attachment = sample_attachment();
+        attachment.backup_root = Some(PathBuf::from("fake_root"));
+
+        // The merged database is read like one from macOS, but the file is still in the backup
+        assert_eq!(attachment.extension(), Some("png"));
+        assert_eq!(
+            attachment.resolved_attachment_path(&Platform::macOS, &db_path, None),
+            Some("fake_root/41/41746ffc65924078eae42725c979305626f57cca".to_string())
+        );
+    }
+
     #[test]
     fn cant_get_missing_resolved_path_macos() {
         let db_path = PathBuf::from("fake_root");
@@ -676,4 +1035,47 @@ mod tests {
 
         assert_eq!(attachment.file_size(), String::from("16777216.00 TB"));
     }
+
+    #[test]
+    fn can_sort_missing_by_chat() {
+        let counts = HashMap::from([
+            ("Family".to_string(), 3),
+            ("+15558675309".to_string(), 10),
+            ("Book Club".to_string(), 3),
+            ("orphaned".to_string(), 1),
+        ]);
+
+        assert_eq!(
+            most_missing(counts, 3),
+            vec![
+                ("+15558675309".to_string(), 10),
+                ("Book Club".to_string(), 3),
+                ("Family".to_string(), 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn can_detect_contact_card() {
+        let mut attachment = sample_attachment();
+        attachment.mime_type = Some("text/vcard".to_string());
+        assert!(attachment.is_contact_card());
+
+        attachment.mime_type = Some("text/x-vCard".to_string());
+        assert!(attachment.is_contact_card());
+    }
+
+    #[test]
+    fn can_detect_contact_card_from_extension() {
+        let mut attachment = sample_attachment();
+        attachment.mime_type = None;
+        attachment.filename = Some("a/b/Jane Doe.vcf".to_string());
+        assert!(attachment.is_contact_card());
+    }
+
+    #[test]
+    fn cant_detect_contact_card() {
+        let attachment = sample_attachment();
+        assert!(!attachment.is_contact_card());
+    }
 }
@@ -2,33 +2,56 @@
  This module represents common (but not all) columns in the `chat` table.
 */
 
-use std::collections::HashMap;
+use std::{collections::HashMap, io::Cursor};
 
+use plist::Value;
 use rusqlite::{Connection, Error, Result, Row, Statement};
 
 use crate::{
     error::table::TableError,
-    tables::table::{Cacheable, Table, CHAT},
+    tables::table::{Cacheable, Table, CHAT, CHAT_MESSAGE_JOIN, MESSAGE},
 };
 
+/// The `style` of a group chat
+const GROUP_CHAT_STYLE: i32 = 43;
+/// The `style` of a one-on-one chat
+const DIRECT_CHAT_STYLE: i32 = 45;
+
+/// The number of messages in a chat and when the first and last of them were sent
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChatActivity {
+    /// The number of messages in the chat
+    pub messages: usize,
+    /// The `date` of the earliest message, in the database's timestamp format
+    pub first_date: i64,
+    /// The `date` of the latest message, in the database's timestamp format
+    pub last_date: i64,
+}
+
 /// Represents a single row in the `chat` table.
 #[derive(Debug)]
 pub struct Chat {
     pub rowid: i32,
+    /// The unique identifier of the chat, i.e. `iMessage;-;+15558675309`
+    pub guid: String,
     pub chat_identifier: String,
     /// The service the chat used, i.e. iMessage, SMS, IRC, etc.
     pub service_name: Option<String>,
     /// Optional custom name created created for the chat
     pub display_name: Option<String>,
+    /// Whether the chat is a group chat (`43`) or a one-on-one chat (`45`)
+    pub style: Option<i32>,
 }
 
 impl Table for Chat {
     fn from_row(row: &Row) -> Result<Chat> {
         Ok(Chat {
             rowid: row.get("rowid")?,
+            guid: row.get("guid").unwrap_or_default(),
             chat_identifier: row.get("chat_identifier")?,
             service_name: row.get("service_name")?,
             display_name: row.get("display_name").unwrap_or(None),
+            style: row.get("style").unwrap_or(None),
         })
     }
 
@@ -102,4 +125,204 @@ impl Chat {
             None => None,
         }
     }
+
+    /// Get the GUID of the attachment that holds the chat's group photo, if one is set
+    ///
+    /// The photo is referenced by the `groupPhotoGuid` key of the plist stored in the chat's `properties`.
+    pub fn group_photo_guid(&self, db: &Connection) -> Option<String> {
+        let properties: Vec<u8> = db
+            .query_row(
+                &format!("SELECT properties FROM {CHAT} WHERE ROWID = ?1"),
+                [self.rowid],
+                |row| row.get(0),
+            )
+            .ok()?;
+        Value::from_reader(Cursor::new(properties))
+            .ok()?
+            .as_dictionary()?
+            .get("groupPhotoGuid")?
+            .as_string()
+            .map(String::from)
+    }
+
+    /// Determine if the chat is a group chat
+    ///
+    /// Uses the chat's `style` when it is known, otherwise assumes chats with more than one other participant are groups.
+    pub fn is_group(&self, num_participants: usize) -> bool {
+        match self.style {
+            Some(GROUP_CHAT_STYLE) => true,
+            Some(DIRECT_CHAT_STYLE) => false,
+            _ => num_participants > 1,
+        }
+    }
+
+    /// Get the number of messages in each chat and the dates of its first and last messages
+    ///
+    /// Chats without any messages are not included.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::chat::Chat;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let activity = Chat::activity(&conn);
+    /// ```
+    pub fn activity(db: &Connection) -> Result<HashMap<i32, ChatActivity>, TableError> {
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT c.chat_id, COUNT(*), MIN(m.date), MAX(m.date)
+                FROM {CHAT_MESSAGE_JOIN} c
+                JOIN {MESSAGE} m ON m.ROWID = c.message_id
+                GROUP BY c.chat_id"
+            ))
+            .map_err(TableError::Chat)?;
+
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i32>(0)?,
+                    ChatActivity {
+                        messages: row.get(1)?,
+                        first_date: row.get::<_, Option<i64>>(2)?.unwrap_or_default(),
+                        last_date: row.get::<_, Option<i64>>(3)?.unwrap_or_default(),
+                    },
+                ))
+            })
+            .map_err(TableError::Chat)?;
+
+        rows.collect::<Result<HashMap<_, _>>>()
+            .map_err(TableError::Chat)
+    }
+
+    /// Determine if the chat is one of the pinned conversations, given their identifiers
+    ///
+    /// Pins refer to a chat by its `guid`, i.e. `iMessage;-;+15558675309`, or by its `chat_identifier`.
+    pub fn is_pinned(&self, pinned: &[String]) -> bool {
+        pinned.iter().any(|identifier| {
+            (!self.guid.is_empty() && *identifier == self.guid)
+                || *identifier == self.chat_identifier
+                || identifier
+                    .rsplit_once(';')
+                    .is_some_and(|(_, id)| id == self.chat_identifier)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+    use rusqlite::Connection;
+
+    use crate::tables::chat::{Chat, ChatActivity};
+
+    fn blank() -> Chat {
+        Chat {
+            rowid: 0,
+            guid: String::new(),
+            chat_identifier: String::new(),
+            service_name: None,
+            display_name: None,
+            style: None,
+        }
+    }
+
+    #[test]
+    fn can_get_group_from_style() {
+        let mut chat = blank();
+        chat.style = Some(43);
+        assert!(chat.is_group(1));
+    }
+
+    #[test]
+    fn can_get_direct_from_style() {
+        let mut chat = blank();
+        chat.style = Some(45);
+        assert!(!chat.is_group(3));
+    }
+
+    #[test]
+    fn can_get_pinned() {
+        let mut chat = blank();
+        chat.guid = "iMessage;+;chat123456789".to_string();
+        chat.chat_identifier = "chat123456789".to_string();
+        assert!(chat.is_pinned(&["iMessage;+;chat123456789".to_string()]));
+        assert!(chat.is_pinned(&["SMS;+;chat123456789".to_string()]));
+        assert!(!chat.is_pinned(&["iMessage;-;+15558675309".to_string()]));
+        assert!(!chat.is_pinned(&[]));
+    }
+
+    #[test]
+    fn can_get_group_photo_guid() {
+        let mut properties = Dictionary::new();
+        properties.insert(
+            "groupPhotoGuid".to_string(),
+            Value::String("at_0_F1C3E7D9-1234".to_string()),
+        );
+        let mut blob = vec![];
+        Value::Dictionary(properties)
+            .to_writer_binary(&mut blob)
+            .unwrap();
+
+        let db = Connection::open_in_memory().unwrap();
+        db.execute(
+            "CREATE TABLE chat (ROWID INTEGER PRIMARY KEY, properties BLOB)",
+            [],
+        )
+        .unwrap();
+        db.execute("INSERT INTO chat VALUES (1, ?1), (2, NULL)", [blob])
+            .unwrap();
+
+        let mut chat = blank();
+        chat.rowid = 1;
+        assert_eq!(
+            chat.group_photo_guid(&db),
+            Some("at_0_F1C3E7D9-1234".to_string())
+        );
+
+        chat.rowid = 2;
+        assert_eq!(chat.group_photo_guid(&db), None);
+    }
+
+    #[test]
+    fn can_get_activity() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY, date INTEGER);",
+            "CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);",
+            "INSERT INTO message VALUES (1, 300), (2, 100), (3, 200), (4, 50);",
+            // Message 5 was deleted, so it is not counted
+            "INSERT INTO chat_message_join VALUES (1, 1), (1, 2), (2, 3), (2, 5);",
+        ))
+        .unwrap();
+
+        let activity = Chat::activity(&db).unwrap();
+        assert_eq!(activity.len(), 2);
+        assert_eq!(
+            activity.get(&1),
+            Some(&ChatActivity {
+                messages: 2,
+                first_date: 100,
+                last_date: 300,
+            })
+        );
+        assert_eq!(
+            activity.get(&2),
+            Some(&ChatActivity {
+                messages: 1,
+                first_date: 200,
+                last_date: 200,
+            })
+        );
+    }
+
+    #[test]
+    fn can_get_group_from_participants() {
+        let chat = blank();
+        assert!(chat.is_group(2));
+        assert!(!chat.is_group(1));
+    }
 }
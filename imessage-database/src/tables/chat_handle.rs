@@ -7,9 +7,9 @@ use std::collections::{BTreeSet, HashMap, HashSet};
 use crate::{
     error::table::TableError,
     tables::table::{
-        Cacheable, Deduplicate, Diagnostic, Table, CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN,
+        Cacheable, Deduplicate, Diagnostic, Table, CHAT, CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN,
+        HANDLE, MESSAGE,
     },
-    util::output::{done_processing, processing},
 };
 use rusqlite::{Connection, Error, Result, Row, Statement};
 
@@ -19,6 +19,108 @@ pub struct ChatToHandle {
     handle_id: i32,
 }
 
+/// Diagnostic data about the `chat_handle_join` table
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ChatToHandleDiagnostic {
+    /// The number of chats with messages that have no participants
+    pub chats_with_no_handles: usize,
+    /// The number of participants joined to a chat or handle that no longer exists
+    pub dangling_handle_joins: usize,
+}
+
+/// The ways the members of a group chat can change
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MembershipChangeKind {
+    /// A participant was added to the chat
+    Added,
+    /// A participant was removed from the chat
+    Removed,
+    /// A participant left the chat
+    Left,
+}
+
+/// A change to the members of a group chat, read from the messages that announce it
+#[derive(Debug, PartialEq, Eq)]
+pub struct MembershipChange {
+    /// The date the change happened, in the same format as message dates
+    pub date: i64,
+    /// The kind of change
+    pub kind: MembershipChangeKind,
+    /// The handle of the participant who joined or left, where `0` is the database owner
+    pub member: i32,
+    /// The handle of the participant who made the change, where `0` is the database owner
+    pub actor: i32,
+}
+
+impl ChatToHandle {
+    /// Read the changes to the members of a chat, oldest first
+    ///
+    /// The `chat_handle_join` table only stores the current members of a chat, so past members are
+    /// reconstructed from the participant change messages sent to it.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::chat_handle::ChatToHandle;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let changes = ChatToHandle::membership_changes(&conn, 1);
+    /// ```
+    pub fn membership_changes(
+        db: &Connection,
+        chat_id: i32,
+    ) -> Result<Vec<MembershipChange>, TableError> {
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT m.date, m.item_type, m.group_action_type, m.other_handle, m.handle_id, m.is_from_me
+                 FROM {MESSAGE} m
+                 INNER JOIN {CHAT_MESSAGE_JOIN} c ON m.ROWID = c.message_id
+                 WHERE c.chat_id = ?1 AND m.item_type IN (1, 3)
+                 ORDER BY m.date"
+            ))
+            .map_err(TableError::ChatToHandle)?;
+
+        let rows = statement
+            .query_map([chat_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i32>(1)?,
+                    row.get::<_, i32>(2)?,
+                    row.get::<_, i32>(3)?,
+                    row.get::<_, Option<i32>>(4)?,
+                    row.get::<_, bool>(5)?,
+                ))
+            })
+            .map_err(TableError::ChatToHandle)?;
+
+        let mut changes = vec![];
+        for row in rows {
+            let (date, item_type, action, other_handle, handle_id, is_from_me) =
+                row.map_err(TableError::ChatToHandle)?;
+            let sender = match is_from_me {
+                true => 0,
+                false => handle_id.unwrap_or_default(),
+            };
+            let (kind, member) = match (item_type, action) {
+                (1, 0) => (MembershipChangeKind::Added, other_handle),
+                (1, 1) => (MembershipChangeKind::Removed, other_handle),
+                (3, 0) => (MembershipChangeKind::Left, sender),
+                _ => continue,
+            };
+            changes.push(MembershipChange {
+                date,
+                kind,
+                member,
+                actor: sender,
+            });
+        }
+        Ok(changes)
+    }
+}
+
 impl Table for ChatToHandle {
     fn from_row(row: &Row) -> Result<ChatToHandle> {
         Ok(ChatToHandle {
@@ -114,7 +216,9 @@ impl Deduplicate for ChatToHandle {
 }
 
 impl Diagnostic for ChatToHandle {
-    /// Emit diagnostic data for the Chat to Handle join table
+    type T = ChatToHandleDiagnostic;
+
+    /// Collect diagnostic data for the Chat to Handle join table
     ///
     /// Get the number of chats referenced in the messages table
     /// that do not exist in this join table:
@@ -127,11 +231,9 @@ impl Diagnostic for ChatToHandle {
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
-    /// ChatToHandle::run_diagnostic(&conn);
+    /// let diagnostic = ChatToHandle::run_diagnostic(&conn);
     /// ```
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
-        processing();
-
+    fn run_diagnostic(db: &Connection) -> Result<ChatToHandleDiagnostic, TableError> {
         // Get the Chat IDs that are associated with messages
         let mut statement_message_chats = db
             .prepare(&format!("SELECT DISTINCT chat_id from {CHAT_MESSAGE_JOIN}"))
@@ -160,26 +262,107 @@ impl Diagnostic for ChatToHandle {
             }
         });
 
-        done_processing();
-
-        // Find the set difference and emit
+        // Find the set difference
         let chats_with_no_handles = unique_chats_from_messages
             .difference(&unique_chats_from_handles)
             .count();
-        if chats_with_no_handles > 0 {
-            println!("Thread diagnostic data:");
-            println!("    Chats with no handles: {chats_with_no_handles:?}");
-        }
 
-        Ok(())
+        // Find the participants whose chat or handle was deleted
+        let dangling_handle_joins: usize = db
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {CHAT_HANDLE_JOIN} as j
+                     LEFT JOIN {CHAT} as c ON c.ROWID = j.chat_id
+                     LEFT JOIN {HANDLE} as h ON h.ROWID = j.handle_id
+                     WHERE c.ROWID IS NULL OR h.ROWID IS NULL"
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .map_err(TableError::ChatToHandle)?;
+
+        Ok(ChatToHandleDiagnostic {
+            chats_with_no_handles,
+            dangling_handle_joins,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tables::{chat_handle::ChatToHandle, table::Deduplicate};
+    use crate::tables::{
+        chat_handle::{
+            ChatToHandle, ChatToHandleDiagnostic, MembershipChange, MembershipChangeKind,
+        },
+        table::{Deduplicate, Diagnostic},
+    };
+    use rusqlite::Connection;
     use std::collections::{BTreeSet, HashMap, HashSet};
 
+    #[test]
+    fn can_run_diagnostic() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE chat (ROWID INTEGER PRIMARY KEY);",
+            "CREATE TABLE handle (ROWID INTEGER PRIMARY KEY);",
+            "CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);",
+            "CREATE TABLE chat_handle_join (chat_id INTEGER, handle_id INTEGER);",
+            "INSERT INTO chat VALUES (1), (2);",
+            "INSERT INTO handle VALUES (10);",
+            // Chat 2 has messages but no participants, and chat 3 and handle 11 were deleted
+            "INSERT INTO chat_message_join VALUES (1, 1), (2, 2);",
+            "INSERT INTO chat_handle_join VALUES (1, 10), (1, 11), (3, 10);",
+        ))
+        .unwrap();
+
+        let diagnostic = ChatToHandle::run_diagnostic(&db).unwrap();
+        assert_eq!(
+            diagnostic,
+            ChatToHandleDiagnostic {
+                chats_with_no_handles: 1,
+                dangling_handle_joins: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn can_get_membership_changes() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY, date INTEGER, item_type INTEGER, group_action_type INTEGER, other_handle INTEGER, handle_id INTEGER, is_from_me INTEGER);",
+            "CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);",
+            // Added by me, a normal message, removed by participant 2, left, and a name change
+            "INSERT INTO message VALUES (1, 100, 1, 0, 3, 0, 1), (2, 200, 0, 0, 0, 2, 0), (3, 300, 1, 1, 3, 2, 0), (4, 400, 3, 0, 0, 4, 0), (5, 500, 2, 0, 0, 2, 0);",
+            "INSERT INTO chat_message_join VALUES (1, 1), (1, 2), (1, 3), (1, 4), (1, 5), (2, 1);",
+        ))
+        .unwrap();
+
+        let changes = ChatToHandle::membership_changes(&db, 1).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                MembershipChange {
+                    date: 100,
+                    kind: MembershipChangeKind::Added,
+                    member: 3,
+                    actor: 0
+                },
+                MembershipChange {
+                    date: 300,
+                    kind: MembershipChangeKind::Removed,
+                    member: 3,
+                    actor: 2
+                },
+                MembershipChange {
+                    date: 400,
+                    kind: MembershipChangeKind::Left,
+                    member: 4,
+                    actor: 4
+                },
+            ]
+        );
+    }
+
     #[test]
     fn can_dedupe() {
         let mut input: HashMap<i32, BTreeSet<i32>> = HashMap::new();
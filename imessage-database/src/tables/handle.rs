@@ -8,7 +8,6 @@ use std::collections::{BTreeSet, HashMap};
 use crate::{
     error::table::TableError,
     tables::table::{Cacheable, Deduplicate, Diagnostic, Table, HANDLE, ME},
-    util::output::{done_processing, processing},
 };
 
 /// Represents a single row in the `handle` table.
@@ -19,6 +18,13 @@ pub struct Handle {
     pub person_centric_id: Option<String>,
 }
 
+/// Diagnostic data about the `handle` table
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HandleDiagnostic {
+    /// The number of contacts that are reached at more than one phone number or email address
+    pub contacts_with_multiple_ids: usize,
+}
+
 impl Table for Handle {
     fn from_row(row: &Row) -> Result<Handle> {
         Ok(Handle {
@@ -126,7 +132,9 @@ impl Deduplicate for Handle {
 }
 
 impl Diagnostic for Handle {
-    /// Emit diagnostic data for the Handles table
+    type T = HandleDiagnostic;
+
+    /// Collect diagnostic data for the Handles table
     ///
     /// Get the number of handles that are duplicated
     ///
@@ -145,33 +153,25 @@ impl Diagnostic for Handle {
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
-    /// Handle::run_diagnostic(&conn);
+    /// let diagnostic = Handle::run_diagnostic(&conn);
     /// ```
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
+    fn run_diagnostic(db: &Connection) -> Result<HandleDiagnostic, TableError> {
         let query = concat!(
             "SELECT COUNT(DISTINCT person_centric_id) ",
             "FROM handle ",
             "WHERE person_centric_id NOT NULL"
         );
 
+        let mut diagnostic = HandleDiagnostic::default();
         if let Ok(mut rows) = db.prepare(query).map_err(TableError::Handle) {
-            processing();
-
-            let count_dupes: Option<i32> = rows
+            let count_dupes: Option<usize> = rows
                 .query_row([], |r| r.get(0))
                 .map_err(TableError::Handle)?;
 
-            done_processing();
-
-            if let Some(dupes) = count_dupes {
-                if dupes > 0 {
-                    println!("Handle diagnostic data:");
-                    println!("    Contacts with more than one ID: {dupes}");
-                }
-            }
+            diagnostic.contacts_with_multiple_ids = count_dupes.unwrap_or(0);
         }
 
-        Ok(())
+        Ok(diagnostic)
     }
 }
 
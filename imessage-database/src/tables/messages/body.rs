@@ -1,13 +1,16 @@
 use crate::{
     message_types::{
         edited::EditStatus,
-        text_effects::{Animation, Style, TextEffect, Unit},
+        text_effects::{Animation, DataDetector, Style, TextEffect, Unit},
     },
     tables::messages::{
         models::{BubbleComponent, TextAttributes},
         Message,
     },
-    util::typedstream::models::{Archivable, OutputData},
+    util::typedstream::{
+        attributes::utf16_positions,
+        models::{Archivable, OutputData},
+    },
 };
 
 /// Character found in message body text that indicates attachment position
@@ -35,12 +38,10 @@ pub(crate) fn parse_body_typedstream(message: &Message) -> Option<Vec<BubbleComp
         let mut current_end = 0;
 
         // We want to index into the message text, so we need a table to align
-        // Apple's indexes with the actual chars, not the bytes
-        let char_index_table: Vec<usize> = message
-            .text
-            .as_ref()?
-            .char_indices()
-            .map(|(a, _)| a)
+        // Apple's indexes, which count UTF-16 code units, with the bytes of the actual chars
+        let char_index_table: Vec<usize> = utf16_positions(message.text.as_ref()?)
+            .into_iter()
+            .map(|(_, byte_idx)| byte_idx)
             .collect();
 
         while idx < components.len() {
@@ -103,7 +104,7 @@ pub(crate) fn parse_body_typedstream(message: &Message) -> Option<Vec<BubbleComp
     (!out_v.is_empty()).then_some(out_v)
 }
 
-fn get_range(component: &Archivable) -> Option<(&i64, &u64)> {
+fn get_range<'a>(component: &'a Archivable) -> Option<(&'a i64, &'a u64)> {
     if let Archivable::Data(items) = component {
         if items.len() == 2 {
             if let (OutputData::SignedInteger(item), OutputData::UnsignedInteger(end)) =
@@ -134,7 +135,11 @@ fn get_attribute_dict_length(component: Option<&Archivable>) -> usize {
 }
 
 /// Get a specific number of objects that represent the content of a dictionary
-fn get_n_dict_objects(components: &[Archivable], idx: usize, num_objects: usize) -> &[Archivable] {
+fn get_n_dict_objects<'a, 'b>(
+    components: &'a [Archivable<'b>],
+    idx: usize,
+    num_objects: usize,
+) -> &'a [Archivable<'b>] {
     if num_objects == 0 {
         return &[];
     }
@@ -161,6 +166,8 @@ fn get_bubble_type<'a>(
 ) -> Option<BubbleResult<'a>> {
     let range_start = get_char_idx(message.text.as_ref()?, start, char_indices);
     let range_end = get_char_idx(message.text.as_ref()?, end, char_indices);
+    // Data detectors are only used when the range has no other effect, like a link
+    let mut detected = None;
     for (idx, key) in components.iter().enumerate() {
         if let Some(key_name) = key.as_nsstring() {
             match key_name {
@@ -217,6 +224,12 @@ fn get_bubble_type<'a>(
                         )),
                     )));
                 }
+                "__kIMDataDetectedAttributeName" => {
+                    detected = components
+                        .get(idx + 1..)
+                        .and_then(get_data_detector_payload)
+                        .and_then(DataDetector::from_payload);
+                }
                 _ => {}
             }
         }
@@ -224,10 +237,24 @@ fn get_bubble_type<'a>(
     Some(BubbleResult::Continuation(TextAttributes::new(
         range_start,
         range_end,
-        TextEffect::Default,
+        detected.map_or(TextEffect::Default, TextEffect::Detected),
     )))
 }
 
+/// Get the archived `DDScannerResult`, which is stored after the `NSMutableData` object that holds its length
+fn get_data_detector_payload<'a>(components: &'a [Archivable]) -> Option<&'a [u8]> {
+    components.iter().take(2).find_map(|component| {
+        let items = match component {
+            Archivable::Data(items) | Archivable::Object(_, items) => items,
+            _ => return None,
+        };
+        items.iter().find_map(|item| match item {
+            OutputData::Array(bytes) => Some(bytes.as_ref()),
+            _ => None,
+        })
+    })
+}
+
 /// Extract text styles from a range of key-value pairs
 fn resolve_styles(components: &[Archivable]) -> Vec<Style> {
     let mut styles = vec![];
@@ -304,7 +331,10 @@ mod typedstream_tests {
             models::{BubbleComponent, TextAttributes},
             Message,
         },
-        util::typedstream::parser::TypedStreamReader,
+        util::typedstream::{
+            models::{Archivable, Class, OutputData},
+            parser::TypedStreamReader,
+        },
     };
 
     pub(super) fn blank() -> Message {
@@ -334,6 +364,8 @@ mod typedstream_tests {
             thread_originator_guid: None,
             thread_originator_part: None,
             date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
             associated_message_emoji: None,
             chat_id: None,
             num_attachments: 0,
@@ -358,7 +390,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         assert_eq!(
             parse_body_typedstream(&m).unwrap(),
@@ -384,7 +419,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         assert_eq!(
             parse_body_typedstream(&m).unwrap(),
@@ -408,7 +446,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         assert_eq!(
             parse_body_typedstream(&m).unwrap(),
@@ -434,7 +475,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         assert_eq!(
             parse_body_typedstream(&m).unwrap(),
@@ -465,7 +509,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         assert_eq!(
             parse_body_typedstream(&m).unwrap(),
@@ -493,7 +540,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.edited_parts = Some(EditedMessage {
             parts: vec![
@@ -551,7 +601,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         assert_eq!(
             parse_body_typedstream(&m).unwrap(),
@@ -576,7 +629,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         assert_eq!(
             parse_body_typedstream(&m).unwrap(),
@@ -600,7 +656,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.components
             .as_ref()
@@ -633,7 +692,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.components
             .as_ref()
@@ -666,7 +728,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.components
             .as_ref()
@@ -698,7 +763,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.components
             .as_ref()
@@ -730,7 +798,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.components
             .as_ref()
@@ -762,7 +833,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.components
             .as_ref()
@@ -795,7 +869,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.components
             .as_ref()
@@ -843,7 +920,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.components
             .as_ref()
@@ -891,7 +971,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.components
             .as_ref()
@@ -934,7 +1017,10 @@ mod typedstream_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        m.components = parser.parse().ok();
+        m.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         m.components
             .as_ref()
@@ -953,6 +1039,87 @@ mod typedstream_tests {
             ]),]
         );
     }
+
+    /// Build an archived object holding a single value
+    fn object<'a>(class: &'a str, value: OutputData<'a>) -> Archivable<'a> {
+        Archivable::Object(Class::new(class.into(), 1), vec![value])
+    }
+
+    /// Build the components for a range of `length` UTF-16 code units with a single attribute
+    fn attributed_range<'a>(
+        length: u64,
+        key: &'a str,
+        value: Archivable<'a>,
+    ) -> Vec<Archivable<'a>> {
+        vec![
+            Archivable::Data(vec![
+                OutputData::SignedInteger(1),
+                OutputData::UnsignedInteger(length),
+            ]),
+            object("NSDictionary", OutputData::SignedInteger(1)),
+            object("NSString", OutputData::String(key.into())),
+            value,
+        ]
+    }
+
+    #[test]
+    fn can_get_message_body_emoji_only_ranges() {
+        let mut m = blank();
+        // Each emoji is two UTF-16 code units and four bytes
+        m.text = Some("😀👍".to_string());
+
+        let mut components = vec![object("NSMutableString", OutputData::String("😀👍".into()))];
+        components.extend(attributed_range(
+            2,
+            "__kIMTextBoldAttributeName",
+            object("NSNumber", OutputData::SignedInteger(1)),
+        ));
+        components.extend(attributed_range(
+            2,
+            "__kIMMessagePartAttributeName",
+            object("NSNumber", OutputData::SignedInteger(0)),
+        ));
+        m.components = Some(components);
+
+        assert_eq!(
+            parse_body_typedstream(&m).unwrap(),
+            vec![BubbleComponent::Text(vec![
+                TextAttributes::new(0, 4, TextEffect::Styles(vec![Style::Bold])),
+                TextAttributes::new(4, 8, TextEffect::Default),
+            ])]
+        );
+    }
+
+    #[test]
+    fn can_get_message_body_rtl_ranges() {
+        let mut m = blank();
+        // Each Arabic letter is one UTF-16 code unit and two bytes
+        m.text = Some("مرحبا Bob".to_string());
+
+        let mut components = vec![object(
+            "NSMutableString",
+            OutputData::String("مرحبا Bob".into()),
+        )];
+        components.extend(attributed_range(
+            6,
+            "__kIMMessagePartAttributeName",
+            object("NSNumber", OutputData::SignedInteger(0)),
+        ));
+        components.extend(attributed_range(
+            3,
+            "__kIMMentionConfirmedMention",
+            object("NSString", OutputData::String("+15558675309".into())),
+        ));
+        m.components = Some(components);
+
+        assert_eq!(
+            parse_body_typedstream(&m).unwrap(),
+            vec![BubbleComponent::Text(vec![
+                TextAttributes::new(0, 11, TextEffect::Default),
+                TextAttributes::new(11, 14, TextEffect::Mention("+15558675309")),
+            ])]
+        );
+    }
 }
 
 #[cfg(test)]
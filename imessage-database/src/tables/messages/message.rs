@@ -5,12 +5,16 @@
 use std::{collections::HashMap, io::Read};
 
 use chrono::{offset::Local, DateTime};
+use log::debug;
+
 use plist::Value;
 use rusqlite::{blob::Blob, Connection, Error, Result, Row, Statement};
 
 use crate::{
     error::{message::MessageError, table::TableError},
     message_types::{
+        app::AppMessage,
+        apple_pay::ApplePayMessage,
         edited::{EditStatus, EditedMessage},
         expressives::{BubbleEffect, Expressive, ScreenEffect},
         variants::{Announcement, BalloonProvider, CustomBalloon, Tapback, Variant},
@@ -21,21 +25,34 @@ use crate::{
             models::{BubbleComponent, Service},
         },
         table::{
-            Cacheable, Diagnostic, Table, ATTRIBUTED_BODY, CHAT_MESSAGE_JOIN, MESSAGE,
+            Cacheable, Diagnostic, Table, ATTRIBUTED_BODY, CHAT, CHAT_MESSAGE_JOIN, MESSAGE,
             MESSAGE_ATTACHMENT_JOIN, MESSAGE_PAYLOAD, MESSAGE_SUMMARY_INFO, RECENTLY_DELETED,
         },
     },
     util::{
+        attributed_body::AttributedBody,
         dates::{get_local_time, readable_diff},
-        output::{done_processing, processing},
+        plist::parse_plist,
         query_context::QueryContext,
         streamtyped,
-        typedstream::{models::Archivable, parser::TypedStreamReader},
+        typedstream::{
+            attributes::AUDIO_TRANSCRIPTION_ATTRIBUTE, models::Archivable,
+            parser::TypedStreamReader,
+        },
     },
 };
 
 /// The required columns, interpolated into the most recent schema due to performance considerations
-const COLS: &str = "rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, chat_id";
+const COLS: &str = "rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, is_audio_message, expire_state, chat_id";
+
+/// The [`expire_state`](Message::expire_state) of an audio message that the recipient kept
+const EXPIRE_STATE_KEPT: i32 = 3;
+
+/// The service of a message, falling back to the service of its chat when the message does not have one
+///
+/// Queries that use this must alias the message table as `m` and the chat message join table as `c`.
+pub(crate) const MESSAGE_SERVICE: &str =
+    "COALESCE(m.service, (SELECT ch.service_name FROM chat ch WHERE ch.ROWID = c.chat_id))";
 
 /// Represents a single row in the `message` table.
 #[derive(Debug)]
@@ -88,6 +105,10 @@ pub struct Message {
     pub thread_originator_part: Option<String>,
     /// The date the message was most recently edited
     pub date_edited: i64,
+    /// `true` if the message is an audio message recorded in Messages, else `false`
+    pub is_audio_message: bool,
+    /// Intermediate data for determining if an audio message expires after it is played
+    pub expire_state: i32,
     /// If present, this is the emoji associated with a custom emoji tapback
     pub associated_message_emoji: Option<String>,
     /// The [`identifier`](crate::tables::chat::Chat::chat_identifier) of the chat the message belongs to
@@ -99,11 +120,24 @@ pub struct Message {
     /// The number of replies to the message
     pub num_replies: i32,
     /// The components of the message body, parsed by [`TypedStreamReader`]
-    pub components: Option<Vec<Archivable>>,
+    pub components: Option<Vec<Archivable<'static>>>,
     /// The components of the message that may or may not have been edited or unsent
     pub edited_parts: Option<EditedMessage>,
 }
 
+/// Diagnostic data about the `message` table
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MessageDiagnostic {
+    /// The number of rows in the table
+    pub total_messages: usize,
+    /// The number of messages that do not belong to any chat
+    pub messages_without_chat: usize,
+    /// The number of messages that belong to more than one chat
+    pub messages_in_multiple_chats: usize,
+    /// The number of messages joined to a chat that no longer exists
+    pub messages_in_missing_chats: usize,
+}
+
 impl Table for Message {
     fn from_row(row: &Row) -> Result<Message> {
         Ok(Message {
@@ -132,6 +166,8 @@ impl Table for Message {
             thread_originator_guid: row.get("thread_originator_guid").unwrap_or(None),
             thread_originator_part: row.get("thread_originator_part").unwrap_or(None),
             date_edited: row.get("date_edited").unwrap_or(0),
+            is_audio_message: row.get("is_audio_message").unwrap_or(false),
+            expire_state: row.get("expire_state").unwrap_or(0),
             associated_message_emoji: row.get("associated_message_emoji").unwrap_or(None),
             chat_id: row.get("chat_id").unwrap_or(None),
             num_attachments: row.get("num_attachments")?,
@@ -203,7 +239,9 @@ impl Table for Message {
 }
 
 impl Diagnostic for Message {
-    /// Emit diagnostic data for the Messages table
+    type T = MessageDiagnostic;
+
+    /// Collect diagnostic data for the Messages table
     ///
     /// # Example:
     ///
@@ -214,10 +252,9 @@ impl Diagnostic for Message {
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
-    /// Message::run_diagnostic(&conn);
+    /// let diagnostic = Message::run_diagnostic(&conn);
     /// ```
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
-        processing();
+    fn run_diagnostic(db: &Connection) -> Result<MessageDiagnostic, TableError> {
         let mut messages_without_chat = db
             .prepare(&format!(
                 "
@@ -234,7 +271,7 @@ impl Diagnostic for Message {
             ))
             .map_err(TableError::Messages)?;
 
-        let num_dangling: i32 = messages_without_chat
+        let num_dangling: usize = messages_without_chat
             .query_row([], |r| r.get(0))
             .unwrap_or(0);
 
@@ -255,7 +292,7 @@ impl Diagnostic for Message {
             ))
             .map_err(TableError::Messages)?;
 
-        let messages_in_more_than_one_chat: i32 = messages_in_more_than_one_chat_q
+        let messages_in_more_than_one_chat: usize = messages_in_more_than_one_chat_q
             .query_row([], |r| r.get(0))
             .unwrap_or(0);
 
@@ -270,21 +307,32 @@ impl Diagnostic for Message {
             ))
             .map_err(TableError::Messages)?;
 
-        let total_messages: i64 = messages_count.query_row([], |r| r.get(0)).unwrap_or(0);
+        let total_messages: usize = messages_count.query_row([], |r| r.get(0)).unwrap_or(0);
+
+        let mut messages_in_missing_chats_q = db
+            .prepare(&format!(
+                "
+            SELECT
+                COUNT(DISTINCT j.message_id)
+            FROM
+                {CHAT_MESSAGE_JOIN} as j
+                LEFT JOIN {CHAT} as c ON c.ROWID = j.chat_id
+            WHERE
+                c.ROWID IS NULL
+            "
+            ))
+            .map_err(TableError::Messages)?;
 
-        done_processing();
+        let messages_in_missing_chats: usize = messages_in_missing_chats_q
+            .query_row([], |r| r.get(0))
+            .unwrap_or(0);
 
-        println!("Message diagnostic data:");
-        println!("    Total messages: {total_messages}");
-        if num_dangling > 0 {
-            println!("    Messages not associated with a chat: {num_dangling}");
-        }
-        if messages_in_more_than_one_chat > 0 {
-            println!(
-                "    Messages belonging to more than one chat: {messages_in_more_than_one_chat}"
-            );
-        }
-        Ok(())
+        Ok(MessageDiagnostic {
+            total_messages,
+            messages_without_chat: num_dangling,
+            messages_in_multiple_chats: messages_in_more_than_one_chat,
+            messages_in_missing_chats,
+        })
     }
 }
 
@@ -366,20 +414,45 @@ impl Message {
         if let Some(body) = self.attributed_body(db) {
             // Attempt to deserialize the typedstream data
             let mut typedstream = TypedStreamReader::from(&body);
-            self.components = typedstream.parse().ok();
+            let components = match typedstream.parse() {
+                Ok(components) => Some(components),
+                // Salvage what we can from corrupt data before falling back to the legacy parser
+                Err(why) => {
+                    debug!("Unable to parse the body of message {}: {why}", self.guid);
+                    TypedStreamReader::from(&body).parse_with_recovery().ok()
+                }
+            };
+            // The components borrow from the body, which is not kept
+            self.components =
+                components.map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
             // If we deserialize the typedstream, use that data
-            self.text = self
+            let parsed = self
                 .components
                 .as_ref()
                 .and_then(|items| items.first())
                 .and_then(|item| item.as_nsstring())
-                .map(String::from);
+                .map(String::from)
+                // Some bodies are keyed archives instead of `typedstream` data
+                .or_else(|| {
+                    AttributedBody::from_keyed_archive(&body)
+                        .ok()
+                        .map(|parsed| parsed.text)
+                });
 
             // If the above parsing failed, fall back to the legacy parser instead
-            if self.text.is_none() {
-                self.text =
-                    Some(streamtyped::parse(body).map_err(MessageError::StreamTypedParseError)?);
+            match parsed {
+                Some(text) => self.text = Some(text),
+                None => match streamtyped::parse(body) {
+                    Ok(text) => self.text = Some(text),
+                    // Malformed data keeps the plain `text` column, if there is one
+                    Err(why) if self.text.is_none() => {
+                        return Err(MessageError::StreamTypedParseError(why))
+                    }
+                    Err(why) => {
+                        debug!("Using the text column of message {}: {why}", self.guid);
+                    }
+                },
             }
         }
 
@@ -389,7 +462,14 @@ impl Message {
             .then(|| self.message_summary_info(db))
             .flatten()
             .as_ref()
-            .and_then(|payload| EditedMessage::from_map(payload).ok());
+            .and_then(|payload| match EditedMessage::from_map(payload) {
+                Ok(edited) => Some(edited),
+                Err(why) => {
+                    debug!("Unable to parse the edits of message {}: {why}", self.guid);
+                    None
+                }
+            });
+        self.infer_unsent();
 
         if let Some(t) = &self.text {
             Ok(t)
@@ -398,6 +478,20 @@ impl Message {
         }
     }
 
+    /// Mark an edited message that has nothing left to show as unsent
+    ///
+    /// Unsending a message removes its body and attachments, but the `message_summary_info`
+    /// that lists the unsent parts is missing or unreadable for some messages.
+    fn infer_unsent(&mut self) {
+        if self.edited_parts.is_none()
+            && self.is_edited()
+            && self.text.is_none()
+            && !self.has_attachments()
+        {
+            self.edited_parts = Some(EditedMessage::unsent());
+        }
+    }
+
     /// Get a vector of a message body's components. If the text has not been captured with [`Self::generate_text()`], the vector will be empty.
     ///
     /// # Parsing
@@ -508,7 +602,26 @@ impl Message {
 
     /// `true` if the message is an [`Announcement`], else `false`
     pub fn is_announcement(&self) -> bool {
-        self.group_title.is_some() || self.group_action_type != 0 || self.is_fully_unsent()
+        self.group_title.is_some()
+            || self.group_action_type != 0
+            || self.is_group_event()
+            || self.is_system_event()
+            || self.is_fully_unsent()
+    }
+
+    /// `true` if the message records a change to the members of a group, else `false`
+    ///
+    /// Members joining or leaving are stored with a `group_action_type` of `0`, so the `item_type` tells them apart from normal messages.
+    fn is_group_event(&self) -> bool {
+        matches!(self.item_type, 1 | 3)
+    }
+
+    /// `true` if the message is a system message that is not tied to a group, else `false`
+    ///
+    /// Messages shows these as lines in the conversation, like someone keeping an audio message. Item types
+    /// above [`is_shareplay()`](Self::is_shareplay) are not understood yet, but are still system messages.
+    fn is_system_event(&self) -> bool {
+        self.item_type == 5 || self.item_type > 6
     }
 
     /// `true` if the message is a [`Tapback`] to another message, else `false`
@@ -528,6 +641,8 @@ impl Message {
     }
 
     /// `true` if the message has a URL preview, else `false`
+    ///
+    /// If the preview cannot be read, the link's address is still in the message's `text`.
     pub fn is_url(&self) -> bool {
         matches!(self.variant(), Variant::App(CustomBalloon::URL))
     }
@@ -537,6 +652,11 @@ impl Message {
         matches!(self.variant(), Variant::App(CustomBalloon::Handwriting))
     }
 
+    /// `true` if the message is a Digital Touch sketch or effect, else `false`
+    pub fn is_digital_touch(&self) -> bool {
+        matches!(self.variant(), Variant::App(CustomBalloon::DigitalTouch))
+    }
+
     /// `true` if the message was [`Edited`](crate::message_types::edited), else `false`
     pub fn is_edited(&self) -> bool {
         self.date_edited != 0
@@ -561,6 +681,29 @@ impl Message {
         })
     }
 
+    /// `true` if the message is an audio message that is deleted after it is played, else `false`
+    ///
+    /// Audio messages expire two minutes after they are played unless the recipient keeps them,
+    /// so their files may no longer exist.
+    pub fn is_expiring_audio(&self) -> bool {
+        self.is_audio_message && self.expire_state != 0 && self.expire_state != EXPIRE_STATE_KEPT
+    }
+
+    /// Get the text Apple transcribed from an audio message, if it was stored with the message
+    ///
+    /// The transcription is an attribute of the range of the body that holds the audio attachment,
+    /// so it is only available after the body is read with [`Self::generate_text()`].
+    pub fn audio_transcription(&self) -> Option<&str> {
+        let components = self.components.as_ref()?;
+        components
+            .iter()
+            .position(|item| item.as_nsstring() == Some(AUDIO_TRANSCRIPTION_ATTRIBUTE))
+            .and_then(|idx| components.get(idx + 1))
+            .and_then(Archivable::as_nsstring)
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+    }
+
     /// `true` if the message has attachments, else `false`
     pub fn has_attachments(&self) -> bool {
         self.num_attachments > 0
@@ -577,8 +720,11 @@ impl Message {
     }
 
     /// `true` if the message was sent by the database owner, else `false`
+    ///
+    /// Location sharing events are sent by the database owner if they are shared with `other_handle`.
+    /// Group events also use `other_handle`, for the participant that was added or removed, so it does not apply to them.
     pub fn is_from_me(&self) -> bool {
-        self.is_from_me || self.other_handle != 0 && !self.share_direction
+        self.is_from_me || self.item_type == 4 && self.other_handle != 0 && !self.share_direction
     }
 
     /// `true` if the message indicates a user started sharing their location, else `false`
@@ -634,9 +780,9 @@ impl Message {
     /// ```
     pub fn get_count(db: &Connection, context: &QueryContext) -> Result<u64, TableError> {
         let mut statement = if context.has_filters() {
+            let filters = context.generate_filter_statement("m", "c.chat_id", MESSAGE_SERVICE);
             db.prepare(&format!(
-                "SELECT COUNT(*) FROM {MESSAGE} as m {}",
-                context.generate_filter_statement("m.date")
+                "SELECT COUNT(*) FROM {MESSAGE} as m LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id {filters}"
             ))
             .map_err(TableError::Messages)?
         } else {
@@ -671,7 +817,7 @@ impl Message {
             return Self::get(db);
         }
 
-        let filters = context.generate_filter_statement("m.date");
+        let filters = context.generate_filter_statement("m", "c.chat_id", MESSAGE_SERVICE);
 
         // If database has `thread_originator_guid`, we can parse replies, otherwise default to 0
         Ok(db.prepare(&format!(
@@ -706,8 +852,10 @@ impl Message {
             )).map_err(TableError::Messages)?))
     }
 
+    /// Get the index of the message part and the GUID of the message a tapback or sticker is associated with
+    ///
     /// See [`Tapback`] for details on this data.
-    fn clean_associated_guid(&self) -> Option<(usize, &str)> {
+    pub fn clean_associated_guid(&self) -> Option<(usize, &str)> {
         if let Some(guid) = &self.associated_message_guid {
             if guid.starts_with("p:") {
                 let mut split = guid.split('/');
@@ -732,6 +880,32 @@ impl Message {
         }
     }
 
+    /// Get the emoji used in a custom emoji tapback
+    ///
+    /// Newer databases store the emoji in `associated_message_emoji`; when that column is missing or empty,
+    /// the emoji is parsed from the message text, i.e. `Reacted ☕️ to “Hello”` or `Removed ☕️ from “Hello”`
+    fn tapback_emoji(&self) -> Option<&str> {
+        if let Some(emoji) = self
+            .associated_message_emoji
+            .as_deref()
+            .filter(|emoji| !emoji.is_empty())
+        {
+            return Some(emoji);
+        }
+
+        let text = self.text.as_deref()?;
+        let emoji = if let Some(rest) = text.strip_prefix("Reacted ") {
+            rest.split_once(" to ")?.0
+        } else if let Some(rest) = text.strip_prefix("Removed ") {
+            rest.split_once(" from ")?.0
+        } else {
+            return None;
+        };
+
+        // Guard against text that does not describe an emoji, i.e. `Reacted with a sticker to`
+        (!emoji.is_empty() && !emoji.contains(char::is_alphanumeric)).then_some(emoji)
+    }
+
     /// Build a `HashMap` of message component index to messages that react to that component
     pub fn get_tapbacks(
         &self,
@@ -819,6 +993,37 @@ impl Message {
         Ok(out_h)
     }
 
+    /// Get the message that started the thread a reply belongs to
+    ///
+    /// Returns `None` if the message is not a reply, or if the message it replied to is no longer in the database.
+    pub fn get_thread_originator(&self, db: &Connection) -> Result<Option<Self>, TableError> {
+        let Some(guid) = &self.thread_originator_guid else {
+            return Ok(None);
+        };
+
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT 
+                     *, 
+                     c.chat_id, 
+                     (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
+                     (SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid) as num_replies
+                 FROM 
+                     message as m 
+                     LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id 
+                 WHERE m.guid = ?1
+                 LIMIT 1;
+                "
+            ))
+            .map_err(TableError::Messages)?;
+
+        let mut iter = statement
+            .query_map([guid], |row| Ok(Message::from_row(row)))
+            .map_err(TableError::Messages)?;
+
+        iter.next().map(Message::extract).transpose()
+    }
+
     /// Parse the App's Bundle ID out of the Balloon's Bundle ID
     ///
     /// For example, a Bundle ID like `com.apple.messages.MSMessageExtensionBalloonPlugin:0000000000:com.apple.SafetyMonitorApp.SafetyMonitorMessages`
@@ -856,6 +1061,9 @@ impl Message {
                         "com.apple.Handwriting.HandwritingProvider" => {
                             Variant::App(CustomBalloon::Handwriting)
                         }
+                        "com.apple.DigitalTouchBalloonProvider" => {
+                            Variant::App(CustomBalloon::DigitalTouch)
+                        }
                         "com.apple.PassbookUIService.PeerPaymentMessagesExtension" => {
                             Variant::App(CustomBalloon::ApplePay)
                         }
@@ -888,7 +1096,7 @@ impl Message {
                 2006 => Variant::Tapback(
                     self.tapback_index(),
                     true,
-                    Tapback::Emoji(self.associated_message_emoji.as_deref()),
+                    Tapback::Emoji(self.tapback_emoji()),
                 ),
                 2007 => Variant::Sticker(self.tapback_index()),
                 3000 => Variant::Tapback(self.tapback_index(), false, Tapback::Loved),
@@ -900,7 +1108,7 @@ impl Message {
                 3006 => Variant::Tapback(
                     self.tapback_index(),
                     false,
-                    Tapback::Emoji(self.associated_message_emoji.as_deref()),
+                    Tapback::Emoji(self.tapback_emoji()),
                 ),
                 3007 => Variant::Sticker(self.tapback_index()),
 
@@ -927,10 +1135,17 @@ impl Message {
             return Some(Announcement::FullyUnsent);
         }
 
-        return match &self.group_action_type {
-            0 => None,
-            1 => Some(Announcement::PhotoChange),
-            other => Some(Announcement::Unknown(other)),
+        return match (self.item_type, &self.group_action_type) {
+            // The participant that was added or removed is stored as the other handle
+            (1, 0) => Some(Announcement::ParticipantAdded(self.other_handle)),
+            (1, 1) => Some(Announcement::ParticipantRemoved(self.other_handle)),
+            (3, 0) => Some(Announcement::ParticipantLeft),
+            (3, 2) => Some(Announcement::PhotoRemoved),
+            (5, _) => Some(Announcement::AudioMessageKept),
+            (item_type, 0) if item_type > 6 => Some(Announcement::UnknownEvent(item_type)),
+            (_, 0) => None,
+            (_, 1) => Some(Announcement::PhotoChange),
+            (_, other) => Some(Announcement::Unknown(other)),
         };
     }
 
@@ -958,6 +1173,22 @@ impl Message {
         }
     }
 
+    /// Describe the Apple Pay transaction in a message, i.e. `Sent $25`
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed.
+    ///
+    /// Returns `None` if the message is not an Apple Pay message or its payload cannot be read.
+    pub fn apple_pay_summary(&self, db: &Connection) -> Option<String> {
+        if !matches!(self.variant(), Variant::App(CustomBalloon::ApplePay)) {
+            return None;
+        }
+        let payload = self.payload_data(db)?;
+        let parsed = parse_plist(&payload).ok()?;
+        let balloon = AppMessage::from_map(&parsed).ok()?;
+        ApplePayMessage::from_balloon(&balloon).map(|payment| payment.to_string())
+    }
+
     /// Get a message's plist from the `payload_data` BLOB column
     ///
     /// Calling this hits the database, so it is expensive and should
@@ -973,7 +1204,8 @@ impl Message {
     /// Calling this hits the database, so it is expensive and should
     /// only get invoked when needed.
     ///
-    /// This column contains data used by [`HandwrittenMessage`](crate::message_types::handwriting::HandwrittenMessage)s.
+    /// This column contains data used by [`HandwrittenMessage`](crate::message_types::handwriting::HandwrittenMessage)s
+    /// and the strokes of Digital Touch messages.
     pub fn raw_payload_data(&self, db: &Connection) -> Option<Vec<u8>> {
         let mut buf = Vec::new();
         self.get_blob(db, MESSAGE_PAYLOAD)?
@@ -1058,11 +1290,18 @@ mod tests {
         message_types::{
             edited::{EditStatus, EditedMessage, EditedMessagePart},
             expressives,
-            variants::{CustomBalloon, Variant},
+            variants::{Announcement, CustomBalloon, Tapback, Variant},
+        },
+        tables::{
+            messages::{message::MessageDiagnostic, Message},
+            table::Diagnostic,
+        },
+        util::{
+            dates::get_offset,
+            typedstream::models::{Archivable, Class, OutputData},
         },
-        tables::messages::Message,
-        util::dates::get_offset,
     };
+    use rusqlite::Connection;
 
     fn blank() -> Message {
         Message {
@@ -1091,6 +1330,8 @@ mod tests {
             thread_originator_guid: None,
             thread_originator_part: None,
             date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
             associated_message_emoji: None,
             chat_id: None,
             num_attachments: 0,
@@ -1101,11 +1342,109 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_run_diagnostic() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY, date INTEGER);",
+            "CREATE TABLE chat (ROWID INTEGER PRIMARY KEY);",
+            "CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);",
+            "INSERT INTO message VALUES (1, 0), (2, 0), (3, 0), (4, 0);",
+            "INSERT INTO chat VALUES (1), (2);",
+            // Message 2 is in two chats, message 3 is in a deleted chat, and message 4 is in no chat
+            "INSERT INTO chat_message_join VALUES (1, 1), (1, 2), (2, 2), (3, 3);",
+        ))
+        .unwrap();
+
+        let diagnostic = Message::run_diagnostic(&db).unwrap();
+        assert_eq!(
+            diagnostic,
+            MessageDiagnostic {
+                total_messages: 4,
+                messages_without_chat: 1,
+                messages_in_multiple_chats: 1,
+                messages_in_missing_chats: 1,
+            }
+        );
+    }
     #[test]
     fn can_gen_message() {
         blank();
     }
 
+    #[test]
+    fn can_get_expiring_audio() {
+        let mut message = blank();
+        message.is_audio_message = true;
+        message.expire_state = 1;
+        assert!(message.is_expiring_audio());
+
+        // Kept audio messages do not expire
+        message.expire_state = 3;
+        assert!(!message.is_expiring_audio());
+
+        // Other messages do not expire
+        message.is_audio_message = false;
+        message.expire_state = 1;
+        assert!(!message.is_expiring_audio());
+    }
+
+    fn nsstring(text: &str) -> Archivable {
+        Archivable::Object(
+            Class {
+                name: "NSString".into(),
+                version: 1,
+            },
+            vec![OutputData::String(text.into())],
+        )
+    }
+
+    #[test]
+    fn can_get_audio_transcription() {
+        let mut message = blank();
+        message.is_audio_message = true;
+        message.components = Some(vec![
+            nsstring("\u{FFFC}"),
+            Archivable::Data(vec![
+                OutputData::SignedInteger(1),
+                OutputData::UnsignedInteger(1),
+            ]),
+            Archivable::Object(
+                Class {
+                    name: "NSDictionary".into(),
+                    version: 0,
+                },
+                vec![OutputData::SignedInteger(2)],
+            ),
+            nsstring("__kIMFileTransferGUIDAttributeName"),
+            nsstring("at_0_F0668F79-20C2-49C9-A87F-1B007ABB0CED"),
+            nsstring("IMAudioTranscription"),
+            nsstring("Running ten minutes late "),
+        ]);
+
+        assert_eq!(
+            message.audio_transcription(),
+            Some("Running ten minutes late")
+        );
+    }
+
+    #[test]
+    fn cant_get_audio_transcription_missing() {
+        let mut message = blank();
+        assert_eq!(message.audio_transcription(), None);
+
+        message.components = Some(vec![nsstring("\u{FFFC}")]);
+        assert_eq!(message.audio_transcription(), None);
+
+        // A transcription with no text is not shown
+        message.components = Some(vec![
+            nsstring("\u{FFFC}"),
+            nsstring("IMAudioTranscription"),
+            nsstring(" "),
+        ]);
+        assert_eq!(message.audio_transcription(), None);
+    }
+
     #[test]
     fn can_get_time_date_read_after_date() {
         // Get offset
@@ -1196,6 +1535,116 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_get_group_events() {
+        let mut m = blank();
+        assert!(!m.is_announcement());
+        assert_eq!(m.get_announcement(), None);
+
+        m.item_type = 1;
+        m.other_handle = 3;
+        assert!(m.is_announcement());
+        assert_eq!(
+            m.get_announcement(),
+            Some(Announcement::ParticipantAdded(3))
+        );
+        assert!(!m.is_from_me());
+
+        m.group_action_type = 1;
+        assert_eq!(
+            m.get_announcement(),
+            Some(Announcement::ParticipantRemoved(3))
+        );
+
+        m.item_type = 3;
+        m.group_action_type = 0;
+        assert!(m.is_announcement());
+        assert_eq!(m.get_announcement(), Some(Announcement::ParticipantLeft));
+
+        m.group_action_type = 1;
+        assert_eq!(m.get_announcement(), Some(Announcement::PhotoChange));
+
+        m.group_action_type = 2;
+        assert_eq!(m.get_announcement(), Some(Announcement::PhotoRemoved));
+    }
+
+    #[test]
+    fn can_get_system_events() {
+        let mut m = blank();
+        m.item_type = 5;
+        assert!(m.is_announcement());
+        assert_eq!(m.get_announcement(), Some(Announcement::AudioMessageKept));
+
+        m.item_type = 9;
+        assert!(m.is_announcement());
+        assert_eq!(m.get_announcement(), Some(Announcement::UnknownEvent(9)));
+
+        // SharePlay messages have their own variant
+        m.item_type = 6;
+        assert!(!m.is_announcement());
+        assert_eq!(m.get_announcement(), None);
+    }
+
+    #[test]
+    fn can_get_name_change() {
+        let mut m = blank();
+        m.item_type = 2;
+        m.group_title = Some("Ski Trip".to_string());
+        assert!(m.is_announcement());
+        assert_eq!(
+            m.get_announcement(),
+            Some(Announcement::NameChange("Ski Trip"))
+        );
+    }
+
+    #[test]
+    fn can_get_tapback_emoji() {
+        let mut m = blank();
+        m.associated_message_type = Some(2006);
+        m.associated_message_emoji = Some("☕️".to_string());
+        assert!(matches!(
+            m.variant(),
+            Variant::Tapback(0, true, Tapback::Emoji(Some("☕️")))
+        ));
+    }
+
+    #[test]
+    fn can_get_tapback_emoji_from_text() {
+        let mut m = blank();
+        m.associated_message_type = Some(2006);
+        m.text = Some("Reacted 🎉 to “Hello world”".to_string());
+        assert!(matches!(
+            m.variant(),
+            Variant::Tapback(0, true, Tapback::Emoji(Some("🎉")))
+        ));
+
+        m.associated_message_type = Some(3006);
+        m.text = Some("Removed 🎉 from “Hello world”".to_string());
+        assert!(matches!(
+            m.variant(),
+            Variant::Tapback(0, false, Tapback::Emoji(Some("🎉")))
+        ));
+    }
+
+    #[test]
+    fn cant_get_tapback_emoji_from_other_text() {
+        let mut m = blank();
+        m.associated_message_type = Some(2006);
+        m.text = Some("Reacted with a sticker to “Hello world”".to_string());
+        assert!(matches!(
+            m.variant(),
+            Variant::Tapback(0, true, Tapback::Emoji(None))
+        ));
+    }
+
+    #[test]
+    fn can_get_digital_touch() {
+        let mut m = blank();
+        m.balloon_bundle_id = Some("com.apple.DigitalTouchBalloonProvider".to_owned());
+        assert!(m.is_digital_touch());
+        assert!(!m.is_handwriting());
+    }
+
     #[test]
     fn can_get_balloon_bundle_id_apple() {
         let mut m = blank();
@@ -1311,6 +1760,34 @@ mod tests {
         assert!(m.is_fully_unsent());
     }
 
+    #[test]
+    fn can_infer_unsent_without_summary() {
+        let mut m = blank();
+        m.date_edited = 674530231992568192;
+        m.infer_unsent();
+
+        assert!(m.is_fully_unsent());
+        assert!(m.is_announcement());
+    }
+
+    #[test]
+    fn cant_infer_unsent_with_text() {
+        let mut m = blank();
+        m.date_edited = 674530231992568192;
+        m.text = Some("Hello".to_string());
+        m.infer_unsent();
+
+        assert!(!m.is_fully_unsent());
+    }
+
+    #[test]
+    fn cant_infer_unsent_unedited() {
+        let mut m = blank();
+        m.infer_unsent();
+
+        assert!(!m.is_fully_unsent());
+    }
+
     #[test]
     fn can_get_fully_unsent_false() {
         let mut m = blank();
@@ -36,10 +36,12 @@ pub trait Deduplicate {
     fn dedupe(duplicated_data: &HashMap<i32, Self::T>) -> HashMap<i32, i32>;
 }
 
-/// Defines behavior for printing diagnostic information for a table
+/// Defines behavior for collecting diagnostic information about a table
 pub trait Diagnostic {
-    /// Emit diagnostic data about the table to `stdout`
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError>;
+    /// The diagnostic data collected from the table
+    type T;
+    /// Collect diagnostic data about the table
+    fn run_diagnostic(db: &Connection) -> Result<Self::T, TableError>;
 }
 
 /// Get a connection to the iMessage `SQLite` database
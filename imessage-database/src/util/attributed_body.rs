@@ -0,0 +1,397 @@
+/*!
+ Logic used to decode an archived `NSAttributedString`, like the `attributedBody` column of the `message` table.
+
+ Most bodies are archived by `NSArchiver` as [`typedstream`](crate::util::typedstream) data, but some are archived
+ by `NSKeyedArchiver` as plists instead. [`AttributedBody::parse()`] reads either format.
+
+ ```
+ use imessage_database::util::attributed_body::AttributedBody;
+
+ let bytes: Vec<u8> = vec![]; // Example archive
+ match AttributedBody::parse(&bytes) {
+     Ok(body) => println!("{}", body.text),
+     Err(why) => eprintln!("Unable to decode: {why}"),
+ }
+ ```
+*/
+
+use std::ops::Range;
+
+use plist::Value;
+
+use crate::{
+    error::{
+        attributed_body::AttributedBodyError, plist::PlistParseError, typedstream::TypedStreamError,
+    },
+    util::{
+        keyed_archive::{is_binary_plist, KeyedArchive},
+        typedstream::{
+            self,
+            attributes::{utf16_positions, AttributeRun, AttributeValue},
+        },
+    },
+};
+
+/// The text and attributes of an archived `NSAttributedString`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributedBody {
+    /// The plain text of the string
+    pub text: String,
+    /// The attributes applied to ranges of the text, in order of appearance
+    pub runs: Vec<AttributeRun>,
+}
+
+impl AttributedBody {
+    /// Decode an archived `NSAttributedString`, whether it is `typedstream` or `NSKeyedArchiver` data
+    ///
+    /// Binary plists are read as keyed archives. Anything else is read as a `typedstream`, falling back to a
+    /// keyed archive in case it is an XML plist. If neither works, the `typedstream` error is returned.
+    pub fn parse(bytes: &[u8]) -> Result<Self, AttributedBodyError> {
+        if is_binary_plist(bytes) {
+            return Self::from_keyed_archive(bytes).map_err(AttributedBodyError::KeyedArchiveError);
+        }
+        Self::from_typedstream(bytes).or_else(|why| {
+            Self::from_keyed_archive(bytes).map_err(|_| AttributedBodyError::TypedStreamError(why))
+        })
+    }
+
+    /// Decode an `NSAttributedString` archived as a `typedstream`
+    pub fn from_typedstream(bytes: &[u8]) -> Result<Self, TypedStreamError> {
+        let parsed = typedstream::parse(bytes)?;
+        Ok(Self {
+            text: parsed.text,
+            runs: parsed.runs,
+        })
+    }
+
+    /// Decode an `NSAttributedString` archived by `NSKeyedArchiver`
+    pub fn from_keyed_archive(bytes: &[u8]) -> Result<Self, PlistParseError> {
+        let archive = KeyedArchive::from_bytes(bytes)?;
+        let root = archive.root()?;
+
+        let text = archive
+            .string(archive.get(root, "NSString")?)
+            .ok_or_else(|| {
+                PlistParseError::InvalidType("NSString".to_string(), "string".to_string())
+            })?
+            .to_string();
+
+        let runs = match archive.get(root, "NSAttributes") {
+            Ok(attributes) => read_keyed_runs(&archive, root, attributes, &text)?,
+            // Strings without any attributes do not store them
+            Err(_) => vec![],
+        };
+
+        Ok(Self { text, runs })
+    }
+}
+
+/// Read the attribute runs of a keyed `NSAttributedString`
+///
+/// If the attributes change across the string, `NSAttributes` is an array of dictionaries and `NSAttributeInfo`
+/// stores the length of each range, in UTF-16 code units, followed by the index of its dictionary. Otherwise,
+/// `NSAttributes` is a single dictionary that applies to the whole string.
+fn read_keyed_runs(
+    archive: &KeyedArchive,
+    root: &Value,
+    attributes: &Value,
+    text: &str,
+) -> Result<Vec<AttributeRun>, PlistParseError> {
+    let positions = utf16_positions(text);
+    let char_count = text.chars().count();
+    let to_char = |unit: usize| {
+        positions
+            .get(unit)
+            .map_or(char_count, |(char_idx, _)| *char_idx)
+    };
+
+    let info = archive
+        .get(root, "NSAttributeInfo")
+        .ok()
+        .and_then(|info| archive.data(info));
+    let ranges: Vec<(Range<usize>, &Value)> = match info {
+        Some(info) => {
+            let dictionaries = archive.array(attributes)?;
+            let mut start: usize = 0;
+            read_varints(info)
+                .chunks_exact(2)
+                .filter_map(|pair| {
+                    let end = start.saturating_add(pair[0]);
+                    let range = to_char(start)..to_char(end);
+                    start = end;
+                    Some((range, *dictionaries.get(pair[1])?))
+                })
+                .collect()
+        }
+        None => vec![(0..char_count, attributes)],
+    };
+
+    let mut runs = vec![];
+    for (range, dictionary) in ranges {
+        for (name, value) in archive.dictionary(dictionary)? {
+            runs.push(AttributeRun {
+                range: range.clone(),
+                name: name.to_string(),
+                value: read_keyed_value(archive, value),
+            });
+        }
+    }
+    Ok(runs)
+}
+
+/// Read the unsigned variable length integers in `NSAttributeInfo`
+///
+/// Each byte stores 7 bits, least significant first, and has its high bit set if the next byte continues the number.
+fn read_varints(bytes: &[u8]) -> Vec<usize> {
+    let mut values = vec![];
+    let mut value: usize = 0;
+    let mut shift: u32 = 0;
+    for byte in bytes {
+        value |= usize::from(byte & 0x7F).checked_shl(shift).unwrap_or(0);
+        if byte & 0x80 == 0 {
+            values.push(value);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+        }
+    }
+    values
+}
+
+/// Read the value of an attribute from a keyed archive
+fn read_keyed_value(archive: &KeyedArchive, value: &Value) -> AttributeValue {
+    match value {
+        Value::String(text) => AttributeValue::String(text.to_string()),
+        Value::Integer(number) => match number.as_signed() {
+            Some(number) => AttributeValue::Number(number),
+            None => AttributeValue::Other("NSNumber".to_string()),
+        },
+        Value::Boolean(flag) => AttributeValue::Number(i64::from(*flag)),
+        Value::Real(_) => AttributeValue::Other("NSNumber".to_string()),
+        Value::Data(bytes) => AttributeValue::Data(bytes.to_vec()),
+        _ => match archive.class_name(value) {
+            // The address is stored as a string relative to an optional base
+            Some("NSURL") => match archive
+                .get(value, "NS.relative")
+                .ok()
+                .and_then(|url| archive.string(url))
+            {
+                Some(url) => AttributeValue::Url(url.to_string()),
+                None => AttributeValue::Other("NSURL".to_string()),
+            },
+            Some(class @ ("NSString" | "NSMutableString")) => match archive.string(value) {
+                Some(text) => AttributeValue::String(text.to_string()),
+                None => AttributeValue::Other(class.to_string()),
+            },
+            Some(class @ ("NSData" | "NSMutableData")) => match archive.data(value) {
+                Some(bytes) => AttributeValue::Data(bytes.to_vec()),
+                None => AttributeValue::Other(class.to_string()),
+            },
+            Some(class) => AttributeValue::Other(class.to_string()),
+            None => AttributeValue::Other(String::new()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::File, io::Read};
+
+    use plist::{Dictionary, Uid, Value};
+
+    use crate::util::{
+        attributed_body::{read_varints, AttributedBody},
+        typedstream::attributes::{
+            AttributeRun, AttributeValue, MENTION_ATTRIBUTE, MESSAGE_PART_ATTRIBUTE,
+        },
+    };
+
+    /// Build a pointer to an object in the archive
+    fn uid(idx: u64) -> Value {
+        Value::Uid(Uid::new(idx))
+    }
+
+    /// Build an archived object from its fields
+    fn object(fields: Vec<(&str, Value)>) -> Value {
+        let mut dict = Dictionary::new();
+        for (key, value) in fields {
+            dict.insert(key.to_string(), value);
+        }
+        Value::Dictionary(dict)
+    }
+
+    /// Build the class description of an archived object
+    fn class(name: &str) -> Value {
+        object(vec![
+            ("$classname", Value::String(name.to_string())),
+            (
+                "$classes",
+                Value::Array(vec![
+                    Value::String(name.to_string()),
+                    Value::String("NSObject".to_string()),
+                ]),
+            ),
+        ])
+    }
+
+    /// Write a binary `NSKeyedArchiver` plist with the first real object as its root
+    fn keyed_archive(objects: Vec<Value>) -> Vec<u8> {
+        let mut archive = vec![Value::String("$null".to_string())];
+        archive.extend(objects);
+        let plist = object(vec![
+            ("$archiver", Value::String("NSKeyedArchiver".to_string())),
+            ("$version", Value::Integer(100000_i64.into())),
+            ("$top", object(vec![("root", uid(1))])),
+            ("$objects", Value::Array(archive)),
+        ]);
+
+        let mut bytes = vec![];
+        plist.to_writer_binary(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn can_parse_keyed_archive_single_attributes() {
+        let bytes = keyed_archive(vec![
+            // 1
+            object(vec![
+                ("$class", uid(7)),
+                ("NSString", uid(2)),
+                ("NSAttributes", uid(4)),
+            ]),
+            // 2
+            object(vec![
+                ("$class", uid(3)),
+                ("NS.string", Value::String("Hi 😀".to_string())),
+            ]),
+            // 3
+            class("NSMutableString"),
+            // 4
+            object(vec![
+                ("$class", uid(6)),
+                ("NS.keys", Value::Array(vec![uid(5)])),
+                ("NS.objects", Value::Array(vec![uid(8)])),
+            ]),
+            // 5
+            Value::String(MESSAGE_PART_ATTRIBUTE.to_string()),
+            // 6
+            class("NSDictionary"),
+            // 7
+            class("NSMutableAttributedString"),
+            // 8
+            Value::Integer(0_i64.into()),
+        ]);
+
+        let result = AttributedBody::parse(&bytes).unwrap();
+
+        assert_eq!(result.text, "Hi 😀");
+        assert_eq!(
+            result.runs,
+            vec![AttributeRun {
+                range: 0..4,
+                name: MESSAGE_PART_ATTRIBUTE.to_string(),
+                value: AttributeValue::Number(0),
+            }]
+        );
+    }
+
+    #[test]
+    fn can_parse_keyed_archive_attribute_ranges() {
+        let bytes = keyed_archive(vec![
+            // 1
+            object(vec![
+                ("$class", uid(2)),
+                ("NSString", uid(3)),
+                ("NSAttributes", uid(4)),
+                ("NSAttributeInfo", uid(10)),
+            ]),
+            // 2
+            class("NSAttributedString"),
+            // 3
+            Value::String("Test Dad".to_string()),
+            // 4
+            object(vec![
+                ("$class", uid(5)),
+                ("NS.objects", Value::Array(vec![uid(6), uid(7)])),
+            ]),
+            // 5
+            class("NSArray"),
+            // 6
+            object(vec![
+                ("$class", uid(11)),
+                ("NS.keys", Value::Array(vec![uid(8)])),
+                ("NS.objects", Value::Array(vec![uid(12)])),
+            ]),
+            // 7
+            object(vec![
+                ("$class", uid(11)),
+                ("NS.keys", Value::Array(vec![uid(9), uid(8)])),
+                ("NS.objects", Value::Array(vec![uid(13), uid(12)])),
+            ]),
+            // 8
+            Value::String(MESSAGE_PART_ATTRIBUTE.to_string()),
+            // 9
+            Value::String(MENTION_ATTRIBUTE.to_string()),
+            // 10
+            Value::Data(vec![5, 0, 3, 1]),
+            // 11
+            class("NSDictionary"),
+            // 12
+            Value::Integer(0_i64.into()),
+            // 13
+            Value::String("+15558675309".to_string()),
+        ]);
+
+        let result = AttributedBody::parse(&bytes).unwrap();
+
+        assert_eq!(result.text, "Test Dad");
+        assert_eq!(
+            result.runs,
+            vec![
+                AttributeRun {
+                    range: 0..5,
+                    name: MESSAGE_PART_ATTRIBUTE.to_string(),
+                    value: AttributeValue::Number(0),
+                },
+                AttributeRun {
+                    range: 5..8,
+                    name: MENTION_ATTRIBUTE.to_string(),
+                    value: AttributeValue::String("+15558675309".to_string()),
+                },
+                AttributeRun {
+                    range: 5..8,
+                    name: MESSAGE_PART_ATTRIBUTE.to_string(),
+                    value: AttributeValue::Number(0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn can_parse_typedstream() {
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/Mention");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let result = AttributedBody::parse(&bytes).unwrap();
+
+        assert_eq!(result.text, "Test Dad ");
+        assert_eq!(result.runs.len(), 3);
+    }
+
+    #[test]
+    fn cant_parse_other_data() {
+        assert!(AttributedBody::parse(b"bplist00").is_err());
+        assert!(AttributedBody::parse(&[0x04, 0x0b, 0x84]).is_err());
+    }
+
+    #[test]
+    fn can_read_varints() {
+        assert_eq!(read_varints(&[5, 0, 0xAC, 0x02, 1]), vec![5, 0, 300, 1]);
+    }
+}
@@ -0,0 +1,213 @@
+/*!
+ Contains logic for finding files in an iOS or iPadOS backup made by Finder or iTunes.
+
+ Backups do not keep the folder structure of the device. Each file is stored under the SHA-1 hash of its domain and
+ path on the device, i.e. `HomeDomain-Library/SMS/sms.db`, in a folder named for the first two characters of the hash.
+ `Manifest.db` lists the hash of every file in the backup, so it is read first when it is available.
+
+ Read more [here](https://theapplewiki.com/index.php?title=ITunes_Backup).
+*/
+
+use std::{
+    fs::{metadata, read_dir},
+    path::{Path, PathBuf},
+};
+
+use plist::Value;
+use rusqlite::{Connection, OpenFlags};
+use sha1::{Digest, Sha1};
+
+use crate::tables::table::DEFAULT_PATH_IOS;
+
+/// The database that lists every file in a backup
+pub const MANIFEST_DB: &str = "Manifest.db";
+/// The property list that describes a backup, including whether it is encrypted
+pub const MANIFEST_PLIST: &str = "Manifest.plist";
+/// The domain of the Messages database
+pub const SMS_DOMAIN: &str = "HomeDomain";
+/// The path of the Messages database on the device, relative to its domain
+pub const SMS_DB_PATH: &str = "Library/SMS/sms.db";
+/// The domain of message attachments
+pub const ATTACHMENT_DOMAIN: &str = "MediaDomain";
+
+/// Determine if a directory is the root of a backup
+///
+/// Older backups may not include `Manifest.db`, so a directory that holds the Messages database is also a backup.
+pub fn is_backup(path: &Path) -> bool {
+    path.join(MANIFEST_DB).is_file() || path.join(DEFAULT_PATH_IOS).is_file()
+}
+
+/// Determine if a backup is encrypted
+///
+/// Encrypted backups can be read with the `encrypted-backups` feature, which decrypts them
+/// with [`decrypt_messages()`](crate::util::encrypted_backup::decrypt_messages).
+pub fn is_encrypted(backup: &Path) -> bool {
+    Value::from_file(backup.join(MANIFEST_PLIST))
+        .ok()
+        .and_then(|manifest| manifest.as_dictionary()?.get("IsEncrypted")?.as_boolean())
+        .unwrap_or(false)
+}
+
+/// Generate the name a file is stored under in a backup, given its domain and its path on the device
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::backup::file_id;
+///
+/// assert_eq!(
+///     file_id("HomeDomain", "Library/SMS/sms.db"),
+///     "3d0d7e5fb2ce288813306e4d4636395e047a3d28"
+/// );
+/// ```
+pub fn file_id(domain: &str, relative_path: &str) -> String {
+    format!(
+        "{:x}",
+        Sha1::digest(format!("{domain}-{relative_path}").as_bytes())
+    )
+}
+
+/// Get the path of a file in a backup, given its domain and its path on the device
+///
+/// The file is looked up in `Manifest.db`, falling back to [`file_id`] when the backup has no manifest
+/// or the manifest does not list the file. The returned path may not exist.
+pub fn backup_file(backup: &Path, domain: &str, relative_path: &str) -> Option<PathBuf> {
+    let file_id = manifest_file_id(backup, domain, relative_path)
+        .unwrap_or_else(|| file_id(domain, relative_path));
+    let directory = file_id.get(0..2)?;
+    Some(backup.join(directory).join(&file_id))
+}
+
+/// Get the path of the Messages database in a backup
+///
+/// # Example:
+///
+/// ```
+/// use std::path::Path;
+/// use imessage_database::util::backup::sms_db_path;
+///
+/// let path = sms_db_path(Path::new("/path/to/backup"));
+/// ```
+pub fn sms_db_path(backup: &Path) -> PathBuf {
+    backup_file(backup, SMS_DOMAIN, SMS_DB_PATH).unwrap_or_else(|| backup.join(DEFAULT_PATH_IOS))
+}
+
+/// Find the most recently updated backup in a folder of backups, i.e. `~/Library/Application Support/MobileSync/Backup`
+///
+/// Returns `None` if the folder does not hold any backups.
+pub fn latest_backup(backups: &Path) -> Option<PathBuf> {
+    read_dir(backups)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| is_backup(path))
+        .max_by_key(|path| {
+            metadata(path.join(MANIFEST_DB))
+                .or_else(|_| metadata(path))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+}
+
+/// Look up the name a file is stored under in the backup's `Manifest.db`
+fn manifest_file_id(backup: &Path, domain: &str, relative_path: &str) -> Option<String> {
+    let manifest = backup.join(MANIFEST_DB);
+    if !manifest.is_file() {
+        return None;
+    }
+    let db = Connection::open_with_flags(manifest, OpenFlags::SQLITE_OPEN_READ_ONLY).ok()?;
+    db.query_row(
+        "SELECT fileID FROM Files WHERE domain = ?1 AND relativePath = ?2",
+        [domain, relative_path],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+    };
+
+    use plist::{Dictionary, Value};
+    use rusqlite::Connection;
+
+    use crate::{
+        tables::table::DEFAULT_PATH_IOS,
+        util::backup::{
+            backup_file, file_id, is_backup, is_encrypted, latest_backup, sms_db_path,
+            ATTACHMENT_DOMAIN, MANIFEST_DB, MANIFEST_PLIST,
+        },
+    };
+
+    #[test]
+    fn can_generate_file_id() {
+        assert_eq!(
+            file_id("HomeDomain", "Library/SMS/sms.db"),
+            DEFAULT_PATH_IOS.rsplit('/').next().unwrap()
+        );
+    }
+
+    #[test]
+    fn can_find_file_without_manifest() {
+        let backup = temp_dir().join("imessage-database-backup-no-manifest");
+        let path = backup_file(
+            &backup,
+            ATTACHMENT_DOMAIN,
+            "Library/SMS/Attachments/a/b/IMG_0001.HEIC",
+        )
+        .unwrap();
+        let expected = file_id(
+            ATTACHMENT_DOMAIN,
+            "Library/SMS/Attachments/a/b/IMG_0001.HEIC",
+        );
+        assert_eq!(path, backup.join(&expected[0..2]).join(&expected));
+        assert_eq!(sms_db_path(&backup), backup.join(DEFAULT_PATH_IOS));
+    }
+
+    #[test]
+    fn can_find_file_in_manifest() {
+        let backup = temp_dir().join("imessage-database-backup-manifest");
+        let _ = remove_dir_all(&backup);
+        create_dir_all(&backup).unwrap();
+        let db = Connection::open(backup.join(MANIFEST_DB)).unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE Files (fileID TEXT PRIMARY KEY, domain TEXT, relativePath TEXT);",
+            "INSERT INTO Files VALUES ('ab0123', 'HomeDomain', 'Library/SMS/sms.db');",
+        ))
+        .unwrap();
+
+        assert!(is_backup(&backup));
+        assert_eq!(sms_db_path(&backup), backup.join("ab").join("ab0123"));
+    }
+
+    #[test]
+    fn can_determine_encrypted() {
+        let backup = temp_dir().join("imessage-database-backup-encrypted");
+        create_dir_all(&backup).unwrap();
+        let mut manifest = Dictionary::new();
+        manifest.insert("IsEncrypted".to_string(), Value::Boolean(true));
+        Value::Dictionary(manifest)
+            .to_file_xml(backup.join(MANIFEST_PLIST))
+            .unwrap();
+
+        assert!(is_encrypted(&backup));
+        assert!(!is_encrypted(&backup.join("missing")));
+    }
+
+    #[test]
+    fn can_find_latest_backup() {
+        let backups = temp_dir().join("imessage-database-backups");
+        let _ = remove_dir_all(&backups);
+        let backup = backups.join("00008030-001A2B3C4D5E6F70");
+        create_dir_all(backups.join("not-a-backup")).unwrap();
+        create_dir_all(&backup).unwrap();
+        write(backup.join(MANIFEST_DB), "").unwrap();
+
+        assert!(!is_backup(&backups));
+        assert_eq!(latest_backup(&backups), Some(backup));
+        assert_eq!(latest_backup(&backups.join("missing")), None);
+    }
+}
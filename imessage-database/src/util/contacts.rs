@@ -0,0 +1,455 @@
+/*!
+ Contains logic for reading the names of contacts, so phone numbers and email addresses can be shown as the people they belong to.
+
+ Contacts are not stored in the Messages database. On macOS, they are stored in the Contacts database, a `SQLite`
+ file named `AddressBook-v22.abcddb` that exists in the `AddressBook` directory and in each of its `Sources`.
+ Contacts archives (`.abbu`) are directories that hold the same files. Contacts can also be read from vCard (`.vcf`) files,
+ or from a names file that lists a phone number or email address and a name on each line.
+*/
+
+use std::{
+    collections::HashMap,
+    fs::{read_dir, read_to_string},
+    iter::once,
+    path::Path,
+};
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::{error::table::TableError, message_types::contact_card::parse_contact_cards};
+
+/// Default location of the Contacts database on macOS, relative to the home directory
+pub const DEFAULT_ADDRESS_BOOK_PATH_MACOS: &str = "Library/Application Support/AddressBook";
+/// The extension of Contacts database files
+const ADDRESS_BOOK_EXTENSION: &str = "abcddb";
+/// The extension of vCard files
+const VCARD_EXTENSION: &str = "vcf";
+/// The number of trailing digits that identify a phone number regardless of how its country code is written
+const PHONE_DIGITS: usize = 10;
+/// Bytes that start every `JPEG` file
+const JPEG_SIGNATURE: [u8; 2] = [0xFF, 0xD8];
+/// The most bytes Contacts stores before the `JPEG` data of a photo
+const JPEG_PREFIX_SEARCH: usize = 4;
+
+/// A map of phone numbers and email addresses to the names of the contacts they belong to
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ContactNames {
+    names: HashMap<String, String>,
+    /// Map of phone numbers and email addresses to a key that identifies the contact they belong to
+    people: HashMap<String, String>,
+    /// Map of keys that identify contacts to the image data of their photos
+    avatars: HashMap<String, Vec<u8>>,
+}
+
+impl ContactNames {
+    /// Read contact names from a Contacts database, a directory that contains Contacts databases
+    /// (like a Contacts archive), a vCard file, or a directory of vCard files
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use imessage_database::util::contacts::ContactNames;
+    ///
+    /// let contacts = ContactNames::from_path(Path::new("Contacts.abbu")).unwrap();
+    /// let name = contacts.get("+15558675309");
+    /// ```
+    pub fn from_path(path: &Path) -> Result<Self, TableError> {
+        let mut contacts = Self::default();
+        contacts.read_path(path)?;
+        Ok(contacts)
+    }
+
+    /// Read contact names from a names file
+    ///
+    /// Each line holds a phone number or email address and a name, separated either by a comma,
+    /// as in a CSV file, or by an equals sign, as in a TOML table. Names may be quoted.
+    /// Blank lines and lines starting with `#` or `[` are ignored.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use imessage_database::util::contacts::ContactNames;
+    ///
+    /// let names = ContactNames::from_names_file(Path::new("names.csv")).unwrap();
+    /// let name = names.get("+15558675309");
+    /// ```
+    pub fn from_names_file(path: &Path) -> Result<Self, TableError> {
+        let mut names = Self::default();
+        names.read_names_file(path)?;
+        Ok(names)
+    }
+
+    /// Add the contact names read from a path, keeping any names that were already read
+    ///
+    /// This accepts the same paths as [`ContactNames::from_path`].
+    pub fn read_contacts(&mut self, path: &Path) -> Result<(), TableError> {
+        self.read_path(path)
+    }
+
+    /// Add the names read from a names file, keeping any names that were already read
+    ///
+    /// Handles given the same name in the file belong to the same person.
+    /// This accepts the same files as [`ContactNames::from_names_file`].
+    pub fn read_names_file(&mut self, path: &Path) -> Result<(), TableError> {
+        let contents = read_to_string(path).map_err(TableError::CannotRead)?;
+        for (id, name) in contents.lines().filter_map(parse_name_line) {
+            self.insert(id, name, &format!("name:{name}"));
+        }
+        Ok(())
+    }
+
+    /// Get the name of the contact that a phone number or email address belongs to
+    ///
+    /// Handles that represent the same person are stored as their IDs separated by spaces, so if the whole ID
+    /// does not match, each of them is tried.
+    pub fn get(&self, id: &str) -> Option<&str> {
+        id_candidates(id)
+            .find_map(|candidate| self.names.get(&normalize_id(candidate)))
+            .map(String::as_str)
+    }
+
+    /// Get a key that identifies the contact a phone number or email address belongs to
+    ///
+    /// Phone numbers and email addresses that belong to the same contact have the same key.
+    pub fn person(&self, id: &str) -> Option<&str> {
+        id_candidates(id)
+            .find_map(|candidate| self.people.get(&normalize_id(candidate)))
+            .map(String::as_str)
+    }
+
+    /// Get the photo of the contact a phone number or email address belongs to, if it has one
+    ///
+    /// Photos are only read from Contacts databases, which store them as `JPEG` thumbnails.
+    pub fn avatar(&self, id: &str) -> Option<&[u8]> {
+        self.avatars.get(self.person(id)?).map(Vec::as_slice)
+    }
+
+    /// Add a contact name for a phone number or email address, keeping the first name added for each
+    ///
+    /// `person` identifies the contact, so every phone number and email address added with the same `person`
+    /// is treated as belonging to one person.
+    pub fn insert(&mut self, id: &str, name: &str, person: &str) {
+        let key = normalize_id(id);
+        if !key.is_empty() && !name.trim().is_empty() && !self.names.contains_key(&key) {
+            self.names.insert(key.clone(), name.trim().to_string());
+            self.people.insert(key, person.to_string());
+        }
+    }
+
+    /// The number of phone numbers and email addresses that have a name
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Determine if no contact names were found
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Read every Contacts database and vCard file at a path, searching directories recursively
+    fn read_path(&mut self, path: &Path) -> Result<(), TableError> {
+        if path.is_dir() {
+            let mut entries: Vec<_> = read_dir(path)
+                .map_err(TableError::CannotRead)?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .collect();
+            // Read in a stable order so the same contact always wins
+            entries.sort();
+            for entry in entries {
+                if entry.is_dir() || has_extension(&entry, ADDRESS_BOOK_EXTENSION) {
+                    self.read_path(&entry)?;
+                } else if has_extension(&entry, VCARD_EXTENSION) {
+                    self.read_vcards(&entry)?;
+                }
+            }
+            return Ok(());
+        }
+
+        if has_extension(path, VCARD_EXTENSION) {
+            return self.read_vcards(path);
+        }
+        self.read_address_book(path)
+    }
+
+    /// Read the phone numbers and email addresses of each contact in a Contacts database
+    fn read_address_book(&mut self, path: &Path) -> Result<(), TableError> {
+        let db = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(TableError::Contacts)?;
+        let mut statement = db
+            .prepare(concat!(
+                "SELECT r.ZFIRSTNAME, r.ZLASTNAME, r.ZORGANIZATION, p.ZFULLNUMBER, r.Z_PK ",
+                "FROM ZABCDRECORD r INNER JOIN ZABCDPHONENUMBER p ON p.ZOWNER = r.Z_PK ",
+                "UNION ALL ",
+                "SELECT r.ZFIRSTNAME, r.ZLASTNAME, r.ZORGANIZATION, e.ZADDRESS, r.Z_PK ",
+                "FROM ZABCDRECORD r INNER JOIN ZABCDEMAILADDRESS e ON e.ZOWNER = r.Z_PK",
+            ))
+            .map_err(TableError::Contacts)?;
+
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            })
+            .map_err(TableError::Contacts)?;
+
+        for row in rows {
+            let (first, last, organization, id, record) = row.map_err(TableError::Contacts)?;
+            let full_name = [first, last]
+                .into_iter()
+                .flatten()
+                .filter(|part| !part.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let name = match full_name.is_empty() {
+                true => organization.unwrap_or_default(),
+                false => full_name,
+            };
+            if let Some(id) = id {
+                self.insert(&id, &name, &format!("{}:{record}", path.display()));
+            }
+        }
+
+        // Photos are optional, so a database without them still provides names
+        if let Ok(mut statement) = db.prepare(
+            "SELECT Z_PK, ZTHUMBNAILIMAGEDATA FROM ZABCDRECORD WHERE ZTHUMBNAILIMAGEDATA IS NOT NULL",
+        ) {
+            let rows = statement
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)))
+                .map_err(TableError::Contacts)?;
+            for (record, data) in rows.flatten() {
+                self.avatars
+                    .entry(format!("{}:{record}", path.display()))
+                    .or_insert_with(|| strip_image_prefix(&data).to_vec());
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the phone numbers and email addresses of each contact in a vCard file
+    fn read_vcards(&mut self, path: &Path) -> Result<(), TableError> {
+        let contents = read_to_string(path).map_err(TableError::CannotRead)?;
+        for (index, card) in parse_contact_cards(&contents).iter().enumerate() {
+            let Some(name) = card.name.as_deref().or(card.organization.as_deref()) else {
+                continue;
+            };
+            let person = format!("{}:{index}", path.display());
+            for id in card.phone_numbers.iter().chain(&card.emails) {
+                self.insert(id, name, &person);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Remove the bytes Contacts stores before the `JPEG` data of a photo, if there are any
+fn strip_image_prefix(data: &[u8]) -> &[u8] {
+    data.windows(JPEG_SIGNATURE.len())
+        .take(JPEG_PREFIX_SEARCH)
+        .position(|window| window == JPEG_SIGNATURE)
+        .map_or(data, |start| &data[start..])
+}
+
+/// Determine if a path has the given file extension, ignoring case
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/// The IDs to look up for a handle: the whole ID, which may be a phone number formatted with spaces,
+/// followed by each of the space separated IDs of a deduplicated handle
+fn id_candidates(id: &str) -> impl Iterator<Item = &str> {
+    once(id).chain(id.split_whitespace())
+}
+
+/// Split a line of a names file into a phone number or email address and a name
+fn parse_name_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+        return None;
+    }
+    let (id, name) = line.split_once([',', '='])?;
+    Some((unquote(id), unquote(name)))
+}
+
+/// Remove whitespace and a pair of surrounding quotes from a value
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Reduce a phone number or email address to the form used to match it
+///
+/// Email addresses are compared without case. Phone numbers are compared by their digits, and only the last
+/// few digits are kept so that numbers match whether or not they were saved with a country code or trunk prefix.
+pub fn normalize_id(id: &str) -> String {
+    let id = id.trim();
+    if id.contains('@') {
+        return id.to_lowercase();
+    }
+    let digits: String = id.chars().filter(char::is_ascii_digit).collect();
+    match digits.len().checked_sub(PHONE_DIGITS) {
+        Some(start) => digits[start..].to_string(),
+        None => digits,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, remove_file, write},
+    };
+
+    use rusqlite::Connection;
+
+    use crate::util::contacts::{normalize_id, ContactNames};
+
+    #[test]
+    fn can_normalize_phone_numbers() {
+        assert_eq!(normalize_id("+1 (555) 867-5309"), "5558675309");
+        assert_eq!(normalize_id("555.867.5309"), "5558675309");
+        assert_eq!(normalize_id("+447911123456"), normalize_id("07911 123456"));
+        assert_eq!(normalize_id("12345"), "12345");
+    }
+
+    #[test]
+    fn can_normalize_emails() {
+        assert_eq!(normalize_id(" Jane@Example.com "), "jane@example.com");
+    }
+
+    #[test]
+    fn can_get_deduplicated_handle() {
+        let mut contacts = ContactNames::default();
+        contacts.insert("jane@example.com", "Jane Doe", "jane");
+
+        assert_eq!(
+            contacts.get("+15558675309 jane@example.com"),
+            Some("Jane Doe")
+        );
+        assert_eq!(contacts.get("+15550000000"), None);
+    }
+
+    #[test]
+    fn can_keep_first_name() {
+        let mut contacts = ContactNames::default();
+        contacts.insert("+15558675309", "Mom", "mom");
+        contacts.insert("555-867-5309", "Jane Doe", "jane");
+        contacts.insert("+15550000000", " ", "blank");
+
+        assert_eq!(contacts.get("+15558675309"), Some("Mom"));
+        assert_eq!(contacts.person("+15558675309"), Some("mom"));
+        assert_eq!(contacts.len(), 1);
+    }
+
+    #[test]
+    fn can_read_names_file() {
+        let path = temp_dir().join("imessage-database-contacts-names.csv");
+        write(
+            &path,
+            "# Family\n+15558675309,Mom\n\"jane@example.com\", \"Doe, Jane\"\n\n[names]\n\"+1 555 000 1111\" = \"Dentist\"\nno separator\n",
+        )
+        .unwrap();
+
+        let names = ContactNames::from_names_file(&path).unwrap();
+        remove_file(&path).unwrap();
+
+        assert_eq!(names.get("(555) 867-5309"), Some("Mom"));
+        assert_eq!(names.get("JANE@example.com"), Some("Doe, Jane"));
+        assert_eq!(names.get("+15550001111"), Some("Dentist"));
+        assert_eq!(names.len(), 3);
+        assert_ne!(names.person("+15558675309"), names.person("+15550001111"));
+    }
+
+    #[test]
+    fn can_merge_names_file_people() {
+        let path = temp_dir().join("imessage-database-contacts-people.csv");
+        write(
+            &path,
+            "+15558675309,Jane Doe\njane@example.com,Jane Doe\n+15550001111,Mom\n",
+        )
+        .unwrap();
+
+        let names = ContactNames::from_names_file(&path).unwrap();
+        remove_file(&path).unwrap();
+
+        assert_eq!(
+            names.person("+15558675309"),
+            names.person("jane@example.com")
+        );
+        assert_ne!(names.person("+15558675309"), names.person("+15550001111"));
+    }
+
+    #[test]
+    fn can_read_vcards() {
+        let dir = temp_dir().join("imessage-database-contacts-vcards");
+        create_dir_all(&dir).unwrap();
+        write(
+            dir.join("contacts.vcf"),
+            "BEGIN:VCARD\nFN:Jane Doe\nTEL:(555) 867-5309\nEMAIL:jane@example.com\nEND:VCARD\nBEGIN:VCARD\nORG:Pizza Place\nTEL:+15550001111\nEND:VCARD\n",
+        )
+        .unwrap();
+
+        let contacts = ContactNames::from_path(&dir).unwrap();
+        remove_dir_all(&dir).unwrap();
+
+        assert_eq!(contacts.get("+15558675309"), Some("Jane Doe"));
+        assert_eq!(contacts.get("JANE@example.com"), Some("Jane Doe"));
+        assert_eq!(contacts.get("+15550001111"), Some("Pizza Place"));
+        assert_eq!(
+            contacts.person("+15558675309"),
+            contacts.person("jane@example.com")
+        );
+        assert_ne!(
+            contacts.person("+15558675309"),
+            contacts.person("+15550001111")
+        );
+    }
+
+    #[test]
+    fn can_read_address_book() {
+        let dir = temp_dir().join("imessage-database-contacts-abbu/Contacts.abbu");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("AddressBook-v22.abcddb");
+        let _ = remove_file(&path);
+
+        let db = Connection::open(&path).unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE ZABCDRECORD (Z_PK INTEGER PRIMARY KEY, ZFIRSTNAME TEXT, ZLASTNAME TEXT, ZORGANIZATION TEXT, ZTHUMBNAILIMAGEDATA BLOB);",
+            "CREATE TABLE ZABCDPHONENUMBER (ZOWNER INTEGER, ZFULLNUMBER TEXT);",
+            "CREATE TABLE ZABCDEMAILADDRESS (ZOWNER INTEGER, ZADDRESS TEXT);",
+            "INSERT INTO ZABCDRECORD VALUES (1, 'Mom', NULL, NULL, X'01FFD8FFE0'), (2, NULL, NULL, 'Dentist', NULL);",
+            "INSERT INTO ZABCDPHONENUMBER VALUES (1, '(555) 123-4567'), (2, '555-000-1111');",
+            "INSERT INTO ZABCDEMAILADDRESS VALUES (1, 'mom@example.com');",
+        ))
+        .unwrap();
+        drop(db);
+
+        let contacts = ContactNames::from_path(dir.parent().unwrap()).unwrap();
+        remove_dir_all(dir.parent().unwrap()).unwrap();
+
+        assert_eq!(contacts.get("+15551234567"), Some("Mom"));
+        assert_eq!(contacts.get("mom@example.com"), Some("Mom"));
+        assert_eq!(contacts.get("+15550001111"), Some("Dentist"));
+        assert_eq!(
+            contacts.person("+15551234567"),
+            contacts.person("mom@example.com")
+        );
+        assert_eq!(
+            contacts.avatar("mom@example.com"),
+            Some([0xFF, 0xD8, 0xFF, 0xE0].as_slice())
+        );
+        assert_eq!(contacts.avatar("+15550001111"), None);
+    }
+}
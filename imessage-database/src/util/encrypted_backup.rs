@@ -0,0 +1,497 @@
+/*!
+ Contains logic for decrypting the Messages data in an encrypted iOS or iPadOS backup.
+
+ Each file in an encrypted backup has its own key, which is wrapped with one of the class keys in the backup's keybag.
+ The class keys are wrapped with a key derived from the backup password. `Manifest.db` is encrypted the same way,
+ with its key stored in `Manifest.plist`.
+
+ Decrypted files are written in the same layout as an unencrypted backup, so they can be read like one.
+ Read more [here](https://theapplewiki.com/index.php?title=ITunes_Backup).
+
+ This module requires the `encrypted-backups` feature.
+*/
+
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, read, write},
+    path::Path,
+};
+
+use aes::{
+    cipher::{generic_array::GenericArray, BlockDecrypt, KeyInit},
+    Aes256,
+};
+use pbkdf2::pbkdf2_hmac;
+use plist::{Dictionary, Value};
+use rusqlite::{Connection, OpenFlags};
+use sha1::Sha1;
+use sha2::Sha256;
+
+use crate::{
+    error::backup::BackupError,
+    util::{
+        backup::{ATTACHMENT_DOMAIN, MANIFEST_DB, MANIFEST_PLIST, SMS_DOMAIN},
+        keyed_archive::KeyedArchive,
+    },
+};
+
+/// The size of an AES block, in bytes
+const AES_BLOCK: usize = 16;
+/// The size of an AES-256 key, in bytes
+const KEY_LENGTH: usize = 32;
+/// The value [RFC 3394](https://www.rfc-editor.org/rfc/rfc3394) key unwrapping must produce for the key to be valid
+const KEY_WRAP_IV: u64 = 0xA6A6_A6A6_A6A6_A6A6;
+/// The `WRAP` flag of class keys that are wrapped with the key derived from the password
+const WRAP_PASSCODE: u32 = 2;
+/// The folder that holds the Messages database and attachments, relative to their domains
+const SMS_DIRECTORY: &str = "Library/SMS/";
+
+/// A class key from the keybag, still wrapped
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ClassKey {
+    /// The protection class the key unlocks
+    class: u32,
+    /// Flags describing how the key is wrapped
+    wrap: u32,
+    /// The wrapped key
+    wrapped_key: Vec<u8>,
+}
+
+/// The keybag stored in `Manifest.plist`, which holds the class keys
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Keybag {
+    /// The salt for the second round of key derivation
+    salt: Vec<u8>,
+    /// The iterations for the second round of key derivation
+    iterations: u32,
+    /// The salt for the first round of key derivation, used by iOS 10.2 and newer
+    double_protection_salt: Option<Vec<u8>>,
+    /// The iterations for the first round of key derivation, used by iOS 10.2 and newer
+    double_protection_iterations: u32,
+    /// The class keys
+    class_keys: Vec<ClassKey>,
+}
+
+impl Keybag {
+    /// Read a keybag, which is a list of 4 byte tags, each followed by the length of its value and the value
+    ///
+    /// The keybag's own fields come first. Each `UUID` tag after the first starts a new class key.
+    fn parse(bytes: &[u8]) -> Result<Self, BackupError> {
+        let mut keybag = Keybag::default();
+        let mut class_key: Option<ClassKey> = None;
+        let mut seen_uuid = false;
+
+        let mut idx = 0;
+        while idx < bytes.len() {
+            let tag = bytes.get(idx..idx + 4).ok_or(BackupError::InvalidKeybag)?;
+            let length = bytes
+                .get(idx + 4..idx + 8)
+                .map(be_int)
+                .ok_or(BackupError::InvalidKeybag)? as usize;
+            let value = bytes
+                .get(idx + 8..idx + 8 + length)
+                .ok_or(BackupError::InvalidKeybag)?;
+            idx += 8 + length;
+
+            if tag == b"UUID" {
+                if seen_uuid {
+                    keybag.class_keys.extend(class_key.take());
+                    class_key = Some(ClassKey::default());
+                }
+                seen_uuid = true;
+                continue;
+            }
+            match (tag, class_key.as_mut()) {
+                (b"CLAS", Some(key)) => key.class = be_int(value),
+                (b"WRAP", Some(key)) => key.wrap = be_int(value),
+                (b"WPKY", Some(key)) => key.wrapped_key = value.to_vec(),
+                (b"SALT", None) => keybag.salt = value.to_vec(),
+                (b"ITER", None) => keybag.iterations = be_int(value),
+                (b"DPSL", None) => keybag.double_protection_salt = Some(value.to_vec()),
+                (b"DPIC", None) => keybag.double_protection_iterations = be_int(value),
+                _ => {}
+            }
+        }
+        keybag.class_keys.extend(class_key);
+
+        if keybag.salt.is_empty() || keybag.class_keys.is_empty() {
+            return Err(BackupError::InvalidKeybag);
+        }
+        Ok(keybag)
+    }
+
+    /// Derive the key from the password and use it to unwrap the class keys, returning them by protection class
+    fn unlock(&self, password: &str) -> Result<HashMap<u32, Vec<u8>>, BackupError> {
+        let mut passcode_key = [0; KEY_LENGTH];
+        match &self.double_protection_salt {
+            Some(salt) => {
+                let mut first_round = [0; KEY_LENGTH];
+                pbkdf2_hmac::<Sha256>(
+                    password.as_bytes(),
+                    salt,
+                    self.double_protection_iterations,
+                    &mut first_round,
+                );
+                pbkdf2_hmac::<Sha1>(&first_round, &self.salt, self.iterations, &mut passcode_key);
+            }
+            None => pbkdf2_hmac::<Sha1>(
+                password.as_bytes(),
+                &self.salt,
+                self.iterations,
+                &mut passcode_key,
+            ),
+        }
+
+        self.class_keys
+            .iter()
+            .filter(|class_key| class_key.wrap & WRAP_PASSCODE != 0)
+            .map(|class_key| {
+                unwrap_key(&passcode_key, &class_key.wrapped_key)
+                    .map(|key| (class_key.class, key))
+                    .ok_or(BackupError::WrongPassword)
+            })
+            .collect()
+    }
+}
+
+/// Decrypt the Messages database and attachments in an encrypted backup, writing them to `destination`
+///
+/// `destination` can be read like an unencrypted backup. Returns the number of files that were decrypted.
+///
+/// # Example:
+///
+/// ```no_run
+/// use std::path::Path;
+/// use imessage_database::util::encrypted_backup::decrypt_messages;
+///
+/// let files = decrypt_messages(
+///     Path::new("/path/to/backup"),
+///     "password",
+///     Path::new("/path/to/decrypted"),
+/// );
+/// ```
+pub fn decrypt_messages(
+    backup: &Path,
+    password: &str,
+    destination: &Path,
+) -> Result<usize, BackupError> {
+    let manifest =
+        Value::from_file(backup.join(MANIFEST_PLIST)).map_err(BackupError::InvalidManifest)?;
+    let manifest = manifest
+        .as_dictionary()
+        .ok_or_else(|| BackupError::MissingKey(String::from("BackupKeyBag")))?;
+
+    let class_keys = Keybag::parse(manifest_data(manifest, "BackupKeyBag")?)?.unlock(password)?;
+
+    // The manifest lists the files in the backup, so it is decrypted first
+    let manifest_key = unwrap_file_key(&class_keys, manifest_data(manifest, "ManifestKey")?)?;
+    let encrypted_manifest = read(backup.join(MANIFEST_DB)).map_err(BackupError::CannotRead)?;
+    let manifest_db = decrypt_cbc(&manifest_key, &encrypted_manifest)
+        .ok_or_else(|| BackupError::CannotDecrypt(MANIFEST_DB.to_string()))?;
+    create_dir_all(destination)
+        .map_err(|why| BackupError::CannotWrite(why, destination.to_path_buf()))?;
+    let manifest_path = destination.join(MANIFEST_DB);
+    write(&manifest_path, manifest_db)
+        .map_err(|why| BackupError::CannotWrite(why, manifest_path.clone()))?;
+
+    let db = Connection::open_with_flags(&manifest_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(BackupError::ManifestError)?;
+    let mut statement = db
+        .prepare(
+            "SELECT fileID, file FROM Files
+            WHERE domain IN (?1, ?2) AND relativePath LIKE ?3 AND flags = 1",
+        )
+        .map_err(BackupError::ManifestError)?;
+    let files = statement
+        .query_map(
+            [SMS_DOMAIN, ATTACHMENT_DOMAIN, &format!("{SMS_DIRECTORY}%")],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)),
+        )
+        .map_err(BackupError::ManifestError)?;
+
+    let mut decrypted = 0;
+    for file in files {
+        let (file_id, metadata) = file.map_err(BackupError::ManifestError)?;
+        if decrypt_file(backup, destination, &class_keys, &file_id, &metadata)? {
+            decrypted += 1;
+        }
+    }
+    Ok(decrypted)
+}
+
+/// Decrypt a file listed in `Manifest.db`, given the archived metadata stored with it
+///
+/// Returns `false` if the file is listed but missing from the backup.
+fn decrypt_file(
+    backup: &Path,
+    destination: &Path,
+    class_keys: &HashMap<u32, Vec<u8>>,
+    file_id: &str,
+    metadata: &[u8],
+) -> Result<bool, BackupError> {
+    let directory = file_id
+        .get(0..2)
+        .ok_or_else(|| BackupError::CannotDecrypt(file_id.to_string()))?;
+    let source = backup.join(directory).join(file_id);
+    if !source.is_file() {
+        return Ok(false);
+    }
+
+    let invalid = |why| BackupError::InvalidFile(file_id.to_string(), why);
+    let archive = KeyedArchive::from_bytes(metadata).map_err(invalid)?;
+    let root = archive.root().map_err(invalid)?;
+    let wrapped_key = archive
+        .data(archive.get(root, "EncryptionKey").map_err(invalid)?)
+        .ok_or_else(|| BackupError::CannotDecrypt(file_id.to_string()))?;
+    let size = archive
+        .get(root, "Size")
+        .ok()
+        .and_then(Value::as_unsigned_integer);
+
+    let key = unwrap_file_key(class_keys, wrapped_key)?;
+    let mut data = decrypt_cbc(&key, &read(&source).map_err(BackupError::CannotRead)?)
+        .ok_or_else(|| BackupError::CannotDecrypt(file_id.to_string()))?;
+    match size {
+        Some(size) => data.truncate(size as usize),
+        None => remove_padding(&mut data),
+    }
+
+    let directory = destination.join(directory);
+    create_dir_all(&directory).map_err(|why| BackupError::CannotWrite(why, directory.clone()))?;
+    let path = directory.join(file_id);
+    write(&path, data).map_err(|why| BackupError::CannotWrite(why, path))?;
+    Ok(true)
+}
+
+/// Get the bytes stored at a key of `Manifest.plist`
+fn manifest_data<'a>(manifest: &'a Dictionary, key: &str) -> Result<&'a [u8], BackupError> {
+    manifest
+        .get(key)
+        .and_then(Value::as_data)
+        .ok_or_else(|| BackupError::MissingKey(key.to_string()))
+}
+
+/// Unwrap the key of a file, which starts with its protection class as a little-endian integer
+fn unwrap_file_key(
+    class_keys: &HashMap<u32, Vec<u8>>,
+    key_data: &[u8],
+) -> Result<Vec<u8>, BackupError> {
+    if key_data.len() < 4 {
+        return Err(BackupError::CannotDecrypt(String::from("file key")));
+    }
+    let (class, wrapped_key) = key_data.split_at(4);
+    let class = u32::from_le_bytes([class[0], class[1], class[2], class[3]]);
+    let class_key = class_keys
+        .get(&class)
+        .ok_or(BackupError::MissingClassKey(class))?;
+    unwrap_key(class_key, wrapped_key)
+        .ok_or_else(|| BackupError::CannotDecrypt(String::from("file key")))
+}
+
+/// Read a big-endian integer of up to 4 bytes
+fn be_int(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |value, byte| (value << 8) | u32::from(*byte))
+}
+
+/// Unwrap a key with the [RFC 3394](https://www.rfc-editor.org/rfc/rfc3394) AES key wrap algorithm
+///
+/// Returns `None` if the key encryption key is wrong.
+fn unwrap_key(kek: &[u8], wrapped: &[u8]) -> Option<Vec<u8>> {
+    if wrapped.len() % 8 != 0 || wrapped.len() < 24 {
+        return None;
+    }
+    let cipher = Aes256::new_from_slice(kek).ok()?;
+
+    let (integrity, wrapped) = wrapped.split_at(8);
+    let mut integrity = u64::from_be_bytes(integrity.try_into().ok()?);
+    let mut blocks: Vec<[u8; 8]> = wrapped
+        .chunks_exact(8)
+        .map(|chunk| {
+            let mut block = [0; 8];
+            block.copy_from_slice(chunk);
+            block
+        })
+        .collect();
+
+    let count = blocks.len();
+    for round in (0..6).rev() {
+        for idx in (0..count).rev() {
+            let step = (count * round + idx + 1) as u64;
+            let mut block = GenericArray::clone_from_slice(&[0; AES_BLOCK]);
+            block[..8].copy_from_slice(&(integrity ^ step).to_be_bytes());
+            block[8..].copy_from_slice(&blocks[idx]);
+            cipher.decrypt_block(&mut block);
+
+            let (high, low) = block.split_at(8);
+            integrity = u64::from_be_bytes(high.try_into().ok()?);
+            blocks[idx].copy_from_slice(low);
+        }
+    }
+
+    (integrity == KEY_WRAP_IV).then(|| blocks.concat())
+}
+
+/// Decrypt data with AES-256 in CBC mode, using the zero initialization vector backups are written with
+fn decrypt_cbc(key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    decrypt_cbc_with_iv(key, &[0; AES_BLOCK], data)
+}
+
+/// Decrypt data with AES-256 in CBC mode
+fn decrypt_cbc_with_iv(key: &[u8], iv: &[u8; AES_BLOCK], data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() % AES_BLOCK != 0 {
+        return None;
+    }
+    let cipher = Aes256::new_from_slice(key).ok()?;
+
+    let mut previous = *iv;
+    let mut decrypted = Vec::with_capacity(data.len());
+    for chunk in data.chunks_exact(AES_BLOCK) {
+        let mut block = GenericArray::clone_from_slice(chunk);
+        cipher.decrypt_block(&mut block);
+        decrypted.extend(block.iter().zip(previous).map(|(byte, prev)| byte ^ prev));
+        previous.copy_from_slice(chunk);
+    }
+    Some(decrypted)
+}
+
+/// Remove the PKCS #7 padding from the end of decrypted data, if it has any
+fn remove_padding(data: &mut Vec<u8>) {
+    if let Some(&padding) = data.last() {
+        let padding = padding as usize;
+        if (1..=AES_BLOCK).contains(&padding)
+            && data.len() >= padding
+            && data[data.len() - padding..]
+                .iter()
+                .all(|byte| *byte as usize == padding)
+        {
+            data.truncate(data.len() - padding);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::encrypted_backup::{
+        be_int, decrypt_cbc_with_iv, remove_padding, unwrap_key, ClassKey, Keybag,
+    };
+
+    /// Decode a hex string, ignoring spaces
+    fn hex(text: &str) -> Vec<u8> {
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        (0..text.len())
+            .step_by(2)
+            .map(|idx| u8::from_str_radix(&text[idx..idx + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Encode a keybag field
+    fn field(tag: &[u8; 4], value: &[u8]) -> Vec<u8> {
+        let mut out = tag.to_vec();
+        out.extend((value.len() as u32).to_be_bytes());
+        out.extend(value);
+        out
+    }
+
+    #[test]
+    fn can_unwrap_key() {
+        // RFC 3394, section 4.6
+        let kek = hex("000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F");
+        let wrapped = hex(
+            "28C9F404C4B810F4 CBCCB35CFB87F826 3F5786E2D80ED326 CBC7F0E71A99F43B FB988B9B7A02DD21",
+        );
+        let expected = hex("00112233445566778899AABBCCDDEEFF000102030405060708090A0B0C0D0E0F");
+        assert_eq!(unwrap_key(&kek, &wrapped), Some(expected));
+    }
+
+    #[test]
+    fn cant_unwrap_key_wrong_kek() {
+        let kek = [0; 32];
+        let wrapped = hex(
+            "28C9F404C4B810F4 CBCCB35CFB87F826 3F5786E2D80ED326 CBC7F0E71A99F43B FB988B9B7A02DD21",
+        );
+        assert_eq!(unwrap_key(&kek, &wrapped), None);
+        assert_eq!(unwrap_key(&kek, &[0; 12]), None);
+    }
+
+    #[test]
+    fn can_decrypt_cbc() {
+        // NIST SP 800-38A, section F.2.6
+        let key = hex("603DEB1015CA71BE2B73AEF0857D77811F352C073B6108D72D9810A30914DFF4");
+        let iv = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
+            0x0E, 0x0F,
+        ];
+        let ciphertext = hex("F58C4C04D6E5F1BA779EABFB5F7BFBD6 9CFC4E967EDB808D679F777BC6702C7D");
+        let expected = hex("6BC1BEE22E409F96E93D7E117393172A AE2D8A571E03AC9C9EB76FAC45AF8E51");
+        assert_eq!(decrypt_cbc_with_iv(&key, &iv, &ciphertext), Some(expected));
+        assert_eq!(decrypt_cbc_with_iv(&key, &iv, &ciphertext[..15]), None);
+    }
+
+    #[test]
+    fn can_remove_padding() {
+        let mut data = vec![1, 2, 3, 3, 3, 3];
+        remove_padding(&mut data);
+        assert_eq!(data, vec![1, 2, 3]);
+
+        let mut data = vec![1, 2, 3, 0];
+        remove_padding(&mut data);
+        assert_eq!(data, vec![1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn can_read_be_int() {
+        assert_eq!(be_int(&[0x00, 0x00, 0x27, 0x10]), 10000);
+        assert_eq!(be_int(&[0x02]), 2);
+    }
+
+    #[test]
+    fn can_parse_keybag() {
+        let bytes = [
+            field(b"VERS", &[0, 0, 0, 4]),
+            field(b"UUID", &[0xAA; 16]),
+            field(b"SALT", &[0x01; 20]),
+            field(b"ITER", &10000_u32.to_be_bytes()),
+            field(b"DPSL", &[0x02; 20]),
+            field(b"DPIC", &10_000_000_u32.to_be_bytes()),
+            field(b"UUID", &[0xBB; 16]),
+            field(b"CLAS", &1_u32.to_be_bytes()),
+            field(b"WRAP", &3_u32.to_be_bytes()),
+            field(b"WPKY", &[0x03; 40]),
+            field(b"UUID", &[0xCC; 16]),
+            field(b"CLAS", &3_u32.to_be_bytes()),
+            field(b"WRAP", &2_u32.to_be_bytes()),
+            field(b"WPKY", &[0x04; 40]),
+        ]
+        .concat();
+
+        let expected = Keybag {
+            salt: vec![0x01; 20],
+            iterations: 10000,
+            double_protection_salt: Some(vec![0x02; 20]),
+            double_protection_iterations: 10_000_000,
+            class_keys: vec![
+                ClassKey {
+                    class: 1,
+                    wrap: 3,
+                    wrapped_key: vec![0x03; 40],
+                },
+                ClassKey {
+                    class: 3,
+                    wrap: 2,
+                    wrapped_key: vec![0x04; 40],
+                },
+            ],
+        };
+        assert_eq!(Keybag::parse(&bytes).unwrap(), expected);
+    }
+
+    #[test]
+    fn cant_parse_truncated_keybag() {
+        let mut bytes = field(b"SALT", &[0x01; 20]);
+        bytes.truncate(10);
+        assert!(Keybag::parse(&bytes).is_err());
+        assert!(Keybag::parse(&[]).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+/*!
+ Contains logic to read objects from `NSKeyedArchiver` data.
+
+ Unlike [`parse_plist()`](crate::util::plist::parse_plist), which flattens an archive into a single [`Value`],
+ this keeps the class of each object, so archived types like `NSAttributedString` can be decoded field by field.
+*/
+
+use std::io::Cursor;
+
+use plist::{Dictionary, Value};
+
+use crate::{
+    error::plist::PlistParseError,
+    util::plist::{extract_array_key, extract_dictionary, extract_uid_key},
+};
+
+/// The bytes that start binary plist data
+const BINARY_PLIST_HEADER: &[u8] = b"bplist";
+
+/// Determine if some bytes are a binary plist, like the data written by `NSKeyedArchiver`
+pub fn is_binary_plist(bytes: &[u8]) -> bool {
+    bytes.starts_with(BINARY_PLIST_HEADER)
+}
+
+/// An `NSKeyedArchiver` document
+///
+/// Archived objects are stored in a table called `$objects`, and refer to each other by their index in it.
+/// A `$top` entry points to the object that was archived.
+#[derive(Debug)]
+pub struct KeyedArchive {
+    /// The table of archived objects
+    objects: Vec<Value>,
+    /// The index of the archived object in [`KeyedArchive::objects`]
+    root: usize,
+}
+
+impl KeyedArchive {
+    /// Read an archive from binary or XML plist data
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PlistParseError> {
+        let plist =
+            Value::from_reader(Cursor::new(bytes)).map_err(PlistParseError::InvalidPlist)?;
+        let body = plist.as_dictionary().ok_or_else(|| {
+            PlistParseError::InvalidType("body".to_string(), "dictionary".to_string())
+        })?;
+
+        Ok(Self {
+            objects: extract_array_key(body, "$objects")?.to_vec(),
+            root: extract_uid_key(extract_dictionary(body, "$top")?, "root")?,
+        })
+    }
+
+    /// Get the archived object
+    pub fn root(&self) -> Result<&Value, PlistParseError> {
+        self.object(self.root)
+    }
+
+    /// Get the object at an index in the table of archived objects
+    pub fn object(&self, idx: usize) -> Result<&Value, PlistParseError> {
+        self.objects
+            .get(idx)
+            .ok_or(PlistParseError::NoValueAtIndex(idx))
+    }
+
+    /// Get the object a value points to, or the value itself if it is not a pointer
+    pub fn resolve<'a>(&'a self, value: &'a Value) -> Result<&'a Value, PlistParseError> {
+        match value.as_uid() {
+            Some(uid) => self.object(uid.get() as usize),
+            None => Ok(value),
+        }
+    }
+
+    /// Get the object stored at a key of an archived object
+    pub fn get<'a>(&'a self, object: &'a Value, key: &str) -> Result<&'a Value, PlistParseError> {
+        let value = object
+            .as_dictionary()
+            .and_then(|dict| dict.get(key))
+            .ok_or_else(|| PlistParseError::MissingKey(key.to_string()))?;
+        self.resolve(value)
+    }
+
+    /// Get the name of the class of an archived object, i.e. `NSMutableString`
+    pub fn class_name<'a>(&'a self, object: &'a Value) -> Option<&'a str> {
+        self.get(object, "$class")
+            .ok()?
+            .as_dictionary()?
+            .get("$classname")?
+            .as_string()
+    }
+
+    /// Get the items of an archived `NSArray`
+    pub fn array<'a>(&'a self, object: &'a Value) -> Result<Vec<&'a Value>, PlistParseError> {
+        self.items(object, "NS.objects")
+    }
+
+    /// Get the key and value pairs of an archived `NSDictionary`, in the order they are stored
+    pub fn dictionary<'a>(
+        &'a self,
+        object: &'a Value,
+    ) -> Result<Vec<(&'a str, &'a Value)>, PlistParseError> {
+        let keys = self.items(object, "NS.keys")?;
+        let values = self.items(object, "NS.objects")?;
+        if keys.len() != values.len() {
+            return Err(PlistParseError::InvalidDictionarySize(
+                keys.len(),
+                values.len(),
+            ));
+        }
+
+        keys.into_iter()
+            .zip(values)
+            .enumerate()
+            .map(|(idx, (key, value))| match key.as_string() {
+                Some(key) => Ok((key, value)),
+                None => Err(PlistParseError::InvalidTypeIndex(idx, "string".to_string())),
+            })
+            .collect()
+    }
+
+    /// Get the text of an archived `NSString` or `NSMutableString`
+    pub fn string<'a>(&'a self, object: &'a Value) -> Option<&'a str> {
+        match object {
+            Value::String(text) => Some(text),
+            _ => self.get(object, "NS.string").ok()?.as_string(),
+        }
+    }
+
+    /// Get the bytes of an archived `NSData` or `NSMutableData`
+    pub fn data<'a>(&'a self, object: &'a Value) -> Option<&'a [u8]> {
+        match object {
+            Value::Data(bytes) => Some(bytes),
+            _ => self.get(object, "NS.data").ok()?.as_data(),
+        }
+    }
+
+    /// Resolve every pointer in the array stored at a key of an archived object
+    fn items<'a>(
+        &'a self,
+        object: &'a Value,
+        key: &str,
+    ) -> Result<Vec<&'a Value>, PlistParseError> {
+        let dict: &Dictionary = object.as_dictionary().ok_or_else(|| {
+            PlistParseError::InvalidType(key.to_string(), "dictionary".to_string())
+        })?;
+        extract_array_key(dict, key)?
+            .iter()
+            .map(|item| self.resolve(item))
+            .collect()
+    }
+}
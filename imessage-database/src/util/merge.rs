@@ -0,0 +1,567 @@
+/*!
+ Contains logic for merging several Messages databases, i.e. from an old Mac, a new Mac, and an iPhone backup, into one.
+
+ Each database is first copied with `VACUUM INTO`, so the originals are never written to. The first database is kept
+ as-is, and rows from the others are added to it when they are not already present:
+
+ - Messages, conversations, and attachments are matched by their `guid`
+ - Handles are matched by their `id` and `service`
+ - Join tables are rebuilt from the matched rows, since `ROWID`s differ between databases
+
+ Only columns that exist in both databases are copied. Attachments from iOS backups keep their `filename`, and the
+ root of their backup is stored in the [`BACKUP_ROOT`] column, so their files can still be found when the merged
+ database is read like one from macOS.
+
+ Android [SMS backups](crate::util::sms_backup) can be merged too, after the first database. They are converted
+ to a database laid out like `chat.db`, and their attachments are written to the `android` folder of the destination.
+*/
+
+use std::{
+    fs::{create_dir_all, remove_file},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::{
+    error::table::TableError,
+    tables::table::{
+        get_connection, ATTACHMENT, CHAT, CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN, HANDLE, MESSAGE,
+        MESSAGE_ATTACHMENT_JOIN,
+    },
+    util::{
+        backup::sms_db_path,
+        platform::Platform,
+        sms_backup::{import_sms_backup, is_sms_backup},
+    },
+};
+
+/// The name of the merged database written to the destination folder
+pub const MERGED_DB: &str = "merged.db";
+/// The folder in the destination that attachments from Android SMS backups are written to
+pub const ANDROID_ATTACHMENTS_DIR: &str = "android";
+/// The column added to the `attachment` table of the merged database that holds the root of the iOS backup an attachment was read from
+pub const BACKUP_ROOT: &str = "backup_root";
+
+/// Rows in conversations, messages, and attachments are matched by their `guid`
+const GUID_KEY: &[&str] = &["guid"];
+/// Handles are matched by their address and service, since they do not have a `guid`
+const HANDLE_KEY: &[&str] = &["id", "service"];
+
+/// A database to merge
+#[derive(Debug)]
+pub struct MergeSource {
+    /// The path to a `chat.db` file, the root of an iOS backup, or an Android SMS backup
+    pub path: PathBuf,
+    /// The platform that created the database
+    pub platform: Platform,
+}
+
+impl MergeSource {
+    /// Get the path to the database file
+    fn db_path(&self) -> PathBuf {
+        match self.platform {
+            Platform::macOS => self.path.clone(),
+            Platform::iOS => sms_db_path(&self.path),
+        }
+    }
+}
+
+/// A column that refers to a row in another table, and how rows in that table are matched
+struct Link {
+    column: &'static str,
+    table: &'static str,
+    key: &'static [&'static str],
+}
+
+/// Merge `sources` into a single database in the `destination` folder, returning its path and the number
+/// of messages each source added
+///
+/// The first source is copied in full, so every message in it is counted. Messages in later sources that are
+/// already in the merged database are skipped. The first source must be a Messages database, not an SMS backup.
+///
+/// Phone numbers in SMS backups that were saved without a country code are given `country_code`.
+///
+/// # Example:
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use imessage_database::util::{merge::{merge_databases, MergeSource}, platform::Platform};
+///
+/// let sources = [
+///     MergeSource { path: PathBuf::from("/path/to/new/chat.db"), platform: Platform::macOS },
+///     MergeSource { path: PathBuf::from("/path/to/old/chat.db"), platform: Platform::macOS },
+/// ];
+/// let (merged, added) = merge_databases(&sources, &PathBuf::from("/tmp/merged"), "1").unwrap();
+/// ```
+pub fn merge_databases(
+    sources: &[MergeSource],
+    destination: &Path,
+    country_code: &str,
+) -> Result<(PathBuf, Vec<usize>), TableError> {
+    create_dir_all(destination).map_err(TableError::CannotRead)?;
+    let merged_path = destination.join(MERGED_DB);
+    let mut added = Vec::with_capacity(sources.len());
+
+    for (index, source) in sources.iter().enumerate() {
+        // The first database becomes the merged one, the others are merged into it
+        if index == 0 {
+            snapshot(source, &merged_path)?;
+            let db = open(&merged_path)?;
+            added.push(count_messages(&db).map_err(TableError::Merge)?);
+            continue;
+        }
+
+        let snapshot_path = destination.join(format!("source-{index}.db"));
+        match is_sms_backup(&source.path) {
+            true => {
+                remove(&snapshot_path)?;
+                import_sms_backup(
+                    &source.path,
+                    &snapshot_path,
+                    &destination.join(ANDROID_ATTACHMENTS_DIR),
+                    country_code,
+                )
+                .map_err(TableError::SmsBackup)?;
+            }
+            false => snapshot(source, &snapshot_path)?,
+        }
+        let db = open(&merged_path)?;
+        added.push(merge_snapshot(&db, &snapshot_path).map_err(TableError::Merge)?);
+        drop(db);
+        remove(&snapshot_path)?;
+    }
+
+    Ok((merged_path, added))
+}
+
+/// Copy a source database to `path`, preparing the copy to be merged
+fn snapshot(source: &MergeSource, path: &Path) -> Result<(), TableError> {
+    remove(path)?;
+    get_connection(&source.db_path())?
+        .execute("VACUUM INTO ?1", [path.to_string_lossy()])
+        .map_err(TableError::Merge)?;
+
+    let db = open(path)?;
+    drop_triggers(&db).map_err(TableError::Merge)?;
+    let backup = (source.platform == Platform::iOS).then_some(source.path.as_path());
+    record_backup_root(&db, backup).map_err(TableError::Attachment)?;
+    Ok(())
+}
+
+/// Open a copied database for writing
+fn open(path: &Path) -> Result<Connection, TableError> {
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE).map_err(|why| {
+        TableError::CannotConnect(format!("Unable to open {}: {why}", path.display()))
+    })
+}
+
+/// Delete a file, if it exists
+fn remove(path: &Path) -> Result<(), TableError> {
+    match remove_file(path) {
+        Err(why) if why.kind() != ErrorKind::NotFound => Err(TableError::CannotRead(why)),
+        _ => Ok(()),
+    }
+}
+
+/// Drop the triggers Messages adds to its tables, as they call functions that only exist inside Messages
+fn drop_triggers(db: &Connection) -> Result<(), rusqlite::Error> {
+    let triggers = db
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'trigger'")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for trigger in triggers {
+        db.execute(&format!("DROP TRIGGER \"{trigger}\""), [])?;
+    }
+    Ok(())
+}
+
+/// Add the [`BACKUP_ROOT`] column to the attachments of a copied database, filling it in if they are from an iOS backup
+///
+/// The files in a backup are named for the hash of their path, so they are found when the attachment is read.
+fn record_backup_root(db: &Connection, backup: Option<&Path>) -> Result<(), rusqlite::Error> {
+    db.execute(
+        &format!("ALTER TABLE {ATTACHMENT} ADD COLUMN {BACKUP_ROOT} TEXT"),
+        [],
+    )?;
+    if let Some(backup) = backup {
+        db.execute(
+            &format!("UPDATE {ATTACHMENT} SET {BACKUP_ROOT} = ?1 WHERE filename IS NOT NULL"),
+            [backup.to_string_lossy()],
+        )?;
+    }
+    Ok(())
+}
+
+/// Add the rows from a copied database that the merged database does not have yet
+fn merge_snapshot(db: &Connection, snapshot: &Path) -> Result<usize, rusqlite::Error> {
+    db.execute("ATTACH DATABASE ?1 AS source", [snapshot.to_string_lossy()])?;
+    let before = count_messages(db)?;
+
+    copy_rows(db, HANDLE, HANDLE_KEY, &[])?;
+    copy_rows(db, CHAT, GUID_KEY, &[])?;
+    copy_rows(db, ATTACHMENT, GUID_KEY, &[])?;
+    copy_rows(
+        db,
+        MESSAGE,
+        GUID_KEY,
+        &[
+            ("handle_id", remap_handle("handle_id")),
+            ("other_handle", remap_handle("other_handle")),
+        ],
+    )?;
+
+    let chat = Link {
+        column: "chat_id",
+        table: CHAT,
+        key: GUID_KEY,
+    };
+    let message = Link {
+        column: "message_id",
+        table: MESSAGE,
+        key: GUID_KEY,
+    };
+    copy_joins(
+        db,
+        CHAT_HANDLE_JOIN,
+        &chat,
+        &Link {
+            column: "handle_id",
+            table: HANDLE,
+            key: HANDLE_KEY,
+        },
+    )?;
+    copy_joins(db, CHAT_MESSAGE_JOIN, &chat, &message)?;
+    copy_joins(
+        db,
+        MESSAGE_ATTACHMENT_JOIN,
+        &message,
+        &Link {
+            column: "attachment_id",
+            table: ATTACHMENT,
+            key: GUID_KEY,
+        },
+    )?;
+
+    let after = count_messages(db)?;
+    db.execute("DETACH DATABASE source", [])?;
+    Ok(after - before)
+}
+
+/// Count the messages in a database
+fn count_messages(db: &Connection) -> Result<usize, rusqlite::Error> {
+    db.query_row(&format!("SELECT COUNT(*) FROM main.{MESSAGE}"), [], |row| {
+        row.get(0)
+    })
+}
+
+/// Get the columns of a table that exist in both the merged and the source database, other than the `ROWID`
+fn shared_columns(db: &Connection, table: &str) -> Result<Vec<String>, rusqlite::Error> {
+    let source_columns = db
+        .prepare("SELECT name FROM pragma_table_info(?1, 'source')")?
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(db
+        .prepare("SELECT name FROM pragma_table_info(?1, 'main')")?
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|column| !column.eq_ignore_ascii_case("ROWID") && source_columns.contains(column))
+        .collect())
+}
+
+/// Build a condition that matches a row in one table to the same row in another
+fn matches(key: &[&str], source: &str, main: &str) -> String {
+    key.iter()
+        .map(|column| format!("{source}.\"{column}\" = {main}.\"{column}\""))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Build an expression that finds the merged `ROWID` of the handle in a message column
+fn remap_handle(column: &str) -> String {
+    format!(
+        "COALESCE((SELECT m.ROWID FROM main.{HANDLE} AS m JOIN source.{HANDLE} AS h ON {} WHERE h.ROWID = s.\"{column}\"), 0)",
+        matches(HANDLE_KEY, "h", "m")
+    )
+}
+
+/// Copy the rows of a table that are not in the merged database yet, replacing the values of `remapped` columns
+fn copy_rows(
+    db: &Connection,
+    table: &str,
+    key: &[&str],
+    remapped: &[(&str, String)],
+) -> Result<usize, rusqlite::Error> {
+    let columns = shared_columns(db, table)?;
+    if columns.is_empty() {
+        return Ok(0);
+    }
+
+    let values = columns
+        .iter()
+        .map(|column| {
+            remapped
+                .iter()
+                .find(|(name, _)| name == column)
+                .map_or_else(|| format!("s.\"{column}\""), |(_, value)| value.clone())
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    db.execute(
+        &format!(
+            "INSERT OR IGNORE INTO main.{table} ({}) SELECT {values} FROM source.{table} AS s WHERE NOT EXISTS (SELECT 1 FROM main.{table} AS m WHERE {})",
+            quote(&columns),
+            matches(key, "s", "m")
+        ),
+        [],
+    )
+}
+
+/// Copy the rows of a join table, pointing them at the merged rows they join
+fn copy_joins(
+    db: &Connection,
+    table: &str,
+    left: &Link,
+    right: &Link,
+) -> Result<usize, rusqlite::Error> {
+    let columns = shared_columns(db, table)?;
+    if !columns.iter().any(|column| column == left.column)
+        || !columns.iter().any(|column| column == right.column)
+    {
+        return Ok(0);
+    }
+
+    let values = columns
+        .iter()
+        .map(|column| match column.as_str() {
+            name if name == left.column => String::from("ml.ROWID"),
+            name if name == right.column => String::from("mr.ROWID"),
+            _ => format!("s.\"{column}\""),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    db.execute(
+        &format!(
+            "INSERT OR IGNORE INTO main.{table} ({}) SELECT {values} FROM source.{table} AS s \
+            JOIN source.{} AS sl ON sl.ROWID = s.{} JOIN main.{} AS ml ON {} \
+            JOIN source.{} AS sr ON sr.ROWID = s.{} JOIN main.{} AS mr ON {} \
+            WHERE NOT EXISTS (SELECT 1 FROM main.{table} AS m WHERE m.{} = ml.ROWID AND m.{} = mr.ROWID)",
+            quote(&columns),
+            left.table,
+            left.column,
+            left.table,
+            matches(left.key, "sl", "ml"),
+            right.table,
+            right.column,
+            right.table,
+            matches(right.key, "sr", "mr"),
+            left.column,
+            right.column,
+        ),
+        [],
+    )
+}
+
+/// Quote a list of column names for use in a query
+fn quote(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|column| format!("\"{column}\""))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs::remove_dir_all, path::Path};
+
+    use rusqlite::Connection;
+
+    use crate::util::{
+        merge::{merge_databases, MergeSource, BACKUP_ROOT},
+        platform::Platform,
+    };
+
+    fn create_database(path: &Path, rows: &str) {
+        let db = Connection::open(path).unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE handle (ROWID INTEGER PRIMARY KEY AUTOINCREMENT, id TEXT, service TEXT, UNIQUE (id, service));",
+            "CREATE TABLE chat (ROWID INTEGER PRIMARY KEY AUTOINCREMENT, guid TEXT UNIQUE, chat_identifier TEXT);",
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY AUTOINCREMENT, guid TEXT UNIQUE, text TEXT, handle_id INTEGER, other_handle INTEGER);",
+            "CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY AUTOINCREMENT, guid TEXT UNIQUE, filename TEXT);",
+            "CREATE TABLE chat_handle_join (chat_id INTEGER, handle_id INTEGER, UNIQUE (chat_id, handle_id));",
+            "CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER, message_date INTEGER, PRIMARY KEY (chat_id, message_id));",
+            "CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER, UNIQUE (message_id, attachment_id));",
+        ))
+        .unwrap();
+        db.execute_batch(rows).unwrap();
+        db.execute_batch(
+            "CREATE TRIGGER after_insert_on_message AFTER INSERT ON message BEGIN SELECT missing_function(); END;",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn can_merge_databases() {
+        let folder = temp_dir().join("imessage-database-merge");
+        let _ = remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+
+        let new = folder.join("new.db");
+        let old = folder.join("old.db");
+        create_database(
+            &new,
+            concat!(
+                "INSERT INTO handle VALUES (1, '+15558675309', 'iMessage');",
+                "INSERT INTO chat VALUES (1, 'iMessage;-;+15558675309', '+15558675309');",
+                "INSERT INTO message VALUES (1, 'shared', 'Hello', 1, 0);",
+                "INSERT INTO chat_handle_join VALUES (1, 1);",
+                "INSERT INTO chat_message_join VALUES (1, 1, 100);",
+            ),
+        );
+        create_database(
+            &old,
+            concat!(
+                "INSERT INTO handle VALUES (1, 'friend@example.com', 'iMessage');",
+                "INSERT INTO handle VALUES (2, '+15558675309', 'iMessage');",
+                "INSERT INTO chat VALUES (1, 'iMessage;-;friend@example.com', 'friend@example.com');",
+                "INSERT INTO chat VALUES (2, 'iMessage;-;+15558675309', '+15558675309');",
+                "INSERT INTO message VALUES (1, 'old', 'Old message', 1, 0);",
+                "INSERT INTO message VALUES (2, 'shared', 'Hello', 2, 0);",
+                "INSERT INTO message VALUES (3, 'older', 'Older message', 2, 0);",
+                "INSERT INTO attachment VALUES (1, 'attachment', '~/Library/Messages/Attachments/a/b/c.png');",
+                "INSERT INTO chat_handle_join VALUES (1, 1);",
+                "INSERT INTO chat_handle_join VALUES (2, 2);",
+                "INSERT INTO chat_message_join VALUES (1, 1, 50);",
+                "INSERT INTO chat_message_join VALUES (2, 2, 100);",
+                "INSERT INTO chat_message_join VALUES (2, 3, 25);",
+                "INSERT INTO message_attachment_join VALUES (3, 1);",
+            ),
+        );
+
+        let sources = [
+            MergeSource {
+                path: new,
+                platform: Platform::macOS,
+            },
+            MergeSource {
+                path: old,
+                platform: Platform::macOS,
+            },
+        ];
+        let (merged, added) = merge_databases(&sources, &folder.join("merged"), "1").unwrap();
+        assert_eq!(added, vec![1, 2]);
+
+        let db = Connection::open(merged).unwrap();
+        let count = |query: &str| db.query_row(query, [], |row| row.get::<_, i32>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM handle"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM chat"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM chat_handle_join"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM chat_message_join"), 3);
+
+        // Messages point at the merged handles and conversations
+        assert_eq!(
+            count("SELECT h.ROWID FROM message m JOIN handle h ON h.ROWID = m.handle_id WHERE m.guid = 'older'"),
+            1
+        );
+        assert_eq!(
+            count("SELECT j.chat_id FROM chat_message_join j JOIN message m ON m.ROWID = j.message_id WHERE m.guid = 'older'"),
+            1
+        );
+        assert_eq!(
+            count("SELECT COUNT(*) FROM message_attachment_join j JOIN message m ON m.ROWID = j.message_id WHERE m.guid = 'older'"),
+            1
+        );
+        assert_eq!(
+            count("SELECT COUNT(*) FROM sqlite_master WHERE type = 'trigger'"),
+            0
+        );
+        // Attachments from macOS are not in a backup
+        assert_eq!(
+            count("SELECT COUNT(*) FROM attachment WHERE backup_root IS NULL"),
+            1
+        );
+    }
+
+    #[test]
+    fn can_merge_sms_backup() {
+        let folder = temp_dir().join("imessage-database-merge-sms");
+        let _ = remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+
+        let mac = folder.join("chat.db");
+        create_database(
+            &mac,
+            concat!(
+                "INSERT INTO handle VALUES (1, '+15558675309', 'SMS');",
+                "INSERT INTO chat VALUES (1, 'SMS;-;+15558675309', '+15558675309');",
+                "INSERT INTO message VALUES (1, 'mac', 'From the Mac', 1, 0);",
+                "INSERT INTO chat_handle_join VALUES (1, 1);",
+                "INSERT INTO chat_message_join VALUES (1, 1, 100);",
+            ),
+        );
+        let android = folder.join("sms.xml");
+        std::fs::write(
+            &android,
+            r#"<smses count="1"><sms address="(555) 867-5309" date="978307200000" type="1" body="From Android" read="1" /></smses>"#,
+        )
+        .unwrap();
+
+        let sources = [
+            MergeSource {
+                path: mac,
+                platform: Platform::macOS,
+            },
+            MergeSource {
+                path: android,
+                platform: Platform::macOS,
+            },
+        ];
+        let (merged, added) = merge_databases(&sources, &folder.join("merged"), "1").unwrap();
+        assert_eq!(added, vec![1, 1]);
+
+        // The Android message joins the existing SMS conversation
+        let db = Connection::open(merged).unwrap();
+        let count = |query: &str| db.query_row(query, [], |row| row.get::<_, i32>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM handle"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM chat"), 1);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM chat_message_join WHERE chat_id = 1"),
+            2
+        );
+    }
+
+    #[test]
+    fn can_keep_ios_attachment_filenames() {
+        let folder = temp_dir().join("imessage-database-merge-ios");
+        let _ = remove_dir_all(&folder);
+        let backup = folder.join("backup");
+        std::fs::create_dir_all(backup.join("3d")).unwrap();
+        create_database(
+            &backup.join("3d").join("3d0d7e5fb2ce288813306e4d4636395e047a3d28"),
+            "INSERT INTO attachment VALUES (1, 'attachment', '~/Library/SMS/Attachments/a/b/IMG_0001.HEIC');",
+        );
+
+        let sources = [MergeSource {
+            path: backup.clone(),
+            platform: Platform::iOS,
+        }];
+        let (merged, _) = merge_databases(&sources, &folder.join("merged"), "1").unwrap();
+
+        let db = Connection::open(merged).unwrap();
+        let (filename, root): (String, String) = db
+            .query_row(
+                &format!("SELECT filename, {BACKUP_ROOT} FROM attachment"),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(filename, "~/Library/SMS/Attachments/a/b/IMG_0001.HEIC");
+        assert_eq!(root, backup.to_string_lossy());
+    }
+}
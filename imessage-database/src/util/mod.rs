@@ -2,12 +2,22 @@
  This module defines common utilities used across table queries.
 */
 
+pub mod attributed_body;
+pub mod backup;
+pub mod contacts;
 pub mod dates;
 pub mod dirs;
+#[cfg(feature = "encrypted-backups")]
+pub mod encrypted_backup;
+pub mod keyed_archive;
+pub mod merge;
 pub mod output;
+pub mod phone;
+pub mod pinned;
 pub mod platform;
 pub mod plist;
 pub mod query_context;
 pub mod size;
+pub mod sms_backup;
 pub mod streamtyped;
 pub mod typedstream;
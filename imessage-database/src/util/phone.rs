@@ -0,0 +1,158 @@
+/*!
+ Contains logic for normalizing phone numbers to the [E.164](https://en.wikipedia.org/wiki/E.164) format.
+
+ Messages stores each handle as it was first seen, so the same phone number can appear as `+1 (555) 867-5309`,
+ `5558675309`, and `+15558675309`. Normalizing handles lets them be compared regardless of how they were written.
+*/
+
+/// The country code used for phone numbers that were saved without one
+pub const DEFAULT_COUNTRY_CODE: &str = "1";
+/// The fewest digits in a phone number; anything shorter is a short code, i.e. `12345`
+const MIN_PHONE_DIGITS: usize = 7;
+/// The most digits in an E.164 phone number, including its country code
+const MAX_PHONE_DIGITS: usize = 15;
+/// The prefix used to dial an international number in most countries
+const INTERNATIONAL_PREFIX: &str = "00";
+/// The prefix used to dial a national number in most countries outside North America
+const TRUNK_PREFIX: char = '0';
+
+/// Normalize a phone number to E.164, i.e. `+15558675309`
+///
+/// Numbers that do not start with `+` or an international prefix are given `country_code`.
+/// Returns `None` if `number` is not a phone number, i.e. an email address or a short code.
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::phone::normalize_phone_number;
+///
+/// assert_eq!(normalize_phone_number("(555) 867-5309", "1"), Some("+15558675309".to_string()));
+/// assert_eq!(normalize_phone_number("07911 123456", "44"), Some("+447911123456".to_string()));
+/// assert_eq!(normalize_phone_number("jane@example.com", "1"), None);
+/// ```
+pub fn normalize_phone_number(number: &str, country_code: &str) -> Option<String> {
+    let number = number.trim();
+    if number.is_empty()
+        || !number
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | '(' | ')' | ' '))
+    {
+        return None;
+    }
+
+    let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+    if digits.len() < MIN_PHONE_DIGITS {
+        return None;
+    }
+
+    let country_code = country_code.trim_start_matches('+');
+    let international = if number.starts_with('+') {
+        digits
+    } else if let Some(international) = digits.strip_prefix(INTERNATIONAL_PREFIX) {
+        international.to_string()
+    } else if let Some(national) = digits.strip_prefix(TRUNK_PREFIX) {
+        format!("{country_code}{national}")
+    } else if digits.starts_with(country_code)
+        && national_digits(country_code)
+            .is_some_and(|national| digits.len() == country_code.len() + national)
+    {
+        // The number already has its country code, just without the `+`
+        digits
+    } else {
+        format!("{country_code}{digits}")
+    };
+
+    (international.len() <= MAX_PHONE_DIGITS).then(|| format!("+{international}"))
+}
+
+/// Normalize a handle so that handles for the same phone number or email address are equal
+///
+/// Phone numbers are normalized with [`normalize_phone_number`] and email addresses are compared without case.
+/// Anything else, like a short code, is returned without surrounding whitespace.
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::phone::normalize_handle;
+///
+/// assert_eq!(normalize_handle("+1 (555) 867-5309", "1"), normalize_handle("5558675309", "1"));
+/// assert_eq!(normalize_handle("Jane@Example.com", "1"), "jane@example.com");
+/// ```
+pub fn normalize_handle(handle: &str, country_code: &str) -> String {
+    let handle = handle.trim();
+    if handle.contains('@') {
+        return handle.to_lowercase();
+    }
+    normalize_phone_number(handle, country_code).unwrap_or_else(|| handle.to_string())
+}
+
+/// Determine if a country code is valid, i.e. `1` or `+44`
+pub fn is_country_code(country_code: &str) -> bool {
+    let digits = country_code.strip_prefix('+').unwrap_or(country_code);
+    (1..=3).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The number of digits in a national number, for countries where every number has the same length
+///
+/// This is used to recognize numbers that were saved with their country code but without a `+`.
+/// Other countries are assumed to need the `+`, since their national numbers may start with the country code.
+fn national_digits(country_code: &str) -> Option<usize> {
+    match country_code {
+        // North American Numbering Plan
+        "1" => Some(10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::phone::{is_country_code, normalize_handle, normalize_phone_number};
+
+    #[test]
+    fn can_normalize_us_numbers() {
+        let expected = Some("+15558675309".to_string());
+        assert_eq!(normalize_phone_number("+1 (555) 867-5309", "1"), expected);
+        assert_eq!(normalize_phone_number("5558675309", "1"), expected);
+        assert_eq!(normalize_phone_number("+15558675309", "1"), expected);
+        assert_eq!(normalize_phone_number("15558675309", "1"), expected);
+        assert_eq!(normalize_phone_number("555.867.5309", "1"), expected);
+    }
+
+    #[test]
+    fn can_normalize_international_numbers() {
+        let expected = Some("+447911123456".to_string());
+        assert_eq!(normalize_phone_number("07911 123456", "44"), expected);
+        assert_eq!(normalize_phone_number("+44 7911 123456", "1"), expected);
+        assert_eq!(normalize_phone_number("0044 7911 123456", "1"), expected);
+        assert_eq!(normalize_phone_number("7911 123456", "+44"), expected);
+    }
+
+    #[test]
+    fn cant_normalize_non_numbers() {
+        assert_eq!(normalize_phone_number("jane@example.com", "1"), None);
+        assert_eq!(normalize_phone_number("12345", "1"), None);
+        assert_eq!(normalize_phone_number("chat123456789", "1"), None);
+        assert_eq!(normalize_phone_number("", "1"), None);
+        assert_eq!(normalize_phone_number("+1234567890123456", "1"), None);
+    }
+
+    #[test]
+    fn can_normalize_handles() {
+        assert_eq!(
+            normalize_handle(" Jane@Example.com ", "1"),
+            "jane@example.com"
+        );
+        assert_eq!(normalize_handle("(555) 867-5309", "1"), "+15558675309");
+        assert_eq!(normalize_handle("12345", "1"), "12345");
+    }
+
+    #[test]
+    fn can_validate_country_codes() {
+        assert!(is_country_code("1"));
+        assert!(is_country_code("+44"));
+        assert!(is_country_code("353"));
+        assert!(!is_country_code(""));
+        assert!(!is_country_code("1234"));
+        assert!(!is_country_code("uk"));
+    }
+}
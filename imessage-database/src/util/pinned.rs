@@ -0,0 +1,75 @@
+/*!
+ Contains logic for reading the conversations the user pinned in Messages.
+
+ Pins are not stored in the Messages database. On macOS, they are stored in the
+ `com.apple.messages.pinning` preferences, which list the identifier of each pinned chat.
+*/
+
+use std::path::Path;
+
+use plist::Value;
+
+/// Default location of the pinned conversation preferences on macOS, relative to the home directory
+pub const DEFAULT_PINNING_PATH_MACOS: &str = "Library/Preferences/com.apple.messages.pinning.plist";
+
+/// Read the identifiers of the pinned conversations from a pinning preferences file
+///
+/// The layout of the file is not documented, so every string in it is treated as a possible chat
+/// identifier; values that are not identifiers will not match any chat.
+///
+/// # Example:
+///
+/// ```no_run
+/// use std::path::Path;
+/// use imessage_database::util::pinned::pinned_identifiers;
+///
+/// let pinned = pinned_identifiers(Path::new("com.apple.messages.pinning.plist")).unwrap();
+/// ```
+pub fn pinned_identifiers(path: &Path) -> Result<Vec<String>, plist::Error> {
+    let mut identifiers = vec![];
+    collect_strings(&Value::from_file(path)?, &mut identifiers);
+    Ok(identifiers)
+}
+
+/// Recursively collect every string stored in a plist value
+fn collect_strings(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(string) => out.push(string.to_string()),
+        Value::Array(array) => array.iter().for_each(|item| collect_strings(item, out)),
+        Value::Dictionary(dictionary) => dictionary
+            .values()
+            .for_each(|item| collect_strings(item, out)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use crate::util::pinned::collect_strings;
+
+    #[test]
+    fn can_collect_nested_strings() {
+        let mut pinned = Dictionary::new();
+        pinned.insert(
+            "pP".to_string(),
+            Value::Array(vec![
+                Value::String("iMessage;-;+15558675309".to_string()),
+                Value::String("iMessage;+;chat123456789".to_string()),
+            ]),
+        );
+        pinned.insert("pV".to_string(), Value::Integer(2.into()));
+        let mut root = Dictionary::new();
+        root.insert("pD".to_string(), Value::Dictionary(pinned));
+
+        let mut out = vec![];
+        collect_strings(&Value::Dictionary(root), &mut out);
+        out.sort();
+
+        assert_eq!(
+            out,
+            vec!["iMessage;+;chat123456789", "iMessage;-;+15558675309"]
+        );
+    }
+}
@@ -4,10 +4,10 @@
 
 use std::{fmt::Display, path::Path};
 
-use crate::tables::table::DEFAULT_PATH_IOS;
+use crate::util::backup::is_backup;
 
 /// Represents the platform that created the database this library connects to
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub enum Platform {
     /// macOS-sourced data
     #[allow(non_camel_case_types)]
@@ -20,7 +20,7 @@ pub enum Platform {
 impl Platform {
     /// Try to determine the current platform, defaulting to macOS.
     pub fn determine(db_path: &Path) -> Self {
-        if db_path.join(DEFAULT_PATH_IOS).exists() {
+        if is_backup(db_path) {
             return Self::iOS;
         } else if db_path.is_file() {
             return Self::macOS;
@@ -171,7 +171,7 @@ pub fn extract_array_key<'a>(
 }
 
 /// Extract a Uid from a specific key in a collection
-fn extract_uid_key(body: &Dictionary, key: &str) -> Result<usize, PlistParseError> {
+pub(crate) fn extract_uid_key(body: &Dictionary, key: &str) -> Result<usize, PlistParseError> {
     Ok(body
         .get(key)
         .ok_or_else(|| PlistParseError::MissingKey(key.to_string()))?
@@ -1,13 +1,23 @@
 /*!
  Contains logic for handling query filter configurations.
 */
+use std::collections::BTreeSet;
+
 use chrono::prelude::*;
 
 use crate::{
     error::query_context::QueryContextError,
+    tables::table::{
+        ATTACHMENT, CHAT, CHAT_MESSAGE_JOIN, MESSAGE, MESSAGE_ATTACHMENT_JOIN, RECENTLY_DELETED,
+    },
     util::dates::{get_offset, TIMESTAMP_FACTOR},
 };
 
+/// The kinds of attachments that can be filtered on, matching [`MediaType::category()`](crate::tables::attachment::MediaType::category)
+pub const ATTACHMENT_TYPES: [&str; 5] = ["image", "video", "audio", "text", "application"];
+/// The UTI of audio messages, which are stored without a MIME type
+const AUDIO_MESSAGE_UTI: &str = "com.apple.coreaudio-format";
+
 #[derive(Debug, Default, PartialEq, Eq)]
 /// Represents filter configurations for a SQL query.
 pub struct QueryContext {
@@ -15,6 +25,28 @@ pub struct QueryContext {
     pub start: Option<i64>,
     /// The end date filter. Only messages sent before this date will be included.
     pub end: Option<i64>,
+    /// The selected chat filter. Only messages in these chats will be included.
+    pub selected_chat_ids: Option<BTreeSet<i32>>,
+    /// The service filter. Only messages sent with this service, i.e. `iMessage` or `SMS`, will be included.
+    pub service: Option<&'static str>,
+    /// The attachment filter. Only messages with an attachment of one of these kinds, i.e. `image`, will be included.
+    pub attachment_types: Option<BTreeSet<&'static str>>,
+    /// The sender filter. If `true`, only messages sent by the database owner will be included; if `false`, only messages they received.
+    pub from_me: Option<bool>,
+    /// If `true`, tapbacks and the stickers placed on messages will not be included.
+    pub skip_tapbacks: bool,
+    /// If `true`, system messages like group renames, participant changes, and location sharing notices will not be included.
+    pub skip_announcements: bool,
+    /// The thread filter. Only the message with this GUID, the message it replied to, and the rest of their thread will be included.
+    pub thread_guid: Option<String>,
+    /// The recent message filter. Only this many of the most recent messages in each chat will be included.
+    pub recent: Option<usize>,
+    /// If `true`, only received messages that have not been read will be included.
+    pub unread: bool,
+    /// If `true`, only messages that were deleted but can still be recovered will be included.
+    pub deleted: bool,
+    /// If `true`, messages that do not belong to a chat, or whose chat no longer exists, are kept when chats are selected.
+    pub include_orphaned: bool,
 }
 
 impl QueryContext {
@@ -50,6 +82,196 @@ impl QueryContext {
         Ok(())
     }
 
+    /// Restrict the `QueryContext` to messages in the given chats
+    /// # Example:
+    ///
+    /// ```
+    /// use std::collections::BTreeSet;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_selected_chat_ids(BTreeSet::from([1, 2, 3]));
+    /// ```
+    pub fn set_selected_chat_ids(&mut self, selected_chat_ids: BTreeSet<i32>) {
+        self.selected_chat_ids = Some(selected_chat_ids);
+    }
+
+    /// Restrict the `QueryContext` to messages sent with a service, either `imessage` or `sms`
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_service("sms");
+    /// ```
+    pub fn set_service(&mut self, service: &str) -> Result<(), QueryContextError> {
+        self.service = Some(match service.to_lowercase().as_str() {
+            "imessage" => "iMessage",
+            "sms" => "SMS",
+            _ => return Err(QueryContextError::InvalidService(service.to_string())),
+        });
+        Ok(())
+    }
+
+    /// Restrict the `QueryContext` to messages with an attachment of one of the given kinds, i.e. `image` or `video`
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_attachment_types(&["image", "video"]);
+    /// ```
+    pub fn set_attachment_types(
+        &mut self,
+        attachment_types: &[&str],
+    ) -> Result<(), QueryContextError> {
+        let mut selected = BTreeSet::new();
+        for attachment_type in attachment_types {
+            let lowercase = attachment_type.to_lowercase();
+            let known = ATTACHMENT_TYPES
+                .iter()
+                .find(|known| **known == lowercase)
+                .ok_or(QueryContextError::InvalidAttachmentType(
+                    attachment_type.to_string(),
+                ))?;
+            selected.insert(*known);
+        }
+        self.attachment_types = Some(selected);
+        Ok(())
+    }
+
+    /// Restrict the `QueryContext` to messages sent by the database owner, or to messages they received
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_from_me(true);
+    /// ```
+    pub fn set_from_me(&mut self, from_me: bool) {
+        self.from_me = Some(from_me);
+    }
+
+    /// Leave tapbacks, and the stickers placed on messages, out of the `QueryContext`
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_skip_tapbacks(true);
+    /// ```
+    pub fn set_skip_tapbacks(&mut self, skip_tapbacks: bool) {
+        self.skip_tapbacks = skip_tapbacks;
+    }
+
+    /// Leave system messages, like group renames and participant changes, out of the `QueryContext`
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_skip_announcements(true);
+    /// ```
+    pub fn set_skip_announcements(&mut self, skip_announcements: bool) {
+        self.skip_announcements = skip_announcements;
+    }
+
+    /// Keep messages that do not belong to a chat when the `QueryContext` is restricted to selected chats
+    /// # Example:
+    ///
+    /// ```
+    /// use std::collections::BTreeSet;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_selected_chat_ids(BTreeSet::from([1, 2, 3]));
+    /// context.set_include_orphaned(true);
+    /// ```
+    pub fn set_include_orphaned(&mut self, include_orphaned: bool) {
+        self.include_orphaned = include_orphaned;
+    }
+
+    /// Restrict the `QueryContext` to received messages that have not been read
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_unread(true);
+    /// ```
+    pub fn set_unread(&mut self, unread: bool) {
+        self.unread = unread;
+    }
+
+    /// Restrict the `QueryContext` to messages that were deleted but can still be recovered
+    ///
+    /// See [`Message::is_deleted()`](crate::tables::messages::Message::is_deleted) for details on which messages are recoverable.
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_deleted(true);
+    /// ```
+    pub fn set_deleted(&mut self, deleted: bool) {
+        self.deleted = deleted;
+    }
+
+    /// Restrict the `QueryContext` to a single reply thread, given the GUID of any message in it
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_thread("A1B2C3D4-E5F6-4A5B-8C9D-0E1F2A3B4C5D");
+    /// ```
+    pub fn set_thread(&mut self, guid: &str) -> Result<(), QueryContextError> {
+        // GUIDs are interpolated into the query, so only allow the characters they are made of
+        if guid.is_empty() || !guid.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(QueryContextError::InvalidGuid(guid.to_string()));
+        }
+        self.thread_guid = Some(guid.to_string());
+        Ok(())
+    }
+
+    /// Restrict the `QueryContext` to the most recent messages in each chat
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_recent(100);
+    /// ```
+    pub fn set_recent(&mut self, recent: usize) {
+        self.recent = Some(recent);
+    }
+
+    /// Generate the condition that matches attachments of the selected kinds, where `alias` refers to the `attachment` table
+    pub(crate) fn attachment_type_filter(&self, alias: &str) -> Option<String> {
+        let conditions: Vec<String> = self
+            .attachment_types
+            .as_ref()?
+            .iter()
+            .map(|attachment_type| match *attachment_type {
+                // Audio messages do not have a MIME type, so match them the same way `Attachment::mime_type()` does
+                "audio" => format!(
+                    "{alias}.mime_type LIKE 'audio/%' OR ({alias}.mime_type IS NULL AND {alias}.uti = '{AUDIO_MESSAGE_UTI}')"
+                ),
+                attachment_type => format!("{alias}.mime_type LIKE '{attachment_type}/%'"),
+            })
+            .collect();
+        Some(format!("({})", conditions.join(" OR ")))
+    }
+
     /// Ensure a date string is valid
     fn sanitize_date(date: &str) -> Option<i64> {
         if date.len() < 9 {
@@ -96,9 +318,23 @@ impl QueryContext {
     /// ```
     pub fn has_filters(&self) -> bool {
         [self.start, self.end].iter().any(Option::is_some)
+            || self.selected_chat_ids.is_some()
+            || self.service.is_some()
+            || self.attachment_types.is_some()
+            || self.from_me.is_some()
+            || self.skip_tapbacks
+            || self.skip_announcements
+            || self.thread_guid.is_some()
+            || self.recent.is_some()
+            || self.unread
+            || self.deleted
     }
 
     /// Generate the SQL `WHERE` clause described by this `QueryContext`
+    ///
+    /// `message_alias` is the alias of the `message` table in the query, `chat_field` is the column
+    /// that holds the chat ID of each row, and `service_field` is the expression that holds its service.
+    ///
     /// # Example:
     ///
     /// ```
@@ -106,18 +342,122 @@ impl QueryContext {
     ///
     /// let mut context = QueryContext::default();
     /// context.set_start("2023-01-01");
-    /// let filters = context.generate_filter_statement("field_name");
+    /// let filters = context.generate_filter_statement("m", "c.chat_id", "m.service");
     /// ```
-    pub fn generate_filter_statement(&self, field: &str) -> String {
+    pub fn generate_filter_statement(
+        &self,
+        message_alias: &str,
+        chat_field: &str,
+        service_field: &str,
+    ) -> String {
+        let date_field = format!("{message_alias}.date");
         let mut filters = String::new();
         if let Some(start) = self.start {
-            filters.push_str(&format!("    {field} >= {start}"));
+            filters.push_str(&format!("    {date_field} >= {start}"));
         }
         if let Some(end) = self.end {
             if !filters.is_empty() {
                 filters.push_str(" AND ");
             }
-            filters.push_str(&format!("    {field} <= {end}"));
+            filters.push_str(&format!("    {date_field} < {end}"));
+        }
+        if let Some(chat_ids) = &self.selected_chat_ids {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            // Deleted messages are removed from their chat, so use the chat they were deleted from instead
+            let chat_field = if self.deleted {
+                format!("(SELECT d.chat_id FROM {RECENTLY_DELETED} d WHERE d.message_id = {message_alias}.ROWID)")
+            } else {
+                chat_field.to_string()
+            };
+            let chat_ids: Vec<String> = chat_ids.iter().map(i32::to_string).collect();
+            if self.include_orphaned {
+                filters.push_str(&format!(
+                    "    ({chat_field} IN ({}) OR {chat_field} IS NULL OR {chat_field} NOT IN (SELECT ROWID FROM {CHAT}))",
+                    chat_ids.join(", ")
+                ));
+            } else {
+                filters.push_str(&format!("    {chat_field} IN ({})", chat_ids.join(", ")));
+            }
+        }
+        if let Some(service) = self.service {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            filters.push_str(&format!("    {service_field} = '{service}'"));
+        }
+        if let Some(from_me) = self.from_me {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            filters.push_str(&format!(
+                "    {message_alias}.is_from_me = {}",
+                i32::from(from_me)
+            ));
+        }
+        if self.skip_tapbacks {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            // Matches the tapback and sticker variants in `Message::variant()`
+            filters.push_str(&format!(
+                "    NOT (COALESCE({message_alias}.associated_message_type, 0) BETWEEN 2000 AND 2007 OR COALESCE({message_alias}.associated_message_type, 0) BETWEEN 3000 AND 3007 OR (COALESCE({message_alias}.associated_message_type, 0) = 1000 AND {message_alias}.associated_message_guid IS NOT NULL))"
+            ));
+        }
+        if self.skip_announcements {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            filters.push_str(&format!(
+                "    {message_alias}.item_type = 0 AND {message_alias}.group_title IS NULL AND {message_alias}.group_action_type = 0"
+            ));
+        }
+        if self.unread {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            // Messages we sent are never marked as read by us
+            filters.push_str(&format!(
+                "    {message_alias}.is_from_me = 0 AND {message_alias}.is_read = 0"
+            ));
+        }
+        if self.deleted {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            filters.push_str(&format!(
+                "    {message_alias}.ROWID IN (SELECT d.message_id FROM {RECENTLY_DELETED} d)"
+            ));
+        }
+        if let Some(guid) = &self.thread_guid {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            // Replies point to the first message in their thread, so start from there
+            let root = format!(
+                "(SELECT COALESCE(t.thread_originator_guid, t.guid) FROM {MESSAGE} t WHERE t.guid = '{guid}')"
+            );
+            filters.push_str(&format!(
+                "    ({message_alias}.guid = {root} OR {message_alias}.thread_originator_guid = {root})"
+            ));
+        }
+        if let Some(recent) = self.recent {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            // Number each chat's messages from newest to oldest and keep the first few
+            filters.push_str(&format!(
+                "    {message_alias}.ROWID IN (SELECT r.message_id FROM (SELECT j.message_id, ROW_NUMBER() OVER (PARTITION BY j.chat_id ORDER BY rm.date DESC) AS position FROM {CHAT_MESSAGE_JOIN} j JOIN {MESSAGE} rm ON j.message_id = rm.ROWID) r WHERE r.position <= {recent})"
+            ));
+        }
+        if let Some(attachment_types) = self.attachment_type_filter("a") {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            filters.push_str(&format!(
+                "    {message_alias}.ROWID IN (SELECT j.message_id FROM {MESSAGE_ATTACHMENT_JOIN} j JOIN {ATTACHMENT} a ON j.attachment_id = a.ROWID WHERE {attachment_types})"
+            ));
         }
 
         if !filters.is_empty() {
@@ -132,7 +472,7 @@ impl QueryContext {
 
 #[cfg(test)]
 mod use_tests {
-    use std::env::set_var;
+    use std::{collections::BTreeSet, env::set_var};
 
     use chrono::prelude::*;
 
@@ -146,6 +486,17 @@ mod use_tests {
         let context = QueryContext::default();
         assert!(context.start.is_none());
         assert!(context.end.is_none());
+        assert!(context.selected_chat_ids.is_none());
+        assert!(context.service.is_none());
+        assert!(context.attachment_types.is_none());
+        assert!(context.from_me.is_none());
+        assert!(!context.skip_tapbacks);
+        assert!(!context.skip_announcements);
+        assert!(context.thread_guid.is_none());
+        assert!(context.recent.is_none());
+        assert!(!context.unread);
+        assert!(!context.deleted);
+        assert!(!context.include_orphaned);
         assert!(!context.has_filters());
     }
 
@@ -167,7 +518,7 @@ mod use_tests {
 
         assert_eq!(format(&Ok(local)), "Jan 01, 2020 12:00:00 AM");
         assert_eq!(
-            context.generate_filter_statement("m.date"),
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
             " WHERE\n                     m.date >= 599558400000000000"
         );
         assert!(context.start.is_some());
@@ -191,8 +542,8 @@ mod use_tests {
 
         assert_eq!(format(&Ok(local)), "Jan 01, 2020 12:00:00 AM");
         assert_eq!(
-            context.generate_filter_statement("m.date"),
-            " WHERE\n                     m.date <= 599558400000000000"
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     m.date < 599558400000000000"
         );
         assert!(context.start.is_none());
         assert!(context.end.is_some());
@@ -225,20 +576,206 @@ mod use_tests {
         assert_eq!(format(&Ok(local_start)), "Jan 01, 2020 12:00:00 AM");
         assert_eq!(format(&Ok(local_end)), "Feb 02, 2020 12:00:00 AM");
         assert_eq!(
-            context.generate_filter_statement("m.date"),
-            " WHERE\n                     m.date >= 599558400000000000 AND     m.date <= 602323200000000000"
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     m.date >= 599558400000000000 AND     m.date < 602323200000000000"
         );
         assert!(context.start.is_some());
         assert!(context.end.is_some());
         assert!(context.has_filters());
     }
 
+    #[test]
+    fn can_create_selected_chats() {
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([3, 1, 2]));
+
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     c.chat_id IN (1, 2, 3)"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_selected_chats_with_orphans() {
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([2, 1]));
+        context.set_include_orphaned(true);
+
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     (c.chat_id IN (1, 2) OR c.chat_id IS NULL OR c.chat_id NOT IN (SELECT ROWID FROM chat))"
+        );
+    }
+
+    #[test]
+    fn can_create_service() {
+        let mut context = QueryContext::default();
+        context.set_service("SMS").unwrap();
+
+        assert_eq!(context.service, Some("SMS"));
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     m.service = 'SMS'"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_invalid_service() {
+        let mut context = QueryContext::default();
+        assert!(context.set_service("fax").is_err());
+        assert!(!context.has_filters());
+    }
+
+    #[test]
+    fn can_create_attachment_types() {
+        let mut context = QueryContext::default();
+        context.set_attachment_types(&["Video", "image"]).unwrap();
+
+        assert_eq!(
+            context.attachment_types,
+            Some(BTreeSet::from(["image", "video"]))
+        );
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     m.ROWID IN (SELECT j.message_id FROM message_attachment_join j JOIN attachment a ON j.attachment_id = a.ROWID WHERE (a.mime_type LIKE 'image/%' OR a.mime_type LIKE 'video/%'))"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_audio_attachment_type() {
+        let mut context = QueryContext::default();
+        context.set_attachment_types(&["audio"]).unwrap();
+
+        assert_eq!(
+            context.attachment_type_filter("a").unwrap(),
+            "(a.mime_type LIKE 'audio/%' OR (a.mime_type IS NULL AND a.uti = 'com.apple.coreaudio-format'))"
+        );
+    }
+
+    #[test]
+    fn can_create_invalid_attachment_type() {
+        let mut context = QueryContext::default();
+        assert!(context
+            .set_attachment_types(&["image", "hologram"])
+            .is_err());
+        assert!(!context.has_filters());
+    }
+
+    #[test]
+    fn can_create_from_me() {
+        let mut context = QueryContext::default();
+        context.set_from_me(false);
+
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     m.is_from_me = 0"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_unread() {
+        let mut context = QueryContext::default();
+        context.set_unread(true);
+
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     m.is_from_me = 0 AND m.is_read = 0"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_deleted() {
+        let mut context = QueryContext::default();
+        context.set_deleted(true);
+
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     m.ROWID IN (SELECT d.message_id FROM chat_recoverable_message_join d)"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_deleted_chat_ids() {
+        let mut context = QueryContext::default();
+        context.set_deleted(true);
+        context.set_selected_chat_ids(BTreeSet::from([1]));
+
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     (SELECT d.chat_id FROM chat_recoverable_message_join d WHERE d.message_id = m.ROWID) IN (1) AND     m.ROWID IN (SELECT d.message_id FROM chat_recoverable_message_join d)"
+        );
+    }
+
+    #[test]
+    fn can_create_skip_announcements() {
+        let mut context = QueryContext::default();
+        context.set_skip_announcements(true);
+
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     m.item_type = 0 AND m.group_title IS NULL AND m.group_action_type = 0"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_skip_tapbacks() {
+        let mut context = QueryContext::default();
+        context.set_skip_tapbacks(true);
+
+        assert!(context
+            .generate_filter_statement("m", "c.chat_id", "m.service")
+            .contains("NOT (COALESCE(m.associated_message_type, 0) BETWEEN 2000 AND 2007"));
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_thread() {
+        let mut context = QueryContext::default();
+        context.set_thread("ABC-123").unwrap();
+
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     (m.guid = (SELECT COALESCE(t.thread_originator_guid, t.guid) FROM message t WHERE t.guid = 'ABC-123') OR m.thread_originator_guid = (SELECT COALESCE(t.thread_originator_guid, t.guid) FROM message t WHERE t.guid = 'ABC-123'))"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_invalid_thread() {
+        let mut context = QueryContext::default();
+        assert!(context.set_thread("abc' OR 1=1 --").is_err());
+        assert!(context.set_thread("").is_err());
+        assert!(!context.has_filters());
+    }
+
+    #[test]
+    fn can_create_recent() {
+        let mut context = QueryContext::default();
+        context.set_recent(50);
+
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            " WHERE\n                     m.ROWID IN (SELECT r.message_id FROM (SELECT j.message_id, ROW_NUMBER() OVER (PARTITION BY j.chat_id ORDER BY rm.date DESC) AS position FROM chat_message_join j JOIN message rm ON j.message_id = rm.ROWID) r WHERE r.position <= 50)"
+        );
+        assert!(context.has_filters());
+    }
+
     #[test]
     fn can_create_invalid_start() {
         let mut context = QueryContext::default();
         assert!(context.set_start("2020-13-32").is_err());
         assert!(!context.has_filters());
-        assert_eq!(context.generate_filter_statement("m.date"), "");
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            ""
+        );
     }
 
     #[test]
@@ -246,7 +783,10 @@ mod use_tests {
         let mut context = QueryContext::default();
         assert!(context.set_end("fake").is_err());
         assert!(!context.has_filters());
-        assert_eq!(context.generate_filter_statement("m.date"), "");
+        assert_eq!(
+            context.generate_filter_statement("m", "c.chat_id", "m.service"),
+            ""
+        );
     }
 }
 
@@ -1,6 +1,6 @@
 /*!
- Contains logic for creating human-readable file size strings.
- */
+Contains logic for creating and parsing human-readable file size strings.
+*/
 
 const DIVISOR: f64 = 1024.;
 const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
@@ -26,9 +26,39 @@ pub fn format_file_size(total_bytes: u64) -> String {
     format!("{bytes:.2} {}", UNITS[index])
 }
 
+/// Parse a human readable file size, i.e. `25MB` or `1.5 GB`, into an amount of bytes
+///
+/// Units are case-insensitive and use the same powers of 1024 as [`format_file_size`].
+/// A number without a unit is an amount of bytes.
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::size::parse_file_size;
+///
+/// let size = parse_file_size("25MB");
+/// assert_eq!(size, Some(26214400));
+/// ```
+pub fn parse_file_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (amount, unit) = size.split_at(split);
+    let amount: f64 = amount.parse().ok()?;
+
+    let unit = unit.trim().to_uppercase();
+    let power = match unit.as_str() {
+        "" => 0,
+        _ => UNITS.iter().position(|known| *known == unit)?,
+    };
+
+    Some((amount * DIVISOR.powi(power as i32)) as u64)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::util::size::format_file_size;
+    use crate::util::size::{format_file_size, parse_file_size};
 
     #[test]
     fn can_get_file_size_bytes() {
@@ -58,4 +88,24 @@ mod tests {
         let expected = format_file_size(u64::MAX);
         assert_eq!(expected, String::from("16777216.00 TB"));
     }
+
+    #[test]
+    fn can_parse_file_size_bytes() {
+        assert_eq!(parse_file_size("100"), Some(100));
+        assert_eq!(parse_file_size("100B"), Some(100));
+    }
+
+    #[test]
+    fn can_parse_file_size_units() {
+        assert_eq!(parse_file_size("2KB"), Some(2048));
+        assert_eq!(parse_file_size("25mb"), Some(26214400));
+        assert_eq!(parse_file_size("1.5 GB"), Some(1610612736));
+    }
+
+    #[test]
+    fn cant_parse_file_size_invalid() {
+        assert_eq!(parse_file_size("MB"), None);
+        assert_eq!(parse_file_size("10 parsecs"), None);
+        assert_eq!(parse_file_size(""), None);
+    }
 }
@@ -0,0 +1,747 @@
+/*!
+ Contains logic for reading the XML files made by [SMS Backup & Restore](https://www.synctech.com.au/sms-backup-restore/) on Android.
+
+ Each file is converted to a database laid out like `chat.db`, so it can be [merged](crate::util::merge) with
+ databases from Apple devices:
+
+ - Each `sms` element becomes a message, and each `mms` element becomes a message with its `part`s as attachments
+ - Phone numbers are normalized with [`normalize_handle`], so they match the handles Messages stores
+ - Conversations use the `SMS` service, with the same `guid`s Messages uses for SMS conversations
+ - Messages are given a `guid` made from their contents, so overlapping backups do not duplicate them
+
+ Drafts are not imported.
+*/
+
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, write, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::warn;
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use rusqlite::Connection;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    error::sms_backup::SmsBackupError,
+    util::{
+        dates::{get_offset, TIMESTAMP_FACTOR},
+        phone::normalize_handle,
+    },
+};
+
+/// The service used for conversations and messages read from an SMS backup
+pub const SMS_SERVICE: &str = "SMS";
+/// The style of a conversation with one other person
+const DIRECT_STYLE: i32 = 45;
+/// The style of a group conversation
+const GROUP_STYLE: i32 = 43;
+/// The MMS part that lays out the other parts, which is not shown
+const SMIL: &str = "application/smil";
+/// The MMS address type of the sender
+const MMS_FROM: &str = "137";
+/// The placeholder Android uses for the device's own number in MMS addresses
+const OWN_ADDRESS: &str = "insert-address-token";
+/// The character Messages uses to mark where an attachment appears in a message's text
+const ATTACHMENT_MARKER: char = '\u{FFFC}';
+
+/// The tables an SMS backup is converted to, with the columns of `chat.db` that it can fill
+const SCHEMA: &str = concat!(
+    "CREATE TABLE handle (ROWID INTEGER PRIMARY KEY AUTOINCREMENT, id TEXT NOT NULL, service TEXT NOT NULL, UNIQUE (id, service));",
+    "CREATE TABLE chat (ROWID INTEGER PRIMARY KEY AUTOINCREMENT, guid TEXT UNIQUE NOT NULL, style INTEGER, chat_identifier TEXT, service_name TEXT);",
+    "CREATE TABLE message (ROWID INTEGER PRIMARY KEY AUTOINCREMENT, guid TEXT UNIQUE NOT NULL, text TEXT, handle_id INTEGER DEFAULT 0, subject TEXT, service TEXT, date INTEGER DEFAULT 0, is_from_me INTEGER DEFAULT 0, is_read INTEGER DEFAULT 0, is_sent INTEGER DEFAULT 0, is_delivered INTEGER DEFAULT 0, is_finished INTEGER DEFAULT 0, cache_has_attachments INTEGER DEFAULT 0);",
+    "CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY AUTOINCREMENT, guid TEXT UNIQUE NOT NULL, filename TEXT, mime_type TEXT, transfer_name TEXT, total_bytes INTEGER DEFAULT 0);",
+    "CREATE TABLE chat_handle_join (chat_id INTEGER, handle_id INTEGER, UNIQUE (chat_id, handle_id));",
+    "CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER, message_date INTEGER DEFAULT 0, PRIMARY KEY (chat_id, message_id));",
+    "CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER, UNIQUE (message_id, attachment_id));",
+);
+
+/// Determine if a file is an SMS Backup & Restore file
+pub fn is_sms_backup(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("xml"))
+}
+
+/// Convert an SMS backup to a database laid out like `chat.db`, returning the number of messages it holds
+///
+/// MMS attachments are written to the `attachments` folder. Phone numbers saved without a country code are given `country_code`.
+///
+/// # Example:
+///
+/// ```no_run
+/// use std::path::Path;
+/// use imessage_database::util::sms_backup::import_sms_backup;
+///
+/// let count = import_sms_backup(
+///     Path::new("/path/to/sms-20240101000000.xml"),
+///     Path::new("/tmp/sms.db"),
+///     Path::new("/tmp/attachments"),
+///     "1",
+/// ).unwrap();
+/// ```
+pub fn import_sms_backup(
+    xml: &Path,
+    destination: &Path,
+    attachments: &Path,
+    country_code: &str,
+) -> Result<usize, SmsBackupError> {
+    let mut db = Connection::open(destination).map_err(SmsBackupError::CannotWrite)?;
+    db.execute_batch(SCHEMA)
+        .map_err(SmsBackupError::CannotWrite)?;
+    let transaction = db.transaction().map_err(SmsBackupError::CannotWrite)?;
+
+    let mut importer = Importer {
+        db: &transaction,
+        attachments,
+        country_code,
+        handles: HashMap::new(),
+        chats: HashMap::new(),
+        messages: 0,
+    };
+
+    let file = File::open(xml).map_err(SmsBackupError::CannotRead)?;
+    let mut reader = Reader::from_reader(BufReader::new(file));
+    let mut buffer = Vec::new();
+    let mut mms: Option<Mms> = None;
+
+    loop {
+        let event = reader
+            .read_event_into(&mut buffer)
+            .map_err(SmsBackupError::InvalidXml)?;
+        match &event {
+            Event::Start(element) | Event::Empty(element) => match element.name().as_ref() {
+                b"sms" => {
+                    if let Some(message) =
+                        AndroidMessage::from_sms(&attributes(element)?, country_code)
+                    {
+                        importer.insert(message)?;
+                    }
+                }
+                b"mms" => {
+                    mms = Some(Mms {
+                        attributes: attributes(element)?,
+                        parts: vec![],
+                        addresses: vec![],
+                    });
+                }
+                b"part" => {
+                    if let Some(mms) = &mut mms {
+                        mms.parts.push(attributes(element)?);
+                    }
+                }
+                b"addr" => {
+                    if let Some(mms) = &mut mms {
+                        mms.addresses.push(attributes(element)?);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+
+        // An `mms` element is complete once it closes, or right away if it has no children
+        let closes_mms = match &event {
+            Event::End(element) => element.name().as_ref() == b"mms",
+            Event::Empty(element) => element.name().as_ref() == b"mms",
+            _ => false,
+        };
+        if closes_mms {
+            if let Some(message) = mms
+                .take()
+                .and_then(|mms| AndroidMessage::from_mms(&mms, country_code))
+            {
+                importer.insert(message)?;
+            }
+        }
+        buffer.clear();
+    }
+
+    let messages = importer.messages;
+    transaction.commit().map_err(SmsBackupError::CannotWrite)?;
+    Ok(messages)
+}
+
+/// An `mms` element and its children, collected until the element closes
+struct Mms {
+    attributes: HashMap<String, String>,
+    parts: Vec<HashMap<String, String>>,
+    addresses: Vec<HashMap<String, String>>,
+}
+
+/// An attachment sent with an MMS
+#[derive(Debug, PartialEq, Eq)]
+struct AndroidAttachment {
+    name: Option<String>,
+    mime_type: Option<String>,
+    data: Vec<u8>,
+}
+
+/// A message read from an SMS backup
+#[derive(Debug, PartialEq, Eq)]
+struct AndroidMessage {
+    guid: String,
+    text: Option<String>,
+    subject: Option<String>,
+    /// Milliseconds since the Unix epoch
+    date: i64,
+    is_from_me: bool,
+    is_read: bool,
+    /// The address of the sender, if someone else sent the message
+    sender: Option<String>,
+    /// The addresses of everyone in the conversation other than the device's owner
+    addresses: Vec<String>,
+    attachments: Vec<AndroidAttachment>,
+}
+
+impl AndroidMessage {
+    /// Read a message from the attributes of an `sms` element
+    ///
+    /// The `guid` is built from the normalized address and the direction of the message, so the same message
+    /// is only imported once from backups that store the number differently or mark it as queued and then sent.
+    fn from_sms(attributes: &HashMap<String, String>, country_code: &str) -> Option<Self> {
+        let kind = attributes.get("type")?;
+        // 1 is received; 2 is sent, 4 is outbox, 5 is failed, and 6 is queued; 3 is a draft
+        let is_from_me = match kind.as_str() {
+            "1" => false,
+            "2" | "4" | "5" | "6" => true,
+            _ => return None,
+        };
+        let address = attributes.get("address")?;
+        let date = attributes.get("date")?;
+        let text = attributes.get("body").cloned();
+
+        Some(Self {
+            guid: guid(&[
+                "sms",
+                &normalize_handle(address, country_code),
+                date,
+                direction(is_from_me),
+                text.as_deref().unwrap_or_default(),
+            ]),
+            text,
+            subject: attributes.get("subject").cloned(),
+            date: date.parse().ok()?,
+            is_from_me,
+            is_read: attributes.get("read").is_some_and(|read| read == "1"),
+            sender: (!is_from_me).then(|| address.clone()),
+            addresses: vec![address.clone()],
+            attachments: vec![],
+        })
+    }
+
+    /// Read a message from an `mms` element and its children
+    ///
+    /// Like [`AndroidMessage::from_sms`], the `guid` is built from the normalized addresses and the direction of the message.
+    fn from_mms(mms: &Mms, country_code: &str) -> Option<Self> {
+        let message_box = mms.attributes.get("msg_box")?;
+        // 1 is received; 2 is sent and 4 is outbox; 3 is a draft
+        let is_from_me = match message_box.as_str() {
+            "1" => false,
+            "2" | "4" => true,
+            _ => return None,
+        };
+        let date = mms.attributes.get("date")?;
+
+        // The `address` attribute lists the conversation's other members, the `addr` children also list the owner
+        let mut addresses: Vec<String> = mms
+            .attributes
+            .get("address")
+            .map(|addresses| {
+                addresses
+                    .split('~')
+                    .filter(|address| !address.is_empty() && *address != OWN_ADDRESS)
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let sender = mms
+            .addresses
+            .iter()
+            .find(|address| address.get("type").is_some_and(|kind| kind == MMS_FROM))
+            .and_then(|address| address.get("address"))
+            .filter(|address| *address != OWN_ADDRESS)
+            .cloned();
+        if addresses.is_empty() {
+            addresses.extend(sender.iter().cloned());
+        }
+
+        let mut normalized: Vec<String> = addresses
+            .iter()
+            .map(|address| normalize_handle(address, country_code))
+            .collect();
+        normalized.sort();
+
+        // Text parts are joined in order, with a marker where each attachment appears
+        let mut text = String::new();
+        let mut attachments = vec![];
+        for part in &mms.parts {
+            let mime_type = part.get("ct");
+            if mime_type.is_some_and(|mime_type| mime_type == SMIL) {
+                continue;
+            }
+            if mime_type.is_some_and(|mime_type| mime_type == "text/plain") {
+                if let Some(part_text) = part.get("text") {
+                    text.push_str(part_text);
+                }
+                continue;
+            }
+            let Some(data) = part.get("data") else {
+                continue;
+            };
+            match STANDARD.decode(data) {
+                Ok(data) => {
+                    text.push(ATTACHMENT_MARKER);
+                    attachments.push(AndroidAttachment {
+                        name: part
+                            .get("name")
+                            .or_else(|| part.get("cl"))
+                            .or_else(|| part.get("fn"))
+                            .cloned(),
+                        mime_type: mime_type.cloned(),
+                        data,
+                    });
+                }
+                Err(why) => warn!("Skipping an MMS attachment that cannot be decoded: {why}"),
+            }
+        }
+
+        Some(Self {
+            guid: guid(&[
+                "mms",
+                mms.attributes
+                    .get("m_id")
+                    .map(String::as_str)
+                    .unwrap_or_default(),
+                &normalized.join("~"),
+                date,
+                direction(is_from_me),
+                &text,
+            ]),
+            text: (!text.is_empty()).then_some(text),
+            subject: mms.attributes.get("sub").cloned(),
+            date: date.parse().ok()?,
+            is_from_me,
+            is_read: mms.attributes.get("read").is_some_and(|read| read == "1"),
+            sender,
+            addresses,
+            attachments,
+        })
+    }
+
+    /// Get the date in the format Messages uses, nanoseconds since `2001-01-01 00:00:00`
+    ///
+    /// Returns `None` if the date cannot be represented, i.e. it is corrupt.
+    fn apple_date(&self) -> Option<i64> {
+        self.date
+            .checked_sub(get_offset().checked_mul(1000)?)?
+            .checked_mul(TIMESTAMP_FACTOR / 1000)
+    }
+}
+
+/// Writes the messages in an SMS backup to the converted database
+struct Importer<'a> {
+    db: &'a Connection,
+    attachments: &'a Path,
+    country_code: &'a str,
+    /// Map of normalized address to handle ID
+    handles: HashMap<String, i64>,
+    /// Map of conversation `guid` to chat ID
+    chats: HashMap<String, i64>,
+    /// The number of messages written
+    messages: usize,
+}
+
+impl Importer<'_> {
+    /// Write a message, skipping it if a message with the same `guid` was already written
+    fn insert(&mut self, message: AndroidMessage) -> Result<(), SmsBackupError> {
+        let Some(date) = message.apple_date() else {
+            warn!(
+                "Skipping message {} with a date that is out of range: {}",
+                message.guid, message.date
+            );
+            return Ok(());
+        };
+
+        let mut participants: Vec<String> = message
+            .addresses
+            .iter()
+            .map(|address| normalize_handle(address, self.country_code))
+            .collect();
+        participants.sort();
+        participants.dedup();
+        if participants.is_empty() {
+            return Ok(());
+        }
+
+        let chat_id = self.chat(&participants)?;
+        // Messages sent to one person are stored with that person's handle, as Messages does
+        let handle_id = match (&message.sender, message.is_from_me) {
+            (_, true) if participants.len() == 1 => self.handle(&participants[0])?,
+            (_, true) => 0,
+            (Some(sender), false) => self.handle(&normalize_handle(sender, self.country_code))?,
+            (None, false) => self.handle(&participants[0])?,
+        };
+
+        let inserted = self
+            .db
+            .execute(
+                "INSERT OR IGNORE INTO message (guid, text, handle_id, subject, service, date, is_from_me, is_read, is_sent, is_delivered, is_finished, cache_has_attachments) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?7, 1, 1, ?9)",
+                (
+                    &message.guid,
+                    &message.text,
+                    handle_id,
+                    &message.subject,
+                    SMS_SERVICE,
+                    date,
+                    message.is_from_me,
+                    message.is_read,
+                    !message.attachments.is_empty(),
+                ),
+            )
+            .map_err(SmsBackupError::CannotWrite)?;
+        if inserted == 0 {
+            return Ok(());
+        }
+        let message_id = self.db.last_insert_rowid();
+        self.db
+            .execute(
+                "INSERT INTO chat_message_join (chat_id, message_id, message_date) VALUES (?1, ?2, ?3)",
+                (chat_id, message_id, date),
+            )
+            .map_err(SmsBackupError::CannotWrite)?;
+
+        for (index, attachment) in message.attachments.iter().enumerate() {
+            let guid = format!("{}-{index}", message.guid);
+            let path = self.write_attachment(&guid, attachment)?;
+            self.db
+                .execute(
+                    "INSERT INTO attachment (guid, filename, mime_type, transfer_name, total_bytes) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    (
+                        &guid,
+                        path.to_string_lossy(),
+                        &attachment.mime_type,
+                        &attachment.name,
+                        attachment.data.len(),
+                    ),
+                )
+                .map_err(SmsBackupError::CannotWrite)?;
+            self.db
+                .execute(
+                    "INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (?1, ?2)",
+                    (message_id, self.db.last_insert_rowid()),
+                )
+                .map_err(SmsBackupError::CannotWrite)?;
+        }
+
+        self.messages += 1;
+        Ok(())
+    }
+
+    /// Get the ID of the handle for a normalized address, creating it if needed
+    fn handle(&mut self, address: &str) -> Result<i64, SmsBackupError> {
+        if let Some(id) = self.handles.get(address) {
+            return Ok(*id);
+        }
+        self.db
+            .execute(
+                "INSERT INTO handle (id, service) VALUES (?1, ?2)",
+                (address, SMS_SERVICE),
+            )
+            .map_err(SmsBackupError::CannotWrite)?;
+        let id = self.db.last_insert_rowid();
+        self.handles.insert(address.to_string(), id);
+        Ok(id)
+    }
+
+    /// Get the ID of the conversation with a set of normalized addresses, creating it if needed
+    fn chat(&mut self, participants: &[String]) -> Result<i64, SmsBackupError> {
+        let (guid, identifier, style) = match participants {
+            [address] => (
+                format!("{SMS_SERVICE};-;{address}"),
+                address.clone(),
+                DIRECT_STYLE,
+            ),
+            _ => {
+                // Group conversations are named for a hash of their members, since Android does not name them
+                let hash = format!("{:x}", Sha1::digest(participants.join(",").as_bytes()));
+                let identifier = format!("chat{}", &hash[..16]);
+                (
+                    format!("{SMS_SERVICE};+;{identifier}"),
+                    identifier,
+                    GROUP_STYLE,
+                )
+            }
+        };
+        if let Some(id) = self.chats.get(&guid) {
+            return Ok(*id);
+        }
+
+        self.db
+            .execute(
+                "INSERT INTO chat (guid, style, chat_identifier, service_name) VALUES (?1, ?2, ?3, ?4)",
+                (&guid, style, &identifier, SMS_SERVICE),
+            )
+            .map_err(SmsBackupError::CannotWrite)?;
+        let chat_id = self.db.last_insert_rowid();
+        for participant in participants {
+            let handle_id = self.handle(participant)?;
+            self.db
+                .execute(
+                    "INSERT OR IGNORE INTO chat_handle_join (chat_id, handle_id) VALUES (?1, ?2)",
+                    (chat_id, handle_id),
+                )
+                .map_err(SmsBackupError::CannotWrite)?;
+        }
+        self.chats.insert(guid, chat_id);
+        Ok(chat_id)
+    }
+
+    /// Write an attachment to disk, returning its path
+    fn write_attachment(
+        &self,
+        guid: &str,
+        attachment: &AndroidAttachment,
+    ) -> Result<PathBuf, SmsBackupError> {
+        create_dir_all(self.attachments).map_err(|why| {
+            SmsBackupError::CannotWriteAttachment(why, self.attachments.to_path_buf())
+        })?;
+        let mut path = self.attachments.join(guid);
+        if let Some(extension) = attachment
+            .name
+            .as_deref()
+            .and_then(|name| Path::new(name).extension())
+        {
+            path.set_extension(extension);
+        }
+        write(&path, &attachment.data)
+            .map_err(|why| SmsBackupError::CannotWriteAttachment(why, path.clone()))?;
+        Ok(path)
+    }
+}
+
+/// Describe whether a message was sent or received, for its `guid`
+fn direction(is_from_me: bool) -> &'static str {
+    if is_from_me {
+        "sent"
+    } else {
+        "received"
+    }
+}
+
+/// Build a `guid` for a message from the values that identify it
+fn guid(values: &[&str]) -> String {
+    format!(
+        "android-{:x}",
+        Sha1::digest(values.join("\u{0}").as_bytes())
+    )
+}
+
+/// Read the attributes of an element, leaving out the ones Android stores as `null`
+fn attributes(element: &BytesStart) -> Result<HashMap<String, String>, SmsBackupError> {
+    let mut attributes = HashMap::new();
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|why| SmsBackupError::InvalidXml(why.into()))?;
+        let value = unescape(&String::from_utf8_lossy(&attribute.value));
+        if value != "null" {
+            attributes.insert(
+                String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                value,
+            );
+        }
+    }
+    Ok(attributes)
+}
+
+/// Replace the entities in an attribute value with the characters they stand for
+///
+/// SMS Backup & Restore writes characters outside the Basic Multilingual Plane, like emoji, as a pair of
+/// UTF-16 surrogates, i.e. `&#55357;&#56832;`, which XML parsers reject, so the pairs are combined here.
+fn unescape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut high_surrogate: Option<u32> = None;
+    let mut rest = value;
+
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let code = rest.find(';').and_then(|end| {
+            let code = match &rest[1..end] {
+                "amp" => '&' as u32,
+                "lt" => '<' as u32,
+                "gt" => '>' as u32,
+                "quot" => '"' as u32,
+                "apos" => '\'' as u32,
+                entity => match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => entity.strip_prefix('#')?.parse().ok()?,
+                },
+            };
+            Some((code, end))
+        });
+        let Some((code, end)) = code else {
+            output.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[end + 1..];
+
+        match (high_surrogate.take(), code) {
+            (None, 0xD800..=0xDBFF) => high_surrogate = Some(code),
+            (Some(high), 0xDC00..=0xDFFF) => output.push(
+                char::from_u32(0x10000 + ((high - 0xD800) << 10) + (code - 0xDC00))
+                    .unwrap_or(char::REPLACEMENT_CHARACTER),
+            ),
+            (high, _) => {
+                if high.is_some() {
+                    output.push(char::REPLACEMENT_CHARACTER);
+                }
+                output.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+        }
+    }
+    if high_surrogate.is_some() {
+        output.push(char::REPLACEMENT_CHARACTER);
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read, remove_dir_all, write},
+    };
+
+    use rusqlite::Connection;
+
+    use crate::util::sms_backup::{import_sms_backup, unescape};
+
+    const BACKUP: &str = r#"<?xml version='1.0' encoding='UTF-8' standalone='yes' ?>
+<smses count="4">
+  <sms protocol="0" address="(555) 867-5309" date="978307200000" type="1" subject="null" body="Hi &#55357;&#56832;" read="1" />
+  <sms protocol="0" address="+15558675309" date="978307260000" type="2" subject="null" body="Hello!" read="1" />
+  <sms protocol="0" address="+15558675309" date="978307260000" type="2" subject="null" body="Hello!" read="1" />
+  <sms protocol="0" address="+15558675309" date="978307320000" type="3" subject="null" body="Draft" read="1" />
+  <mms date="978307380000" msg_box="1" address="+15558675309~+15551234567" m_id="abc" read="0" sub="null">
+    <parts>
+      <part seq="-1" ct="application/smil" text="&lt;smil /&gt;" />
+      <part seq="0" ct="image/png" name="photo.png" data="iVBORw0KGgo=" />
+      <part seq="1" ct="text/plain" text="Look" />
+    </parts>
+    <addrs>
+      <addr address="+15551234567" type="137" charset="106" />
+      <addr address="+15558675309" type="151" charset="106" />
+    </addrs>
+  </mms>
+</smses>
+"#;
+
+    #[test]
+    fn can_unescape() {
+        assert_eq!(unescape("Hi &#55357;&#56832;"), "Hi 😀");
+        assert_eq!(unescape("&lt;b&gt; &amp; &#x41;"), "<b> & A");
+        assert_eq!(unescape("Tom & Jerry"), "Tom & Jerry");
+        assert_eq!(unescape("&#55357;!"), "\u{FFFD}!");
+    }
+
+    #[test]
+    fn can_import_sms_backup() {
+        let folder = temp_dir().join("imessage-database-sms-backup");
+        let _ = remove_dir_all(&folder);
+        create_dir_all(&folder).unwrap();
+        let xml = folder.join("sms.xml");
+        write(&xml, BACKUP).unwrap();
+
+        let count = import_sms_backup(
+            &xml,
+            &folder.join("sms.db"),
+            &folder.join("attachments"),
+            "1",
+        )
+        .unwrap();
+        assert_eq!(count, 3);
+
+        let db = Connection::open(folder.join("sms.db")).unwrap();
+        let text: String = db
+            .query_row("SELECT text FROM message WHERE date = 0", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(text, "Hi 😀");
+
+        let chats: Vec<String> = db
+            .prepare("SELECT guid FROM chat ORDER BY ROWID")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(chats[0], "SMS;-;+15558675309");
+        assert!(chats[1].starts_with("SMS;+;chat"));
+
+        let (text, sender): (String, String) = db
+            .query_row(
+                "SELECT m.text, h.id FROM message m JOIN handle h ON h.ROWID = m.handle_id WHERE m.cache_has_attachments = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(text, "\u{FFFC}Look");
+        assert_eq!(sender, "+15551234567");
+
+        let filename: String = db
+            .query_row("SELECT filename FROM attachment", [], |row| row.get(0))
+            .unwrap();
+        assert!(filename.ends_with(".png"));
+        assert_eq!(read(filename).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn can_import_sms_backup_duplicates_and_bad_dates() {
+        let folder = temp_dir().join("imessage-database-sms-backup-duplicates");
+        let _ = remove_dir_all(&folder);
+        create_dir_all(&folder).unwrap();
+        let xml = folder.join("sms.xml");
+        write(
+            &xml,
+            r#"<smses count="4">
+  <sms address="(555) 867-5309" date="978307200000" type="6" body="Hello!" read="1" />
+  <sms address="+15558675309" date="978307200000" type="2" body="Hello!" read="1" />
+  <sms address="+15558675309" date="978307200000" type="1" body="Hello!" read="1" />
+  <sms address="+15558675309" date="9223372036854775807" type="1" body="Corrupt" read="1" />
+</smses>
+"#,
+        )
+        .unwrap();
+
+        let count = import_sms_backup(
+            &xml,
+            &folder.join("sms.db"),
+            &folder.join("attachments"),
+            "1",
+        )
+        .unwrap();
+        // The queued and sent copies are the same message; the received one is not
+        assert_eq!(count, 2);
+
+        let db = Connection::open(folder.join("sms.db")).unwrap();
+        let corrupt: i64 = db
+            .query_row(
+                "SELECT COUNT(*) FROM message WHERE text = 'Corrupt'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(corrupt, 0);
+    }
+}
@@ -0,0 +1,176 @@
+/*!
+ Logic used to read the attributes of an archived `NSAttributedString` into runs of characters.
+
+ After the text, the `typedstream` alternates between a range and a `NSDictionary` of the attributes
+ that apply to the characters in it. Each range stores its length in UTF-16 code units, and starts where the
+ previous one ended.
+*/
+
+use std::ops::Range;
+
+use crate::util::typedstream::models::{Archivable, OutputData};
+
+/// The index of the message part a range belongs to, as an [`AttributeValue::Number`]
+pub const MESSAGE_PART_ATTRIBUTE: &str = "__kIMMessagePartAttributeName";
+/// The handle of a mentioned contact, as an [`AttributeValue::String`]
+pub const MENTION_ATTRIBUTE: &str = "__kIMMentionConfirmedMention";
+/// The address of a link, as an [`AttributeValue::Url`]
+pub const LINK_ATTRIBUTE: &str = "__kIMLinkAttributeName";
+/// The `GUID` of the attachment shown in place of a range, as an [`AttributeValue::String`]
+pub const ATTACHMENT_ATTRIBUTE: &str = "__kIMFileTransferGUIDAttributeName";
+/// The text Apple transcribed from an audio message, as an [`AttributeValue::String`]
+pub const AUDIO_TRANSCRIPTION_ATTRIBUTE: &str = "IMAudioTranscription";
+
+/// The value of an attribute
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    /// Text, like the handle of a mentioned contact
+    String(String),
+    /// A number, like the index of a message part
+    Number(i64),
+    /// The address of a link
+    Url(String),
+    /// Raw bytes, like the results of data detection
+    Data(Vec<u8>),
+    /// An object this parser does not decode, named by its class
+    Other(String),
+}
+
+/// A single attribute applied to a range of characters
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeRun {
+    /// The characters the attribute applies to, counted in `char`s rather than bytes
+    pub range: Range<usize>,
+    /// The name of the attribute, i.e. [`MENTION_ATTRIBUTE`]
+    pub name: String,
+    /// The value of the attribute
+    pub value: AttributeValue,
+}
+
+/// Map each UTF-16 code unit in `text` to the `char` it is part of, as its `(char index, byte offset)`
+///
+/// `NSAttributedString` ranges count UTF-16 code units, so a `char` outside the Basic Multilingual Plane,
+/// like most emoji, takes up two places in a range.
+pub(crate) fn utf16_positions(text: &str) -> Vec<(usize, usize)> {
+    text.char_indices()
+        .enumerate()
+        .flat_map(|(char_idx, (byte_idx, ch))| {
+            (0..ch.len_utf16()).map(move |_| (char_idx, byte_idx))
+        })
+        .collect()
+}
+
+/// Read the attribute runs from the components of an `NSAttributedString`, skipping the text itself
+///
+/// A range with several attributes produces a run for each one, in the order they are stored.
+pub(crate) fn read_runs(components: &[Archivable], text: &str) -> Vec<AttributeRun> {
+    let mut runs = vec![];
+    let mut start: usize = 0;
+    let mut idx = 1;
+
+    // Ranges are stored in UTF-16 code units, but runs count `char`s
+    let positions = utf16_positions(text);
+    let char_count = text.chars().count();
+    let to_char = |unit: usize| {
+        positions
+            .get(unit)
+            .map_or(char_count, |(char_idx, _)| *char_idx)
+    };
+
+    while idx < components.len() {
+        let Some(length) = range_length(&components[idx]) else {
+            idx += 1;
+            continue;
+        };
+        let end = start.saturating_add(length);
+        let range = to_char(start)..to_char(end);
+        start = end;
+        idx += 1;
+
+        // The range is followed by a dictionary of its attributes, if there are any
+        let pairs = dictionary_length(components.get(idx));
+        if pairs > 0 {
+            idx += 1;
+        }
+        for _ in 0..pairs {
+            let Some(name) = components.get(idx).and_then(Archivable::as_nsstring) else {
+                break;
+            };
+            let (value, used) = read_value(&components[idx + 1..]);
+            runs.push(AttributeRun {
+                range: range.clone(),
+                name: name.to_string(),
+                value,
+            });
+            idx += 1 + used;
+        }
+    }
+
+    runs
+}
+
+/// Get the length of a range, if the component is one
+fn range_length(component: &Archivable) -> Option<usize> {
+    match component {
+        Archivable::Data(items) => match items.as_slice() {
+            [OutputData::SignedInteger(_), OutputData::UnsignedInteger(length)] => {
+                usize::try_from(*length).ok()
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Get the number of key/value pairs in a `NSDictionary`
+fn dictionary_length(component: Option<&Archivable>) -> usize {
+    match component {
+        Some(Archivable::Object(class, data)) if class.name == "NSDictionary" => {
+            match data.first() {
+                Some(OutputData::SignedInteger(length)) => usize::try_from(*length).unwrap_or(0),
+                _ => 0,
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Read the value at the start of `components`, returning it with the number of components it used
+fn read_value(components: &[Archivable]) -> (AttributeValue, usize) {
+    let Some(Archivable::Object(class, data)) = components.first() else {
+        return (AttributeValue::Other(String::new()), 0);
+    };
+    match class.name.as_ref() {
+        "NSString" | "NSMutableString" => match data.first() {
+            Some(OutputData::String(text)) => (AttributeValue::String(text.to_string()), 1),
+            _ => (AttributeValue::Other(class.name.to_string()), 1),
+        },
+        "NSNumber" => match data.first() {
+            Some(OutputData::SignedInteger(number)) => (AttributeValue::Number(*number), 1),
+            _ => (AttributeValue::Other(class.name.to_string()), 1),
+        },
+        // The address is stored as a string after the `NSURL`
+        "NSURL" => match components.get(1).and_then(Archivable::as_nsstring) {
+            Some(url) => (AttributeValue::Url(url.to_string()), 2),
+            None => (AttributeValue::Other(class.name.to_string()), 1),
+        },
+        // The bytes are stored after their length
+        "NSData" | "NSMutableData" => match components.get(1).and_then(find_bytes) {
+            Some(bytes) => (AttributeValue::Data(bytes.to_vec()), 2),
+            None => (AttributeValue::Other(class.name.to_string()), 1),
+        },
+        _ => (AttributeValue::Other(class.name.to_string()), 1),
+    }
+}
+
+/// Find the array of bytes stored in a component
+pub(crate) fn find_bytes<'a>(component: &'a Archivable) -> Option<&'a [u8]> {
+    let items = match component {
+        Archivable::Data(items) | Archivable::Object(_, items) => items,
+        _ => return None,
+    };
+    items.iter().find_map(|item| match item {
+        OutputData::Array(bytes) => Some(bytes.as_ref()),
+        _ => None,
+    })
+}
@@ -15,8 +15,74 @@
  - Pure Rust implementation for efficient and safe deserialization
  - No dependencies on Apple frameworks
  - Robust error handling for malformed or incomplete `typedstream` data
+
+ ## Usage
+
+ [`parse()`] decodes an archived [NSAttributedString](https://developer.apple.com/documentation/foundation/nsattributedstring),
+ like the `attributedBody` column of the `message` table, into its text and the [runs](attributes::AttributeRun) of
+ attributes, like mentions, links, and message part indexes, applied to ranges of it.
+ It does not depend on the rest of this crate, so it can be used to decode `typedstream` data from any source.
+ For other archived objects, [`TypedStreamReader`](parser::TypedStreamReader) emits every object in the stream.
+
+ ```
+ use imessage_database::util::typedstream::parse;
+
+ let bytes: Vec<u8> = vec![]; // Example stream
+ match parse(&bytes) {
+     Ok(attributed_string) => {
+         println!("{}", attributed_string.text);
+         for run in attributed_string.runs {
+             println!("{:?}: {} = {:?}", run.range, run.name, run.value);
+         }
+     }
+     Err(why) => eprintln!("Unable to decode: {why}"),
+ }
+ ```
 */
 
+use crate::{
+    error::typedstream::TypedStreamError,
+    util::typedstream::{
+        attributes::{read_runs, AttributeRun},
+        models::Archivable,
+        parser::TypedStreamReader,
+    },
+};
+
+pub mod attributes;
 pub mod models;
 pub mod parser;
 mod tests;
+pub mod value;
+
+/// The contents of an archived `NSAttributedString`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedAttributedString<'a> {
+    /// The plain text of the string
+    pub text: String,
+    /// The attributes applied to ranges of the text, in order of appearance
+    pub runs: Vec<AttributeRun>,
+    /// Every object in the stream, in order of appearance
+    ///
+    /// The first is the text itself. It is followed by pairs of ranges and the
+    /// attributes, like mentions or links, that apply to the characters in them.
+    pub components: Vec<Archivable<'a>>,
+}
+
+/// Decode `typedstream` data that holds an `NSAttributedString`
+///
+/// Returns [`TypedStreamError::MissingText`] if the stream is valid but does not start with a string.
+pub fn parse(stream: &[u8]) -> Result<ParsedAttributedString<'_>, TypedStreamError> {
+    let components = TypedStreamReader::from(stream).parse()?;
+    let text = components
+        .first()
+        .and_then(Archivable::as_nsstring)
+        .ok_or(TypedStreamError::MissingText)?
+        .to_string();
+    let runs = read_runs(&components, &text);
+    Ok(ParsedAttributedString {
+        text,
+        runs,
+        components,
+    })
+}
@@ -2,26 +2,40 @@
  Data structures and models used by the `typedstream` parser.
 */
 
+use std::{borrow::Cow, sync::Arc};
+
 /// Represents a class stored in the `typedstream`
+///
+/// The name is borrowed from the `typedstream` it was read from.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Class {
+pub struct Class<'a> {
     /// The name of the class
-    pub name: String,
+    pub name: Cow<'a, str>,
     /// The encoded version of the class
     pub version: u64,
 }
 
-impl Class {
-    pub(crate) fn new(name: String, version: u64) -> Self {
+impl<'a> Class<'a> {
+    pub(crate) fn new(name: Cow<'a, str>, version: u64) -> Self {
         Self { name, version }
     }
+
+    /// Copy the borrowed name, so the class can outlive the `typedstream` it was read from
+    pub fn into_owned(self) -> Class<'static> {
+        Class {
+            name: Cow::Owned(self.name.into_owned()),
+            version: self.version,
+        }
+    }
 }
 
 /// Rust structures containing data stored in the `typedstream`
+///
+/// Text and arrays are borrowed from the `typedstream` they were read from, unless they had to be decoded.
 #[derive(Debug, Clone, PartialEq)]
-pub enum OutputData {
+pub enum OutputData<'a> {
     /// Text data
-    String(String),
+    String(Cow<'a, str>),
     /// Signed integer types are coerced into this container
     SignedInteger(i64),
     /// Unsigned integer types are coerced into this container
@@ -33,31 +47,79 @@ pub enum OutputData {
     /// Bytes whose type is not known
     Byte(u8),
     /// Arbitrary collection of bytes in an array
-    Array(Vec<u8>),
+    Array(Cow<'a, [u8]>),
     /// A found class, in order of inheritance
-    Class(Class),
+    Class(Class<'a>),
+}
+
+impl OutputData<'_> {
+    /// Copy any borrowed data, so it can outlive the `typedstream` it was read from
+    pub fn into_owned(self) -> OutputData<'static> {
+        match self {
+            OutputData::String(text) => OutputData::String(Cow::Owned(text.into_owned())),
+            OutputData::SignedInteger(number) => OutputData::SignedInteger(number),
+            OutputData::UnsignedInteger(number) => OutputData::UnsignedInteger(number),
+            OutputData::Float(number) => OutputData::Float(number),
+            OutputData::Double(number) => OutputData::Double(number),
+            OutputData::Byte(byte) => OutputData::Byte(byte),
+            OutputData::Array(bytes) => OutputData::Array(Cow::Owned(bytes.into_owned())),
+            OutputData::Class(class) => OutputData::Class(class.into_owned()),
+        }
+    }
 }
 
 /// Types of data that can be archived into the `typedstream`
+///
+/// Parsed data borrows from the `typedstream`; use [`Archivable::into_owned`] to keep it after the stream is dropped.
 #[derive(Debug, Clone, PartialEq)]
-pub enum Archivable {
+pub enum Archivable<'a> {
     /// An instance of a class that may contain some embedded data. `typedstream` data doesn't include property
     /// names, so data is stored in order of appearance.
-    Object(Class, Vec<OutputData>),
+    Object(Class<'a>, Vec<OutputData<'a>>),
     /// Some data that is likely a property on the object described by the `typedstream` but not part of a class.
-    Data(Vec<OutputData>),
+    Data(Vec<OutputData<'a>>),
     /// A class referenced in the `typedstream`, usually part of an inheritance heirarchy that does not contain any data itself.
-    Class(Class),
+    Class(Class<'a>),
     /// A placeholder, only used when reserving a spot in the objects table for a reference to be filled with read class information.
     /// In a `typedstream`, the classes are stored in order of inheritance, so the top-level class described by the `typedstream`
     /// comes before the ones it inherits from. To preserve the order, we reserve the first slot to store the actual object's data
     /// and then later add it back to the right place.
     Placeholder,
     /// A type that made it through the parsing process without getting replaced by an object.
-    Type(Vec<Type>),
+    ///
+    /// The types are shared with the reader's table of types, so they are not copied.
+    Type(Arc<[Type<'a>]>),
 }
 
-impl Archivable {
+impl Archivable<'_> {
+    /// Copy any borrowed data, so it can outlive the `typedstream` it was read from
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use imessage_database::util::typedstream::{models::Archivable, parser::TypedStreamReader};
+    ///
+    /// let bytes: Vec<u8> = vec![]; // Example stream
+    /// let components: Vec<Archivable<'static>> = TypedStreamReader::from(&bytes)
+    ///     .parse()
+    ///     .map(|items| items.into_iter().map(Archivable::into_owned).collect())
+    ///     .unwrap_or_default();
+    /// ```
+    pub fn into_owned(self) -> Archivable<'static> {
+        match self {
+            Archivable::Object(class, data) => Archivable::Object(
+                class.into_owned(),
+                data.into_iter().map(OutputData::into_owned).collect(),
+            ),
+            Archivable::Data(data) => {
+                Archivable::Data(data.into_iter().map(OutputData::into_owned).collect())
+            }
+            Archivable::Class(class) => Archivable::Class(class.into_owned()),
+            Archivable::Placeholder => Archivable::Placeholder,
+            Archivable::Type(types) => Archivable::Type(types.iter().map(Type::to_owned).collect()),
+        }
+    }
+
     /// If `self` is an [`Object`](Archivable::Object) that contains a [`Class`] named `NSString` or `NSMutableString`,
     /// extract a Rust string slice from the associated [`Data`](Archivable::Data).
     ///
@@ -68,16 +130,16 @@ impl Archivable {
     ///
     /// let nsstring = Archivable::Object(
     ///     Class {
-    ///         name: "NSString".to_string(),
+    ///         name: "NSString".into(),
     ///         version: 1
     ///     },
-    ///     vec![OutputData::String("Hello world".to_string())]
+    ///     vec![OutputData::String("Hello world".into())]
     /// );
     /// println!("{:?}", nsstring.as_nsstring()); // Some("Hello world")
     ///
     /// let not_nsstring = Archivable::Object(
     ///     Class {
-    ///         name: "NSNumber".to_string(),
+    ///         name: "NSNumber".into(),
     ///         version: 1
     ///     },
     ///     vec![OutputData::SignedInteger(100)]
@@ -88,7 +150,7 @@ impl Archivable {
         if let Archivable::Object(Class { name, .. }, value) = self {
             if name == "NSString" || name == "NSMutableString" {
                 if let Some(OutputData::String(text)) = value.first() {
-                    return Some(text);
+                    return Some(text.as_ref());
                 }
             }
         }
@@ -105,7 +167,7 @@ impl Archivable {
     ///
     /// let nsnumber = Archivable::Object(
     ///     Class {
-    ///         name: "NSNumber".to_string(),
+    ///         name: "NSNumber".into(),
     ///         version: 1
     ///     },
     ///     vec![OutputData::SignedInteger(100)]
@@ -114,10 +176,10 @@ impl Archivable {
     ///
     /// let not_nsnumber = Archivable::Object(
     ///     Class {
-    ///         name: "NSString".to_string(),
+    ///         name: "NSString".into(),
     ///         version: 1
     ///     },
-    ///     vec![OutputData::String("Hello world".to_string())]
+    ///     vec![OutputData::String("Hello world".into())]
     /// );
     /// println!("{:?}", not_nsnumber.as_nsnumber()); // None
     /// ```
@@ -134,9 +196,8 @@ impl Archivable {
 }
 
 /// Represents primitive types of data that can be stored in a `typedstream`
-// TODO: Remove clone
-#[derive(Debug, Clone, PartialEq)]
-pub enum Type {
+#[derive(Debug, PartialEq)]
+pub enum Type<'a> {
     /// Encoded string data, usually embedded in an object. Denoted by:
     /// - Hex: `0x2B`, UTF-8: [`+`](https://www.compart.com/en/unicode/U+002B)
     Utf8String,
@@ -170,15 +231,15 @@ pub enum Type {
     /// An [`f64`]. Denoted by:
     /// - Hex: `0x64`, UTF-8: [`d`](https://www.compart.com/en/unicode/U+0064)
     Double,
-    /// Some text we can reuse later, i.e. a class name.
-    String(String),
+    /// Some text we can reuse later, i.e. a class name, borrowed from the `typedstream`.
+    String(Cow<'a, str>),
     /// An array containing some data of a given length. Denoted by braced digits: `[123]`.
     Array(usize),
     /// Data for which we do not know the type, likely for something this parser does not implement.
     Unknown(u8),
 }
 
-impl Type {
+impl<'a> Type<'a> {
     pub(crate) fn from_byte(byte: &u8) -> Self {
         match byte {
             0x40 => Self::Object,
@@ -192,21 +253,39 @@ impl Type {
         }
     }
 
-    pub(crate) fn new_string(string: String) -> Self {
+    pub(crate) fn new_string(string: Cow<'a, str>) -> Self {
         Self::String(string)
     }
 
-    pub(crate) fn get_array_length(types: &[u8]) -> Option<Vec<Type>> {
+    /// Copy any borrowed text, so the type can outlive the `typedstream` it was read from
+    pub fn to_owned(&self) -> Type<'static> {
+        match self {
+            Type::Utf8String => Type::Utf8String,
+            Type::EmbeddedData => Type::EmbeddedData,
+            Type::Object => Type::Object,
+            Type::SignedInt => Type::SignedInt,
+            Type::UnsignedInt => Type::UnsignedInt,
+            Type::Float => Type::Float,
+            Type::Double => Type::Double,
+            Type::String(text) => Type::String(Cow::Owned(text.to_string())),
+            Type::Array(size) => Type::Array(*size),
+            Type::Unknown(byte) => Type::Unknown(*byte),
+        }
+    }
+
+    pub(crate) fn get_array_length(types: &[u8]) -> Option<Vec<Type<'a>>> {
         if types.first() == Some(&0x5b) {
-            let len =
-                types[1..]
-                    .iter()
-                    .take_while(|a| a.is_ascii_digit())
-                    .fold(None, |acc, ch| {
-                        char::from_u32(*ch as u32)?
-                            .to_digit(10)
-                            .map(|b| acc.unwrap_or(0) * 10 + b)
-                    })?;
+            let len = types[1..]
+                .iter()
+                .take_while(|a| a.is_ascii_digit())
+                .try_fold(None, |acc, ch| {
+                    let digit = char::from_u32(*ch as u32)?.to_digit(10)?;
+                    // Lengths too large for a `u32` are not valid
+                    acc.unwrap_or(0_u32)
+                        .checked_mul(10)?
+                        .checked_add(digit)
+                        .map(Some)
+                })??;
             return Some(vec![Type::Array(len as usize)]);
         }
         None
@@ -215,9 +294,9 @@ impl Type {
 
 /// Represents data that results from attempting to parse a class from the `typedstream`
 #[derive(Debug)]
-pub(crate) enum ClassResult {
+pub(crate) enum ClassResult<'a> {
     /// A reference to an already-seen class in the [`TypedStreamReader::object_table`](crate::util::typedstream::parser::TypedStreamReader::object_table)
     Index(usize),
     /// A new class heirarchy to be inserted into the [`TypedStreamReader::object_table`](crate::util::typedstream::parser::TypedStreamReader::object_table)
-    ClassHierarchy(Vec<Archivable>),
+    ClassHierarchy(Vec<Archivable<'a>>),
 }
@@ -6,11 +6,21 @@
    - [`archive.c`](https://opensource.apple.com/source/gcc/gcc-5484/libobjc/archive.c.auto.html)
    - [`objc/typedstream.m`](https://archive.org/details/darwin_0.1)
 */
-use std::collections::HashSet;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::Arc,
+};
+
+use log::debug;
 
 use crate::{
     error::typedstream::TypedStreamError,
-    util::typedstream::models::{Archivable, Class, ClassResult, OutputData, Type},
+    util::typedstream::{
+        models::{Archivable, Class, ClassResult, OutputData, Type},
+        value::{build_values, Value},
+    },
 };
 
 /// Indicates an [`i16`] in the byte stream
@@ -46,13 +56,27 @@ pub struct TypedStreamReader<'a> {
     ///
     /// The first time a [`Type`] is seen, it is present in the stream literally,
     /// but afterwards are only referenced by index in order of appearance.
-    types_table: Vec<Vec<Type>>,
+    ///
+    /// Entries are shared with the objects that use them, so resolving a reference does not copy them.
+    types_table: Vec<Arc<[Type<'a>]>>,
     /// As we parse the `typedstream`, build a table of seen archivable data to reference in the future
-    object_table: Vec<Archivable>,
+    ///
+    /// Entries are shared with the output, so emitting an object does not copy it.
+    object_table: Vec<Arc<Archivable<'a>>>,
     /// We want to copy embedded types the first time they are seen, even if the types were resolved through references
     seen_embedded_types: HashSet<u32>,
     /// Stores the position of the current [`Archivable::Placeholder`]
     placeholder: Option<usize>,
+    /// The index in [`TypedStreamReader::object_table`] of the last object read through a reference
+    last_reference: Option<usize>,
+    /// The index in [`TypedStreamReader::object_table`] of the object that was just read, and whether it was a reference
+    emitted: Option<(usize, bool)>,
+    /// Maps the index of each object in [`TypedStreamReader::object_table`] to the index of the output where it first appeared
+    first_outputs: HashMap<usize, usize>,
+    /// For each output, the index of the earlier output it refers to, if it is a reference
+    references: Vec<Option<usize>>,
+    /// For each output, the bytes of the stream it was read from
+    spans: Vec<Range<usize>>,
 }
 
 impl<'a> TypedStreamReader<'a> {
@@ -74,6 +98,11 @@ impl<'a> TypedStreamReader<'a> {
             object_table: vec![],
             seen_embedded_types: HashSet::new(),
             placeholder: None,
+            last_reference: None,
+            emitted: None,
+            first_outputs: HashMap::new(),
+            references: vec![],
+            spans: vec![],
         }
     }
 
@@ -102,8 +131,12 @@ impl<'a> TypedStreamReader<'a> {
                 Ok(value as i64)
             }
             _ => {
-                if self.get_current_byte()? > REFERENCE_TAG as u8 && self.get_next_byte()? != END {
+                // Skip reference tags that precede the value
+                while self.get_current_byte()? > REFERENCE_TAG as u8 && self.get_next_byte()? != END
+                {
                     self.idx += 1;
+                }
+                if matches!(self.get_current_byte()?, I_16 | I_32) {
                     return self.read_signed_int();
                 }
                 let value = i8::from_le_bytes([self.get_current_byte()?]);
@@ -188,36 +221,40 @@ impl<'a> TypedStreamReader<'a> {
     }
 
     /// Read exactly `n` bytes from the stream
-    fn read_exact_bytes(&mut self, n: usize) -> Result<&[u8], TypedStreamError> {
-        let range =
-            self.stream
-                .get(self.idx..self.idx + n)
-                .ok_or(TypedStreamError::OutOfBounds(
-                    self.idx + n,
-                    self.stream.len(),
-                ))?;
-        self.idx += n;
+    ///
+    /// `n` is read from the stream itself, so it is checked against the remaining data before anything is allocated.
+    ///
+    /// The bytes are borrowed from the stream rather than from the reader, so they can be used while the reader changes.
+    fn read_exact_bytes(&mut self, n: usize) -> Result<&'a [u8], TypedStreamError> {
+        let end = self.idx.saturating_add(n);
+        let range = self
+            .stream
+            .get(self.idx..end)
+            .ok_or(TypedStreamError::OutOfBounds(end, self.stream.len()))?;
+        self.idx = end;
         Ok(range)
     }
 
-    /// Read `n` bytes as a String
-    fn read_exact_as_string(
-        &mut self,
-        n: usize,
-        string: &mut String,
-    ) -> Result<(), TypedStreamError> {
-        let str = std::str::from_utf8(self.read_exact_bytes(n)?)
-            .map_err(TypedStreamError::StringParseError)?;
-        string.push_str(str);
-        Ok(())
+    /// Read `n` bytes as a string slice borrowed from the stream
+    ///
+    /// Strings are usually UTF-8, but those that start with a UTF-16 byte order mark are read as UTF-16, which has to be copied.
+    fn read_exact_as_string(&mut self, n: usize) -> Result<Cow<'a, str>, TypedStreamError> {
+        let start = self.idx;
+        let bytes = self.read_exact_bytes(n)?;
+        match std::str::from_utf8(bytes) {
+            Ok(str) => Ok(Cow::Borrowed(str)),
+            Err(why) => decode_utf16(bytes)
+                .map(Cow::Owned)
+                .ok_or(TypedStreamError::StringParseError(start, why)),
+        }
     }
 
     /// Get the byte at a given index, if the index is within the bounds of the `typedstream`
     fn get_byte(&self, byte_idx: usize) -> Result<u8, TypedStreamError> {
-        if byte_idx < self.stream.len() {
-            return Ok(self.stream[byte_idx]);
-        }
-        Err(TypedStreamError::OutOfBounds(byte_idx, self.stream.len()))
+        self.stream
+            .get(byte_idx)
+            .copied()
+            .ok_or(TypedStreamError::OutOfBounds(byte_idx, self.stream.len()))
     }
 
     /// Read the current byte
@@ -230,38 +267,54 @@ impl<'a> TypedStreamReader<'a> {
         self.get_byte(self.idx + 1)
     }
 
-    /// Read some bytes as an array
-    fn read_array(&mut self, size: usize) -> Result<Vec<u8>, TypedStreamError> {
-        Ok(self.read_exact_bytes(size)?.to_vec())
+    /// Read some bytes as an array borrowed from the stream
+    fn read_array(&mut self, size: usize) -> Result<Cow<'a, [u8]>, TypedStreamError> {
+        Ok(Cow::Borrowed(self.read_exact_bytes(size)?))
     }
 
     /// Determine the current types
-    fn read_type(&mut self) -> Result<Vec<Type>, TypedStreamError> {
+    fn read_type(&mut self) -> Result<Vec<Type<'a>>, TypedStreamError> {
         let length = self.read_unsigned_int()?;
 
+        let start = self.idx;
         let types = self.read_exact_bytes(length as usize)?;
 
         // Handle array size
         if types.first() == Some(&0x5b) {
-            return Type::get_array_length(types).ok_or(TypedStreamError::InvalidArray);
+            return Type::get_array_length(types).ok_or(TypedStreamError::InvalidArray(start));
         }
 
         Ok(types.iter().map(Type::from_byte).collect())
     }
 
     /// Read a reference pointer for a Type
+    ///
+    /// Pointers are stored as signed integers counted from [`REFERENCE_TAG`], so a single byte refers to one of
+    /// the first 238 entries: `0x92` through `0xFF` are the first 110, and `0x00` through `0x7F` wrap around to the rest.
+    /// Tables larger than that use the [`I_16`] and [`I_32`] encodings.
     fn read_pointer(&mut self) -> Result<u32, TypedStreamError> {
+        let start = self.idx;
         let pointer = self.get_current_byte()?;
-        let result = (pointer as u32)
-            .checked_sub(REFERENCE_TAG as u32)
-            .ok_or(TypedStreamError::InvalidPointer(pointer));
-        self.idx += 1;
-        result
+        let value = match pointer {
+            I_16 | I_32 => self.read_signed_int()?,
+            // The other tags are never pointers
+            0x80..=0x91 => {
+                self.idx += 1;
+                return Err(TypedStreamError::InvalidPointer(start, pointer));
+            }
+            _ => {
+                self.idx += 1;
+                i8::from_le_bytes([pointer]) as i64
+            }
+        };
+        // `REFERENCE_TAG` is -110 as a signed byte
+        u32::try_from(value + 0x100 - REFERENCE_TAG as i64)
+            .map_err(|_| TypedStreamError::InvalidPointer(start, pointer))
     }
 
     /// Read a class
-    fn read_class(&mut self) -> Result<ClassResult, TypedStreamError> {
-        let mut out_v: Vec<Archivable> = vec![];
+    fn read_class(&mut self) -> Result<ClassResult<'a>, TypedStreamError> {
+        let mut out_v: Vec<Archivable<'a>> = vec![];
         match self.get_current_byte()? {
             START => {
                 // Skip some header bytes
@@ -275,13 +328,12 @@ impl<'a> TypedStreamReader<'a> {
                     return Ok(ClassResult::Index(index as usize));
                 }
 
-                let mut class_name = String::with_capacity(length as usize);
-                self.read_exact_as_string(length as usize, &mut class_name)?;
+                let class_name = self.read_exact_as_string(length as usize)?;
 
                 let version = self.read_unsigned_int()?;
 
                 self.types_table
-                    .push(vec![Type::new_string(class_name.clone())]);
+                    .push(Arc::new([Type::new_string(class_name.clone())]));
 
                 out_v.push(Archivable::Class(Class::new(class_name, version)));
 
@@ -301,17 +353,16 @@ impl<'a> TypedStreamReader<'a> {
     }
 
     /// Read an object into the cache and emit, or emit an already-cached object
-    fn read_object(&mut self) -> Result<Option<&Archivable>, TypedStreamError> {
+    fn read_object(&mut self) -> Result<Option<&Arc<Archivable<'a>>>, TypedStreamError> {
         match self.get_current_byte()? {
             START => {
                 match self.read_class()? {
                     ClassResult::Index(idx) => {
+                        self.last_reference = Some(idx);
                         return Ok(self.object_table.get(idx));
                     }
                     ClassResult::ClassHierarchy(classes) => {
-                        for class in classes.iter() {
-                            self.object_table.push(class.clone())
-                        }
+                        self.object_table.extend(classes.into_iter().map(Arc::new))
                     }
                 }
                 Ok(None)
@@ -322,22 +373,20 @@ impl<'a> TypedStreamReader<'a> {
             }
             _ => {
                 let index = self.read_pointer()?;
+                self.last_reference = Some(index as usize);
                 Ok(self.object_table.get(index as usize))
             }
         }
     }
 
     /// Read String data
-    fn read_string(&mut self) -> Result<String, TypedStreamError> {
+    fn read_string(&mut self) -> Result<Cow<'a, str>, TypedStreamError> {
         let length = self.read_unsigned_int()?;
-        let mut string = String::with_capacity(length as usize);
-        self.read_exact_as_string(length as usize, &mut string)?;
-
-        Ok(string)
+        self.read_exact_as_string(length as usize)
     }
 
     /// [`Archivable`] data can be embedded on a class or in a C String marked as [`Type::EmbeddedData`]
-    fn read_embedded_data(&mut self) -> Result<Option<Archivable>, TypedStreamError> {
+    fn read_embedded_data(&mut self) -> Result<Option<Arc<Archivable<'a>>>, TypedStreamError> {
         // Skip the 0x84
         self.idx += 1;
         match self.get_type(true)? {
@@ -349,22 +398,22 @@ impl<'a> TypedStreamReader<'a> {
     /// Gets the current type from the stream, either by reading it from the stream or reading it from
     /// the specified index of [`TypedStreamReader::types_table`]. Because methods that use this type can also mutate self,
     /// returning a reference here means other methods could make that reference to the table invalid,
-    /// which is disallowed in Rust. Thus, we return a clone of the cached data.
-    fn get_type(&mut self, embedded: bool) -> Result<Option<Vec<Type>>, TypedStreamError> {
+    /// which is disallowed in Rust. Thus, we return a shared handle to the cached data, which is not copied.
+    fn get_type(&mut self, embedded: bool) -> Result<Option<Arc<[Type<'a>]>>, TypedStreamError> {
         match self.get_current_byte()? {
             START => {
                 // Ignore repeated types, for example in a dict
                 self.idx += 1;
 
-                let object_types = self.read_type()?;
+                let object_types: Arc<[Type<'a>]> = self.read_type()?.into();
 
                 // Embedded data is stored as a C String in the objects table
                 if embedded {
                     self.object_table
-                        .push(Archivable::Type(object_types.clone()));
+                        .push(Arc::new(Archivable::Type(Arc::clone(&object_types))));
                 }
-                self.types_table.push(object_types);
-                Ok(self.types_table.last().cloned())
+                self.types_table.push(Arc::clone(&object_types));
+                Ok(Some(object_types))
             }
             END => {
                 // This indicates the end of the current object
@@ -383,7 +432,8 @@ impl<'a> TypedStreamReader<'a> {
                     if let Some(res) = result {
                         // We only want to include the first embedded reference tag, not subsequent references to the same embed
                         if !self.seen_embedded_types.contains(&ref_tag) {
-                            self.object_table.push(Archivable::Type(res.clone()));
+                            self.object_table
+                                .push(Arc::new(Archivable::Type(Arc::clone(res))));
                             self.seen_embedded_types.insert(ref_tag);
                         }
                     }
@@ -397,12 +447,12 @@ impl<'a> TypedStreamReader<'a> {
     /// Given some [`Type`]s, look at the stream and parse the data according to the specified [`Type`]
     fn read_types(
         &mut self,
-        found_types: Vec<Type>,
-    ) -> Result<Option<Archivable>, TypedStreamError> {
+        found_types: Arc<[Type<'a>]>,
+    ) -> Result<Option<Arc<Archivable<'a>>>, TypedStreamError> {
         let mut out_v = vec![];
         let mut is_obj: bool = false;
 
-        for found_type in found_types {
+        for found_type in found_types.iter() {
             match found_type {
                 Type::Utf8String => out_v.push(OutputData::String(self.read_string()?)),
                 Type::EmbeddedData => {
@@ -412,22 +462,22 @@ impl<'a> TypedStreamReader<'a> {
                     is_obj = true;
                     let length = self.object_table.len();
                     self.placeholder = Some(length);
-                    self.object_table.push(Archivable::Placeholder);
+                    self.object_table.push(Arc::new(Archivable::Placeholder));
+                    self.last_reference = None;
                     if let Some(object) = self.read_object()? {
-                        match object.clone() {
-                            Archivable::Object(_, data) => {
-                                // If this is a new object, i.e. one without any data, we add the data into it later
-                                // If the object already has data in it, we just want to return that object
-                                if !data.is_empty() {
-                                    let result = Ok(Some(object.clone()));
-                                    self.placeholder = None;
-                                    self.object_table.pop();
-                                    return result;
-                                }
-                                out_v.extend(data)
+                        match &**object {
+                            // If the object already has data in it, we just want to return that object
+                            Archivable::Object(_, data) if !data.is_empty() => {
+                                let result = Arc::clone(object);
+                                self.emitted = self.last_reference.map(|idx| (idx, true));
+                                self.placeholder = None;
+                                self.object_table.pop();
+                                return Ok(Some(result));
                             }
-                            Archivable::Class(cls) => out_v.push(OutputData::Class(cls)),
-                            Archivable::Data(data) => out_v.extend(data),
+                            // If this is a new object, i.e. one without any data, we add the data into it later
+                            Archivable::Object(..) => {}
+                            Archivable::Class(cls) => out_v.push(OutputData::Class(cls.clone())),
+                            Archivable::Data(data) => out_v.extend(data.iter().cloned()),
                             // These cases are used internally in the objects table but should not be present in any output
                             Archivable::Placeholder | Archivable::Type(_) => {}
                         }
@@ -439,9 +489,9 @@ impl<'a> TypedStreamReader<'a> {
                 }
                 Type::Float => out_v.push(OutputData::Float(self.read_float()?)),
                 Type::Double => out_v.push(OutputData::Double(self.read_double()?)),
-                Type::Unknown(byte) => out_v.push(OutputData::Byte(byte)),
-                Type::String(s) => out_v.push(OutputData::String(s)),
-                Type::Array(size) => out_v.push(OutputData::Array(self.read_array(size)?)),
+                Type::Unknown(byte) => out_v.push(OutputData::Byte(*byte)),
+                Type::String(s) => out_v.push(OutputData::String(s.clone())),
+                Type::Array(size) => out_v.push(OutputData::Array(self.read_array(*size)?)),
             };
         }
 
@@ -450,34 +500,44 @@ impl<'a> TypedStreamReader<'a> {
             if !out_v.is_empty() {
                 // We got a class, but do not have its respective data yet
                 if let Some(OutputData::Class(class)) = out_v.last() {
-                    self.object_table[spot] = Archivable::Object(class.clone(), vec![]);
+                    self.object_table[spot] = Arc::new(Archivable::Object(class.clone(), vec![]));
                 // The spot after the current placeholder contains the class at the top of the class heirarchy, i.e.
                 // if we get a placeholder and then find a new class heirarchy, the object table holds the class chain
                 // in descending order of inheritance
-                } else if let Some(Archivable::Class(class)) = self.object_table.get(spot + 1) {
-                    self.object_table[spot] = Archivable::Object(class.clone(), out_v.clone());
-                    self.placeholder = None;
-                    return Ok(self.object_table.get(spot).cloned());
+                } else if let Some(Archivable::Class(class)) =
+                    self.object_table.get(spot + 1).map(|object| &**object)
+                {
+                    self.object_table[spot] = Arc::new(Archivable::Object(class.clone(), out_v));
+                    return Ok(self.fill_placeholder(spot));
                 // We got some data for a class that was already seen
-                } else if let Some(Archivable::Object(_, data)) = self.object_table.get_mut(spot) {
-                    data.extend(out_v.clone());
-                    self.placeholder = None;
-                    return Ok(self.object_table.get(spot).cloned());
+                } else if let Some(Archivable::Object(_, data)) =
+                    self.object_table.get_mut(spot).map(Arc::make_mut)
+                {
+                    data.extend(out_v);
+                    return Ok(self.fill_placeholder(spot));
                 // We got some data that is not part of a class, i.e. a field in the parent object for which we don't know the name
                 } else {
-                    self.object_table[spot] = Archivable::Data(out_v.clone());
-                    self.placeholder = None;
-                    return Ok(self.object_table.get(spot).cloned());
+                    self.object_table[spot] = Arc::new(Archivable::Data(out_v));
+                    return Ok(self.fill_placeholder(spot));
                 }
             }
         }
 
         if !out_v.is_empty() && !is_obj {
-            return Ok(Some(Archivable::Data(out_v.clone())));
+            return Ok(Some(Arc::new(Archivable::Data(out_v))));
         }
         Ok(None)
     }
 
+    /// Finish filling the placeholder at `spot` in the objects table, emitting the object stored there
+    ///
+    /// The object is shared with the table so later references to it can be resolved without copying it.
+    fn fill_placeholder(&mut self, spot: usize) -> Option<Arc<Archivable<'a>>> {
+        self.placeholder = None;
+        self.emitted = Some((spot, false));
+        self.object_table.get(spot).map(Arc::clone)
+    }
+
     /// In the original source there are several variants of the header, but we
     /// only need to validate that this is the header used by macOS/iOS, as iMessage
     /// is probably not available on any NeXT platform
@@ -501,6 +561,9 @@ impl<'a> TypedStreamReader<'a> {
     /// Given a stream, construct a reader object to parse it. `typedstream` data doesn't include property
     /// names, so data is stored on [`Object`](crate::util::typedstream::models::Archivable::Object)s in order of appearance.
     ///
+    /// Malformed data never panics: an invalid header or a stream that ends in the middle of a value returns a
+    /// [`TypedStreamError`] with the byte offset of the problem, so callers can fall back to other sources of the text.
+    ///
     /// # Example:
     ///
     /// ```
@@ -519,8 +582,75 @@ impl<'a> TypedStreamReader<'a> {
     /// Object(Class { name: "NSString", version: 1 }, [String("__kIMMessagePartAttributeName")])  // The first key in the `NSDictionary`
     /// Object(Class { name: "NSNumber", version: 0 }, [Integer(0)])  // The first value in the `NSDictionary`
     /// ```
-    pub fn parse(&mut self) -> Result<Vec<Archivable>, TypedStreamError> {
+    pub fn parse(&mut self) -> Result<Vec<Archivable<'a>>, TypedStreamError> {
+        self.read_objects(false)
+    }
+
+    /// Attempt to get the data from the `typedstream`, skipping over any parts of it that are corrupt.
+    ///
+    /// When [`parse()`](Self::parse) finds an unknown tag or a reference to a type that was never defined,
+    /// it stops reading. This instead scans forward to the next byte that starts an object and continues
+    /// from there, so text that comes after the damage is still recovered. Objects read after a skip may
+    /// be incomplete, so this is best used as a fallback when [`parse()`](Self::parse) fails.
+    ///
+    /// An invalid header is still returned as an error, since there is nothing to recover.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::typedstream::parser::TypedStreamReader;
+    ///
+    /// let bytes: Vec<u8> = vec![]; // Example stream
+    /// let mut reader = TypedStreamReader::from(&bytes);
+    /// let result = reader.parse_with_recovery();
+    /// ```
+    pub fn parse_with_recovery(&mut self) -> Result<Vec<Archivable<'a>>, TypedStreamError> {
+        self.read_objects(true)
+    }
+
+    /// Attempt to get the data from the `typedstream` as a tree of [`Value`]s.
+    ///
+    /// [`parse()`](Self::parse) emits the contents of collections like `NSDictionary` and `NSArray` after the
+    /// collections themselves, and emits a reference to an object that was already read as a copy of it without
+    /// its contents. This nests the contents inside each collection and replaces references with the [`Value`]
+    /// they point to.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::typedstream::parser::TypedStreamReader;
+    ///
+    /// let bytes: Vec<u8> = vec![]; // Example stream
+    /// let mut reader = TypedStreamReader::from(&bytes);
+    /// let result = reader.parse_values();
+    /// ```
+    pub fn parse_values(&mut self) -> Result<Vec<Value<'a>>, TypedStreamError> {
+        let components = self.read_objects(false)?;
+        Ok(build_values(&components, &self.references))
+    }
+
+    /// Get the table of [`Type`]s built while reading the stream, in the order they were defined
+    ///
+    /// Types that are used more than once are only written out the first time, and are later referred to by their index in this table.
+    pub fn types(&self) -> &[Arc<[Type<'a>]>] {
+        &self.types_table
+    }
+
+    /// Get the bytes of the stream each object was read from, in the order the objects were emitted
+    ///
+    /// This is only filled in after the stream is parsed, and is used to find the data behind each object when debugging.
+    pub fn spans(&self) -> &[Range<usize>] {
+        &self.spans
+    }
+
+    /// Read every object in the stream, optionally skipping past data that cannot be read
+    ///
+    /// Objects are only copied if they appear in the output more than once, i.e. when they are referenced.
+    fn read_objects(&mut self, recover: bool) -> Result<Vec<Archivable<'a>>, TypedStreamError> {
         let mut out_v = vec![];
+        self.first_outputs.clear();
+        self.references.clear();
+        self.spans.clear();
 
         self.validate_header()?;
 
@@ -529,16 +659,87 @@ impl<'a> TypedStreamReader<'a> {
                 self.idx += 1;
                 continue;
             }
+            let start = self.idx;
+            self.emitted = None;
 
             // First, get the current type
-            if let Some(found_types) = self.get_type(false)? {
-                let result = self.read_types(found_types);
-                if let Ok(Some(res)) = result {
-                    out_v.push(res);
+            let found_types = match self.get_type(false) {
+                Ok(found_types) => found_types,
+                Err(why) if recover => {
+                    self.skip_to_next_object(start, &why);
+                    continue;
+                }
+                Err(why) => return Err(why),
+            };
+
+            match found_types {
+                Some(found_types) => match self.read_types(found_types) {
+                    Ok(Some(res)) => {
+                        self.record_output(out_v.len());
+                        self.spans.push(start..self.idx);
+                        out_v.push(res);
+                    }
+                    Ok(None) => {}
+                    Err(why) if recover => self.skip_to_next_object(start, &why),
+                    Err(why) => return Err(why),
+                },
+                // The type refers to an entry that is not in the table of seen types
+                None if recover => {
+                    self.skip_to_next_object(start, &TypedStreamError::InvalidPointer(start, 0))
                 }
+                None => return Err(TypedStreamError::InvalidPointer(start, 0)),
             }
         }
 
-        Ok(out_v)
+        // The table is not needed once the stream is read, so the objects it shares with the output can be moved out
+        self.object_table.clear();
+        Ok(out_v
+            .into_iter()
+            .map(|object| Arc::try_unwrap(object).unwrap_or_else(|shared| (*shared).clone()))
+            .collect())
+    }
+
+    /// Remember where the object that was just read first appeared, so references to it can be resolved
+    fn record_output(&mut self, output_idx: usize) {
+        let reference = match self.emitted.take() {
+            Some((table_idx, true)) => self.first_outputs.get(&table_idx).copied(),
+            Some((table_idx, false)) => {
+                self.first_outputs.entry(table_idx).or_insert(output_idx);
+                None
+            }
+            None => None,
+        };
+        self.references.push(reference);
+    }
+
+    /// Move to the first byte after `start` that begins an object, or to the end of the stream if there is none
+    fn skip_to_next_object(&mut self, start: usize, why: &TypedStreamError) {
+        debug!("Skipping corrupt typedstream data at byte {start}: {why}");
+        self.placeholder = None;
+        self.idx = self
+            .stream
+            .get(start + 1..)
+            .and_then(|rest| rest.iter().position(|byte| *byte == START))
+            .map_or(self.stream.len(), |offset| start + 1 + offset);
+    }
+}
+
+/// Decode UTF-16 bytes that start with a byte order mark
+fn decode_utf16(bytes: &[u8]) -> Option<String> {
+    let read_unit: fn([u8; 2]) -> u16 = match bytes.get(0..2)? {
+        [0xFF, 0xFE] => u16::from_le_bytes,
+        [0xFE, 0xFF] => u16::from_be_bytes,
+        _ => return None,
+    };
+    let units = bytes.get(2..)?;
+    if units.len() % 2 != 0 {
+        return None;
     }
+    char::decode_utf16(
+        units
+            .chunks_exact(2)
+            .map(|unit| read_unit([unit[0], unit[1]])),
+    )
+    .collect::<Result<String, _>>()
+    .ok()
 }
@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod attribute_tests {
+    use std::env::current_dir;
+    use std::fs::File;
+    use std::io::Read;
+
+    use crate::util::typedstream::{
+        attributes::{
+            AttributeRun, AttributeValue, LINK_ATTRIBUTE, MENTION_ATTRIBUTE, MESSAGE_PART_ATTRIBUTE,
+        },
+        parse,
+    };
+
+    fn read_test_data(name: &str) -> Vec<u8> {
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream")
+            .join(name);
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn can_get_runs_basic() {
+        let bytes = read_test_data("AttributedBodyTextOnly");
+        let result = parse(&bytes).unwrap();
+
+        let expected = vec![AttributeRun {
+            range: 0..10,
+            name: MESSAGE_PART_ATTRIBUTE.to_string(),
+            value: AttributeValue::Number(0),
+        }];
+
+        assert_eq!(result.runs, expected);
+    }
+
+    #[test]
+    fn can_get_runs_mention() {
+        let bytes = read_test_data("Mention");
+        let result = parse(&bytes).unwrap();
+
+        let expected = vec![
+            AttributeRun {
+                range: 0..5,
+                name: MESSAGE_PART_ATTRIBUTE.to_string(),
+                value: AttributeValue::Number(0),
+            },
+            AttributeRun {
+                range: 5..8,
+                name: MENTION_ATTRIBUTE.to_string(),
+                value: AttributeValue::String("+15558675309".to_string()),
+            },
+            AttributeRun {
+                range: 5..8,
+                name: MESSAGE_PART_ATTRIBUTE.to_string(),
+                value: AttributeValue::Number(0),
+            },
+        ];
+
+        assert_eq!(result.text, "Test Dad ");
+        assert_eq!(result.runs, expected);
+    }
+
+    #[test]
+    fn can_get_runs_link() {
+        let bytes = read_test_data("URLMessage");
+        let result = parse(&bytes).unwrap();
+
+        let names: Vec<&str> = result.runs.iter().map(|run| run.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                LINK_ATTRIBUTE,
+                MESSAGE_PART_ATTRIBUTE,
+                "__kIMDataDetectedAttributeName",
+                "__kIMBaseWritingDirectionAttributeName",
+            ]
+        );
+        assert!(result.runs.iter().all(|run| run.range == (0..56)));
+        assert_eq!(
+            result.runs[0].value,
+            AttributeValue::Url(
+                "https://twitter.com/xxxxxxxxx/status/0000223300009216128".to_string()
+            )
+        );
+        assert!(matches!(&result.runs[2].value, AttributeValue::Data(bytes) if bytes.len() == 604));
+        assert_eq!(result.runs[3].value, AttributeValue::Number(-1));
+    }
+
+    #[test]
+    fn can_get_runs_astral_chars() {
+        // Each letter is outside the Basic Multilingual Plane, so it is two UTF-16 code units
+        let bytes = read_test_data("WeirdText");
+        let result = parse(&bytes).unwrap();
+
+        let ranges: Vec<_> = result.runs.iter().map(|run| run.range.clone()).collect();
+
+        assert_eq!(result.text, "𝖍𝖊𝖑𝖑𝖔 𝖜𝖔𝖗𝖑𝖉");
+        assert_eq!(ranges, vec![0..11]);
+    }
+}
@@ -5,9 +5,13 @@ mod parser_tests {
     use std::io::Read;
     use std::vec;
 
-    use crate::util::typedstream::{
-        models::{Archivable, Class, OutputData},
-        parser::TypedStreamReader,
+    use crate::{
+        error::typedstream::TypedStreamError,
+        util::typedstream::{
+            models::{Archivable, Class, OutputData, Type},
+            parse,
+            parser::TypedStreamReader,
+        },
     };
 
     #[test]
@@ -45,10 +49,10 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSMutableString".to_string(),
+                    name: "NSMutableString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("Test Dad ".to_string())],
+                vec![OutputData::String("Test Dad ".into())],
             ),
             Archivable::Data(vec![
                 OutputData::SignedInteger(1),
@@ -56,23 +60,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -83,39 +85,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMentionConfirmedMention".to_string(),
-                )],
+                vec![OutputData::String("__kIMMentionConfirmedMention".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("+15558675309".to_string())],
+                vec![OutputData::String("+15558675309".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -148,10 +146,10 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSMutableString".to_string(),
+                    name: "NSMutableString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("Noter test".to_string())],
+                vec![OutputData::String("Noter test".into())],
             ),
             Archivable::Data(vec![
                 OutputData::SignedInteger(1),
@@ -159,23 +157,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -204,10 +200,10 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("Test 3".to_string())],
+                vec![OutputData::String("Test 3".into())],
             ),
             Archivable::Data(vec![
                 OutputData::SignedInteger(1),
@@ -215,39 +211,37 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -280,23 +274,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -326,11 +318,11 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSMutableString".to_string(),
+                    name: "NSMutableString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "\u{FFFC}test 1\u{FFFC}test 2 \u{FFFC}test 3".to_string(),
+                    "\u{FFFC}test 1\u{FFFC}test 2 \u{FFFC}test 3".into(),
                 )],
             ),
             Archivable::Data(vec![
@@ -339,41 +331,39 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMFileTransferGUIDAttributeName".to_string(),
+                    "__kIMFileTransferGUIDAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "at_0_F0668F79-20C2-49C9-A87F-1B007ABB0CED".to_string(),
+                    "at_0_F0668F79-20C2-49C9-A87F-1B007ABB0CED".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -384,23 +374,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
@@ -411,41 +399,39 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMFileTransferGUIDAttributeName".to_string(),
+                    "__kIMFileTransferGUIDAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "at_2_F0668F79-20C2-49C9-A87F-1B007ABB0CED".to_string(),
+                    "at_2_F0668F79-20C2-49C9-A87F-1B007ABB0CED".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
@@ -456,23 +442,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(3)],
@@ -483,41 +467,39 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMFileTransferGUIDAttributeName".to_string(),
+                    "__kIMFileTransferGUIDAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "at_4_F0668F79-20C2-49C9-A87F-1B007ABB0CED".to_string(),
+                    "at_4_F0668F79-20C2-49C9-A87F-1B007ABB0CED".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(4)],
@@ -528,23 +510,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(5)],
@@ -573,11 +553,11 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSMutableString".to_string(),
+                    name: "NSMutableString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "From arbitrary byte stream:\r￼To native Rust data structures:\r".to_string(),
+                    "From arbitrary byte stream:\r￼To native Rust data structures:\r".into(),
                 )],
             ),
             Archivable::Data(vec![
@@ -586,23 +566,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -613,41 +591,39 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMFileTransferGUIDAttributeName".to_string(),
+                    "__kIMFileTransferGUIDAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "D0551D89-4E11-43D0-9A0E-06F19704E97B".to_string(),
+                    "D0551D89-4E11-43D0-9A0E-06F19704E97B".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
@@ -658,23 +634,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
@@ -707,115 +681,115 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSMutableString".to_string(),
+                    name: "NSMutableString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("\u{FFFC}This is how the notes look to me fyi, in case it helps make sense of anything".to_string())],
+                vec![OutputData::String("\u{FFFC}This is how the notes look to me fyi, in case it helps make sense of anything".into())],
             ),
             Archivable::Data(vec![OutputData::SignedInteger(1), OutputData::UnsignedInteger(1)]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(6)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMFileTransferGUIDAttributeName".to_string(),
+                    "__kIMFileTransferGUIDAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "at_0_2E5F12C3-E649-48AA-954D-3EA67C016BCC".to_string(),
+                    "at_0_2E5F12C3-E649-48AA-954D-3EA67C016BCC".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMInlineMediaHeightAttributeName".to_string(),
+                    "__kIMInlineMediaHeightAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::Double(1139.0)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
+                    "__kIMMessagePartAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMFilenameAttributeName".to_string(),
+                    "__kIMFilenameAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "Messages Image(785748029).png".to_string(),
+                    "Messages Image(785748029).png".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMInlineMediaWidthAttributeName".to_string(),
+                    "__kIMInlineMediaWidthAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::Double(952.0)],
@@ -823,39 +797,39 @@ mod parser_tests {
             Archivable::Data(vec![OutputData::SignedInteger(2), OutputData::UnsignedInteger(77)]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
+                    "__kIMMessagePartAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
@@ -883,10 +857,10 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSMutableString".to_string(),
+                    name: "NSMutableString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("\u{FFFC}".to_string())],
+                vec![OutputData::String("\u{FFFC}".into())],
             ),
             Archivable::Data(vec![
                 OutputData::SignedInteger(1),
@@ -894,105 +868,103 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(6)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMFileTransferGUIDAttributeName".to_string(),
+                    "__kIMFileTransferGUIDAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "at_0_BE588799-C4BC-47DF-A56D-7EE90C74911D".to_string(),
+                    "at_0_BE588799-C4BC-47DF-A56D-7EE90C74911D".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMInlineMediaHeightAttributeName".to_string(),
+                    "__kIMInlineMediaHeightAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(600)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("__kIMFilenameAttributeName".to_string())],
+                vec![OutputData::String("__kIMFilenameAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "brilliant-kids-test-answers-32-93042.jpeg".to_string(),
+                    "brilliant-kids-test-answers-32-93042.jpeg".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMInlineMediaWidthAttributeName".to_string(),
+                    "__kIMInlineMediaWidthAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(660)],
@@ -1024,11 +996,11 @@ mod parser_tests {
         let expected_1 = vec![
             Archivable::Object(
                 Class {
-                    name: "NSMutableString".to_string(),
+                    name: "NSMutableString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "https://twitter.com/xxxxxxxxx/status/0000223300009216128".to_string(),
+                    "https://twitter.com/xxxxxxxxx/status/0000223300009216128".into(),
                 )],
             ),
             Archivable::Data(vec![
@@ -1037,62 +1009,58 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(4)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("__kIMLinkAttributeName".to_string())],
+                vec![OutputData::String("__kIMLinkAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSURL".to_string(),
+                    name: "NSURL".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "https://twitter.com/xxxxxxxxx/status/0000223300009216128".to_string(),
+                    "https://twitter.com/xxxxxxxxx/status/0000223300009216128".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMDataDetectedAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMDataDetectedAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSMutableData".to_string(),
+                    name: "NSMutableData".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(604)],
@@ -1102,16 +1070,16 @@ mod parser_tests {
         let expected_2 = vec![
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
@@ -1142,11 +1110,11 @@ mod parser_tests {
         let expected_1 = vec![
             Archivable::Object(
                 Class {
-                    name: "NSMutableString".to_string(),
+                    name: "NSMutableString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "A single ChatGPT instance takes 5MW of power to run".to_string(),
+                    "A single ChatGPT instance takes 5MW of power to run".into(),
                 )],
             ),
             Archivable::Data(vec![
@@ -1155,23 +1123,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -1182,23 +1148,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMDataDetectedAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMDataDetectedAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSData".to_string(),
+                    name: "NSData".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(904)],
@@ -1208,16 +1172,14 @@ mod parser_tests {
         let expected_2 = vec![
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -1251,10 +1213,10 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("\u{FFFC}".to_string())],
+                vec![OutputData::String("\u{FFFC}".into())],
             ),
             Archivable::Data(vec![
                 OutputData::SignedInteger(1),
@@ -1262,57 +1224,55 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(3)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMFileTransferGUIDAttributeName".to_string(),
+                    "__kIMFileTransferGUIDAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "F0B18A15-E9A5-4B18-A38F-685B7B3FF037".to_string(),
+                    "F0B18A15-E9A5-4B18-A38F-685B7B3FF037".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -1341,12 +1301,12 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
                     "Reacted with a sticker to “Like I wonder if the stickers can be reactions ”￼"
-                        .to_string(),
+                        .into(),
                 )],
             ),
             Archivable::Data(vec![
@@ -1355,39 +1315,37 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -1398,57 +1356,55 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(3)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMFileTransferGUIDAttributeName".to_string(),
+                    "__kIMFileTransferGUIDAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "41C4376E-397E-4C42-84E2-B16F7801F638".to_string(),
+                    "41C4376E-397E-4C42-84E2-B16F7801F638".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
@@ -1480,11 +1436,11 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "Bold underline italic strikethrough all four".to_string(),
+                    "Bold underline italic strikethrough all four".into(),
                 )],
             ),
             Archivable::Data(vec![
@@ -1493,37 +1449,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("__kIMTextBoldAttributeName".to_string())],
+                vec![OutputData::String("__kIMTextBoldAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -1534,23 +1488,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -1561,39 +1513,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextUnderlineAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextUnderlineAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -1608,39 +1556,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextItalicAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextItalicAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -1655,39 +1599,37 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMTextStrikethroughAttributeName".to_string(),
+                    "__kIMTextStrikethroughAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -1702,85 +1644,79 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(5)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("__kIMTextBoldAttributeName".to_string())],
+                vec![OutputData::String("__kIMTextBoldAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMTextStrikethroughAttributeName".to_string(),
+                    "__kIMTextStrikethroughAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextUnderlineAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextUnderlineAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextItalicAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextItalicAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
@@ -1812,10 +1748,10 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("Everything".to_string())],
+                vec![OutputData::String("Everything".into())],
             ),
             Archivable::Data(vec![
                 OutputData::SignedInteger(1),
@@ -1823,85 +1759,79 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(5)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String("__kIMTextBoldAttributeName".to_string())],
+                vec![OutputData::String("__kIMTextBoldAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMTextStrikethroughAttributeName".to_string(),
+                    "__kIMTextStrikethroughAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextUnderlineAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextUnderlineAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextItalicAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextItalicAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
@@ -1933,11 +1863,11 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "Big small shake nod explode ripple bloom jitter".to_string(),
+                    "Big small shake nod explode ripple bloom jitter".into(),
                 )],
             ),
             Archivable::Data(vec![
@@ -1946,39 +1876,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextEffectAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextEffectAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(5)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -1989,23 +1915,21 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -2016,39 +1940,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextEffectAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextEffectAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(11)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -2059,39 +1979,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextEffectAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextEffectAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(9)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -2106,39 +2022,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextEffectAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextEffectAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(8)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -2153,39 +2065,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextEffectAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextEffectAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(12)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -2196,39 +2104,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextEffectAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextEffectAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(4)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -2243,39 +2147,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextEffectAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextEffectAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(6)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -2290,39 +2190,35 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextEffectAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextEffectAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(10)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -2354,12 +2250,10 @@ mod parser_tests {
         let expected = vec![
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "Underline normal jitter normal".to_string(),
-                )],
+                vec![OutputData::String("Underline normal jitter normal".into())],
             ),
             Archivable::Data(vec![
                 OutputData::SignedInteger(1),
@@ -2367,55 +2261,51 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(3)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextUnderlineAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextUnderlineAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(1)],
@@ -2426,39 +2316,37 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(2)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
@@ -2469,55 +2357,51 @@ mod parser_tests {
             ]),
             Archivable::Object(
                 Class {
-                    name: "NSDictionary".to_string(),
+                    name: "NSDictionary".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(3)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
                 vec![OutputData::String(
-                    "__kIMBaseWritingDirectionAttributeName".to_string(),
+                    "__kIMBaseWritingDirectionAttributeName".into(),
                 )],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(-1)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMMessagePartAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMMessagePartAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(0)],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSString".to_string(),
+                    name: "NSString".into(),
                     version: 1,
                 },
-                vec![OutputData::String(
-                    "__kIMTextEffectAttributeName".to_string(),
-                )],
+                vec![OutputData::String("__kIMTextEffectAttributeName".into())],
             ),
             Archivable::Object(
                 Class {
-                    name: "NSNumber".to_string(),
+                    name: "NSNumber".into(),
                     version: 0,
                 },
                 vec![OutputData::SignedInteger(10)],
@@ -2530,4 +2414,286 @@ mod parser_tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn cant_parse_truncated_header() {
+        let bytes: Vec<u8> = vec![0x04, 0x0b, b's', b't', b'r', b'e', b'a', b'm'];
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse();
+
+        assert!(matches!(result, Err(TypedStreamError::OutOfBounds(13, 8))));
+    }
+
+    #[test]
+    fn cant_parse_invalid_utf8_header() {
+        let bytes: Vec<u8> = vec![0x04, 0x02, 0xc3, 0x28];
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.validate_header();
+
+        assert!(matches!(
+            result,
+            Err(TypedStreamError::StringParseError(2, _))
+        ));
+    }
+
+    #[test]
+    fn can_parse_string_longer_than_stream() {
+        // A valid header followed by a string that claims to be 4 GB long
+        let mut bytes: Vec<u8> = vec![0x04, 0x0b];
+        bytes.extend_from_slice(b"streamtyped");
+        bytes.extend_from_slice(&[0x81, 0xe8, 0x03]);
+        bytes.extend_from_slice(&[0x84, 0x01, 0x2b, 0x82, 0xff, 0xff, 0xff, 0xff]);
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse();
+
+        assert!(matches!(
+            result,
+            Err(TypedStreamError::OutOfBounds(end, 24)) if end == 24 + 0xffff_ffff
+        ));
+    }
+
+    #[test]
+    fn can_parse_attributed_string() {
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/AttributedBodyTextOnly");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let result = parse(&bytes).unwrap();
+
+        assert_eq!(result.text, "Noter test");
+        assert_eq!(
+            result.components.first().unwrap().as_nsstring(),
+            Some("Noter test")
+        );
+    }
+
+    #[test]
+    fn cant_parse_attributed_string_without_text() {
+        // A valid header followed by a single integer
+        let mut bytes: Vec<u8> = vec![0x04, 0x0b];
+        bytes.extend_from_slice(b"streamtyped");
+        bytes.extend_from_slice(&[0x81, 0xe8, 0x03]);
+        bytes.extend_from_slice(&[0x84, 0x01, 0x69, 0x05]);
+
+        let result = parse(&bytes);
+
+        assert!(matches!(result, Err(TypedStreamError::MissingText)));
+    }
+
+    /// Build a stream with a valid header, a string, a corrupt byte, and another string
+    fn corrupt_stream() -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![0x04, 0x0b];
+        bytes.extend_from_slice(b"streamtyped");
+        bytes.extend_from_slice(&[0x81, 0xe8, 0x03]);
+        bytes.extend_from_slice(&[0x84, 0x01, 0x2b, 0x05]);
+        bytes.extend_from_slice(b"Hello");
+        bytes.push(0x85);
+        bytes.extend_from_slice(&[0x84, 0x01, 0x2b, 0x05]);
+        bytes.extend_from_slice(b"World");
+        bytes
+    }
+
+    #[test]
+    fn cant_parse_corrupt_stream() {
+        let bytes = corrupt_stream();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse();
+
+        assert!(matches!(
+            result,
+            Err(TypedStreamError::InvalidPointer(25, 0x85))
+        ));
+    }
+
+    #[test]
+    fn can_parse_corrupt_stream_with_recovery() {
+        let bytes = corrupt_stream();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse_with_recovery().unwrap();
+
+        let expected = vec![
+            Archivable::Data(vec![OutputData::String("Hello".into())]),
+            Archivable::Data(vec![OutputData::String("World".into())]),
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_recover_text_before_corrupt_attributes() {
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/AttributedBodyTextOnly");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        // Replace the start of the first range with a tag that cannot begin a type
+        bytes[0x84] = 0x85;
+
+        assert!(TypedStreamReader::from(&bytes).parse().is_err());
+
+        let result = TypedStreamReader::from(&bytes)
+            .parse_with_recovery()
+            .unwrap();
+        assert_eq!(
+            result.first().and_then(Archivable::as_nsstring),
+            Some("Noter test")
+        );
+    }
+
+    #[test]
+    fn cant_recover_invalid_header() {
+        let bytes: Vec<u8> = vec![0x04, 0x0b, 0x84, 0x01, 0x2b];
+
+        let mut parser = TypedStreamReader::from(&bytes);
+
+        assert!(parser.parse_with_recovery().is_err());
+    }
+
+    /// Build a stream with a valid header and `count` types that each hold an empty string
+    fn stream_with_types(count: usize) -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![0x04, 0x0b];
+        bytes.extend_from_slice(b"streamtyped");
+        bytes.extend_from_slice(&[0x81, 0xe8, 0x03]);
+        for _ in 0..count {
+            bytes.extend_from_slice(&[0x84, 0x01, 0x2b, 0x00]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn can_parse_single_byte_pointer_past_tags() {
+        // 110 types fill the pointers from `0x92` to `0xFF`, so the next type is at `0x00`
+        let mut bytes = stream_with_types(110);
+        bytes.extend_from_slice(&[0x84, 0x01, 0x69, 0x05]);
+        bytes.extend_from_slice(&[0x00, 0x07]);
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse().unwrap();
+
+        assert_eq!(result.len(), 112);
+        assert_eq!(
+            result.last(),
+            Some(&Archivable::Data(vec![OutputData::SignedInteger(7)]))
+        );
+    }
+
+    #[test]
+    fn can_parse_two_byte_pointer() {
+        // The type at index 400 is stored as 290 after the `i16` tag
+        let mut bytes = stream_with_types(400);
+        bytes.extend_from_slice(&[0x84, 0x01, 0x69, 0x05]);
+        bytes.extend_from_slice(&[0x81, 0x22, 0x01, 0x07]);
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse().unwrap();
+
+        assert_eq!(result.len(), 402);
+        assert_eq!(
+            result.last(),
+            Some(&Archivable::Data(vec![OutputData::SignedInteger(7)]))
+        );
+    }
+
+    #[test]
+    fn can_parse_string_with_two_byte_length() {
+        let text = "a".repeat(300);
+        let mut bytes = stream_with_types(0);
+        bytes.extend_from_slice(&[0x84, 0x01, 0x2b, 0x81, 0x2c, 0x01]);
+        bytes.extend_from_slice(text.as_bytes());
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse().unwrap();
+
+        assert_eq!(
+            result,
+            vec![Archivable::Data(vec![OutputData::String(text.into())])]
+        );
+    }
+
+    #[test]
+    fn can_parse_utf16_string() {
+        let text = "Hi 😀";
+        let mut encoded = vec![0xff, 0xfe];
+        encoded.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+
+        let mut bytes = stream_with_types(0);
+        bytes.extend_from_slice(&[0x84, 0x01, 0x2b, encoded.len() as u8]);
+        bytes.extend_from_slice(&encoded);
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse().unwrap();
+
+        assert_eq!(
+            result,
+            vec![Archivable::Data(vec![OutputData::String(text.into())])]
+        );
+    }
+
+    #[test]
+    fn can_parse_utf16_big_endian_string() {
+        let text = "שלום";
+        let mut encoded = vec![0xfe, 0xff];
+        encoded.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+
+        let mut bytes = stream_with_types(0);
+        bytes.extend_from_slice(&[0x84, 0x01, 0x2b, encoded.len() as u8]);
+        bytes.extend_from_slice(&encoded);
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse().unwrap();
+
+        assert_eq!(
+            result,
+            vec![Archivable::Data(vec![OutputData::String(text.into())])]
+        );
+    }
+
+    #[test]
+    fn can_get_spans_and_types() {
+        let header = stream_with_types(0);
+        let mut bytes = header.clone();
+        bytes.extend_from_slice(&[0x84, 0x01, 0x2b, 0x04]);
+        bytes.extend_from_slice(b"meme");
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        parser.parse().unwrap();
+
+        assert_eq!(parser.spans().len(), 1);
+        assert_eq!(parser.spans()[0], header.len()..bytes.len());
+        assert_eq!(parser.types().len(), 1);
+        assert_eq!(*parser.types()[0], [Type::Utf8String]);
+    }
+
+    #[test]
+    fn can_get_spans_mention() {
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/Mention");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse().unwrap();
+        let spans = parser.spans();
+
+        assert_eq!(spans.len(), result.len());
+        assert!(spans.windows(2).all(|pair| pair[0].end <= pair[1].start));
+        assert!(spans
+            .iter()
+            .all(|span| span.start < span.end && span.end <= bytes.len()));
+    }
 }
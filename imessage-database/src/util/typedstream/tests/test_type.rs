@@ -20,4 +20,13 @@ mod type_tests {
 
         assert!(result.is_none())
     }
+
+    #[test]
+    fn cant_get_array_overflow() {
+        let items: Vec<u8> = b"[99999999999c]".to_vec();
+
+        let result = Type::get_array_length(&items);
+
+        assert!(result.is_none())
+    }
 }
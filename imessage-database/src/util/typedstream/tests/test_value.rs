@@ -0,0 +1,162 @@
+#[cfg(test)]
+mod value_tests {
+    use std::env::current_dir;
+    use std::fs::File;
+    use std::io::Read;
+
+    use crate::util::typedstream::{
+        models::{Archivable, Class, OutputData},
+        parser::TypedStreamReader,
+        value::{build_values, Value},
+    };
+
+    fn read_test_data(name: &str) -> Vec<u8> {
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream")
+            .join(name);
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn object<'a>(name: &'a str, data: Vec<OutputData<'a>>) -> Archivable<'a> {
+        Archivable::Object(
+            Class {
+                name: name.into(),
+                version: 0,
+            },
+            data,
+        )
+    }
+
+    #[test]
+    fn can_parse_values_mention() {
+        let bytes = read_test_data("Mention");
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse_values().unwrap();
+
+        let expected = vec![
+            Value::String("Test Dad ".into()),
+            Value::Data(vec![
+                OutputData::SignedInteger(1),
+                OutputData::UnsignedInteger(5),
+            ]),
+            Value::Dictionary(vec![(
+                Value::String("__kIMMessagePartAttributeName".into()),
+                Value::Integer(0),
+            )]),
+            Value::Data(vec![
+                OutputData::SignedInteger(2),
+                OutputData::UnsignedInteger(3),
+            ]),
+            Value::Dictionary(vec![
+                (
+                    Value::String("__kIMMentionConfirmedMention".into()),
+                    Value::String("+15558675309".into()),
+                ),
+                (
+                    Value::String("__kIMMessagePartAttributeName".into()),
+                    Value::Integer(0),
+                ),
+            ]),
+            Value::Data(vec![
+                OutputData::SignedInteger(1),
+                OutputData::UnsignedInteger(1),
+            ]),
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_parse_values_data() {
+        let bytes = read_test_data("Array");
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse_values().unwrap();
+
+        let detected = result
+            .iter()
+            .find_map(|value| value.get("__kIMDataDetectedAttributeName"));
+
+        assert!(matches!(detected, Some(Value::Bytes(bytes)) if bytes.len() == 904));
+    }
+
+    #[test]
+    fn can_build_values_from_reference() {
+        let components = vec![
+            object("NSDictionary", vec![OutputData::SignedInteger(1)]),
+            object("NSString", vec![OutputData::String("key".into())]),
+            object("NSNumber", vec![OutputData::SignedInteger(7)]),
+            // A reference to the dictionary, which does not repeat its contents
+            object("NSDictionary", vec![OutputData::SignedInteger(1)]),
+            object("NSString", vec![OutputData::String("after".into())]),
+        ];
+        let references = vec![None, None, None, Some(0), None];
+
+        let result = build_values(&components, &references);
+
+        let dictionary = Value::Dictionary(vec![(Value::String("key".into()), Value::Integer(7))]);
+        assert_eq!(
+            result,
+            vec![
+                dictionary.clone(),
+                dictionary,
+                Value::String("after".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn can_build_values_with_cycle() {
+        let components = vec![
+            object("NSArray", vec![OutputData::SignedInteger(2)]),
+            object("NSString", vec![OutputData::String("item".into())]),
+            // A reference to the array from inside itself
+            object("NSArray", vec![OutputData::SignedInteger(2)]),
+        ];
+        let references = vec![None, None, Some(0)];
+
+        let result = build_values(&components, &references);
+
+        assert_eq!(
+            result,
+            vec![Value::Array(vec![
+                Value::String("item".into()),
+                Value::Reference(0),
+            ])]
+        );
+    }
+
+    #[test]
+    fn can_build_values_nested() {
+        let components = vec![
+            object("NSArray", vec![OutputData::SignedInteger(2)]),
+            object("NSDictionary", vec![OutputData::SignedInteger(1)]),
+            object("NSString", vec![OutputData::String("url".into())]),
+            object("NSURL", vec![]),
+            object(
+                "NSString",
+                vec![OutputData::String("https://example.com".into())],
+            ),
+            object("NSNumber", vec![OutputData::Double(1.5)]),
+        ];
+        let references = vec![None; components.len()];
+
+        let result = build_values(&components, &references);
+
+        assert_eq!(
+            result,
+            vec![Value::Array(vec![
+                Value::Dictionary(vec![(
+                    Value::String("url".into()),
+                    Value::Url("https://example.com".to_string()),
+                )]),
+                Value::Float(1.5),
+            ])]
+        );
+        assert_eq!(result[0].get("url"), None, "Only dictionaries have keys");
+    }
+}
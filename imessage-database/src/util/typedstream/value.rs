@@ -0,0 +1,212 @@
+/*!
+ Logic used to nest the objects read from a `typedstream` into a tree of [`Value`]s.
+
+ The reader emits objects in the order they appear, so an `NSDictionary` or `NSArray` is followed by its contents,
+ and a reference to an object that was already read is emitted as a copy of that object without its contents.
+ This rebuilds the object graph: collections hold their contents, and references resolve to the [`Value`] they point to.
+*/
+
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::util::typedstream::{
+    attributes::find_bytes,
+    models::{Archivable, Class, OutputData},
+};
+
+/// How deeply collections can be nested before their contents are no longer read
+const MAX_DEPTH: usize = 256;
+
+/// An object read from a `typedstream`
+///
+/// Strings and other data are borrowed from the `typedstream` they were read from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'a> {
+    /// An `NSString` or `NSMutableString`
+    String(Cow<'a, str>),
+    /// An `NSNumber` that holds an integer
+    Integer(i64),
+    /// An `NSNumber` that holds a floating point number
+    Float(f64),
+    /// The bytes of an `NSData` or `NSMutableData`
+    Bytes(Vec<u8>),
+    /// The address of an `NSURL`
+    Url(String),
+    /// An `NSArray` or `NSMutableArray`
+    Array(Vec<Value<'a>>),
+    /// An `NSDictionary` or `NSMutableDictionary`, as key and value pairs in the order they are stored
+    Dictionary(Vec<(Value<'a>, Value<'a>)>),
+    /// An instance of any other class, with the data stored on it
+    Object(Class<'a>, Vec<OutputData<'a>>),
+    /// Data that is not part of an object, like the ranges of an `NSAttributedString`
+    Data(Vec<OutputData<'a>>),
+    /// A reference to a collection from inside itself, by the index of the collection in the reader's output
+    ///
+    /// A cyclic graph cannot be expanded into a tree, so the cycle is broken here.
+    Reference(usize),
+}
+
+impl<'a> Value<'a> {
+    /// Get the text of a [`Value::String`]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Get the value stored at a string key of a [`Value::Dictionary`]
+    pub fn get(&self, key: &str) -> Option<&Value<'a>> {
+        match self {
+            Value::Dictionary(pairs) => pairs
+                .iter()
+                .find(|(item, _)| item.as_str() == Some(key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+/// Nest the objects emitted by the reader into a tree
+///
+/// `references` holds, for each of the `components`, the index of the earlier component it refers to, if any.
+pub(crate) fn build_values<'a>(
+    components: &[Archivable<'a>],
+    references: &[Option<usize>],
+) -> Vec<Value<'a>> {
+    let mut builder = ValueBuilder {
+        components,
+        references,
+        idx: 0,
+        built: HashMap::new(),
+        in_progress: vec![],
+    };
+
+    let mut values = vec![];
+    while builder.idx < components.len() {
+        values.push(builder.next_value());
+    }
+    values
+}
+
+/// Walks the reader's output, consuming the contents of each collection as it is built
+struct ValueBuilder<'a, 'b> {
+    /// The objects emitted by the reader
+    components: &'b [Archivable<'a>],
+    /// For each component, the index of the earlier component it refers to, if it is a reference
+    references: &'b [Option<usize>],
+    /// The index of the next component to read
+    idx: usize,
+    /// Values that have been built, by the index of their component, so references can reuse them
+    built: HashMap<usize, Value<'a>>,
+    /// The indexes of the collections whose contents are being read
+    in_progress: Vec<usize>,
+}
+
+impl<'a> ValueBuilder<'a, '_> {
+    /// Build the value that starts at the current component
+    fn next_value(&mut self) -> Value<'a> {
+        let idx = self.idx;
+        self.idx += 1;
+
+        // A reference does not repeat the contents of the object it points to, so reuse what was built for it
+        if let Some(target) = self.references.get(idx).copied().flatten() {
+            if let Some(value) = self.built.get(&target) {
+                return value.clone();
+            }
+            if self.in_progress.contains(&target) {
+                return Value::Reference(target);
+            }
+        }
+
+        let components = self.components;
+        let value = match &components[idx] {
+            Archivable::Object(class, data) => self.build_object(idx, class, data),
+            Archivable::Data(data) => Value::Data(data.clone()),
+            Archivable::Class(class) => Value::Object(class.clone(), vec![]),
+            // These are only used internally by the reader
+            Archivable::Placeholder | Archivable::Type(_) => Value::Data(vec![]),
+        };
+        self.built.insert(idx, value.clone());
+        value
+    }
+
+    /// Build an object, reading the contents of collections from the components that follow it
+    fn build_object(
+        &mut self,
+        idx: usize,
+        class: &Class<'a>,
+        data: &[OutputData<'a>],
+    ) -> Value<'a> {
+        let components = self.components;
+        match class.name.as_ref() {
+            "NSString" | "NSMutableString" => match data.first() {
+                Some(OutputData::String(text)) => Value::String(text.clone()),
+                _ => Value::Object(class.clone(), data.to_vec()),
+            },
+            "NSNumber" => match data.first() {
+                Some(OutputData::SignedInteger(number)) => Value::Integer(*number),
+                Some(OutputData::UnsignedInteger(number)) => match i64::try_from(*number) {
+                    Ok(number) => Value::Integer(number),
+                    Err(_) => Value::Float(*number as f64),
+                },
+                Some(OutputData::Float(number)) => Value::Float(f64::from(*number)),
+                Some(OutputData::Double(number)) => Value::Float(*number),
+                _ => Value::Object(class.clone(), data.to_vec()),
+            },
+            // The address is stored as a string after the `NSURL`
+            "NSURL" => match components.get(self.idx).and_then(Archivable::as_nsstring) {
+                Some(url) => {
+                    self.idx += 1;
+                    Value::Url(url.to_string())
+                }
+                None => Value::Object(class.clone(), data.to_vec()),
+            },
+            // The bytes are stored after their length
+            "NSData" | "NSMutableData" => match components.get(self.idx).and_then(find_bytes) {
+                Some(bytes) => {
+                    self.idx += 1;
+                    Value::Bytes(bytes.to_vec())
+                }
+                None => Value::Object(class.clone(), data.to_vec()),
+            },
+            "NSArray" | "NSMutableArray" if self.in_progress.len() < MAX_DEPTH => {
+                let count = collection_length(data);
+                self.in_progress.push(idx);
+                let mut items = vec![];
+                for _ in 0..count {
+                    if self.idx >= components.len() {
+                        break;
+                    }
+                    items.push(self.next_value());
+                }
+                self.in_progress.pop();
+                Value::Array(items)
+            }
+            "NSDictionary" | "NSMutableDictionary" if self.in_progress.len() < MAX_DEPTH => {
+                let count = collection_length(data);
+                self.in_progress.push(idx);
+                let mut pairs = vec![];
+                for _ in 0..count {
+                    if self.idx + 1 >= components.len() {
+                        break;
+                    }
+                    let key = self.next_value();
+                    let value = self.next_value();
+                    pairs.push((key, value));
+                }
+                self.in_progress.pop();
+                Value::Dictionary(pairs)
+            }
+            _ => Value::Object(class.clone(), data.to_vec()),
+        }
+    }
+}
+
+/// Get the number of items in a collection, which is stored before its contents
+fn collection_length(data: &[OutputData]) -> usize {
+    match data.first() {
+        Some(OutputData::SignedInteger(length)) => usize::try_from(*length).unwrap_or(0),
+        Some(OutputData::UnsignedInteger(length)) => usize::try_from(*length).unwrap_or(0),
+        _ => 0,
+    }
+}
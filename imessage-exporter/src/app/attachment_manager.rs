@@ -1,11 +1,21 @@
 use std::{
     fmt::Display,
-    fs::{copy, create_dir_all, metadata, write},
+    fs::{copy, create_dir_all, hard_link, metadata, write},
+    io,
     path::{Path, PathBuf},
 };
 
+use log::warn;
+
 use crate::app::{
-    converter::{convert_heic, Converter, ImageType},
+    converter::{
+        convert_audio, convert_heic, document_thumbnail, image_thumbnail, pdf_thumbnail,
+        video_thumbnail, Converter, DocumentType, ImageType, AUDIO_EXTENSION,
+    },
+    dedupe::ContentKey,
+    manifest::ManifestEntry,
+    metadata::strip_metadata,
+    missing_media::MissingMediaEntry,
     runtime::Config,
 };
 
@@ -13,10 +23,21 @@ use imessage_database::message_types::handwriting::HandwrittenMessage;
 use imessage_database::tables::{
     attachment::{Attachment, MediaType},
     messages::Message,
+    table::ORPHANED,
 };
 
 use filetime::{set_file_times, FileTime};
 
+#[cfg(unix)]
+use std::os::unix::fs::symlink;
+
+/// Name of the copied group photo in a conversation's attachment directory
+const CHAT_ICON_NAME: &str = "chat_icon";
+/// Directory in the attachment directory that holds the photos of contacts
+const AVATARS_DIR: &str = "avatars";
+/// Name of the database owner's copied photo in the avatar directory
+const MY_AVATAR_NAME: &str = "me";
+
 /// Represents different ways the app can interact with attachment data
 #[derive(Debug, PartialEq, Eq)]
 pub enum AttachmentManager {
@@ -26,6 +47,8 @@ pub enum AttachmentManager {
     Compatible,
     /// Copy attachments without converting; preserves quality but may not display correctly in all browsers
     Efficient,
+    /// Hard link attachments into the export instead of copying them, falling back to symbolic links, then copies
+    Link,
 }
 
 impl AttachmentManager {
@@ -34,6 +57,7 @@ impl AttachmentManager {
         match copy_state.to_lowercase().as_str() {
             "compatible" => Some(Self::Compatible),
             "efficient" => Some(Self::Efficient),
+            "link" => Some(Self::Link),
             "disabled" => Some(Self::Disabled),
             _ => None,
         }
@@ -45,6 +69,35 @@ impl AttachmentManager {
         message: &Message,
         handwriting: &HandwrittenMessage,
         config: &Config,
+    ) -> Option<PathBuf> {
+        // Each handwriting has a unique id, so cache then all in the same place
+        self.write_generated(message, &handwriting.id, "svg", config, || {
+            handwriting.render_svg().into_bytes()
+        })
+    }
+
+    /// Handle a Digital Touch message, optionally writing its undecoded stroke data to a file
+    pub fn handle_digital_touch(
+        &self,
+        message: &Message,
+        payload: &[u8],
+        config: &Config,
+    ) -> Option<PathBuf> {
+        if payload.is_empty() {
+            return None;
+        }
+        self.write_generated(message, &message.guid, "touch", config, || payload.to_vec())
+    }
+
+    /// Write a file generated from message data into the conversation's attachment directory,
+    /// unless attachments are disabled or the file was already written
+    fn write_generated(
+        &self,
+        message: &Message,
+        name: &str,
+        extension: &str,
+        config: &Config,
+        contents: impl FnOnce() -> Vec<u8>,
     ) -> Option<PathBuf> {
         if !matches!(self, AttachmentManager::Disabled) {
             // Create a path to copy the file to
@@ -55,11 +108,8 @@ impl AttachmentManager {
             to.push(sub_dir);
 
             // Add the filename
-            // Each handwriting has a unique id, so cache then all in the same place
-            to.push(&handwriting.id);
-
-            // Set the new file's extension to svg
-            to.set_extension("svg");
+            to.push(name);
+            to.set_extension(extension);
             if to.exists() {
                 return Some(to);
             }
@@ -68,14 +118,13 @@ impl AttachmentManager {
             if let Some(folder) = to.parent() {
                 if !folder.exists() {
                     if let Err(why) = create_dir_all(folder) {
-                        eprintln!("Unable to create {folder:?}: {why}");
+                        warn!("Unable to create {folder:?}: {why}");
                     }
                 }
             }
 
-            // Attempt the svg render
-            if let Err(why) = write(to.to_str()?, handwriting.render_svg()) {
-                eprintln!("Unable to write to {to:?}: {why}");
+            if let Err(why) = write(to.to_str()?, contents()) {
+                warn!("Unable to write to {to:?}: {why}");
             };
 
             // Update file metadata
@@ -95,6 +144,16 @@ impl AttachmentManager {
         attachment: &'a mut Attachment,
         config: &Config,
     ) -> Option<()> {
+        // Skip attachments of the kinds the export leaves out
+        if !config.includes_attachment(attachment) {
+            return None;
+        }
+
+        // Attachments that were removed on purpose are neither copied nor reported as missing
+        if config.removed_attachment(message, attachment).is_some() {
+            return None;
+        }
+
         // Resolve the path to the attachment
         let attachment_path = attachment.resolved_attachment_path(
             &config.options.platform,
@@ -102,12 +161,17 @@ impl AttachmentManager {
             config.options.attachment_root.as_deref(),
         )?;
 
+        // List attachments whose files are not on disk, i.e. because they were offloaded to iCloud
+        if !Path::new(&attachment_path).exists() {
+            Self::record_missing(message, attachment, Path::new(&attachment_path), config);
+        }
+
         if !matches!(self, AttachmentManager::Disabled) {
             let from = Path::new(&attachment_path);
 
             // Ensure the file exists at the specified location
             if !from.exists() {
-                eprintln!("Attachment not found at specified path: {from:?}");
+                warn!("Attachment not found at specified path: {from:?}");
                 return None;
             }
 
@@ -118,17 +182,65 @@ impl AttachmentManager {
             let sub_dir = config.conversation_attachment_path(message.chat_id);
             to.push(sub_dir);
 
-            // Add a stable filename
-            to.push(attachment.rowid.to_string());
+            // Add a stable filename, following the `--attachment-name` template if one was passed
+            to = match &config.attachment_namer {
+                Some(namer) => {
+                    let name = namer.template.render(
+                        attachment.rowid,
+                        |date_format| match message.date(&config.offset) {
+                            Ok(date) => date.format(date_format).to_string(),
+                            Err(_) => String::new(),
+                        },
+                        config.who(
+                            message.handle_id,
+                            message.is_from_me(),
+                            &message.destination_caller_id,
+                        ),
+                        attachment.filename(),
+                    );
+                    namer.claim(&to, &name, attachment.rowid)
+                }
+                None => to.join(attachment.rowid.to_string()),
+            };
 
             // Set the new file's extension to the original one
             to.set_extension(attachment.extension()?);
             if to.exists() {
+                Self::record_verification(from, &to, attachment, config);
                 attachment.copied_path = Some(to);
                 return Some(());
             }
 
+            // Reuse an identical file that was already copied during this export
+            let content_key = config
+                .deduplicator
+                .as_ref()
+                .and_then(|_| ContentKey::from_path(from));
+            if let (Some(deduplicator), Some(key)) = (&config.deduplicator, &content_key) {
+                if let Some(existing) = deduplicator.existing(key) {
+                    // The existing copy may have been converted to another format
+                    if let Some(extension) = existing.extension() {
+                        to.set_extension(extension);
+                    }
+                    let copied_path = deduplicator.link(key, &existing, &to);
+                    Self::record(message, attachment, from, &copied_path, config);
+                    Self::record_verification(from, &copied_path, attachment, config);
+                    attachment.copied_path = Some(copied_path);
+                    return Some(());
+                }
+            }
+
             match self {
+                AttachmentManager::Compatible if needs_transcoding(&attachment.mime_type()) => {
+                    match &config.media_converter {
+                        Some(media_converter) => {
+                            Self::convert_or_copy(from, &mut to, AUDIO_EXTENSION, |from, to| {
+                                convert_audio(from, to, media_converter)
+                            })
+                        }
+                        None => Self::copy_raw(from, &to),
+                    }
+                }
                 AttachmentManager::Compatible => match &config.converter {
                     Some(converter) => {
                         Self::copy_convert(
@@ -142,37 +254,352 @@ impl AttachmentManager {
                     None => Self::copy_raw(from, &to),
                 },
                 AttachmentManager::Efficient => Self::copy_raw(from, &to),
+                AttachmentManager::Link => Self::link(from, &to),
                 AttachmentManager::Disabled => unreachable!(),
             };
 
-            // Update file metadata
-            update_file_metadata(from, &to, message, config);
+            // Remove metadata from the copy, if requested
+            if Self::strips_metadata(attachment, config) {
+                Self::strip(&to);
+            }
+
+            // Update file metadata; links share it with the original, which must not change
+            if !matches!(self, AttachmentManager::Link) {
+                update_file_metadata(from, &to, message, config);
+            }
+
+            // Remember the copy so later duplicates can reference it
+            if let (Some(deduplicator), Some(key)) = (&config.deduplicator, content_key) {
+                if to.exists() {
+                    deduplicator.insert(key, to.clone());
+                }
+            }
+            Self::record(message, attachment, from, &to, config);
+            Self::record_verification(from, &to, attachment, config);
             attachment.copied_path = Some(to);
         }
         Some(())
     }
 
+    /// Handle the video of a Live Photo, copying it next to its still image if requested
+    ///
+    /// Returns the path to the video, or `None` if the attachment is not a Live Photo or videos are skipped
+    pub fn handle_live_photo(
+        &self,
+        message: &Message,
+        attachment: &Attachment,
+        config: &Config,
+    ) -> Option<PathBuf> {
+        // The video half is left out along with other videos
+        if config
+            .options
+            .skipped_attachment_types
+            .iter()
+            .any(|skipped| skipped == "video")
+        {
+            return None;
+        }
+
+        let from = PathBuf::from(attachment.live_photo_path(
+            &config.options.platform,
+            &config.options.db_path,
+            config.options.attachment_root.as_deref(),
+        )?);
+
+        if matches!(self, AttachmentManager::Disabled) {
+            return Some(from);
+        }
+
+        // Create a path to copy the video to, next to the still image
+        let mut to = config.attachment_path();
+        to.push(config.conversation_attachment_path(message.chat_id));
+        to.push(format!("{}_live", Self::copied_name(attachment)));
+        to.set_extension(from.extension()?);
+        if !to.exists() {
+            match self {
+                AttachmentManager::Link => Self::link(&from, &to),
+                _ => {
+                    Self::copy_raw(&from, &to);
+                    update_file_metadata(&from, &to, message, config);
+                }
+            }
+        }
+        if let Some(verifier) = &config.verifier {
+            verifier.record(&from, &to, true);
+        }
+        Some(to)
+    }
+
+    /// Handle a chat's group photo, copying it into the conversation's attachment directory if requested
+    ///
+    /// Returns the path to the photo, which is the original file if attachments are not copied
+    pub fn handle_chat_icon(
+        &self,
+        chat_id: i32,
+        attachment: &Attachment,
+        config: &Config,
+    ) -> Option<PathBuf> {
+        let from = PathBuf::from(attachment.resolved_attachment_path(
+            &config.options.platform,
+            &config.options.db_path,
+            config.options.attachment_root.as_deref(),
+        )?);
+        if !from.exists() {
+            warn!("Group photo not found at specified path: {from:?}");
+            return None;
+        }
+
+        if matches!(self, AttachmentManager::Disabled) {
+            return Some(from);
+        }
+
+        // Every chat has at most one group photo, so it gets a fixed name
+        let mut to = config.attachment_path();
+        to.push(config.conversation_attachment_path(Some(chat_id)));
+        to.push(CHAT_ICON_NAME);
+        to.set_extension(attachment.extension()?);
+        if !to.exists() {
+            match (self, &config.converter) {
+                (AttachmentManager::Compatible, Some(converter)) => {
+                    Self::copy_convert(&from, &mut to, converter, false, attachment.mime_type())
+                }
+                (AttachmentManager::Link, _) => Self::link(&from, &to),
+                _ => Self::copy_raw(&from, &to),
+            }
+        }
+        to.exists().then_some(to)
+    }
+
+    /// Handle the photo of a contact, writing it into the export's avatar directory unless attachments are disabled
+    pub fn handle_avatar(&self, handle_id: i32, image: &[u8], config: &Config) -> Option<PathBuf> {
+        if matches!(self, AttachmentManager::Disabled) {
+            return None;
+        }
+
+        let mut to = config.attachment_path();
+        to.push(AVATARS_DIR);
+        to.push(handle_id.to_string());
+        to.set_extension("jpg");
+        if !to.exists() {
+            if let Some(folder) = to.parent() {
+                if let Err(why) = create_dir_all(folder) {
+                    warn!("Unable to create {folder:?}: {why}");
+                }
+            }
+            if let Err(why) = write(&to, image) {
+                warn!("Unable to write to {to:?}: {why}");
+                return None;
+            }
+        }
+        Some(to)
+    }
+
+    /// Handle the photo passed for the database owner, copying it into the export's avatar directory if requested
+    ///
+    /// Returns the path to the photo, which is the original file if attachments are not copied
+    pub fn handle_my_avatar(&self, from: &Path, config: &Config) -> Option<PathBuf> {
+        if matches!(self, AttachmentManager::Disabled) {
+            return Some(from.to_path_buf());
+        }
+
+        let mut to = config.attachment_path();
+        to.push(AVATARS_DIR);
+        to.push(MY_AVATAR_NAME);
+        if let Some(extension) = from.extension() {
+            to.set_extension(extension);
+        }
+        if !to.exists() {
+            match self {
+                AttachmentManager::Link => Self::link(from, &to),
+                _ => Self::copy_raw(from, &to),
+            }
+        }
+        to.exists().then_some(to)
+    }
+
+    /// Generate a thumbnail for a copied image or video attachment, if requested
+    ///
+    /// Stickers and `GIF` images are left alone, since they are small or animated.
+    pub fn handle_thumbnail(
+        &self,
+        message: &Message,
+        attachment: &Attachment,
+        config: &Config,
+    ) -> Option<PathBuf> {
+        if !config.options.thumbnails || attachment.is_sticker {
+            return None;
+        }
+        let from = attachment.copied_path.as_deref()?;
+
+        // Create a path to write the thumbnail to, next to the copied attachment
+        let mut to = config.attachment_path();
+        to.push(config.conversation_attachment_path(message.chat_id));
+        to.push(format!("{}_thumbnail", Self::copied_name(attachment)));
+        to.set_extension(ImageType::Jpeg.to_str());
+        if to.exists() {
+            return Some(to);
+        }
+
+        // Documents show their first page
+        let document = attachment
+            .extension()
+            .and_then(DocumentType::from_extension);
+        match (document, attachment.mime_type()) {
+            (Some(DocumentType::Pdf), _) => pdf_thumbnail(from, &to, config.converter.as_ref()?)?,
+            (Some(DocumentType::Office), _) => document_thumbnail(
+                from,
+                &to,
+                config.document_converter.as_ref()?,
+                config.converter.as_ref()?,
+            )?,
+            (None, MediaType::Image(subtype)) if !subtype.eq_ignore_ascii_case("gif") => {
+                image_thumbnail(from, &to, config.converter.as_ref()?)?
+            }
+            (None, MediaType::Video(_)) => {
+                video_thumbnail(from, &to, config.media_converter.as_ref()?)?
+            }
+            _ => return None,
+        };
+
+        // Remove metadata from the thumbnail, if requested
+        if config.options.strip_metadata {
+            Self::strip(&to);
+        }
+
+        // Update file metadata
+        update_file_metadata(from, &to, message, config);
+
+        Some(to)
+    }
+
+    /// Get the name of a copied attachment without its extension, used to name the files made from it
+    fn copied_name(attachment: &Attachment) -> String {
+        attachment
+            .copied_path
+            .as_deref()
+            .and_then(Path::file_stem)
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| attachment.rowid.to_string())
+    }
+
+    /// Add an attachment whose file is not on disk to the missing media report, if requested
+    fn record_missing(message: &Message, attachment: &Attachment, from: &Path, config: &Config) {
+        if let Some(missing_media) = &config.missing_media {
+            let chat = match config.conversation(message) {
+                Some((chatroom, _)) => config.filename(chatroom),
+                None => ORPHANED.to_string(),
+            };
+            let date = message
+                .date(&config.offset)
+                .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+            missing_media.record(&MissingMediaEntry {
+                message_guid: &message.guid,
+                chat: &chat,
+                date: &date,
+                original_path: from,
+                transfer_name: attachment.transfer_name.as_deref().unwrap_or_default(),
+                mime_type: attachment.mime_type.as_deref().unwrap_or_default(),
+                bytes: attachment.total_bytes,
+            });
+        }
+    }
+
+    /// Add a copied attachment to the manifest, if requested
+    fn record(message: &Message, attachment: &Attachment, from: &Path, to: &Path, config: &Config) {
+        if let Some(manifest) = &config.manifest {
+            if !to.exists() {
+                return;
+            }
+            let chat = match config.conversation(message) {
+                Some((chatroom, _)) => config.filename(chatroom),
+                None => ORPHANED.to_string(),
+            };
+            let exported_path = config.relative_path(to.to_path_buf()).unwrap_or_default();
+            manifest.record(
+                &ManifestEntry {
+                    message_guid: &message.guid,
+                    chat: &chat,
+                    original_path: from,
+                    exported_path: &exported_path,
+                    mime_type: attachment.mime_type.as_deref().unwrap_or_default(),
+                },
+                to,
+            );
+        }
+    }
+
+    /// Remember a copied attachment so it can be checked against the original after the export
+    fn record_verification(from: &Path, to: &Path, attachment: &Attachment, config: &Config) {
+        if let Some(verifier) = &config.verifier {
+            // Converted copies have a different extension, and stripped copies lose some data
+            let exact =
+                to.extension() == from.extension() && !Self::strips_metadata(attachment, config);
+            verifier.record(from, to, exact);
+        }
+    }
+
+    /// Determine if metadata is removed from the copy of an attachment; GIFs do not hold location data
+    fn strips_metadata(attachment: &Attachment, config: &Config) -> bool {
+        config.options.strip_metadata
+            && matches!(attachment.mime_type(), MediaType::Image(subtype) if !subtype.eq_ignore_ascii_case("gif"))
+    }
+
+    /// Remove metadata from a copied image, warning if it cannot be removed
+    fn strip(path: &Path) {
+        if strip_metadata(path).is_none() {
+            warn!("Unable to remove metadata from {path:?}");
+        }
+    }
+
     /// Copy a file without altering it
     fn copy_raw(from: &Path, to: &Path) {
         // Ensure the directory tree exists
         if let Some(folder) = to.parent() {
             if !folder.exists() {
                 if let Err(why) = create_dir_all(folder) {
-                    eprintln!("Unable to create {folder:?}: {why}");
+                    warn!("Unable to create {folder:?}: {why}");
                 }
             }
         }
         if let Err(why) = copy(from, to) {
-            eprintln!("Unable to copy {from:?} to {to:?}: {why}");
+            warn!("Unable to copy {from:?} to {to:?}: {why}");
         };
     }
 
+    /// Link a file into the export, copying it if it cannot be linked
+    ///
+    /// Hard links only work within a volume, so a symbolic link is used when the export is on
+    /// another volume.
+    fn link(from: &Path, to: &Path) {
+        // Ensure the directory tree exists
+        if let Some(folder) = to.parent() {
+            if !folder.exists() {
+                if let Err(why) = create_dir_all(folder) {
+                    warn!("Unable to create {folder:?}: {why}");
+                }
+            }
+        }
+        if hard_link(from, to).is_ok() {
+            return;
+        }
+        #[cfg(unix)]
+        if let Ok(from) = from.canonicalize() {
+            if symlink(from, to).is_ok() {
+                return;
+            }
+        }
+        Self::copy_raw(from, to);
+    }
+
     /// Copy a file, converting if possible
     ///
     /// - Sticker `HEIC` files convert to `PNG`
     /// - Sticker `HEICS` files convert to `GIF`
-    /// - Attachment `HEIC` files convert to `JPEG`
+    /// - Attachment `HEIC` and `HEIF` files convert to `JPEG`
     /// - Other files are copied with their original formats
+    ///
+    /// If a conversion fails, the original file is copied instead so the export can still link to it
     fn copy_convert(
         from: &Path,
         to: &mut PathBuf,
@@ -192,29 +619,45 @@ impl AttachmentManager {
 
             match output_type {
                 Some(output_type) => {
-                    to.set_extension(output_type.to_str());
-                    if convert_heic(from, to, converter, &output_type).is_none() {
-                        eprintln!("Unable to convert {from:?}");
-                    }
+                    Self::convert_or_copy(from, to, output_type.to_str(), |from, to| {
+                        convert_heic(from, to, converter, &output_type)
+                    });
                 }
                 None => Self::copy_raw(from, to),
             }
         }
         // Normal attachments always get converted to jpeg
-        else if matches!(
-            mime_type,
-            MediaType::Image("heic") | MediaType::Image("HEIC")
-        ) {
-            let output_type = ImageType::Jpeg;
-            // Update extension for conversion
-            to.set_extension(output_type.to_str());
-            if convert_heic(from, to, converter, &output_type).is_none() {
-                eprintln!("Unable to convert {from:?}");
-            }
+        else if matches!(mime_type, MediaType::Image(subtype) if subtype.eq_ignore_ascii_case("heic") || subtype.eq_ignore_ascii_case("heif"))
+        {
+            Self::convert_or_copy(from, to, ImageType::Jpeg.to_str(), |from, to| {
+                convert_heic(from, to, converter, &ImageType::Jpeg)
+            });
         } else {
             Self::copy_raw(from, to);
         }
     }
+
+    /// Convert a file to a format with the provided extension, copying the original if the conversion fails
+    fn convert_or_copy(
+        from: &Path,
+        to: &mut PathBuf,
+        extension: &str,
+        convert: impl FnOnce(&Path, &Path) -> Option<()>,
+    ) {
+        // Keep the original extension in case we need to fall back to it
+        let original = to.clone();
+
+        // Update extension for conversion
+        to.set_extension(extension);
+        if to.exists() {
+            return;
+        }
+        if convert(from, to).is_none() {
+            warn!("Unable to convert {from:?}, copying the original file instead");
+            *to = original;
+            Self::copy_raw(from, to);
+        }
+    }
 }
 
 impl Default for AttachmentManager {
@@ -229,7 +672,21 @@ impl Display for AttachmentManager {
             AttachmentManager::Disabled => write!(fmt, "disabled"),
             AttachmentManager::Compatible => write!(fmt, "compatible"),
             AttachmentManager::Efficient => write!(fmt, "efficient"),
+            AttachmentManager::Link => write!(fmt, "link"),
+        }
+    }
+}
+
+/// Determine if an audio attachment is in a format most browsers cannot play
+///
+/// Audio messages are stored as `caf` files, and older ones sent over SMS as `amr` files.
+fn needs_transcoding(mime_type: &MediaType) -> bool {
+    match mime_type {
+        MediaType::Audio(subtype) => {
+            let subtype = subtype.to_lowercase();
+            subtype.starts_with("x-caf") || subtype.starts_with("amr")
         }
+        _ => false,
     }
 }
 
@@ -237,17 +694,133 @@ impl Display for AttachmentManager {
 fn update_file_metadata(from: &Path, to: &Path, message: &Message, config: &Config) {
     // Update file metadata
     if let Ok(metadata) = metadata(from) {
-        // The modification time is the message's date, otherwise the the original file's creation time
-        let mtime = match message.date(&config.offset) {
-            Ok(date) => FileTime::from_unix_time(date.timestamp(), date.timestamp_subsec_nanos()),
-            Err(_) => FileTime::from_last_modification_time(&metadata),
+        // The creation and modification times are the message's date, otherwise the original file's times
+        let (btime, mtime) = match message.date(&config.offset) {
+            Ok(date) => {
+                let time =
+                    FileTime::from_unix_time(date.timestamp(), date.timestamp_subsec_nanos());
+                (time, time)
+            }
+            Err(_) => {
+                let mtime = FileTime::from_last_modification_time(&metadata);
+                (
+                    FileTime::from_creation_time(&metadata).unwrap_or(mtime),
+                    mtime,
+                )
+            }
         };
 
         // The new last access time comes from the metadata of the original file
         let atime = FileTime::from_last_access_time(&metadata);
 
         if let Err(why) = set_file_times(to, atime, mtime) {
-            eprintln!("Unable to update {to:?} metadata: {why}");
+            warn!("Unable to update {to:?} metadata: {why}");
+        }
+
+        // Photo libraries sort imported files without a capture date by their creation time
+        if let Err(why) = set_creation_time(to, btime) {
+            warn!("Unable to update {to:?} creation time: {why}");
         }
     }
 }
+
+/// Set the time a file was created, on platforms that store it
+#[cfg(any(target_os = "macos", target_os = "ios", windows))]
+fn set_creation_time(path: &Path, time: FileTime) -> io::Result<()> {
+    #[cfg(target_os = "ios")]
+    use std::os::ios::fs::FileTimesExt;
+    #[cfg(target_os = "macos")]
+    use std::os::macos::fs::FileTimesExt;
+    #[cfg(windows)]
+    use std::os::windows::fs::FileTimesExt;
+    use std::{
+        fs::{File, FileTimes},
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    // Messages did not exist before 1970, so earlier times are not written
+    let Ok(seconds) = u64::try_from(time.unix_seconds()) else {
+        return Ok(());
+    };
+    let created = UNIX_EPOCH + Duration::new(seconds, time.nanoseconds());
+    File::options()
+        .write(true)
+        .open(path)?
+        .set_times(FileTimes::new().set_created(created))
+}
+
+/// Set the time a file was created, on platforms that store it
+#[cfg(not(any(target_os = "macos", target_os = "ios", windows)))]
+fn set_creation_time(_: &Path, _: FileTime) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_to_string, remove_file, write},
+    };
+
+    use filetime::FileTime;
+
+    use crate::app::attachment_manager::{needs_transcoding, set_creation_time, AttachmentManager};
+
+    use imessage_database::tables::attachment::MediaType;
+
+    #[test]
+    fn can_transcode_voice_messages() {
+        assert!(needs_transcoding(&MediaType::Audio("x-caf; codecs=opus")));
+        assert!(needs_transcoding(&MediaType::Audio("AMR")));
+    }
+
+    #[test]
+    fn cant_transcode_playable_audio() {
+        assert!(!needs_transcoding(&MediaType::Audio("mp4")));
+        assert!(!needs_transcoding(&MediaType::Audio("mpeg")));
+        assert!(!needs_transcoding(&MediaType::Image("x-caf")));
+    }
+
+    #[test]
+    fn can_parse_link() {
+        assert_eq!(
+            AttachmentManager::from_cli("link"),
+            Some(AttachmentManager::Link)
+        );
+    }
+
+    #[test]
+    fn can_link_attachment() {
+        let dir = temp_dir().join("imessage-exporter-link");
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("original.txt");
+        let to = dir.join("export").join("linked.txt");
+        write(&from, "meme").unwrap();
+        let _ = remove_file(&to);
+
+        AttachmentManager::link(&from, &to);
+
+        assert_eq!(read_to_string(&to).unwrap(), "meme");
+    }
+
+    #[test]
+    fn can_set_creation_time() {
+        let dir = temp_dir().join("imessage-exporter-creation-time");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("attachment.txt");
+        write(&path, "meme").unwrap();
+
+        // May 17, 2022
+        assert!(set_creation_time(&path, FileTime::from_unix_time(1652808582, 0)).is_ok());
+    }
+
+    #[test]
+    fn can_skip_creation_time_before_epoch() {
+        let dir = temp_dir().join("imessage-exporter-creation-time");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("old_attachment.txt");
+        write(&path, "meme").unwrap();
+
+        assert!(set_creation_time(&path, FileTime::from_unix_time(-1, 0)).is_ok());
+    }
+}
@@ -0,0 +1,298 @@
+/*!
+ Parses the templates that name copied attachments.
+
+ A template is literal text with these placeholders:
+
+ - `{rowid}`: the ID of the attachment in the database, which is the default name
+ - `{date}`: the time the message was sent, i.e. `2022-05-17_17-29-42`
+ - `{date:<format>}`: the time the message was sent, using a [`strftime`](https://docs.rs/chrono/latest/chrono/format/strftime/index.html) format
+ - `{sender}`: the name of the sender
+ - `{original_name}`: the name of the file when it was sent or received, without its extension
+
+ The original file's extension is always added to the name, and `{{` and `}}` write literal braces.
+*/
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::format::{Item, StrftimeItems};
+
+use crate::app::sanitizers::sanitize_filename;
+
+/// Date format used by `{date}`, which avoids characters that are not allowed in file names
+pub const DEFAULT_ATTACHMENT_DATE_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+
+/// Part of a parsed template
+#[derive(Debug, PartialEq, Eq)]
+enum Segment {
+    /// Text written as-is
+    Literal(String),
+    /// ID of the attachment
+    RowId,
+    /// Date the message was sent, with an optional `strftime` format
+    Date(Option<String>),
+    /// Name of the sender
+    Sender,
+    /// Name of the file when it was sent, without its extension
+    OriginalName,
+}
+
+/// A parsed template for the names of copied attachments
+#[derive(Debug, PartialEq, Eq)]
+pub struct AttachmentName {
+    segments: Vec<Segment>,
+}
+
+impl AttachmentName {
+    /// Parse a template, describing the problem if it is invalid
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => literal.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => literal.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(format!("Placeholder `{{{name}` is missing a `}}`"))
+                            }
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(AttachmentName::placeholder(&name)?);
+                }
+                '}' => return Err(String::from("Unmatched `}`; use `}}` for a literal brace")),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        if !segments
+            .iter()
+            .any(|segment| !matches!(segment, Segment::Literal(_)))
+        {
+            return Err(String::from(
+                "The template must include at least one placeholder",
+            ));
+        }
+
+        Ok(AttachmentName { segments })
+    }
+
+    /// Parse the name between the braces of a placeholder
+    fn placeholder(name: &str) -> Result<Segment, String> {
+        match name.split_once(':') {
+            Some(("date", date_format)) => {
+                if StrftimeItems::new(date_format).any(|item| item == Item::Error) {
+                    return Err(format!("`{date_format}` is not a valid date format"));
+                }
+                Ok(Segment::Date(Some(date_format.to_string())))
+            }
+            None if name == "rowid" => Ok(Segment::RowId),
+            None if name == "date" => Ok(Segment::Date(None)),
+            None if name == "sender" => Ok(Segment::Sender),
+            None if name == "original_name" => Ok(Segment::OriginalName),
+            _ => Err(format!(
+                "`{{{name}}}` is not a valid placeholder; use `{{rowid}}`, `{{date}}`, `{{sender}}`, or `{{original_name}}`"
+            )),
+        }
+    }
+
+    /// Render the name of an attachment, without an extension
+    ///
+    /// `date` is called with each placeholder's `strftime` format. `original_name` may include
+    /// an extension, which is removed. Characters that are not allowed in file names are replaced.
+    pub fn render(
+        &self,
+        rowid: i32,
+        date: impl Fn(&str) -> String,
+        sender: &str,
+        original_name: &str,
+    ) -> String {
+        let mut rendered = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => rendered.push_str(literal),
+                Segment::RowId => rendered.push_str(&rowid.to_string()),
+                Segment::Date(date_format) => rendered.push_str(&date(
+                    date_format
+                        .as_deref()
+                        .unwrap_or(DEFAULT_ATTACHMENT_DATE_FORMAT),
+                )),
+                Segment::Sender => rendered.push_str(sender),
+                Segment::OriginalName => rendered.push_str(
+                    &Path::new(original_name)
+                        .file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy(),
+                ),
+            }
+        }
+        // A `#` would end the path of a link in HTML exports
+        let rendered = sanitize_filename(rendered.trim()).replace('#', "_");
+        match rendered.is_empty() {
+            true => rowid.to_string(),
+            false => rendered,
+        }
+    }
+}
+
+/// Names copied attachments with a template, keeping names used by different attachments apart
+#[derive(Debug)]
+pub struct AttachmentNamer {
+    /// The template for each name
+    pub template: AttachmentName,
+    /// Map of the paths, without extensions, given to attachments during this export to their IDs
+    claimed: RefCell<HashMap<PathBuf, i32>>,
+}
+
+impl AttachmentNamer {
+    pub fn new(template: AttachmentName) -> Self {
+        Self {
+            template,
+            claimed: RefCell::default(),
+        }
+    }
+
+    /// Get a path in `directory` for a rendered name that no other attachment in this export uses
+    ///
+    /// If the name is taken, `_1`, `_2`, and so on are added to it. Attachments are exported in the
+    /// same order each time, so an attachment gets the same name when the export is run again.
+    pub fn claim(&self, directory: &Path, name: &str, rowid: i32) -> PathBuf {
+        let mut claimed = self.claimed.borrow_mut();
+        let mut path = directory.join(name);
+        let mut suffix = 0;
+        while let Some(owner) = claimed.get(&path) {
+            // The same attachment can be shown more than once, i.e. in a reply thread
+            if *owner == rowid {
+                return path;
+            }
+            suffix += 1;
+            path = directory.join(format!("{name}_{suffix}"));
+        }
+        claimed.insert(path.clone(), rowid);
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::app::attachment_name::{
+        AttachmentName, AttachmentNamer, Segment, DEFAULT_ATTACHMENT_DATE_FORMAT,
+    };
+
+    #[test]
+    fn can_parse_template() {
+        let name = AttachmentName::parse("{date}_{sender}_{original_name}").unwrap();
+        assert_eq!(
+            name.segments,
+            vec![
+                Segment::Date(None),
+                Segment::Literal("_".to_string()),
+                Segment::Sender,
+                Segment::Literal("_".to_string()),
+                Segment::OriginalName,
+            ]
+        );
+    }
+
+    #[test]
+    fn can_render_template() {
+        let name = AttachmentName::parse("{date}_{sender}_{original_name}").unwrap();
+        assert_eq!(
+            name.render(
+                42,
+                |date_format| {
+                    assert_eq!(date_format, DEFAULT_ATTACHMENT_DATE_FORMAT);
+                    "2022-05-17_17-29-42".to_string()
+                },
+                "Sample Contact",
+                "IMG_0001.HEIC"
+            ),
+            "2022-05-17_17-29-42_Sample Contact_IMG_0001"
+        );
+    }
+
+    #[test]
+    fn can_render_custom_date_and_rowid() {
+        let name = AttachmentName::parse("{date:%Y}/{rowid}").unwrap();
+        assert_eq!(
+            name.render(42, |_| "2022".to_string(), "Me", "a.png"),
+            "2022_42"
+        );
+    }
+
+    #[test]
+    fn can_replace_link_fragments() {
+        let name = AttachmentName::parse("{original_name}").unwrap();
+        assert_eq!(
+            name.render(1, |_| String::new(), "Me", "Receipt #12.pdf"),
+            "Receipt _12"
+        );
+    }
+
+    #[test]
+    fn can_fall_back_to_rowid() {
+        let name = AttachmentName::parse("{original_name}").unwrap();
+        assert_eq!(name.render(7, |_| String::new(), "Me", ""), "7");
+    }
+
+    #[test]
+    fn cant_parse_invalid_placeholder() {
+        assert!(AttachmentName::parse("{guid}").is_err());
+    }
+
+    #[test]
+    fn cant_parse_unclosed_placeholder() {
+        assert!(AttachmentName::parse("{date").is_err());
+    }
+
+    #[test]
+    fn cant_parse_template_without_placeholders() {
+        assert!(AttachmentName::parse("attachment").is_err());
+    }
+
+    #[test]
+    fn can_claim_colliding_names() {
+        let namer = AttachmentNamer::new(AttachmentName::parse("{original_name}").unwrap());
+        let directory = Path::new("attachments/1");
+
+        assert_eq!(
+            namer.claim(directory, "IMG_0001", 1),
+            directory.join("IMG_0001")
+        );
+        assert_eq!(
+            namer.claim(directory, "IMG_0001", 2),
+            directory.join("IMG_0001_1")
+        );
+        assert_eq!(
+            namer.claim(directory, "IMG_0001", 3),
+            directory.join("IMG_0001_2")
+        );
+        assert_eq!(
+            namer.claim(Path::new("attachments/2"), "IMG_0001", 4),
+            Path::new("attachments/2").join("IMG_0001")
+        );
+        // Claiming a name again for the same attachment does not add a suffix
+        assert_eq!(
+            namer.claim(directory, "IMG_0001", 2),
+            directory.join("IMG_0001_1")
+        );
+    }
+}
@@ -0,0 +1,64 @@
+/*!
+ Contains data structures used to describe which kinds of conversations are exported.
+*/
+
+/// Represents the kinds of conversations included in an export
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub enum ChatType {
+    /// Export every conversation
+    #[default]
+    All,
+    /// Only export group conversations
+    Group,
+    /// Only export one-on-one conversations
+    Direct,
+}
+
+impl ChatType {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(chat_type: &str) -> Option<Self> {
+        match chat_type.to_lowercase().as_str() {
+            "all" => Some(Self::All),
+            "group" => Some(Self::Group),
+            "direct" | "dm" => Some(Self::Direct),
+            _ => None,
+        }
+    }
+
+    /// Determine if a conversation of this kind should be exported
+    pub fn includes(&self, is_group: bool) -> bool {
+        match self {
+            ChatType::All => true,
+            ChatType::Group => is_group,
+            ChatType::Direct => !is_group,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::chat_type::ChatType;
+
+    #[test]
+    fn can_parse_any_case() {
+        assert_eq!(ChatType::from_cli("all"), Some(ChatType::All));
+        assert_eq!(ChatType::from_cli("Group"), Some(ChatType::Group));
+        assert_eq!(ChatType::from_cli("DIRECT"), Some(ChatType::Direct));
+        assert_eq!(ChatType::from_cli("dm"), Some(ChatType::Direct));
+    }
+
+    #[test]
+    fn cant_parse_invalid() {
+        assert_eq!(ChatType::from_cli("channel"), None);
+    }
+
+    #[test]
+    fn can_include() {
+        assert!(ChatType::All.includes(true));
+        assert!(ChatType::All.includes(false));
+        assert!(ChatType::Group.includes(true));
+        assert!(!ChatType::Group.includes(false));
+        assert!(ChatType::Direct.includes(false));
+        assert!(!ChatType::Direct.includes(true));
+    }
+}
@@ -1,9 +1,12 @@
 use std::{
-    fs::create_dir_all,
+    env::temp_dir,
+    fs::{create_dir_all, remove_file},
     path::Path,
     process::{Command, Stdio},
 };
 
+use log::warn;
+
 #[derive(Debug)]
 pub enum ImageType {
     #[allow(non_camel_case_types)]
@@ -30,6 +33,67 @@ pub enum Converter {
     Imagemagick,
 }
 
+/// Program used to convert audio and video files
+#[derive(Debug)]
+pub enum MediaConverter {
+    Ffmpeg,
+}
+
+/// Program used to render the first page of office documents
+#[derive(Debug)]
+pub enum DocumentConverter {
+    /// The macOS Quick Look thumbnail generator
+    QuickLook,
+    /// LibreOffice, run without opening a window
+    LibreOffice,
+}
+
+/// Kinds of documents that can be previewed
+#[derive(Debug, PartialEq, Eq)]
+pub enum DocumentType {
+    /// `PDF` files, which image converters can render
+    Pdf,
+    /// Word processing, spreadsheet, and presentation files
+    Office,
+}
+
+impl DocumentType {
+    /// Determine the kind of document from a file extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "pdf" => Some(Self::Pdf),
+            "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "pages" | "numbers" | "key"
+            | "odt" | "ods" | "odp" | "rtf" => Some(Self::Office),
+            _ => None,
+        }
+    }
+}
+
+impl MediaConverter {
+    /// Determine the audio and video converter type for the current shell environment
+    pub fn determine() -> Option<MediaConverter> {
+        if exists("ffmpeg") {
+            return Some(MediaConverter::Ffmpeg);
+        }
+        warn!("No ffmpeg found, audio and video files will not be converted!");
+        None
+    }
+}
+
+impl DocumentConverter {
+    /// Determine the document converter type for the current shell environment
+    pub fn determine() -> Option<DocumentConverter> {
+        if exists("qlmanage") {
+            return Some(DocumentConverter::QuickLook);
+        }
+        if exists("soffice") {
+            return Some(DocumentConverter::LibreOffice);
+        }
+        warn!("No qlmanage or LibreOffice found, office documents will not have previews!");
+        None
+    }
+}
+
 impl Converter {
     /// Determine the converter type for the current shell environment
     pub fn determine() -> Option<Converter> {
@@ -39,7 +103,7 @@ impl Converter {
         if exists("magick") {
             return Some(Converter::Imagemagick);
         }
-        eprintln!("No HEIC converter found, attachments will not be converted!");
+        warn!("No HEIC converter found, attachments will not be converted!");
         None
     }
 }
@@ -73,6 +137,8 @@ fn exists(name: &str) -> bool {
 
 /// Convert a HEIC image file to the provided format
 ///
+/// Returns `None` if the converter could not be run or did not produce the output file
+///
 /// This uses the macOS builtin `sips` program
 /// Docs: <https://www.unix.com/man-page/osx/1/sips/> (or `man sips`)
 ///
@@ -96,7 +162,7 @@ pub fn convert_heic(
     if let Some(folder) = to.parent() {
         if !folder.exists() {
             if let Err(why) = create_dir_all(folder) {
-                eprintln!("Unable to create {folder:?}: {why}");
+                warn!("Unable to create {folder:?}: {why}");
                 return None;
             }
         }
@@ -120,14 +186,18 @@ pub fn convert_heic(
                 .spawn()
             {
                 Ok(mut sips) => match sips.wait() {
-                    Ok(_) => Some(()),
+                    Ok(status) if status.success() && to.exists() => Some(()),
+                    Ok(status) => {
+                        warn!("Conversion failed: sips exited with {status}");
+                        None
+                    }
                     Err(why) => {
-                        eprintln!("Conversion failed: {why}");
+                        warn!("Conversion failed: {why}");
                         None
                     }
                 },
                 Err(why) => {
-                    eprintln!("Conversion failed: {why}");
+                    warn!("Conversion failed: {why}");
                     None
                 }
             }
@@ -143,26 +213,215 @@ pub fn convert_heic(
                 .spawn()
             {
                 Ok(mut convert) => match convert.wait() {
-                    Ok(_) => Some(()),
+                    Ok(status) if status.success() && to.exists() => Some(()),
+                    Ok(status) => {
+                        warn!("Conversion failed: magick exited with {status}");
+                        None
+                    }
                     Err(why) => {
-                        eprintln!("Conversion failed: {why}");
+                        warn!("Conversion failed: {why}");
                         None
                     }
                 },
                 Err(why) => {
-                    eprintln!("Conversion failed: {why}");
+                    warn!("Conversion failed: {why}");
                     None
                 }
             }
         }
+    }
+}
+
+/// The longest side of a generated thumbnail, in pixels
+pub const THUMBNAIL_SIZE: u32 = 320;
+
+/// Generate a `JPEG` thumbnail of an image file that fits in [`THUMBNAIL_SIZE`]
+pub fn image_thumbnail(from: &Path, to: &Path, converter: &Converter) -> Option<()> {
+    let from_path = from.to_str()?;
+    let to_path = to.to_str()?;
+    let size = THUMBNAIL_SIZE.to_string();
+
+    match converter {
+        Converter::Sips => run(
+            "sips",
+            &[
+                "-Z",
+                &size,
+                "-s",
+                "format",
+                ImageType::Jpeg.to_str(),
+                from_path,
+                "-o",
+                to_path,
+            ],
+            to,
+        ),
+        // The trailing `>` only shrinks images that are larger than the thumbnail
+        Converter::Imagemagick => run(
+            "magick",
+            &[
+                from_path,
+                "-auto-orient",
+                "-thumbnail",
+                &format!("{size}x{size}>"),
+                to_path,
+            ],
+            to,
+        ),
+    }
+}
+
+/// Generate a `JPEG` thumbnail from the first frame of a video file that fits in [`THUMBNAIL_SIZE`]
+pub fn video_thumbnail(from: &Path, to: &Path, converter: &MediaConverter) -> Option<()> {
+    let from_path = from.to_str()?;
+    let to_path = to.to_str()?;
+
+    match converter {
+        MediaConverter::Ffmpeg => run(
+            "ffmpeg",
+            &[
+                "-y",
+                "-i",
+                from_path,
+                "-frames:v",
+                "1",
+                "-vf",
+                &format!(
+                    "scale={THUMBNAIL_SIZE}:{THUMBNAIL_SIZE}:force_original_aspect_ratio=decrease"
+                ),
+                to_path,
+            ],
+            to,
+        ),
+    }
+}
+
+/// Generate a `JPEG` thumbnail of the first page of a `PDF` file that fits in [`THUMBNAIL_SIZE`]
+pub fn pdf_thumbnail(from: &Path, to: &Path, converter: &Converter) -> Option<()> {
+    match converter {
+        // `sips` renders the first page of a PDF like any other image
+        Converter::Sips => image_thumbnail(from, to, converter),
+        // ImageMagick renders PDFs with Ghostscript, which leaves transparent pages black in a JPEG
+        Converter::Imagemagick => run(
+            "magick",
+            &[
+                &format!("{}[0]", from.to_str()?),
+                "-background",
+                "white",
+                "-flatten",
+                "-thumbnail",
+                &format!("{THUMBNAIL_SIZE}x{THUMBNAIL_SIZE}>"),
+                to.to_str()?,
+            ],
+            to,
+        ),
+    }
+}
+
+/// Generate a `JPEG` thumbnail of the first page of an office document that fits in [`THUMBNAIL_SIZE`]
+///
+/// The page is rendered to a `PNG` file in a temporary folder, so it cannot overwrite an exported file,
+/// then shrunk by the image `converter`.
+pub fn document_thumbnail(
+    from: &Path,
+    to: &Path,
+    document_converter: &DocumentConverter,
+    converter: &Converter,
+) -> Option<()> {
+    let folder = temp_dir().join("imessage-exporter-previews");
+    create_dir_all(&folder).ok()?;
+    let from_path = from.to_str()?;
+    let folder_path = folder.to_str()?;
+
+    let rendered = match document_converter {
+        // Quick Look adds `.png` to the whole file name
+        DocumentConverter::QuickLook => {
+            let rendered = folder.join(format!("{}.png", from.file_name()?.to_str()?));
+            let size = THUMBNAIL_SIZE.to_string();
+            run(
+                "qlmanage",
+                &["-t", "-s", &size, "-o", folder_path, from_path],
+                &rendered,
+            )?;
+            rendered
+        }
+        // LibreOffice replaces the extension
+        DocumentConverter::LibreOffice => {
+            let rendered = folder.join(format!("{}.png", from.file_stem()?.to_str()?));
+            run(
+                "soffice",
+                &[
+                    "--headless",
+                    "--convert-to",
+                    "png",
+                    "--outdir",
+                    folder_path,
+                    from_path,
+                ],
+                &rendered,
+            )?;
+            rendered
+        }
     };
 
-    Some(())
+    let thumbnail = image_thumbnail(&rendered, to, converter);
+    let _ = remove_file(&rendered);
+    thumbnail
+}
+
+/// The extension of transcoded audio files, which hold `AAC` audio
+pub const AUDIO_EXTENSION: &str = "m4a";
+
+/// Transcode an audio file, i.e. a `caf` audio message, to `AAC` audio that browsers can play
+pub fn convert_audio(from: &Path, to: &Path, converter: &MediaConverter) -> Option<()> {
+    let from_path = from.to_str()?;
+    let to_path = to.to_str()?;
+
+    match converter {
+        MediaConverter::Ffmpeg => run(
+            "ffmpeg",
+            &[
+                "-y", "-i", from_path, "-vn", "-c:a", "aac", "-b:a", "64k", to_path,
+            ],
+            to,
+        ),
+    }
+}
+
+/// Run a conversion program, ensuring it wrote the file at `to`
+fn run(program: &str, args: &[&str], to: &Path) -> Option<()> {
+    // Ensure the directory tree exists
+    if let Some(folder) = to.parent() {
+        if !folder.exists() {
+            if let Err(why) = create_dir_all(folder) {
+                warn!("Unable to create {folder:?}: {why}");
+                return None;
+            }
+        }
+    }
+
+    match Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null())
+        .status()
+    {
+        Ok(status) if status.success() && to.exists() => Some(()),
+        Ok(status) => {
+            warn!("Conversion failed: {program} exited with {status}");
+            None
+        }
+        Err(why) => {
+            warn!("Conversion failed: {why}");
+            None
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::exists;
+    use super::{exists, DocumentType};
 
     #[test]
     fn can_find_program() {
@@ -173,4 +432,18 @@ mod test {
     fn can_miss_program() {
         assert!(!exists("fake_name"));
     }
+
+    #[test]
+    fn can_get_document_type() {
+        assert_eq!(DocumentType::from_extension("PDF"), Some(DocumentType::Pdf));
+        assert_eq!(
+            DocumentType::from_extension("docx"),
+            Some(DocumentType::Office)
+        );
+        assert_eq!(
+            DocumentType::from_extension("key"),
+            Some(DocumentType::Office)
+        );
+        assert_eq!(DocumentType::from_extension("jpeg"), None);
+    }
 }
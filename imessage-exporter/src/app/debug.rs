@@ -0,0 +1,227 @@
+/*!
+ Contains logic for the `debug` subcommand, which prints how raw message data is read.
+
+ The output is meant to be attached to bug reports, so problems with the parsers can be
+ reproduced without access to the database the data came from.
+*/
+
+use std::{
+    fmt::Write,
+    fs::read,
+    path::{Path, PathBuf},
+};
+
+use clap::ArgMatches;
+use rusqlite::Error as SqliteError;
+
+use imessage_database::{
+    error::table::TableError,
+    tables::table::{get_connection, ATTRIBUTED_BODY, MESSAGE},
+    util::{
+        backup::sms_db_path, dirs::default_db_path, platform::Platform,
+        typedstream::parser::TypedStreamReader,
+    },
+};
+
+use crate::app::{
+    error::RuntimeError,
+    options::{ARG_SOURCE, COMMAND_TYPEDSTREAM, OPTION_DB_PATH, OPTION_PLATFORM},
+};
+
+/// The most bytes of each object to print before the rest are elided
+const MAX_BYTES_SHOWN: usize = 32;
+
+/// Run the debugging tool selected on the command line
+pub fn run(args: &ArgMatches) -> Result<(), RuntimeError> {
+    match args.subcommand() {
+        Some((COMMAND_TYPEDSTREAM, args)) => {
+            let source: &String = args.get_one(ARG_SOURCE).ok_or_else(|| {
+                RuntimeError::InvalidOptions(format!("Missing {ARG_SOURCE} to read from"))
+            })?;
+            let bytes = read_source(args, source)?;
+            print!("{}", dump_typedstream(&bytes));
+            Ok(())
+        }
+        _ => Err(RuntimeError::InvalidOptions(format!(
+            "Unknown debug command! Must be one of <{COMMAND_TYPEDSTREAM}>"
+        ))),
+    }
+}
+
+/// Read `typedstream` data from a file, or from the `attributedBody` of the message with a GUID
+fn read_source(args: &ArgMatches, source: &str) -> Result<Vec<u8>, RuntimeError> {
+    let path = Path::new(source);
+    if path.is_file() {
+        return read(path).map_err(RuntimeError::DiskError);
+    }
+
+    let db = get_connection(&db_path(args)?).map_err(RuntimeError::DatabaseError)?;
+    let body: Option<Vec<u8>> = db
+        .query_row(
+            &format!("SELECT {ATTRIBUTED_BODY} FROM {MESSAGE} WHERE guid = ?1"),
+            [source],
+            |row| row.get(0),
+        )
+        .map_err(|why| match why {
+            SqliteError::QueryReturnedNoRows => RuntimeError::InvalidOptions(format!(
+                "`{source}` is not a file or the GUID of a message in the database!"
+            )),
+            why => RuntimeError::DatabaseError(TableError::Messages(why)),
+        })?;
+
+    body.ok_or_else(|| {
+        RuntimeError::InvalidOptions(format!("Message `{source}` has no {ATTRIBUTED_BODY}!"))
+    })
+}
+
+/// Get the path to the database, the same way exports find it
+fn db_path(args: &ArgMatches) -> Result<PathBuf, RuntimeError> {
+    let db_path = match args.get_one::<String>(OPTION_DB_PATH) {
+        Some(path) => PathBuf::from(path),
+        None => default_db_path(),
+    };
+    let platform = match args.get_one::<String>(OPTION_PLATFORM) {
+        Some(platform_str) => Platform::from_cli(platform_str).ok_or_else(|| {
+            RuntimeError::InvalidOptions(format!("{platform_str} is not a valid platform!"))
+        })?,
+        None => Platform::determine(&db_path),
+    };
+    Ok(match platform {
+        Platform::iOS => sms_db_path(&db_path),
+        Platform::macOS => db_path,
+    })
+}
+
+/// Describe the contents of a `typedstream`: the tree of objects it holds, the bytes each object
+/// was read from, and the table of types it defines
+fn dump_typedstream(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "Read {} bytes", bytes.len());
+
+    let _ = writeln!(out, "\nObject tree:");
+    match TypedStreamReader::from(bytes).parse_values() {
+        Ok(values) => values.iter().for_each(|value| {
+            let _ = writeln!(out, "{value:#?}");
+        }),
+        Err(why) => {
+            let _ = writeln!(out, "Unable to parse: {why}");
+        }
+    }
+
+    // Corrupt data is skipped, so the objects around it are still shown
+    let mut reader = TypedStreamReader::from(bytes);
+    let components = match reader.parse_with_recovery() {
+        Ok(components) => components,
+        Err(why) => {
+            let _ = writeln!(out, "\nUnable to read objects: {why}");
+            return out;
+        }
+    };
+
+    let _ = writeln!(out, "\nObjects:");
+    for (idx, (component, span)) in components.iter().zip(reader.spans()).enumerate() {
+        let _ = writeln!(
+            out,
+            "{idx:>4}  0x{:04x}..0x{:04x}  {component:?}",
+            span.start, span.end
+        );
+        let _ = writeln!(out, "      {}", format_bytes(&bytes[span.clone()]));
+    }
+
+    let _ = writeln!(out, "\nTypes:");
+    for (idx, types) in reader.types().iter().enumerate() {
+        let _ = writeln!(out, "{idx:>4}  {types:?}");
+    }
+
+    out
+}
+
+/// Format bytes as hex, eliding any past [`MAX_BYTES_SHOWN`]
+fn format_bytes(bytes: &[u8]) -> String {
+    let mut hex = bytes
+        .iter()
+        .take(MAX_BYTES_SHOWN)
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if bytes.len() > MAX_BYTES_SHOWN {
+        let _ = write!(hex, " ... ({} more bytes)", bytes.len() - MAX_BYTES_SHOWN);
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::read};
+
+    use crate::app::{
+        debug::{dump_typedstream, format_bytes},
+        options::{get_command, ARG_SOURCE, COMMAND_DEBUG, COMMAND_TYPEDSTREAM, OPTION_DB_PATH},
+    };
+
+    #[test]
+    fn can_dump_typedstream() {
+        let bytes = read(
+            current_dir()
+                .unwrap()
+                .parent()
+                .unwrap()
+                .join("imessage-database/test_data/typedstream/Mention"),
+        )
+        .unwrap();
+
+        let dump = dump_typedstream(&bytes);
+
+        assert!(dump.starts_with(&format!("Read {} bytes", bytes.len())));
+        assert!(dump.contains("String(\n    \"Test Dad \",\n)"));
+        assert!(dump.contains("\nObjects:\n   0  0x"));
+        assert!(dump.contains("\nTypes:\n   0  "));
+    }
+
+    #[test]
+    fn cant_dump_invalid_typedstream() {
+        let dump = dump_typedstream(&[0x00, 0x01, 0x02]);
+
+        assert!(dump.contains("Unable to parse"));
+        assert!(dump.contains("Unable to read objects"));
+        assert!(!dump.contains("Types:"));
+    }
+
+    #[test]
+    fn can_format_bytes() {
+        assert_eq!(format_bytes(&[0x84, 0x01, 0x2b]), "84 01 2b");
+        assert_eq!(
+            format_bytes(&[0; 34]),
+            format!("{} ... (2 more bytes)", ["00"; 32].join(" "))
+        );
+    }
+
+    #[test]
+    fn can_parse_debug_command() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "debug",
+            "typedstream",
+            "-p",
+            "chat.db",
+            "ABC-123",
+        ]);
+
+        let (name, debug) = args.subcommand().unwrap();
+        assert_eq!(name, COMMAND_DEBUG);
+        let (name, typedstream) = debug.subcommand().unwrap();
+        assert_eq!(name, COMMAND_TYPEDSTREAM);
+        assert_eq!(
+            typedstream
+                .get_one::<String>(ARG_SOURCE)
+                .map(String::as_str),
+            Some("ABC-123")
+        );
+        assert_eq!(
+            typedstream
+                .get_one::<String>(OPTION_DB_PATH)
+                .map(String::as_str),
+            Some("chat.db")
+        );
+    }
+}
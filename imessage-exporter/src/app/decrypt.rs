@@ -0,0 +1,38 @@
+/*!
+ Contains logic for reading encrypted iOS backups, which requires the `encrypted-backups` feature.
+
+ The Messages database and attachments are decrypted to a private [`TempFolder`] laid out like an unencrypted backup,
+ which the export reads instead of the original backup.
+*/
+
+use std::path::{Path, PathBuf};
+
+use log::info;
+
+use imessage_database::util::encrypted_backup::decrypt_messages;
+
+use crate::app::{error::RuntimeError, temp_folder::TempFolder};
+
+/// A decrypted copy of the Messages data in an encrypted backup, deleted when it is dropped
+#[derive(Debug)]
+pub struct DecryptedBackup {
+    /// The folder the decrypted files were written to
+    pub path: PathBuf,
+    /// Keeps the folder until the backup is dropped
+    _folder: TempFolder,
+}
+
+impl DecryptedBackup {
+    /// Decrypt the Messages data in `backup` with the backup password
+    pub fn new(backup: &Path, password: &str) -> Result<Self, RuntimeError> {
+        let folder = TempFolder::new("imessage-exporter-backup-")?;
+        info!("Decrypting backup to {}...", folder.path().display());
+        let files =
+            decrypt_messages(backup, password, folder.path()).map_err(RuntimeError::BackupError)?;
+        info!("Decrypted {files} files");
+        Ok(Self {
+            path: folder.path().to_path_buf(),
+            _folder: folder,
+        })
+    }
+}
@@ -0,0 +1,167 @@
+/*!
+ Contains logic for storing identical attachments only once in an export.
+*/
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fs::{hard_link, File},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use sha2::{Digest, Sha256};
+
+use imessage_database::util::size::format_file_size;
+
+/// The size and SHA-256 digest of a file's contents, used to find identical files
+///
+/// Files are hard linked to each other when their keys match, so the digest has to be collision resistant.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct ContentKey {
+    /// The size of the file in bytes
+    size: u64,
+    /// The SHA-256 digest of the file's contents
+    digest: [u8; 32],
+}
+
+impl ContentKey {
+    /// Hash the contents of the file at `path`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let mut reader = BufReader::new(File::open(path).ok()?);
+        let mut hasher = Sha256::new();
+        let mut buffer = [0; 64 * 1024];
+        let mut size = 0;
+        loop {
+            let read = reader.read(&mut buffer).ok()?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+        Some(Self {
+            size,
+            digest: hasher.finalize().into(),
+        })
+    }
+}
+
+/// Tracks the attachments copied during an export so identical files are stored once
+#[derive(Debug, Default)]
+pub struct AttachmentDeduplicator {
+    /// Map of file contents to the first copy written to the export
+    copies: RefCell<HashMap<ContentKey, PathBuf>>,
+    /// The number of attachments that reused an existing copy
+    duplicates: Cell<usize>,
+    /// The number of bytes that were not written because of reused copies
+    bytes_saved: Cell<u64>,
+}
+
+impl AttachmentDeduplicator {
+    /// Get the path of an existing copy of a file with the same contents
+    pub fn existing(&self, key: &ContentKey) -> Option<PathBuf> {
+        self.copies.borrow().get(key).cloned()
+    }
+
+    /// Record the path a file with these contents was copied to
+    pub fn insert(&self, key: ContentKey, path: PathBuf) {
+        self.copies.borrow_mut().entry(key).or_insert(path);
+    }
+
+    /// Reference an existing copy from `to`, returning the path the export should use
+    ///
+    /// `to` is hard linked to the existing copy, so each conversation keeps its own attachment
+    /// directory while the data is only stored once. If the file system does not support hard
+    /// links, the existing copy is used directly.
+    pub fn link(&self, key: &ContentKey, existing: &Path, to: &Path) -> PathBuf {
+        self.duplicates.set(self.duplicates.get() + 1);
+        self.bytes_saved.set(self.bytes_saved.get() + key.size);
+
+        if to.exists() || hard_link(existing, to).is_ok() {
+            return to.to_path_buf();
+        }
+        existing.to_path_buf()
+    }
+
+    /// Point later duplicates at the new location of a copy that was moved
+    pub fn rename(&self, from: &Path, to: &Path) {
+        for path in self.copies.borrow_mut().values_mut() {
+            if path == from {
+                *path = to.to_path_buf();
+            }
+        }
+    }
+
+    /// Print a summary of the deduplicated attachments
+    pub fn report(&self) {
+        info!(
+            "Deduplicated {} attachments into {} unique files, saving {}",
+            self.duplicates.get() + self.copies.borrow().len(),
+            self.copies.borrow().len(),
+            format_file_size(self.bytes_saved.get())
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_file, write},
+    };
+
+    use crate::app::dedupe::{AttachmentDeduplicator, ContentKey};
+
+    #[test]
+    fn can_match_identical_files() {
+        let dir = temp_dir().join("imessage-exporter-dedupe-match");
+        create_dir_all(&dir).unwrap();
+        let first = dir.join("first.txt");
+        let second = dir.join("second.txt");
+        write(&first, "meme").unwrap();
+        write(&second, "meme").unwrap();
+
+        assert_eq!(
+            ContentKey::from_path(&first),
+            ContentKey::from_path(&second)
+        );
+    }
+
+    #[test]
+    fn cant_match_different_files() {
+        let dir = temp_dir().join("imessage-exporter-dedupe-different");
+        create_dir_all(&dir).unwrap();
+        let first = dir.join("first.txt");
+        let second = dir.join("second.txt");
+        write(&first, "meme").unwrap();
+        write(&second, "other meme").unwrap();
+
+        assert_ne!(
+            ContentKey::from_path(&first),
+            ContentKey::from_path(&second)
+        );
+    }
+
+    #[test]
+    fn can_link_existing_copy() {
+        let dir = temp_dir().join("imessage-exporter-dedupe-link");
+        create_dir_all(&dir).unwrap();
+        let existing = dir.join("1.txt");
+        let to = dir.join("2.txt");
+        let _ = remove_file(&to);
+        write(&existing, "meme").unwrap();
+
+        let deduplicator = AttachmentDeduplicator::default();
+        let key = ContentKey::from_path(&existing).unwrap();
+        deduplicator.insert(key, existing.clone());
+
+        let found = deduplicator.existing(&key).unwrap();
+        let linked = deduplicator.link(&key, &found, &to);
+
+        assert!(linked.exists());
+        assert_eq!(deduplicator.duplicates.get(), 1);
+        assert_eq!(deduplicator.bytes_saved.get(), 4);
+    }
+}
@@ -19,6 +19,12 @@ pub enum RuntimeError {
     CreateError(IoError, PathBuf),
     DiskError(IoError),
     DatabaseError(TableError),
+    SqliteError(rusqlite::Error),
+    #[cfg(feature = "parquet")]
+    ParquetError(parquet::errors::ParquetError),
+    #[cfg(feature = "encrypted-backups")]
+    BackupError(imessage_database::error::backup::BackupError),
+    TemplateError(tera::Error),
     NotEnoughAvailableSpace(u64, u64),
 }
 
@@ -29,6 +35,21 @@ impl Display for RuntimeError {
             RuntimeError::CreateError(why, path) => write!(fmt, "{why}: {path:?}"),
             RuntimeError::DiskError(why) => write!(fmt, "{why}"),
             RuntimeError::DatabaseError(why) => write!(fmt, "{why}"),
+            RuntimeError::SqliteError(why) => write!(fmt, "Failed to write export database: {why}"),
+            #[cfg(feature = "parquet")]
+            RuntimeError::ParquetError(why) => write!(fmt, "Failed to write Parquet file: {why}"),
+            #[cfg(feature = "encrypted-backups")]
+            RuntimeError::BackupError(why) => write!(fmt, "Failed to decrypt backup: {why}"),
+            RuntimeError::TemplateError(why) => {
+                write!(fmt, "Failed to render template: {why}")?;
+                // Tera reports the useful detail, like the line of a syntax error, as the source
+                let mut source = std::error::Error::source(why);
+                while let Some(cause) = source {
+                    write!(fmt, "\n{cause}")?;
+                    source = cause.source();
+                }
+                Ok(())
+            }
             RuntimeError::NotEnoughAvailableSpace(estimated_bytes, available_bytes) => {
                 write!(
                     fmt, 
@@ -11,6 +11,36 @@ pub enum ExportType {
     Html,
     /// Text file export
     Txt,
+    /// Markdown file export
+    Markdown,
+    /// PDF file export
+    Pdf,
+    /// EPUB book export
+    Epub,
+    /// MHTML archive export, with attachments stored in each file
+    Mhtml,
+    /// Normalized SQLite database export
+    Sqlite,
+    /// mbox email archive export
+    Mbox,
+    /// Maildir export, with a folder per conversation
+    Maildir,
+    /// Newline-delimited JSON stream export
+    Ndjson,
+    /// Obsidian vault export, with a note per contact per day
+    Obsidian,
+    /// Day One journal import export
+    DayOne,
+    /// Matrix room event export
+    Matrix,
+    /// WhatsApp `_chat.txt` export
+    WhatsApp,
+    /// LaTeX book export
+    Latex,
+    /// Apache Parquet columnar export, available when built with the `parquet` feature
+    Parquet,
+    /// Attachments only, with a folder per conversation and month
+    Attachments,
 }
 
 impl ExportType {
@@ -19,6 +49,21 @@ impl ExportType {
         match platform.to_lowercase().as_str() {
             "txt" => Some(Self::Txt),
             "html" => Some(Self::Html),
+            "md" | "markdown" => Some(Self::Markdown),
+            "pdf" => Some(Self::Pdf),
+            "epub" => Some(Self::Epub),
+            "mht" | "mhtml" => Some(Self::Mhtml),
+            "sqlite" => Some(Self::Sqlite),
+            "mbox" => Some(Self::Mbox),
+            "maildir" => Some(Self::Maildir),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            "parquet" => Some(Self::Parquet),
+            "obsidian" => Some(Self::Obsidian),
+            "dayone" | "day-one" => Some(Self::DayOne),
+            "matrix" => Some(Self::Matrix),
+            "whatsapp" => Some(Self::WhatsApp),
+            "tex" | "latex" => Some(Self::Latex),
+            "attachments" => Some(Self::Attachments),
             _ => None,
         }
     }
@@ -29,6 +74,21 @@ impl Display for ExportType {
         match self {
             ExportType::Txt => write!(fmt, "txt"),
             ExportType::Html => write!(fmt, "html"),
+            ExportType::Markdown => write!(fmt, "md"),
+            ExportType::Pdf => write!(fmt, "pdf"),
+            ExportType::Epub => write!(fmt, "epub"),
+            ExportType::Mhtml => write!(fmt, "mht"),
+            ExportType::Sqlite => write!(fmt, "sqlite"),
+            ExportType::Mbox => write!(fmt, "mbox"),
+            ExportType::Maildir => write!(fmt, "maildir"),
+            ExportType::Ndjson => write!(fmt, "ndjson"),
+            ExportType::Parquet => write!(fmt, "parquet"),
+            ExportType::Obsidian => write!(fmt, "obsidian"),
+            ExportType::DayOne => write!(fmt, "dayone"),
+            ExportType::Matrix => write!(fmt, "matrix"),
+            ExportType::WhatsApp => write!(fmt, "whatsapp"),
+            ExportType::Latex => write!(fmt, "tex"),
+            ExportType::Attachments => write!(fmt, "attachments"),
         }
     }
 }
@@ -60,9 +120,239 @@ mod tests {
         assert!(matches!(ExportType::from_cli("tXt"), Some(ExportType::Txt)));
     }
 
+    #[test]
+    fn can_parse_markdown_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("md"),
+            Some(ExportType::Markdown)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("MD"),
+            Some(ExportType::Markdown)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Markdown"),
+            Some(ExportType::Markdown)
+        ));
+    }
+
+    #[test]
+    fn can_parse_pdf_any_case() {
+        assert!(matches!(ExportType::from_cli("pdf"), Some(ExportType::Pdf)));
+        assert!(matches!(ExportType::from_cli("PDF"), Some(ExportType::Pdf)));
+        assert!(matches!(ExportType::from_cli("pDf"), Some(ExportType::Pdf)));
+    }
+
+    #[test]
+    fn can_parse_epub_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("epub"),
+            Some(ExportType::Epub)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("EPUB"),
+            Some(ExportType::Epub)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("ePub"),
+            Some(ExportType::Epub)
+        ));
+    }
+
+    #[test]
+    fn can_parse_mhtml_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("mht"),
+            Some(ExportType::Mhtml)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("MHTML"),
+            Some(ExportType::Mhtml)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Mht"),
+            Some(ExportType::Mhtml)
+        ));
+    }
+
+    #[test]
+    fn can_parse_sqlite_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("sqlite"),
+            Some(ExportType::Sqlite)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("SQLITE"),
+            Some(ExportType::Sqlite)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("SQLite"),
+            Some(ExportType::Sqlite)
+        ));
+    }
+
+    #[test]
+    fn can_parse_mbox_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("mbox"),
+            Some(ExportType::Mbox)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("MBOX"),
+            Some(ExportType::Mbox)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("mBox"),
+            Some(ExportType::Mbox)
+        ));
+    }
+
+    #[test]
+    fn can_parse_maildir_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("maildir"),
+            Some(ExportType::Maildir)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("MAILDIR"),
+            Some(ExportType::Maildir)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("MailDir"),
+            Some(ExportType::Maildir)
+        ));
+    }
+
+    #[test]
+    fn can_parse_ndjson_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("ndjson"),
+            Some(ExportType::Ndjson)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("NDJSON"),
+            Some(ExportType::Ndjson)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("JsonL"),
+            Some(ExportType::Ndjson)
+        ));
+    }
+
+    #[test]
+    fn can_parse_parquet_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("parquet"),
+            Some(ExportType::Parquet)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("PARQUET"),
+            Some(ExportType::Parquet)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Parquet"),
+            Some(ExportType::Parquet)
+        ));
+    }
+
+    #[test]
+    fn can_parse_obsidian_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("obsidian"),
+            Some(ExportType::Obsidian)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("OBSIDIAN"),
+            Some(ExportType::Obsidian)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Obsidian"),
+            Some(ExportType::Obsidian)
+        ));
+    }
+
+    #[test]
+    fn can_parse_day_one_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("dayone"),
+            Some(ExportType::DayOne)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("DAYONE"),
+            Some(ExportType::DayOne)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Day-One"),
+            Some(ExportType::DayOne)
+        ));
+    }
+
+    #[test]
+    fn can_parse_matrix_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("matrix"),
+            Some(ExportType::Matrix)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("MATRIX"),
+            Some(ExportType::Matrix)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Matrix"),
+            Some(ExportType::Matrix)
+        ));
+    }
+
+    #[test]
+    fn can_parse_whatsapp_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("whatsapp"),
+            Some(ExportType::WhatsApp)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("WHATSAPP"),
+            Some(ExportType::WhatsApp)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("WhatsApp"),
+            Some(ExportType::WhatsApp)
+        ));
+    }
+
+    #[test]
+    fn can_parse_latex_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("tex"),
+            Some(ExportType::Latex)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("LATEX"),
+            Some(ExportType::Latex)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("LaTeX"),
+            Some(ExportType::Latex)
+        ));
+    }
+
+    #[test]
+    fn can_parse_attachments_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("attachments"),
+            Some(ExportType::Attachments)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("ATTACHMENTS"),
+            Some(ExportType::Attachments)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Attachments"),
+            Some(ExportType::Attachments)
+        ));
+    }
+
     #[test]
     fn cant_parse_invalid() {
-        assert!(ExportType::from_cli("pdf").is_none());
         assert!(ExportType::from_cli("json").is_none());
         assert!(ExportType::from_cli("").is_none());
     }
@@ -0,0 +1,230 @@
+/*!
+ Contains logic for the `list` subcommand, which prints every conversation in the database.
+
+ Each conversation is listed with the identifier to pass to `--conversation`, so users can
+ find the value that selects it without reading the database themselves.
+*/
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use chrono::{DateTime, Local};
+use clap::ArgMatches;
+
+use imessage_database::{
+    tables::chat::{Chat, ChatActivity},
+    util::dates::get_local_time,
+};
+
+use crate::app::{
+    error::RuntimeError, options::ARG_JSON, runtime::Config, sanitizers::sanitize_json,
+};
+
+/// A conversation, as it is listed
+pub struct Listing<'a> {
+    /// The `ROWID` of the chat
+    pub chat_id: i32,
+    /// The value to pass to `--conversation` to select the conversation
+    pub identifier: &'a str,
+    /// The custom name of the conversation, if it has one
+    pub name: Option<&'a str>,
+    /// The service the conversation used, i.e. iMessage or SMS
+    pub service: Option<&'a str>,
+    /// The names or handles of everyone in the conversation, besides the database owner
+    pub participants: Vec<&'a str>,
+    /// The number of messages in the conversation
+    pub messages: usize,
+    /// When the first message was sent
+    pub first: Option<DateTime<Local>>,
+    /// When the last message was sent
+    pub last: Option<DateTime<Local>>,
+}
+
+/// List the conversations in the database, printing JSON if requested on the command line
+pub fn run(config: &Config, args: &ArgMatches) -> Result<(), RuntimeError> {
+    let activity = Chat::activity(&config.db).map_err(RuntimeError::DatabaseError)?;
+    let listings = listings(config, &activity);
+
+    if args.get_flag(ARG_JSON) {
+        println!("{}", to_json(&listings));
+    } else {
+        print!("{}", to_text(&listings));
+    }
+    Ok(())
+}
+
+/// Build a listing for every conversation, most recently active first
+pub fn listings<'a>(config: &'a Config, activity: &HashMap<i32, ChatActivity>) -> Vec<Listing<'a>> {
+    let date = |date: i64| get_local_time(&date, &config.offset).ok();
+    let mut listings: Vec<Listing> = config
+        .chatrooms
+        .values()
+        .map(|chat| {
+            let activity = activity.get(&chat.rowid);
+            let participants = config
+                .chatroom_participants
+                .get(&chat.rowid)
+                .map(|participants| {
+                    participants
+                        .iter()
+                        .map(|id| config.who(Some(*id), false, &None))
+                        .collect()
+                })
+                .unwrap_or_default();
+            Listing {
+                chat_id: chat.rowid,
+                identifier: &chat.chat_identifier,
+                name: chat.display_name(),
+                service: chat.service_name.as_deref(),
+                participants,
+                messages: activity.map_or(0, |activity| activity.messages),
+                first: activity.and_then(|activity| date(activity.first_date)),
+                last: activity.and_then(|activity| date(activity.last_date)),
+            }
+        })
+        .collect();
+
+    listings.sort_by_key(|listing| (Reverse(listing.last), listing.chat_id));
+    listings
+}
+
+/// Describe each conversation on a few lines
+fn to_text(listings: &[Listing]) -> String {
+    let mut out_s = String::new();
+    for listing in listings {
+        out_s.push_str(listing.identifier);
+        if let Some(name) = listing.name {
+            out_s.push_str(&format!(" ({name})"));
+        }
+        if let Some(service) = listing.service {
+            out_s.push_str(&format!(" [{service}]"));
+        }
+        out_s.push('\n');
+
+        if !listing.participants.is_empty() {
+            out_s.push_str(&format!(
+                "    Participants: {}\n",
+                listing.participants.join(", ")
+            ));
+        }
+        out_s.push_str(&format!("    Messages: {}", listing.messages));
+        if let (Some(first), Some(last)) = (listing.first, listing.last) {
+            out_s.push_str(&format!(
+                ", {} to {}",
+                first.format("%Y-%m-%d"),
+                last.format("%Y-%m-%d")
+            ));
+        }
+        out_s.push('\n');
+    }
+    out_s
+}
+
+/// Describe each conversation as an object in a JSON array
+fn to_json(listings: &[Listing]) -> String {
+    let string = |value: Option<&str>| match value {
+        Some(value) => format!("\"{}\"", sanitize_json(value)),
+        None => String::from("null"),
+    };
+    let date = |date: Option<DateTime<Local>>| string(date.map(|d| d.to_rfc3339()).as_deref());
+
+    let listings: Vec<String> = listings
+        .iter()
+        .map(|listing| {
+            let participants: Vec<String> = listing
+                .participants
+                .iter()
+                .map(|participant| string(Some(*participant)))
+                .collect();
+            format!(
+                "{{\"identifier\":{},\"name\":{},\"service\":{},\"participants\":[{}],\"messages\":{},\"first_message\":{},\"last_message\":{}}}",
+                string(Some(listing.identifier)),
+                string(listing.name),
+                string(listing.service),
+                participants.join(","),
+                listing.messages,
+                date(listing.first),
+                date(listing.last),
+            )
+        })
+        .collect();
+    format!("[{}]", listings.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use crate::app::{
+        list::{to_json, to_text, Listing},
+        options::{get_command, ARG_JSON, COMMAND_LIST, OPTION_DB_PATH},
+    };
+
+    fn sample() -> Vec<Listing<'static>> {
+        vec![
+            Listing {
+                chat_id: 1,
+                identifier: "chat123456789",
+                name: Some("Family"),
+                service: Some("iMessage"),
+                participants: vec!["+15558675309", "jane@example.com"],
+                messages: 12,
+                first: Local.with_ymd_and_hms(2020, 1, 2, 12, 0, 0).single(),
+                last: Local.with_ymd_and_hms(2024, 5, 17, 12, 0, 0).single(),
+            },
+            Listing {
+                chat_id: 2,
+                identifier: "+15558675309",
+                name: None,
+                service: None,
+                participants: vec![],
+                messages: 0,
+                first: None,
+                last: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn can_list_text() {
+        let expected = concat!(
+            "chat123456789 (Family) [iMessage]\n",
+            "    Participants: +15558675309, jane@example.com\n",
+            "    Messages: 12, 2020-01-02 to 2024-05-17\n",
+            "+15558675309\n",
+            "    Messages: 0\n",
+        );
+        assert_eq!(to_text(&sample()), expected);
+    }
+
+    #[test]
+    fn can_list_json() {
+        let json = to_json(&sample());
+        assert!(json.starts_with("[{\"identifier\":\"chat123456789\",\"name\":\"Family\",\"service\":\"iMessage\",\"participants\":[\"+15558675309\",\"jane@example.com\"],\"messages\":12,\"first_message\":\"2020-01-02T12:00:00"));
+        assert!(json.ends_with("{\"identifier\":\"+15558675309\",\"name\":null,\"service\":null,\"participants\":[],\"messages\":0,\"first_message\":null,\"last_message\":null}]"));
+    }
+
+    #[test]
+    fn can_parse_list_command() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "list",
+            "--json",
+            "-p",
+            "chat.db",
+        ]);
+
+        let (name, list) = args.subcommand().unwrap();
+        assert_eq!(name, COMMAND_LIST);
+        assert!(list.get_flag(ARG_JSON));
+        assert_eq!(
+            list.get_one::<String>(OPTION_DB_PATH).map(String::as_str),
+            Some("chat.db")
+        );
+    }
+
+    #[test]
+    fn can_list_nothing() {
+        assert_eq!(to_text(&[]), "");
+        assert_eq!(to_json(&[]), "[]");
+    }
+}
@@ -0,0 +1,91 @@
+/*!
+ Contains the logger that reports progress, warnings, and debugging information.
+
+ Log messages are written to `stderr`, so they never mix with exported data streamed to `stdout`.
+*/
+
+use std::str::FromStr;
+
+use clap::ArgMatches;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::app::options::OPTION_LOG_LEVEL;
+
+/// Levels that can be passed to `--log-level`, from quietest to loudest
+pub const LOG_LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+/// Level used when `--log-level` is omitted, which shows progress and warnings
+const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// Writes log messages to `stderr`
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            // Progress is shown as-is
+            Level::Info => eprintln!("{}", record.args()),
+            Level::Error => eprintln!("Error: {}", record.args()),
+            Level::Warn => eprintln!("Warning: {}", record.args()),
+            Level::Debug | Level::Trace => {
+                eprintln!("[{}] {}", record.target(), record.args())
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Get the log level requested on the command line
+fn log_level(args: &ArgMatches) -> LevelFilter {
+    args.get_one::<String>(OPTION_LOG_LEVEL)
+        .and_then(|level| LevelFilter::from_str(level).ok())
+        .unwrap_or(DEFAULT_LOG_LEVEL)
+}
+
+/// Send log messages at or above the requested level to `stderr`
+///
+/// This runs before the rest of the options are built, so warnings about them are shown.
+pub fn init(args: &ArgMatches) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log_level(args));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::LevelFilter;
+
+    use crate::app::{logger::log_level, options::get_command};
+
+    #[test]
+    fn can_get_default_log_level() {
+        let args = get_command().get_matches_from(["imessage-exporter", "-d"]);
+
+        assert_eq!(log_level(&args), LevelFilter::Info);
+    }
+
+    #[test]
+    fn can_get_log_level() {
+        let args =
+            get_command().get_matches_from(["imessage-exporter", "-d", "--log-level", "DEBUG"]);
+
+        assert_eq!(log_level(&args), LevelFilter::Debug);
+    }
+
+    #[test]
+    fn cant_get_invalid_log_level() {
+        let args =
+            get_command().try_get_matches_from(["imessage-exporter", "-d", "--log-level", "loud"]);
+
+        assert!(args.is_err());
+    }
+}
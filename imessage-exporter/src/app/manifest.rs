@@ -0,0 +1,135 @@
+/*!
+ Contains logic for writing a machine-readable list of the attachments copied during an export.
+*/
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{copy, BufWriter, Write},
+    path::Path,
+};
+
+use log::warn;
+
+use sha1::{Digest, Sha1};
+
+use crate::app::{error::RuntimeError, sanitizers::sanitize_csv};
+
+/// Name of the manifest file, written to the root of the export directory
+pub const MANIFEST_FILENAME: &str = "attachment_manifest.csv";
+/// Column names of the manifest file
+const MANIFEST_HEADER: &str = "message_guid,chat,original_path,exported_path,mime_type,bytes,sha1";
+
+/// Represents a single copied attachment in the manifest
+pub struct ManifestEntry<'a> {
+    /// The GUID of the message the attachment was sent with
+    pub message_guid: &'a str,
+    /// The name of the conversation, as used for its exported file
+    pub chat: &'a str,
+    /// Where the attachment is stored by Messages
+    pub original_path: &'a Path,
+    /// Where the attachment was copied to, relative to the export directory
+    pub exported_path: &'a str,
+    /// The MIME type of the original attachment
+    pub mime_type: &'a str,
+}
+
+/// Writes a CSV row for each attachment copied during an export
+pub struct AttachmentManifest {
+    /// Where rows are written
+    writer: RefCell<BufWriter<File>>,
+}
+
+impl AttachmentManifest {
+    /// Create the manifest file in the export directory and write its header
+    pub fn new(export_path: &Path) -> Result<Self, RuntimeError> {
+        let mut writer = BufWriter::new(
+            File::create(export_path.join(MANIFEST_FILENAME)).map_err(RuntimeError::DiskError)?,
+        );
+        writeln!(writer, "{MANIFEST_HEADER}").map_err(RuntimeError::DiskError)?;
+        Ok(Self {
+            writer: RefCell::new(writer),
+        })
+    }
+
+    /// Add a copied attachment to the manifest, reading the copy to get its size and hash
+    pub fn record(&self, entry: &ManifestEntry, copied_path: &Path) {
+        let Some((bytes, hash)) = hash_file(copied_path) else {
+            warn!("Unable to read {copied_path:?} for the attachment manifest");
+            return;
+        };
+        let row = format_row(entry, bytes, &hash);
+        if let Err(why) = writeln!(self.writer.borrow_mut(), "{row}") {
+            warn!("Unable to write to the attachment manifest: {why}");
+        }
+    }
+}
+
+impl Drop for AttachmentManifest {
+    fn drop(&mut self) {
+        if let Err(why) = self.writer.get_mut().flush() {
+            warn!("Unable to write the attachment manifest: {why}");
+        }
+    }
+}
+
+/// Get the size and `SHA-1` hash of a file
+pub fn hash_file(path: &Path) -> Option<(u64, String)> {
+    let mut hasher = Sha1::new();
+    let bytes = copy(&mut File::open(path).ok()?, &mut hasher).ok()?;
+    Some((bytes, format!("{:x}", hasher.finalize())))
+}
+
+/// Build a CSV row for a manifest entry
+fn format_row(entry: &ManifestEntry, bytes: u64, hash: &str) -> String {
+    [
+        sanitize_csv(entry.message_guid),
+        sanitize_csv(entry.chat),
+        sanitize_csv(&entry.original_path.display().to_string()),
+        sanitize_csv(entry.exported_path),
+        sanitize_csv(entry.mime_type),
+        bytes.to_string().into(),
+        hash.into(),
+    ]
+    .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, write},
+        path::Path,
+    };
+
+    use crate::app::manifest::{format_row, hash_file, ManifestEntry};
+
+    #[test]
+    fn can_format_row() {
+        let entry = ManifestEntry {
+            message_guid: "ABC-123",
+            chat: "Ada, Grace",
+            original_path: Path::new("/Users/me/Library/Messages/Attachments/a/b/IMG_0001.HEIC"),
+            exported_path: "attachments/1/42.jpeg",
+            mime_type: "image/heic",
+        };
+
+        assert_eq!(
+            format_row(&entry, 4, "abcd"),
+            "ABC-123,\"Ada, Grace\",/Users/me/Library/Messages/Attachments/a/b/IMG_0001.HEIC,attachments/1/42.jpeg,image/heic,4,abcd"
+        );
+    }
+
+    #[test]
+    fn can_hash_file() {
+        let dir = temp_dir().join("imessage-exporter-manifest-hash");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        write(&path, "meme").unwrap();
+
+        assert_eq!(
+            hash_file(&path),
+            Some((4, "58117e24e4d0b8a958146c9eaa28336184f4d491".to_string()))
+        );
+    }
+}
@@ -0,0 +1,62 @@
+/*!
+ Contains logic for exporting several databases at once, i.e. from an old Mac, a new Mac, an iPhone backup, and an Android phone.
+
+ The databases are merged into a temporary database that the export reads instead of `--db-path`.
+ It is written to a private [`TempFolder`], along with any attachments decoded from Android backups.
+*/
+
+use std::path::{Path, PathBuf};
+
+use log::info;
+
+use imessage_database::util::{
+    merge::{merge_databases, MergeSource},
+    platform::Platform,
+};
+
+use crate::app::{error::RuntimeError, temp_folder::TempFolder};
+
+/// A merged copy of several databases, deleted when it is dropped
+#[derive(Debug)]
+pub struct MergedDatabase {
+    /// The path to the merged database
+    pub path: PathBuf,
+    /// Keeps the folder until the database is dropped
+    _folder: TempFolder,
+}
+
+impl MergedDatabase {
+    /// Merge `others` into the database at `db_path`
+    ///
+    /// Rows already in `db_path` are kept as they are, and rows from `others` are added in the order they are passed.
+    /// Phone numbers in Android SMS backups that were saved without a country code are given `country_code`.
+    pub fn new(
+        db_path: &Path,
+        platform: Platform,
+        others: &[PathBuf],
+        country_code: &str,
+    ) -> Result<Self, RuntimeError> {
+        let folder = TempFolder::new("imessage-exporter-merge-")?;
+
+        let mut sources = vec![MergeSource {
+            path: db_path.to_path_buf(),
+            platform,
+        }];
+        sources.extend(others.iter().map(|path| MergeSource {
+            path: path.clone(),
+            platform: Platform::determine(path),
+        }));
+
+        info!("Merging {} databases...", sources.len());
+        let (path, added) = merge_databases(&sources, folder.path(), country_code)
+            .map_err(RuntimeError::DatabaseError)?;
+        for (source, count) in sources.iter().zip(added) {
+            info!("Read {count} new messages from {}", source.path.display());
+        }
+
+        Ok(Self {
+            path,
+            _folder: folder,
+        })
+    }
+}
@@ -0,0 +1,264 @@
+/*!
+ Contains logic for removing metadata, like GPS coordinates, from copied image files.
+*/
+
+use std::{
+    fs::{read, write},
+    path::Path,
+};
+
+/// Bytes that start every `JPEG` file
+const JPEG_SIGNATURE: [u8; 2] = [0xFF, 0xD8];
+/// Bytes that start every `PNG` file
+const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+/// Prefix of the `APP1` segment that holds `EXIF` data
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+/// Prefix of the `APP1` segment that holds `XMP` data
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/";
+/// The `EXIF` tag that describes how to rotate the image for display
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// Remove `EXIF`, `XMP`, and other text metadata from a copied image in place
+///
+/// `JPEG` files keep their orientation so they are still displayed upright.
+///
+/// Returns `None` if the file could not be read or written, or if it is not a `JPEG` or `PNG` file.
+pub fn strip_metadata(path: &Path) -> Option<()> {
+    let data = read(path).ok()?;
+    let stripped = if data.starts_with(&JPEG_SIGNATURE) {
+        strip_jpeg(&data)?
+    } else if data.starts_with(&PNG_SIGNATURE) {
+        strip_png(&data)?
+    } else {
+        return None;
+    };
+    write(path, stripped).ok()
+}
+
+/// Remove metadata segments from `JPEG` data, keeping a minimal `EXIF` segment with the orientation
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&JPEG_SIGNATURE);
+
+    let mut idx = JPEG_SIGNATURE.len();
+    while idx < data.len() {
+        if data[idx] != 0xFF {
+            return None;
+        }
+        let marker = *data.get(idx + 1)?;
+        match marker {
+            // Fill bytes before a marker
+            0xFF => {
+                idx += 1;
+                continue;
+            }
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                out.extend_from_slice(&data[idx..idx + 2]);
+                idx += 2;
+                continue;
+            }
+            // Start of scan: the rest of the file is image data
+            0xDA => {
+                out.extend_from_slice(&data[idx..]);
+                return Some(out);
+            }
+            _ => {}
+        }
+
+        let length = u16::from_be_bytes([*data.get(idx + 2)?, *data.get(idx + 3)?]) as usize;
+        let end = idx + 2 + length;
+        let segment = data.get(idx..end)?;
+        let payload = segment.get(4..)?;
+
+        match marker {
+            // APP1 holds EXIF and XMP data
+            0xE1 if payload.starts_with(EXIF_HEADER) => {
+                if let Some(orientation) = exif_orientation(&payload[EXIF_HEADER.len()..]) {
+                    out.extend_from_slice(&orientation_segment(orientation));
+                }
+            }
+            0xE1 if payload.starts_with(XMP_HEADER) => {}
+            // APP13 holds Photoshop and IPTC data
+            0xED => {}
+            _ => out.extend_from_slice(segment),
+        }
+        idx = end;
+    }
+    Some(out)
+}
+
+/// Read the orientation from the `TIFF` structure of an `EXIF` segment
+fn exif_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+        Some(match big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    };
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = [
+            *tiff.get(offset)?,
+            *tiff.get(offset + 1)?,
+            *tiff.get(offset + 2)?,
+            *tiff.get(offset + 3)?,
+        ];
+        Some(match big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    };
+
+    // The first image file directory holds the orientation
+    let ifd = read_u32(4)? as usize;
+    let entries = read_u16(ifd)? as usize;
+    (0..entries)
+        .map(|entry| ifd + 2 + entry * 12)
+        .find(|&entry| read_u16(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| read_u16(entry + 8))
+}
+
+/// Build an `APP1` segment whose `EXIF` data only holds an orientation
+fn orientation_segment(orientation: u16) -> Vec<u8> {
+    let mut tiff = vec![];
+    // Big endian header, with the first image file directory right after it
+    tiff.extend_from_slice(b"MM\0*");
+    tiff.extend_from_slice(&8u32.to_be_bytes());
+    // A single entry: the orientation, stored as one `SHORT`
+    tiff.extend_from_slice(&1u16.to_be_bytes());
+    tiff.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    tiff.extend_from_slice(&3u16.to_be_bytes());
+    tiff.extend_from_slice(&1u32.to_be_bytes());
+    tiff.extend_from_slice(&orientation.to_be_bytes());
+    tiff.extend_from_slice(&[0, 0]);
+    // There is no next image file directory
+    tiff.extend_from_slice(&0u32.to_be_bytes());
+
+    let length = (2 + EXIF_HEADER.len() + tiff.len()) as u16;
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&length.to_be_bytes());
+    segment.extend_from_slice(EXIF_HEADER);
+    segment.extend_from_slice(&tiff);
+    segment
+}
+
+/// Remove `EXIF` and text chunks from `PNG` data
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&PNG_SIGNATURE);
+
+    let mut idx = PNG_SIGNATURE.len();
+    while idx < data.len() {
+        let length = u32::from_be_bytes(data.get(idx..idx + 4)?.try_into().ok()?) as usize;
+        // Length, type, data, and checksum
+        let end = idx + 12 + length;
+        let chunk = data.get(idx..end)?;
+        match &chunk[4..8] {
+            b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" => {}
+            _ => out.extend_from_slice(chunk),
+        }
+        idx = end;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::metadata::{
+        exif_orientation, orientation_segment, strip_jpeg, strip_png, EXIF_HEADER, JPEG_SIGNATURE,
+        PNG_SIGNATURE, XMP_HEADER,
+    };
+
+    /// Build a `JPEG` segment with the provided marker and payload
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0xFF, marker];
+        segment.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        segment.extend_from_slice(payload);
+        segment
+    }
+
+    /// Build a `PNG` chunk with the provided type and data; the checksum is not validated
+    fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0, 0, 0, 0]);
+        chunk
+    }
+
+    #[test]
+    fn can_read_orientation() {
+        let segment = orientation_segment(6);
+        assert_eq!(exif_orientation(&segment[4 + EXIF_HEADER.len()..]), Some(6));
+    }
+
+    #[test]
+    fn can_read_orientation_little_endian() {
+        let tiff = [
+            b'I', b'I', 42, 0, 8, 0, 0, 0, // Header
+            1, 0, // Entry count
+            0x12, 0x01, 3, 0, 1, 0, 0, 0, 3, 0, 0, 0, // Orientation
+            0, 0, 0, 0, // Next IFD
+        ];
+        assert_eq!(exif_orientation(&tiff), Some(3));
+    }
+
+    #[test]
+    fn can_strip_jpeg() {
+        let mut exif = EXIF_HEADER.to_vec();
+        exif.extend_from_slice(&orientation_segment(6)[4 + EXIF_HEADER.len()..]);
+        // Pretend the EXIF segment also holds GPS data
+        exif.extend_from_slice(b"GPS 37.33, -122.01");
+        let mut xmp = XMP_HEADER.to_vec();
+        xmp.extend_from_slice(b"<x:xmpmeta/>");
+
+        let jfif = segment(0xE0, b"JFIF\0");
+        let mut data = JPEG_SIGNATURE.to_vec();
+        data.extend_from_slice(&jfif);
+        data.extend_from_slice(&segment(0xE1, &exif));
+        data.extend_from_slice(&segment(0xE1, &xmp));
+        data.extend_from_slice(&[0xFF, 0xDA, 1, 2, 3, 0xFF, 0xD9]);
+
+        let mut expected = JPEG_SIGNATURE.to_vec();
+        expected.extend_from_slice(&jfif);
+        expected.extend_from_slice(&orientation_segment(6));
+        expected.extend_from_slice(&[0xFF, 0xDA, 1, 2, 3, 0xFF, 0xD9]);
+
+        assert_eq!(strip_jpeg(&data), Some(expected));
+    }
+
+    #[test]
+    fn can_strip_png() {
+        let header = chunk(b"IHDR", &[0; 13]);
+        let image = chunk(b"IDAT", &[1, 2, 3]);
+        let end = chunk(b"IEND", &[]);
+
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend_from_slice(&header);
+        data.extend_from_slice(&chunk(b"eXIf", b"MM\0*"));
+        data.extend_from_slice(&chunk(b"iTXt", b"XML:com.adobe.xmp"));
+        data.extend_from_slice(&image);
+        data.extend_from_slice(&end);
+
+        let mut expected = PNG_SIGNATURE.to_vec();
+        expected.extend_from_slice(&header);
+        expected.extend_from_slice(&image);
+        expected.extend_from_slice(&end);
+
+        assert_eq!(strip_png(&data), Some(expected));
+    }
+
+    #[test]
+    fn cant_strip_truncated_jpeg() {
+        let mut data = JPEG_SIGNATURE.to_vec();
+        data.extend_from_slice(&[0xFF, 0xE1, 0x10]);
+
+        assert_eq!(strip_jpeg(&data), None);
+    }
+}
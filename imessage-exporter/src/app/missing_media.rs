@@ -0,0 +1,135 @@
+/*!
+ Contains logic for writing a machine-readable list of the attachments whose files are not on disk.
+
+ When Messages in iCloud is enabled, older attachments are removed from the device to save space
+ while their rows stay in the database, so an export shows them as missing until they are downloaded again.
+*/
+
+use std::{
+    cell::{Cell, RefCell},
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+
+use crate::app::{error::RuntimeError, sanitizers::sanitize_csv};
+
+/// Name of the missing media report, written to the root of the export directory
+pub const MISSING_MEDIA_FILENAME: &str = "missing_media.csv";
+/// Column names of the missing media report
+const MISSING_MEDIA_HEADER: &str =
+    "message_guid,chat,date,original_path,transfer_name,mime_type,bytes";
+
+/// Represents a single attachment whose file was not found
+pub struct MissingMediaEntry<'a> {
+    /// The GUID of the message the attachment was sent with
+    pub message_guid: &'a str,
+    /// The name of the conversation, as used for its exported file
+    pub chat: &'a str,
+    /// The date the message was sent
+    pub date: &'a str,
+    /// Where Messages expects the attachment to be stored
+    pub original_path: &'a Path,
+    /// The name of the file when it was sent or received
+    pub transfer_name: &'a str,
+    /// The MIME type of the attachment
+    pub mime_type: &'a str,
+    /// The size of the attachment, as recorded in the database
+    pub bytes: u64,
+}
+
+/// Writes a CSV row for each attachment whose file is missing during an export
+pub struct MissingMediaReport {
+    /// Where the report is written
+    path: PathBuf,
+    /// Where rows are written
+    writer: RefCell<BufWriter<File>>,
+    /// The number of missing attachments written to the report
+    missing: Cell<usize>,
+    /// The number of bytes referenced by the missing attachments
+    bytes: Cell<u64>,
+}
+
+impl MissingMediaReport {
+    /// Create the report file in the export directory and write its header
+    pub fn new(export_path: &Path) -> Result<Self, RuntimeError> {
+        let path = export_path.join(MISSING_MEDIA_FILENAME);
+        let mut writer = BufWriter::new(File::create(&path).map_err(RuntimeError::DiskError)?);
+        writeln!(writer, "{MISSING_MEDIA_HEADER}").map_err(RuntimeError::DiskError)?;
+        Ok(Self {
+            path,
+            writer: RefCell::new(writer),
+            missing: Cell::new(0),
+            bytes: Cell::new(0),
+        })
+    }
+
+    /// Add a missing attachment to the report
+    pub fn record(&self, entry: &MissingMediaEntry) {
+        self.missing.set(self.missing.get() + 1);
+        self.bytes.set(self.bytes.get() + entry.bytes);
+        if let Err(why) = writeln!(self.writer.borrow_mut(), "{}", format_row(entry)) {
+            warn!("Unable to write to the missing media report: {why}");
+        }
+    }
+
+    /// Print a summary of the missing attachments
+    pub fn report(&self) {
+        if self.missing.get() > 0 {
+            info!(
+                "{} attachments were not found on disk; they are listed in {}",
+                self.missing.get(),
+                self.path.display()
+            );
+        }
+    }
+}
+
+impl Drop for MissingMediaReport {
+    fn drop(&mut self) {
+        if let Err(why) = self.writer.get_mut().flush() {
+            warn!("Unable to write the missing media report: {why}");
+        }
+    }
+}
+
+/// Build a CSV row for a missing attachment
+fn format_row(entry: &MissingMediaEntry) -> String {
+    [
+        sanitize_csv(entry.message_guid),
+        sanitize_csv(entry.chat),
+        sanitize_csv(entry.date),
+        sanitize_csv(&entry.original_path.display().to_string()),
+        sanitize_csv(entry.transfer_name),
+        sanitize_csv(entry.mime_type),
+        entry.bytes.to_string().into(),
+    ]
+    .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::app::missing_media::{format_row, MissingMediaEntry};
+
+    #[test]
+    fn can_format_row() {
+        let entry = MissingMediaEntry {
+            message_guid: "ABC-123",
+            chat: "Family",
+            date: "2022-05-17 17:29:42",
+            original_path: Path::new("/Users/me/Library/Messages/Attachments/a/b/IMG_0001.HEIC"),
+            transfer_name: "IMG_0001.HEIC",
+            mime_type: "image/heic",
+            bytes: 2048,
+        };
+
+        assert_eq!(
+            format_row(&entry),
+            "ABC-123,Family,2022-05-17 17:29:42,/Users/me/Library/Messages/Attachments/a/b/IMG_0001.HEIC,IMG_0001.HEIC,image/heic,2048"
+        );
+    }
+}
@@ -1,8 +1,28 @@
 pub mod attachment_manager;
+pub mod attachment_name;
+pub mod chat_type;
 pub mod converter;
+pub mod debug;
+#[cfg(feature = "encrypted-backups")]
+pub mod decrypt;
+pub mod dedupe;
 pub mod error;
 pub mod export_type;
+pub mod list;
+pub mod logger;
+pub mod manifest;
+pub mod merge;
+pub mod metadata;
+pub mod missing_media;
 pub mod options;
+pub mod pagination;
+pub mod picker;
 pub mod progress;
+pub mod roster;
 pub mod runtime;
 pub mod sanitizers;
+pub mod stats;
+pub mod temp_folder;
+pub mod theme;
+pub mod txt_format;
+pub mod verify;
@@ -1,22 +1,41 @@
-use std::path::PathBuf;
+use std::{fs::read_to_string, path::PathBuf};
 
 use clap::{crate_version, Arg, ArgAction, ArgMatches, Command};
+use log::{info, warn};
 
 use imessage_database::{
-    tables::{attachment::DEFAULT_ATTACHMENT_ROOT, table::DEFAULT_PATH_IOS},
+    tables::attachment::DEFAULT_ATTACHMENT_ROOT,
     util::{
+        backup::{is_backup, is_encrypted, latest_backup, sms_db_path},
+        contacts::DEFAULT_ADDRESS_BOOK_PATH_MACOS,
         dirs::{default_db_path, home},
+        phone::{is_country_code, DEFAULT_COUNTRY_CODE},
+        pinned::DEFAULT_PINNING_PATH_MACOS,
         platform::Platform,
-        query_context::QueryContext,
+        query_context::{QueryContext, ATTACHMENT_TYPES},
+        size::parse_file_size,
+        sms_backup::is_sms_backup,
     },
 };
 
 use crate::app::{
-    attachment_manager::AttachmentManager, error::RuntimeError, export_type::ExportType,
+    attachment_manager::AttachmentManager,
+    attachment_name::AttachmentName,
+    chat_type::ChatType,
+    error::RuntimeError,
+    export_type::ExportType,
+    logger::LOG_LEVELS,
+    manifest::MANIFEST_FILENAME,
+    missing_media::MISSING_MEDIA_FILENAME,
+    pagination::Pagination,
+    theme::Theme,
+    txt_format::{TxtFormat, DEFAULT_TXT_FORMAT},
 };
 
 /// Default export directory name
 pub const DEFAULT_OUTPUT_DIR: &str = "imessage_export";
+/// Export path that streams exported data to stdout
+pub const STDOUT_EXPORT_PATH: &str = "-";
 
 // CLI Arg Names
 pub const OPTION_DB_PATH: &str = "db-path";
@@ -32,11 +51,72 @@ pub const OPTION_CUSTOM_NAME: &str = "custom-name";
 pub const OPTION_PLATFORM: &str = "platform";
 pub const OPTION_BYPASS_FREE_SPACE_CHECK: &str = "ignore-disk-warning";
 pub const OPTION_USE_CALLER_ID: &str = "use-caller-id";
+pub const OPTION_STATIC_SITE: &str = "site";
+pub const OPTION_TEMPLATE_DIR: &str = "template-dir";
+pub const OPTION_TXT_FORMAT: &str = "txt-format";
+pub const OPTION_PAGINATE: &str = "paginate";
+pub const OPTION_THEME: &str = "theme";
+pub const OPTION_SINGLE_FILE: &str = "single-file";
+pub const OPTION_CONVERSATION: &str = "conversation";
+pub const OPTION_EXCLUDE: &str = "exclude";
+pub const OPTION_EXCLUDE_FILE: &str = "exclude-file";
+pub const OPTION_SERVICE: &str = "service";
+pub const OPTION_CHAT_TYPE: &str = "chat-type";
+pub const OPTION_ONLY_ATTACHMENTS: &str = "only-attachments";
+pub const OPTION_SKIP_ATTACHMENT_TYPE: &str = "skip-attachment-type";
+pub const OPTION_DIRECTION: &str = "direction";
+pub const OPTION_NO_REACTIONS: &str = "no-reactions";
+pub const OPTION_NO_SYSTEM_MESSAGES: &str = "no-system-messages";
+pub const OPTION_SKIP_SHORT_CODES: &str = "skip-short-codes";
+pub const OPTION_KNOWN_CONTACTS: &str = "known-contacts";
+pub const OPTION_THREAD: &str = "thread";
+pub const OPTION_RECENT: &str = "recent";
+pub const OPTION_PINNED: &str = "pinned";
+pub const OPTION_UNREAD: &str = "unread";
+pub const OPTION_DEDUPE_ATTACHMENTS: &str = "dedupe-attachments";
+pub const OPTION_THUMBNAILS: &str = "thumbnails";
+pub const OPTION_MAX_ATTACHMENT_SIZE: &str = "max-attachment-size";
+pub const OPTION_STRIP_METADATA: &str = "strip-metadata";
+pub const OPTION_ATTACHMENT_MANIFEST: &str = "attachment-manifest";
+pub const OPTION_MISSING_MEDIA_REPORT: &str = "missing-media-report";
+pub const OPTION_ATTACHMENT_NAME: &str = "attachment-name";
+pub const OPTION_VERIFY: &str = "verify";
+pub const OPTION_PINNING_FILE: &str = "pinning-file";
+pub const OPTION_LOG_LEVEL: &str = "log-level";
+pub const OPTION_RECEIPTS: &str = "receipts";
+pub const OPTION_DELETED: &str = "deleted";
+pub const OPTION_CONTACTS: &str = "contacts";
+pub const OPTION_CONTACTS_FILE: &str = "contacts-file";
+pub const OPTION_NAMES_FILE: &str = "names-file";
+pub const OPTION_MERGE_IDENTITIES: &str = "merge-identities";
+pub const OPTION_MERGE_SERVICES: &str = "merge-services";
+pub const OPTION_STATS: &str = "stats";
+pub const OPTION_COUNTRY_CODE: &str = "country-code";
+pub const OPTION_ME_HANDLE: &str = "me-handle";
+pub const OPTION_ME_AVATAR: &str = "me-avatar";
+pub const OPTION_INCLUDE_ORPHANED: &str = "include-orphaned";
+pub const OPTION_PICK: &str = "pick";
+pub const OPTION_BACKUP_PASSWORD: &str = "backup-password";
+pub const OPTION_MERGE_DB: &str = "merge-db";
+
+// CLI Subcommand Names
+pub const COMMAND_DEBUG: &str = "debug";
+pub const COMMAND_TYPEDSTREAM: &str = "typedstream";
+pub const ARG_SOURCE: &str = "source";
+pub const COMMAND_LIST: &str = "list";
+pub const ARG_JSON: &str = "json";
 
 // Other CLI Text
-pub const SUPPORTED_FILE_TYPES: &str = "txt, html";
+pub const SUPPORTED_FILE_TYPES: &str =
+    "txt, html, md, pdf, epub, mht, sqlite, mbox, maildir, ndjson, parquet, obsidian, dayone, matrix, whatsapp, tex, attachments";
 pub const SUPPORTED_PLATFORMS: &str = "macOS, iOS";
-pub const SUPPORTED_ATTACHMENT_MANAGER_MODES: &str = "compatible, efficient, disabled";
+pub const SUPPORTED_ATTACHMENT_MANAGER_MODES: &str = "compatible, efficient, link, disabled";
+pub const SUPPORTED_PAGINATIONS: &str = "year, month, or a number of messages";
+pub const SUPPORTED_THEMES: &str = "auto, light, dark";
+/// Directions of messages that can be exported
+pub const SUPPORTED_DIRECTIONS: &str = "sent, received, all";
+/// Kinds of conversations that can be exported
+pub const SUPPORTED_CHAT_TYPES: &str = "all, group, direct";
 pub const ABOUT: &str = concat!(
     "The `imessage-exporter` binary exports iMessage data to\n",
     "`txt` or `html` formats. It can also run diagnostics\n",
@@ -69,6 +149,72 @@ pub struct Options {
     pub platform: Platform,
     /// If true, disable the free disk space check
     pub ignore_disk_space: bool,
+    /// If true, split HTML exports into a static site with an index and search
+    pub site: bool,
+    /// Directory of templates that replace the default HTML export layout
+    pub template_dir: Option<PathBuf>,
+    /// Format string that lays out messages in TXT exports
+    pub txt_format: Option<String>,
+    /// How HTML exports split each conversation into pages
+    pub paginate: Option<Pagination>,
+    /// Colors used to style HTML and EPUB exports
+    pub theme: Theme,
+    /// If true, embed attachments in HTML exports so each conversation is a single file
+    pub single_file: bool,
+    /// Conversations to export, as chat identifiers, phone numbers, emails, or group chat names
+    pub conversations: Vec<String>,
+    /// Conversations to skip, in the same forms as `conversations`
+    pub excluded: Vec<String>,
+    /// Only export group conversations or only one-on-one conversations
+    pub chat_type: ChatType,
+    /// Kinds of attachments, i.e. `audio`, that are not copied or included in the export
+    pub skipped_attachment_types: Vec<String>,
+    /// If `true`, skip one-on-one conversations with SMS short codes
+    pub skip_short_codes: bool,
+    /// If set, skip one-on-one conversations with anyone not in this list of phone numbers and email addresses
+    pub known_contacts: Option<Vec<String>>,
+    /// If set, only export the conversations pinned in this pinning preferences file
+    pub pinning_file: Option<PathBuf>,
+    /// If true, attachments with identical contents are only stored once
+    pub dedupe_attachments: bool,
+    /// If true, HTML exports show thumbnails of images and videos that link to the full-size files
+    pub thumbnails: bool,
+    /// If set, attachments larger than this many bytes are left out of the export
+    pub max_attachment_size: Option<u64>,
+    /// If true, metadata like GPS coordinates is removed from copied images
+    pub strip_metadata: bool,
+    /// If true, write a CSV file that lists every copied attachment
+    pub attachment_manifest: bool,
+    /// If true, write a CSV file that lists every attachment whose file is not on disk
+    pub missing_media_report: bool,
+    /// Template used to name copied attachments
+    pub attachment_name: Option<String>,
+    /// If true, copied attachments are compared to the originals after the export
+    pub verify: bool,
+    /// If true, the dates messages were delivered and read are shown next to them
+    pub receipts: bool,
+    /// If set, phone numbers and email addresses are shown as the names of the contacts read from this path
+    pub contacts_file: Option<PathBuf>,
+    /// If set, names read from this file are used for phone numbers and email addresses before any contacts
+    pub names_file: Option<PathBuf>,
+    /// If true, conversations with the same people are combined even if they used different handles
+    pub merge_identities: bool,
+    /// If true, conversations with the same handles on different services, like SMS and iMessage, are combined
+    pub merge_services: bool,
+    /// If true, print a report of message and attachment counts and write it to `stats.json`
+    pub stats: bool,
+    /// The country calling code given to phone numbers that were saved without one, i.e. `44`
+    pub country_code: Option<String>,
+    /// The phone number or email address used for the database owner in formats that identify senders by handle
+    pub me_handle: Option<String>,
+    /// An image to show as the database owner's photo
+    pub me_avatar: Option<PathBuf>,
+    /// If true, ask which conversations to export before the export starts
+    pub pick: bool,
+    /// The password used to decrypt an encrypted iOS backup
+    pub backup_password: Option<String>,
+    /// Other databases or iOS backups to merge into the export, deduplicating messages that are in more than one
+    pub merge_databases: Vec<PathBuf>,
 }
 
 impl Options {
@@ -86,6 +232,66 @@ impl Options {
         let use_caller_id = args.get_flag(OPTION_USE_CALLER_ID);
         let platform_type: Option<&String> = args.get_one(OPTION_PLATFORM);
         let ignore_disk_space = args.get_flag(OPTION_BYPASS_FREE_SPACE_CHECK);
+        let site = args.get_flag(OPTION_STATIC_SITE);
+        let template_dir: Option<&String> = args.get_one(OPTION_TEMPLATE_DIR);
+        let txt_format: Option<&String> = args.get_one(OPTION_TXT_FORMAT);
+        let paginate: Option<&String> = args.get_one(OPTION_PAGINATE);
+        let theme: Option<&String> = args.get_one(OPTION_THEME);
+        let single_file = args.get_flag(OPTION_SINGLE_FILE);
+        let conversations: Vec<String> = args
+            .get_many(OPTION_CONVERSATION)
+            .map(|values| values.cloned().collect())
+            .unwrap_or_default();
+        let mut excluded: Vec<String> = args
+            .get_many(OPTION_EXCLUDE)
+            .map(|values| values.cloned().collect())
+            .unwrap_or_default();
+        let exclude_file: Option<&String> = args.get_one(OPTION_EXCLUDE_FILE);
+        let service: Option<&String> = args.get_one(OPTION_SERVICE);
+        let chat_type: Option<&String> = args.get_one(OPTION_CHAT_TYPE);
+        let direction: Option<&String> = args.get_one(OPTION_DIRECTION);
+        let no_reactions = args.get_flag(OPTION_NO_REACTIONS);
+        let no_system_messages = args.get_flag(OPTION_NO_SYSTEM_MESSAGES);
+        let skip_short_codes = args.get_flag(OPTION_SKIP_SHORT_CODES);
+        let known_contacts_file: Option<&String> = args.get_one(OPTION_KNOWN_CONTACTS);
+        let thread: Option<&String> = args.get_one(OPTION_THREAD);
+        let recent: Option<&String> = args.get_one(OPTION_RECENT);
+        let pinned = args.get_flag(OPTION_PINNED);
+        let unread = args.get_flag(OPTION_UNREAD);
+        let dedupe_attachments = args.get_flag(OPTION_DEDUPE_ATTACHMENTS);
+        let thumbnails = args.get_flag(OPTION_THUMBNAILS);
+        let max_attachment_size: Option<&String> = args.get_one(OPTION_MAX_ATTACHMENT_SIZE);
+        let strip_metadata = args.get_flag(OPTION_STRIP_METADATA);
+        let attachment_manifest = args.get_flag(OPTION_ATTACHMENT_MANIFEST);
+        let missing_media_report = args.get_flag(OPTION_MISSING_MEDIA_REPORT);
+        let attachment_name: Option<&String> = args.get_one(OPTION_ATTACHMENT_NAME);
+        let verify = args.get_flag(OPTION_VERIFY);
+        let receipts = args.get_flag(OPTION_RECEIPTS);
+        let deleted = args.get_flag(OPTION_DELETED);
+        let contacts = args.get_flag(OPTION_CONTACTS);
+        let contacts_file: Option<&String> = args.get_one(OPTION_CONTACTS_FILE);
+        let names_file: Option<&String> = args.get_one(OPTION_NAMES_FILE);
+        let merge_identities = args.get_flag(OPTION_MERGE_IDENTITIES);
+        let merge_services = args.get_flag(OPTION_MERGE_SERVICES);
+        let stats = args.get_flag(OPTION_STATS);
+        let country_code: Option<&String> = args.get_one(OPTION_COUNTRY_CODE);
+        let me_handle: Option<&String> = args.get_one(OPTION_ME_HANDLE);
+        let me_avatar: Option<&String> = args.get_one(OPTION_ME_AVATAR);
+        let include_orphaned = args.get_flag(OPTION_INCLUDE_ORPHANED);
+        let pick = args.get_flag(OPTION_PICK);
+        let backup_password: Option<&String> = args.get_one(OPTION_BACKUP_PASSWORD);
+        let merge_databases: Vec<PathBuf> = args
+            .get_many::<String>(OPTION_MERGE_DB)
+            .map(|values| values.map(PathBuf::from).collect())
+            .unwrap_or_default();
+        let pinning_file: Option<&String> = args.get_one(OPTION_PINNING_FILE);
+        let only_attachments: Option<Vec<&str>> = args
+            .get_many::<String>(OPTION_ONLY_ATTACHMENTS)
+            .map(|values| values.map(String::as_str).collect());
+        let skipped_attachment_types: Vec<String> = args
+            .get_many::<String>(OPTION_SKIP_ATTACHMENT_TYPE)
+            .map(|values| values.map(|value| value.to_lowercase()).collect())
+            .unwrap_or_default();
 
         // Build the export type
         let export_type: Option<ExportType> = match export_file_type {
@@ -103,19 +309,30 @@ impl Options {
                 "Option {OPTION_ATTACHMENT_MANAGER} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
             )));
         }
-        if user_export_path.is_some() && export_file_type.is_none() {
+        if user_export_path.is_some() && export_file_type.is_none() && !stats {
             return Err(RuntimeError::InvalidOptions(format!(
-                "Option {OPTION_EXPORT_PATH} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+                "Option {OPTION_EXPORT_PATH} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_STATS}`"
             )));
         }
-        if start_date.is_some() && export_file_type.is_none() {
+        if start_date.is_some() && export_file_type.is_none() && !stats {
             return Err(RuntimeError::InvalidOptions(format!(
-                "Option {OPTION_START_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+                "Option {OPTION_START_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_STATS}`"
             )));
         }
-        if end_date.is_some() && export_file_type.is_none() {
+        if end_date.is_some() && export_file_type.is_none() && !stats {
             return Err(RuntimeError::InvalidOptions(format!(
-                "Option {OPTION_END_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+                "Option {OPTION_END_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_STATS}`"
+            )));
+        }
+        if !conversations.is_empty() && export_file_type.is_none() && !stats {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_CONVERSATION} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_STATS}`"
+            )));
+        }
+        if (!excluded.is_empty() || exclude_file.is_some()) && export_file_type.is_none() && !stats
+        {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_EXCLUDE} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_STATS}`"
             )));
         }
         if use_caller_id && export_file_type.is_none() {
@@ -124,9 +341,131 @@ impl Options {
             )));
         }
 
+        // Ensure only streaming formats are written to stdout
+        let to_stdout = user_export_path.is_some_and(|path| path == STDOUT_EXPORT_PATH);
+        if to_stdout && export_type != Some(ExportType::Ndjson) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "`--{OPTION_EXPORT_PATH} {STDOUT_EXPORT_PATH}` writes to stdout, which requires `--{OPTION_EXPORT_TYPE} ndjson`"
+            )));
+        }
+
+        // Ensure the binary can write the requested format
+        #[cfg(not(feature = "parquet"))]
+        if export_type == Some(ExportType::Parquet) {
+            return Err(RuntimeError::InvalidOptions(String::from(
+                "Parquet exports require building with `--features parquet`",
+            )));
+        }
+
+        // Ensure the binary can decrypt backups
+        #[cfg(not(feature = "encrypted-backups"))]
+        if backup_password.is_some() {
+            return Err(RuntimeError::InvalidOptions(String::from(
+                "Encrypted backups require building with `--features encrypted-backups`",
+            )));
+        }
+
+        // Ensure static sites are only built from HTML exports
+        if site && export_type != Some(ExportType::Html) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_STATIC_SITE} is enabled, which requires `--{OPTION_EXPORT_TYPE} html`"
+            )));
+        }
+
+        // Ensure templates are only used with exports rendered as HTML
+        if template_dir.is_some()
+            && !matches!(export_type, Some(ExportType::Html | ExportType::Mhtml))
+        {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_TEMPLATE_DIR} is enabled, which requires `--{OPTION_EXPORT_TYPE} html` or `--{OPTION_EXPORT_TYPE} mht`"
+            )));
+        }
+
+        // Ensure format strings are only used with TXT exports, and are valid
+        if let Some(format) = txt_format {
+            if export_type != Some(ExportType::Txt) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_TXT_FORMAT} is enabled, which requires `--{OPTION_EXPORT_TYPE} txt`"
+                )));
+            }
+            TxtFormat::parse(format).map_err(|why| {
+                RuntimeError::InvalidOptions(format!(
+                    "Invalid {OPTION_TXT_FORMAT} `{format}`: {why}"
+                ))
+            })?;
+        }
+
+        // Build the pagination, which only applies to HTML exports
+        let paginate = match paginate {
+            Some(pagination) => {
+                if export_type != Some(ExportType::Html) {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "Option {OPTION_PAGINATE} is enabled, which requires `--{OPTION_EXPORT_TYPE} html`"
+                    )));
+                }
+                Some(Pagination::from_cli(pagination).ok_or(RuntimeError::InvalidOptions(format!(
+                    "{pagination} is not a valid pagination! Must be one of <{SUPPORTED_PAGINATIONS}>"
+                )))?)
+            }
+            None => None,
+        };
+
+        // Ensure single file exports are HTML exports that are not split into pages
+        if single_file {
+            if export_type != Some(ExportType::Html) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_SINGLE_FILE} is enabled, which requires `--{OPTION_EXPORT_TYPE} html`"
+                )));
+            }
+            if site || paginate.is_some() {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_SINGLE_FILE} is enabled; {OPTION_STATIC_SITE} and {OPTION_PAGINATE} are disallowed"
+                )));
+            }
+        }
+
+        // Ensure thumbnails are only generated for HTML exports that link to their attachments
+        if thumbnails {
+            if export_type != Some(ExportType::Html) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_THUMBNAILS} is enabled, which requires `--{OPTION_EXPORT_TYPE} html`"
+                )));
+            }
+            if single_file {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_THUMBNAILS} is enabled; {OPTION_SINGLE_FILE} is disallowed"
+                )));
+            }
+        }
+
+        // Build the theme, which only applies to exports styled with CSS
+        let theme = match theme {
+            Some(theme) => {
+                if !matches!(
+                    export_type,
+                    Some(ExportType::Html | ExportType::Mhtml | ExportType::Epub)
+                ) {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "Option {OPTION_THEME} is enabled, which requires `--{OPTION_EXPORT_TYPE} html`, `--{OPTION_EXPORT_TYPE} mht`, or `--{OPTION_EXPORT_TYPE} epub`"
+                    )));
+                }
+                let theme = Theme::from_cli(theme);
+                if let Theme::Custom(path) = &theme {
+                    if !path.is_file() {
+                        return Err(RuntimeError::InvalidOptions(format!(
+                            "Supplied {OPTION_THEME} `{}` is not one of <{SUPPORTED_THEMES}> or a CSS file!",
+                            path.display()
+                        )));
+                    }
+                }
+                theme
+            }
+            None => Theme::default(),
+        };
+
         // Warn the user if they are exporting to a file type for which lazy loading has no effect
         if no_lazy && export_file_type != Some(&"html".to_string()) {
-            eprintln!(
+            warn!(
                 "Option {OPTION_DISABLE_LAZY_LOADING} is enabled, but the format specified is not `html`!"
             );
         }
@@ -137,6 +476,131 @@ impl Options {
                 "Diagnostics are enabled; {OPTION_ATTACHMENT_MANAGER} is disallowed"
             )));
         }
+        if service.is_some() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_SERVICE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if chat_type.is_some() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_CHAT_TYPE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if only_attachments.is_some() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_ONLY_ATTACHMENTS} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if !skipped_attachment_types.is_empty() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_SKIP_ATTACHMENT_TYPE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if direction.is_some() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_DIRECTION} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if no_reactions && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_NO_REACTIONS} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if no_system_messages && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_NO_SYSTEM_MESSAGES} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if skip_short_codes && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_SKIP_SHORT_CODES} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if known_contacts_file.is_some() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_KNOWN_CONTACTS} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if thread.is_some() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_THREAD} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if recent.is_some() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_RECENT} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if (pinned || pinning_file.is_some()) && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_PINNED} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if unread && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_UNREAD} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if (contacts || contacts_file.is_some()) && export_file_type.is_none() && !stats {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_CONTACTS} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_STATS}`"
+            )));
+        }
+        if names_file.is_some() && export_file_type.is_none() && !stats {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_NAMES_FILE} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_STATS}`"
+            )));
+        }
+        if merge_identities && export_file_type.is_none() && !stats {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_MERGE_IDENTITIES} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_STATS}`"
+            )));
+        }
+        if country_code.is_some() && export_file_type.is_none() && !stats {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_COUNTRY_CODE} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_STATS}`"
+            )));
+        }
+        if me_handle.is_some() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_ME_HANDLE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if me_avatar.is_some() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_ME_AVATAR} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if merge_services && export_file_type.is_none() && !stats {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_MERGE_SERVICES} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_STATS}`"
+            )));
+        }
+        if pick && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_PICK} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if include_orphaned && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_INCLUDE_ORPHANED} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if deleted && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_DELETED} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if max_attachment_size.is_some() && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_MAX_ATTACHMENT_SIZE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
+        if missing_media_report && export_file_type.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_MISSING_MEDIA_REPORT} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            )));
+        }
         if diagnostic && user_export_path.is_some() {
             return Err(RuntimeError::InvalidOptions(format!(
                 "Diagnostics are enabled; {OPTION_EXPORT_PATH} is disallowed"
@@ -163,6 +627,18 @@ impl Options {
             )));
         }
 
+        // Ensure that if stats are enabled, nothing is exported
+        if stats && diagnostic {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Stats are enabled; {OPTION_DIAGNOSTIC} is disallowed"
+            )));
+        }
+        if stats && export_file_type.is_some() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Stats are enabled; {OPTION_EXPORT_TYPE} is disallowed"
+            )));
+        }
+
         // Ensure that there are no custom name conflicts
         if custom_name.is_some() && use_caller_id {
             return Err(RuntimeError::InvalidOptions(format!(
@@ -170,6 +646,47 @@ impl Options {
             )));
         }
 
+        // Build the kind of conversations to export
+        let chat_type = match chat_type {
+            Some(chat_type) => ChatType::from_cli(chat_type).ok_or(RuntimeError::InvalidOptions(
+                format!(
+                    "{chat_type} is not a valid {OPTION_CHAT_TYPE}! Must be one of <{SUPPORTED_CHAT_TYPES}>"
+                ),
+            ))?,
+            None => ChatType::default(),
+        };
+
+        // Read the exclusion file, which lists a conversation on each line
+        if let Some(path) = exclude_file {
+            excluded.extend(read_list(path, OPTION_EXCLUDE_FILE)?);
+        }
+
+        // Pins are read from the local preferences unless another file is given
+        let pinning_file = match (pinning_file, pinned) {
+            (Some(path), _) => Some(PathBuf::from(path)),
+            (None, true) => Some(PathBuf::from(format!(
+                "{}/{DEFAULT_PINNING_PATH_MACOS}",
+                home()
+            ))),
+            (None, false) => None,
+        };
+
+        // Contacts are read from the local Contacts database unless another path is given
+        let contacts_file = match (contacts_file, contacts) {
+            (Some(path), _) => Some(PathBuf::from(path)),
+            (None, true) => Some(PathBuf::from(format!(
+                "{}/{DEFAULT_ADDRESS_BOOK_PATH_MACOS}",
+                home()
+            ))),
+            (None, false) => None,
+        };
+
+        // Read the list of known contacts, which lists a phone number or email address on each line
+        let known_contacts = match known_contacts_file {
+            Some(path) => Some(read_list(path, OPTION_KNOWN_CONTACTS)?),
+            None => None,
+        };
+
         // Build query context
         let mut query_context = QueryContext::default();
         if let Some(start) = start_date {
@@ -182,6 +699,99 @@ impl Options {
                 return Err(RuntimeError::InvalidOptions(format!("{why}")));
             }
         }
+        if let Some(service) = service.filter(|service| !service.eq_ignore_ascii_case("all")) {
+            if let Err(why) = query_context.set_service(service) {
+                return Err(RuntimeError::InvalidOptions(format!("{why}")));
+            }
+        }
+        if let Some(guid) = thread {
+            if let Err(why) = query_context.set_thread(guid) {
+                return Err(RuntimeError::InvalidOptions(format!("{why}")));
+            }
+        }
+        if let Some(recent) = recent {
+            match recent.parse::<usize>() {
+                Ok(count) if count > 0 => query_context.set_recent(count),
+                _ => {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "{recent} is not a valid {OPTION_RECENT}! Must be a number greater than 0"
+                    )))
+                }
+            }
+        }
+        query_context.set_skip_tapbacks(no_reactions);
+        query_context.set_skip_announcements(no_system_messages);
+        if let Some(direction) = direction {
+            match direction.to_lowercase().as_str() {
+                "sent" => query_context.set_from_me(true),
+                "received" => query_context.set_from_me(false),
+                "all" => {}
+                _ => {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "{direction} is not a valid {OPTION_DIRECTION}! Must be one of <{SUPPORTED_DIRECTIONS}>"
+                    )))
+                }
+            }
+        }
+        if unread {
+            if query_context.from_me == Some(true) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_UNREAD} only includes received messages, so it cannot be used with `--{OPTION_DIRECTION} sent`"
+                )));
+            }
+            query_context.set_unread(true);
+        }
+        if deleted {
+            query_context.set_deleted(true);
+        }
+        if include_orphaned {
+            query_context.set_include_orphaned(true);
+        }
+        if let Some(only_attachments) = &only_attachments {
+            if let Err(why) = query_context.set_attachment_types(only_attachments) {
+                return Err(RuntimeError::InvalidOptions(format!("{why}")));
+            }
+        }
+        if let Some(skipped) = skipped_attachment_types
+            .iter()
+            .find(|skipped| !ATTACHMENT_TYPES.contains(&skipped.as_str()))
+        {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "{skipped} is not a valid {OPTION_SKIP_ATTACHMENT_TYPE}! Must be one of <{}>",
+                ATTACHMENT_TYPES.join(", ")
+            )));
+        }
+        if let Some(attachment_types) = &query_context.attachment_types {
+            if let Some(skipped) = skipped_attachment_types
+                .iter()
+                .find(|skipped| attachment_types.contains(skipped.as_str()))
+            {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "`{skipped}` cannot be passed to both {OPTION_ONLY_ATTACHMENTS} and {OPTION_SKIP_ATTACHMENT_TYPE}"
+                )));
+            }
+        }
+        if let Some(code) = country_code {
+            if !is_country_code(code) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "{code} is not a valid {OPTION_COUNTRY_CODE}! Must be a country calling code like `1` or `44`"
+                )));
+            }
+        }
+
+        let max_attachment_size = match max_attachment_size {
+            Some(size) => Some(parse_file_size(size).ok_or(RuntimeError::InvalidOptions(
+                format!("{size} is not a valid {OPTION_MAX_ATTACHMENT_SIZE}! Must be a size like `25MB`"),
+            ))?),
+            None => None,
+        };
+        if let (Some(start), Some(end)) = (query_context.start, query_context.end) {
+            if start >= end {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_START_DATE} must be before {OPTION_END_DATE}"
+                )));
+            }
+        }
 
         // We have to allocate a PathBuf here because it can be created from data owned by this function in the default state
         let db_path = match user_path {
@@ -189,6 +799,26 @@ impl Options {
             None => default_db_path(),
         };
 
+        // A folder of backups, i.e. `MobileSync/Backup`, is read from the most recently updated backup in it
+        let db_path = match db_path.is_dir() && !is_backup(&db_path) {
+            true => match latest_backup(&db_path) {
+                Some(backup) => {
+                    info!("Reading the most recent backup, {}", backup.display());
+                    backup
+                }
+                None => db_path,
+            },
+            false => db_path,
+        };
+
+        // Android backups are not Messages databases, so they can only be merged into one
+        if is_sms_backup(&db_path) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "{} is an Android SMS backup! Pass it to `--{OPTION_MERGE_DB}` along with a Messages database",
+                db_path.display()
+            )));
+        }
+
         // Build the Platform
         let platform = match platform_type {
             Some(platform_str) => Platform::from_cli(platform_str).ok_or(
@@ -208,9 +838,66 @@ impl Options {
             }
         };
 
+        // Validate that the database owner's photo exists, if provided
+        if let Some(path) = me_avatar {
+            if !PathBuf::from(path).is_file() {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Supplied {OPTION_ME_AVATAR} `{path}` does not exist!"
+                )));
+            }
+        }
+
+        // Validate that the template directory exists, if provided
+        let template_dir = match template_dir {
+            Some(path) => {
+                let template_path = PathBuf::from(path);
+                if !template_path.is_dir() {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "Supplied {OPTION_TEMPLATE_DIR} `{path}` is not a directory!"
+                    )));
+                }
+                Some(template_path)
+            }
+            None => None,
+        };
+
+        // Ensure encrypted backups are only read with their password
+        let backup_password = match (
+            platform == Platform::iOS && is_encrypted(&db_path),
+            backup_password,
+        ) {
+            (true, None) => {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "The backup at {} is encrypted! Pass its password with `--{OPTION_BACKUP_PASSWORD}`",
+                    db_path.display()
+                )));
+            }
+            (false, Some(_)) => {
+                warn!("Option {OPTION_BACKUP_PASSWORD} is enabled, but the backup is not encrypted, so the password will have no effect!");
+                None
+            }
+            (_, password) => password.cloned(),
+        };
+
+        // Validate that the databases to merge exist and can be read without a password
+        for path in &merge_databases {
+            if !path.exists() {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Supplied {OPTION_MERGE_DB} `{}` does not exist!",
+                    path.display()
+                )));
+            }
+            if is_backup(path) && is_encrypted(path) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "The backup at {} is encrypted! Only the backup passed to `--{OPTION_DB_PATH}` can be decrypted",
+                    path.display()
+                )));
+            }
+        }
+
         // Warn the user that custom attachment roots have no effect on iOS backups
         if attachment_root.is_some() && platform == Platform::iOS {
-            eprintln!(
+            warn!(
                 "Option {OPTION_ATTACHMENT_ROOT} is enabled, but the platform is {}, so the root will have no effect!", Platform::iOS
             );
         }
@@ -225,33 +912,141 @@ impl Options {
             None => AttachmentManager::default(),
         };
 
-        // Validate the provided export path
-        let export_path = validate_path(user_export_path, &export_type.as_ref())?;
-
-        Ok(Options {
-            db_path,
-            attachment_root: attachment_root.cloned(),
-            attachment_manager: attachment_manager_mode,
-            diagnostic,
-            export_type,
-            export_path,
-            query_context,
-            no_lazy,
-            custom_name: custom_name.cloned(),
-            use_caller_id,
-            platform,
-            ignore_disk_space,
-        })
-    }
-
-    /// Generate a path to the database based on the currently selected platform
-    pub fn get_db_path(&self) -> PathBuf {
-        match self.platform {
-            Platform::iOS => self.db_path.join(DEFAULT_PATH_IOS),
-            Platform::macOS => self.db_path.clone(),
+        // Attachments are copied next to the export, so there is nowhere to put them when streaming
+        if to_stdout && !matches!(attachment_manager_mode, AttachmentManager::Disabled) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "`--{OPTION_EXPORT_PATH} {STDOUT_EXPORT_PATH}` writes to stdout; {OPTION_ATTACHMENT_MANAGER} is disallowed"
+            )));
         }
-    }
-}
+
+        // Attachments-only exports have nothing to write unless attachments are copied
+        if export_type == Some(ExportType::Attachments)
+            && matches!(attachment_manager_mode, AttachmentManager::Disabled)
+        {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "`--{OPTION_EXPORT_TYPE} attachments` requires `--{OPTION_ATTACHMENT_MANAGER}`"
+            )));
+        }
+
+        // There is nothing to deduplicate unless attachments are copied
+        if dedupe_attachments && matches!(attachment_manager_mode, AttachmentManager::Disabled) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_DEDUPE_ATTACHMENTS} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}`"
+            )));
+        }
+
+        // Thumbnails are written next to the copied attachments
+        if thumbnails && matches!(attachment_manager_mode, AttachmentManager::Disabled) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_THUMBNAILS} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}`"
+            )));
+        }
+
+        // Metadata is only removed from the copies, never from the originals
+        if strip_metadata && matches!(attachment_manager_mode, AttachmentManager::Disabled) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_STRIP_METADATA} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}`"
+            )));
+        }
+        if strip_metadata && matches!(attachment_manager_mode, AttachmentManager::Link) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_STRIP_METADATA} cannot be used with `--{OPTION_ATTACHMENT_MANAGER} link`, which shares files with the originals"
+            )));
+        }
+
+        // The manifest only lists attachments that were copied
+        if attachment_manifest && matches!(attachment_manager_mode, AttachmentManager::Disabled) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_ATTACHMENT_MANIFEST} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}`"
+            )));
+        }
+
+        // Names only apply to copied attachments, and must be valid templates
+        if let Some(template) = attachment_name {
+            if matches!(attachment_manager_mode, AttachmentManager::Disabled) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_ATTACHMENT_NAME} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}`"
+                )));
+            }
+            AttachmentName::parse(template).map_err(|why| {
+                RuntimeError::InvalidOptions(format!(
+                    "Invalid {OPTION_ATTACHMENT_NAME} `{template}`: {why}"
+                ))
+            })?;
+        }
+
+        // Only copies can be verified
+        if verify && matches!(attachment_manager_mode, AttachmentManager::Disabled) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_VERIFY} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}`"
+            )));
+        }
+
+        // Validate the provided export path
+        let export_path = validate_path(user_export_path, &export_type.as_ref())?;
+
+        Ok(Options {
+            db_path,
+            attachment_root: attachment_root.cloned(),
+            attachment_manager: attachment_manager_mode,
+            diagnostic,
+            export_type,
+            export_path,
+            query_context,
+            no_lazy,
+            custom_name: custom_name.cloned(),
+            use_caller_id,
+            platform,
+            ignore_disk_space,
+            site,
+            template_dir,
+            txt_format: txt_format.cloned(),
+            paginate,
+            theme,
+            single_file,
+            conversations,
+            excluded,
+            chat_type,
+            skipped_attachment_types,
+            skip_short_codes,
+            known_contacts,
+            pinning_file,
+            dedupe_attachments,
+            thumbnails,
+            max_attachment_size,
+            strip_metadata,
+            attachment_manifest,
+            missing_media_report,
+            attachment_name: attachment_name.cloned(),
+            verify,
+            receipts,
+            contacts_file,
+            names_file: names_file.map(PathBuf::from),
+            merge_identities,
+            merge_services,
+            stats,
+            country_code: country_code.map(|code| code.trim_start_matches('+').to_string()),
+            me_handle: me_handle.map(|handle| handle.trim().to_string()),
+            me_avatar: me_avatar.map(PathBuf::from),
+            pick,
+            backup_password,
+            merge_databases,
+        })
+    }
+
+    /// Generate a path to the database based on the currently selected platform
+    pub fn get_db_path(&self) -> PathBuf {
+        match self.platform {
+            Platform::iOS => sms_db_path(&self.db_path),
+            Platform::macOS => self.db_path.clone(),
+        }
+    }
+
+    /// Determine if exported data is streamed to stdout instead of written to the export path
+    pub fn writes_to_stdout(&self) -> bool {
+        self.export_path.as_os_str() == STDOUT_EXPORT_PATH
+    }
+}
 
 /// Ensure export path is empty or does not contain files of the existing export type
 ///
@@ -260,6 +1055,11 @@ fn validate_path(
     export_path: Option<&String>,
     export_type: &Option<&ExportType>,
 ) -> Result<PathBuf, RuntimeError> {
+    // Streamed exports are not written to a directory
+    if export_path.is_some_and(|path| path == STDOUT_EXPORT_PATH) {
+        return Ok(PathBuf::from(STDOUT_EXPORT_PATH));
+    }
+
     // Build a path from the user-provided data or the default location
     let resolved_path =
         PathBuf::from(export_path.unwrap_or(&format!("{}/{DEFAULT_OUTPUT_DIR}", home())));
@@ -301,8 +1101,21 @@ fn validate_path(
     Ok(resolved_path)
 }
 
+/// Read a file that lists one value on each line, ignoring blank lines and lines starting with `#`
+fn read_list(path: &str, option: &str) -> Result<Vec<String>, RuntimeError> {
+    let contents = read_to_string(path).map_err(|why| {
+        RuntimeError::InvalidOptions(format!("Unable to read {option} `{path}`: {why}"))
+    })?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
 /// Build the command line argument parser
-fn get_command() -> Command {
+pub fn get_command() -> Command {
     Command::new("iMessage Exporter")
         .version(crate_version!())
         .about(ABOUT)
@@ -327,7 +1140,7 @@ fn get_command() -> Command {
             Arg::new(OPTION_ATTACHMENT_MANAGER)
             .short('c')
             .long(OPTION_ATTACHMENT_MANAGER)
-            .help(format!("Specify an optional method to use when copying message attachments\nCompatible will convert HEIC files to JPEG\nEfficient will copy files without converting anything\nIf omitted, the default is `{}`\nImageMagick is required to convert images on non-macOS platforms.\n", AttachmentManager::default()))
+            .help(format!("Specify an optional method to use when copying message attachments\nCompatible will convert HEIC and HEIF files to JPEG and audio messages to M4A\nEfficient will copy files without converting anything\nLink will hard link files into the export without copying their data\nIf omitted, the default is `{}`\nImageMagick is required to convert images on non-macOS platforms.\nffmpeg is required to convert audio messages.\n", AttachmentManager::default()))
             .display_order(2)
             .value_name(SUPPORTED_ATTACHMENT_MANAGER_MODES),
         )
@@ -335,9 +1148,10 @@ fn get_command() -> Command {
             Arg::new(OPTION_DB_PATH)
                 .short('p')
                 .long(OPTION_DB_PATH)
-                .help(format!("Specify an optional custom path for the iMessage database location\nFor macOS, specify a path to a `chat.db` file\nFor iOS, specify a path to the root of an unencrypted backup directory\nIf omitted, the default directory is {}\n", default_db_path().display()))
+                .help(format!("Specify an optional custom path for the iMessage database location\nFor macOS, specify a path to a `chat.db` file\nFor iOS, specify a path to the root of a backup directory, or to a folder of backups to read the most recent one\nIf omitted, the default directory is {}\n", default_db_path().display()))
                 .display_order(3)
-                .value_name("path/to/source"),
+                .value_name("path/to/source")
+                .global(true),
         )
         .arg(
             Arg::new(OPTION_ATTACHMENT_ROOT)
@@ -353,13 +1167,14 @@ fn get_command() -> Command {
             .long(OPTION_PLATFORM)
             .help("Specify the platform the database was created on\nIf omitted, the platform type is determined automatically\n")
             .display_order(5)
-            .value_name(SUPPORTED_PLATFORMS),
+            .value_name(SUPPORTED_PLATFORMS)
+            .global(true),
         )
         .arg(
             Arg::new(OPTION_EXPORT_PATH)
                 .short('o')
                 .long(OPTION_EXPORT_PATH)
-                .help(format!("Specify an optional custom directory for outputting exported data\nIf omitted, the default directory is {}/{DEFAULT_OUTPUT_DIR}\n", home()))
+                .help(format!("Specify an optional custom directory for outputting exported data\nIf omitted, the default directory is {}/{DEFAULT_OUTPUT_DIR}\nUse `{STDOUT_EXPORT_PATH}` to stream `ndjson` exports to stdout\n", home()))
                 .display_order(6)
                 .value_name("path/to/save/files"),
         )
@@ -410,60 +1225,2026 @@ fn get_command() -> Command {
                 .action(ArgAction::SetTrue)
                 .display_order(12)
         )
+        .arg(
+            Arg::new(OPTION_STATIC_SITE)
+                .short('w')
+                .long(OPTION_STATIC_SITE)
+                .help("Split HTML exports into a static site\nEach conversation gets a page per year, linked from an index page with message search\n")
+                .action(ArgAction::SetTrue)
+                .display_order(13)
+        )
+        .arg(
+            Arg::new(OPTION_TEMPLATE_DIR)
+                .short('t')
+                .long(OPTION_TEMPLATE_DIR)
+                .help("Specify a directory of templates that replace the default HTML and MHTML export layout\nMay contain `conversation.html`, `message.html`, and `style.css`\n")
+                .value_name("path/to/templates")
+                .display_order(14)
+        )
+        .arg(
+            Arg::new(OPTION_TXT_FORMAT)
+                .long(OPTION_TXT_FORMAT)
+                .help(format!("Specify how messages are laid out in TXT exports\nPlaceholders are {{date}}, {{date:<strftime format>}}, {{sender}}, and {{text}}\nDefaults to `{}`\n", DEFAULT_TXT_FORMAT.replace('\n', "\\n")))
+                .value_name("format")
+                .display_order(15)
+        )
+        .arg(
+            Arg::new(OPTION_PAGINATE)
+                .short('g')
+                .long(OPTION_PAGINATE)
+                .help(format!("Split each conversation in HTML exports into pages, linked to each other\nPages can hold a year, a month, or a number of messages: <{SUPPORTED_PAGINATIONS}>\n"))
+                .value_name("year|month|count")
+                .display_order(16)
+        )
+        .arg(
+            Arg::new(OPTION_THEME)
+                .long(OPTION_THEME)
+                .help(format!("Specify the colors of HTML, MHTML, and EPUB exports: <{SUPPORTED_THEMES}>\n`auto` follows the reader's system setting\nA path to a CSS file overrides the colors of the `auto` theme\nIf omitted, the default is `auto`\n"))
+                .value_name("theme|path/to/palette.css")
+                .display_order(17)
+        )
+        .arg(
+            Arg::new(OPTION_SINGLE_FILE)
+                .long(OPTION_SINGLE_FILE)
+                .help("Embed attachments in HTML exports as data URIs\nEach conversation is written to one file that does not need the attachments directory\n")
+                .action(ArgAction::SetTrue)
+                .display_order(18)
+        )
+        .arg(
+            Arg::new(OPTION_CONVERSATION)
+                .long(OPTION_CONVERSATION)
+                .help("Only export the specified conversation\nMay be a chat identifier, a phone number or email address, or a group chat name\nRepeat to export more than one conversation\n")
+                .value_name("identifier|name")
+                .action(ArgAction::Append)
+                .display_order(19)
+        )
+        .arg(
+            Arg::new(OPTION_EXCLUDE)
+                .long(OPTION_EXCLUDE)
+                .help("Skip the specified conversation, including its attachments\nAccepts the same values as --conversation\nRepeat to skip more than one conversation\n")
+                .value_name("identifier|name")
+                .action(ArgAction::Append)
+                .display_order(20)
+        )
+        .arg(
+            Arg::new(OPTION_EXCLUDE_FILE)
+                .long(OPTION_EXCLUDE_FILE)
+                .help("Skip every conversation listed in a file, one per line\nBlank lines and lines starting with # are ignored\n")
+                .value_name("path/to/exclusions.txt")
+                .display_order(21)
+        )
+        .arg(
+            Arg::new(OPTION_SERVICE)
+                .long(OPTION_SERVICE)
+                .help("Only export messages sent with the specified service\nMessages without a service use the service of their conversation\nIf omitted, messages from every service are exported\n")
+                .value_name("imessage|sms|all")
+                .display_order(22)
+        )
+        .arg(
+            Arg::new(OPTION_CHAT_TYPE)
+                .long(OPTION_CHAT_TYPE)
+                .help(format!("Only export conversations of the specified kind: <{SUPPORTED_CHAT_TYPES}>\n`group` exports group chats and `direct` exports one-on-one conversations\nIf omitted, the default is `all`\n"))
+                .value_name(SUPPORTED_CHAT_TYPES)
+                .display_order(23)
+        )
+        .arg(
+            Arg::new(OPTION_ONLY_ATTACHMENTS)
+                .long(OPTION_ONLY_ATTACHMENTS)
+                .help(format!("Only export messages with an attachment of the specified kinds: <{}>\nAttachments of other kinds are not copied or included\nSeparate kinds with commas, i.e. `image,video`\n", ATTACHMENT_TYPES.join(", ")))
+                .value_name("kind,kind")
+                .value_delimiter(',')
+                .display_order(24)
+        )
+        .arg(
+            Arg::new(OPTION_SKIP_ATTACHMENT_TYPE)
+                .long(OPTION_SKIP_ATTACHMENT_TYPE)
+                .help(format!("Do not copy or include attachments of the specified kinds: <{}>\nMessages are still exported\nSeparate kinds with commas, i.e. `audio,application`\n", ATTACHMENT_TYPES.join(", ")))
+                .value_name("kind,kind")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .display_order(25)
+        )
+        .arg(
+            Arg::new(OPTION_DIRECTION)
+                .long(OPTION_DIRECTION)
+                .help(format!("Only export messages in the specified direction: <{SUPPORTED_DIRECTIONS}>\n`sent` exports messages you sent and `received` exports messages sent to you\nIf omitted, the default is `all`\n"))
+                .value_name(SUPPORTED_DIRECTIONS)
+                .display_order(26)
+        )
+        .arg(
+            Arg::new(OPTION_NO_REACTIONS)
+                .long(OPTION_NO_REACTIONS)
+                .help("Do not include tapbacks or the stickers placed on messages\n")
+                .action(ArgAction::SetTrue)
+                .display_order(27)
+        )
+        .arg(
+            Arg::new(OPTION_NO_SYSTEM_MESSAGES)
+                .long(OPTION_NO_SYSTEM_MESSAGES)
+                .help("Do not include system messages, like group renames, participant changes, and location sharing notices\n")
+                .action(ArgAction::SetTrue)
+                .display_order(28)
+        )
+        .arg(
+            Arg::new(OPTION_SKIP_SHORT_CODES)
+                .long(OPTION_SKIP_SHORT_CODES)
+                .help("Skip one-on-one conversations with SMS short codes, i.e. 5 or 6 digit numbers that send verification codes\n")
+                .action(ArgAction::SetTrue)
+                .display_order(29)
+        )
+        .arg(
+            Arg::new(OPTION_KNOWN_CONTACTS)
+                .long(OPTION_KNOWN_CONTACTS)
+                .help("Skip one-on-one conversations with anyone not listed in a file\nList a phone number or email address on each line\nBlank lines and lines starting with # are ignored\n")
+                .value_name("path/to/contacts.txt")
+                .display_order(30)
+        )
+        .arg(
+            Arg::new(OPTION_THREAD)
+                .long(OPTION_THREAD)
+                .help("Only export the reply thread that contains the message with the specified GUID\nIncludes the first message in the thread and every reply to it\n")
+                .value_name("guid")
+                .display_order(31)
+        )
+        .arg(
+            Arg::new(OPTION_RECENT)
+                .long(OPTION_RECENT)
+                .help("Only export the most recent messages in each conversation\nTapbacks count toward the limit\n")
+                .value_name("count")
+                .display_order(32)
+        )
+        .arg(
+            Arg::new(OPTION_PINNED)
+                .long(OPTION_PINNED)
+                .help(format!("Only export the conversations pinned in Messages\nPins are read from ~/{DEFAULT_PINNING_PATH_MACOS}\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(33)
+        )
+        .arg(
+            Arg::new(OPTION_PINNING_FILE)
+                .long(OPTION_PINNING_FILE)
+                .help(format!("Read pinned conversations from a copy of the pinning preferences\nImplies --{OPTION_PINNED}\n"))
+                .value_name("path/to/com.apple.messages.pinning.plist")
+                .display_order(34)
+        )
+        .arg(
+            Arg::new(OPTION_UNREAD)
+                .long(OPTION_UNREAD)
+                .help("Only export received messages that have not been read\n")
+                .action(ArgAction::SetTrue)
+                .display_order(35)
+        )
+        .arg(
+            Arg::new(OPTION_DEDUPE_ATTACHMENTS)
+                .long(OPTION_DEDUPE_ATTACHMENTS)
+                .help(format!("Store attachments with identical contents only once\nDuplicates are hard linked to the first copy when the file system allows it\nRequires `--{OPTION_ATTACHMENT_MANAGER}`\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(36)
+        )
+        .arg(
+            Arg::new(OPTION_THUMBNAILS)
+                .long(OPTION_THUMBNAILS)
+                .help(format!("Show small thumbnails of images, videos, and documents in HTML exports that link to the full-size files\nffmpeg is required to generate video thumbnails\nRequires `--{OPTION_ATTACHMENT_MANAGER}`\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(37)
+        )
+        .arg(
+            Arg::new(OPTION_MAX_ATTACHMENT_SIZE)
+                .long(OPTION_MAX_ATTACHMENT_SIZE)
+                .help("Leave out attachments larger than this size, i.e. `25MB`\nTheir messages are still exported, showing the attachment's name instead\n")
+                .value_name("size")
+                .display_order(38)
+        )
+        .arg(
+            Arg::new(OPTION_STRIP_METADATA)
+                .long(OPTION_STRIP_METADATA)
+                .help(format!("Remove metadata, like GPS coordinates, from copied JPEG and PNG images\nRequires `--{OPTION_ATTACHMENT_MANAGER}`\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(39)
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_MANIFEST)
+                .long(OPTION_ATTACHMENT_MANIFEST)
+                .help(format!("Write {MANIFEST_FILENAME}, which lists every copied attachment\nEach row has the message GUID, conversation, original and exported paths, MIME type, size, and SHA-1 hash\nRequires `--{OPTION_ATTACHMENT_MANAGER}`\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(40)
+        )
+        .arg(
+            Arg::new(OPTION_MISSING_MEDIA_REPORT)
+                .long(OPTION_MISSING_MEDIA_REPORT)
+                .help(format!("Write {MISSING_MEDIA_FILENAME}, which lists every attachment whose file is not on disk\nThese are usually attachments Messages in iCloud removed to save space\nEach row has the message GUID, conversation, date, expected path, file name, MIME type, and size\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(41)
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_NAME)
+                .long(OPTION_ATTACHMENT_NAME)
+                .help(format!("Template used to name copied attachments\nPlaceholders are {{rowid}}, {{date}}, {{date:<strftime format>}}, {{sender}}, and {{original_name}}\nThe original extension is always added, and names used more than once in a conversation get a number\nIf omitted, the default is `{{rowid}}`\nRequires `--{OPTION_ATTACHMENT_MANAGER}`\n"))
+                .value_name("template")
+                .display_order(42)
+        )
+        .arg(
+            Arg::new(OPTION_VERIFY)
+                .long(OPTION_VERIFY)
+                .help(format!("Check every copied attachment against its original after the export\nReports copies that are missing, truncated, or do not match\nConverted copies are only checked for content\nRequires `--{OPTION_ATTACHMENT_MANAGER}`\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(43)
+        )
+        .arg(
+            Arg::new(OPTION_LOG_LEVEL)
+                .long(OPTION_LOG_LEVEL)
+                .help(format!("How much to report while running: <{}>\nLog messages are written to stderr, so they never mix with data exported to stdout\nIf omitted, the default is `info`, which shows progress and warnings\n", LOG_LEVELS.join(", ")))
+                .value_name("level")
+                .value_parser(LOG_LEVELS)
+                .ignore_case(true)
+                .display_order(44)
+        )
+        .arg(
+            Arg::new(OPTION_RECEIPTS)
+                .long(OPTION_RECEIPTS)
+                .help("Show when each message was delivered and read next to its timestamp\nMessages only store one read date, so messages sent to group chats are not marked as read by each participant\n")
+                .action(ArgAction::SetTrue)
+                .display_order(45)
+        )
+        .arg(
+            Arg::new(OPTION_DELETED)
+                .long(OPTION_DELETED)
+                .help("Only export messages that were deleted but can still be recovered\nDeleted messages stay in the database for up to 30 days and are annotated in the export\n")
+                .action(ArgAction::SetTrue)
+                .display_order(46)
+        )
+        .arg(
+            Arg::new(OPTION_CONTACTS)
+                .long(OPTION_CONTACTS)
+                .help(format!("Show the names of contacts instead of their phone numbers and email addresses\nNames are read from ~/{DEFAULT_ADDRESS_BOOK_PATH_MACOS}\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(47)
+        )
+        .arg(
+            Arg::new(OPTION_CONTACTS_FILE)
+                .long(OPTION_CONTACTS_FILE)
+                .help(format!("Read contact names from a Contacts database, a Contacts archive (.abbu), a vCard file, or a directory of vCard files\nImplies --{OPTION_CONTACTS}\n"))
+                .value_name("path/to/contacts")
+                .display_order(48)
+        )
+        .arg(
+            Arg::new(OPTION_NAMES_FILE)
+                .long(OPTION_NAMES_FILE)
+                .help("Show names from a file instead of phone numbers and email addresses\nList a phone number or email address and a name on each line, separated by a comma (CSV) or an equals sign (TOML)\nNames in this file take precedence over contacts, and handles given the same name are treated as one person\n")
+                .value_name("path/to/names.csv")
+                .display_order(49)
+        )
+        .arg(
+            Arg::new(OPTION_MERGE_IDENTITIES)
+                .long(OPTION_MERGE_IDENTITIES)
+                .help(format!("Combine conversations with the same people into one transcript, even if they used different phone numbers or email addresses\nHandles are matched to people with --{OPTION_CONTACTS} or --{OPTION_NAMES_FILE}\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(50)
+        )
+        .arg(
+            Arg::new(OPTION_MERGE_SERVICES)
+                .long(OPTION_MERGE_SERVICES)
+                .help("Combine the SMS and iMessage conversations with the same phone number or email address into one transcript\nMessages from each service are interleaved in the order they were sent\n")
+                .action(ArgAction::SetTrue)
+                .display_order(51)
+        )
+        .arg(
+            Arg::new(OPTION_STATS)
+                .long(OPTION_STATS)
+                .help(format!("Print message counts per conversation, sender, and year, attachment totals, the busiest days, and average response times, then exit\nThe report is also written to `stats.json` in the --{OPTION_EXPORT_PATH} directory\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(52)
+        )
+        .arg(
+            Arg::new(OPTION_COUNTRY_CODE)
+                .long(OPTION_COUNTRY_CODE)
+                .help(format!("The country calling code of phone numbers that were saved without one, used to match handles like `(555) 867-5309` and `+15558675309`\nIf omitted, the default is `{DEFAULT_COUNTRY_CODE}`\n"))
+                .value_name("code")
+                .display_order(53)
+        )
+        .arg(
+            Arg::new(OPTION_ME_HANDLE)
+                .long(OPTION_ME_HANDLE)
+                .help(format!("The phone number or email address of the database owner, for formats that identify senders by handle, like `mbox` and `matrix`\nUse this with --{OPTION_CUSTOM_NAME} when exporting someone else's messages; with --{OPTION_USE_CALLER_ID}, it replaces the caller ID\n"))
+                .value_name("handle")
+                .display_order(54)
+        )
+        .arg(
+            Arg::new(OPTION_ME_AVATAR)
+                .long(OPTION_ME_AVATAR)
+                .help("An image to show as the database owner's photo at the top of each `html` conversation\n")
+                .value_name("path/to/image")
+                .display_order(55)
+        )
+        .arg(
+            Arg::new(OPTION_INCLUDE_ORPHANED)
+                .long(OPTION_INCLUDE_ORPHANED)
+                .help(format!("Keep messages that do not belong to a conversation when conversations are filtered, i.e. with `--{OPTION_EXCLUDE}`\nThey are written to the `orphaned` file, as they are when nothing is filtered\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(56)
+        )
+        .arg(
+            Arg::new(OPTION_PICK)
+                .long(OPTION_PICK)
+                .help(format!("Choose the conversations to export from a checklist before the export starts\nOnly conversations that match the other conversation filters, like `--{OPTION_EXCLUDE}`, are listed\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(57)
+        )
+        .arg(
+            Arg::new(OPTION_BACKUP_PASSWORD)
+                .long(OPTION_BACKUP_PASSWORD)
+                .help("The password used to decrypt an encrypted iOS backup\nRequires building with `--features encrypted-backups`\nThe Messages data is decrypted to a private temporary folder that is deleted when the export finishes or is interrupted\n")
+                .value_name("password")
+                .display_order(58)
+        )
+        .arg(
+            Arg::new(OPTION_MERGE_DB)
+                .long(OPTION_MERGE_DB)
+                .help(format!("Merge another database into the export, i.e. from an old Mac, an iOS backup, or an Android SMS Backup & Restore XML file\nMessages, conversations, and attachments that are in more than one database are only exported once\nRepeat to merge more than one database; `--{OPTION_DB_PATH}` is read first\n"))
+                .value_name("path/to/source")
+                .action(ArgAction::Append)
+                .display_order(59)
+        )
+        .subcommand(
+            Command::new(COMMAND_DEBUG)
+                .about("Tools for inspecting how message data is read, useful when reporting bugs")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new(COMMAND_TYPEDSTREAM)
+                        .about("Print the objects, byte offsets, and types table read from an `attributedBody`\nThe source is either a file holding the raw data or the GUID of a message in the database\n")
+                        .arg(
+                            Arg::new(ARG_SOURCE)
+                                .help("Path to a file of `typedstream` data, or a message GUID")
+                                .value_name("file|guid")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new(COMMAND_LIST)
+                .about("Print every conversation with its identifier, participants, message count, and date range\nPass the identifier to `--conversation` to export only that conversation\n")
+                .arg(
+                    Arg::new(ARG_JSON)
+                        .long(ARG_JSON)
+                        .help("Print the conversations as a JSON array")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+}
+
+/// Parse arguments from the command line
+pub fn from_command_line() -> ArgMatches {
+    get_command().get_matches()
 }
 
-/// Parse arguments from the command line
-pub fn from_command_line() -> ArgMatches {
-    get_command().get_matches()
-}
+#[cfg(test)]
+mod arg_tests {
+    use std::{collections::BTreeSet, fs, path::PathBuf};
+
+    use imessage_database::util::{
+        dirs::default_db_path, platform::Platform, query_context::QueryContext,
+    };
+
+    use crate::app::{
+        attachment_manager::AttachmentManager,
+        chat_type::ChatType,
+        export_type::ExportType,
+        options::{get_command, validate_path, Options},
+        pagination::Pagination,
+        theme::Theme,
+    };
+
+    #[test]
+    fn can_build_option_diagnostic_flag() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            diagnostic: true,
+            export_type: None,
+            export_path: validate_path(None, &None).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn cant_build_option_diagnostic_flag_with_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-f", "txt"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnostic_flag_with_export_path() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-o", "~/test"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnostic_flag_with_attachment_manager() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-c", "compatible"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnostic_flag_with_start_date() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-s", "2020-01-01"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnostic_flag_with_end() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-e", "2020-01-01"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnostic_flag_with_caller_id() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-i"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_export_html() {
+        // Cleanup existing temp data
+        let _ = fs::remove_file("/tmp/orphaned.html");
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-o", "/tmp"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let tmp_dir = String::from("/tmp");
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            diagnostic: false,
+            export_type: Some(ExportType::Html),
+            export_path: validate_path(Some(&tmp_dir), &None).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_export_txt_no_lazy() {
+        // Cleanup existing temp data
+        let _ = fs::remove_file("/tmp/orphaned.txt");
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-l"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            diagnostic: false,
+            export_type: Some(ExportType::Txt),
+            export_path: validate_path(None, &None).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: true,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn cant_build_option_attachment_manager_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-c", "compatible"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_export_path_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-o", "~/test"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_start_date_path_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-s", "2020-01-01"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_end_date_path_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-e", "2020-01-01"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_date() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-e", "2020-32-32"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_end_date_before_start_date() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-s",
+            "2023-01-01",
+            "-e",
+            "2022-01-01",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_conversation() {
+        // Cleanup existing temp data
+        let _ = fs::remove_file("/tmp/orphaned.txt");
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-o",
+            "/tmp",
+            "--conversation",
+            "+15558675309",
+            "--conversation",
+            "Family",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.conversations, vec!["+15558675309", "Family"]);
+    }
+
+    #[test]
+    fn cant_build_option_conversation_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--conversation", "Family"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_exclude_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--exclude", "Work Chat"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_exclude_file() {
+        // Cleanup existing temp data
+        let _ = fs::remove_file("/tmp/orphaned.txt");
+        fs::create_dir_all("/tmp/imessage-exporter-exclusions").unwrap();
+        fs::write(
+            "/tmp/imessage-exporter-exclusions/exclusions.txt",
+            "# Work\nWork Chat\n\n  +15558675309  \n",
+        )
+        .unwrap();
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-o",
+            "/tmp",
+            "--exclude",
+            "12345",
+            "--exclude-file",
+            "/tmp/imessage-exporter-exclusions/exclusions.txt",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.excluded, vec!["12345", "Work Chat", "+15558675309"]);
+    }
+
+    #[test]
+    fn cant_build_option_exclude_file_missing() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--exclude-file",
+            "/tmp/fake/exclusions.txt",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_service() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--service", "SMS"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.query_context.service, Some("SMS"));
+    }
+
+    #[test]
+    fn can_build_option_service_all() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--service", "all"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.query_context.service.is_none());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_service() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--service", "fax"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_chat_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--chat-type", "group"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.chat_type, ChatType::Group);
+    }
+
+    #[test]
+    fn cant_build_option_invalid_chat_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--chat-type", "channel"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_attachment_types() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--only-attachments",
+            "image,video",
+            "--skip-attachment-type",
+            "Audio",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(
+            actual.query_context.attachment_types,
+            Some(BTreeSet::from(["image", "video"]))
+        );
+        assert_eq!(actual.skipped_attachment_types, vec!["audio"]);
+    }
+
+    #[test]
+    fn cant_build_option_invalid_attachment_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--skip-attachment-type",
+            "hologram",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_conflicting_attachment_types() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--only-attachments",
+            "image",
+            "--skip-attachment-type",
+            "image",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_direction() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--direction", "Sent"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.query_context.from_me, Some(true));
+    }
+
+    #[test]
+    fn cant_build_option_invalid_direction() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--direction", "up"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_no_reactions_no_system_messages() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--no-reactions",
+            "--no-system-messages",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.query_context.skip_tapbacks);
+        assert!(actual.query_context.skip_announcements);
+    }
+
+    #[test]
+    fn cant_build_option_no_reactions_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--no-reactions"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_known_contacts() {
+        fs::create_dir_all("/tmp/imessage-exporter-contacts").unwrap();
+        fs::write(
+            "/tmp/imessage-exporter-contacts/contacts.txt",
+            "# Family\n+15558675309\n\nperson@example.com\n",
+        )
+        .unwrap();
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--skip-short-codes",
+            "--known-contacts",
+            "/tmp/imessage-exporter-contacts/contacts.txt",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.skip_short_codes);
+        assert_eq!(
+            actual.known_contacts,
+            Some(vec![
+                "+15558675309".to_string(),
+                "person@example.com".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn cant_build_option_skip_short_codes_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--skip-short-codes"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_thread() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--thread",
+            "A1B2C3D4-E5F6-4A5B-8C9D-0E1F2A3B4C5D",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(
+            actual.query_context.thread_guid.as_deref(),
+            Some("A1B2C3D4-E5F6-4A5B-8C9D-0E1F2A3B4C5D")
+        );
+    }
+
+    #[test]
+    fn cant_build_option_invalid_thread() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--thread", "1' OR '1"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_recent() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--recent", "250"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.query_context.recent, Some(250));
+    }
+
+    #[test]
+    fn cant_build_option_invalid_recent() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--recent", "0"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_pinned() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--pinned"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual
+            .pinning_file
+            .unwrap()
+            .ends_with("Library/Preferences/com.apple.messages.pinning.plist"));
+    }
+
+    #[test]
+    fn can_build_option_contacts() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--contacts"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual
+            .contacts_file
+            .unwrap()
+            .ends_with("Library/Application Support/AddressBook"));
+    }
+
+    #[test]
+    fn can_build_option_contacts_file() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--contacts-file",
+            "/tmp/Contacts.abbu",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(
+            actual.contacts_file,
+            Some(PathBuf::from("/tmp/Contacts.abbu"))
+        );
+    }
+
+    #[test]
+    fn cant_build_option_contacts_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--contacts"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_names_file() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--names-file",
+            "/tmp/names.csv",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.names_file, Some(PathBuf::from("/tmp/names.csv")));
+        assert_eq!(actual.contacts_file, None);
+    }
+
+    #[test]
+    fn cant_build_option_names_file_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--names-file", "/tmp/names.csv"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_merge_identities() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--contacts",
+            "--merge-identities",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.merge_identities);
+    }
+
+    #[test]
+    fn cant_build_option_merge_identities_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--merge-identities"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_merge_services() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--merge-services"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.merge_services);
+        assert!(!actual.merge_identities);
+    }
+
+    #[test]
+    fn cant_build_option_merge_services_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--merge-services"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_stats() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--stats", "-s", "2020-01-01"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.stats);
+        assert!(actual.export_type.is_none());
+    }
+
+    #[test]
+    fn cant_build_option_stats_with_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--stats", "-f", "txt"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_stats_with_diagnostics() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--stats", "-d"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_country_code() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--country-code", "+44"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.country_code, Some("44".to_string()));
+    }
+
+    #[test]
+    fn cant_build_option_country_code_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--country-code", "uk"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_country_code_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--country-code", "44"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_me_handle() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "mbox",
+            "-m",
+            "Jane Doe",
+            "--me-handle",
+            "jane@example.com",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.custom_name, Some("Jane Doe".to_string()));
+        assert_eq!(actual.me_handle, Some("jane@example.com".to_string()));
+    }
+
+    #[test]
+    fn cant_build_option_me_handle_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--me-handle", "jane@example.com"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_me_avatar_missing_file() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--me-avatar",
+            "/does/not/exist.jpg",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_include_orphaned() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--exclude",
+            "Family",
+            "--include-orphaned",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.query_context.include_orphaned);
+    }
+
+    #[test]
+    fn cant_build_option_include_orphaned_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--include-orphaned"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_pick() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--pick"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.pick);
+    }
+
+    #[test]
+    fn cant_build_option_pick_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--pick"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "encrypted-backups"))]
+    fn cant_build_option_backup_password_without_feature() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--backup-password",
+            "hunter2",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "encrypted-backups")]
+    fn can_ignore_option_backup_password_unencrypted() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--backup-password",
+            "hunter2",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.backup_password, None);
+    }
+
+    #[test]
+    fn can_build_option_merge_db() {
+        fs::write("/tmp/imessage-exporter-merge.db", "").unwrap();
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--merge-db",
+            "/tmp/imessage-exporter-merge.db",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(
+            actual.merge_databases,
+            vec![PathBuf::from("/tmp/imessage-exporter-merge.db")]
+        );
+    }
+
+    #[test]
+    fn cant_build_option_db_path_sms_backup() {
+        fs::write("/tmp/imessage-exporter-sms.xml", "<smses />").unwrap();
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-p",
+            "/tmp/imessage-exporter-sms.xml",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_merge_db_missing() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--merge-db",
+            "/does/not/exist/chat.db",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_pinning_file() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--pinning-file",
+            "/tmp/pinning.plist",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(
+            actual.pinning_file,
+            Some(PathBuf::from("/tmp/pinning.plist"))
+        );
+    }
+
+    #[test]
+    fn can_build_option_unread() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--unread"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.query_context.unread);
+    }
+
+    #[test]
+    fn can_build_option_deleted() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--deleted"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.query_context.deleted);
+    }
+
+    #[test]
+    fn cant_build_option_deleted_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--deleted"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_unread_sent() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--unread",
+            "--direction",
+            "sent",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_dedupe_attachments() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "efficient",
+            "--dedupe-attachments",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.dedupe_attachments);
+    }
+
+    #[test]
+    fn cant_build_option_dedupe_attachments_without_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--dedupe-attachments"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_thumbnails() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "compatible",
+            "--thumbnails",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.thumbnails);
+    }
+
+    #[test]
+    fn cant_build_option_thumbnails_not_html() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-c",
+            "compatible",
+            "--thumbnails",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_thumbnails_without_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--thumbnails"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_max_attachment_size() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--max-attachment-size",
+            "25MB",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.max_attachment_size, Some(26214400));
+    }
+
+    #[test]
+    fn cant_build_option_invalid_max_attachment_size() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--max-attachment-size",
+            "large",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_strip_metadata() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "compatible",
+            "--strip-metadata",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.strip_metadata);
+    }
+
+    #[test]
+    fn cant_build_option_strip_metadata_without_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--strip-metadata"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_attachment_manifest() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-c",
+            "efficient",
+            "--attachment-manifest",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.attachment_manifest);
+    }
+
+    #[test]
+    fn cant_build_option_attachment_manifest_without_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--attachment-manifest"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_strip_metadata_with_link() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "link",
+            "--strip-metadata",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_missing_media_report() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--missing-media-report"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.missing_media_report);
+    }
+
+    #[test]
+    fn cant_build_option_missing_media_report_without_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--missing-media-report"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_attachment_name() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "efficient",
+            "--attachment-name",
+            "{date}_{sender}_{original_name}",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
 
-#[cfg(test)]
-mod arg_tests {
-    use std::fs;
+        assert_eq!(
+            actual.attachment_name.as_deref(),
+            Some("{date}_{sender}_{original_name}")
+        );
+    }
 
-    use imessage_database::util::{
-        dirs::default_db_path, platform::Platform, query_context::QueryContext,
-    };
+    #[test]
+    fn cant_build_option_attachment_name_without_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--attachment-name",
+            "{original_name}",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
 
-    use crate::app::{
-        attachment_manager::AttachmentManager,
-        export_type::ExportType,
-        options::{get_command, validate_path, Options},
-    };
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
 
     #[test]
-    fn can_build_option_diagnostic_flag() {
+    fn cant_build_option_invalid_attachment_name() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "efficient",
+            "--attachment-name",
+            "{guid}",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_verify() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-c",
+            "efficient",
+            "--verify",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
         let actual = Options::from_args(&args).unwrap();
 
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::default(),
-            diagnostic: true,
-            export_type: None,
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-        };
+        assert!(actual.verify);
+    }
 
-        assert_eq!(actual, expected);
+    #[test]
+    fn can_build_option_receipts() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--receipts"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.receipts);
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_export_type() {
+    fn cant_build_option_verify_without_copy() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-f", "txt"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--verify"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -474,9 +3255,22 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_export_path() {
+    fn can_build_option_attachments_export() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-o", "~/test"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "attachments", "-c", "efficient"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.export_type, Some(ExportType::Attachments));
+    }
+
+    #[test]
+    fn cant_build_option_attachments_export_without_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "attachments"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -487,9 +3281,9 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_attachment_manager() {
+    fn cant_build_option_invalid_platform() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-c", "compatible"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-a", "iPad"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -500,9 +3294,9 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_start_date() {
+    fn cant_build_option_invalid_export_type() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-s", "2020-01-01"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "docx"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -513,9 +3307,23 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_end() {
+    fn can_build_option_export_ndjson_stdout() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-e", "2020-01-01"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "ndjson", "-o", "-"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.export_type, Some(ExportType::Ndjson));
+        assert!(actual.writes_to_stdout());
+    }
+
+    #[test]
+    fn cant_build_option_stdout_not_ndjson() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-o", "-"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -526,9 +3334,17 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_caller_id() {
+    fn cant_build_option_stdout_with_attachment_manager() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-i"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "ndjson",
+            "-o",
+            "-",
+            "-c",
+            "efficient",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -539,74 +3355,95 @@ mod arg_tests {
     }
 
     #[test]
-    fn can_build_option_export_html() {
+    #[cfg(not(feature = "parquet"))]
+    fn cant_build_option_parquet_without_feature() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "parquet"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_site() {
         // Cleanup existing temp data
         let _ = fs::remove_file("/tmp/orphaned.html");
 
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-o", "/tmp"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-o", "/tmp", "-w"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
         let actual = Options::from_args(&args).unwrap();
 
-        // Expected data
-        let tmp_dir = String::from("/tmp");
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::default(),
-            diagnostic: false,
-            export_type: Some(ExportType::Html),
-            export_path: validate_path(Some(&tmp_dir), &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-        };
+        assert!(actual.site);
+    }
 
-        assert_eq!(actual, expected);
+    #[test]
+    fn cant_build_option_site_not_html() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-w"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
     }
 
     #[test]
-    fn can_build_option_export_txt_no_lazy() {
+    fn can_build_option_template_dir() {
         // Cleanup existing temp data
-        let _ = fs::remove_file("/tmp/orphaned.txt");
+        let _ = fs::remove_file("/tmp/orphaned.html");
 
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-l"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-o",
+            "/tmp",
+            "-t",
+            "/tmp",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
         let actual = Options::from_args(&args).unwrap();
 
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::default(),
-            diagnostic: false,
-            export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: true,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-        };
+        assert_eq!(actual.template_dir, Some(PathBuf::from("/tmp")));
+    }
 
-        assert_eq!(actual, expected);
+    #[test]
+    fn cant_build_option_template_dir_not_html() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-t", "/tmp"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
     }
 
     #[test]
-    fn cant_build_option_attachment_manager_no_export_type() {
+    fn cant_build_option_template_dir_missing() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-c", "compatible"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-t",
+            "/tmp/fake_template_dir",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -617,9 +3454,37 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_export_path_no_export_type() {
+    fn can_build_option_txt_format() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-o", "~/test"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--txt-format",
+            "{date:%F %T} <{sender}> {text}",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(
+            actual.txt_format,
+            Some("{date:%F %T} <{sender}> {text}".to_string())
+        );
+    }
+
+    #[test]
+    fn cant_build_option_txt_format_not_txt() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--txt-format",
+            "{sender}: {text}",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -630,9 +3495,15 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_start_date_path_no_export_type() {
+    fn cant_build_option_txt_format_invalid() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-s", "2020-01-01"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--txt-format",
+            "{sender}: {message}",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -643,9 +3514,26 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_end_date_path_no_export_type() {
+    fn can_build_option_paginate() {
+        // Cleanup existing temp data
+        let _ = fs::remove_file("/tmp/orphaned.html");
+
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-e", "2020-01-01"];
+        let cli_args: Vec<&str> =
+            vec!["imessage-exporter", "-f", "html", "-o", "/tmp", "-g", "500"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.paginate, Some(Pagination::Messages(500)));
+    }
+
+    #[test]
+    fn cant_build_option_paginate_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-g", "week"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -656,9 +3544,9 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_invalid_date() {
+    fn cant_build_option_paginate_not_html() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-e", "2020-32-32"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-g", "month"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -669,9 +3557,32 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_invalid_platform() {
+    fn can_build_option_single_file() {
+        // Cleanup existing temp data
+        let _ = fs::remove_file("/tmp/orphaned.html");
+
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-a", "iPad"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-o",
+            "/tmp",
+            "--single-file",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.single_file);
+    }
+
+    #[test]
+    fn cant_build_option_single_file_not_html() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--single-file"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -682,9 +3593,61 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_invalid_export_type() {
+    fn cant_build_option_single_file_paginate() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-g",
+            "month",
+            "--single-file",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_theme() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "epub", "--theme", "Dark"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.theme, Theme::Dark);
+    }
+
+    #[test]
+    fn cant_build_option_theme_missing_file() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--theme",
+            "/tmp/fake_palette.css",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_theme_not_html() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "pdf"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--theme", "dark"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -718,6 +3681,39 @@ mod arg_tests {
             use_caller_id: false,
             platform: Platform::default(),
             ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
         };
 
         assert_eq!(actual, expected);
@@ -747,6 +3743,39 @@ mod arg_tests {
             use_caller_id: true,
             platform: Platform::default(),
             ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
         };
 
         assert_eq!(actual, expected);
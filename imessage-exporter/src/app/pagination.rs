@@ -0,0 +1,56 @@
+/*!
+ Contains data structures used to describe how HTML exports are split into pages.
+*/
+
+/// Represents how each conversation in an HTML export is split into pages
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Pagination {
+    /// A page for each year with messages
+    Year,
+    /// A page for each month with messages
+    Month,
+    /// A page for every group of this many messages
+    Messages(usize),
+}
+
+impl Pagination {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(pagination: &str) -> Option<Self> {
+        match pagination.to_lowercase().as_str() {
+            "year" => Some(Self::Year),
+            "month" => Some(Self::Month),
+            count => match count.parse() {
+                Ok(0) | Err(_) => None,
+                Ok(count) => Some(Self::Messages(count)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::pagination::Pagination;
+
+    #[test]
+    fn can_parse_period_any_case() {
+        assert_eq!(Pagination::from_cli("year"), Some(Pagination::Year));
+        assert_eq!(Pagination::from_cli("Month"), Some(Pagination::Month));
+        assert_eq!(Pagination::from_cli("MONTH"), Some(Pagination::Month));
+    }
+
+    #[test]
+    fn can_parse_count() {
+        assert_eq!(
+            Pagination::from_cli("5000"),
+            Some(Pagination::Messages(5000))
+        );
+    }
+
+    #[test]
+    fn cant_parse_invalid() {
+        assert!(Pagination::from_cli("week").is_none());
+        assert!(Pagination::from_cli("0").is_none());
+        assert!(Pagination::from_cli("-1").is_none());
+        assert!(Pagination::from_cli("").is_none());
+    }
+}
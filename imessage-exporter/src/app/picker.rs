@@ -0,0 +1,263 @@
+/*!
+ Contains logic for `--pick`, which asks which conversations to export before the export starts.
+
+ The conversations are shown as a numbered checklist in the terminal. Numbers typed at the prompt
+ toggle conversations on and off, so nobody needs to know a conversation's identifier to select it.
+*/
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    io::{stdin, stdout, BufRead, Write},
+};
+
+use imessage_database::tables::chat::Chat;
+
+use crate::app::{
+    error::RuntimeError,
+    list::{listings, Listing},
+    runtime::Config,
+};
+
+/// The instructions shown below the checklist
+const PROMPT: &str = "Toggle conversations by number, i.e. `1 3 5-7`, `a` to select all, `n` to select none, or `q` to quit\nPress Enter to export the selected conversations: ";
+
+/// A change to the checklist typed at the prompt
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    /// Flip the conversations at these indexes
+    Toggle(Vec<usize>),
+    /// Select every conversation
+    All,
+    /// Clear the selection
+    Clear,
+    /// Export the selected conversations
+    Done,
+    /// Stop without exporting anything
+    Quit,
+}
+
+/// Ask which of the `selected` chats to export, returning the chats that were picked
+///
+/// Chats that are merged in the export are shown once, under the most recently active of them.
+pub fn run(config: &Config, selected: &BTreeSet<i32>) -> Result<BTreeSet<i32>, RuntimeError> {
+    let activity = Chat::activity(&config.db).map_err(RuntimeError::DatabaseError)?;
+
+    let mut seen = HashSet::new();
+    let conversations: Vec<Listing> = listings(config, &activity)
+        .into_iter()
+        .filter(|listing| selected.contains(&listing.chat_id))
+        .filter(|listing| {
+            seen.insert(
+                config
+                    .real_chatrooms
+                    .get(&listing.chat_id)
+                    .copied()
+                    .unwrap_or(listing.chat_id),
+            )
+        })
+        .collect();
+    if conversations.is_empty() {
+        return Err(RuntimeError::InvalidOptions(String::from(
+            "No conversations to pick from!",
+        )));
+    }
+
+    let labels: Vec<String> = conversations.iter().map(label).collect();
+    let picked = pick(&labels, stdin().lock(), stdout())?;
+    Ok(picked
+        .into_iter()
+        .map(|idx| conversations[idx].chat_id)
+        .collect())
+}
+
+/// Describe a conversation by its name, or else by its participants, and when it was last active
+fn label(listing: &Listing) -> String {
+    let name = match listing.name {
+        Some(name) => name.to_string(),
+        None if !listing.participants.is_empty() => listing.participants.join(", "),
+        None => listing.identifier.to_string(),
+    };
+    match listing.last {
+        Some(last) => format!(
+            "{name} ({} messages, last on {})",
+            listing.messages,
+            last.format("%Y-%m-%d")
+        ),
+        None => format!("{name} (no messages)"),
+    }
+}
+
+/// Show the checklist until the selection is confirmed, returning the indexes of the picked labels
+fn pick(
+    labels: &[String],
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<BTreeSet<usize>, RuntimeError> {
+    let mut picked = BTreeSet::new();
+    loop {
+        write!(output, "{}", checklist(labels, &picked)).map_err(RuntimeError::DiskError)?;
+        write!(output, "{PROMPT}").map_err(RuntimeError::DiskError)?;
+        output.flush().map_err(RuntimeError::DiskError)?;
+
+        let mut line = String::new();
+        // Nothing left to read is the same as quitting, so a closed terminal does not start an export
+        let command = match input
+            .read_line(&mut line)
+            .map_err(RuntimeError::DiskError)?
+        {
+            0 => Some(Command::Quit),
+            _ => parse(&line, labels.len()),
+        };
+
+        match command {
+            Some(Command::Toggle(indexes)) => indexes.into_iter().for_each(|idx| {
+                if !picked.remove(&idx) {
+                    picked.insert(idx);
+                }
+            }),
+            Some(Command::All) => picked.extend(0..labels.len()),
+            Some(Command::Clear) => picked.clear(),
+            Some(Command::Done) if !picked.is_empty() => return Ok(picked),
+            Some(Command::Done) => {
+                writeln!(output, "\nSelect at least one conversation!")
+                    .map_err(RuntimeError::DiskError)?;
+            }
+            Some(Command::Quit) => {
+                return Err(RuntimeError::InvalidOptions(String::from(
+                    "No conversations were picked!",
+                )))
+            }
+            None => {
+                writeln!(
+                    output,
+                    "\n`{}` is not a conversation number between 1 and {}!",
+                    line.trim(),
+                    labels.len()
+                )
+                .map_err(RuntimeError::DiskError)?;
+            }
+        }
+    }
+}
+
+/// Lay out the labels as a numbered checklist, marking the picked ones
+fn checklist(labels: &[String], picked: &BTreeSet<usize>) -> String {
+    let width = labels.len().to_string().len();
+    let mut out_s = String::from("\n");
+    for (idx, label) in labels.iter().enumerate() {
+        let mark = if picked.contains(&idx) { 'x' } else { ' ' };
+        out_s.push_str(&format!("[{mark}] {:>width$}. {label}\n", idx + 1));
+    }
+    out_s.push_str(&format!("{} of {} selected\n", picked.len(), labels.len()));
+    out_s
+}
+
+/// Read a line typed at the prompt, given the number of conversations in the checklist
+///
+/// Conversations are numbered from `1`, and ranges like `5-7` include both ends.
+fn parse(line: &str, len: usize) -> Option<Command> {
+    let line = line.trim();
+    match line.to_lowercase().as_str() {
+        "" => return Some(Command::Done),
+        "a" | "all" => return Some(Command::All),
+        "n" | "none" => return Some(Command::Clear),
+        "q" | "quit" => return Some(Command::Quit),
+        _ => {}
+    }
+
+    let number = |number: &str| {
+        number
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|number| (1..=len).contains(number))
+    };
+    let mut indexes = vec![];
+    for token in line
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+    {
+        match token.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (number(start)?, number(end)?);
+                if start > end {
+                    return None;
+                }
+                indexes.extend(start - 1..end);
+            }
+            None => indexes.push(number(token)? - 1),
+        }
+    }
+    Some(Command::Toggle(indexes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::app::picker::{checklist, parse, pick, Command};
+
+    fn labels() -> Vec<String> {
+        vec![
+            "Family".to_string(),
+            "Jane Doe".to_string(),
+            "+15558675309".to_string(),
+        ]
+    }
+
+    #[test]
+    fn can_parse_numbers() {
+        assert_eq!(parse("1 3\n", 3), Some(Command::Toggle(vec![0, 2])));
+        assert_eq!(parse("1,2", 3), Some(Command::Toggle(vec![0, 1])));
+        assert_eq!(parse("2-3", 3), Some(Command::Toggle(vec![1, 2])));
+    }
+
+    #[test]
+    fn can_parse_commands() {
+        assert_eq!(parse("\n", 3), Some(Command::Done));
+        assert_eq!(parse("A", 3), Some(Command::All));
+        assert_eq!(parse("none", 3), Some(Command::Clear));
+        assert_eq!(parse("q", 3), Some(Command::Quit));
+    }
+
+    #[test]
+    fn cant_parse_invalid() {
+        assert_eq!(parse("0", 3), None);
+        assert_eq!(parse("4", 3), None);
+        assert_eq!(parse("3-1", 3), None);
+        assert_eq!(parse("Family", 3), None);
+    }
+
+    #[test]
+    fn can_build_checklist() {
+        let expected = "\n[x] 1. Family\n[ ] 2. Jane Doe\n[x] 3. +15558675309\n2 of 3 selected\n";
+        assert_eq!(checklist(&labels(), &BTreeSet::from([0, 2])), expected);
+    }
+
+    #[test]
+    fn can_pick() {
+        let mut output = vec![];
+        let picked = pick(&labels(), "1 2\n5\n2\n\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(picked, BTreeSet::from([0]));
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("`5` is not a conversation number between 1 and 3!"));
+        assert!(output.contains("[x] 1. Family\n[x] 2. Jane Doe\n"));
+    }
+
+    #[test]
+    fn can_pick_after_empty_selection() {
+        let mut output = vec![];
+        let picked = pick(&labels(), "\na\n\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(picked, BTreeSet::from([0, 1, 2]));
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("Select at least one conversation!"));
+    }
+
+    #[test]
+    fn cant_pick_without_input() {
+        assert!(pick(&labels(), "1\n".as_bytes(), vec![]).is_err());
+        assert!(pick(&labels(), "q\n".as_bytes(), vec![]).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+/*!
+ Contains logic for describing the members of a group chat at the top of its export.
+*/
+
+use log::warn;
+
+use imessage_database::{
+    tables::{
+        chat::Chat,
+        chat_handle::{ChatToHandle, MembershipChange, MembershipChangeKind},
+    },
+    util::dates::{format, get_local_time},
+};
+
+use crate::app::runtime::Config;
+
+/// The members of a group chat and how they changed over time
+#[derive(Debug, PartialEq, Eq)]
+pub struct Roster {
+    /// The names of the current members of the chat, including the database owner
+    pub members: Vec<String>,
+    /// Descriptions of each change to the members of the chat, oldest first
+    pub changes: Vec<String>,
+}
+
+impl Roster {
+    /// Build the roster of a chat, or `None` if the chat is not a group chat
+    ///
+    /// Exporters write the roster once, at the top of a new file, so readers know who is in the chat before its messages.
+    pub fn new(config: &Config, chatroom: &Chat) -> Option<Self> {
+        let participants = config.chatroom_participants.get(&chatroom.rowid)?;
+        if !chatroom.is_group(participants.len()) {
+            return None;
+        }
+
+        let mut members: Vec<String> = participants
+            .iter()
+            .map(|handle| Roster::name(config, *handle).to_string())
+            .collect();
+        members.sort();
+        members.push(Roster::name(config, 0).to_string());
+
+        let changes = match ChatToHandle::membership_changes(&config.db, chatroom.rowid) {
+            Ok(changes) => changes
+                .iter()
+                .map(|change| Roster::describe(config, change))
+                .collect(),
+            Err(why) => {
+                warn!(
+                    "Unable to read membership changes for chat {}: {why}",
+                    chatroom.rowid
+                );
+                vec![]
+            }
+        };
+
+        Some(Self { members, changes })
+    }
+
+    /// Get the name of a participant, where `0` is the database owner
+    fn name(config: &Config, handle: i32) -> &str {
+        config.who(Some(handle), handle == 0, &None)
+    }
+
+    /// Describe a change to the members of a chat, i.e. `May 17, 2022  5:29:42 PM: Me added Jane Doe`
+    fn describe(config: &Config, change: &MembershipChange) -> String {
+        let date = format(&get_local_time(&change.date, &config.offset));
+        let member = Roster::name(config, change.member);
+        let actor = Roster::name(config, change.actor);
+        match change.kind {
+            MembershipChangeKind::Added => format!("{date}: {actor} added {member}"),
+            MembershipChangeKind::Removed => format!("{date}: {actor} removed {member}"),
+            MembershipChangeKind::Left => format!("{date}: {member} left"),
+        }
+    }
+}
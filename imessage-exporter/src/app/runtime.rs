@@ -1,24 +1,47 @@
 use std::{
+    borrow::Cow,
     cmp::min,
     collections::{BTreeSet, HashMap, HashSet},
-    fs::create_dir_all,
+    fs::{create_dir_all, metadata, write},
     path::PathBuf,
 };
 
 use fdlimit::raise_fd_limit;
 use fs2::available_space;
+use log::{info, warn};
 use rusqlite::Connection;
 
 use crate::{
     app::{
-        attachment_manager::AttachmentManager, converter::Converter, error::RuntimeError,
-        export_type::ExportType, options::Options, sanitizers::sanitize_filename,
+        attachment_manager::AttachmentManager,
+        attachment_name::{AttachmentName, AttachmentNamer},
+        chat_type::ChatType,
+        converter::{Converter, DocumentConverter, MediaConverter},
+        dedupe::AttachmentDeduplicator,
+        error::RuntimeError,
+        export_type::ExportType,
+        manifest::AttachmentManifest,
+        merge::MergedDatabase,
+        missing_media::MissingMediaReport,
+        options::Options,
+        picker,
+        sanitizers::sanitize_filename,
+        stats::{Stats, STATS_FILENAME},
+        verify::AttachmentVerifier,
     },
-    Exporter, HTML, TXT,
+    Attachments, DayOne, Exporter, LaTeX, Maildir, Markdown, Matrix, Obsidian, SQLite, WhatsApp,
+    EPUB, HTML, MBOX, MHTML, NDJSON, PDF, TXT,
 };
 
+#[cfg(feature = "parquet")]
+use crate::Parquet;
+
+#[cfg(feature = "encrypted-backups")]
+use crate::app::decrypt::DecryptedBackup;
+
 use imessage_database::{
     error::table::TableError,
+    message_types::variants::Variant,
     tables::{
         attachment::Attachment,
         chat::Chat,
@@ -26,11 +49,20 @@ use imessage_database::{
         handle::Handle,
         messages::Message,
         table::{
-            get_connection, get_db_size, Cacheable, Deduplicate, Diagnostic, ATTACHMENTS_DIR,
-            MAX_LENGTH, ME, ORPHANED, UNKNOWN,
+            get_connection, get_db_size, Cacheable, Deduplicate, Diagnostic, Table,
+            ATTACHMENTS_DIR, MAX_LENGTH, ME, ORPHANED, UNKNOWN,
         },
     },
-    util::{dates::get_offset, size::format_file_size},
+    util::{
+        contacts::ContactNames,
+        dates::{format, get_offset},
+        output::{done_processing, processing},
+        phone::{normalize_handle, DEFAULT_COUNTRY_CODE},
+        pinned::pinned_identifiers,
+        platform::Platform,
+        query_context::QueryContext,
+        size::format_file_size,
+    },
 };
 
 /// Stores the application state and handles application lifecycle
@@ -45,6 +77,10 @@ pub struct Config {
     pub participants: HashMap<i32, String>,
     /// Map of participant ID to an internal unique participant ID
     pub real_participants: HashMap<i32, i32>,
+    /// Map of participant ID to the name of the contact it belongs to, if contacts were read
+    pub contact_names: HashMap<i32, String>,
+    /// Map of participant ID to the photo of the contact it belongs to, if contacts were read
+    pub contact_avatars: HashMap<i32, Vec<u8>>,
     /// Messages that are tapbacks (reactions) to other messages
     pub tapbacks: HashMap<String, HashMap<usize, Vec<Message>>>,
     /// App configuration options
@@ -55,6 +91,25 @@ pub struct Config {
     pub db: Connection,
     /// Converter type used when converting image files
     pub converter: Option<Converter>,
+    /// Tracks copied attachments when identical files are only stored once
+    pub deduplicator: Option<AttachmentDeduplicator>,
+    /// Converter type used when converting audio and video files
+    pub media_converter: Option<MediaConverter>,
+    /// Converter type used when previewing office documents
+    pub document_converter: Option<DocumentConverter>,
+    /// Lists the copied attachments, if requested
+    pub manifest: Option<AttachmentManifest>,
+    /// Lists the attachments whose files are not on disk, if requested
+    pub missing_media: Option<MissingMediaReport>,
+    /// Names copied attachments when a template is passed
+    pub attachment_namer: Option<AttachmentNamer>,
+    /// Tracks copied attachments to check once the export finishes, if requested
+    pub verifier: Option<AttachmentVerifier>,
+    /// The decrypted copy of an encrypted backup, if one is read
+    #[cfg(feature = "encrypted-backups")]
+    pub decrypted_backup: Option<DecryptedBackup>,
+    /// The database the sources were merged into, if more than one is read
+    pub merged_database: Option<MergedDatabase>,
 }
 
 impl Config {
@@ -65,7 +120,7 @@ impl Config {
                 if let Some(chatroom) = self.chatrooms.get(&chat_id) {
                     self.real_chatrooms.get(&chat_id).map(|id| (chatroom, id))
                 } else {
-                    eprintln!("Chat ID {chat_id} does not exist in chat table!");
+                    warn!("Chat ID {chat_id} does not exist in chat table!");
                     None
                 }
             }
@@ -91,6 +146,70 @@ impl Config {
         String::from(ORPHANED)
     }
 
+    /// Determine if an attachment is one of the kinds included in the export
+    ///
+    /// Attachments are left out if `--only-attachments` does not list their kind, if `--skip-attachment-type` does,
+    /// or if they are larger than `--max-attachment-size`.
+    pub fn includes_attachment(&self, attachment: &Attachment) -> bool {
+        if let Some(max_size) = self.options.max_attachment_size {
+            if self.attachment_size(attachment) > max_size {
+                return false;
+            }
+        }
+
+        let category = attachment.mime_type().category();
+        if let Some(attachment_types) = &self.options.query_context.attachment_types {
+            if !category.is_some_and(|category| attachment_types.contains(category)) {
+                return false;
+            }
+        }
+        !category.is_some_and(|category| {
+            self.options
+                .skipped_attachment_types
+                .iter()
+                .any(|skipped| skipped == category)
+        })
+    }
+
+    /// Get the size of an attachment on disk, falling back to the amount of data transferred if the file is missing
+    fn attachment_size(&self, attachment: &Attachment) -> u64 {
+        attachment
+            .resolved_attachment_path(
+                &self.options.platform,
+                &self.options.db_path,
+                self.options.attachment_root.as_deref(),
+            )
+            .and_then(|path| metadata(path).ok())
+            .map_or(attachment.total_bytes, |metadata| metadata.len())
+    }
+
+    /// Describe an attachment whose file was removed on purpose, so it is not shown as a broken link
+    ///
+    /// Audio messages are deleted after they are played unless the recipient keeps them, and Messages
+    /// hides attachments that were deleted from a conversation. If the file is still on disk, the
+    /// attachment is exported as usual.
+    pub fn removed_attachment(
+        &self,
+        message: &Message,
+        attachment: &Attachment,
+    ) -> Option<&'static str> {
+        let label = if message.is_expiring_audio() {
+            "Expired audio message"
+        } else if attachment.is_hidden() {
+            "Deleted attachment"
+        } else {
+            return None;
+        };
+        match attachment.resolved_attachment_path(
+            &self.options.platform,
+            &self.options.db_path,
+            self.options.attachment_root.as_deref(),
+        ) {
+            Some(path) if metadata(&path).is_ok() => None,
+            _ => Some(label),
+        }
+    }
+
     /// Generate a file path for an attachment
     ///
     /// If the attachment was copied, use that path
@@ -122,6 +241,36 @@ impl Config {
         Some(path.display().to_string())
     }
 
+    /// Get the path to a chat's icon, if it has one
+    ///
+    /// Group chats use their group photo, and one-on-one chats use the photo of the contact, if contacts were read.
+    pub fn chat_icon(&self, chatroom: &Chat) -> Option<PathBuf> {
+        if let Some(guid) = chatroom.group_photo_guid(&self.db) {
+            let attachment = Attachment::from_guid(&self.db, &guid).ok()??;
+            return self.options.attachment_manager.handle_chat_icon(
+                chatroom.rowid,
+                &attachment,
+                self,
+            );
+        }
+
+        let participants = self.chatroom_participants.get(&chatroom.rowid)?;
+        if chatroom.is_group(participants.len()) {
+            return None;
+        }
+        let handle_id = participants.first()?;
+        let avatar = self.contact_avatars.get(handle_id)?;
+        self.options
+            .attachment_manager
+            .handle_avatar(*handle_id, avatar, self)
+    }
+
+    /// Get the path to the database owner's photo, if one was passed with `--me-avatar`
+    pub fn my_avatar(&self) -> Option<PathBuf> {
+        let from = self.options.me_avatar.as_deref()?;
+        self.options.attachment_manager.handle_my_avatar(from, self)
+    }
+
     /// Get a filename for a chat, possibly using cached data.
     ///
     /// If the chat has an assigned name, use that, truncating if necessary.
@@ -142,10 +291,7 @@ impl Config {
                 if let Some(participants) = self.chatroom_participants.get(&chatroom.rowid) {
                     self.filename_from_participants(participants)
                 } else {
-                    eprintln!(
-                        "Found error: message chat ID {} has no members!",
-                        chatroom.rowid
-                    );
+                    warn!("Message chat ID {} has no members!", chatroom.rowid);
                     chatroom.chat_identifier.clone()
                 }
             }
@@ -186,6 +332,100 @@ impl Config {
         out_s
     }
 
+    /// Read the names file and the contacts, if either was provided
+    ///
+    /// The names file is read first, so its names take precedence over contacts.
+    fn read_contact_names(options: &Options) -> Result<ContactNames, RuntimeError> {
+        let mut contacts = ContactNames::default();
+        if let Some(path) = &options.names_file {
+            contacts.read_names_file(path).map_err(|why| {
+                RuntimeError::InvalidOptions(format!("Unable to read names from {path:?}: {why}"))
+            })?;
+            if contacts.is_empty() {
+                warn!("No names found in {path:?}!");
+            }
+        }
+        if let Some(path) = &options.contacts_file {
+            let named = contacts.len();
+            contacts.read_contacts(path).map_err(|why| {
+                RuntimeError::InvalidOptions(format!(
+                    "Unable to read contacts from {path:?}: {why}"
+                ))
+            })?;
+            if contacts.len() == named {
+                warn!("No contacts found in {path:?}!");
+            }
+        }
+        Ok(contacts)
+    }
+
+    /// Map each participant to the name of the contact it belongs to, skipping participants without one
+    fn match_names(
+        contacts: &ContactNames,
+        participants: &HashMap<i32, String>,
+    ) -> HashMap<i32, String> {
+        let contact_names: HashMap<i32, String> = participants
+            .iter()
+            .filter_map(|(id, handle)| Some((*id, contacts.get(handle)?.to_string())))
+            .collect();
+        if !contacts.is_empty() {
+            info!(
+                "Matched {} of {} participants to contacts",
+                contact_names.len(),
+                participants.len()
+            );
+        }
+        contact_names
+    }
+
+    /// Normalize each participant's handles, so the same phone number is stored the same way however it was written
+    ///
+    /// Handles that belong to the same person are joined with spaces, so each of them is normalized.
+    fn normalize_participants(
+        participants: &HashMap<i32, String>,
+        country_code: &str,
+    ) -> HashMap<i32, String> {
+        participants
+            .iter()
+            .map(|(id, handles)| {
+                let normalized: Vec<String> = handles
+                    .split_whitespace()
+                    .map(|handle| normalize_handle(handle, country_code))
+                    .collect();
+                (*id, normalized.join(" "))
+            })
+            .collect()
+    }
+
+    /// Deduplicate participants, treating handles that belong to the same contact as one person
+    fn dedupe_people(
+        participants: &HashMap<i32, String>,
+        contacts: &ContactNames,
+    ) -> HashMap<i32, i32> {
+        let people: HashMap<i32, String> = participants
+            .iter()
+            .map(|(id, handle)| (*id, contacts.person(handle).unwrap_or(handle).to_owned()))
+            .collect();
+        Handle::dedupe(&people)
+    }
+
+    /// Map each chatroom to the people in it instead of their handles, so chats with the same people can be merged
+    fn chatroom_people(
+        chatroom_participants: &HashMap<i32, BTreeSet<i32>>,
+        real_participants: &HashMap<i32, i32>,
+    ) -> HashMap<i32, BTreeSet<i32>> {
+        chatroom_participants
+            .iter()
+            .map(|(chat_id, handles)| {
+                let people = handles
+                    .iter()
+                    .map(|handle| *real_participants.get(handle).unwrap_or(handle))
+                    .collect();
+                (*chat_id, people)
+            })
+            .collect()
+    }
+
     /// Create a new instance of the application
     ///
     /// # Example:
@@ -200,39 +440,326 @@ impl Config {
     /// let options = Options::from_args(&args);
     /// let app = Config::new(options).unwrap();
     /// ```
-    pub fn new(options: Options) -> Result<Config, RuntimeError> {
+    pub fn new(mut options: Options) -> Result<Config, RuntimeError> {
+        // Encrypted backups are decrypted first, then read like unencrypted ones
+        #[cfg(feature = "encrypted-backups")]
+        let decrypted_backup = match &options.backup_password {
+            Some(password) => {
+                let decrypted = DecryptedBackup::new(&options.db_path, password)?;
+                options.db_path.clone_from(&decrypted.path);
+                Some(decrypted)
+            }
+            None => None,
+        };
+
+        // Several databases are merged into one, which is read like a database from macOS
+        let merged_database = match options.merge_databases.is_empty() {
+            true => None,
+            false => {
+                let merged = MergedDatabase::new(
+                    &options.db_path,
+                    options.platform.clone(),
+                    &options.merge_databases,
+                    options
+                        .country_code
+                        .as_deref()
+                        .unwrap_or(DEFAULT_COUNTRY_CODE),
+                )?;
+                options.db_path.clone_from(&merged.path);
+                options.platform = Platform::macOS;
+                Some(merged)
+            }
+        };
+
         let conn = get_connection(&options.get_db_path()).map_err(RuntimeError::DatabaseError)?;
-        eprintln!("Building cache...");
-        eprintln!("[1/4] Caching chats...");
+        info!("Building cache...");
+        info!("[1/4] Caching chats...");
         let chatrooms = Chat::cache(&conn).map_err(RuntimeError::DatabaseError)?;
-        eprintln!("[2/4] Caching chatrooms...");
+        info!("[2/4] Caching chatrooms...");
         let chatroom_participants =
             ChatToHandle::cache(&conn).map_err(RuntimeError::DatabaseError)?;
-        eprintln!("[3/4] Caching participants...");
+        info!("[3/4] Caching participants...");
         let participants = Handle::cache(&conn).map_err(RuntimeError::DatabaseError)?;
-        eprintln!("[4/4] Caching tapbacks...");
-        let tapbacks = Message::cache(&conn).map_err(RuntimeError::DatabaseError)?;
-        eprintln!("Cache built!");
+        info!("[4/4] Caching tapbacks...");
+        let tapbacks = match options.query_context.skip_tapbacks {
+            true => HashMap::new(),
+            false => Message::cache(&conn).map_err(RuntimeError::DatabaseError)?,
+        };
+        info!("Cache built!");
+
+        let contacts = Config::read_contact_names(&options)?;
+        let contact_names = Config::match_names(&contacts, &participants);
+        let contact_avatars = participants
+            .iter()
+            .filter_map(|(id, handle)| Some((*id, contacts.avatar(handle)?.to_vec())))
+            .collect();
+        // Handles are compared in E.164 so formatting differences do not split a person in two
+        let country_code = options
+            .country_code
+            .as_deref()
+            .unwrap_or(DEFAULT_COUNTRY_CODE);
+        let handles = Config::normalize_participants(&participants, country_code);
+        let real_participants = Config::dedupe_people(&handles, &contacts);
+        // Chats are merged when they have the same handles, or the same people if requested
+        let real_chatrooms = match (options.merge_identities, options.merge_services) {
+            (true, _) => ChatToHandle::dedupe(&Config::chatroom_people(
+                &chatroom_participants,
+                &real_participants,
+            )),
+            // Handles with the same phone number or email address on different services are one person
+            (false, true) => ChatToHandle::dedupe(&Config::chatroom_people(
+                &chatroom_participants,
+                &Handle::dedupe(&handles),
+            )),
+            (false, false) => ChatToHandle::dedupe(&chatroom_participants),
+        };
 
         // Only attempt to create a converter if we need it
         let converter = match options.attachment_manager {
             AttachmentManager::Disabled => None,
             AttachmentManager::Compatible => Converter::determine(),
-            AttachmentManager::Efficient => None,
+            AttachmentManager::Efficient | AttachmentManager::Link if options.thumbnails => {
+                Converter::determine()
+            }
+            AttachmentManager::Efficient | AttachmentManager::Link => None,
         };
+        let media_converter = match options.attachment_manager {
+            AttachmentManager::Disabled => None,
+            AttachmentManager::Compatible => MediaConverter::determine(),
+            AttachmentManager::Efficient | AttachmentManager::Link if options.thumbnails => {
+                MediaConverter::determine()
+            }
+            AttachmentManager::Efficient | AttachmentManager::Link => None,
+        };
+        // Documents are only rendered for their previews
+        let document_converter = match options.thumbnails {
+            true => DocumentConverter::determine(),
+            false => None,
+        };
+
+        // Only track copied attachments if we need to deduplicate them
+        let deduplicator = options
+            .dedupe_attachments
+            .then(AttachmentDeduplicator::default);
 
-        Ok(Config {
+        // The manifest is written as attachments are copied, so create it before the export starts
+        let manifest = match options.attachment_manifest {
+            true => {
+                create_dir_all(&options.export_path).map_err(RuntimeError::DiskError)?;
+                Some(AttachmentManifest::new(&options.export_path)?)
+            }
+            false => None,
+        };
+        let missing_media = match options.missing_media_report {
+            true => {
+                create_dir_all(&options.export_path).map_err(RuntimeError::DiskError)?;
+                Some(MissingMediaReport::new(&options.export_path)?)
+            }
+            false => None,
+        };
+
+        // The template is validated when the options are built
+        let attachment_namer = options
+            .attachment_name
+            .as_deref()
+            .and_then(|template| AttachmentName::parse(template).ok())
+            .map(AttachmentNamer::new);
+
+        let verifier = options.verify.then(AttachmentVerifier::default);
+
+        let mut config = Config {
             chatrooms,
-            real_chatrooms: ChatToHandle::dedupe(&chatroom_participants),
+            real_chatrooms,
             chatroom_participants,
-            real_participants: Handle::dedupe(&participants),
+            real_participants,
+            contact_names,
+            contact_avatars,
             participants,
             tapbacks,
             options,
             offset: get_offset(),
             db: conn,
             converter,
-        })
+            deduplicator,
+            media_converter,
+            document_converter,
+            manifest,
+            missing_media,
+            attachment_namer,
+            verifier,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup,
+            merged_database,
+        };
+        config.select_conversations()?;
+        config.ensure_thread_exists()?;
+        Ok(config)
+    }
+
+    /// Restrict the export to the conversations passed with `--conversation` and `--chat-type`, and skip those passed with `--exclude`
+    ///
+    /// Chats that are merged into a selected or excluded conversation are treated the same way.
+    /// With `--pick`, the conversations that remain are offered in a checklist before the export starts.
+    fn select_conversations(&mut self) -> Result<(), RuntimeError> {
+        if self.options.conversations.is_empty()
+            && self.options.excluded.is_empty()
+            && self.options.chat_type == ChatType::All
+            && !self.options.skip_short_codes
+            && self.options.known_contacts.is_none()
+            && self.options.pinning_file.is_none()
+            && !self.options.pick
+        {
+            return Ok(());
+        }
+
+        let mut selected = if self.options.conversations.is_empty() {
+            self.chatrooms.keys().copied().collect()
+        } else {
+            let mut selected = BTreeSet::new();
+            for term in &self.options.conversations {
+                let matches = self.matching_chats(term);
+                if matches.is_empty() {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "No conversation matches `{term}`!"
+                    )));
+                }
+                selected.extend(matches);
+            }
+            selected
+        };
+
+        for term in &self.options.excluded {
+            let matches = self.matching_chats(term);
+            if matches.is_empty() {
+                warn!("No conversation matches excluded `{term}`");
+            }
+            for chat_id in matches {
+                selected.remove(&chat_id);
+            }
+        }
+
+        if self.options.chat_type != ChatType::All {
+            selected.retain(|chat_id| {
+                self.chatrooms.get(chat_id).is_some_and(|chat| {
+                    let num_participants = self
+                        .chatroom_participants
+                        .get(chat_id)
+                        .map_or(0, BTreeSet::len);
+                    self.options
+                        .chat_type
+                        .includes(chat.is_group(num_participants))
+                })
+            });
+        }
+
+        if let Some(path) = &self.options.pinning_file {
+            let pinned = pinned_identifiers(path).map_err(|why| {
+                RuntimeError::InvalidOptions(format!(
+                    "Unable to read pinned conversations from {path:?}: {why}"
+                ))
+            })?;
+            let pinned_chats: BTreeSet<i32> = self
+                .chatrooms
+                .values()
+                .filter(|chat| chat.is_pinned(&pinned))
+                .map(|chat| chat.rowid)
+                .collect();
+            if pinned_chats.is_empty() {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "No pinned conversations found in {path:?}!"
+                )));
+            }
+            let pinned_chats = self.with_merged_chats(pinned_chats);
+            selected.retain(|chat_id| pinned_chats.contains(chat_id));
+        }
+
+        if self.options.skip_short_codes || self.options.known_contacts.is_some() {
+            selected.retain(|chat_id| !self.is_unwanted_sender(*chat_id));
+        }
+
+        if self.options.pick {
+            let picked = self.with_merged_chats(picker::run(self, &selected)?);
+            selected.retain(|chat_id| picked.contains(chat_id));
+        }
+
+        self.options.query_context.set_selected_chat_ids(selected);
+        Ok(())
+    }
+
+    /// Ensure the message passed with `--thread` exists, so we do not silently export nothing
+    fn ensure_thread_exists(&self) -> Result<(), RuntimeError> {
+        if let Some(guid) = &self.options.query_context.thread_guid {
+            let context = QueryContext {
+                thread_guid: Some(guid.clone()),
+                ..Default::default()
+            };
+            if Message::get_count(&self.db, &context).map_err(RuntimeError::DatabaseError)? == 0 {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "No message has the GUID `{guid}`!"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Determine if a one-on-one conversation is with a short code passed with `--skip-short-codes`,
+    /// or with someone missing from the file passed with `--known-contacts`
+    fn is_unwanted_sender(&self, chat_id: i32) -> bool {
+        let Some(participants) = self.chatroom_participants.get(&chat_id) else {
+            return false;
+        };
+        // Group chats are never skipped, since other people in them may be known
+        if participants.len() != 1 {
+            return false;
+        }
+        let Some(handle) = participants
+            .first()
+            .and_then(|handle_id| self.participants.get(handle_id))
+        else {
+            return false;
+        };
+
+        (self.options.skip_short_codes && is_short_code(handle))
+            || self.options.known_contacts.as_ref().is_some_and(|known| {
+                !known
+                    .iter()
+                    .any(|contact| identifier_matches(handle, contact, self.country_code()))
+            })
+    }
+
+    /// The country calling code given to phone numbers that were saved without one
+    fn country_code(&self) -> &str {
+        self.options
+            .country_code
+            .as_deref()
+            .unwrap_or(DEFAULT_COUNTRY_CODE)
+    }
+
+    /// Get the chats that match a `--conversation` or `--exclude` value, along with the chats merged into them
+    fn matching_chats(&self, term: &str) -> BTreeSet<i32> {
+        let matches: BTreeSet<i32> = self
+            .chatrooms
+            .values()
+            .filter(|chat| conversation_matches(chat, term, self.country_code()))
+            .map(|chat| chat.rowid)
+            .collect();
+        self.with_merged_chats(matches)
+    }
+
+    /// Add the chats that are merged into the given chats in the export
+    fn with_merged_chats(&self, mut matches: BTreeSet<i32>) -> BTreeSet<i32> {
+        let conversations: HashSet<i32> = matches
+            .iter()
+            .filter_map(|chat_id| self.real_chatrooms.get(chat_id))
+            .copied()
+            .collect();
+        matches.extend(
+            self.real_chatrooms
+                .iter()
+                .filter(|(_, real_id)| conversations.contains(real_id))
+                .map(|(chat_id, _)| *chat_id),
+        );
+        matches
     }
 
     /// Ensure there is available disk space for the requested export
@@ -247,7 +774,10 @@ impl Config {
             available_space(&self.options.export_path).map_err(RuntimeError::DiskError)?;
 
         // Validate that there is enough disk space free to write the export
-        if let AttachmentManager::Disabled = self.options.attachment_manager {
+        // Linked attachments do not take up any more space
+        if let AttachmentManager::Disabled | AttachmentManager::Link =
+            self.options.attachment_manager
+        {
             if estimated_export_size >= free_space_at_location {
                 return Err(RuntimeError::NotEnoughAvailableSpace(
                     estimated_export_size,
@@ -267,7 +797,7 @@ impl Config {
             }
         };
 
-        println!(
+        info!(
             "Estimated export size: {}",
             format_file_size(estimated_export_size)
         );
@@ -275,13 +805,140 @@ impl Config {
         Ok(())
     }
 
+    /// Count every message that matches the query context, then print the report and write it to the export path
+    fn run_stats(&self) -> Result<(), RuntimeError> {
+        println!("\niMessage Database Statistics\n");
+        processing();
+        let mut stats = Stats::default();
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        let mut statement = Message::stream_rows(&self.db, &self.options.query_context)
+            .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and count the same message GUID twice
+            if msg.rowid == current_message_row {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            stats.add(self, &msg)?;
+        }
+        done_processing();
+
+        println!("{}", stats.to_table());
+
+        create_dir_all(&self.options.export_path).map_err(RuntimeError::DiskError)?;
+        let path = self.options.export_path.join(STATS_FILENAME);
+        write(&path, stats.to_json())
+            .map_err(|why| RuntimeError::CreateError(why, path.clone()))?;
+        println!("Statistics written to {}", path.display());
+
+        Ok(())
+    }
+
     /// Handles diagnostic tests for database
     fn run_diagnostic(&self) -> Result<(), TableError> {
         println!("\niMessage Database Diagnostics\n");
-        Handle::run_diagnostic(&self.db)?;
-        Message::run_diagnostic(&self.db)?;
-        Attachment::run_diagnostic(&self.db, &self.options.db_path, &self.options.platform)?;
-        ChatToHandle::run_diagnostic(&self.db)?;
+        processing();
+        let handles = Handle::run_diagnostic(&self.db)?;
+        let messages = Message::run_diagnostic(&self.db)?;
+        let attachments =
+            Attachment::run_diagnostic(&self.db, &self.options.db_path, &self.options.platform)?;
+        let chat_handles = ChatToHandle::run_diagnostic(&self.db)?;
+        done_processing();
+
+        if handles.contacts_with_multiple_ids > 0 {
+            println!("Handle diagnostic data:");
+            println!(
+                "    Contacts with more than one ID: {}",
+                handles.contacts_with_multiple_ids
+            );
+        }
+
+        println!("Message diagnostic data:");
+        println!("    Total messages: {}", messages.total_messages);
+        if messages.messages_without_chat > 0 {
+            println!(
+                "    Messages not associated with a chat: {}",
+                messages.messages_without_chat
+            );
+        }
+        if messages.messages_in_multiple_chats > 0 {
+            println!(
+                "    Messages belonging to more than one chat: {}",
+                messages.messages_in_multiple_chats
+            );
+        }
+        if messages.messages_in_missing_chats > 0 {
+            println!(
+                "    Messages joined to a chat that no longer exists: {}",
+                messages.messages_in_missing_chats
+            );
+        }
+
+        if attachments.total_attachments > 0 {
+            println!("Attachment diagnostic data:");
+            println!("    Total attachments: {}", attachments.total_attachments);
+            println!(
+                "        Data referenced in table: {}",
+                format_file_size(attachments.total_bytes)
+            );
+            println!(
+                "        Data present on disk: {}",
+                format_file_size(attachments.bytes_on_disk)
+            );
+            if attachments.missing_files > 0 {
+                println!(
+                    "    Missing files: {} ({:.0}%)",
+                    attachments.missing_files,
+                    (attachments.missing_files as f64 / attachments.total_attachments as f64)
+                        * 100f64
+                );
+                println!("        No path provided: {}", attachments.no_path_provided);
+                println!(
+                    "        No file located, likely offloaded to iCloud: {}",
+                    attachments.offloaded_files()
+                );
+                if !attachments.most_missing_chats.is_empty() {
+                    println!("        Conversations with the most files not located:");
+                    attachments
+                        .most_missing_chats
+                        .iter()
+                        .for_each(|(chat, count)| println!("            {chat}: {count}"));
+                }
+            }
+            if attachments.dangling_joins > 0 {
+                println!(
+                    "    Attachment links to missing messages or attachments: {}",
+                    attachments.dangling_joins
+                );
+            }
+        }
+
+        if chat_handles.chats_with_no_handles > 0 || chat_handles.dangling_handle_joins > 0 {
+            println!("Thread diagnostic data:");
+            if chat_handles.chats_with_no_handles > 0 {
+                println!(
+                    "    Chats with no handles: {}",
+                    chat_handles.chats_with_no_handles
+                );
+            }
+            if chat_handles.dangling_handle_joins > 0 {
+                println!(
+                    "    Participants in a chat or handle that no longer exists: {}",
+                    chat_handles.dangling_handle_joins
+                );
+            }
+        }
 
         // Global Diagnostics
         println!("Global diagnostic data:");
@@ -327,18 +984,23 @@ impl Config {
     pub fn start(&self) -> Result<(), RuntimeError> {
         if self.options.diagnostic {
             self.run_diagnostic().map_err(RuntimeError::DatabaseError)?;
+        } else if self.options.stats {
+            self.run_stats()?;
         } else if let Some(export_type) = &self.options.export_type {
-            // Ensure the path we want to export to exists
-            create_dir_all(&self.options.export_path).map_err(RuntimeError::DiskError)?;
-
-            // Ensure the path we want to copy attachments to exists, if requested
-            if !matches!(self.options.attachment_manager, AttachmentManager::Disabled) {
-                create_dir_all(self.attachment_path()).map_err(RuntimeError::DiskError)?;
-            }
+            // Streamed exports do not write anything to the export path
+            if !self.options.writes_to_stdout() {
+                // Ensure the path we want to export to exists
+                create_dir_all(&self.options.export_path).map_err(RuntimeError::DiskError)?;
+
+                // Ensure the path we want to copy attachments to exists, if requested
+                if !matches!(self.options.attachment_manager, AttachmentManager::Disabled) {
+                    create_dir_all(self.attachment_path()).map_err(RuntimeError::DiskError)?;
+                }
 
-            // Ensure there is enough free disk space to write the export
-            if !self.options.ignore_disk_space {
-                self.ensure_free_space()?;
+                // Ensure there is enough free disk space to write the export
+                if !self.options.ignore_disk_space {
+                    self.ensure_free_space()?;
+                }
             }
 
             // Ensure we have enough file handles to export
@@ -352,9 +1014,67 @@ impl Config {
                 ExportType::Txt => {
                     TXT::new(self)?.iter_messages()?;
                 }
+                ExportType::Markdown => {
+                    Markdown::new(self)?.iter_messages()?;
+                }
+                ExportType::Pdf => {
+                    PDF::new(self)?.iter_messages()?;
+                }
+                ExportType::Epub => {
+                    EPUB::new(self)?.iter_messages()?;
+                }
+                ExportType::Mhtml => {
+                    MHTML::new(self)?.iter_messages()?;
+                }
+                ExportType::Sqlite => {
+                    SQLite::new(self)?.iter_messages()?;
+                }
+                ExportType::Mbox => {
+                    MBOX::new(self)?.iter_messages()?;
+                }
+                ExportType::Maildir => {
+                    Maildir::new(self)?.iter_messages()?;
+                }
+                ExportType::Ndjson => {
+                    NDJSON::new(self)?.iter_messages()?;
+                }
+                ExportType::Obsidian => {
+                    Obsidian::new(self)?.iter_messages()?;
+                }
+                ExportType::DayOne => {
+                    DayOne::new(self)?.iter_messages()?;
+                }
+                ExportType::Matrix => {
+                    Matrix::new(self)?.iter_messages()?;
+                }
+                ExportType::WhatsApp => {
+                    WhatsApp::new(self)?.iter_messages()?;
+                }
+                ExportType::Latex => {
+                    LaTeX::new(self)?.iter_messages()?;
+                }
+                ExportType::Attachments => {
+                    Attachments::new(self)?.iter_messages()?;
+                }
+                #[cfg(feature = "parquet")]
+                ExportType::Parquet => {
+                    Parquet::new(self)?.iter_messages()?;
+                }
+                // Rejected when the options are built
+                #[cfg(not(feature = "parquet"))]
+                ExportType::Parquet => unreachable!(),
             }
         }
-        println!("Done!");
+        if let Some(deduplicator) = &self.deduplicator {
+            deduplicator.report();
+        }
+        if let Some(missing_media) = &self.missing_media {
+            missing_media.report();
+        }
+        if let Some(verifier) = &self.verifier {
+            verifier.verify();
+        }
+        info!("Done!");
         Ok(())
     }
 
@@ -367,10 +1087,13 @@ impl Config {
     ) -> &'a str {
         if is_from_me {
             if self.options.use_caller_id {
-                return destination_caller_id.as_deref().unwrap_or(ME);
+                return self.my_handle(destination_caller_id).unwrap_or(ME);
             }
             return self.options.custom_name.as_deref().unwrap_or(ME);
         } else if let Some(handle_id) = handle_id {
+            if let Some(name) = self.contact_names.get(&handle_id) {
+                return name;
+            }
             return match self.participants.get(&handle_id) {
                 Some(contact) => contact,
                 None => UNKNOWN,
@@ -378,11 +1101,189 @@ impl Config {
         }
         UNKNOWN
     }
+
+    /// Get the phone number or email address of the database owner
+    ///
+    /// This is the handle passed with `--me-handle`, or else the caller ID the message was sent with.
+    pub fn my_handle<'a, 'b: 'a>(
+        &'a self,
+        destination_caller_id: &'b Option<String>,
+    ) -> Option<&'a str> {
+        self.options
+            .me_handle
+            .as_deref()
+            .or(destination_caller_id.as_deref().filter(|id| !id.is_empty()))
+    }
+
+    /// Get the text of a message, for formats that do not render app balloons
+    ///
+    /// Apple Pay messages have no text of their own, so the transaction is described instead, i.e. `Sent $25`.
+    pub fn message_text<'b>(&self, message: &'b Message) -> Option<Cow<'b, str>> {
+        match message.apple_pay_summary(&self.db) {
+            Some(payment) => Some(Cow::Owned(payment)),
+            None => message.text.as_deref().map(Cow::Borrowed),
+        }
+    }
+
+    /// Describe the tapbacks and stickers placed on a message, i.e. `Loved by Alice`, one per reaction
+    ///
+    /// Removed tapbacks are skipped, so formats that cannot show reactions in place can still list them with their message.
+    pub fn tapback_lines(&self, message: &Message) -> Vec<String> {
+        let Some(reactions) = self.tapbacks.get(&message.guid) else {
+            return vec![];
+        };
+
+        // Reactions are grouped by message part in a map, so sort them to list them in the same order every time
+        let mut ordered: Vec<(usize, &Message)> = reactions
+            .iter()
+            .flat_map(|(part, reactions)| reactions.iter().map(move |reaction| (*part, reaction)))
+            .collect();
+        ordered.sort_by_key(|(part, reaction)| (*part, reaction.date));
+
+        ordered
+            .into_iter()
+            .filter_map(|(_, reaction)| {
+                let who = self.who(
+                    reaction.handle_id,
+                    reaction.is_from_me(),
+                    &reaction.destination_caller_id,
+                );
+                match reaction.variant() {
+                    Variant::Tapback(_, true, tapback) => Some(format!("{tapback} by {who}")),
+                    Variant::Sticker(_) => Some(format!("Sticker from {who}")),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Describe when a message was delivered and read, if receipts were requested, i.e. `Delivered May 17, 2022  5:29:42 PM, read by Alice May 17, 2022  6:02:11 PM`
+    ///
+    /// A message only stores one read date, so a message sent to a group chat is only marked as read by `them`.
+    pub fn receipts(&self, message: &Message) -> Option<String> {
+        if !self.options.receipts {
+            return None;
+        }
+
+        let mut receipts = vec![];
+        if message.is_from_me() && message.date_delivered != 0 {
+            receipts.push(format!(
+                "Delivered {}",
+                format(&message.date_delivered(&self.offset))
+            ));
+        }
+        if message.date_read != 0 {
+            let who = if message.is_from_me() {
+                // In a conversation with one other person, they are the one who read it
+                match message
+                    .chat_id
+                    .and_then(|chat_id| self.chatroom_participants.get(&chat_id))
+                    .filter(|participants| participants.len() == 1)
+                    .and_then(|participants| participants.first())
+                {
+                    Some(handle_id) => {
+                        self.who(Some(*handle_id), false, &message.destination_caller_id)
+                    }
+                    None => "them",
+                }
+            } else {
+                self.options.custom_name.as_deref().unwrap_or("you")
+            };
+            receipts.push(format!(
+                "read by {who} {}",
+                format(&message.date_read(&self.offset))
+            ));
+        }
+
+        if receipts.is_empty() {
+            return None;
+        }
+        let mut receipts = receipts.join(", ");
+        // Capitalize the first receipt, which is only lowercase if the message was not delivered
+        if let Some(rest) = receipts.strip_prefix("read") {
+            receipts = format!("Read{rest}");
+        }
+        Some(receipts)
+    }
+
+    /// Get the name of a mentioned contact, who is stored as their phone number or email address
+    ///
+    /// Participants are named from contacts the same way [`Config::who`] names senders, or else shown as the one handle that was mentioned.
+    /// Contacts that are not in the export are shown as they were stored.
+    pub fn mention_name<'a>(&'a self, mentioned: &'a str) -> &'a str {
+        self.participants
+            .iter()
+            .filter_map(|(handle_id, contact)| {
+                // Handles that belong to the same person are joined with spaces
+                let identifier = contact.split(' ').find(|identifier| {
+                    identifier_matches(identifier, mentioned, self.country_code())
+                })?;
+                Some((handle_id, identifier))
+            })
+            // Participants are not stored in order, so use the first handle that matches to name a mention the same way every time
+            .min_by_key(|(handle_id, _)| **handle_id)
+            .map(|(handle_id, identifier)| {
+                self.contact_names
+                    .get(handle_id)
+                    .map_or(identifier, String::as_str)
+            })
+            .unwrap_or(mentioned)
+    }
+}
+
+/// Determine if a chat is the conversation described by a `--conversation` value
+///
+/// Group chats match their name, and all chats match their identifier, which is the phone
+/// number or email address of the other person in a one-on-one conversation.
+fn conversation_matches(chat: &Chat, term: &str, country_code: &str) -> bool {
+    let term = term.trim();
+    if chat
+        .display_name()
+        .is_some_and(|name| name.to_lowercase() == term.to_lowercase())
+    {
+        return true;
+    }
+
+    identifier_matches(&chat.chat_identifier, term, country_code)
+}
+
+/// Determine if a phone number or email address is the one described by `term`, ignoring formatting
+///
+/// Phone numbers without a country code are given `country_code` before they are compared.
+fn identifier_matches(identifier: &str, term: &str, country_code: &str) -> bool {
+    if normalize_handle(identifier, country_code) == normalize_handle(term, country_code) {
+        return true;
+    }
+    let identifier = normalize_identifier(identifier);
+    let term = normalize_identifier(term);
+    // Phone numbers may be given without their country code
+    identifier == term
+        || (term.len() >= 7
+            && term.chars().all(|c| c.is_ascii_digit())
+            && identifier.ends_with(&term))
+}
+
+/// Determine if a handle is an SMS short code, i.e. `12345`, which businesses use to send automated messages
+fn is_short_code(identifier: &str) -> bool {
+    let identifier = normalize_identifier(identifier);
+    (5..=6).contains(&identifier.len()) && identifier.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Remove the formatting people add to phone numbers, i.e. `(555) 867-5309`
+fn normalize_identifier(identifier: &str) -> String {
+    identifier
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '-' | '(' | ')'))
+        .flat_map(char::to_lowercase)
+        .collect()
 }
 
 #[cfg(test)]
 mod filename_tests {
-    use crate::{app::attachment_manager::AttachmentManager, Config, Options};
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        Config, Options,
+    };
     use imessage_database::{
         tables::{
             chat::Chat,
@@ -409,15 +1310,50 @@ mod filename_tests {
             use_caller_id: false,
             platform: Platform::macOS,
             ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
         }
     }
 
     fn fake_chat() -> Chat {
         Chat {
             rowid: 0,
+            guid: String::new(),
             chat_identifier: "Default".to_string(),
             service_name: Some(String::new()),
             display_name: None,
+            style: None,
         }
     }
 
@@ -429,11 +1365,23 @@ mod filename_tests {
             chatroom_participants: HashMap::new(),
             participants: HashMap::new(),
             real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
             tapbacks: HashMap::new(),
             options,
             offset: 0,
             db: connection,
             converter: Some(crate::app::converter::Converter::Sips),
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
         }
     }
 
@@ -603,6 +1551,30 @@ mod filename_tests {
         assert_eq!(filename, "Person 10, Person 11");
     }
 
+    #[test]
+    fn can_get_filename_chat_contact_names() {
+        let options = fake_options();
+        let mut app = fake_app(options);
+
+        // Create chat
+        let chat = fake_chat();
+
+        // Create participant data
+        app.participants.insert(10, "+15558675309".to_string());
+        app.participants.insert(11, "Person 11".to_string());
+        app.contact_names.insert(10, "Mom".to_string());
+
+        // Add participants
+        let mut people = BTreeSet::new();
+        people.insert(10);
+        people.insert(11);
+        app.chatroom_participants.insert(chat.rowid, people);
+
+        // Get filename
+        let filename = app.filename(&chat);
+        assert_eq!(filename, "Mom, Person 11");
+    }
+
     #[test]
     fn can_get_filename_chat_no_participants() {
         let options = fake_options();
@@ -619,12 +1591,28 @@ mod filename_tests {
 
 #[cfg(test)]
 mod who_tests {
-    use crate::{app::attachment_manager::AttachmentManager, Config, Options};
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        Config, Options,
+    };
     use imessage_database::{
-        tables::{chat::Chat, messages::Message, table::get_connection},
-        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
+        tables::{
+            chat::Chat,
+            chat_handle::ChatToHandle,
+            handle::Handle,
+            messages::Message,
+            table::{get_connection, Deduplicate},
+        },
+        util::{
+            contacts::ContactNames, dates::format, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+    use std::{
+        collections::{BTreeSet, HashMap},
+        fs,
+        path::PathBuf,
     };
-    use std::{collections::HashMap, path::PathBuf};
 
     fn fake_options() -> Options {
         Options {
@@ -640,15 +1628,50 @@ mod who_tests {
             use_caller_id: false,
             platform: Platform::macOS,
             ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
         }
     }
 
     fn fake_chat() -> Chat {
         Chat {
             rowid: 0,
+            guid: String::new(),
             chat_identifier: "Default".to_string(),
             service_name: Some(String::new()),
             display_name: None,
+            style: None,
         }
     }
 
@@ -660,11 +1683,23 @@ mod who_tests {
             chatroom_participants: HashMap::new(),
             participants: HashMap::new(),
             real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
             tapbacks: HashMap::new(),
             options,
             offset: 0,
             db: connection,
             converter: Some(crate::app::converter::Converter::Sips),
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
         }
     }
 
@@ -695,6 +1730,8 @@ mod who_tests {
             thread_originator_guid: None,
             thread_originator_part: None,
             date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
             associated_message_emoji: None,
             chat_id: None,
             num_attachments: 0,
@@ -718,6 +1755,97 @@ mod who_tests {
         assert_eq!(who, "Person 10".to_string());
     }
 
+    #[test]
+    fn can_get_who_them_contact_name() {
+        let options = fake_options();
+        let mut app = fake_app(options);
+
+        // Create participant data
+        app.participants.insert(10, "+15558675309".to_string());
+        app.contact_names.insert(10, "Mom".to_string());
+
+        // Get participant name
+        let who = app.who(Some(10), false, &None);
+        assert_eq!(who, "Mom".to_string());
+    }
+
+    #[test]
+    fn can_dedupe_people() {
+        let mut participants = HashMap::new();
+        participants.insert(10, "+15558675309".to_string());
+        participants.insert(11, "jane@example.com".to_string());
+        participants.insert(12, "+15550001111".to_string());
+
+        let mut contacts = ContactNames::default();
+        contacts.insert("+15558675309", "Jane Doe", "jane");
+        contacts.insert("jane@example.com", "Jane", "jane");
+        contacts.insert("+15550001111", "Jane Doe", "other jane");
+
+        let real_participants = Config::dedupe_people(&participants, &contacts);
+        assert_eq!(real_participants.get(&10), real_participants.get(&11));
+        assert_ne!(real_participants.get(&10), real_participants.get(&12));
+    }
+
+    #[test]
+    fn can_normalize_participants() {
+        let mut participants = HashMap::new();
+        participants.insert(10, "+1(555)867-5309".to_string());
+        participants.insert(11, "5558675309".to_string());
+        participants.insert(12, "+15558675309 Jane@Example.com".to_string());
+        participants.insert(13, "+15550001111".to_string());
+
+        let handles = Config::normalize_participants(&participants, "1");
+        assert_eq!(
+            handles.get(&12),
+            Some(&"+15558675309 jane@example.com".to_string())
+        );
+
+        let real_participants = Config::dedupe_people(&handles, &ContactNames::default());
+        assert_eq!(real_participants.get(&10), real_participants.get(&11));
+        assert_ne!(real_participants.get(&10), real_participants.get(&13));
+    }
+
+    #[test]
+    fn can_merge_chatroom_people() {
+        let mut chatroom_participants = HashMap::new();
+        chatroom_participants.insert(1, BTreeSet::from([10]));
+        chatroom_participants.insert(2, BTreeSet::from([11]));
+        chatroom_participants.insert(3, BTreeSet::from([12]));
+
+        let mut real_participants = HashMap::new();
+        real_participants.insert(10, 0);
+        real_participants.insert(11, 0);
+        real_participants.insert(12, 1);
+
+        let real_chatrooms = ChatToHandle::dedupe(&Config::chatroom_people(
+            &chatroom_participants,
+            &real_participants,
+        ));
+        assert_eq!(real_chatrooms.get(&1), real_chatrooms.get(&2));
+        assert_ne!(real_chatrooms.get(&1), real_chatrooms.get(&3));
+    }
+
+    #[test]
+    fn can_merge_chatroom_services() {
+        // The same phone number has a handle for iMessage and a handle for SMS
+        let mut participants = HashMap::new();
+        participants.insert(10, "+15558675309".to_string());
+        participants.insert(11, "+15558675309".to_string());
+        participants.insert(12, "+15550001111".to_string());
+
+        let mut chatroom_participants = HashMap::new();
+        chatroom_participants.insert(1, BTreeSet::from([10]));
+        chatroom_participants.insert(2, BTreeSet::from([11]));
+        chatroom_participants.insert(3, BTreeSet::from([11, 12]));
+
+        let real_chatrooms = ChatToHandle::dedupe(&Config::chatroom_people(
+            &chatroom_participants,
+            &Handle::dedupe(&participants),
+        ));
+        assert_eq!(real_chatrooms.get(&1), real_chatrooms.get(&2));
+        assert_ne!(real_chatrooms.get(&1), real_chatrooms.get(&3));
+    }
+
     #[test]
     fn can_get_who_them_missing() {
         let options = fake_options();
@@ -750,6 +1878,178 @@ mod who_tests {
         assert_eq!(who, "test".to_string());
     }
 
+    #[test]
+    fn can_get_tapback_lines() {
+        let options = fake_options();
+        let mut app = fake_app(options);
+        app.participants.insert(10, "Person 10".to_string());
+
+        let mut message = blank();
+        message.guid = "target".to_string();
+
+        let mut loved = blank();
+        loved.handle_id = Some(10);
+        loved.associated_message_type = Some(2000);
+        loved.associated_message_guid = Some("p:0/target".to_string());
+
+        // Removed tapbacks are not listed
+        let mut removed = blank();
+        removed.is_from_me = true;
+        removed.associated_message_type = Some(3001);
+        removed.associated_message_guid = Some("p:0/target".to_string());
+
+        app.tapbacks.insert(
+            "target".to_string(),
+            HashMap::from([(0, vec![loved, removed])]),
+        );
+
+        assert_eq!(app.tapback_lines(&message), vec!["Loved by Person 10"]);
+        assert!(app.tapback_lines(&blank()).is_empty());
+    }
+
+    #[test]
+    fn can_get_tapback_lines_in_order() {
+        let options = fake_options();
+        let mut app = fake_app(options);
+        app.participants.insert(10, "Person 10".to_string());
+        app.participants.insert(11, "Person 11".to_string());
+
+        let mut message = blank();
+        message.guid = "target".to_string();
+
+        let tapback = |handle_id, associated_message_type, part, date| {
+            let mut tapback = blank();
+            tapback.handle_id = Some(handle_id);
+            tapback.associated_message_type = Some(associated_message_type);
+            tapback.associated_message_guid = Some(format!("p:{part}/target"));
+            tapback.date = date;
+            tapback
+        };
+
+        app.tapbacks.insert(
+            "target".to_string(),
+            HashMap::from([
+                (1, vec![tapback(11, 2003, 1, 30), tapback(10, 2001, 1, 20)]),
+                (0, vec![tapback(11, 2000, 0, 40)]),
+            ]),
+        );
+
+        assert_eq!(
+            app.tapback_lines(&message),
+            vec![
+                "Loved by Person 11",
+                "Liked by Person 10",
+                "Laughed by Person 11"
+            ]
+        );
+    }
+
+    #[test]
+    fn can_get_receipts() {
+        let mut options = fake_options();
+        options.receipts = true;
+        let mut app = fake_app(options);
+        app.participants.insert(10, "Person 10".to_string());
+        app.chatroom_participants.insert(0, BTreeSet::from([10]));
+
+        let mut message = blank();
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+        message.date_delivered = 674526582885055488;
+        message.date_read = 674530231992568192;
+
+        assert_eq!(
+            app.receipts(&message),
+            Some(format!(
+                "Delivered {}, read by Person 10 {}",
+                format(&message.date_delivered(&app.offset)),
+                format(&message.date_read(&app.offset))
+            ))
+        );
+
+        // In a group chat, the reader is not known
+        app.chatroom_participants
+            .insert(0, BTreeSet::from([10, 11]));
+        message.date_delivered = 0;
+        assert_eq!(
+            app.receipts(&message),
+            Some(format!(
+                "Read by them {}",
+                format(&message.date_read(&app.offset))
+            ))
+        );
+
+        // Received messages are read by the database owner
+        message.is_from_me = false;
+        assert!(app.receipts(&message).unwrap().starts_with("Read by you "));
+    }
+
+    #[test]
+    fn cant_get_receipts_without_option() {
+        let app = fake_app(fake_options());
+
+        let mut message = blank();
+        message.date_read = 674530231992568192;
+
+        assert_eq!(app.receipts(&message), None);
+    }
+
+    #[test]
+    fn can_get_mention_name() {
+        let options = fake_options();
+        let mut app = fake_app(options);
+
+        // Create participant data
+        app.participants
+            .insert(10, "+15558675309 test@example.com".to_string());
+
+        assert_eq!(app.mention_name("+15558675309"), "+15558675309");
+        assert_eq!(app.mention_name("test@example.com"), "test@example.com");
+        assert_eq!(app.mention_name("other@example.com"), "other@example.com");
+    }
+
+    #[test]
+    fn can_get_mention_name_from_vcard() {
+        let options = fake_options();
+        let mut app = fake_app(options);
+
+        // Create participant data
+        app.participants
+            .insert(10, "+15558675309 test@example.com".to_string());
+        app.participants.insert(11, "+15550001111".to_string());
+
+        // Read contacts
+        let path = PathBuf::from("/tmp/mention_contacts.vcf");
+        fs::write(
+            &path,
+            "BEGIN:VCARD\nFN:Jane Doe\nTEL:(555) 867-5309\nEMAIL:test@example.com\nEND:VCARD\n",
+        )
+        .unwrap();
+        let contacts = ContactNames::from_path(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        app.contact_names = Config::match_names(&contacts, &app.participants);
+
+        assert_eq!(app.mention_name("+15558675309"), "Jane Doe");
+        assert_eq!(app.mention_name("test@example.com"), "Jane Doe");
+        assert_eq!(app.mention_name("+15550001111"), "+15550001111");
+    }
+
+    #[test]
+    fn can_get_mention_name_first_handle() {
+        let options = fake_options();
+        let mut app = fake_app(options);
+
+        // Create participant data with several handles for the same phone number
+        for handle_id in [12, 10, 11, 13] {
+            app.participants
+                .insert(handle_id, "+15558675309".to_string());
+            app.contact_names
+                .insert(handle_id, format!("Contact {handle_id}"));
+        }
+
+        assert_eq!(app.mention_name("+15558675309"), "Contact 10");
+    }
+
     #[test]
     fn can_get_who_me_custom() {
         let mut options = fake_options();
@@ -874,7 +2174,10 @@ mod who_tests {
 
 #[cfg(test)]
 mod directory_tests {
-    use crate::{app::attachment_manager::AttachmentManager, Config, Options};
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        Config, Options,
+    };
     use imessage_database::{
         tables::{attachment::Attachment, table::get_connection},
         util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
@@ -895,6 +2198,39 @@ mod directory_tests {
             use_caller_id: false,
             platform: Platform::macOS,
             ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
         }
     }
 
@@ -906,11 +2242,23 @@ mod directory_tests {
             chatroom_participants: HashMap::new(),
             participants: HashMap::new(),
             real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
             tapbacks: HashMap::new(),
             options,
             offset: 0,
             db: connection,
             converter: Some(crate::app::converter::Converter::Sips),
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
         }
     }
 
@@ -925,6 +2273,7 @@ mod directory_tests {
             is_sticker: false,
             hide_attachment: 0,
             copied_path: None,
+            backup_root: None,
         }
     }
 
@@ -1016,3 +2365,83 @@ mod directory_tests {
         assert_eq!(result, expected);
     }
 }
+
+#[cfg(test)]
+mod conversation_tests {
+    use crate::app::runtime::{conversation_matches, identifier_matches, is_short_code};
+    use imessage_database::tables::chat::Chat;
+
+    fn fake_chat(chat_identifier: &str, display_name: Option<&str>) -> Chat {
+        Chat {
+            rowid: 0,
+            guid: String::new(),
+            chat_identifier: chat_identifier.to_string(),
+            service_name: Some(String::new()),
+            display_name: display_name.map(String::from),
+            style: None,
+        }
+    }
+
+    #[test]
+    fn can_match_identifier() {
+        let chat = fake_chat("+15558675309", None);
+        assert!(conversation_matches(&chat, "+15558675309", "1"));
+        assert!(conversation_matches(&chat, "+1 (555) 867-5309", "1"));
+    }
+
+    #[test]
+    fn can_match_phone_without_country_code() {
+        let chat = fake_chat("+15558675309", None);
+        assert!(conversation_matches(&chat, "555-867-5309", "1"));
+        assert!(!conversation_matches(&chat, "5309", "1"));
+    }
+
+    #[test]
+    fn can_match_phone_saved_without_country_code() {
+        let chat = fake_chat("5558675309", None);
+        assert!(conversation_matches(&chat, "+15558675309", "1"));
+        assert!(!conversation_matches(&chat, "+445558675309", "1"));
+
+        let chat = fake_chat("07911 123456", None);
+        assert!(conversation_matches(&chat, "+447911123456", "44"));
+    }
+
+    #[test]
+    fn can_match_email_any_case() {
+        let chat = fake_chat("person@example.com", None);
+        assert!(conversation_matches(&chat, "Person@Example.com", "1"));
+        assert!(!conversation_matches(&chat, "other@example.com", "1"));
+    }
+
+    #[test]
+    fn can_match_display_name() {
+        let chat = fake_chat("chat123456789", Some("Family Chat"));
+        assert!(conversation_matches(&chat, "family chat", "1"));
+        assert!(conversation_matches(&chat, "chat123456789", "1"));
+        assert!(!conversation_matches(&chat, "Family", "1"));
+    }
+
+    #[test]
+    fn can_match_known_contact() {
+        assert!(identifier_matches("+15558675309", "(555) 867-5309", "1"));
+        assert!(identifier_matches(
+            "person@example.com",
+            "PERSON@example.com",
+            "1"
+        ));
+        assert!(!identifier_matches(
+            "+15558675309",
+            "person@example.com",
+            "1"
+        ));
+    }
+
+    #[test]
+    fn can_detect_short_code() {
+        assert!(is_short_code("12345"));
+        assert!(is_short_code("876-543"));
+        assert!(!is_short_code("1234"));
+        assert!(!is_short_code("+15558675309"));
+        assert!(!is_short_code("12a45"));
+    }
+}
@@ -30,6 +30,37 @@ static HTML_DISALLOWED_CHARS: LazyLock<HashMap<&char, &str>> = LazyLock::new(||
     map.insert(&' ', "&nbsp;");
     map
 });
+/// Characters that carry meaning in Markdown
+static MARKDOWN_SPECIAL_CHARS: LazyLock<HashSet<&char>> = LazyLock::new(|| {
+    let mut set = HashSet::new();
+    set.insert(&'\\');
+    set.insert(&'`');
+    set.insert(&'*');
+    set.insert(&'_');
+    set.insert(&'[');
+    set.insert(&']');
+    set.insert(&'<');
+    set.insert(&'>');
+    set.insert(&'#');
+    set.insert(&'|');
+    set.insert(&'~');
+    set
+});
+/// Characters that carry meaning in LaTeX, and the text that typesets them literally
+static LATEX_SPECIAL_CHARS: LazyLock<HashMap<&char, &str>> = LazyLock::new(|| {
+    let mut map = HashMap::new();
+    map.insert(&'\\', "\\textbackslash{}");
+    map.insert(&'{', "\\{");
+    map.insert(&'}', "\\}");
+    map.insert(&'$', "\\$");
+    map.insert(&'&', "\\&");
+    map.insert(&'#', "\\#");
+    map.insert(&'%', "\\%");
+    map.insert(&'_', "\\_");
+    map.insert(&'^', "\\textasciicircum{}");
+    map.insert(&'~', "\\textasciitilde{}");
+    map
+});
 /// The character to replace disallowed chars with
 const FILENAME_REPLACEMENT_CHAR: char = '_';
 
@@ -64,6 +95,69 @@ pub fn sanitize_html(input: &str) -> Cow<str> {
     Cow::Borrowed(input)
 }
 
+/// Escapes Markdown special characters in [this list](MARKDOWN_SPECIAL_CHARS) in the input string.
+pub fn sanitize_markdown(input: &str) -> Cow<str> {
+    for (idx, c) in input.char_indices() {
+        if MARKDOWN_SPECIAL_CHARS.contains(&c) {
+            let mut res = String::from(&input[..idx]);
+            input[idx..].chars().for_each(|c| {
+                if MARKDOWN_SPECIAL_CHARS.contains(&c) {
+                    res.push('\\');
+                }
+                res.push(c);
+            });
+            return Cow::Owned(res);
+        }
+    }
+    Cow::Borrowed(input)
+}
+
+/// Escapes LaTeX special characters in [this list](LATEX_SPECIAL_CHARS) in the input string.
+pub fn sanitize_latex(input: &str) -> Cow<str> {
+    for (idx, c) in input.char_indices() {
+        if LATEX_SPECIAL_CHARS.contains_key(&c) {
+            let mut res = String::from(&input[..idx]);
+            input[idx..]
+                .chars()
+                .for_each(|c| match LATEX_SPECIAL_CHARS.get(&c) {
+                    Some(replacement) => res.push_str(replacement),
+                    None => res.push(c),
+                });
+            return Cow::Owned(res);
+        }
+    }
+    Cow::Borrowed(input)
+}
+
+/// Escapes the characters JSON strings cannot contain: quotes, backslashes, and control characters.
+pub fn sanitize_json(input: &str) -> Cow<str> {
+    let needs_escape = |c: char| c == '"' || c == '\\' || c.is_control();
+    match input.find(needs_escape) {
+        Some(idx) => {
+            let mut res = String::from(&input[..idx]);
+            input[idx..].chars().for_each(|c| match c {
+                '"' => res.push_str("\\\""),
+                '\\' => res.push_str("\\\\"),
+                '\n' => res.push_str("\\n"),
+                '\r' => res.push_str("\\r"),
+                '\t' => res.push_str("\\t"),
+                c if c.is_control() => res.push_str(&format!("\\u{:04x}", c as u32)),
+                c => res.push(c),
+            });
+            Cow::Owned(res)
+        }
+        None => Cow::Borrowed(input),
+    }
+}
+
+/// Quotes a CSV field if it contains a comma, quote, or line break, doubling any quotes inside it.
+pub fn sanitize_csv(input: &str) -> Cow<str> {
+    if input.contains([',', '"', '\n', '\r']) {
+        return Cow::Owned(format!("\"{}\"", input.replace('"', "\"\"")));
+    }
+    Cow::Borrowed(input)
+}
+
 #[cfg(test)]
 mod test_filename {
     use crate::app::sanitizers::sanitize_filename;
@@ -146,3 +240,89 @@ mod tests {
         );
     }
 }
+
+#[cfg(test)]
+mod test_markdown {
+    use crate::app::sanitizers::sanitize_markdown;
+
+    #[test]
+    fn doesnt_sanitize_plain_text() {
+        assert_eq!(&sanitize_markdown("Hello world"), "Hello world");
+    }
+
+    #[test]
+    fn can_sanitize_emphasis() {
+        assert_eq!(&sanitize_markdown("*bold* _it_"), "\\*bold\\* \\_it\\_");
+    }
+
+    #[test]
+    fn can_sanitize_block_chars() {
+        assert_eq!(&sanitize_markdown("# > `code`"), "\\# \\> \\`code\\`");
+    }
+}
+
+#[cfg(test)]
+mod test_json {
+    use crate::app::sanitizers::sanitize_json;
+
+    #[test]
+    fn doesnt_sanitize_plain_text() {
+        assert_eq!(&sanitize_json("Hello world 👋"), "Hello world 👋");
+    }
+
+    #[test]
+    fn can_sanitize_quotes() {
+        assert_eq!(&sanitize_json("say \"hi\" \\o/"), "say \\\"hi\\\" \\\\o/");
+    }
+
+    #[test]
+    fn can_sanitize_control_chars() {
+        assert_eq!(&sanitize_json("a\nb\tc\u{7}"), "a\\nb\\tc\\u0007");
+    }
+}
+
+#[cfg(test)]
+mod test_csv {
+    use crate::app::sanitizers::sanitize_csv;
+
+    #[test]
+    fn doesnt_sanitize_plain_text() {
+        assert_eq!(&sanitize_csv("IMG_0001.jpeg"), "IMG_0001.jpeg");
+    }
+
+    #[test]
+    fn can_sanitize_commas() {
+        assert_eq!(&sanitize_csv("Ada, Grace"), "\"Ada, Grace\"");
+    }
+
+    #[test]
+    fn can_sanitize_quotes() {
+        assert_eq!(&sanitize_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
+
+#[cfg(test)]
+mod test_latex {
+    use crate::app::sanitizers::sanitize_latex;
+
+    #[test]
+    fn doesnt_sanitize_plain_text() {
+        assert_eq!(&sanitize_latex("Hello world"), "Hello world");
+    }
+
+    #[test]
+    fn can_sanitize_special_chars() {
+        assert_eq!(
+            &sanitize_latex("50% of $10 & {more}"),
+            "50\\% of \\$10 \\& \\{more\\}"
+        );
+    }
+
+    #[test]
+    fn can_sanitize_commands() {
+        assert_eq!(
+            &sanitize_latex("\\emph{x} ~ a^b"),
+            "\\textbackslash{}emph\\{x\\} \\textasciitilde{} a\\textasciicircum{}b"
+        );
+    }
+}
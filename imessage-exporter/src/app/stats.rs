@@ -0,0 +1,368 @@
+/*!
+ Contains logic for counting messages and attachments to build a statistics report.
+
+ The report is printed as a table and written to `stats.json` in the export directory.
+*/
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, Local};
+
+use imessage_database::{
+    tables::{attachment::Attachment, messages::Message},
+    util::{dates::readable_diff, size::format_file_size},
+};
+
+use crate::app::{error::RuntimeError, runtime::Config, sanitizers::sanitize_json};
+
+/// Name of the file the report is written to
+pub const STATS_FILENAME: &str = "stats.json";
+/// The number of days listed as the busiest
+const BUSIEST_DAYS: usize = 10;
+
+/// Totals for a single person's replies
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Responses {
+    /// The number of replies
+    count: u64,
+    /// The sum of the seconds between each reply and the message before it
+    seconds: i64,
+}
+
+impl Responses {
+    /// The average number of seconds a reply took
+    fn average(&self) -> i64 {
+        match self.count {
+            0 => 0,
+            count => self.seconds / count as i64,
+        }
+    }
+}
+
+/// Counts of messages and attachments, collected one message at a time
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// The number of messages counted
+    messages: u64,
+    /// The number of attachments sent
+    attachments: u64,
+    /// The total size of the attachments sent, in bytes
+    attachment_bytes: u64,
+    /// Map of conversation name to the number of messages in it
+    chats: HashMap<String, u64>,
+    /// Map of sender name to the number of messages they sent
+    senders: HashMap<String, u64>,
+    /// Map of year to the number of messages sent in it
+    years: BTreeMap<String, u64>,
+    /// Map of month, i.e. `2022-05`, to the number of messages sent in it
+    months: BTreeMap<String, u64>,
+    /// Map of day, i.e. `2022-05-17`, to the number of messages sent on it
+    days: HashMap<String, u64>,
+    /// Map of sender name to their replies
+    responses: HashMap<String, Responses>,
+    /// Map of deduplicated chat ID to the sender and timestamp of its latest message
+    latest: HashMap<i32, (String, i64)>,
+}
+
+impl Stats {
+    /// Count a message
+    ///
+    /// Tapbacks and announcements are not counted. A message counts as a reply when the message before it
+    /// in the same conversation was sent by someone else.
+    pub fn add(&mut self, config: &Config, message: &Message) -> Result<(), RuntimeError> {
+        if message.is_tapback() || message.is_announcement() {
+            return Ok(());
+        }
+        self.messages += 1;
+
+        let sender = config
+            .who(
+                message.handle_id,
+                message.is_from_me(),
+                &message.destination_caller_id,
+            )
+            .to_string();
+        *self.senders.entry(sender.clone()).or_default() += 1;
+
+        if let Some((chatroom, real_id)) = config.conversation(message) {
+            *self.chats.entry(config.filename(chatroom)).or_default() += 1;
+
+            if let Ok(date) = message.date(&config.offset) {
+                let timestamp = date.timestamp();
+                if let Some((previous, previous_timestamp)) = self.latest.get(real_id) {
+                    if *previous != sender && timestamp >= *previous_timestamp {
+                        let responses = self.responses.entry(sender.clone()).or_default();
+                        responses.count += 1;
+                        responses.seconds += timestamp - previous_timestamp;
+                    }
+                }
+                self.latest.insert(*real_id, (sender, timestamp));
+            }
+        }
+
+        if let Ok(date) = message.date(&config.offset) {
+            self.add_date(&date);
+        }
+
+        if message.has_attachments() {
+            let attachments = Attachment::from_message(&config.db, message)
+                .map_err(RuntimeError::DatabaseError)?;
+            for attachment in attachments {
+                self.attachments += 1;
+                self.attachment_bytes += attachment.total_bytes;
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a message in the year, month, and day it was sent
+    fn add_date(&mut self, date: &DateTime<Local>) {
+        *self.years.entry(date.format("%Y").to_string()).or_default() += 1;
+        *self
+            .months
+            .entry(date.format("%Y-%m").to_string())
+            .or_default() += 1;
+        *self
+            .days
+            .entry(date.format("%Y-%m-%d").to_string())
+            .or_default() += 1;
+    }
+
+    /// Sort counts from largest to smallest, breaking ties by name
+    fn ranked(counts: &HashMap<String, u64>) -> Vec<(&str, u64)> {
+        let mut ranked: Vec<(&str, u64)> = counts
+            .iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect();
+        ranked.sort_by(|(a_name, a_count), (b_name, b_count)| {
+            b_count.cmp(a_count).then(a_name.cmp(b_name))
+        });
+        ranked
+    }
+
+    /// The days with the most messages, busiest first
+    fn busiest_days(&self) -> Vec<(&str, u64)> {
+        let mut days = Stats::ranked(&self.days);
+        days.truncate(BUSIEST_DAYS);
+        days
+    }
+
+    /// Each sender's replies, sorted by name
+    fn response_times(&self) -> Vec<(&str, &Responses)> {
+        let mut responses: Vec<(&str, &Responses)> = self
+            .responses
+            .iter()
+            .map(|(name, responses)| (name.as_str(), responses))
+            .collect();
+        responses.sort_by_key(|(name, _)| *name);
+        responses
+    }
+
+    /// Build the JSON report
+    pub fn to_json(&self) -> String {
+        let counts = |counts: Vec<(&str, u64)>, key: &str| -> String {
+            counts
+                .iter()
+                .map(|(name, count)| {
+                    format!(
+                        "{{\"{key}\":\"{}\",\"messages\":{count}}}",
+                        sanitize_json(name)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let periods = |periods: &BTreeMap<String, u64>| -> String {
+            periods
+                .iter()
+                .map(|(period, count)| format!("\"{period}\":{count}"))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let responses = self
+            .response_times()
+            .iter()
+            .map(|(name, responses)| {
+                format!(
+                    "{{\"sender\":\"{}\",\"responses\":{},\"average_seconds\":{}}}",
+                    sanitize_json(name),
+                    responses.count,
+                    responses.average()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            "{{\"messages\":{},\"attachments\":{{\"count\":{},\"bytes\":{}}},\"chats\":[{}],\"senders\":[{}],\"years\":{{{}}},\"months\":{{{}}},\"busiest_days\":[{}],\"response_times\":[{}]}}\n",
+            self.messages,
+            self.attachments,
+            self.attachment_bytes,
+            counts(Stats::ranked(&self.chats), "chat"),
+            counts(Stats::ranked(&self.senders), "sender"),
+            periods(&self.years),
+            periods(&self.months),
+            counts(self.busiest_days(), "date"),
+            responses,
+        )
+    }
+
+    /// Build the report as tables for the terminal
+    pub fn to_table(&self) -> String {
+        let mut out_s = String::new();
+        out_s.push_str(&format!("Messages: {}\n", self.messages));
+        out_s.push_str(&format!(
+            "Attachments: {} ({})\n",
+            self.attachments,
+            format_file_size(self.attachment_bytes)
+        ));
+
+        let tables: [(&str, Vec<(&str, u64)>); 4] = [
+            ("Conversation", Stats::ranked(&self.chats)),
+            ("Sender", Stats::ranked(&self.senders)),
+            (
+                "Year",
+                self.years.iter().map(|(y, c)| (y.as_str(), *c)).collect(),
+            ),
+            ("Busiest day", self.busiest_days()),
+        ];
+        for (heading, rows) in tables {
+            let rows: Vec<(&str, String)> = rows
+                .into_iter()
+                .map(|(name, count)| (name, count.to_string()))
+                .collect();
+            out_s.push('\n');
+            out_s.push_str(&table(heading, "Messages", &rows));
+        }
+
+        let responses: Vec<(&str, String)> = self
+            .response_times()
+            .into_iter()
+            .map(|(name, responses)| (name, readable_seconds(responses.average())))
+            .collect();
+        if !responses.is_empty() {
+            out_s.push('\n');
+            out_s.push_str(&table("Sender", "Average response time", &responses));
+        }
+        out_s
+    }
+}
+
+/// Lay out rows of a name and a value in two aligned columns
+fn table(name_heading: &str, value_heading: &str, rows: &[(&str, String)]) -> String {
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .chain([name_heading.chars().count()])
+        .max()
+        .unwrap_or_default();
+
+    let mut out_s = format!("{name_heading:<width$}  {value_heading}\n");
+    out_s.push_str(&format!(
+        "{}  {}\n",
+        "-".repeat(width),
+        "-".repeat(value_heading.chars().count())
+    ));
+    for (name, value) in rows {
+        out_s.push_str(&format!("{name:<width$}  {value}\n"));
+    }
+    out_s
+}
+
+/// Describe a number of seconds, i.e. `1 hour, 5 minutes`
+fn readable_seconds(seconds: i64) -> String {
+    match DateTime::from_timestamp(0, 0) {
+        Some(start) => {
+            let start = start.with_timezone(&Local);
+            readable_diff(Ok(start), Ok(start + Duration::seconds(seconds)))
+                .filter(|diff| !diff.is_empty())
+                .unwrap_or_else(|| String::from("0 seconds"))
+        }
+        None => format!("{seconds} seconds"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{Local, TimeZone};
+
+    use crate::app::stats::{table, Responses, Stats};
+
+    fn sample() -> Stats {
+        let mut stats = Stats {
+            messages: 3,
+            attachments: 1,
+            attachment_bytes: 2048,
+            ..Default::default()
+        };
+        stats.chats.insert("Family".to_string(), 2);
+        stats.chats.insert("Bob".to_string(), 1);
+        stats.senders.insert("Me".to_string(), 2);
+        stats.senders.insert("Bob".to_string(), 1);
+        stats.add_date(&Local.with_ymd_and_hms(2022, 5, 17, 12, 0, 0).unwrap());
+        stats.add_date(&Local.with_ymd_and_hms(2022, 5, 17, 13, 0, 0).unwrap());
+        stats.add_date(&Local.with_ymd_and_hms(2023, 1, 2, 9, 0, 0).unwrap());
+        stats.responses.insert(
+            "Bob".to_string(),
+            Responses {
+                count: 2,
+                seconds: 600,
+            },
+        );
+        stats
+    }
+
+    #[test]
+    fn can_count_dates() {
+        let stats = sample();
+        assert_eq!(stats.years.get("2022"), Some(&2));
+        assert_eq!(stats.months.get("2023-01"), Some(&1));
+        assert_eq!(
+            stats.busiest_days(),
+            vec![("2022-05-17", 2), ("2023-01-02", 1)]
+        );
+    }
+
+    #[test]
+    fn can_rank() {
+        let mut counts = HashMap::new();
+        counts.insert("b".to_string(), 1);
+        counts.insert("a".to_string(), 1);
+        counts.insert("c".to_string(), 5);
+        assert_eq!(Stats::ranked(&counts), vec![("c", 5), ("a", 1), ("b", 1)]);
+    }
+
+    #[test]
+    fn can_build_json() {
+        let expected = concat!(
+            "{\"messages\":3,\"attachments\":{\"count\":1,\"bytes\":2048},",
+            "\"chats\":[{\"chat\":\"Family\",\"messages\":2},{\"chat\":\"Bob\",\"messages\":1}],",
+            "\"senders\":[{\"sender\":\"Me\",\"messages\":2},{\"sender\":\"Bob\",\"messages\":1}],",
+            "\"years\":{\"2022\":2,\"2023\":1},",
+            "\"months\":{\"2022-05\":2,\"2023-01\":1},",
+            "\"busiest_days\":[{\"date\":\"2022-05-17\",\"messages\":2},{\"date\":\"2023-01-02\",\"messages\":1}],",
+            "\"response_times\":[{\"sender\":\"Bob\",\"responses\":2,\"average_seconds\":300}]}\n"
+        );
+        assert_eq!(sample().to_json(), expected);
+    }
+
+    #[test]
+    fn can_build_table() {
+        let actual = table(
+            "Sender",
+            "Messages",
+            &[("Me", "2".to_string()), ("Jane Doe", "1".to_string())],
+        );
+        let expected = "Sender    Messages\n--------  --------\nMe        2\nJane Doe  1\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_report() {
+        let report = sample().to_table();
+        assert!(report.starts_with("Messages: 3\nAttachments: 1 (2.00 KB)\n"));
+        assert!(report.contains("Bob     5 minutes\n"));
+    }
+}
@@ -0,0 +1,132 @@
+/*!
+ Contains logic for the temporary folders that hold decrypted or merged copies of the messages being exported.
+
+ Those copies are as sensitive as the source data, so each folder gets a random name that only the current user
+ can read, and it is deleted when the export finishes, fails, panics, or is interrupted with Ctrl-C.
+ Release builds abort on panic instead of unwinding, so `Drop` alone is not enough.
+*/
+
+use std::{
+    fs::remove_dir_all,
+    panic,
+    path::{Path, PathBuf},
+    process,
+    sync::{Mutex, Once},
+};
+
+use log::warn;
+use tempfile::{Builder, TempDir};
+
+use crate::app::error::RuntimeError;
+
+/// The exit code of a process interrupted by `SIGINT`
+const INTERRUPTED: i32 = 130;
+
+/// Folders to delete if the process exits without dropping them
+static LIVE_FOLDERS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+/// Ensures the cleanup handlers are installed once
+static INSTALL_CLEANUP: Once = Once::new();
+
+/// A private temporary folder, deleted when it is dropped or the process is stopped early
+#[derive(Debug)]
+pub struct TempFolder {
+    dir: TempDir,
+}
+
+impl TempFolder {
+    /// Create a folder in the system temporary directory whose name starts with `prefix`
+    ///
+    /// The folder is created with a random name and, on Unix, is only readable by the current user.
+    pub fn new(prefix: &str) -> Result<Self, RuntimeError> {
+        INSTALL_CLEANUP.call_once(install_cleanup);
+
+        let dir = Builder::new()
+            .prefix(prefix)
+            .tempdir()
+            .map_err(RuntimeError::DiskError)?;
+        #[cfg(unix)]
+        {
+            use std::{
+                fs::{set_permissions, Permissions},
+                os::unix::fs::PermissionsExt,
+            };
+            set_permissions(dir.path(), Permissions::from_mode(0o700))
+                .map_err(|why| RuntimeError::CreateError(why, dir.path().to_path_buf()))?;
+        }
+
+        if let Ok(mut folders) = LIVE_FOLDERS.lock() {
+            folders.push(dir.path().to_path_buf());
+        }
+        Ok(Self { dir })
+    }
+
+    /// Get the path to the folder
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+impl Drop for TempFolder {
+    fn drop(&mut self) {
+        // `TempDir` deletes the folder itself, so it only needs to be forgotten here
+        if let Ok(mut folders) = LIVE_FOLDERS.lock() {
+            folders.retain(|folder| folder != self.dir.path());
+        }
+    }
+}
+
+/// Delete every folder that has not been dropped yet
+fn remove_live_folders() {
+    if let Ok(mut folders) = LIVE_FOLDERS.lock() {
+        for folder in folders.drain(..) {
+            let _ = remove_dir_all(folder);
+        }
+    }
+}
+
+/// Delete the folders when the process panics or is interrupted
+fn install_cleanup() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        remove_live_folders();
+        default_hook(info);
+    }));
+
+    if let Err(why) = ctrlc::set_handler(|| {
+        remove_live_folders();
+        process::exit(INTERRUPTED);
+    }) {
+        warn!("Unable to delete temporary files if the export is interrupted: {why}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::temp_folder::{TempFolder, LIVE_FOLDERS};
+
+    #[test]
+    fn can_create_private_folder() {
+        let folder = TempFolder::new("imessage-exporter-test-").unwrap();
+        let path = folder.path().to_path_buf();
+        assert!(path.is_dir());
+        assert!(LIVE_FOLDERS.lock().unwrap().contains(&path));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = path.metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        drop(folder);
+        assert!(!path.exists());
+        assert!(!LIVE_FOLDERS.lock().unwrap().contains(&path));
+    }
+
+    #[test]
+    fn can_create_unique_folders() {
+        let first = TempFolder::new("imessage-exporter-test-").unwrap();
+        let second = TempFolder::new("imessage-exporter-test-").unwrap();
+        assert_ne!(first.path(), second.path());
+    }
+}
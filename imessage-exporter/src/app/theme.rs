@@ -0,0 +1,53 @@
+/*!
+ Contains data structures used to describe the color themes of HTML exports.
+*/
+
+use std::path::PathBuf;
+
+/// Represents the colors used to style HTML and EPUB exports
+#[derive(Debug, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// Follow the reader's system setting using `prefers-color-scheme`
+    #[default]
+    Auto,
+    /// Always use light colors
+    Light,
+    /// Always use dark colors
+    Dark,
+    /// Follow the reader's system setting, then apply a stylesheet that overrides the palette
+    Custom(PathBuf),
+}
+
+impl Theme {
+    /// Create an instance of the enum given user input; anything that is not a built-in theme is a path to a stylesheet
+    pub fn from_cli(theme: &str) -> Self {
+        match theme.to_lowercase().as_str() {
+            "auto" => Self::Auto,
+            "light" => Self::Light,
+            "dark" => Self::Dark,
+            _ => Self::Custom(PathBuf::from(theme)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::app::theme::Theme;
+
+    #[test]
+    fn can_parse_builtin_any_case() {
+        assert_eq!(Theme::from_cli("auto"), Theme::Auto);
+        assert_eq!(Theme::from_cli("Light"), Theme::Light);
+        assert_eq!(Theme::from_cli("DARK"), Theme::Dark);
+    }
+
+    #[test]
+    fn can_parse_custom() {
+        assert_eq!(
+            Theme::from_cli("themes/Solarized.css"),
+            Theme::Custom(PathBuf::from("themes/Solarized.css"))
+        );
+    }
+}
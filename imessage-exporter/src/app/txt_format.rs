@@ -0,0 +1,224 @@
+/*!
+ Parses the format strings that lay out messages in TXT exports.
+
+ A format string is literal text with these placeholders:
+
+ - `{date}`: the time the message was sent, followed by when it was read, if known
+ - `{date:<format>}`: the time the message was sent, using a [`strftime`](https://docs.rs/chrono/latest/chrono/format/strftime/index.html) format
+ - `{sender}`: the name of the sender
+ - `{text}`: the body of the message, including attachments, tapbacks, and replies
+
+ `\n` and `\t` are replaced with a newline and a tab, and `{{` and `}}` write literal braces.
+*/
+
+use chrono::format::{Item, StrftimeItems};
+
+/// Format string that matches the original TXT export layout
+pub const DEFAULT_TXT_FORMAT: &str = "{date}\n{sender}\n{text}";
+
+/// Part of a parsed format string
+#[derive(Debug, PartialEq, Eq)]
+enum Segment {
+    /// Text written as-is
+    Literal(String),
+    /// Date the message was sent, with an optional `strftime` format
+    Date(Option<String>),
+    /// Name of the sender
+    Sender,
+    /// Body of the message
+    Text,
+}
+
+/// A parsed format string for TXT exports
+#[derive(Debug, PartialEq, Eq)]
+pub struct TxtFormat {
+    segments: Vec<Segment>,
+}
+
+impl TxtFormat {
+    /// Parse a format string, describing the problem if it is invalid
+    pub fn parse(format: &str) -> Result<Self, String> {
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let mut chars = format.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next_if(|next| matches!(next, 'n' | 't' | '\\')) {
+                    Some('n') => literal.push('\n'),
+                    Some('t') => literal.push('\t'),
+                    _ => literal.push('\\'),
+                },
+                '{' if chars.next_if_eq(&'{').is_some() => literal.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => literal.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(format!("Placeholder `{{{name}` is missing a `}}`"))
+                            }
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(TxtFormat::placeholder(&name)?);
+                }
+                '}' => return Err(String::from("Unmatched `}`; use `}}` for a literal brace")),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(TxtFormat { segments })
+    }
+
+    /// Parse the name between the braces of a placeholder
+    fn placeholder(name: &str) -> Result<Segment, String> {
+        match name.split_once(':') {
+            Some(("date", date_format)) => {
+                if StrftimeItems::new(date_format).any(|item| item == Item::Error) {
+                    return Err(format!("`{date_format}` is not a valid date format"));
+                }
+                Ok(Segment::Date(Some(date_format.to_string())))
+            }
+            None if name == "date" => Ok(Segment::Date(None)),
+            None if name == "sender" => Ok(Segment::Sender),
+            None if name == "text" => Ok(Segment::Text),
+            _ => Err(format!(
+                "`{{{name}}}` is not a valid placeholder; use `{{date}}`, `{{sender}}`, or `{{text}}`"
+            )),
+        }
+    }
+
+    /// Get the first custom date format in the format string, if any
+    pub fn date_format(&self) -> Option<&str> {
+        self.segments.iter().find_map(|segment| match segment {
+            Segment::Date(Some(date_format)) => Some(date_format.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Render a message, ending with a newline
+    ///
+    /// `date` is called with each placeholder's custom date format. Every line after a newline in
+    /// the format string is prefixed with `indent`, and `text` is expected to be indented already.
+    pub fn render(
+        &self,
+        date: impl Fn(Option<&str>) -> String,
+        sender: &str,
+        text: &str,
+        indent: &str,
+    ) -> String {
+        let mut rendered = String::from(indent);
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => {
+                    rendered.push_str(&literal.replace('\n', &format!("\n{indent}")))
+                }
+                Segment::Date(date_format) => rendered.push_str(&date(date_format.as_deref())),
+                Segment::Sender => rendered.push_str(sender),
+                Segment::Text => rendered.push_str(
+                    text.strip_prefix(indent)
+                        .unwrap_or(text)
+                        .trim_end_matches('\n'),
+                ),
+            }
+        }
+        if !rendered.ends_with('\n') {
+            rendered.push('\n');
+        }
+        rendered
+    }
+}
+
+impl Default for TxtFormat {
+    fn default() -> Self {
+        // The default format is always valid
+        TxtFormat::parse(DEFAULT_TXT_FORMAT).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Segment, TxtFormat};
+
+    fn date(date_format: Option<&str>) -> String {
+        date_format.unwrap_or("May 17, 2022").to_string()
+    }
+
+    #[test]
+    fn can_parse_default() {
+        assert_eq!(
+            TxtFormat::default().segments,
+            vec![
+                Segment::Date(None),
+                Segment::Literal("\n".to_string()),
+                Segment::Sender,
+                Segment::Literal("\n".to_string()),
+                Segment::Text,
+            ]
+        );
+    }
+
+    #[test]
+    fn can_parse_escapes() {
+        let format = TxtFormat::parse("{{{sender}}}\\t{text}").unwrap();
+        assert_eq!(
+            format.segments,
+            vec![
+                Segment::Literal("{".to_string()),
+                Segment::Sender,
+                Segment::Literal("}\t".to_string()),
+                Segment::Text,
+            ]
+        );
+    }
+
+    #[test]
+    fn can_parse_date_format() {
+        let format = TxtFormat::parse("[{date:%Y-%m-%d %H:%M}] {text}").unwrap();
+        assert_eq!(format.date_format(), Some("%Y-%m-%d %H:%M"));
+    }
+
+    #[test]
+    fn cant_parse_invalid() {
+        assert!(TxtFormat::parse("{name}").is_err());
+        assert!(TxtFormat::parse("{date:%Q}").is_err());
+        assert!(TxtFormat::parse("{text").is_err());
+        assert!(TxtFormat::parse("text}").is_err());
+    }
+
+    #[test]
+    fn can_render_default() {
+        let format = TxtFormat::default();
+        assert_eq!(
+            format.render(date, "Me", "Hello\nworld\n", ""),
+            "May 17, 2022\nMe\nHello\nworld\n"
+        );
+        assert_eq!(format.render(date, "Me", "", ""), "May 17, 2022\nMe\n");
+    }
+
+    #[test]
+    fn can_render_custom() {
+        let format = TxtFormat::parse("{date:%Y} <{sender}> {text}").unwrap();
+        assert_eq!(
+            format.render(date, "Me", "Hello world\n", ""),
+            "%Y <Me> Hello world\n"
+        );
+    }
+
+    #[test]
+    fn can_render_indented() {
+        let format = TxtFormat::default();
+        assert_eq!(
+            format.render(date, "Me", "    Hello\n    world\n", "    "),
+            "    May 17, 2022\n    Me\n    Hello\n    world\n"
+        );
+    }
+}
@@ -0,0 +1,234 @@
+/*!
+ Contains logic for checking that copied attachments were written completely.
+
+ Copies to unreliable drives can be cut short or corrupted without an error being reported,
+ so each copy is read back after the export and compared to the original.
+*/
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::{Display, Formatter, Result},
+    fs::metadata,
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+
+use imessage_database::util::size::format_file_size;
+
+use crate::app::{manifest::hash_file, progress::build_progress_bar_export};
+
+/// Represents a copied attachment that is checked after the export
+#[derive(Debug)]
+struct CopiedFile {
+    /// Where the original attachment is stored
+    from: PathBuf,
+    /// If true, the copy should be identical to the original
+    ///
+    /// Converted copies and copies with their metadata removed are only checked for content.
+    exact: bool,
+}
+
+/// Reasons a copied attachment fails verification
+#[derive(Debug, PartialEq, Eq)]
+pub enum VerifyFailure {
+    /// The copy is not in the export
+    Missing,
+    /// The copy holds no data
+    Empty,
+    /// The copy is a different size than the original, i.e. it was truncated
+    Size(u64, u64),
+    /// The copy is the same size as the original, but its contents differ
+    Hash,
+    /// The original or the copy could not be read
+    Unreadable,
+}
+
+impl Display for VerifyFailure {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            VerifyFailure::Missing => write!(fmt, "copy not found"),
+            VerifyFailure::Empty => write!(fmt, "copy is empty"),
+            VerifyFailure::Size(expected, actual) => write!(
+                fmt,
+                "copy is {}, but the original is {}",
+                format_file_size(*actual),
+                format_file_size(*expected)
+            ),
+            VerifyFailure::Hash => write!(fmt, "copy does not match the original"),
+            VerifyFailure::Unreadable => write!(fmt, "unable to read the file"),
+        }
+    }
+}
+
+/// Tracks the attachments copied during an export so they can be checked once it finishes
+#[derive(Debug, Default)]
+pub struct AttachmentVerifier {
+    /// Map of each copy to the original it was made from
+    copies: RefCell<BTreeMap<PathBuf, CopiedFile>>,
+}
+
+impl AttachmentVerifier {
+    /// Remember a copy to check after the export
+    pub fn record(&self, from: &Path, to: &Path, exact: bool) {
+        self.copies.borrow_mut().insert(
+            to.to_path_buf(),
+            CopiedFile {
+                from: from.to_path_buf(),
+                exact,
+            },
+        );
+    }
+
+    /// Check a copy at its new location after it was moved
+    pub fn rename(&self, from: &Path, to: &Path) {
+        let mut copies = self.copies.borrow_mut();
+        if let Some(copy) = copies.remove(from) {
+            copies.insert(to.to_path_buf(), copy);
+        }
+    }
+
+    /// Check every copy, printing the ones that failed
+    pub fn verify(&self) {
+        let copies = self.copies.borrow();
+        if copies.is_empty() {
+            return;
+        }
+
+        info!("Verifying {} copied attachments...", copies.len());
+        let pb = build_progress_bar_export(copies.len() as u64);
+        let mut failures = vec![];
+        for (to, copy) in copies.iter() {
+            if let Some(failure) = verify_copy(&copy.from, to, copy.exact) {
+                failures.push((to, failure));
+            }
+            pb.inc(1);
+        }
+        pb.finish_and_clear();
+
+        if failures.is_empty() {
+            info!("All {} copied attachments verified", copies.len());
+            return;
+        }
+        warn!(
+            "{} of {} copied attachments failed verification:",
+            failures.len(),
+            copies.len()
+        );
+        for (to, failure) in failures {
+            warn!("{}: {failure}", to.display());
+        }
+    }
+}
+
+/// Compare a copy to its original, returning why it failed if it is not intact
+fn verify_copy(from: &Path, to: &Path, exact: bool) -> Option<VerifyFailure> {
+    let Ok(copied) = metadata(to) else {
+        return Some(VerifyFailure::Missing);
+    };
+    if copied.len() == 0 {
+        return Some(VerifyFailure::Empty);
+    }
+    if !exact {
+        return None;
+    }
+
+    let Ok(original) = metadata(from) else {
+        return Some(VerifyFailure::Unreadable);
+    };
+    if original.len() != copied.len() {
+        return Some(VerifyFailure::Size(original.len(), copied.len()));
+    }
+    match (hash_file(from), hash_file(to)) {
+        (Some((_, expected)), Some((_, actual))) if expected == actual => None,
+        (Some(_), Some(_)) => Some(VerifyFailure::Hash),
+        _ => Some(VerifyFailure::Unreadable),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, write},
+    };
+
+    use crate::app::verify::{verify_copy, VerifyFailure};
+
+    #[test]
+    fn can_verify_copy() {
+        let dir = temp_dir().join("imessage-exporter-verify-intact");
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("original.txt");
+        let to = dir.join("copy.txt");
+        write(&from, "meme").unwrap();
+        write(&to, "meme").unwrap();
+
+        assert_eq!(verify_copy(&from, &to, true), None);
+    }
+
+    #[test]
+    fn cant_verify_truncated_copy() {
+        let dir = temp_dir().join("imessage-exporter-verify-truncated");
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("original.txt");
+        let to = dir.join("copy.txt");
+        write(&from, "meme").unwrap();
+        write(&to, "me").unwrap();
+
+        assert_eq!(
+            verify_copy(&from, &to, true),
+            Some(VerifyFailure::Size(4, 2))
+        );
+    }
+
+    #[test]
+    fn cant_verify_corrupted_copy() {
+        let dir = temp_dir().join("imessage-exporter-verify-corrupted");
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("original.txt");
+        let to = dir.join("copy.txt");
+        write(&from, "meme").unwrap();
+        write(&to, "mime").unwrap();
+
+        assert_eq!(verify_copy(&from, &to, true), Some(VerifyFailure::Hash));
+    }
+
+    #[test]
+    fn cant_verify_missing_copy() {
+        let dir = temp_dir().join("imessage-exporter-verify-missing");
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("original.txt");
+        write(&from, "meme").unwrap();
+
+        assert_eq!(
+            verify_copy(&from, &dir.join("copy.txt"), true),
+            Some(VerifyFailure::Missing)
+        );
+    }
+
+    #[test]
+    fn can_verify_converted_copy() {
+        let dir = temp_dir().join("imessage-exporter-verify-converted");
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("original.heic");
+        let to = dir.join("copy.jpeg");
+        write(&from, "heic data").unwrap();
+        write(&to, "jpeg").unwrap();
+
+        assert_eq!(verify_copy(&from, &to, false), None);
+    }
+
+    #[test]
+    fn cant_verify_empty_converted_copy() {
+        let dir = temp_dir().join("imessage-exporter-verify-empty");
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("original.heic");
+        let to = dir.join("copy.jpeg");
+        write(&from, "heic data").unwrap();
+        write(&to, "").unwrap();
+
+        assert_eq!(verify_copy(&from, &to, false), Some(VerifyFailure::Empty));
+    }
+}
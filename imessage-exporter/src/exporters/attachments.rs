@@ -0,0 +1,203 @@
+use std::{
+    fs::{create_dir_all, read_dir, remove_dir, rename},
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+
+use crate::app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        attachment::Attachment,
+        messages::Message,
+        table::{Table, ORPHANED},
+    },
+};
+
+/// Name of the folder for attachments whose message date cannot be read
+const UNKNOWN_MONTH: &str = "Unknown";
+
+/// Exports only the attachments, without any transcripts
+///
+/// Each attachment is copied to a folder for its conversation, with a folder inside it for the month
+/// it was sent, i.e. `Family - 12/2022-05/4821.jpeg`.
+pub struct Attachments<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+}
+
+impl<'a> Attachments<'a> {
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(Attachments { config })
+    }
+
+    /// Copy the attachments of every message into the folders for their conversations and months
+    pub fn iter_messages(&self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting attachments to {}...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            if msg.has_attachments() {
+                let mut attachments = Attachment::from_message(&self.config.db, &msg)
+                    .map_err(RuntimeError::DatabaseError)?;
+                for attachment in attachments.iter_mut() {
+                    self.export_attachment(attachment, &msg)?;
+                }
+            }
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        // Attachments are copied to the usual attachment folder first, which is left empty
+        remove_empty_dirs(&self.config.attachment_path());
+        Ok(())
+    }
+
+    /// Copy an attachment, then move it and the video of a Live Photo into the folder for its month
+    fn export_attachment(
+        &self,
+        attachment: &mut Attachment,
+        message: &Message,
+    ) -> Result<(), RuntimeError> {
+        let manager = &self.config.options.attachment_manager;
+        if manager
+            .handle_attachment(message, attachment, self.config)
+            .is_none()
+        {
+            return Ok(());
+        }
+
+        let folder = self.month_folder(message);
+        create_dir_all(&folder).map_err(|err| RuntimeError::CreateError(err, folder.clone()))?;
+
+        if let Some(copy) = &attachment.copied_path {
+            self.move_into(copy, &folder);
+        }
+        if let Some(video) = manager.handle_live_photo(message, attachment, self.config) {
+            self.move_into(&video, &folder);
+        }
+        Ok(())
+    }
+
+    /// Get the folder for the conversation and month a message was sent in
+    fn month_folder(&self, message: &Message) -> PathBuf {
+        let chat = match self.config.conversation(message) {
+            Some((chatroom, _)) => self.config.filename(chatroom),
+            None => String::from(ORPHANED),
+        };
+        let month = match message.date(&self.config.offset) {
+            Ok(date) => date.format("%Y-%m").to_string(),
+            Err(_) => String::from(UNKNOWN_MONTH),
+        };
+        self.config.options.export_path.join(chat).join(month)
+    }
+
+    /// Move a copied file into `folder`, keeping its name
+    ///
+    /// Only files in the attachment folder are moved; a duplicate that could not be linked
+    /// refers to a copy that was already moved into the folder of an earlier message.
+    fn move_into(&self, from: &Path, folder: &Path) {
+        if !from.starts_with(self.config.attachment_path()) {
+            return;
+        }
+        let Some(name) = from.file_name() else {
+            return;
+        };
+        let to = folder.join(name);
+        match rename(from, &to) {
+            Ok(()) => {
+                if let Some(deduplicator) = &self.config.deduplicator {
+                    deduplicator.rename(from, &to);
+                }
+                if let Some(verifier) = &self.config.verifier {
+                    verifier.rename(from, &to);
+                }
+            }
+            Err(why) => warn!("Unable to move {from:?} to {to:?}: {why}"),
+        }
+    }
+}
+
+/// Remove a folder and the folders inside it, if they hold no files
+fn remove_empty_dirs(path: &Path) {
+    if let Ok(entries) = read_dir(path) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                remove_empty_dirs(&entry.path());
+            }
+        }
+    }
+    // Fails if the folder still holds files, which are kept
+    let _ = remove_dir(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, write},
+    };
+
+    use crate::exporters::attachments::remove_empty_dirs;
+
+    #[test]
+    fn can_remove_empty_dirs() {
+        let root = temp_dir().join("imessage-exporter-attachments-empty");
+        create_dir_all(root.join("1")).unwrap();
+        create_dir_all(root.join("2/3")).unwrap();
+
+        remove_empty_dirs(&root);
+
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn cant_remove_dirs_with_files() {
+        let root = temp_dir().join("imessage-exporter-attachments-files");
+        create_dir_all(root.join("1")).unwrap();
+        create_dir_all(root.join("2")).unwrap();
+        write(root.join("2/4821.jpeg"), "jpeg").unwrap();
+
+        remove_empty_dirs(&root);
+
+        assert!(!root.join("1").exists());
+        assert!(root.join("2/4821.jpeg").exists());
+    }
+}
@@ -0,0 +1,709 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::read,
+    path::PathBuf,
+};
+
+use log::info;
+
+use crate::{
+    app::{
+        error::RuntimeError, progress::build_progress_bar_export, runtime::Config,
+        sanitizers::sanitize_markdown,
+    },
+    exporters::{
+        epub::archive::Archive,
+        ndjson::{object, string},
+    },
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::Announcement,
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::Message,
+        table::{Table, ME, ORPHANED, YOU},
+    },
+    util::dates::get_local_time,
+};
+
+/// Name of the archive the journal is written to
+const JOURNAL_FILENAME: &str = "Journal.zip";
+/// Name of the document inside the archive that lists every entry
+const JOURNAL_DOCUMENT: &str = "Journal.json";
+/// Version of the Day One import format
+const JOURNAL_VERSION: &str = "1.0";
+/// Tag added to every entry so imported messages are easy to find
+const TAG: &str = "iMessage";
+/// Name of the day for messages whose date cannot be read
+const UNKNOWN_DAY: &str = "Unknown";
+
+/// A photo that has been stored in the journal archive
+struct Photo {
+    /// Identifier used to place the photo in the entry's text
+    identifier: String,
+    /// MD5 digest of the photo, which Day One uses to find it in the archive
+    md5: String,
+    /// File type of the photo, i.e. `jpeg`
+    kind: String,
+}
+
+/// A journal entry for a single conversation on a single day
+struct Entry {
+    /// Name of the conversation, used as the entry's title and tag
+    title: String,
+    /// Date of the first message in the entry, in UTC
+    created: String,
+    /// Markdown text of the entry
+    text: String,
+    /// Photos referenced by the entry's text, in order
+    photos: Vec<Photo>,
+}
+
+/// Exports conversations in Day One's JSON import format
+///
+/// Each conversation gets one journal entry per day it has messages. Entries are written to
+/// `Journal.json` inside a ZIP archive, with image attachments stored in its `photos` folder.
+pub struct DayOne<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// The archive the journal and its photos are written to
+    archive: Archive,
+    /// The day currently being exported, as `YYYY-MM-DD`
+    day: Option<String>,
+    /// Map of resolved chatroom file location to its entry for the current day
+    entries: BTreeMap<String, Entry>,
+    /// JSON objects for the entries of days that have been exported
+    journal: Vec<String>,
+    /// MD5 digests of the photos already stored in the archive
+    stored: HashSet<String>,
+}
+
+impl<'a> DayOne<'a> {
+    /// Create the journal archive
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut path = config.options.export_path.clone();
+        path.push(JOURNAL_FILENAME);
+        let archive = Archive::new(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+
+        Ok(DayOne {
+            config,
+            archive,
+            day: None,
+            entries: BTreeMap::new(),
+            journal: vec![],
+            stored: HashSet::new(),
+        })
+    }
+
+    /// Add every message to the entry for its conversation and day, then write the journal
+    pub fn iter_messages(mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as a Day One journal...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Tapbacks are listed under the message they belong to
+            if !msg.is_tapback() {
+                let (text, photos) = match msg.is_announcement() {
+                    true => (self.format_announcement(&msg), vec![]),
+                    false => self.format_message(&msg)?,
+                };
+                let entry = self.get_or_create_entry(&msg);
+                entry.text.push_str(&text);
+                entry.photos.extend(photos);
+            }
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        info!("Writing Day One journal...");
+        self.close_day();
+        self.write_journal()
+    }
+
+    /// Get the entry for the message's conversation and day, starting a new one if needed
+    fn get_or_create_entry(&mut self, message: &Message) -> &mut Entry {
+        let day = match message.date(&self.config.offset) {
+            Ok(date) => date.format("%Y-%m-%d").to_string(),
+            Err(_) => String::from(UNKNOWN_DAY),
+        };
+
+        // Messages are sorted by date, so the previous day's entries are complete
+        if self.day.as_ref() != Some(&day) {
+            self.close_day();
+            self.day = Some(day);
+        }
+
+        let (filename, title) = match self.config.conversation(message) {
+            Some((chatroom, _)) => (self.config.filename(chatroom), chatroom.name()),
+            None => (String::from(ORPHANED), ORPHANED),
+        };
+
+        self.entries.entry(filename).or_insert_with(|| Entry {
+            title: title.to_string(),
+            created: timestamp(message.date, &self.config.offset),
+            text: format!("# {}\n\n", sanitize_markdown(title)),
+            photos: vec![],
+        })
+    }
+
+    /// Encode the entries for the day that was being exported
+    fn close_day(&mut self) {
+        let day = self.day.as_deref().unwrap_or(UNKNOWN_DAY);
+        for (filename, entry) in std::mem::take(&mut self.entries) {
+            let photos: Vec<String> = entry
+                .photos
+                .iter()
+                .enumerate()
+                .map(|(order, photo)| {
+                    object(&[
+                        ("identifier", string(&photo.identifier)),
+                        ("md5", string(&photo.md5)),
+                        ("type", string(&photo.kind)),
+                        ("orderInEntry", order.to_string()),
+                    ])
+                })
+                .collect();
+
+            self.journal.push(object(&[
+                ("uuid", string(&identifier(&format!("{filename}/{day}")))),
+                ("creationDate", string(&entry.created)),
+                ("modifiedDate", string(&entry.created)),
+                ("starred", String::from("false")),
+                ("text", string(entry.text.trim_end())),
+                (
+                    "tags",
+                    format!("[{},{}]", string(TAG), string(&entry.title)),
+                ),
+                ("photos", format!("[{}]", photos.join(","))),
+            ]));
+        }
+    }
+
+    /// Add the journal document to the archive and finish writing it
+    fn write_journal(mut self) -> Result<(), RuntimeError> {
+        let document = object(&[
+            ("metadata", object(&[("version", string(JOURNAL_VERSION))])),
+            ("entries", format!("[{}]", self.journal.join(","))),
+        ]);
+        self.archive
+            .add(JOURNAL_DOCUMENT, document.as_bytes())
+            .map_err(RuntimeError::DiskError)?;
+        self.archive.finish().map_err(RuntimeError::DiskError)
+    }
+
+    /// Format a message as a Markdown paragraph, storing any photos it contains in the archive
+    fn format_message(&mut self, message: &Message) -> Result<(String, Vec<Photo>), RuntimeError> {
+        let who = sanitize_markdown(self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        ))
+        .to_string();
+        let time = match message.date(&self.config.offset) {
+            Ok(date) => date.format("%H:%M").to_string(),
+            Err(_) => String::new(),
+        };
+
+        let mut formatted_message = format!("**{who}** {time}\n");
+        if let Some(text) = self.config.message_text(message) {
+            // Attachments are represented by a placeholder character in the message text
+            let text = text.replace('\u{FFFC}', "");
+            let text = text.trim();
+            if !text.is_empty() {
+                formatted_message.push_str(&sanitize_markdown(text));
+                formatted_message.push('\n');
+            }
+        }
+
+        let mut photos = vec![];
+        if message.has_attachments() {
+            let mut attachments = Attachment::from_message(&self.config.db, message)
+                .map_err(RuntimeError::DatabaseError)?;
+            for attachment in &mut attachments {
+                match self.store_photo(message, attachment)? {
+                    Some(photo) => {
+                        formatted_message
+                            .push_str(&format!("![](dayone-moment://{})\n", photo.identifier));
+                        photos.push(photo);
+                    }
+                    None => formatted_message.push_str(&format!(
+                        "*Attachment: {}*\n",
+                        sanitize_markdown(attachment.filename())
+                    )),
+                }
+            }
+            if let Some(transcription) = message.audio_transcription() {
+                formatted_message.push_str(&format!(
+                    "*Transcription: {}*\n",
+                    sanitize_markdown(transcription)
+                ));
+            }
+        }
+        for tapback in self.config.tapback_lines(message) {
+            formatted_message.push_str(&format!("*{}*\n", sanitize_markdown(&tapback)));
+        }
+        formatted_message.push('\n');
+
+        Ok((formatted_message, photos))
+    }
+
+    /// Format an announcement as an italicized line
+    fn format_announcement(&self, message: &Message) -> String {
+        let mut who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+        let who = sanitize_markdown(who);
+
+        match message.get_announcement() {
+            Some(Announcement::NameChange(name)) => format!(
+                "*{who} renamed the conversation to {}*\n\n",
+                sanitize_markdown(name)
+            ),
+            Some(Announcement::PhotoChange) => format!("*{who} changed the group photo.*\n\n"),
+            Some(Announcement::PhotoRemoved) => format!("*{who} removed the group photo.*\n\n"),
+            Some(Announcement::ParticipantAdded(handle)) => format!(
+                "*{who} added {} to the conversation.*\n\n",
+                sanitize_markdown(self.config.who(
+                    Some(handle),
+                    false,
+                    &message.destination_caller_id
+                ))
+            ),
+            Some(Announcement::ParticipantRemoved(handle)) => format!(
+                "*{who} removed {} from the conversation.*\n\n",
+                sanitize_markdown(self.config.who(
+                    Some(handle),
+                    false,
+                    &message.destination_caller_id
+                ))
+            ),
+            Some(Announcement::ParticipantLeft) => format!("*{who} left the conversation.*\n\n"),
+            Some(Announcement::AudioMessageKept) => format!("*{who} kept an audio message.*\n\n"),
+            Some(Announcement::UnknownEvent(num)) => {
+                format!("*{who} performed unknown system event {num}.*\n\n")
+            }
+            Some(Announcement::Unknown(num)) => {
+                format!("*{who} performed unknown action {num}.*\n\n")
+            }
+            Some(Announcement::FullyUnsent) => format!("*{who} unsent a message!*\n\n"),
+            None => String::from("*Unable to format announcement!*\n\n"),
+        }
+    }
+
+    /// Store an image attachment in the archive's `photos` folder, copying it first if requested
+    ///
+    /// Returns `None` if the attachment is not an image or cannot be read.
+    fn store_photo(
+        &mut self,
+        message: &Message,
+        attachment: &mut Attachment,
+    ) -> Result<Option<Photo>, RuntimeError> {
+        if !matches!(attachment.mime_type(), MediaType::Image(_)) {
+            return Ok(None);
+        }
+        if self
+            .config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .is_none()
+        {
+            return Ok(None);
+        }
+
+        // Converted copies are read instead of the original, so HEIC images can become JPEG
+        let path = match &attachment.copied_path {
+            Some(path) => path.clone(),
+            None => match attachment.resolved_attachment_path(
+                &self.config.options.platform,
+                &self.config.options.db_path,
+                self.config.options.attachment_root.as_deref(),
+            ) {
+                Some(path) => PathBuf::from(path),
+                None => return Ok(None),
+            },
+        };
+        let Some(kind) = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(photo_type)
+        else {
+            return Ok(None);
+        };
+        let Ok(data) = read(&path) else {
+            return Ok(None);
+        };
+
+        let md5 = md5(&data);
+        if self.stored.insert(md5.clone()) {
+            self.archive
+                .add(&format!("photos/{md5}.{kind}"), &data)
+                .map_err(RuntimeError::DiskError)?;
+        }
+
+        Ok(Some(Photo {
+            identifier: identifier(&format!("{}/{}", message.guid, attachment.rowid)),
+            md5,
+            kind,
+        }))
+    }
+}
+
+/// Format a timestamp from the source database as an ISO 8601 date in UTC
+fn timestamp(stamp: i64, offset: &i64) -> String {
+    match get_local_time(&stamp, offset) {
+        Ok(date) => date.naive_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        Err(_) => String::from("2001-01-01T00:00:00Z"),
+    }
+}
+
+/// Day One names photo types by their format, not their extension
+fn photo_type(extension: &str) -> String {
+    match extension.to_lowercase().as_str() {
+        "jpg" => String::from("jpeg"),
+        other => other.to_string(),
+    }
+}
+
+/// Build a stable identifier in Day One's format, 32 uppercase hexadecimal digits
+fn identifier(seed: &str) -> String {
+    md5(seed.as_bytes()).to_uppercase()
+}
+
+/// Compute the MD5 digest of `data` as lowercase hexadecimal
+fn md5(data: &[u8]) -> String {
+    /// Per-round shift amounts
+    const SHIFTS: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+    // Pad the message to a multiple of 64 bytes, ending with its length in bits
+    let mut message = data.to_vec();
+    let bit_length = (data.len() as u64).wrapping_mul(8);
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&bit_length.to_le_bytes());
+
+    for chunk in message.chunks_exact(64) {
+        let words: Vec<u32> = chunk
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+
+        let [mut a, mut b, mut c, mut d] = state;
+        for (round, shift) in SHIFTS.iter().enumerate() {
+            let (f, g) = match round / 16 {
+                0 => ((b & c) | (!b & d), round),
+                1 => ((d & b) | (!d & c), (5 * round + 1) % 16),
+                2 => (b ^ c ^ d, (3 * round + 5) % 16),
+                _ => (c ^ (b | !d), (7 * round) % 16),
+            };
+            // The constants are the integer part of the sines of the round numbers, scaled by 2^32
+            let constant = (((round + 1) as f64).sin().abs() * 4_294_967_296.0) as u32;
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(constant)
+                .wrapping_add(words[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(*shift));
+        }
+
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    state
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs::create_dir_all, path::PathBuf};
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        exporters::day_one::{identifier, md5, photo_type},
+        Config, DayOne, Options,
+    };
+    use imessage_database::{
+        tables::{messages::Message, table::get_connection},
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options(export_path: &str) -> Options {
+        let export_path = PathBuf::from(export_path);
+        create_dir_all(&export_path).unwrap();
+
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path,
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options("/tmp/day_one");
+        let config = fake_config(options);
+        let exporter = DayOne::new(&config);
+        assert!(exporter.is_ok());
+    }
+
+    #[test]
+    fn can_compute_md5() {
+        assert_eq!(md5(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            md5(b"The quick brown fox jumps over the lazy dog"),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+    }
+
+    #[test]
+    fn can_build_identifier() {
+        assert_eq!(identifier("abc"), "900150983CD24FB0D6963F7D28E17F72");
+    }
+
+    #[test]
+    fn can_get_photo_type() {
+        assert_eq!(photo_type("JPG"), "jpeg");
+        assert_eq!(photo_type("png"), "png");
+    }
+
+    #[test]
+    fn can_format_message() {
+        let options = fake_options("/tmp/day_one");
+        let config = fake_config(options);
+        let mut exporter = DayOne::new(&config).unwrap();
+
+        let mut message = blank();
+        message.text = Some("Hello *world*".to_string());
+        message.is_from_me = true;
+
+        let (text, photos) = exporter.format_message(&message).unwrap();
+        assert!(text.starts_with("**Me** "));
+        assert!(text.ends_with("\nHello \\*world\\*\n\n"));
+        assert!(photos.is_empty());
+    }
+
+    #[test]
+    fn can_format_message_with_tapbacks() {
+        let options = fake_options("/tmp/day_one_tapbacks");
+        let mut config = fake_config(options);
+        config.participants.insert(10, "Person 10".to_string());
+
+        let mut tapback = blank();
+        tapback.handle_id = Some(10);
+        tapback.associated_message_type = Some(2000);
+        tapback.associated_message_guid = Some("p:0/target".to_string());
+        config
+            .tapbacks
+            .insert("target".to_string(), HashMap::from([(0, vec![tapback])]));
+
+        let mut exporter = DayOne::new(&config).unwrap();
+
+        let mut message = blank();
+        message.guid = "target".to_string();
+        message.text = Some("Hello".to_string());
+        message.is_from_me = true;
+
+        let (text, _) = exporter.format_message(&message).unwrap();
+        assert!(text.ends_with("\nHello\n*Loved by Person 10*\n\n"));
+    }
+
+    #[test]
+    fn can_group_entries_by_conversation_and_day() {
+        let options = fake_options("/tmp/day_one_entries");
+        let config = fake_config(options);
+        let mut exporter = DayOne::new(&config).unwrap();
+
+        let mut message = blank();
+        message.text = Some("Hello".to_string());
+        exporter
+            .get_or_create_entry(&message)
+            .text
+            .push_str("one\n");
+        exporter
+            .get_or_create_entry(&message)
+            .text
+            .push_str("two\n");
+        assert_eq!(exporter.entries.len(), 1);
+
+        // A message on a later day starts a new entry
+        message.date = 674526582885055488;
+        exporter.get_or_create_entry(&message);
+        assert_eq!(exporter.journal.len(), 1);
+        assert!(exporter.journal[0].contains("\"text\":\"# orphaned\\n\\none\\ntwo\""));
+        assert!(exporter.journal[0].contains("\"tags\":[\"iMessage\",\"orphaned\"]"));
+    }
+}
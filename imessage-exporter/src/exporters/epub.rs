@@ -0,0 +1,592 @@
+pub(super) mod archive;
+mod xhtml;
+
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::{metadata, read, read_to_string, remove_file, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::info;
+
+use crate::{
+    app::{
+        error::RuntimeError, progress::build_progress_bar_export, runtime::Config,
+        sanitizers::sanitize_html,
+    },
+    exporters::{
+        epub::{archive::Archive, xhtml::to_xhtml},
+        exporter::{Exporter, Writer},
+        html::{append_extension, templates::Templates, HTML},
+    },
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        messages::Message,
+        table::{Table, ORPHANED},
+    },
+    util::dates::{get_local_time, TIMESTAMP_FACTOR},
+};
+
+/// Name of the book all of the conversations are written to
+const BOOK_FILENAME: &str = "conversations.epub";
+/// Title of the book
+const BOOK_TITLE: &str = "iMessage Conversations";
+/// Extension for the HTML files messages are staged in before they are packaged
+const STAGING_EXTENSION: &str = "epub.part";
+/// Tells e-readers where to find the package document
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+<rootfiles>
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>
+</rootfiles>
+</container>
+";
+
+/// A conversation that has been written to the book
+struct Chapter {
+    id: String,
+    href: String,
+    title: String,
+}
+
+/// An attachment that has been copied into the book
+struct Media {
+    id: String,
+    href: String,
+    media_type: &'static str,
+}
+
+pub struct EPUB<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Renders messages the same way as the HTML export
+    ///
+    /// The renderer's files are staging files: a book can only be packaged once every
+    /// chapter is complete, so messages are staged as HTML until all of them have been written.
+    pub html: HTML<'a>,
+    /// Map of resolved chatroom file location to the chapter title
+    pub titles: HashMap<String, String>,
+}
+
+impl<'a> Exporter<'a> for EPUB<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let orphaned = EPUB::staging_path(config, ORPHANED);
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        Ok(EPUB {
+            config,
+            html: HTML {
+                config,
+                files: HashMap::new(),
+                orphaned: BufWriter::new(file),
+                pages: None,
+                site: None,
+                templates: Templates::new(None, &config.options.theme)?,
+                icons: HashMap::new(),
+            },
+            titles: HashMap::new(),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as epub...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self.html.format_announcement(&msg);
+                HTML::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+            }
+            // Message replies and tapbacks are rendered in context, so no need to render them separately
+            else if !msg.is_tapback() {
+                let message = self
+                    .html
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                HTML::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        info!("Packaging EPUB book...");
+        for buf in self.html.files.values_mut() {
+            buf.flush().map_err(RuntimeError::DiskError)?;
+        }
+        self.html
+            .orphaned
+            .flush()
+            .map_err(RuntimeError::DiskError)?;
+        self.write_book()
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                return match self.html.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let path = EPUB::staging_path(self.config, entry.key());
+                        self.titles
+                            .insert(entry.key().to_string(), chatroom.name().to_string());
+
+                        let file = File::options()
+                            .append(true)
+                            .create(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        Ok(entry.insert(BufWriter::new(file)))
+                    }
+                };
+            }
+            None => Ok(&mut self.html.orphaned),
+        }
+    }
+}
+
+impl<'a> EPUB<'a> {
+    /// Location of the file a conversation is staged in before it is packaged
+    fn staging_path(config: &Config, filename: &str) -> PathBuf {
+        let mut path = config.options.export_path.clone();
+        path.push(filename);
+        append_extension(&mut path, STAGING_EXTENSION);
+        path
+    }
+
+    /// Package the staged conversations as chapters of a book, then remove the staging files
+    fn write_book(&self) -> Result<(), RuntimeError> {
+        let mut path = self.config.options.export_path.clone();
+        path.push(BOOK_FILENAME);
+        let mut book = Archive::new(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+
+        // The mimetype must be the first entry so readers can identify the file
+        book.add("mimetype", b"application/epub+zip")
+            .map_err(RuntimeError::DiskError)?;
+        book.add("META-INF/container.xml", CONTAINER.as_bytes())
+            .map_err(RuntimeError::DiskError)?;
+        book.add("OEBPS/style.css", self.html.templates.style().as_bytes())
+            .map_err(RuntimeError::DiskError)?;
+
+        // Order chapters by title so the table of contents is easy to navigate
+        let mut staged: Vec<(&str, &str)> = self
+            .html
+            .files
+            .keys()
+            .map(|filename| {
+                let title = self.titles.get(filename).map_or(filename.as_str(), |t| t);
+                (filename.as_str(), title)
+            })
+            .collect();
+        staged.sort_by_key(|(_, title)| title.to_lowercase());
+
+        // Orphaned messages are only worth a chapter if there are any
+        let orphaned = EPUB::staging_path(self.config, ORPHANED);
+        if metadata(&orphaned)
+            .map(|meta| meta.len() > 0)
+            .unwrap_or(false)
+        {
+            staged.push((ORPHANED, ORPHANED));
+        }
+
+        let mut chapters = vec![];
+        let mut media: HashMap<PathBuf, Media> = HashMap::new();
+        for (filename, title) in staged {
+            let staging = EPUB::staging_path(self.config, filename);
+            let html = read_to_string(&staging).map_err(RuntimeError::DiskError)?;
+
+            // Point images at copies stored in the book, if they exist
+            let mut new_media = vec![];
+            let body = to_xhtml(&html, |src| {
+                let image_path = self.resolve_media(src)?;
+                if let Some(existing) = media.get(&image_path) {
+                    return Some(existing.href.clone());
+                }
+                let extension = image_path.extension()?.to_str()?.to_lowercase();
+                let id = format!("media{}", media.len() + 1);
+                let item = Media {
+                    href: format!("media/{id}.{extension}"),
+                    media_type: EPUB::media_type(&extension),
+                    id,
+                };
+                let href = item.href.clone();
+                new_media.push(image_path.clone());
+                media.insert(image_path, item);
+                Some(href)
+            });
+
+            let chapter = Chapter {
+                id: format!("chapter{}", chapters.len() + 1),
+                href: format!("chapter{}.xhtml", chapters.len() + 1),
+                title: sanitize_html(title).to_string(),
+            };
+            book.add(
+                &format!("OEBPS/{}", chapter.href),
+                EPUB::chapter_document(&chapter.title, &body).as_bytes(),
+            )
+            .map_err(RuntimeError::DiskError)?;
+
+            for image_path in new_media {
+                let data = read(&image_path).map_err(RuntimeError::DiskError)?;
+                book.add(&format!("OEBPS/{}", media[&image_path].href), &data)
+                    .map_err(RuntimeError::DiskError)?;
+            }
+
+            chapters.push(chapter);
+        }
+
+        let mut media: Vec<Media> = media.into_values().collect();
+        media.sort_by(|a, b| a.href.cmp(&b.href));
+
+        book.add(
+            "OEBPS/nav.xhtml",
+            EPUB::navigation_document(&chapters).as_bytes(),
+        )
+        .map_err(RuntimeError::DiskError)?;
+        book.add(
+            "OEBPS/content.opf",
+            self.package_document(&chapters, &media).as_bytes(),
+        )
+        .map_err(RuntimeError::DiskError)?;
+        book.finish().map_err(RuntimeError::DiskError)?;
+
+        for filename in self.html.files.keys() {
+            remove_file(EPUB::staging_path(self.config, filename))
+                .map_err(RuntimeError::DiskError)?;
+        }
+        remove_file(orphaned).map_err(RuntimeError::DiskError)
+    }
+
+    /// Find the file an image in the HTML export refers to, if it is on disk
+    fn resolve_media(&self, src: &str) -> Option<PathBuf> {
+        if src.contains("://") || src.starts_with("data:") {
+            return None;
+        }
+
+        // Copied attachments are referenced relative to the export directory
+        let mut path = PathBuf::from(src);
+        if path.is_relative() {
+            path = self.config.options.export_path.join(path);
+        }
+        path.is_file().then_some(path)
+    }
+
+    /// Media type for an attachment with the given extension
+    fn media_type(extension: &str) -> &'static str {
+        match extension {
+            "jpg" | "jpeg" => "image/jpeg",
+            "png" => "image/png",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "svg" => "image/svg+xml",
+            "heic" => "image/heic",
+            "tif" | "tiff" => "image/tiff",
+            _ => "application/octet-stream",
+        }
+    }
+
+    /// Wrap a converted conversation in an XHTML document
+    fn chapter_document(title: &str, body: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE html>
+<html xmlns=\"http://www.w3.org/1999/xhtml\">
+<head>
+<meta charset=\"UTF-8\"/>
+<title>{title}</title>
+<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>
+</head>
+<body>
+<h1>{title}</h1>
+{body}
+</body>
+</html>
+"
+        )
+    }
+
+    /// Build the table of contents
+    fn navigation_document(chapters: &[Chapter]) -> String {
+        let entries: String = chapters
+            .iter()
+            .map(|chapter| {
+                format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    chapter.href, chapter.title
+                )
+            })
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE html>
+<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">
+<head>
+<meta charset=\"UTF-8\"/>
+<title>Contents</title>
+</head>
+<body>
+<nav epub:type=\"toc\" id=\"toc\">
+<h1>Contents</h1>
+<ol>
+{entries}</ol>
+</nav>
+</body>
+</html>
+"
+        )
+    }
+
+    /// Build the package document that lists every file in the book and the reading order
+    fn package_document(&self, chapters: &[Chapter], media: &[Media]) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs() as i64);
+        let modified = get_local_time(
+            &((now - self.config.offset) * TIMESTAMP_FACTOR),
+            &self.config.offset,
+        )
+        .map(|date| date.naive_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_default();
+
+        let mut manifest = String::new();
+        let mut spine = String::new();
+        for chapter in chapters {
+            manifest.push_str(&format!(
+                "<item id=\"{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+                chapter.id, chapter.href
+            ));
+            spine.push_str(&format!("<itemref idref=\"{}\"/>\n", chapter.id));
+        }
+        for item in media {
+            manifest.push_str(&format!(
+                "<item id=\"{}\" href=\"{}\" media-type=\"{}\"/>\n",
+                item.id, item.href, item.media_type
+            ));
+        }
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">
+<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">
+<dc:identifier id=\"book-id\">urn:imessage-exporter:{now}</dc:identifier>
+<dc:title>{BOOK_TITLE}</dc:title>
+<dc:language>en</dc:language>
+<meta property=\"dcterms:modified\">{modified}</meta>
+</metadata>
+<manifest>
+<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>
+<item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>
+{manifest}</manifest>
+<spine>
+<itemref idref=\"nav\"/>
+{spine}</spine>
+</package>
+"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs::{create_dir_all, read, write},
+        io::Write,
+        path::PathBuf,
+    };
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        exporters::epub::EPUB,
+        Config, Exporter, Options,
+    };
+    use imessage_database::{
+        tables::table::get_connection,
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn fake_options() -> Options {
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path: PathBuf::from("/tmp"),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = EPUB::new(&config).unwrap();
+        assert_eq!(exporter.html.files.len(), 0);
+    }
+
+    #[test]
+    fn can_get_media_type() {
+        assert_eq!(EPUB::media_type("jpg"), "image/jpeg");
+        assert_eq!(EPUB::media_type("png"), "image/png");
+        assert_eq!(EPUB::media_type("xyz"), "application/octet-stream");
+    }
+
+    #[test]
+    fn can_write_book() {
+        let mut options = fake_options();
+        options.export_path = PathBuf::from("/tmp/can_write_book");
+        create_dir_all(&options.export_path).unwrap();
+        write(options.export_path.join("image.png"), b"not really a png").unwrap();
+        let config = fake_config(options);
+        let mut exporter = EPUB::new(&config).unwrap();
+
+        // Stage an orphaned message that references an image in the export directory
+        exporter
+            .html
+            .orphaned
+            .write_all(b"<div class=\"message\">\n<img src=\"image.png\" loading=\"lazy\">\n")
+            .unwrap();
+        exporter.html.orphaned.flush().unwrap();
+        exporter.write_book().unwrap();
+
+        let book = read("/tmp/can_write_book/conversations.epub").unwrap();
+        let book = String::from_utf8_lossy(&book);
+        assert!(book.contains("mimetypeapplication/epub+zip"));
+        assert!(book.contains("OEBPS/chapter1.xhtml"));
+        assert!(book.contains("<h1>orphaned</h1>"));
+        assert!(book.contains("<img src=\"media/media1.png\" loading=\"lazy\"/>\n</div>"));
+        assert!(book.contains("OEBPS/media/media1.pngnot really a png"));
+        assert!(book.contains("<itemref idref=\"chapter1\"/>"));
+        assert!(!config
+            .options
+            .export_path
+            .join("orphaned.epub.part")
+            .exists());
+    }
+}
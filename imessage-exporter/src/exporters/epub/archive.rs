@@ -0,0 +1,182 @@
+/*!
+ A minimal ZIP writer used to package EPUB books and Day One journals.
+
+ Entries are stored without compression: the EPUB specification requires the `mimetype`
+ entry to be stored, and exported images are already compressed, so deflating the
+ remaining markup would save little space.
+*/
+
+use std::{
+    fs::File,
+    io::{BufWriter, Error, Result, Write},
+    path::Path,
+};
+
+/// Signature of a local file header
+const LOCAL_HEADER: u32 = 0x04034b50;
+/// Signature of a central directory file header
+const CENTRAL_HEADER: u32 = 0x02014b50;
+/// Signature of the end of central directory record
+const END_OF_DIRECTORY: u32 = 0x06054b50;
+/// ZIP specification version needed to extract stored entries
+const VERSION: u16 = 20;
+/// General purpose flag marking entry names as UTF-8
+const UTF8_NAMES: u16 = 1 << 11;
+/// Modification time stamped on each entry, `00:00:00` in DOS format
+const DOS_TIME: u16 = 0;
+/// Modification date stamped on each entry, `1980-01-01` in DOS format
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// An entry that has been written to the archive
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+pub(crate) struct Archive {
+    /// Buffered writer for the archive file
+    file: BufWriter<File>,
+    /// Number of bytes written to the file so far
+    position: u64,
+    /// Entries written so far, in order
+    entries: Vec<Entry>,
+}
+
+impl Archive {
+    /// Create a new, empty archive at `path`
+    pub(crate) fn new(path: &Path) -> Result<Self> {
+        Ok(Archive {
+            file: BufWriter::new(File::create(path)?),
+            position: 0,
+            entries: vec![],
+        })
+    }
+
+    /// Store `data` in the archive as `name`
+    pub(crate) fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let entry = Entry {
+            name: name.to_string(),
+            crc: crc32(data),
+            size: to_u32(data.len() as u64)?,
+            offset: to_u32(self.position)?,
+        };
+
+        let mut header = vec![];
+        header.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        Archive::push_entry_fields(&mut header, &entry);
+        header.extend_from_slice(&0u16.to_le_bytes()); // Extra field length
+        header.extend_from_slice(entry.name.as_bytes());
+
+        self.write(&header)?;
+        self.write(data)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Write the central directory and flush the archive to disk
+    pub(crate) fn finish(mut self) -> Result<()> {
+        let directory_offset = to_u32(self.position)?;
+
+        let mut directory = vec![];
+        for entry in &self.entries {
+            directory.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+            directory.extend_from_slice(&VERSION.to_le_bytes()); // Version made by
+            directory.extend_from_slice(&VERSION.to_le_bytes()); // Version needed
+            Archive::push_entry_fields(&mut directory, entry);
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Extra field length
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Comment length
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Disk number
+            directory.extend_from_slice(&0u16.to_le_bytes()); // Internal attributes
+            directory.extend_from_slice(&0u32.to_le_bytes()); // External attributes
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let directory_size = to_u32(directory.len() as u64)?;
+        let count = u16::try_from(self.entries.len())
+            .map_err(|_| Error::other("too many entries for a ZIP archive"))?;
+
+        directory.extend_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes()); // This disk
+        directory.extend_from_slice(&0u16.to_le_bytes()); // Disk with the directory
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&directory_size.to_le_bytes());
+        directory.extend_from_slice(&directory_offset.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes()); // Comment length
+
+        self.write(&directory)?;
+        self.file.flush()
+    }
+
+    /// Fields shared by the local and central headers, from the flags to the name length
+    fn push_entry_fields(buf: &mut Vec<u8>, entry: &Entry) {
+        buf.extend_from_slice(&UTF8_NAMES.to_le_bytes());
+        buf.extend_from_slice(&0u16.to_le_bytes()); // Stored, no compression
+        buf.extend_from_slice(&DOS_TIME.to_le_bytes());
+        buf.extend_from_slice(&DOS_DATE.to_le_bytes());
+        buf.extend_from_slice(&entry.crc.to_le_bytes());
+        buf.extend_from_slice(&entry.size.to_le_bytes()); // Compressed size
+        buf.extend_from_slice(&entry.size.to_le_bytes()); // Uncompressed size
+        buf.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.file.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+/// Archives without the ZIP64 extensions cannot address more than 4 GiB
+fn to_u32(value: u64) -> Result<u32> {
+    u32::try_from(value).map_err(|_| Error::other("archive is too large for the ZIP format"))
+}
+
+/// Compute the CRC-32 checksum ZIP uses to verify entries
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::read, path::PathBuf};
+
+    use super::{crc32, Archive};
+
+    #[test]
+    fn can_compute_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b"application/epub+zip"), 0x2CAB616F);
+    }
+
+    #[test]
+    fn can_write_archive() {
+        let path = PathBuf::from("/tmp/can_write_archive.zip");
+        let mut archive = Archive::new(&path).unwrap();
+        archive.add("mimetype", b"application/epub+zip").unwrap();
+        archive.add("a/b.txt", b"hello").unwrap();
+        archive.finish().unwrap();
+
+        let bytes = read(&path).unwrap();
+        // Local header, name, and contents of the first entry
+        assert_eq!(&bytes[..4], b"PK\x03\x04");
+        assert_eq!(&bytes[30..38], b"mimetype");
+        assert_eq!(&bytes[38..58], b"application/epub+zip");
+        // End of central directory record with two entries
+        let end = bytes.len() - 22;
+        assert_eq!(&bytes[end..end + 4], b"PK\x05\x06");
+        assert_eq!(&bytes[end + 10..end + 12], &2u16.to_le_bytes());
+    }
+}
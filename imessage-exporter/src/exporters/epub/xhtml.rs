@@ -0,0 +1,271 @@
+/*!
+ Converts the markup generated by the HTML exporter to well-formed XHTML.
+
+ Browsers tolerate unclosed elements, stray end tags, and malformed attributes, so the HTML
+ exporter does not avoid them. E-readers parse book chapters as XML and reject the whole
+ chapter if any of those appear, so chapters are normalized before they are stored.
+*/
+
+use std::collections::HashSet;
+
+/// Elements that never have content, which XHTML requires to be self-closed
+const VOID_ELEMENTS: [&str; 8] = ["area", "br", "col", "embed", "hr", "img", "source", "wbr"];
+
+/// Named entities XML understands without a DTD
+const XML_ENTITIES: [&str; 5] = ["amp", "apos", "gt", "lt", "quot"];
+
+/// Convert HTML exporter output to XHTML
+///
+/// `resolve` is called with the `src` of each image; if it returns a new location, the image
+/// is pointed there instead.
+pub(super) fn to_xhtml(html: &str, mut resolve: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(html.len());
+    // Elements that have been opened but not yet closed
+    let mut open: Vec<String> = vec![];
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        push_text(&mut out, &rest[..start]);
+        rest = &rest[start..];
+
+        // Comments and declarations are dropped
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+
+        let end = tag_end(rest);
+        let tag = &rest[1..end];
+        let (is_end_tag, body) = match tag.strip_prefix('/') {
+            Some(body) => (true, body),
+            None => (false, tag),
+        };
+        if !body.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            // Not a tag, so the bracket was meant as text
+            out.push_str("&lt;");
+            rest = &rest[1..];
+            continue;
+        }
+        rest = rest.get(end + 1..).unwrap_or("");
+
+        let name_len = body
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(body.len());
+        let name = body[..name_len].to_ascii_lowercase();
+
+        if is_end_tag {
+            // Close everything opened since the matching start tag; drop end tags that match nothing
+            if let Some(idx) = open.iter().rposition(|element| *element == name) {
+                for element in open.drain(idx..).rev() {
+                    out.push_str(&format!("</{element}>"));
+                }
+            }
+            continue;
+        }
+
+        out.push('<');
+        out.push_str(&name);
+        push_attributes(&mut out, &name, &body[name_len..], &mut resolve);
+        if VOID_ELEMENTS.contains(&name.as_str()) || body.ends_with('/') {
+            out.push_str("/>");
+        } else {
+            out.push('>');
+            open.push(name);
+        }
+    }
+    push_text(&mut out, rest);
+
+    for element in open.into_iter().rev() {
+        out.push_str(&format!("</{element}>"));
+    }
+    out
+}
+
+/// Find the index of the `>` that ends the tag at the start of `html`, ignoring any in quoted values
+fn tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (idx, c) in html.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return idx,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+/// Write the well-formed attributes in `source`, dropping any XML cannot represent
+fn push_attributes(
+    out: &mut String,
+    element: &str,
+    source: &str,
+    resolve: &mut impl FnMut(&str) -> Option<String>,
+) {
+    let mut seen = HashSet::new();
+    let mut rest = source;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',' || c == '/');
+        if rest.is_empty() {
+            break;
+        }
+
+        let name_len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '=' | '>' | '"' | '\''))
+            .unwrap_or(rest.len())
+            .max(1);
+        let name = &rest[..name_len];
+        rest = rest[name_len..].trim_start();
+
+        // Attributes without a value are boolean, which XML spells out
+        let mut value = name.to_string();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (raw, remaining) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let inner = &after[1..];
+                    let close = inner.find(quote).unwrap_or(inner.len());
+                    (&inner[..close], inner.get(close + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let close = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..close], &after[close..])
+                }
+            };
+            value = raw.to_string();
+            rest = remaining;
+        }
+
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':' | '.'));
+        if !valid || !seen.insert(name.to_ascii_lowercase()) {
+            continue;
+        }
+
+        if element == "img" && name == "src" {
+            if let Some(resolved) = resolve(&value) {
+                value = resolved;
+            }
+        }
+
+        out.push(' ');
+        out.push_str(name);
+        out.push_str("=\"");
+        push_text(out, &value.replace('"', "&quot;"));
+        out.push('"');
+    }
+}
+
+/// Write text content, escaping anything that is not already a valid XML reference
+fn push_text(out: &mut String, text: &str) {
+    let mut rest = text;
+    while let Some(idx) = rest.find(['&', '<', '>']) {
+        out.push_str(&rest[..idx]);
+        let (c, after) = (&rest[idx..idx + 1], &rest[idx + 1..]);
+        rest = after;
+
+        match c {
+            "<" => out.push_str("&lt;"),
+            ">" => out.push_str("&gt;"),
+            _ => match after.find(';').map(|end| &after[..end]) {
+                Some(name) if XML_ENTITIES.contains(&name) || is_char_reference(name) => {
+                    out.push('&');
+                }
+                Some("nbsp") => {
+                    out.push_str("&#160;");
+                    rest = &after[5..];
+                }
+                _ => out.push_str("&amp;"),
+            },
+        }
+    }
+    out.push_str(rest);
+}
+
+/// Determine if the text between `&` and `;` is a numeric character reference
+fn is_char_reference(name: &str) -> bool {
+    match name.strip_prefix('#') {
+        Some(hex) if hex.starts_with(['x', 'X']) => {
+            hex.len() > 1 && hex[1..].chars().all(|c| c.is_ascii_hexdigit())
+        }
+        Some(digits) => !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_xhtml;
+
+    fn convert(html: &str) -> String {
+        to_xhtml(html, |_| None)
+    }
+
+    #[test]
+    fn can_close_void_elements() {
+        assert_eq!(
+            convert("<hr><div class=\"message_part\">a<br>b</div>"),
+            "<hr/><div class=\"message_part\">a<br/>b</div>"
+        );
+    }
+
+    #[test]
+    fn can_drop_stray_end_tags() {
+        assert_eq!(
+            convert("<p><span>a</span></p>\n<span>b</span></p>"),
+            "<p><span>a</span></p>\n<span>b</span>"
+        );
+    }
+
+    #[test]
+    fn can_close_unclosed_elements() {
+        assert_eq!(
+            convert("<div><p><span>a</div>b<i>c"),
+            "<div><p><span>a</span></p></div>b<i>c</i>"
+        );
+    }
+
+    #[test]
+    fn can_fix_attributes() {
+        assert_eq!(
+            convert("<div class=\"message\", id=\"r-1\">"),
+            "<div class=\"message\" id=\"r-1\"></div>"
+        );
+        assert_eq!(
+            convert("<audio controls src=\"a.m4a\" type=\"audio/mp4\" </audio>"),
+            "<audio controls=\"controls\" src=\"a.m4a\" type=\"audio/mp4\"></audio>"
+        );
+        assert_eq!(
+            convert("<img src=\"a.png\" onerror=\"this.style.display='none'\">"),
+            "<img src=\"a.png\" onerror=\"this.style.display='none'\"/>"
+        );
+    }
+
+    #[test]
+    fn can_escape_text() {
+        assert_eq!(
+            convert("a &amp; b &nbsp;by c & d &#8203; 1 < 2"),
+            "a &amp; b &#160;by c &amp; d &#8203; 1 &lt; 2"
+        );
+    }
+
+    #[test]
+    fn can_drop_comments() {
+        assert_eq!(convert("a<!-- b -->c"), "ac");
+    }
+
+    #[test]
+    fn can_resolve_images() {
+        let actual = to_xhtml("<img src=\"a/b.png\"><a href=\"a/b.png\">b</a>", |src| {
+            Some(format!("media/{}", src.len()))
+        });
+        assert_eq!(actual, "<img src=\"media/7\"/><a href=\"a/b.png\">b</a>");
+    }
+}
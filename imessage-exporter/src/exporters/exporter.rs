@@ -7,10 +7,12 @@ use imessage_database::{
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
         edited::EditedMessage,
+        facetime::FaceTimeMessage,
         handwriting::HandwrittenMessage,
+        icloud_photos::ICloudPhotosMessage,
         music::MusicMessage,
         placemark::PlacemarkMessage,
-        text_effects::{Animation, Style, TextEffect, Unit},
+        text_effects::{Animation, DataDetector, Style, TextEffect, Unit},
         url::URLMessage,
     },
     tables::{attachment::Attachment, messages::Message},
@@ -62,6 +64,8 @@ pub(super) trait Writer<'a> {
     fn format_shareplay(&self) -> &str;
     /// Format a legacy Shared Location message
     fn format_shared_location(&self, msg: &'a Message) -> &str;
+    /// Format the transcription of an audio message, shown under its attachment
+    fn format_transcription(&self, transcription: &str) -> String;
     /// Format an edited message
     fn format_edited(
         &self,
@@ -87,8 +91,16 @@ pub(super) trait BalloonFormatter<T> {
     fn format_app_store(&self, balloon: &AppStoreMessage, indent: T) -> String;
     /// Format a shared location message
     fn format_placemark(&self, balloon: &PlacemarkMessage, indent: T) -> String;
+    /// Format a link to join a FaceTime call
+    fn format_facetime(&self, balloon: &FaceTimeMessage, indent: T) -> String;
+    /// Format an iCloud photo link or Shared Album invitation
+    fn format_icloud_photos(&self, balloon: &ICloudPhotosMessage, indent: T) -> String;
     /// Format a handwritten note message
     fn format_handwriting(&self, msg: &Message, balloon: &HandwrittenMessage, indent: T) -> String;
+    /// Format a Digital Touch message, whose strokes cannot be rendered yet
+    ///
+    /// Unlike other balloons, the strokes are not a plist, so `payload` is the [`Message::raw_payload_data`] as it was sent.
+    fn format_digital_touch(&self, msg: &Message, payload: &[u8], indent: T) -> String;
     /// Format an Apple Pay message
     fn format_apple_pay(&self, balloon: &AppMessage, indent: T) -> String;
     /// Format a Fitness message
@@ -122,4 +134,6 @@ pub(super) trait TextEffectFormatter {
     fn format_styles(&self, text: &str, styles: &[Style]) -> String;
     /// Format [`Animated`](imessage_database::message_types::text_effects::TextEffect::Animated) message text
     fn format_animated(&self, text: &str, animation: &Animation) -> String;
+    /// Format message text recognized by a [`DataDetector`](imessage_database::message_types::text_effects::TextEffect::Detected)
+    fn format_detected(&self, text: &str, detector: &DataDetector) -> String;
 }
@@ -6,10 +6,10 @@ use std::{
     },
     fs::File,
     io::{BufWriter, Write},
-    path::{PathBuf},
+    path::PathBuf,
 };
 
-fn append_extension(path: &mut PathBuf, new_ext: &str) {
+pub(super) fn append_extension(path: &mut PathBuf, new_ext: &str) {
     // Check if the current path has an extension
     if path.extension().is_none() {
         // If no existing extension, set the new extension
@@ -21,26 +21,35 @@ fn append_extension(path: &mut PathBuf, new_ext: &str) {
     }
 }
 
+use log::info;
+
 use crate::{
     app::{
-        error::RuntimeError, progress::build_progress_bar_export, runtime::Config,
+        converter::DocumentType, error::RuntimeError, pagination::Pagination,
+        progress::build_progress_bar_export, roster::Roster, runtime::Config,
         sanitizers::sanitize_html,
     },
-    exporters::exporter::{BalloonFormatter, Exporter, TextEffectFormatter, Writer},
+    exporters::{
+        exporter::{BalloonFormatter, Exporter, TextEffectFormatter, Writer},
+        mbox::{base64, read_attachment},
+    },
 };
 
 use imessage_database::{
     error::{plist::PlistParseError, table::TableError},
     message_types::{
         app::AppMessage,
+        app_decoder::{summarize_app, AppSummary},
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
         edited::{EditStatus, EditedMessage},
         expressives::{BubbleEffect, Expressive, ScreenEffect},
+        facetime::FaceTimeMessage,
         handwriting::HandwrittenMessage,
+        icloud_photos::{ICloudPhotosKind, ICloudPhotosMessage},
         music::MusicMessage,
         placemark::PlacemarkMessage,
-        text_effects::{Animation, Style, TextEffect, Unit},
+        text_effects::{Animation, DataDetector, Style, TextEffect, Unit},
         url::URLMessage,
         variants::{Announcement, BalloonProvider, CustomBalloon, URLOverride, Variant},
     },
@@ -55,9 +64,16 @@ use imessage_database::{
     },
 };
 
+mod pages;
+mod site;
+pub(super) mod templates;
+
 const HEADER: &str = "<html>\n<head>\n<meta charset=\"UTF-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">";
-const FOOTER: &str = "</body></html>";
-const STYLE: &str = include_str!("resources/style.css");
+pub(super) const STYLE: &str = include_str!("resources/style.css");
+/// Height of the waveform drawn for audio attachments, in `SVG` units
+const WAVEFORM_HEIGHT: f64 = 20.0;
+/// The most characters of a message to quote above a reply to it
+const REPLY_QUOTE_LENGTH: usize = 100;
 
 pub struct HTML<'a> {
     /// Data that is setup from the application's runtime
@@ -67,6 +83,14 @@ pub struct HTML<'a> {
     pub files: HashMap<String, BufWriter<File>>,
     /// Writer instance for orphaned messages
     pub orphaned: BufWriter<File>,
+    /// Pages each conversation is split into, if exporting a static site or paginating
+    pub(super) pages: Option<pages::Pages>,
+    /// Index and search data, if exporting a static site
+    pub(super) site: Option<site::Site>,
+    /// Templates for the page layout and messages
+    pub(super) templates: templates::Templates,
+    /// Map of conversation names to the relative paths of their icons, for the site index
+    pub(super) icons: HashMap<String, String>,
 }
 
 impl<'a> Exporter<'a> for HTML<'a> {
@@ -80,22 +104,43 @@ impl<'a> Exporter<'a> for HTML<'a> {
             .open(&orphaned)
             .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
 
+        let site = match config.options.site {
+            true => Some(site::Site::new(&config.options.export_path)?),
+            false => None,
+        };
+
+        // Static sites split each conversation into a page per year unless told otherwise
+        let pages = match (config.options.paginate, config.options.site) {
+            (Some(pagination), _) => Some(pages::Pages::new(pagination)),
+            (None, true) => Some(pages::Pages::new(Pagination::Year)),
+            (None, false) => None,
+        };
+
+        let templates = templates::Templates::new(
+            config.options.template_dir.as_deref(),
+            &config.options.theme,
+        )?;
+
         Ok(HTML {
             config,
             files: HashMap::new(),
             orphaned: BufWriter::new(file),
+            pages,
+            site,
+            templates,
+            icons: HashMap::new(),
         })
     }
 
     fn iter_messages(&mut self) -> Result<(), RuntimeError> {
         // Tell the user what we are doing
-        eprintln!(
+        info!(
             "Exporting to {} as html...",
             self.config.options.export_path.display()
         );
 
         // Write orphaned file headers
-        HTML::write_headers(&mut self.orphaned)?;
+        HTML::write_headers(&self.templates, &mut self.orphaned, ORPHANED)?;
 
         // Keep track of current message ROWID
         let mut current_message_row = -1;
@@ -131,7 +176,9 @@ impl<'a> Exporter<'a> for HTML<'a> {
 
             // Render the announcement in-line
             if msg.is_announcement() {
-                let announcement = self.format_announcement(&msg);
+                let announcement = self.wrap_message(&msg, self.format_announcement(&msg))?;
+                self.add_to_pages(&msg);
+                self.add_to_site(&msg)?;
                 HTML::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
             }
             // Message replies and tapbacks are rendered in context, so no need to render them separately
@@ -139,6 +186,9 @@ impl<'a> Exporter<'a> for HTML<'a> {
                 let message = self
                     .format_message(&msg, 0)
                     .map_err(RuntimeError::DatabaseError)?;
+                let message = self.wrap_message(&msg, message)?;
+                self.add_to_pages(&msg);
+                self.add_to_site(&msg)?;
                 HTML::write_to_file(self.get_or_create_file(&msg)?, &message)?;
             }
             current_message += 1;
@@ -148,11 +198,27 @@ impl<'a> Exporter<'a> for HTML<'a> {
         }
         pb.finish();
 
-        eprintln!("Writing HTML footers...");
-        for (_, buf) in self.files.iter_mut() {
-            HTML::write_to_file(buf, FOOTER)?;
+        info!("Writing HTML footers...");
+        for (page, buf) in self.files.iter_mut() {
+            if let Some(pages) = &self.pages {
+                HTML::write_to_file(buf, &pages.nav(page, self.site.is_some()))?;
+            }
+            HTML::write_footers(&self.templates, buf, page)?;
+        }
+        if let (Some(pages), Some(_)) = (&self.pages, &self.site) {
+            HTML::write_to_file(&mut self.orphaned, &pages.nav(ORPHANED, true))?;
+        }
+        HTML::write_footers(&self.templates, &mut self.orphaned, ORPHANED)?;
+
+        if let (Some(site), Some(pages)) = (self.site.take(), &self.pages) {
+            info!("Writing site index...");
+            site.finish(
+                &self.config.options.export_path,
+                self.templates.style(),
+                pages,
+                &self.icons,
+            )?;
         }
-        HTML::write_to_file(&mut self.orphaned, FOOTER)?;
 
         Ok(())
     }
@@ -164,13 +230,23 @@ impl<'a> Exporter<'a> for HTML<'a> {
     ) -> Result<&mut BufWriter<File>, RuntimeError> {
         match self.config.conversation(message) {
             Some((chatroom, _)) => {
-                let filename = self.config.filename(chatroom);
+                let conversation = self.config.filename(chatroom);
+                let mut filename = conversation.clone();
+
+                // Write to the page the message was assigned to, if conversations are split
+                if let Some(page) = self
+                    .pages
+                    .as_ref()
+                    .and_then(|pages| pages.current(&filename))
+                {
+                    filename = page.to_string();
+                }
 
                 return match self.files.entry(filename) {
                     Occupied(entry) => Ok(entry.into_mut()),
                     Vacant(entry) => {
                         let mut path = self.config.options.export_path.clone();
-                        path.push(self.config.filename(chatroom));
+                        path.push(entry.key());
                         append_extension(&mut path, "html");
 
                         // If the file already exists, don't write the headers again
@@ -185,9 +261,41 @@ impl<'a> Exporter<'a> for HTML<'a> {
 
                         let mut buf = BufWriter::new(file);
 
+                        let icon = self
+                            .config
+                            .chat_icon(chatroom)
+                            .and_then(|path| self.config.relative_path(path));
+
                         // Write headers if the file does not exist
                         if !file_exists {
-                            let _ = HTML::write_headers(&mut buf);
+                            let _ = HTML::write_headers(&self.templates, &mut buf, entry.key());
+
+                            // Show the chat's icon above its messages, next to the database owner's photo
+                            let my_avatar = self
+                                .config
+                                .my_avatar()
+                                .and_then(|path| self.config.relative_path(path));
+                            let mut icons = vec![];
+                            if let Some(icon) = &icon {
+                                icons.push((icon.as_str(), chatroom.name()));
+                            }
+                            if let Some(avatar) = &my_avatar {
+                                icons.push((
+                                    avatar.as_str(),
+                                    self.config.options.custom_name.as_deref().unwrap_or(ME),
+                                ));
+                            }
+                            if !icons.is_empty() {
+                                HTML::write_to_file(&mut buf, &HTML::format_chat_icon(&icons))?;
+                            }
+
+                            if let Some(roster) = Roster::new(self.config, chatroom) {
+                                HTML::write_to_file(&mut buf, &HTML::format_roster(&roster))?;
+                            }
+                        }
+
+                        if let Some(icon) = icon {
+                            self.icons.insert(conversation, icon);
                         }
 
                         Ok(entry.insert(buf))
@@ -213,6 +321,14 @@ impl<'a> Writer<'a> for HTML<'a> {
                 "",
                 "",
             );
+        } else if message.has_replies() && indent_size == 0 {
+            // Add an ID for any message that starts a thread so replies can link back to it
+            self.add_line(
+                &mut formatted_message,
+                &format!("<div class=\"message\" id=\"p-{}\">", message.guid),
+                "",
+                "",
+            );
         } else {
             // No ID needed if the message has no replies
             self.add_line(&mut formatted_message, "<div class=\"message\">", "", "");
@@ -271,6 +387,27 @@ impl<'a> Writer<'a> for HTML<'a> {
             "</span></p>",
         );
 
+        // Quote the message this responded to if the reply is not rendered in a thread
+        if message.is_reply() && indent_size == 0 {
+            match message.get_thread_originator(&self.config.db) {
+                Ok(Some(mut parent)) => {
+                    let _ = parent.generate_text(&self.config.db);
+                    self.add_line(
+                        &mut formatted_message,
+                        &self.format_reply_quote(&parent),
+                        "<div class=\"reply_context\">",
+                        "</div>",
+                    );
+                }
+                _ => self.add_line(
+                    &mut formatted_message,
+                    "This message responded to an earlier message.",
+                    "<span class=\"reply_context\">",
+                    "</span>",
+                ),
+            }
+        }
+
         // If message was deleted (not unsent), annotate it
         if message.is_deleted() {
             self.add_line(
@@ -320,15 +457,16 @@ impl<'a> Writer<'a> for HTML<'a> {
             );
         }
 
+        // Messages sent with an effect are animated with the CSS class for that effect
+        let part_start = match self.expressive_class(message) {
+            Some(effect) => format!("<hr><div class=\"message_part {effect}\">"),
+            None => String::from("<hr><div class=\"message_part\">"),
+        };
+
         // Generate the message body from it's components
         for (idx, message_part) in message_parts.iter().enumerate() {
             // Write the part div start
-            self.add_line(
-                &mut formatted_message,
-                "<hr><div class=\"message_part\">",
-                "",
-                "",
-            );
+            self.add_line(&mut formatted_message, &part_start, "", "");
 
             match message_part {
                 BubbleComponent::Text(text_attrs) => {
@@ -422,6 +560,14 @@ impl<'a> Writer<'a> for HTML<'a> {
                                     );
                                     }
                                 }
+                                if let Some(transcription) = message.audio_transcription() {
+                                    self.add_line(
+                                        &mut formatted_message,
+                                        &self.format_transcription(transcription),
+                                        "<div class=\"transcription\">",
+                                        "</div>",
+                                    );
+                                }
                             }
                         }
                         // Attachment does not exist in attachments table
@@ -461,6 +607,34 @@ impl<'a> Writer<'a> for HTML<'a> {
                 }
             };
 
+            // Place stickers over the part they were attached to, like Messages does
+            if let Some(tapbacks_map) = self.config.tapbacks.get(&message.guid) {
+                if let Some(tapbacks) = tapbacks_map.get(&idx) {
+                    let mut placed_stickers = String::new();
+                    tapbacks
+                        .iter()
+                        .filter(|tapback| matches!(tapback.variant(), Variant::Sticker(_)))
+                        .try_for_each(|sticker| -> Result<(), TableError> {
+                            self.add_line(
+                                &mut placed_stickers,
+                                &self.format_placed_sticker(sticker)?,
+                                "",
+                                "",
+                            );
+                            Ok(())
+                        })?;
+
+                    if !placed_stickers.is_empty() {
+                        self.add_line(
+                            &mut formatted_message,
+                            &placed_stickers,
+                            "<div class=\"placed_stickers\">",
+                            "</div>",
+                        );
+                    }
+                }
+            }
+
             // Write the part div end
             self.add_line(&mut formatted_message, "</div>", "", "");
 
@@ -479,8 +653,10 @@ impl<'a> Writer<'a> for HTML<'a> {
                 if let Some(tapbacks) = tapbacks_map.get(&idx) {
                     let mut formatted_tapbacks = String::new();
 
+                    // Stickers were placed over the part above
                     tapbacks
                         .iter()
+                        .filter(|tapback| !matches!(tapback.variant(), Variant::Sticker(_)))
                         .try_for_each(|tapback| -> Result<(), TableError> {
                             let formatted = self.format_tapback(tapback)?;
                             if !formatted.is_empty() {
@@ -529,16 +705,6 @@ impl<'a> Writer<'a> for HTML<'a> {
             }
         }
 
-        // Add a note if the message is a reply and not rendered in a thread
-        if message.is_reply() && indent_size == 0 {
-            self.add_line(
-                &mut formatted_message,
-                "This message responded to an earlier message.",
-                "<span class=\"reply_context\">",
-                "</span>",
-            );
-        }
-
         // End message type div
         self.add_line(&mut formatted_message, "</div>", "", "");
 
@@ -553,6 +719,12 @@ impl<'a> Writer<'a> for HTML<'a> {
         attachment: &'a mut Attachment,
         message: &Message,
     ) -> Result<String, &'a str> {
+        if let Some(removed) = self.config.removed_attachment(message, attachment) {
+            return Ok(format!(
+                "<span class=\"attachment_removed\">{removed}</span>"
+            ));
+        }
+
         // Copy the file, if requested
         self.config
             .options
@@ -560,41 +732,130 @@ impl<'a> Writer<'a> for HTML<'a> {
             .handle_attachment(message, attachment, self.config)
             .ok_or(attachment.filename())?;
 
-        // Build a relative filepath from the fully qualified one on the `Attachment`
-        let embed_path = self.config.message_attachment_path(attachment);
+        // Build a relative filepath from the fully qualified one on the `Attachment`, or embed the data itself
+        let embedded = match self.config.options.single_file {
+            true => self.data_uri(attachment),
+            false => None,
+        };
+        let (embed_path, display_path, download) = match embedded {
+            // Data URIs are too long to show, and downloads need a name to save them as
+            Some(uri) => (
+                uri,
+                attachment.filename().to_string(),
+                format!(" download=\"{}\"", sanitize_html(attachment.filename())),
+            ),
+            None => {
+                let path = self.config.message_attachment_path(attachment);
+                (path.clone(), path, String::new())
+            }
+        };
+
+        // Show the people in a shared contact card instead of only a link to the file
+        if let Some(cards) = self.format_contact_cards(attachment, &embed_path, &download) {
+            return Ok(cards);
+        }
+
+        // Show a small preview that links to the full-size file, if requested
+        let thumbnail = self
+            .config
+            .options
+            .attachment_manager
+            .handle_thumbnail(message, attachment, self.config)
+            .and_then(|path| self.config.relative_path(path));
+
+        // Show the first page of a document as a card that links to the file
+        if let (Some(thumbnail), Some(_)) = (
+            &thumbnail,
+            attachment
+                .extension()
+                .and_then(DocumentType::from_extension),
+        ) {
+            let lazy = match self.config.options.no_lazy {
+                true => "",
+                false => " loading=\"lazy\"",
+            };
+            return Ok(format!(
+                "<a class=\"document_preview\" href=\"{embed_path}\"{download}><img src=\"{thumbnail}\"{lazy}><div class=\"name\">{}</div><div class=\"size\">{}</div></a>",
+                sanitize_html(attachment.filename()),
+                attachment.file_size()
+            ));
+        }
 
         return Ok(match attachment.mime_type() {
             MediaType::Image(_) => {
-                if self.config.options.no_lazy {
-                    format!("<img src=\"{embed_path}\">")
+                let image_path = thumbnail.as_deref().unwrap_or(&embed_path);
+                let image = if self.config.options.no_lazy {
+                    format!("<img src=\"{image_path}\">")
                 } else {
-                    format!("<img src=\"{embed_path}\" loading=\"lazy\">")
+                    format!("<img src=\"{image_path}\" loading=\"lazy\">")
+                };
+                let image = match thumbnail {
+                    Some(_) => format!("<a href=\"{embed_path}\">{image}</a>"),
+                    None => image,
+                };
+
+                // Play the video of a Live Photo over its still image on hover
+                let live_photo = match self.config.options.single_file {
+                    true => None,
+                    false => self
+                        .config
+                        .options
+                        .attachment_manager
+                        .handle_live_photo(message, attachment, self.config)
+                        .and_then(|path| self.config.relative_path(path)),
+                };
+                match live_photo {
+                    Some(video_path) => format!("<div class=\"live_photo\" onmouseenter=\"this.querySelector('video').play()\" onmouseleave=\"this.querySelector('video').pause()\">{image}<video src=\"{video_path}\" muted loop playsinline preload=\"none\"></video></div>"),
+                    None => image,
                 }
             }
             MediaType::Video(media_type) => {
+                // Only load the video once it is played when there is a thumbnail to show instead
+                let poster = match &thumbnail {
+                    Some(thumbnail) => format!(" preload=\"none\" poster=\"{thumbnail}\""),
+                    None => String::new(),
+                };
                 // See https://github.com/ReagentX/imessage-exporter/issues/73 for why duplicate the source tag
-                format!("<video controls> <source src=\"{embed_path}\" type=\"{media_type}\"> <source src=\"{embed_path}\"> </video>")
+                format!("<video controls{poster}> <source src=\"{embed_path}\" type=\"{media_type}\"> <source src=\"{embed_path}\"> </video>")
             }
             MediaType::Audio(media_type) => {
-                format!("<audio controls src=\"{embed_path}\" type=\"{media_type}\" </audio>")
+                let player =
+                    format!("<audio controls src=\"{embed_path}\" type=\"{media_type}\"></audio>");
+                // Label the player with the length of the audio, and its waveform where available
+                match attachment
+                    .get_audio_metadata(
+                        &self.config.options.platform,
+                        &self.config.options.db_path,
+                        self.config.options.attachment_root.as_deref(),
+                    )
+                    .ok()
+                    .flatten()
+                {
+                    Some(audio) => format!(
+                        "<div class=\"audio_message\">{}{player}<span class=\"duration\">{}</span></div>",
+                        HTML::format_waveform(&audio.waveform),
+                        audio.format_duration()
+                    ),
+                    None => player,
+                }
             }
             MediaType::Text(_) => {
                 format!(
-                    "<a href=\"{embed_path}\">Click to download {} ({})</a>",
+                    "<a href=\"{embed_path}\"{download}>Click to download {} ({})</a>",
                     attachment.filename(),
                     attachment.file_size()
                 )
             }
             MediaType::Application(_) => format!(
-                "<a href=\"{embed_path}\">Click to download {} ({})</a>",
+                "<a href=\"{embed_path}\"{download}>Click to download {} ({})</a>",
                 attachment.filename(),
                 attachment.file_size()
             ),
             MediaType::Unknown => {
-                format!("<p>Unknown attachment type: {embed_path}</p> <a href=\"{embed_path}\">Download ({})</a>", attachment.file_size())
+                format!("<p>Unknown attachment type: {display_path}</p> <a href=\"{embed_path}\"{download}>Download ({})</a>", attachment.file_size())
             }
             MediaType::Other(media_type) => {
-                format!("<p>Unable to embed {media_type} attachments: {embed_path}</p>")
+                format!("<p>Unable to embed {media_type} attachments: {display_path}</p>")
             }
         });
     }
@@ -635,10 +896,23 @@ impl<'a> Writer<'a> for HTML<'a> {
                 }
             }
 
+            if message.is_digital_touch() {
+                let payload = message
+                    .raw_payload_data(&self.config.db)
+                    .unwrap_or_default();
+                return Ok(self.format_digital_touch(message, &payload, message));
+            }
+
             if let Some(payload) = message.payload_data(&self.config.db) {
                 let res = if message.is_url() {
-                    let parsed = parse_plist(&payload)?;
-                    let bubble = URLMessage::get_url_message_override(&parsed)?;
+                    let parsed = match parse_plist(&payload) {
+                        Ok(parsed) => parsed,
+                        Err(why) => return self.format_url_text(message).ok_or(why),
+                    };
+                    let bubble = match URLMessage::get_url_message_override(&parsed) {
+                        Ok(bubble) => bubble,
+                        Err(why) => return self.format_url_text(message).ok_or(why),
+                    };
                     match bubble {
                         URLOverride::Normal(balloon) => self.format_url(message, &balloon, message),
                         URLOverride::AppleMusic(balloon) => self.format_music(&balloon, message),
@@ -649,6 +923,10 @@ impl<'a> Writer<'a> for HTML<'a> {
                         URLOverride::SharedPlacemark(balloon) => {
                             self.format_placemark(&balloon, message)
                         }
+                        URLOverride::FaceTime(balloon) => self.format_facetime(&balloon, message),
+                        URLOverride::ICloudPhotos(balloon) => {
+                            self.format_icloud_photos(&balloon, message)
+                        }
                     }
                 } else {
                     let parsed = parse_plist(&payload)?;
@@ -663,6 +941,7 @@ impl<'a> Writer<'a> for HTML<'a> {
                             CustomBalloon::CheckIn => self.format_check_in(&bubble, message),
                             CustomBalloon::FindMy => self.format_find_my(&bubble, message),
                             CustomBalloon::Handwriting => unreachable!(),
+                            CustomBalloon::DigitalTouch => unreachable!(),
                             CustomBalloon::URL => unreachable!(),
                         },
                         Err(why) => return Err(why),
@@ -672,21 +951,8 @@ impl<'a> Writer<'a> for HTML<'a> {
             } else {
                 // Sometimes, URL messages are missing their payloads
                 if message.is_url() {
-                    if let Some(text) = &message.text {
-                        let mut out_s = String::new();
-                        out_s.push_str("<a href=\"");
-                        out_s.push_str(text);
-                        out_s.push_str("\">");
-
-                        out_s.push_str("<div class=\"app_header\"><div class=\"name\">");
-                        out_s.push_str(text);
-                        out_s.push_str("</div></div>");
-
-                        out_s.push_str("<div class=\"app_footer\"><div class=\"caption\">");
-                        out_s.push_str(text);
-                        out_s.push_str("</div></div></a>");
-
-                        return Ok(out_s);
+                    if let Some(bubble) = self.format_url_text(message) {
+                        return Ok(bubble);
                     }
                 }
                 return Err(PlistParseError::NoPayload);
@@ -777,6 +1043,42 @@ impl<'a> Writer<'a> for HTML<'a> {
                         "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} changed the group photo.</p></div>\n"
                     )
                 }
+                Announcement::PhotoRemoved => {
+                    format!(
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} removed the group photo.</p></div>\n"
+                    )
+                }
+                Announcement::ParticipantAdded(handle) => {
+                    let added = self
+                        .config
+                        .who(Some(handle), false, &msg.destination_caller_id);
+                    format!(
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} added {added} to the conversation.</p></div>\n"
+                    )
+                }
+                Announcement::ParticipantRemoved(handle) => {
+                    let removed = self
+                        .config
+                        .who(Some(handle), false, &msg.destination_caller_id);
+                    format!(
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} removed {removed} from the conversation.</p></div>\n"
+                    )
+                }
+                Announcement::ParticipantLeft => {
+                    format!(
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} left the conversation.</p></div>\n"
+                    )
+                }
+                Announcement::AudioMessageKept => {
+                    format!(
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} kept an audio message.</p></div>\n"
+                    )
+                }
+                Announcement::UnknownEvent(num) => {
+                    format!(
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} performed unknown system event {num}</p></div>\n"
+                    )
+                }
                 Announcement::Unknown(num) => {
                     format!(
                         "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} performed unknown action {num}</p></div>\n"
@@ -808,6 +1110,13 @@ impl<'a> Writer<'a> for HTML<'a> {
         "<hr>Shared location!"
     }
 
+    fn format_transcription(&self, transcription: &str) -> String {
+        format!(
+            "<span class=\"label\">Transcription:</span> {}",
+            sanitize_html(transcription)
+        )
+    }
+
     fn format_edited(
         &self,
         msg: &'a Message,
@@ -821,13 +1130,33 @@ impl<'a> Writer<'a> for HTML<'a> {
 
             match edited_message_part.status {
                 EditStatus::Edited => {
-                    out_s.push_str("<table>");
+                    let history = &edited_message_part.edit_history;
+
+                    // The current text is shown like any other message part
+                    if let Some(current) = history.last() {
+                        out_s.push_str("<span class=\"bubble\">");
+                        out_s.push_str(&sanitize_html(&current.text));
+                        out_s.push_str("</span>");
+                    }
+
+                    // Earlier revisions are hidden until the indicator is clicked
+                    let summary = match history.len() {
+                        0..=2 => "Edited".to_string(),
+                        count => format!("Edited {} times", count - 1),
+                    };
+                    out_s.push_str("<details class=\"edit_history\"><summary>");
+                    out_s.push_str(&summary);
+                    out_s.push_str("</summary><table>");
 
-                    for (idx, event) in edited_message_part.edit_history.iter().enumerate() {
-                        let last = idx == edited_message_part.edit_history.len() - 1;
+                    for (idx, event) in history.iter().enumerate() {
+                        let last = idx == history.len() - 1;
                         let clean_text = sanitize_html(&event.text);
                         match previous_timestamp {
-                            None => out_s.push_str(&self.edited_to_html("", &clean_text, last)),
+                            None => out_s.push_str(&self.edited_to_html(
+                                &format(&get_local_time(&event.date, &self.config.offset)),
+                                &clean_text,
+                                last,
+                            )),
                             Some(prev_timestamp) => {
                                 let end = get_local_time(&event.date, &self.config.offset);
                                 let start = get_local_time(prev_timestamp, &self.config.offset);
@@ -845,7 +1174,7 @@ impl<'a> Writer<'a> for HTML<'a> {
                         previous_timestamp = Some(&event.date);
                     }
 
-                    out_s.push_str("</table>");
+                    out_s.push_str("</table></details>");
                 }
                 EditStatus::Unsent => {
                     let who = if msg.is_from_me() {
@@ -889,6 +1218,7 @@ impl<'a> Writer<'a> for HTML<'a> {
             TextEffect::Styles(styles) => Cow::Owned(self.format_styles(text, styles)),
             TextEffect::Animated(animation) => Cow::Owned(self.format_animated(text, animation)),
             TextEffect::Conversion(unit) => Cow::Owned(self.format_conversion(text, unit)),
+            TextEffect::Detected(detector) => Cow::Owned(self.format_detected(text, detector)),
         }
     }
 
@@ -1153,26 +1483,91 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
         out_s
     }
 
-    fn format_placemark(&self, balloon: &PlacemarkMessage, _: &'a Message) -> String {
+    fn format_facetime(&self, balloon: &FaceTimeMessage, _: &'a Message) -> String {
         let mut out_s = String::new();
 
         // Make the whole bubble clickable
-        if let Some(url) = balloon.get_url() {
+        out_s.push_str("<a href=\"");
+        out_s.push_str(balloon.url);
+        out_s.push_str("\">");
+
+        out_s.push_str("<div class=\"app_header\"><div class=\"name\">");
+        match balloon.title {
+            Some(title) => out_s.push_str(&sanitize_html(title)),
+            None => out_s.push_str("FaceTime"),
+        }
+        out_s.push_str("</div></div>");
+
+        out_s.push_str("<div class=\"app_footer\">");
+        out_s.push_str("<div class=\"caption\">FaceTime call link shared</div>");
+        out_s.push_str("<div class=\"subcaption\">Join the call</div>");
+        out_s.push_str("</div></a>");
+
+        out_s
+    }
+
+    fn format_icloud_photos(&self, balloon: &ICloudPhotosMessage, _: &'a Message) -> String {
+        let mut out_s = String::new();
+
+        // Make the whole bubble clickable
+        out_s.push_str("<a href=\"");
+        out_s.push_str(balloon.url);
+        out_s.push_str("\">");
+
+        let (name, caption) = match balloon.kind {
+            ICloudPhotosKind::SharedAlbum => ("Shared Album", "Shared Album invitation"),
+            ICloudPhotosKind::PhotoLink => ("iCloud Photos", "iCloud Photos link shared"),
+        };
+
+        out_s.push_str("<div class=\"app_header\"><div class=\"name\">");
+        match balloon.title {
+            Some(title) => out_s.push_str(&sanitize_html(title)),
+            None => out_s.push_str(name),
+        }
+        out_s.push_str("</div></div>");
+
+        out_s.push_str("<div class=\"app_footer\">");
+        out_s.push_str("<div class=\"caption\">");
+        out_s.push_str(caption);
+        out_s.push_str("</div>");
+        if let Some(summary) = balloon.summary {
+            out_s.push_str("<div class=\"subcaption\">");
+            out_s.push_str(&sanitize_html(summary));
+            out_s.push_str("</div>");
+        }
+        out_s.push_str("</div></a>");
+
+        out_s
+    }
+
+    fn format_placemark(&self, balloon: &PlacemarkMessage, _: &'a Message) -> String {
+        let mut out_s = String::new();
+        let coordinates = balloon
+            .coordinates()
+            .map(|(latitude, longitude)| format!("{latitude}, {longitude}"));
+
+        // Make the whole bubble clickable, linking to the coordinates if there is no URL
+        let link = balloon.get_url().map(String::from).or_else(|| {
+            balloon.coordinates().map(|(latitude, longitude)| {
+                format!("https://maps.apple.com/?ll={latitude},{longitude}")
+            })
+        });
+        if let Some(link) = &link {
             out_s.push_str("<a href=\"");
-            out_s.push_str(url);
+            out_s.push_str(link);
             out_s.push_str("\">");
         }
 
         // Header section
         out_s.push_str("<div class=\"app_header\">");
 
-        if let Some(place_name) = balloon.place_name {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(place_name);
-            out_s.push_str("</div>");
-        } else if let Some(url) = balloon.get_url() {
+        if let Some(name) = balloon
+            .place_name
+            .or(balloon.placemark.name)
+            .or(balloon.get_url())
+        {
             out_s.push_str("<div class=\"name\">");
-            out_s.push_str(url);
+            out_s.push_str(name);
             out_s.push_str("</div>");
         }
 
@@ -1184,6 +1579,7 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
             || balloon.placemark.postal_code.is_some()
             || balloon.placemark.country.is_some()
             || balloon.placemark.sub_administrative_area.is_some()
+            || coordinates.is_some()
         {
             out_s.push_str("<div class=\"app_footer\">");
 
@@ -1215,12 +1611,19 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
                 out_s.push_str("</div>");
             }
 
+            // Coordinates
+            if let Some(coordinates) = &coordinates {
+                out_s.push_str("<div class=\"coordinates\">");
+                out_s.push_str(coordinates);
+                out_s.push_str("</div>");
+            }
+
             // End footer
             out_s.push_str("</div>");
         }
 
         // End the link
-        if balloon.get_url().is_some() {
+        if link.is_some() {
             out_s.push_str("</a>");
         }
         out_s
@@ -1231,6 +1634,35 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
         balloon.render_svg()
     }
 
+    fn format_digital_touch(&self, msg: &Message, payload: &[u8], _: &Message) -> String {
+        let mut out_s = String::new();
+
+        out_s.push_str("<div class=\"app_header\"><div class=\"name\">Digital Touch</div></div>");
+        out_s.push_str("<div class=\"app_footer\">");
+        out_s.push_str("<div class=\"caption\">Digital Touch messages cannot be displayed</div>");
+
+        // Link to the strokes so they are not lost
+        if let Some(filepath) =
+            self.config
+                .options
+                .attachment_manager
+                .handle_digital_touch(msg, payload, self.config)
+        {
+            let path = self
+                .config
+                .relative_path(filepath.clone())
+                .unwrap_or(filepath.display().to_string());
+            out_s.push_str("<div class=\"subcaption\"><a href=\"");
+            out_s.push_str(&path);
+            out_s.push_str("\">Strokes</a></div>");
+        }
+
+        // End footer
+        out_s.push_str("</div>");
+
+        out_s
+    }
+
     fn format_apple_pay(&self, balloon: &AppMessage, _: &Message) -> String {
         let mut out_s = String::new();
 
@@ -1246,10 +1678,13 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
         out_s.push_str("</div>");
         out_s.push_str("<div class=\"app_footer\">");
 
-        if let Some(ldtext) = balloon.ldtext {
+        // The location itself is compressed in the URL, so describe what was shared instead
+        if let Some(ldtext) = balloon.ldtext.or(balloon.caption).or(balloon.subcaption) {
             out_s.push_str("<div class=\"caption\">");
             out_s.push_str(ldtext);
             out_s.push_str("</div>");
+        } else {
+            out_s.push_str("<div class=\"caption\">Shared location</div>");
         }
 
         // End footer
@@ -1372,13 +1807,20 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
         attachments: &mut Vec<Attachment>,
         message: &Message,
     ) -> String {
-        self.balloon_to_html(balloon, bundle_id, attachments, message)
+        // Apps we know how to read are described in a sentence instead of listing every field
+        match summarize_app(bundle_id, balloon) {
+            Some(summary) => self.summary_to_html(&summary, balloon, attachments, message),
+            None => self.balloon_to_html(balloon, bundle_id, attachments, message),
+        }
     }
 }
 
 impl<'a> TextEffectFormatter for HTML<'a> {
     fn format_mention(&self, text: &str, mentioned: &str) -> String {
-        format!("<span title=\"{mentioned}\"><b>{text}</b></span>")
+        format!(
+            "<span class=\"mention\" title=\"{}\">{text}</span>",
+            sanitize_html(self.config.mention_name(mentioned))
+        )
     }
 
     fn format_link(&self, text: &str, url: &str) -> String {
@@ -1415,9 +1857,54 @@ impl<'a> TextEffectFormatter for HTML<'a> {
     fn format_animated(&self, text: &str, animation: &Animation) -> String {
         format!("<span class=\"animation{animation:?}\">{text}</span>")
     }
+
+    fn format_detected(&self, text: &str, detector: &DataDetector) -> String {
+        match detector.link(text) {
+            Some(url) => self.format_link(text, &url),
+            None => format!("<u>{text}</u>"),
+        }
+    }
 }
 
 impl<'a> HTML<'a> {
+    /// Show a chat's icon at the top of its page, given as pairs of image paths and names
+    fn format_chat_icon(icons: &[(&str, &str)]) -> String {
+        let images: Vec<String> = icons
+            .iter()
+            .map(|(path, name)| {
+                format!(
+                    "<img src=\"{}\" alt=\"{}\">",
+                    sanitize_html(path),
+                    sanitize_html(name)
+                )
+            })
+            .collect();
+        format!("\n<div class=\"chat_icon\">{}</div>\n", images.join(" "))
+    }
+
+    /// List the members of a group chat and the changes to them, for the top of its page
+    fn format_roster(roster: &Roster) -> String {
+        let members: Vec<_> = roster
+            .members
+            .iter()
+            .map(|member| sanitize_html(member))
+            .collect();
+        let mut out_s = format!(
+            "\n<div class =\"announcement\"><p><b>Participants:</b> {}</p>",
+            members.join(", ")
+        );
+        if !roster.changes.is_empty() {
+            let changes: Vec<_> = roster
+                .changes
+                .iter()
+                .map(|change| sanitize_html(change))
+                .collect();
+            out_s.push_str(&format!("<p>{}</p>", changes.join("<br>")));
+        }
+        out_s.push_str("</div>\n");
+        out_s
+    }
+
     fn get_time(&self, message: &Message) -> String {
         let mut date = format(&message.date(&self.config.offset));
         let read_after = message.time_until_read(&self.config.offset);
@@ -1431,9 +1918,165 @@ impl<'a> HTML<'a> {
                 date.push_str(&format!(" (Read by {who} after {time})"));
             }
         }
+        if let Some(receipts) = self.config.receipts(message) {
+            date.push_str(&format!(" ({receipts})"));
+        }
         date
     }
 
+    /// Draw the waveform of an audio attachment as a row of bars
+    fn format_waveform(waveform: &[f64]) -> String {
+        if waveform.is_empty() {
+            return String::new();
+        }
+        let bars: String = waveform
+            .iter()
+            .enumerate()
+            .map(|(idx, level)| {
+                // Keep quiet parts visible as a thin line
+                let height = (level * WAVEFORM_HEIGHT).max(1.0);
+                format!(
+                    "<rect x=\"{}\" y=\"{:.1}\" width=\"2\" height=\"{height:.1}\"/>",
+                    idx * 3,
+                    (WAVEFORM_HEIGHT - height) / 2.0
+                )
+            })
+            .collect();
+        format!(
+            "<svg class=\"waveform\" viewBox=\"0 0 {} {WAVEFORM_HEIGHT}\" preserveAspectRatio=\"none\">{bars}</svg>",
+            waveform.len() * 3
+        )
+    }
+
+    /// Format the contacts in a contact card attachment, if it holds any, with a link to the file
+    fn format_contact_cards(
+        &self,
+        attachment: &Attachment,
+        embed_path: &str,
+        download: &str,
+    ) -> Option<String> {
+        let cards = attachment
+            .get_contact_cards(
+                &self.config.options.platform,
+                &self.config.options.db_path,
+                self.config.options.attachment_root.as_deref(),
+            )
+            .ok()?;
+        if cards.is_empty() {
+            return None;
+        }
+
+        let mut out_s = String::new();
+        for card in &cards {
+            out_s.push_str("<div class=\"contact_card\">");
+            if let Some(name) = card.display_name() {
+                out_s.push_str("<div class=\"name\">");
+                out_s.push_str(&sanitize_html(name));
+                out_s.push_str("</div>");
+            }
+            for detail in card.details() {
+                out_s.push_str("<div class=\"detail\">");
+                out_s.push_str(&sanitize_html(detail));
+                out_s.push_str("</div>");
+            }
+            out_s.push_str("</div>");
+        }
+        out_s.push_str(&format!(
+            "<a href=\"{embed_path}\"{download}>Download contact card ({})</a>",
+            attachment.file_size()
+        ));
+        Some(out_s)
+    }
+
+    /// Format a sticker that was placed on a message, to be shown over the message's bubble
+    fn format_placed_sticker(&self, msg: &Message) -> Result<String, TableError> {
+        let mut paths = Attachment::from_message(&self.config.db, msg)?;
+        let who = self
+            .config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        // Sticker messages have only one attachment, the sticker image
+        Ok(match paths.get_mut(0) {
+            Some(sticker) => {
+                let embed = match self.format_attachment(sticker, msg) {
+                    Ok(embed) => embed,
+                    Err(filename) => filename.to_string(),
+                };
+                format!(
+                    "<div class=\"placed_sticker\" title=\"Sticker from {}\">{embed}</div>",
+                    sanitize_html(who)
+                )
+            }
+            None => format!("<span class=\"tapback\">Sticker from {who} not found!</span>"),
+        })
+    }
+
+    /// Get the CSS class that replays the effect a message was sent with, if it has one
+    fn expressive_class(&self, message: &Message) -> Option<&'static str> {
+        match message.get_expressive() {
+            Expressive::Bubble(effect) => Some(match effect {
+                BubbleEffect::Slam => "effect_slam",
+                BubbleEffect::Loud => "effect_loud",
+                BubbleEffect::Gentle => "effect_gentle",
+                BubbleEffect::InvisibleInk => "effect_invisible_ink",
+            }),
+            // Screen effects cannot be drawn over the page, so the bubble is highlighted instead
+            Expressive::Screen(_) => Some("effect_screen"),
+            Expressive::Unknown(_) | Expressive::None => None,
+        }
+    }
+
+    /// Format a link bubble from the address in a URL message's text, for links without a readable preview
+    fn format_url_text(&self, message: &Message) -> Option<String> {
+        let text = message.text.as_deref()?;
+        let mut out_s = String::new();
+        out_s.push_str("<a href=\"");
+        out_s.push_str(text);
+        out_s.push_str("\">");
+
+        out_s.push_str("<div class=\"app_header\"><div class=\"name\">");
+        out_s.push_str(text);
+        out_s.push_str("</div></div>");
+
+        out_s.push_str("<div class=\"app_footer\"><div class=\"caption\">");
+        out_s.push_str(text);
+        out_s.push_str("</div></div></a>");
+
+        Some(out_s)
+    }
+
+    /// Format the start of the message a reply responded to, linking to where it is in the conversation
+    fn format_reply_quote(&self, parent: &Message) -> String {
+        let who = self.config.who(
+            parent.handle_id,
+            parent.is_from_me(),
+            &parent.destination_caller_id,
+        );
+        let mut out_s = format!(
+            "<a href=\"#p-{}\">Replying to {}</a>",
+            parent.guid,
+            sanitize_html(who)
+        );
+
+        // Attachments are represented by a placeholder character in the message text
+        let text = parent
+            .text
+            .as_deref()
+            .unwrap_or_default()
+            .replace('\u{FFFC}', "");
+        let text = text.trim();
+        if !text.is_empty() {
+            let mut quote: String = text.chars().take(REPLY_QUOTE_LENGTH).collect();
+            if text.chars().nth(REPLY_QUOTE_LENGTH).is_some() {
+                quote.push('…');
+            }
+            out_s.push_str(&format!(
+                "<blockquote class=\"reply_quote\">{}</blockquote>",
+                sanitize_html(&quote)
+            ));
+        }
+        out_s
+    }
+
     fn add_line(&self, string: &mut String, part: &str, pre: &str, post: &str) {
         if !part.is_empty() {
             string.push_str(pre);
@@ -1443,24 +2086,165 @@ impl<'a> HTML<'a> {
         }
     }
 
-    fn write_headers(file: &mut BufWriter<File>) -> Result<(), RuntimeError> {
-        // Write file header
-        HTML::write_to_file(file, HEADER)?;
+    /// Record the page a message belongs on, if conversations are split into pages
+    fn add_to_pages(&mut self, message: &Message) {
+        if let (Some(pages), Some((chatroom, _))) =
+            (&mut self.pages, self.config.conversation(message))
+        {
+            pages.add_message(
+                &self.config.filename(chatroom),
+                &message.date(&self.config.offset),
+            );
+        }
+    }
+
+    /// Write an anchor for a message and add it to the search index, if exporting a static site
+    fn add_to_site(&mut self, message: &Message) -> Result<(), RuntimeError> {
+        if self.site.is_none() {
+            return Ok(());
+        }
 
-        // Write CSS
-        HTML::write_to_file(file, "<style>\n")?;
-        HTML::write_to_file(file, STYLE)?;
-        HTML::write_to_file(file, "\n</style>")?;
-        HTML::write_to_file(file, "\n</head>\n<body>\n")?;
+        let anchor = format!("m-{}", message.rowid);
+        HTML::write_to_file(
+            self.get_or_create_file(message)?,
+            &format!("<span id=\"{anchor}\"></span>\n"),
+        )?;
+
+        let config = self.config;
+        let conversation = config
+            .conversation(message)
+            .map(|(chatroom, _)| config.filename(chatroom));
+        let sender = config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        let date = format(&message.date(&config.offset));
+        let page = conversation
+            .as_deref()
+            .and_then(|conversation| self.pages.as_ref()?.current(conversation))
+            .unwrap_or(ORPHANED)
+            .to_string();
+
+        if let Some(site) = &mut self.site {
+            site.add_message(
+                conversation.as_deref(),
+                &page,
+                &anchor,
+                sender,
+                &date,
+                message.text.as_deref(),
+            )?;
+        }
         Ok(())
     }
 
+    /// Build a data URI that embeds an attachment, if its data can be read
+    fn data_uri(&self, attachment: &Attachment) -> Option<String> {
+        let (data, _, mime_type) = read_attachment(self.config, attachment);
+        Some(format!(
+            "data:{mime_type};base64,{}",
+            base64(&data?).replace('\n', "")
+        ))
+    }
+
+    /// Wrap the markup for a message in the message template
+    pub(super) fn wrap_message(
+        &self,
+        message: &Message,
+        content: String,
+    ) -> Result<String, RuntimeError> {
+        let sender = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        self.templates.message(
+            content,
+            message,
+            sender,
+            &format(&message.date(&self.config.offset)),
+        )
+    }
+
+    pub(super) fn write_headers(
+        templates: &templates::Templates,
+        file: &mut BufWriter<File>,
+        title: &str,
+    ) -> Result<(), RuntimeError> {
+        let (header, _) = templates.page(title)?;
+        HTML::write_to_file(file, &header)
+    }
+
+    pub(super) fn write_footers(
+        templates: &templates::Templates,
+        file: &mut BufWriter<File>,
+        title: &str,
+    ) -> Result<(), RuntimeError> {
+        let (_, footer) = templates.page(title)?;
+        HTML::write_to_file(file, &footer)
+    }
+
     fn edited_to_html(&self, timestamp: &str, text: &str, last: bool) -> String {
         let tag = if last { "tfoot" } else { "tbody" };
         format!("<{tag}><tr><td><span class=\"timestamp\">{timestamp}</span></td><td>{text}</td></tr></{tag}>")
     }
 
-    fn balloon_to_html(
+    /// Render the description of an app message in a bubble, with its details in the footer
+    fn summary_to_html(
+        &self,
+        summary: &AppSummary,
+        balloon: &AppMessage,
+        attachments: &mut [Attachment],
+        message: &Message,
+    ) -> String {
+        let mut out_s = String::new();
+        if let Some(url) = balloon.url {
+            out_s.push_str("<a href=\"");
+            out_s.push_str(url);
+            out_s.push_str("\">");
+        }
+        out_s.push_str("<div class=\"app_header\">");
+
+        // Image
+        if let Some(image) = balloon.image {
+            out_s.push_str("<img src=\"");
+            out_s.push_str(image);
+            out_s.push_str("\">");
+        } else if let Some(attachment) = attachments.get_mut(0) {
+            out_s.push_str(
+                &self
+                    .format_attachment(attachment, message)
+                    .unwrap_or_default(),
+            );
+        }
+
+        // Name
+        out_s.push_str("<div class=\"name\">");
+        out_s.push_str(&sanitize_html(&summary.app));
+        out_s.push_str("</div>");
+
+        // Header end, footer begin
+        out_s.push_str("</div><div class=\"app_footer\">");
+
+        out_s.push_str("<div class=\"caption\">");
+        out_s.push_str(&sanitize_html(&summary.description));
+        out_s.push_str("</div>");
+
+        summary.details.iter().for_each(|detail| {
+            out_s.push_str("<div class=\"subcaption\">");
+            out_s.push_str(&sanitize_html(detail));
+            out_s.push_str("</div>");
+        });
+
+        out_s.push_str("</div>");
+        if balloon.url.is_some() {
+            out_s.push_str("</a>");
+        }
+        out_s
+    }
+
+    fn balloon_to_html(
         &self,
         balloon: &AppMessage,
         bundle_id: &str,
@@ -1566,13 +2350,18 @@ impl<'a> HTML<'a> {
 mod tests {
     use std::{
         collections::HashMap,
-        env::{current_dir, set_var},
+        env::{current_dir, set_var, temp_dir},
+        fs::{create_dir_all, write},
         path::PathBuf,
     };
 
     use crate::{
-        app::attachment_manager::AttachmentManager, exporters::exporter::Writer, Config, Exporter,
-        Options, HTML,
+        app::{
+            attachment_manager::AttachmentManager, chat_type::ChatType, roster::Roster,
+            theme::Theme,
+        },
+        exporters::exporter::Writer,
+        Config, Exporter, Options, HTML,
     };
     use imessage_database::{
         tables::{
@@ -1613,6 +2402,8 @@ mod tests {
             thread_originator_guid: None,
             thread_originator_part: None,
             date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
             chat_id: None,
             associated_message_emoji: None,
             num_attachments: 0,
@@ -1637,6 +2428,39 @@ mod tests {
             use_caller_id: false,
             platform: Platform::macOS,
             ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
         }
     }
 
@@ -1648,11 +2472,23 @@ mod tests {
             chatroom_participants: HashMap::new(),
             participants: HashMap::new(),
             real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
             tapbacks: HashMap::new(),
             options,
             offset: get_offset(),
             db,
             converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
         }
     }
 
@@ -1667,6 +2503,7 @@ mod tests {
             is_sticker: false,
             hide_attachment: 0,
             copied_path: None,
+            backup_root: None,
         }
     }
 
@@ -1767,6 +2604,41 @@ mod tests {
         assert_eq!(s, "<div>hello world</div>\n".to_string());
     }
 
+    #[test]
+    fn can_format_html_chat_icon() {
+        let actual = HTML::format_chat_icon(&[("attachments/3/chat_icon.jpeg", "Family")]);
+        let expected = "\n<div class=\"chat_icon\"><img src=\"attachments/3/chat_icon.jpeg\" alt=\"Family\"></div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_html_chat_icon_my_avatar() {
+        let actual = HTML::format_chat_icon(&[
+            ("attachments/avatars/4.jpg", "Jane"),
+            ("attachments/avatars/me.png", "Me"),
+        ]);
+        let expected = "\n<div class=\"chat_icon\"><img src=\"attachments/avatars/4.jpg\" alt=\"Jane\"> <img src=\"attachments/avatars/me.png\" alt=\"Me\"></div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_html_roster() {
+        let roster = Roster {
+            members: vec!["Alice & Bob".to_string(), "Me".to_string()],
+            changes: vec![
+                "May 17, 2022  5:29:42 PM: Me added Alice & Bob".to_string(),
+                "May 18, 2022  5:29:42 PM: Alice & Bob left".to_string(),
+            ],
+        };
+
+        let actual = HTML::format_roster(&roster);
+        let expected = "\n<div class =\"announcement\"><p><b>Participants:</b> Alice &amp; Bob, Me</p><p>May 17, 2022  5:29:42 PM: Me added Alice &amp; Bob<br>May 18, 2022  5:29:42 PM: Alice &amp; Bob left</p></div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_from_me_normal() {
         // Set timezone to PST for consistent Local time
@@ -1790,6 +2662,31 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_from_me_expressive() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+        message.expressive_send_style_id =
+            Some("com.apple.MobileSMS.expressivesend.impact".to_string());
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part effect_slam\">\n<span class=\"bubble\">Hello world</span>\n</div>\n<span class=\"expressive\">Sent with Slam</span>\n</div>\n</div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_message_with_html() {
         // Set timezone to PST for consistent Local time
@@ -1947,6 +2844,65 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_reply_quote() {
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config.participants.insert(10, "Sam".to_string());
+
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut parent = blank();
+        parent.guid = "parent".to_string();
+        parent.handle_id = Some(10);
+        parent.text = Some("\u{FFFC}Lunch?".to_string());
+
+        let actual = exporter.format_reply_quote(&parent);
+        let expected = "<a href=\"#p-parent\">Replying to Sam</a><blockquote class=\"reply_quote\">Lunch?</blockquote>";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_html_reply_quote_long() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut parent = blank();
+        parent.guid = "parent".to_string();
+        parent.is_from_me = true;
+        parent.text = Some("a".repeat(150));
+
+        let actual = exporter.format_reply_quote(&parent);
+        let expected = format!(
+            "<a href=\"#p-parent\">Replying to Me</a><blockquote class=\"reply_quote\">{}…</blockquote>",
+            "a".repeat(100)
+        );
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_html_reply_quote_attachment() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut parent = blank();
+        parent.guid = "parent".to_string();
+        parent.is_from_me = true;
+        parent.text = Some("\u{FFFC}".to_string());
+
+        let actual = exporter.format_reply_quote(&parent);
+        let expected = "<a href=\"#p-parent\">Replying to Me</a>";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_shareplay() {
         // Set timezone to PST for consistent Local time
@@ -2018,6 +2974,39 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_announcement_group_events() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config.participants.insert(1, "Alice".to_string());
+        config.participants.insert(2, "Bob".to_string());
+
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.handle_id = Some(1);
+        message.item_type = 1;
+        message.other_handle = 2;
+
+        let actual = exporter.format_announcement(&message);
+        let expected = "\n<div class =\"announcement\"><p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span> Alice added Bob to the conversation.</p></div>\n";
+        assert_eq!(actual, expected);
+
+        message.handle_id = Some(2);
+        message.item_type = 3;
+        message.other_handle = 0;
+
+        let actual = exporter.format_announcement(&message);
+        let expected = "\n<div class =\"announcement\"><p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span> Bob left the conversation.</p></div>\n";
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_tapback_me() {
         // Set timezone to PST for consistent Local time
@@ -2096,6 +3085,33 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_tapback_custom_emoji_from_text() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.associated_message_type = Some(2006);
+        message.associated_message_guid = Some("fake_guid".to_string());
+        message.handle_id = Some(999999);
+        message.text = Some("Reacted 🎉 to “Hello world”".to_string());
+
+        let actual = exporter.format_tapback(&message).unwrap();
+        let expected = "<span class=\"tapback\"><b>🎉</b> by Sample Contact</span>";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_tapback_custom_sticker() {
         // Set timezone to PST for consistent Local time
@@ -2123,6 +3139,27 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_placed_sticker_missing() {
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut message = blank();
+        message.associated_message_type = Some(1000);
+        message.associated_message_guid = Some("p:0/fake_guid".to_string());
+        message.handle_id = Some(999999);
+
+        let actual = exporter.format_placed_sticker(&message).unwrap();
+        let expected = "<span class=\"tapback\">Sticker from Sample Contact not found!</span>";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_started_sharing_location_me() {
         // Set timezone to PST for consistent Local time
@@ -2235,6 +3272,206 @@ mod tests {
         assert_eq!(actual, "<img src=\"a/b/c/d.jpg\" loading=\"lazy\">");
     }
 
+    #[test]
+    fn can_format_html_attachment_live_photo() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = blank();
+
+        let dir = temp_dir().join("imessage-exporter-live-photo");
+        create_dir_all(&dir).unwrap();
+        write(dir.join("IMG_0001.HEIC"), "still").unwrap();
+        write(dir.join("IMG_0001.MOV"), "video").unwrap();
+        let mut attachment = fake_attachment();
+        attachment.filename = Some(dir.join("IMG_0001.HEIC").display().to_string());
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(
+            actual,
+            format!(
+                "<div class=\"live_photo\" onmouseenter=\"this.querySelector('video').play()\" onmouseleave=\"this.querySelector('video').pause()\"><img src=\"{}\" loading=\"lazy\"><video src=\"{}\" muted loop playsinline preload=\"none\"></video></div>",
+                dir.join("IMG_0001.HEIC").display(),
+                // The video is linked relative to the export path, which holds the test files
+                config.relative_path(dir.join("IMG_0001.MOV")).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn can_format_html_attachment_contact_card() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = blank();
+
+        let dir = temp_dir().join("imessage-exporter-html-contact-card");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("Jane Doe.vcf");
+        write(
+            &path,
+            "BEGIN:VCARD\nFN:Jane <Doe>\nTEL:+15558675309\nEND:VCARD\n",
+        )
+        .unwrap();
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("text/vcard".to_string());
+        attachment.filename = Some(path.display().to_string());
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(
+            actual,
+            format!(
+                "<div class=\"contact_card\"><div class=\"name\">Jane &lt;Doe&gt;</div><div class=\"detail\">+15558675309</div></div><a href=\"{}\">Download contact card (100.00 B)</a>",
+                path.display()
+            )
+        );
+    }
+
+    #[test]
+    fn can_format_html_attachment_audio_duration() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = blank();
+
+        // An `M4A` file that is 65 seconds long at a timescale of 1 kHz
+        let mut mvhd = vec![0; 12];
+        mvhd.extend_from_slice(&1000u32.to_be_bytes());
+        mvhd.extend_from_slice(&65000u32.to_be_bytes());
+        let mut data = 16u32.to_be_bytes().to_vec();
+        data.extend_from_slice(b"ftypM4A \0\0\0\0");
+        data.extend_from_slice(&(mvhd.len() as u32 + 16).to_be_bytes());
+        data.extend_from_slice(b"moov");
+        data.extend_from_slice(&(mvhd.len() as u32 + 8).to_be_bytes());
+        data.extend_from_slice(b"mvhd");
+        data.extend_from_slice(&mvhd);
+
+        let dir = temp_dir().join("imessage-exporter-html-audio");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("Audio Message.m4a");
+        write(&path, data).unwrap();
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("audio/mp4".to_string());
+        attachment.filename = Some(path.display().to_string());
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(
+            actual,
+            format!(
+                "<div class=\"audio_message\"><audio controls src=\"{}\" type=\"mp4\"></audio><span class=\"duration\">1:05</span></div>",
+                path.display()
+            )
+        );
+    }
+
+    #[test]
+    fn can_format_html_attachment_expired_audio() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut message = blank();
+        message.is_audio_message = true;
+        message.expire_state = 1;
+
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("audio/x-caf".to_string());
+        attachment.filename = None;
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(
+            actual,
+            "<span class=\"attachment_removed\">Expired audio message</span>"
+        );
+    }
+
+    #[test]
+    fn can_format_html_transcription() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let actual = exporter.format_transcription("<snacks>&drinks");
+
+        assert_eq!(
+            actual,
+            "<span class=\"label\">Transcription:</span> &lt;snacks&gt;&amp;drinks"
+        );
+    }
+
+    #[test]
+    fn can_format_html_waveform() {
+        assert_eq!(
+            HTML::format_waveform(&[0.0, 1.0]),
+            "<svg class=\"waveform\" viewBox=\"0 0 6 20\" preserveAspectRatio=\"none\"><rect x=\"0\" y=\"9.5\" width=\"2\" height=\"1.0\"/><rect x=\"3\" y=\"0.0\" width=\"2\" height=\"20.0\"/></svg>"
+        );
+        assert!(HTML::format_waveform(&[]).is_empty());
+    }
+
+    #[test]
+    fn can_format_html_attachment_single_file() {
+        // Create exporter
+        let mut options = fake_options();
+        options.single_file = true;
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = blank();
+
+        let path = PathBuf::from("/tmp/single_file_attachment.png");
+        write(&path, b"foo").unwrap();
+        let mut attachment = fake_attachment();
+        attachment.copied_path = Some(path);
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(
+            actual,
+            "<img src=\"data:image/png;base64,Zm9v\" loading=\"lazy\">"
+        );
+    }
+
+    #[test]
+    fn can_format_html_attachment_single_file_missing() {
+        // Create exporter
+        let mut options = fake_options();
+        options.single_file = true;
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = blank();
+
+        let mut attachment = fake_attachment();
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(actual, "<img src=\"a/b/c/d.jpg\" loading=\"lazy\">");
+    }
+
     #[test]
     fn can_format_html_attachment_macos_invalid() {
         // Create exporter
@@ -2335,6 +3572,8 @@ mod balloon_format_tests {
         app::AppMessage,
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
+        facetime::FaceTimeMessage,
+        icloud_photos::{ICloudPhotosKind, ICloudPhotosMessage},
         music::MusicMessage,
         placemark::{Placemark, PlacemarkMessage},
         url::URLMessage,
@@ -2391,6 +3630,23 @@ mod balloon_format_tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn can_format_html_url_text() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut message = blank();
+        message.text = Some("https://example.com".to_string());
+
+        let expected = exporter.format_url_text(&message);
+        let actual = "<a href=\"https://example.com\"><div class=\"app_header\"><div class=\"name\">https://example.com</div></div><div class=\"app_footer\"><div class=\"caption\">https://example.com</div></div></a>";
+
+        assert_eq!(expected.as_deref(), Some(actual));
+        assert!(exporter.format_url_text(&blank()).is_none());
+    }
+
     #[test]
     fn can_format_html_music() {
         // Create exporter
@@ -2460,6 +3716,37 @@ mod balloon_format_tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn can_format_html_known_app() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let balloon = AppMessage {
+            image: None,
+            url: Some("url"),
+            title: None,
+            subtitle: None,
+            caption: Some("Your move."),
+            subcaption: None,
+            trailing_caption: None,
+            trailing_subcaption: None,
+            app_name: Some("GamePigeon"),
+            ldtext: Some("Chess"),
+        };
+
+        let expected = exporter.format_generic_app(
+            &balloon,
+            "com.gamerdelights.gamepigeon.ext.MessagesExtension",
+            &mut vec![],
+            &blank(),
+        );
+        let actual = "<a href=\"url\"><div class=\"app_header\"><div class=\"name\">GamePigeon</div></div><div class=\"app_footer\"><div class=\"caption\">Game of Chess</div><div class=\"subcaption\">Your move.</div></div></a>";
+
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn can_format_html_fitness() {
         // Create exporter
@@ -2538,6 +3825,57 @@ mod balloon_format_tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn can_format_html_facetime() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let balloon = FaceTimeMessage {
+            url: "https://facetime.apple.com/join#v=1&p=AbCdEf",
+            title: None,
+        };
+
+        let expected = exporter.format_facetime(&balloon, &blank());
+        let actual = "<a href=\"https://facetime.apple.com/join#v=1&p=AbCdEf\"><div class=\"app_header\"><div class=\"name\">FaceTime</div></div><div class=\"app_footer\"><div class=\"caption\">FaceTime call link shared</div><div class=\"subcaption\">Join the call</div></div></a>";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_format_html_icloud_photos() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let balloon = ICloudPhotosMessage {
+            kind: ICloudPhotosKind::PhotoLink,
+            url: "https://share.icloud.com/photos/0a1B2c3D4e5F6g7H8i9J",
+            title: None,
+            summary: Some("12 Photos"),
+        };
+
+        let expected = exporter.format_icloud_photos(&balloon, &blank());
+        let actual = "<a href=\"https://share.icloud.com/photos/0a1B2c3D4e5F6g7H8i9J\"><div class=\"app_header\"><div class=\"name\">iCloud Photos</div></div><div class=\"app_footer\"><div class=\"caption\">iCloud Photos link shared</div><div class=\"subcaption\">12 Photos</div></div></a>";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_format_html_digital_touch() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let expected = exporter.format_digital_touch(&blank(), &[], &blank());
+        let actual = "<div class=\"app_header\"><div class=\"name\">Digital Touch</div></div><div class=\"app_footer\"><div class=\"caption\">Digital Touch messages cannot be displayed</div></div>";
+
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn can_format_html_check_in_timer() {
         // Set timezone to PST for consistent Local time
@@ -2678,6 +4016,26 @@ mod balloon_format_tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn can_format_html_placemark_coordinates() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let balloon = PlacemarkMessage {
+            url: None,
+            original_url: Some("https://maps.apple.com/?ll=33.450858,-118.508212&t=m"),
+            place_name: None,
+            placemark: Placemark::default(),
+        };
+
+        let expected = exporter.format_placemark(&balloon, &blank());
+        let actual = "<a href=\"https://maps.apple.com/?ll=33.450858,-118.508212&t=m\"><div class=\"app_header\"><div class=\"name\">https://maps.apple.com/?ll=33.450858,-118.508212&t=m</div></div><div class=\"app_footer\"><div class=\"coordinates\">33.450858, -118.508212</div></div></a>";
+
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn can_format_html_generic_app() {
         // Create exporter
@@ -2713,8 +4071,8 @@ mod text_effect_tests {
         Exporter, HTML,
     };
     use imessage_database::{
-        message_types::text_effects::{Style, TextEffect, Unit},
-        util::typedstream::parser::TypedStreamReader,
+        message_types::text_effects::{DataDetector, Style, TextEffect, Unit},
+        util::typedstream::{models::Archivable, parser::TypedStreamReader},
     };
     use std::{
         env::{current_dir, set_var},
@@ -2743,7 +4101,7 @@ mod text_effect_tests {
         let exporter = HTML::new(&config).unwrap();
 
         let expected = exporter.format_mention("Chris", "+15558675309");
-        let actual = "<span title=\"+15558675309\"><b>Chris</b></span>";
+        let actual = "<span class=\"mention\" title=\"+15558675309\">Chris</span>";
 
         assert_eq!(expected, actual);
     }
@@ -2834,6 +4192,33 @@ mod text_effect_tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn can_format_html_detected_address() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let expected = exporter.format_detected("1 Infinite Loop", &DataDetector::Address);
+        let actual =
+            "<a href=\"https://maps.apple.com/?q=1%20Infinite%20Loop\">1 Infinite Loop</a>";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_format_html_detected_flight() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let expected = exporter.format_detected("UA 123", &DataDetector::Flight);
+        let actual = "<u>UA 123</u>";
+
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn can_format_html_mention_end_to_end() {
         // Set timezone to PST for consistent Local time
@@ -2861,10 +4246,13 @@ mod text_effect_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Test <span title=\"+15558675309\"><b>Dad</b></span> </span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Test <span class=\"mention\" title=\"+15558675309\">Dad</span> </span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -2896,7 +4284,10 @@ mod text_effect_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><u>000123</u> is your security code. Don&apos;t share your code.</span>\n</div>\n</div>\n</div>\n";
@@ -2931,7 +4322,10 @@ mod text_effect_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><a href=\"https://twitter.com/xxxxxxxxx/status/0000223300009216128\">https://twitter.com/xxxxxxxxx/status/0000223300009216128</a></span>\n</div>\n</div>\n</div>\n";
@@ -2966,7 +4360,10 @@ mod text_effect_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hi. Right now or <u>tomorrow</u>?</span>\n</div>\n</div>\n</div>\n";
@@ -3001,7 +4398,10 @@ mod text_effect_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><span class=\"animationBig\">Big</span> <span class=\"animationSmall\">small </span><span class=\"animationShake\">shake</span> <span class=\"animationNod\">nod</span> <span class=\"animationExplode\">explode </span><span class=\"animationRipple\">ripple</span> <span class=\"animationBloom\">bloom</span> <span class=\"animationJitter\">jitter</span></span>\n</div>\n</div>\n</div>\n";
@@ -3036,7 +4436,10 @@ mod text_effect_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><b>Bold</b> <u>underline</u> <i>italic</i> <s>strikethrough</s> all <i><u><s><b>four</b></s></u></i></span>\n</div>\n</div>\n</div>\n";
@@ -3071,7 +4474,10 @@ mod text_effect_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><i><u><s><b>Everything</b></s></u></i></span>\n</div>\n</div>\n</div>\n";
@@ -3106,7 +4512,10 @@ mod text_effect_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><u>Underline</u> normal <span class=\"animationJitter\">jitter</span> normal</span>\n</div>\n</div>\n</div>\n";
@@ -3127,10 +4536,77 @@ mod edited_tests {
 
     use crate::{exporters::exporter::Writer, Exporter, HTML};
     use imessage_database::{
-        message_types::edited::{EditStatus, EditedMessage, EditedMessagePart},
-        util::typedstream::parser::TypedStreamReader,
+        message_types::edited::{EditStatus, EditedEvent, EditedMessage, EditedMessagePart},
+        util::typedstream::{models::Archivable, parser::TypedStreamReader},
     };
 
+    #[test]
+    fn can_format_html_edited_history() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = blank();
+        let edited = EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Edited,
+                edit_history: vec![
+                    EditedEvent {
+                        // May 17, 2022  8:29:42 PM
+                        date: 674526582885055488,
+                        text: "Hello".to_string(),
+                        guid: None,
+                    },
+                    EditedEvent {
+                        date: 674526642885055488,
+                        text: "Hello <world>".to_string(),
+                        guid: None,
+                    },
+                ],
+            }],
+        };
+
+        let actual = exporter.format_edited(&message, &edited, 0, "").unwrap();
+        let expected = "<span class=\"bubble\">Hello &lt;world&gt;</span><details class=\"edit_history\"><summary>Edited</summary><table><tbody><tr><td><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span></td><td>Hello</td></tr></tbody><tfoot><tr><td><span class=\"timestamp\">Edited 1 minute later</span></td><td>Hello &lt;world&gt;</td></tr></tfoot></table></details>";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_html_edited_count() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = blank();
+        let edited = EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Edited,
+                edit_history: ["One", "Two", "Three"]
+                    .iter()
+                    .map(|text| EditedEvent {
+                        date: 674526582885055488,
+                        text: text.to_string(),
+                        guid: None,
+                    })
+                    .collect(),
+            }],
+        };
+
+        let actual = exporter.format_edited(&message, &edited, 0, "").unwrap();
+
+        assert!(actual.starts_with("<span class=\"bubble\">Three</span>"));
+        assert!(actual.contains("<summary>Edited 2 times</summary>"));
+    }
+
     #[test]
     fn can_format_html_conversion_final_unsent() {
         // Set timezone to PST for consistent Local time
@@ -3181,7 +4657,10 @@ mod edited_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">From arbitrary byte stream:\r</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"attachment_error\">Attachment does not exist!</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">To native Rust data structures:\r</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"unsent\"><span class=\"unsent\">You unsent this message part 1 hour, 49 seconds after sending!</span></span>\n</div>\n</div>\n</div>\n";
@@ -3218,7 +4697,10 @@ mod edited_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">From arbitrary byte stream:\r</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"attachment_error\">Attachment does not exist!</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">To native Rust data structures:\r</span>\n</div>\n</div>\n</div>\n";
@@ -3260,7 +4742,10 @@ mod edited_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_announcement(&message);
         let expected = "<div class =\"announcement\"><p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span> You unsent a message.</p></div>";
@@ -0,0 +1,220 @@
+/*!
+ Splits the conversations in HTML exports into pages.
+
+ Large conversations make HTML files that are too big for browsers to open, so conversations can
+ be split into a page per year, a page per month, or a page for every group of some number of
+ messages. Each page links to the conversation's other pages.
+*/
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::app::pagination::Pagination;
+
+use imessage_database::error::message::MessageError;
+
+/// Local date and time, as returned by [`Message::date()`](imessage_database::tables::messages::Message::date)
+type Date = chrono::DateTime<chrono::Local>;
+
+pub(crate) struct Pages {
+    /// How conversations are split
+    pagination: Pagination,
+    /// Map of conversation name to its pages, in the order they were created, and how many messages each has
+    conversations: BTreeMap<String, Vec<(String, usize)>>,
+    /// Map of page name to the conversation it belongs to
+    page_conversations: HashMap<String, String>,
+    /// Map of conversation name to the page its last message was written to
+    current: HashMap<String, String>,
+}
+
+impl Pages {
+    pub(super) fn new(pagination: Pagination) -> Self {
+        Pages {
+            pagination,
+            conversations: BTreeMap::new(),
+            page_conversations: HashMap::new(),
+            current: HashMap::new(),
+        }
+    }
+
+    /// Record a message sent to a conversation at `date`, returning the name of the page it belongs on
+    pub(super) fn add_message(
+        &mut self,
+        conversation: &str,
+        date: &Result<Date, MessageError>,
+    ) -> String {
+        let pages = self
+            .conversations
+            .entry(conversation.to_string())
+            .or_default();
+
+        let label = match (self.pagination, date) {
+            (Pagination::Year, Ok(date)) => date.format("%Y").to_string(),
+            (Pagination::Month, Ok(date)) => date.format("%Y-%m").to_string(),
+            (Pagination::Year | Pagination::Month, Err(_)) => String::from("Unknown"),
+            (Pagination::Messages(size), _) => match pages.last() {
+                Some((label, count)) if *count < size => label.clone(),
+                _ => format!("Page {}", pages.len() + 1),
+            },
+        };
+
+        // Messages are sorted by date, so the page is usually the last one
+        match pages.iter_mut().rev().find(|(page, _)| *page == label) {
+            Some((_, count)) => *count += 1,
+            None => pages.push((label.clone(), 1)),
+        }
+
+        let page = page_name(conversation, &label);
+        self.page_conversations
+            .entry(page.clone())
+            .or_insert_with(|| conversation.to_string());
+        self.current.insert(conversation.to_string(), page.clone());
+        page
+    }
+
+    /// Get the name of the page the last message sent to a conversation belongs on
+    pub(super) fn current(&self, conversation: &str) -> Option<&str> {
+        self.current.get(conversation).map(String::as_str)
+    }
+
+    /// Get each conversation, with its pages and how many messages each has
+    pub(super) fn conversations(&self) -> impl Iterator<Item = (&String, &Vec<(String, usize)>)> {
+        self.conversations.iter()
+    }
+
+    /// Build the navigation links written at the bottom of a page to the conversation's other pages
+    ///
+    /// If `index` is true, the links include the index page of a static site.
+    pub(super) fn nav(&self, page: &str, index: bool) -> String {
+        let mut nav = String::from("<nav class=\"pages\">\n");
+        if index {
+            nav.push_str("<a href=\"index.html\">All conversations</a>\n");
+        }
+
+        if let Some((conversation, pages)) = self
+            .page_conversations
+            .get(page)
+            .and_then(|conversation| Some((conversation, self.conversations.get(conversation)?)))
+        {
+            let position = pages
+                .iter()
+                .position(|(label, _)| page_name(conversation, label) == page);
+            let link = |idx: usize, text: &str| {
+                pages.get(idx).map(|(label, _)| {
+                    format!(
+                        "<a href=\"{}\">{text}</a>\n",
+                        page_href(&page_name(conversation, label))
+                    )
+                })
+            };
+
+            if let Some(previous) = position.and_then(|idx| link(idx.checked_sub(1)?, "Previous")) {
+                nav.push_str(&previous);
+            }
+            nav.push_str(&page_links(conversation, pages));
+            nav.push('\n');
+            if let Some(next) = position.and_then(|idx| link(idx + 1, "Next")) {
+                nav.push_str(&next);
+            }
+        }
+
+        nav.push_str("</nav>\n");
+        nav
+    }
+}
+
+/// Get the name of the page that holds a conversation's messages for a label, i.e. a year
+fn page_name(conversation: &str, label: &str) -> String {
+    format!("{conversation} - {label}")
+}
+
+/// Build links to each page of a conversation
+pub(super) fn page_links(conversation: &str, pages: &[(String, usize)]) -> String {
+    pages
+        .iter()
+        .map(|(label, _)| {
+            format!(
+                "<a href=\"{}\">{label}</a>",
+                page_href(&page_name(conversation, label))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Build a relative link to a page, percent-encoding characters that are not safe in a URL
+pub(super) fn page_href(page: &str) -> String {
+    let mut href = String::with_capacity(page.len() + 5);
+    for byte in format!("{page}.html").bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            href.push(byte as char);
+        } else {
+            href.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    href
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use super::{page_href, Pages};
+    use crate::app::pagination::Pagination;
+
+    #[test]
+    fn can_build_page_href() {
+        assert_eq!(
+            page_href("Person 10, Person 11 - 2022"),
+            "Person%2010%2C%20Person%2011%20-%202022.html"
+        );
+        assert_eq!(page_href("Café #1"), "Caf%C3%A9%20%231.html");
+    }
+
+    #[test]
+    fn can_split_by_month() {
+        let mut pages = Pages::new(Pagination::Month);
+        let may = Ok(Local.with_ymd_and_hms(2022, 5, 17, 12, 0, 0).unwrap());
+        let june = Ok(Local.with_ymd_and_hms(2022, 6, 1, 12, 0, 0).unwrap());
+
+        assert_eq!(pages.add_message("Chat", &may), "Chat - 2022-05");
+        assert_eq!(pages.add_message("Chat", &may), "Chat - 2022-05");
+        assert_eq!(pages.add_message("Chat", &june), "Chat - 2022-06");
+        assert_eq!(pages.current("Chat"), Some("Chat - 2022-06"));
+        assert_eq!(pages.current("Other"), None);
+    }
+
+    #[test]
+    fn can_split_by_message_count() {
+        let mut pages = Pages::new(Pagination::Messages(2));
+        let date = Ok(Local.with_ymd_and_hms(2022, 5, 17, 12, 0, 0).unwrap());
+
+        assert_eq!(pages.add_message("Chat", &date), "Chat - Page 1");
+        assert_eq!(pages.add_message("Other", &date), "Other - Page 1");
+        assert_eq!(pages.add_message("Chat", &date), "Chat - Page 1");
+        assert_eq!(pages.add_message("Chat", &date), "Chat - Page 2");
+    }
+
+    #[test]
+    fn can_build_nav() {
+        let mut pages = Pages::new(Pagination::Year);
+        for year in [2020, 2021, 2022] {
+            let date = Ok(Local.with_ymd_and_hms(year, 5, 17, 12, 0, 0).unwrap());
+            pages.add_message("Chat", &date);
+        }
+
+        assert_eq!(
+            pages.nav("Chat - 2021", false),
+            concat!(
+                "<nav class=\"pages\">\n",
+                "<a href=\"Chat%20-%202020.html\">Previous</a>\n",
+                "<a href=\"Chat%20-%202020.html\">2020</a> <a href=\"Chat%20-%202021.html\">2021</a> <a href=\"Chat%20-%202022.html\">2022</a>\n",
+                "<a href=\"Chat%20-%202022.html\">Next</a>\n",
+                "</nav>\n"
+            )
+        );
+        assert_eq!(
+            pages.nav("orphaned", true),
+            "<nav class=\"pages\">\n<a href=\"index.html\">All conversations</a>\n</nav>\n"
+        );
+    }
+}
@@ -0,0 +1,248 @@
+/*!
+ Builds the index page and search index for static site exports.
+
+ In static site mode, each conversation is split into a page per year, or as set by `--paginate`,
+ so that large histories stay fast to load. Pages are written next to each other in the export
+ directory, the same as regular HTML exports, so relative links to copied attachments keep working.
+
+ The search index is a script instead of a JSON file because browsers refuse to `fetch()`
+ local files, and the site should work when opened straight from disk.
+*/
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    app::{
+        error::RuntimeError,
+        sanitizers::{sanitize_html, sanitize_json},
+    },
+    exporters::html::{
+        pages::{page_href, page_links, Pages},
+        HEADER,
+    },
+};
+
+use imessage_database::tables::table::ORPHANED;
+
+/// Name of the page that lists every conversation
+const INDEX_FILENAME: &str = "index.html";
+/// Name of the script that holds the search index
+const SEARCH_INDEX_FILENAME: &str = "search_index.js";
+/// Name of the script that searches the index
+const SEARCH_SCRIPT_FILENAME: &str = "search.js";
+/// Client-side search over the search index
+const SEARCH_SCRIPT: &str = include_str!("../resources/search.js");
+/// Styles for the index page, added to the regular HTML export styles
+const SITE_STYLE: &str = include_str!("../resources/site.css");
+
+pub(crate) struct Site {
+    /// Number of messages that do not belong to a conversation
+    orphaned: usize,
+    /// Buffered writer for the search index, which is written as messages are exported
+    search_index: BufWriter<File>,
+}
+
+impl Site {
+    /// Create the search index in the export directory
+    pub(super) fn new(export_path: &Path) -> Result<Self, RuntimeError> {
+        let path = export_path.join(SEARCH_INDEX_FILENAME);
+        let file = File::create(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+        let mut search_index = BufWriter::new(file);
+        search_index
+            .write_all(b"const SEARCH_INDEX = [\n")
+            .map_err(RuntimeError::DiskError)?;
+
+        Ok(Site {
+            orphaned: 0,
+            search_index,
+        })
+    }
+
+    /// Add a message written to `page` to the search index
+    ///
+    /// `anchor` is the `id` of the element the message was written after.
+    pub(super) fn add_message(
+        &mut self,
+        conversation: Option<&str>,
+        page: &str,
+        anchor: &str,
+        sender: &str,
+        date: &str,
+        text: Option<&str>,
+    ) -> Result<(), RuntimeError> {
+        if conversation.is_none() {
+            self.orphaned += 1;
+        }
+
+        if let Some(text) = text.filter(|text| !text.trim().is_empty()) {
+            let entry = format!(
+                "[\"{}#{anchor}\",\"{}\",\"{}\",\"{}\",\"{}\"],\n",
+                sanitize_json(&page_href(page)),
+                sanitize_json(conversation.unwrap_or(ORPHANED)),
+                sanitize_json(sender),
+                sanitize_json(date),
+                sanitize_json(text),
+            );
+            self.search_index
+                .write_all(entry.as_bytes())
+                .map_err(RuntimeError::DiskError)?;
+        }
+        Ok(())
+    }
+
+    /// Write the index page and search script, then close the search index
+    pub(super) fn finish(
+        mut self,
+        export_path: &Path,
+        style: &str,
+        pages: &Pages,
+        icons: &HashMap<String, String>,
+    ) -> Result<(), RuntimeError> {
+        self.search_index
+            .write_all(b"];\n")
+            .map_err(RuntimeError::DiskError)?;
+        self.search_index.flush().map_err(RuntimeError::DiskError)?;
+
+        let path = export_path.join(SEARCH_SCRIPT_FILENAME);
+        std::fs::write(&path, SEARCH_SCRIPT).map_err(|err| RuntimeError::CreateError(err, path))?;
+
+        let path = export_path.join(INDEX_FILENAME);
+        std::fs::write(&path, self.index(style, pages, icons))
+            .map_err(|err| RuntimeError::CreateError(err, path))
+    }
+
+    /// Build the index page listing every conversation
+    fn index(&self, style: &str, pages: &Pages, icons: &HashMap<String, String>) -> String {
+        let mut page = String::from(HEADER);
+        page.push_str("\n<title>Conversations</title>\n<style>\n");
+        page.push_str(style);
+        page.push_str(SITE_STYLE);
+        page.push_str(
+            "\n</style>\n</head>\n<body>\n<div class=\"site\">\n<h1>Conversations</h1>\n",
+        );
+        page.push_str(
+            "<input id=\"search\" type=\"search\" placeholder=\"Search messages\" autofocus>\n",
+        );
+        page.push_str("<ol id=\"results\"></ol>\n");
+
+        page.push_str("<table class=\"conversations\">\n<tr><th>Conversation</th><th>Messages</th><th>Pages</th></tr>\n");
+        for (conversation, conversation_pages) in pages.conversations() {
+            let icon = icons
+                .get(conversation)
+                .map(|icon| {
+                    format!(
+                        "<img class=\"chat_icon\" src=\"{}\" alt=\"\"> ",
+                        sanitize_html(icon)
+                    )
+                })
+                .unwrap_or_default();
+            page.push_str(&format!(
+                "<tr><td>{icon}{}</td><td>{}</td><td>{}</td></tr>\n",
+                sanitize_html(conversation),
+                conversation_pages
+                    .iter()
+                    .map(|(_, count)| count)
+                    .sum::<usize>(),
+                page_links(conversation, conversation_pages),
+            ));
+        }
+        page.push_str("</table>\n");
+
+        if self.orphaned > 0 {
+            page.push_str(&format!(
+                "<p><a href=\"{}\">Messages without a conversation</a> ({})</p>\n",
+                page_href(ORPHANED),
+                self.orphaned,
+            ));
+        }
+
+        page.push_str(&format!(
+            "</div>\n<script src=\"{SEARCH_INDEX_FILENAME}\"></script>\n<script src=\"{SEARCH_SCRIPT_FILENAME}\"></script>\n</body></html>"
+        ));
+        page
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs::{create_dir_all, read_to_string},
+        path::PathBuf,
+    };
+
+    use chrono::{Local, TimeZone};
+
+    use super::Site;
+    use crate::{app::pagination::Pagination, exporters::html::pages::Pages};
+
+    #[test]
+    fn can_write_search_index() {
+        let path = PathBuf::from("/tmp/site_can_write_search_index");
+        create_dir_all(&path).unwrap();
+        let mut site = Site::new(&path).unwrap();
+        let mut pages = Pages::new(Pagination::Year);
+        let date = Ok(Local.with_ymd_and_hms(2022, 5, 17, 12, 0, 0).unwrap());
+
+        let page = pages.add_message("Chat", &date);
+        site.add_message(
+            Some("Chat"),
+            &page,
+            "m-1",
+            "Me",
+            "May 17, 2022",
+            Some("Say \"hi\""),
+        )
+        .unwrap();
+        site.add_message(None, "orphaned", "m-2", "Me", "May 17, 2022", Some("Lost"))
+            .unwrap();
+        let page = pages.add_message("Chat", &date);
+        site.add_message(Some("Chat"), &page, "m-3", "Me", "May 17, 2022", None)
+            .unwrap();
+        site.finish(&path, "", &pages, &HashMap::new()).unwrap();
+
+        let index = read_to_string(path.join("search_index.js")).unwrap();
+        assert_eq!(
+            index,
+            concat!(
+                "const SEARCH_INDEX = [\n",
+                "[\"Chat%20-%202022.html#m-1\",\"Chat\",\"Me\",\"May 17, 2022\",\"Say \\\"hi\\\"\"],\n",
+                "[\"orphaned.html#m-2\",\"orphaned\",\"Me\",\"May 17, 2022\",\"Lost\"],\n",
+                "];\n"
+            )
+        );
+
+        let page = read_to_string(path.join("index.html")).unwrap();
+        assert!(page.contains(
+            "<tr><td>Chat</td><td>2</td><td><a href=\"Chat%20-%202022.html\">2022</a></td></tr>"
+        ));
+        assert!(page.contains("<a href=\"orphaned.html\">Messages without a conversation</a> (1)"));
+    }
+
+    #[test]
+    fn can_write_index_icons() {
+        let path = PathBuf::from("/tmp/site_can_write_index_icons");
+        create_dir_all(&path).unwrap();
+        let site = Site::new(&path).unwrap();
+        let mut pages = Pages::new(Pagination::Year);
+        let date = Ok(Local.with_ymd_and_hms(2022, 5, 17, 12, 0, 0).unwrap());
+        pages.add_message("Family", &date);
+
+        let mut icons = HashMap::new();
+        icons.insert(
+            "Family".to_string(),
+            "attachments/3/chat_icon.jpeg".to_string(),
+        );
+        site.finish(&path, "", &pages, &icons).unwrap();
+
+        let page = read_to_string(path.join("index.html")).unwrap();
+        assert!(page.contains(
+            "<tr><td><img class=\"chat_icon\" src=\"attachments/3/chat_icon.jpeg\" alt=\"\"> Family</td>"
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+/*!
+ Renders the page layout and message wrappers for HTML exports.
+
+ Users can customize exports by passing `--template-dir` with any of these files, which replace
+ the defaults in `resources/templates`:
+
+ - `conversation.html`: the page written for each conversation, as a [Tera](https://keats.github.io/tera/docs/) template
+ - `message.html`: wraps the markup of each message, as a Tera template
+ - `style.css`: the stylesheet, available to `conversation.html` as `style`
+
+ The colors of the selected theme are added to the end of the stylesheet.
+*/
+
+use std::{fs::read_to_string, path::Path};
+
+use tera::{Context, Tera};
+
+use crate::{
+    app::{error::RuntimeError, theme::Theme},
+    exporters::html::STYLE,
+};
+
+use imessage_database::tables::messages::Message;
+
+/// Name of the template for conversation pages
+const CONVERSATION: &str = "conversation.html";
+/// Name of the template that wraps each message
+const MESSAGE: &str = "message.html";
+/// Name of the stylesheet
+const STYLESHEET: &str = "style.css";
+/// Default template for conversation pages
+const DEFAULT_CONVERSATION: &str = include_str!("../resources/templates/conversation.html");
+/// Default template that wraps each message
+const DEFAULT_MESSAGE: &str = include_str!("../resources/templates/message.html");
+/// Colors for the dark theme, which override the light colors in the default stylesheet
+const DARK_THEME: &str = include_str!("../resources/themes/dark.css");
+/// Stands in for the messages when rendering a page, so the page can be split into the parts
+/// written before and after them
+const MESSAGES_MARKER: &str = "\u{E000}messages\u{E000}";
+
+pub(crate) struct Templates {
+    /// Compiled templates
+    tera: Tera,
+    /// Contents of the stylesheet
+    style: String,
+    /// If true, messages are rendered through `message.html`; the default template
+    /// writes them unchanged, so rendering it is skipped
+    wrap_messages: bool,
+}
+
+impl Templates {
+    /// Load the templates in `dir`, falling back to the defaults for any that are missing, and style them with `theme`
+    pub(crate) fn new(dir: Option<&Path>, theme: &Theme) -> Result<Self, RuntimeError> {
+        let load = |name: &str| -> Result<Option<String>, RuntimeError> {
+            match dir.map(|dir| dir.join(name)) {
+                Some(path) if path.exists() => read_to_string(&path)
+                    .map(Some)
+                    .map_err(|err| RuntimeError::CreateError(err, path)),
+                _ => Ok(None),
+            }
+        };
+
+        let conversation = load(CONVERSATION)?;
+        let message = load(MESSAGE)?;
+        let style = load(STYLESHEET)?;
+        let wrap_messages = message.is_some();
+
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            (
+                CONVERSATION,
+                conversation.as_deref().unwrap_or(DEFAULT_CONVERSATION),
+            ),
+            (MESSAGE, message.as_deref().unwrap_or(DEFAULT_MESSAGE)),
+        ])
+        .map_err(RuntimeError::TemplateError)?;
+
+        let mut style = style.unwrap_or_else(|| STYLE.to_string());
+        style.push_str(&theme_style(theme)?);
+
+        Ok(Templates {
+            tera,
+            style,
+            wrap_messages,
+        })
+    }
+
+    /// Get the stylesheet
+    pub(crate) fn style(&self) -> &str {
+        &self.style
+    }
+
+    /// Render the page for a conversation, returning the parts written before and after its messages
+    pub(super) fn page(&self, title: &str) -> Result<(String, String), RuntimeError> {
+        let mut context = Context::new();
+        context.insert("title", title);
+        context.insert("style", &self.style);
+        context.insert("messages", MESSAGES_MARKER);
+
+        let page = self
+            .tera
+            .render(CONVERSATION, &context)
+            .map_err(RuntimeError::TemplateError)?;
+        match page.split_once(MESSAGES_MARKER) {
+            Some((header, footer)) => Ok((header.to_string(), footer.to_string())),
+            None => Err(RuntimeError::InvalidOptions(format!(
+                "Template {CONVERSATION} must include `{{{{ messages | safe }}}}`"
+            ))),
+        }
+    }
+
+    /// Wrap the markup generated for a message
+    pub(super) fn message(
+        &self,
+        content: String,
+        message: &Message,
+        sender: &str,
+        date: &str,
+    ) -> Result<String, RuntimeError> {
+        if !self.wrap_messages {
+            return Ok(content);
+        }
+
+        let kind = if message.is_announcement() {
+            "announcement"
+        } else {
+            "message"
+        };
+
+        let mut context = Context::new();
+        context.insert("content", &content);
+        context.insert("kind", kind);
+        context.insert("guid", &message.guid);
+        context.insert("sender", sender);
+        context.insert("date", date);
+        context.insert("is_from_me", &message.is_from_me());
+        context.insert("service", &message.service);
+
+        self.tera
+            .render(MESSAGE, &context)
+            .map_err(RuntimeError::TemplateError)
+    }
+}
+
+/// Build the CSS that applies a theme's colors
+fn theme_style(theme: &Theme) -> Result<String, RuntimeError> {
+    // Nest the dark colors in a media query so they only apply when the reader prefers them
+    let auto = || {
+        let rules: Vec<String> = DARK_THEME
+            .lines()
+            .map(|line| match line.is_empty() {
+                true => String::new(),
+                false => format!("\t{line}"),
+            })
+            .collect();
+        format!(
+            "\n\n@media (prefers-color-scheme: dark) {{\n{}\n}}",
+            rules.join("\n").trim_end()
+        )
+    };
+
+    match theme {
+        Theme::Auto => Ok(auto()),
+        Theme::Light => Ok(String::new()),
+        Theme::Dark => Ok(format!("\n\n{}", DARK_THEME.trim_end())),
+        Theme::Custom(path) => {
+            let palette = read_to_string(path)
+                .map_err(|err| RuntimeError::CreateError(err, path.to_path_buf()))?;
+            Ok(format!("{}\n\n{}", auto(), palette.trim_end()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, write},
+        path::PathBuf,
+    };
+
+    use super::{Templates, DARK_THEME};
+    use crate::{
+        app::theme::Theme,
+        exporters::html::{HEADER, STYLE},
+    };
+
+    use imessage_database::tables::messages::Message;
+
+    fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    #[test]
+    fn can_render_default_page() {
+        let templates = Templates::new(None, &Theme::Light).unwrap();
+        let (header, footer) = templates.page("Chat").unwrap();
+
+        assert_eq!(
+            header,
+            format!("{HEADER}<style>\n{STYLE}\n</style>\n</head>\n<body>\n")
+        );
+        assert_eq!(footer, "</body></html>");
+    }
+
+    #[test]
+    fn can_skip_default_message() {
+        let templates = Templates::new(None, &Theme::default()).unwrap();
+        let actual = templates
+            .message("<p>hi</p>".to_string(), &blank(), "Me", "date")
+            .unwrap();
+
+        assert_eq!(actual, "<p>hi</p>");
+    }
+
+    #[test]
+    fn can_render_custom_templates() {
+        let dir = PathBuf::from("/tmp/can_render_custom_templates");
+        create_dir_all(&dir).unwrap();
+        write(
+            dir.join("conversation.html"),
+            "<title>{{ title }}</title><style>{{ style }}</style><main>{{ messages | safe }}</main>",
+        )
+        .unwrap();
+        write(
+            dir.join("message.html"),
+            "<article data-from=\"{{ sender }}\">{{ content | safe }}</article>",
+        )
+        .unwrap();
+        write(dir.join("style.css"), "main {}").unwrap();
+
+        let templates = Templates::new(Some(&dir), &Theme::Light).unwrap();
+        let (header, footer) = templates.page("A & B").unwrap();
+        assert_eq!(
+            header,
+            "<title>A &amp; B</title><style>main {}</style><main>"
+        );
+        assert_eq!(footer, "</main>");
+
+        let actual = templates
+            .message("<p>hi</p>".to_string(), &blank(), "<Me>", "date")
+            .unwrap();
+        assert_eq!(
+            actual,
+            "<article data-from=\"&lt;Me&gt;\"><p>hi</p></article>"
+        );
+    }
+
+    #[test]
+    fn can_apply_auto_theme() {
+        let templates = Templates::new(None, &Theme::Auto).unwrap();
+
+        assert!(templates.style().starts_with(STYLE));
+        assert!(templates
+            .style()
+            .contains("@media (prefers-color-scheme: dark) {\n\tbody {\n\t\tbackground: black;"));
+    }
+
+    #[test]
+    fn can_apply_dark_theme() {
+        let templates = Templates::new(None, &Theme::Dark).unwrap();
+
+        assert_eq!(
+            templates.style(),
+            format!("{STYLE}\n\n{}", DARK_THEME.trim_end())
+        );
+    }
+
+    #[test]
+    fn can_apply_custom_theme() {
+        let dir = PathBuf::from("/tmp/can_apply_custom_theme");
+        create_dir_all(&dir).unwrap();
+        write(dir.join("palette.css"), "body { background: navy; }\n").unwrap();
+
+        let templates = Templates::new(None, &Theme::Custom(dir.join("palette.css"))).unwrap();
+
+        assert!(templates
+            .style()
+            .contains("@media (prefers-color-scheme: dark)"));
+        assert!(templates
+            .style()
+            .ends_with("}\n\nbody { background: navy; }"));
+    }
+
+    #[test]
+    fn cant_render_page_without_messages() {
+        let dir = PathBuf::from("/tmp/cant_render_page_without_messages");
+        create_dir_all(&dir).unwrap();
+        write(dir.join("conversation.html"), "<main></main>").unwrap();
+
+        let templates = Templates::new(Some(&dir), &Theme::Light).unwrap();
+        assert!(templates.page("Chat").is_err());
+    }
+}
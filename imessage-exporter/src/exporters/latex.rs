@@ -0,0 +1,556 @@
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use log::info;
+
+use chrono::Datelike;
+
+use crate::{
+    app::{
+        error::RuntimeError, progress::build_progress_bar_export, runtime::Config,
+        sanitizers::sanitize_latex,
+    },
+    exporters::{exporter::Exporter, html::append_extension},
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::Announcement,
+    tables::{
+        attachment::Attachment,
+        messages::Message,
+        table::{Table, ME, ORPHANED, YOU},
+    },
+    util::dates::format,
+};
+
+/// Title of the book for messages that do not belong to a conversation
+const ORPHANED_TITLE: &str = "Orphaned Messages";
+/// Closes the document started by [`preamble()`]
+const END_DOCUMENT: &str = "\\end{document}\n";
+
+/// Exports each conversation as a LaTeX book
+///
+/// Every conversation is written to its own `.tex` document with a chapter for each year, so
+/// it can be typeset and printed. Attachments are listed in footnotes.
+pub struct LaTeX<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
+    /// Map of resolved chatroom file location to the year of its current chapter
+    chapters: HashMap<String, i32>,
+}
+
+impl<'a> Exporter<'a> for LaTeX<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("tex");
+
+        let file =
+            File::create(&orphaned).map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+        let mut orphaned = BufWriter::new(file);
+        LaTeX::write_to_file(&mut orphaned, &preamble(ORPHANED_TITLE))?;
+
+        Ok(LaTeX {
+            config,
+            files: HashMap::new(),
+            orphaned,
+            chapters: HashMap::new(),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as tex...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Tapbacks are listed under the message they belong to
+            if !msg.is_tapback() {
+                let paragraph = match msg.is_announcement() {
+                    true => self.format_announcement(&msg),
+                    false => self.format_message(&msg)?,
+                };
+                let chapter = self.start_chapter(&msg);
+                let file = self.get_or_create_file(&msg)?;
+                if let Some(chapter) = chapter {
+                    LaTeX::write_to_file(file, &chapter)?;
+                }
+                LaTeX::write_to_file(file, &paragraph)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        // Close every document so it can be typeset
+        for buf in self.files.values_mut() {
+            LaTeX::write_to_file(buf, END_DOCUMENT)?;
+            buf.flush().map_err(RuntimeError::DiskError)?;
+        }
+        LaTeX::write_to_file(&mut self.orphaned, END_DOCUMENT)?;
+        self.orphaned.flush().map_err(RuntimeError::DiskError)
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                return match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.options.export_path.clone();
+                        path.push(entry.key());
+                        append_extension(&mut path, "tex");
+
+                        let file = File::create(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        let mut buf = BufWriter::new(file);
+                        LaTeX::write_to_file(&mut buf, &preamble(chatroom.name()))?;
+
+                        Ok(entry.insert(buf))
+                    }
+                };
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> LaTeX<'a> {
+    /// Start a new chapter if the message is the first of its year in its conversation
+    fn start_chapter(&mut self, message: &Message) -> Option<String> {
+        let year = message.date(&self.config.offset).ok()?.year();
+        let filename = match self.config.conversation(message) {
+            Some((chatroom, _)) => self.config.filename(chatroom),
+            None => String::from(ORPHANED),
+        };
+
+        // Messages are sorted by date, so each year only starts once
+        match self.chapters.insert(filename, year) {
+            Some(previous) if previous == year => None,
+            _ => Some(format!("\\chapter{{{year}}}\n\n")),
+        }
+    }
+
+    /// Format a message as a paragraph, with attachments in footnotes
+    fn format_message(&self, message: &Message) -> Result<String, RuntimeError> {
+        let who = sanitize_latex(self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        ));
+        let date = format(&message.date(&self.config.offset));
+
+        let mut body = String::new();
+
+        if let Some(text) = self.config.message_text(message) {
+            // Attachments are listed in footnotes
+            let text = text.replace('\u{FFFC}', "");
+            let text = text.trim();
+            if !text.is_empty() {
+                // Blank lines would start a new paragraph, so each line is broken explicitly
+                let lines: Vec<String> = text
+                    .lines()
+                    .map(|line| sanitize_latex(line).to_string())
+                    .collect();
+                body.push_str(&lines.join("\\\\\n"));
+            }
+        }
+
+        if message.has_attachments() {
+            let mut attachments = Attachment::from_message(&self.config.db, message)
+                .map_err(RuntimeError::DatabaseError)?;
+            for attachment in attachments.iter_mut() {
+                body.push_str(&self.format_attachment(attachment, message));
+            }
+            if let Some(transcription) = message.audio_transcription() {
+                body.push_str(&format!(
+                    "\\\\\n\\textit{{Transcription: {}}}",
+                    sanitize_latex(transcription)
+                ));
+            }
+        }
+
+        let tapbacks = self.config.tapback_lines(message);
+        if !tapbacks.is_empty() {
+            if !body.is_empty() {
+                body.push_str("\\\\\n");
+            }
+            body.push_str(&format!(
+                "{{\\small\\textit{{{}}}}}",
+                sanitize_latex(&tapbacks.join(", "))
+            ));
+        }
+
+        // A line can only be broken if there is something after it
+        let mut paragraph = format!(
+            "\\noindent\\textbf{{{who}}} {{\\small\\textit{{{}}}}}",
+            date.trim()
+        );
+        if !body.is_empty() {
+            paragraph.push_str("\\\\\n");
+            paragraph.push_str(&body);
+        }
+        paragraph.push_str("\n\n");
+        Ok(paragraph)
+    }
+
+    /// Format a footnote describing an attachment, copying it if requested
+    fn format_attachment(&self, attachment: &mut Attachment, message: &Message) -> String {
+        let name = sanitize_latex(attachment.filename()).to_string();
+        match self.config.options.attachment_manager.handle_attachment(
+            message,
+            attachment,
+            self.config,
+        ) {
+            Some(()) => {
+                let path =
+                    sanitize_latex(&self.config.message_attachment_path(attachment)).to_string();
+                format!("\\footnote{{Attachment: {name}, \\texttt{{{path}}}}}")
+            }
+            None => format!("\\footnote{{Attachment: {name}}}"),
+        }
+    }
+
+    /// Format an announcement as an italicized paragraph
+    fn format_announcement(&self, message: &Message) -> String {
+        let mut who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+        let who = sanitize_latex(who);
+        let date = format(&message.date(&self.config.offset));
+
+        let sentence = match message.get_announcement() {
+            Some(Announcement::NameChange(name)) => {
+                format!("{who} renamed the conversation to {}", sanitize_latex(name))
+            }
+            Some(Announcement::PhotoChange) => format!("{who} changed the group photo."),
+            Some(Announcement::PhotoRemoved) => format!("{who} removed the group photo."),
+            Some(Announcement::ParticipantAdded(handle)) => format!(
+                "{who} added {} to the conversation.",
+                sanitize_latex(self.config.who(
+                    Some(handle),
+                    false,
+                    &message.destination_caller_id
+                ))
+            ),
+            Some(Announcement::ParticipantRemoved(handle)) => format!(
+                "{who} removed {} from the conversation.",
+                sanitize_latex(self.config.who(
+                    Some(handle),
+                    false,
+                    &message.destination_caller_id
+                ))
+            ),
+            Some(Announcement::ParticipantLeft) => format!("{who} left the conversation."),
+            Some(Announcement::AudioMessageKept) => format!("{who} kept an audio message."),
+            Some(Announcement::UnknownEvent(num)) => {
+                format!("{who} performed unknown system event {num}.")
+            }
+            Some(Announcement::Unknown(num)) => format!("{who} performed unknown action {num}."),
+            Some(Announcement::FullyUnsent) => format!("{who} unsent a message!"),
+            None => String::from("Unable to format announcement!"),
+        };
+        format!(
+            "\\begin{{center}}\\small\\textit{{{} {sentence}}}\\end{{center}}\n\n",
+            date.trim()
+        )
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+/// Start a book titled after its conversation, ready for `lualatex` or `xelatex`
+fn preamble(title: &str) -> String {
+    format!(
+        "\\documentclass[11pt]{{book}}\n\
+        \\usepackage{{fontspec}}\n\
+        \\title{{{}}}\n\
+        \\date{{}}\n\
+        \\begin{{document}}\n\
+        \\maketitle\n\
+        \\tableofcontents\n\n",
+        sanitize_latex(title)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env::set_var, fs::create_dir_all, path::PathBuf};
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        exporters::latex::preamble,
+        Config, Exporter, LaTeX, Options,
+    };
+    use imessage_database::{
+        tables::{messages::Message, table::get_connection},
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options() -> Options {
+        let export_path = PathBuf::from("/tmp/latex");
+        create_dir_all(&export_path).unwrap();
+
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path,
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = LaTeX::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_format_message() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = LaTeX::new(&config).unwrap();
+
+        let mut message = blank();
+        message.text = Some("100% done\nSee you_soon".to_string());
+        message.is_from_me = true;
+        message.date = 674526582885055488;
+
+        assert_eq!(
+            exporter.format_message(&message).unwrap(),
+            "\\noindent\\textbf{Me} {\\small\\textit{May 17, 2022  5:29:42 PM}}\\\\\n100\\% done\\\\\nSee you\\_soon\n\n"
+        );
+    }
+
+    #[test]
+    fn can_format_message_with_tapbacks() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config.participants.insert(10, "Person 10".to_string());
+
+        let mut tapback = blank();
+        tapback.handle_id = Some(10);
+        tapback.associated_message_type = Some(2001);
+        tapback.associated_message_guid = Some("p:0/target".to_string());
+        config
+            .tapbacks
+            .insert("target".to_string(), HashMap::from([(0, vec![tapback])]));
+
+        let exporter = LaTeX::new(&config).unwrap();
+
+        let mut message = blank();
+        message.guid = "target".to_string();
+        message.text = Some("Hello".to_string());
+        message.is_from_me = true;
+        message.date = 674526582885055488;
+
+        assert_eq!(
+            exporter.format_message(&message).unwrap(),
+            "\\noindent\\textbf{Me} {\\small\\textit{May 17, 2022  5:29:42 PM}}\\\\\nHello\\\\\n{\\small\\textit{Liked by Person 10}}\n\n"
+        );
+    }
+
+    #[test]
+    fn can_start_chapter_each_year() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let mut exporter = LaTeX::new(&config).unwrap();
+
+        let mut message = blank();
+        message.date = 674526582885055488;
+        assert!(exporter.start_chapter(&message).is_some());
+        assert!(exporter.start_chapter(&message).is_none());
+
+        // A message a year later starts a new chapter
+        message.date += 365 * 24 * 60 * 60 * 1_000_000_000;
+        assert!(exporter.start_chapter(&message).is_some());
+    }
+
+    #[test]
+    fn can_build_preamble() {
+        let preamble = preamble("Book Club #2");
+        assert!(preamble.starts_with("\\documentclass[11pt]{book}\n"));
+        assert!(preamble.contains("\\title{Book Club \\#2}\n"));
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::{create_dir_all, write},
+    path::PathBuf,
+};
+
+use log::info;
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::mbox::format_email,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        messages::Message,
+        table::{Table, ORPHANED},
+    },
+    util::dates::get_local_time,
+};
+
+/// Folders every maildir must contain
+const MAILDIR_FOLDERS: [&str; 3] = ["cur", "new", "tmp"];
+/// Host part of generated filenames, which maildir uses to keep names unique across machines
+const HOSTNAME: &str = "imessage-exporter";
+/// Info suffix of each message's filename, marking it as seen
+const SEEN_INFO: &str = ":2,S";
+
+/// Exports each conversation to a maildir, one message per file
+///
+/// Messages are rendered the same way as mbox exports, but each one is written to its own file
+/// in the conversation's `cur` folder, so the export can be served by an IMAP server like Dovecot.
+pub struct Maildir<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Map of resolved chatroom file location to the `cur` folder of its maildir
+    pub folders: HashMap<String, PathBuf>,
+}
+
+impl<'a> Maildir<'a> {
+    /// Create a new exporter with references to the cached data
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(Maildir {
+            config,
+            folders: HashMap::new(),
+        })
+    }
+
+    /// Write every message to the maildir for its conversation
+    pub fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as maildir...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Tapbacks are listed in the email for the message they belong to
+            if !msg.is_tapback() {
+                let email = format_email(self.config, &msg)?;
+                let mut path = self.get_or_create_folder(&msg)?.clone();
+                path.push(Maildir::filename(self.config, &msg));
+                write(&path, email).map_err(|err| RuntimeError::CreateError(err, path))?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+        Ok(())
+    }
+
+    /// Create the maildir for the given chat, caching its `cur` folder so we don't need to build it later
+    fn get_or_create_folder(&mut self, message: &Message) -> Result<&PathBuf, RuntimeError> {
+        let name = match self.config.conversation(message) {
+            Some((chatroom, _)) => self.config.filename(chatroom),
+            None => ORPHANED.to_string(),
+        };
+
+        match self.folders.entry(name) {
+            Occupied(entry) => Ok(entry.into_mut()),
+            Vacant(entry) => {
+                let mut maildir = self.config.options.export_path.clone();
+                maildir.push(entry.key());
+                for folder in MAILDIR_FOLDERS {
+                    let path = maildir.join(folder);
+                    create_dir_all(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+                }
+                maildir.push("cur");
+                Ok(entry.insert(maildir))
+            }
+        }
+    }
+
+    /// Build a unique maildir filename for a message
+    ///
+    /// Names are derived from the message so that exporting again replaces files instead of duplicating them.
+    fn filename(config: &Config, message: &Message) -> String {
+        let timestamp = get_local_time(&message.date, &config.offset)
+            .map(|date| date.timestamp())
+            .unwrap_or_default();
+        format!("{timestamp}.{}.{HOSTNAME}{SEEN_INFO}", message.guid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env::set_var, path::PathBuf};
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        exporters::maildir::Maildir,
+        Config, Options,
+    };
+    use imessage_database::{
+        tables::{messages::Message, table::get_connection},
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options() -> Options {
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path: PathBuf::from("/tmp"),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Maildir::new(&config).unwrap();
+        assert_eq!(exporter.folders.len(), 0);
+    }
+
+    #[test]
+    fn can_build_filename() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options();
+        let config = fake_config(options);
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.guid = "guid".to_string();
+
+        assert_eq!(
+            Maildir::filename(&config, &message),
+            "1652833782.guid.imessage-exporter:2,S"
+        );
+    }
+}
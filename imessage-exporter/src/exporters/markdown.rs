@@ -0,0 +1,1809 @@
+use std::{
+    borrow::Cow,
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use log::info;
+
+use crate::{
+    app::{
+        attachment_manager::AttachmentManager, error::RuntimeError,
+        progress::build_progress_bar_export, roster::Roster, runtime::Config,
+        sanitizers::sanitize_markdown,
+    },
+    exporters::{
+        exporter::{BalloonFormatter, Exporter, TextEffectFormatter, Writer},
+        html::append_extension,
+    },
+};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        app_store::AppStoreMessage,
+        collaboration::CollaborationMessage,
+        edited::{EditStatus, EditedMessage},
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        facetime::FaceTimeMessage,
+        handwriting::HandwrittenMessage,
+        icloud_photos::{ICloudPhotosKind, ICloudPhotosMessage},
+        music::MusicMessage,
+        placemark::PlacemarkMessage,
+        text_effects::{Animation, DataDetector, Style, TextEffect, Unit},
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, CustomBalloon, URLOverride, Variant},
+    },
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::{models::BubbleComponent, Message},
+        table::{Table, FITNESS_RECEIVER, ME, ORPHANED, YOU},
+    },
+    util::{
+        dates::{format, get_local_time, readable_diff, TIMESTAMP_FACTOR},
+        plist::parse_plist,
+    },
+};
+
+/// Markdown joins adjacent lines into a single paragraph, so lines that must stay separate end with a hard break
+const HARD_BREAK: &str = "  \n";
+/// Prefix used to nest content in a blockquote
+const QUOTE: &str = "> ";
+
+pub struct Markdown<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
+}
+
+impl<'a> Exporter<'a> for Markdown<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("md");
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        Ok(Markdown {
+            config,
+            files: HashMap::new(),
+            orphaned: BufWriter::new(file),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as md...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self.format_announcement(&msg);
+                Markdown::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+            }
+            // Message replies and tapbacks are rendered in context, so no need to render them separately
+            else if !msg.is_tapback() {
+                let message = self
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                Markdown::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                return match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.options.export_path.clone();
+                        path.push(entry.key());
+                        append_extension(&mut path, "md");
+
+                        // If the file already exists, don't write the title again
+                        // This can happen if multiple chats use the same group name
+                        let file_exists = path.exists();
+
+                        let file = File::options()
+                            .append(true)
+                            .create(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        let mut buf = BufWriter::new(file);
+
+                        // Title the document with the name of the conversation
+                        if !file_exists {
+                            let title = format!("# {}\n\n", sanitize_markdown(chatroom.name()));
+                            Markdown::write_to_file(&mut buf, &title)?;
+
+                            if let Some(roster) = Roster::new(self.config, chatroom) {
+                                Markdown::write_to_file(
+                                    &mut buf,
+                                    &Markdown::format_roster(&roster),
+                                )?;
+                            }
+                        }
+
+                        Ok(entry.insert(buf))
+                    }
+                };
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> Writer<'a> for Markdown<'a> {
+    /// Format a message as a series of Markdown blocks
+    ///
+    /// Received messages are rendered as blockquotes. `indent_size` is the number of blockquote
+    /// levels the message is nested in, so replies are always one level deeper than their parent.
+    fn format_message(&self, message: &Message, indent_size: usize) -> Result<String, TableError> {
+        let depth = indent_size + usize::from(!message.is_from_me());
+        let indent = QUOTE.repeat(depth);
+        // Data we want to write to a file
+        let mut formatted_message = String::new();
+
+        // Add message sender and date
+        let who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        self.add_line(
+            &mut formatted_message,
+            &format!(
+                "**{}** *{}*",
+                sanitize_markdown(who),
+                self.get_time(message)
+            ),
+            &indent,
+        );
+
+        // If message was deleted, annotate it
+        if message.is_deleted() {
+            self.add_line(
+                &mut formatted_message,
+                "*This message was deleted from the conversation!*",
+                &indent,
+            );
+        }
+
+        // Useful message metadata
+        let message_parts = message.body();
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let mut replies = message.get_replies(&self.config.db)?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        // Render subject
+        if let Some(subject) = &message.subject {
+            self.add_line(
+                &mut formatted_message,
+                &format!("**{}**", sanitize_markdown(subject)),
+                &indent,
+            );
+        }
+
+        // Handle SharePlay
+        if message.is_shareplay() {
+            self.add_line(&mut formatted_message, self.format_shareplay(), &indent);
+        }
+
+        // Handle Shared Location
+        if message.started_sharing_location() || message.stopped_sharing_location() {
+            self.add_line(
+                &mut formatted_message,
+                self.format_shared_location(message),
+                &indent,
+            );
+        }
+
+        // Generate the message body from it's components
+        for (idx, message_part) in message_parts.iter().enumerate() {
+            match message_part {
+                // Fitness messages have a prefix that we need to replace with the opposite if who sent the message
+                BubbleComponent::Text(text_attrs) => {
+                    if let Some(text) = &message.text {
+                        // Render edited message content, if applicable
+                        if message.is_part_edited(idx) {
+                            if let Some(edited_parts) = &message.edited_parts {
+                                if let Some(edited) =
+                                    self.format_edited(message, edited_parts, idx, &indent)
+                                {
+                                    self.add_line(&mut formatted_message, &edited, &indent);
+                                };
+                            }
+                        } else {
+                            let mut formatted_text = String::with_capacity(text.len());
+
+                            for text_attr in text_attrs {
+                                if let Some(message_content) =
+                                    text.get(text_attr.start..text_attr.end)
+                                {
+                                    // We cannot sanitize the text beforehand because it may change the length of the text
+                                    formatted_text.push_str(&self.format_attributed(
+                                        &sanitize_markdown(message_content),
+                                        &text_attr.effect,
+                                    ))
+                                }
+                            }
+
+                            // If we failed to parse any text above, make sure we sanitize if before using it
+                            if formatted_text.is_empty() {
+                                formatted_text.push_str(&sanitize_markdown(text));
+                            }
+
+                            // Preserve the line breaks the sender typed
+                            let formatted_text = formatted_text.replace('\n', HARD_BREAK);
+
+                            if formatted_text.starts_with(FITNESS_RECEIVER) {
+                                self.add_line(
+                                    &mut formatted_message,
+                                    &formatted_text.replace(FITNESS_RECEIVER, YOU),
+                                    &indent,
+                                );
+                            } else {
+                                self.add_line(&mut formatted_message, &formatted_text, &indent);
+                            }
+                        }
+                    }
+                }
+                BubbleComponent::Attachment(_) => match attachments.get_mut(attachment_index) {
+                    Some(attachment) => {
+                        if attachment.is_sticker {
+                            let result = self.format_sticker(attachment, message);
+                            self.add_line(&mut formatted_message, &result, &indent);
+                        } else {
+                            match self.format_attachment(attachment, message) {
+                                Ok(result) => {
+                                    attachment_index += 1;
+                                    self.add_line(&mut formatted_message, &result, &indent);
+                                }
+                                Err(result) => {
+                                    self.add_line(
+                                        &mut formatted_message,
+                                        &sanitize_markdown(result),
+                                        &indent,
+                                    );
+                                }
+                            }
+                            if let Some(transcription) = message.audio_transcription() {
+                                self.add_line(
+                                    &mut formatted_message,
+                                    &self.format_transcription(transcription),
+                                    &indent,
+                                );
+                            }
+                        }
+                    }
+                    // Attachment does not exist in attachments table
+                    None => self.add_line(&mut formatted_message, "*Attachment missing!*", &indent),
+                },
+                // We use an empty indent here because `add_line` quotes the entire app message
+                BubbleComponent::App => match self.format_app(message, &mut attachments, "") {
+                    Ok(ok_bubble) => self.add_line(&mut formatted_message, &ok_bubble, &indent),
+                    Err(why) => self.add_line(
+                        &mut formatted_message,
+                        &format!("*Unable to format app message: {why}*"),
+                        &indent,
+                    ),
+                },
+                BubbleComponent::Retracted => {
+                    if let Some(edited_parts) = &message.edited_parts {
+                        if let Some(edited) =
+                            self.format_edited(message, edited_parts, idx, &indent)
+                        {
+                            self.add_line(&mut formatted_message, &edited, &indent);
+                        };
+                    }
+                }
+            };
+
+            // Handle expressives
+            if message.expressive_send_style_id.is_some() {
+                self.add_line(
+                    &mut formatted_message,
+                    &format!("*{}*", self.format_expressive(message)),
+                    &indent,
+                );
+            }
+
+            // Handle Tapbacks
+            if let Some(tapbacks_map) = self.config.tapbacks.get(&message.guid) {
+                if let Some(tapbacks) = tapbacks_map.get(&idx) {
+                    let mut formatted_tapbacks = String::new();
+                    tapbacks
+                        .iter()
+                        .try_for_each(|tapbacks| -> Result<(), TableError> {
+                            let formatted = self.format_tapback(tapbacks)?;
+                            if !formatted.is_empty() {
+                                formatted_tapbacks.push_str("\n- ");
+                                formatted_tapbacks.push_str(&formatted);
+                            }
+                            Ok(())
+                        })?;
+
+                    if !formatted_tapbacks.is_empty() {
+                        self.add_line(
+                            &mut formatted_message,
+                            &format!("Tapbacks:{formatted_tapbacks}"),
+                            &indent,
+                        );
+                    }
+                }
+            }
+
+            // Handle Replies
+            if let Some(replies) = replies.get_mut(&idx) {
+                replies
+                    .iter_mut()
+                    .try_for_each(|reply| -> Result<(), TableError> {
+                        let _ = reply.generate_text(&self.config.db);
+                        if !reply.is_tapback() {
+                            // Close the current block so the reply starts its own blockquote
+                            formatted_message.push_str(indent.trim_end());
+                            formatted_message.push('\n');
+                            formatted_message.push_str(&self.format_message(reply, depth + 1)?);
+                        }
+                        Ok(())
+                    })?;
+            }
+        }
+
+        // Add a note if the message is a reply
+        if message.is_reply() && indent_size == 0 {
+            self.add_line(
+                &mut formatted_message,
+                "*This message responded to an earlier message.*",
+                &indent,
+            );
+        }
+
+        if indent_size == 0 {
+            // Add a blank line for top-level messages so adjacent blockquotes do not merge
+            formatted_message.push('\n');
+        }
+
+        Ok(formatted_message)
+    }
+
+    fn format_attachment(
+        &self,
+        attachment: &'a mut Attachment,
+        message: &Message,
+    ) -> Result<String, &'a str> {
+        if let Some(removed) = self.config.removed_attachment(message, attachment) {
+            return Ok(format!("*{removed}*"));
+        }
+
+        // Copy the file, if requested
+        self.config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .ok_or(attachment.filename())?;
+
+        // Build a relative filepath from the fully qualified one on the `Attachment`
+        let path = self.config.message_attachment_path(attachment);
+        let name = sanitize_markdown(attachment.filename());
+
+        // Images can be embedded, everything else gets a link
+        Ok(match attachment.mime_type() {
+            MediaType::Image(_) => format!("![{name}]({})", link_target(&path)),
+            _ => format!("[{name}]({})", link_target(&path)),
+        })
+    }
+
+    fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
+        let who = sanitize_markdown(self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        ));
+        match self.format_attachment(sticker, message) {
+            Ok(sticker_embed) => {
+                let sticker_effect = sticker.get_sticker_effect(
+                    &self.config.options.platform,
+                    &self.config.options.db_path,
+                    self.config.options.attachment_root.as_deref(),
+                );
+                if let Ok(Some(sticker_effect)) = sticker_effect {
+                    return format!("{sticker_effect} Sticker from {who}: {sticker_embed}");
+                }
+                format!("Sticker from {who}: {sticker_embed}")
+            }
+            Err(path) => format!("Sticker from {who}: {}", sanitize_markdown(path)),
+        }
+    }
+
+    fn format_app(
+        &self,
+        message: &'a Message,
+        attachments: &mut Vec<Attachment>,
+        indent: &str,
+    ) -> Result<String, PlistParseError> {
+        if let Variant::App(balloon) = message.variant() {
+            let mut app_bubble = String::new();
+
+            // Handwritten messages use a different payload type, so check that first
+            if message.is_handwriting() {
+                if let Some(payload) = message.raw_payload_data(&self.config.db) {
+                    return match HandwrittenMessage::from_payload(&payload) {
+                        Ok(bubble) => Ok(self.format_handwriting(message, &bubble, indent)),
+                        Err(why) => Err(PlistParseError::HandwritingError(why)),
+                    };
+                }
+            }
+
+            if message.is_digital_touch() {
+                let payload = message
+                    .raw_payload_data(&self.config.db)
+                    .unwrap_or_default();
+                return Ok(self.format_digital_touch(message, &payload, indent));
+            }
+
+            if let Some(payload) = message.payload_data(&self.config.db) {
+                // Handle URL messages separately since they are a special case
+                let res = if message.is_url() {
+                    let parsed = match parse_plist(&payload) {
+                        Ok(parsed) => parsed,
+                        Err(why) => return self.format_url_text(message).ok_or(why),
+                    };
+                    let bubble = match URLMessage::get_url_message_override(&parsed) {
+                        Ok(bubble) => bubble,
+                        Err(why) => return self.format_url_text(message).ok_or(why),
+                    };
+                    match bubble {
+                        URLOverride::Normal(balloon) => self.format_url(message, &balloon, indent),
+                        URLOverride::AppleMusic(balloon) => self.format_music(&balloon, indent),
+                        URLOverride::Collaboration(balloon) => {
+                            self.format_collaboration(&balloon, indent)
+                        }
+                        URLOverride::AppStore(balloon) => self.format_app_store(&balloon, indent),
+                        URLOverride::SharedPlacemark(balloon) => {
+                            self.format_placemark(&balloon, indent)
+                        }
+                        URLOverride::FaceTime(balloon) => self.format_facetime(&balloon, indent),
+                        URLOverride::ICloudPhotos(balloon) => {
+                            self.format_icloud_photos(&balloon, indent)
+                        }
+                    }
+                // Handwriting uses a different payload type than the rest of the branches
+                } else {
+                    // Handle the app case
+                    let parsed = parse_plist(&payload)?;
+                    match AppMessage::from_map(&parsed) {
+                        Ok(bubble) => match balloon {
+                            CustomBalloon::Application(bundle_id) => {
+                                self.format_generic_app(&bubble, bundle_id, attachments, indent)
+                            }
+                            CustomBalloon::ApplePay => self.format_apple_pay(&bubble, indent),
+                            CustomBalloon::Fitness => self.format_fitness(&bubble, indent),
+                            CustomBalloon::Slideshow => self.format_slideshow(&bubble, indent),
+                            CustomBalloon::CheckIn => self.format_check_in(&bubble, indent),
+                            CustomBalloon::FindMy => self.format_find_my(&bubble, indent),
+                            CustomBalloon::Handwriting => unreachable!(),
+                            CustomBalloon::DigitalTouch => unreachable!(),
+                            CustomBalloon::URL => unreachable!(),
+                        },
+                        Err(why) => return Err(why),
+                    }
+                };
+                app_bubble.push_str(&res);
+            } else {
+                // Sometimes, URL messages are missing their payloads
+                if message.is_url() {
+                    if let Some(text) = self.format_url_text(message) {
+                        return Ok(text);
+                    }
+                }
+                return Err(PlistParseError::NoPayload);
+            };
+            Ok(app_bubble)
+        } else {
+            Err(PlistParseError::WrongMessageType)
+        }
+    }
+
+    fn format_tapback(&self, msg: &Message) -> Result<String, TableError> {
+        match msg.variant() {
+            Variant::Tapback(_, added, tapback) => {
+                if !added {
+                    return Ok(String::new());
+                }
+                Ok(format!(
+                    "{} by {}",
+                    tapback,
+                    sanitize_markdown(self.config.who(
+                        msg.handle_id,
+                        msg.is_from_me(),
+                        &msg.destination_caller_id
+                    )),
+                ))
+            }
+            Variant::Sticker(_) => {
+                let mut paths = Attachment::from_message(&self.config.db, msg)?;
+                let who = sanitize_markdown(self.config.who(
+                    msg.handle_id,
+                    msg.is_from_me(),
+                    &msg.destination_caller_id,
+                ));
+                // Sticker messages have only one attachment, the sticker image
+                Ok(if let Some(sticker) = paths.get_mut(0) {
+                    format!("{} from {who}", self.format_sticker(sticker, msg))
+                } else {
+                    format!("Sticker from {who} not found!")
+                })
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn format_expressive(&self, msg: &'a Message) -> &'a str {
+        match msg.get_expressive() {
+            Expressive::Screen(effect) => match effect {
+                ScreenEffect::Confetti => "Sent with Confetti",
+                ScreenEffect::Echo => "Sent with Echo",
+                ScreenEffect::Fireworks => "Sent with Fireworks",
+                ScreenEffect::Balloons => "Sent with Balloons",
+                ScreenEffect::Heart => "Sent with Heart",
+                ScreenEffect::Lasers => "Sent with Lasers",
+                ScreenEffect::ShootingStar => "Sent with Shooting Star",
+                ScreenEffect::Sparkles => "Sent with Sparkles",
+                ScreenEffect::Spotlight => "Sent with Spotlight",
+            },
+            Expressive::Bubble(effect) => match effect {
+                BubbleEffect::Slam => "Sent with Slam",
+                BubbleEffect::Loud => "Sent with Loud",
+                BubbleEffect::Gentle => "Sent with Gentle",
+                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
+            },
+            Expressive::Unknown(effect) => effect,
+            Expressive::None => "",
+        }
+    }
+
+    fn format_announcement(&self, msg: &'a Message) -> String {
+        let mut who = self
+            .config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+        let who = sanitize_markdown(who);
+
+        let timestamp = format(&msg.date(&self.config.offset));
+
+        return match msg.get_announcement() {
+            Some(announcement) => match announcement {
+                Announcement::NameChange(name) => {
+                    let name = sanitize_markdown(name);
+                    format!("*{timestamp}* {who} renamed the conversation to **{name}**\n\n")
+                }
+                Announcement::PhotoChange => {
+                    format!("*{timestamp}* {who} changed the group photo.\n\n")
+                }
+                Announcement::PhotoRemoved => {
+                    format!("*{timestamp}* {who} removed the group photo.\n\n")
+                }
+                Announcement::ParticipantAdded(handle) => {
+                    let added = sanitize_markdown(self.config.who(
+                        Some(handle),
+                        false,
+                        &msg.destination_caller_id,
+                    ));
+                    format!("*{timestamp}* {who} added {added} to the conversation.\n\n")
+                }
+                Announcement::ParticipantRemoved(handle) => {
+                    let removed = sanitize_markdown(self.config.who(
+                        Some(handle),
+                        false,
+                        &msg.destination_caller_id,
+                    ));
+                    format!("*{timestamp}* {who} removed {removed} from the conversation.\n\n")
+                }
+                Announcement::ParticipantLeft => {
+                    format!("*{timestamp}* {who} left the conversation.\n\n")
+                }
+                Announcement::AudioMessageKept => {
+                    format!("*{timestamp}* {who} kept an audio message.\n\n")
+                }
+                Announcement::UnknownEvent(num) => {
+                    format!("*{timestamp}* {who} performed unknown system event {num}.\n\n")
+                }
+                Announcement::Unknown(num) => {
+                    format!("*{timestamp}* {who} performed unknown action {num}.\n\n")
+                }
+                Announcement::FullyUnsent => {
+                    format!("*{timestamp}* {who} unsent a message!\n\n")
+                }
+            },
+            None => String::from("*Unable to format announcement!*\n\n"),
+        };
+    }
+
+    fn format_shareplay(&self) -> &str {
+        "SharePlay Message  \nEnded"
+    }
+
+    fn format_shared_location(&self, msg: &'a Message) -> &str {
+        // Handle Shared Location
+        if msg.started_sharing_location() {
+            return "Started sharing location!";
+        } else if msg.stopped_sharing_location() {
+            return "Stopped sharing location!";
+        }
+        "Shared location!"
+    }
+
+    fn format_transcription(&self, transcription: &str) -> String {
+        format!("*Transcription: {}*", sanitize_markdown(transcription))
+    }
+
+    fn format_edited(
+        &self,
+        msg: &'a Message,
+        edited_message: &'a EditedMessage,
+        message_part_idx: usize,
+        _: &str,
+    ) -> Option<String> {
+        if let Some(edited_message_part) = edited_message.part(message_part_idx) {
+            let mut out_s = String::new();
+            let mut previous_timestamp: Option<&i64> = None;
+
+            match edited_message_part.status {
+                EditStatus::Edited => {
+                    for event in &edited_message_part.edit_history {
+                        if !out_s.is_empty() {
+                            out_s.push_str(HARD_BREAK);
+                        }
+
+                        match previous_timestamp {
+                            // Original message get an absolute timestamp
+                            None => {
+                                let parsed_timestamp =
+                                    format(&get_local_time(&event.date, &self.config.offset));
+                                out_s.push('*');
+                                out_s.push_str(&parsed_timestamp);
+                                out_s.push_str("* ");
+                            }
+                            // Subsequent edits get a relative timestamp
+                            Some(prev_timestamp) => {
+                                let end = get_local_time(&event.date, &self.config.offset);
+                                let start = get_local_time(prev_timestamp, &self.config.offset);
+                                if let Some(diff) = readable_diff(start, end) {
+                                    out_s.push_str("*Edited ");
+                                    out_s.push_str(&diff);
+                                    out_s.push_str(" later:* ");
+                                }
+                            }
+                        };
+
+                        // Update the previous timestamp for the next loop
+                        previous_timestamp = Some(&event.date);
+
+                        // Render the message text
+                        out_s.push_str(&sanitize_markdown(&event.text).replace('\n', HARD_BREAK));
+                    }
+                }
+                EditStatus::Unsent => {
+                    let who = if msg.is_from_me() {
+                        self.config.options.custom_name.as_deref().unwrap_or(YOU)
+                    } else {
+                        "They"
+                    };
+
+                    out_s.push('*');
+                    match readable_diff(
+                        msg.date(&self.config.offset),
+                        msg.date_edited(&self.config.offset),
+                    ) {
+                        Some(diff) => {
+                            out_s.push_str(&sanitize_markdown(who));
+                            out_s.push_str(" unsent this message part ");
+                            out_s.push_str(&diff);
+                            out_s.push_str(" after sending!");
+                        }
+                        None => {
+                            out_s.push_str(&sanitize_markdown(who));
+                            out_s.push_str(" unsent this message part!");
+                        }
+                    }
+                    out_s.push('*');
+                }
+                EditStatus::Original => {
+                    return None;
+                }
+            }
+
+            return Some(out_s);
+        }
+        None
+    }
+
+    fn format_attributed(&'a self, text: &'a str, attribute: &'a TextEffect) -> Cow<str> {
+        match attribute {
+            TextEffect::Default => Cow::Borrowed(text),
+            TextEffect::Mention(mentioned) => Cow::Owned(self.format_mention(text, mentioned)),
+            TextEffect::Link(url) => Cow::Owned(self.format_link(text, url)),
+            TextEffect::OTP => Cow::Owned(self.format_otp(text)),
+            TextEffect::Styles(styles) => Cow::Owned(self.format_styles(text, styles)),
+            TextEffect::Animated(animation) => Cow::Owned(self.format_animated(text, animation)),
+            TextEffect::Conversion(unit) => Cow::Owned(self.format_conversion(text, unit)),
+            TextEffect::Detected(detector) => Cow::Owned(self.format_detected(text, detector)),
+        }
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+impl<'a> BalloonFormatter<&'a str> for Markdown<'a> {
+    fn format_url(&self, msg: &Message, balloon: &URLMessage, indent: &str) -> String {
+        let mut out_s = String::new();
+
+        match (balloon.get_url(), balloon.title) {
+            (Some(url), Some(title)) => self.add_line(
+                &mut out_s,
+                &format!("[{}]({})", sanitize_markdown(title), link_target(url)),
+                indent,
+            ),
+            (Some(url), None) => self.add_line(&mut out_s, &format!("<{url}>"), indent),
+            (None, title) => {
+                if let Some(text) = &msg.text {
+                    self.add_line(&mut out_s, &sanitize_markdown(text), indent);
+                }
+                if let Some(title) = title {
+                    self.add_line(&mut out_s, &sanitize_markdown(title), indent);
+                }
+            }
+        }
+
+        if let Some(summary) = balloon.summary {
+            self.add_line(&mut out_s, &sanitize_markdown(summary), indent);
+        }
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_music(&self, balloon: &MusicMessage, indent: &str) -> String {
+        let mut out_s = String::new();
+
+        if let Some(track_name) = balloon.track_name {
+            self.add_line(
+                &mut out_s,
+                &format!("**{}**", sanitize_markdown(track_name)),
+                indent,
+            );
+        }
+
+        if let Some(album) = balloon.album {
+            self.add_line(&mut out_s, &sanitize_markdown(album), indent);
+        }
+
+        if let Some(artist) = balloon.artist {
+            self.add_line(&mut out_s, &sanitize_markdown(artist), indent);
+        }
+
+        if let Some(url) = balloon.url {
+            self.add_line(&mut out_s, &format!("<{url}>"), indent);
+        }
+
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_collaboration(&self, balloon: &CollaborationMessage, indent: &str) -> String {
+        let mut out_s = String::new();
+
+        if let Some(name) = balloon.app_name.or(balloon.bundle_id) {
+            self.add_line(
+                &mut out_s,
+                &format!("{} message:", sanitize_markdown(name)),
+                indent,
+            );
+        }
+
+        if let Some(title) = balloon.title {
+            self.add_line(&mut out_s, &sanitize_markdown(title), indent);
+        }
+
+        if let Some(url) = balloon.get_url() {
+            self.add_line(&mut out_s, &format!("<{url}>"), indent);
+        }
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_app_store(&self, balloon: &AppStoreMessage, indent: &'a str) -> String {
+        let mut out_s = String::new();
+
+        if let Some(name) = balloon.app_name {
+            self.add_line(
+                &mut out_s,
+                &format!("**{}**", sanitize_markdown(name)),
+                indent,
+            );
+        }
+
+        if let Some(description) = balloon.description {
+            self.add_line(&mut out_s, &sanitize_markdown(description), indent);
+        }
+
+        if let Some(platform) = balloon.platform {
+            self.add_line(&mut out_s, &sanitize_markdown(platform), indent);
+        }
+
+        if let Some(genre) = balloon.genre {
+            self.add_line(&mut out_s, &sanitize_markdown(genre), indent);
+        }
+
+        if let Some(url) = balloon.url {
+            self.add_line(&mut out_s, &format!("<{url}>"), indent);
+        }
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_facetime(&self, balloon: &FaceTimeMessage, indent: &'a str) -> String {
+        let mut out_s = String::new();
+
+        let heading = match balloon.title {
+            Some(title) => format!(
+                "**FaceTime call link shared: {}**",
+                sanitize_markdown(title)
+            ),
+            None => String::from("**FaceTime call link shared**"),
+        };
+        self.add_line(&mut out_s, &heading, indent);
+        self.add_line(&mut out_s, &format!("<{}>", balloon.url), indent);
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_icloud_photos(&self, balloon: &ICloudPhotosMessage, indent: &'a str) -> String {
+        let mut out_s = String::new();
+
+        let kind = match balloon.kind {
+            ICloudPhotosKind::SharedAlbum => "Shared Album invitation",
+            ICloudPhotosKind::PhotoLink => "iCloud Photos link shared",
+        };
+        let heading = match balloon.title {
+            Some(title) => format!("**{kind}: {}**", sanitize_markdown(title)),
+            None => format!("**{kind}**"),
+        };
+        self.add_line(&mut out_s, &heading, indent);
+        if let Some(summary) = balloon.summary {
+            self.add_line(&mut out_s, &sanitize_markdown(summary), indent);
+        }
+        self.add_line(&mut out_s, &format!("<{}>", balloon.url), indent);
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_placemark(&self, balloon: &PlacemarkMessage, indent: &'a str) -> String {
+        let mut out_s = String::new();
+
+        if let Some(name) = balloon.place_name {
+            self.add_line(
+                &mut out_s,
+                &format!("**{}**", sanitize_markdown(name)),
+                indent,
+            );
+        }
+
+        if let Some(url) = balloon.get_url() {
+            self.add_line(&mut out_s, &format!("<{url}>"), indent);
+        }
+
+        // Collect the address into a single block
+        let address = [
+            balloon.placemark.name,
+            balloon.placemark.address,
+            balloon.placemark.state,
+            balloon.placemark.city,
+            balloon.placemark.iso_country_code,
+            balloon.placemark.postal_code,
+            balloon.placemark.country,
+            balloon.placemark.street,
+            balloon.placemark.sub_administrative_area,
+            balloon.placemark.sub_locality,
+        ]
+        .into_iter()
+        .flatten()
+        .map(sanitize_markdown)
+        .collect::<Vec<_>>()
+        .join(HARD_BREAK);
+        self.add_line(&mut out_s, &address, indent);
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_handwriting(
+        &self,
+        msg: &Message,
+        balloon: &HandwrittenMessage,
+        indent: &str,
+    ) -> String {
+        let ascii = || format!("{indent}```\n{}\n{indent}```", balloon.render_ascii(40));
+        match self.config.options.attachment_manager {
+            AttachmentManager::Disabled => ascii(),
+            AttachmentManager::Compatible
+            | AttachmentManager::Efficient
+            | AttachmentManager::Link => self
+                .config
+                .options
+                .attachment_manager
+                .handle_handwriting(msg, balloon, self.config)
+                .map(|filepath| {
+                    self.config
+                        .relative_path(PathBuf::from(&filepath))
+                        .unwrap_or(filepath.display().to_string())
+                })
+                .map(|filepath| {
+                    format!("{indent}![Handwritten message]({})", link_target(&filepath))
+                })
+                .unwrap_or_else(ascii),
+        }
+    }
+
+    fn format_digital_touch(&self, msg: &Message, payload: &[u8], indent: &str) -> String {
+        match self
+            .config
+            .options
+            .attachment_manager
+            .handle_digital_touch(msg, payload, self.config)
+        {
+            Some(filepath) => format!(
+                "{indent}*Digital Touch message* ([strokes]({}))",
+                link_target(
+                    &self
+                        .config
+                        .relative_path(filepath.clone())
+                        .unwrap_or(filepath.display().to_string())
+                )
+            ),
+            None => format!("{indent}*Digital Touch message*"),
+        }
+    }
+
+    fn format_apple_pay(&self, balloon: &AppMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        if let Some(caption) = balloon.caption {
+            out_s.push_str(&sanitize_markdown(caption));
+            out_s.push_str(" transaction: ");
+        }
+
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str(&sanitize_markdown(ldtext));
+        } else {
+            out_s.push_str("unknown amount");
+        }
+
+        out_s
+    }
+
+    fn format_fitness(&self, balloon: &AppMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str(&sanitize_markdown(app_name));
+            out_s.push_str(" message: ");
+        }
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str(&sanitize_markdown(ldtext));
+        } else {
+            out_s.push_str("unknown workout");
+        }
+        out_s
+    }
+
+    fn format_slideshow(&self, balloon: &AppMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str("Photo album: ");
+            out_s.push_str(&sanitize_markdown(ldtext));
+        }
+
+        if let Some(url) = balloon.url {
+            out_s.push_str(" <");
+            out_s.push_str(url);
+            out_s.push('>');
+        }
+
+        out_s
+    }
+
+    fn format_find_my(&self, balloon: &AppMessage, indent: &'a str) -> String {
+        let mut out_s = String::from(indent);
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str(&sanitize_markdown(app_name));
+            out_s.push(':');
+        }
+
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push(' ');
+            out_s.push_str(&sanitize_markdown(ldtext));
+        }
+
+        out_s
+    }
+
+    fn format_check_in(&self, balloon: &AppMessage, indent: &'a str) -> String {
+        let mut out_s = String::from(indent);
+
+        out_s.push_str(&sanitize_markdown(balloon.caption.unwrap_or("Check In")));
+
+        let metadata: HashMap<&str, &str> = balloon.parse_query_string();
+
+        // Before manual check-in
+        if let Some(date_str) = metadata.get("estimatedEndTime") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            out_s.push_str(HARD_BREAK);
+            out_s.push_str("Expected at ");
+            out_s.push_str(&date_string);
+        }
+        // Expired check-in
+        else if let Some(date_str) = metadata.get("triggerTime") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            out_s.push_str(HARD_BREAK);
+            out_s.push_str("Was expected at ");
+            out_s.push_str(&date_string);
+        }
+        // Accepted check-in
+        else if let Some(date_str) = metadata.get("sendDate") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            out_s.push_str(HARD_BREAK);
+            out_s.push_str("Checked in at ");
+            out_s.push_str(&date_string);
+        }
+
+        out_s
+    }
+
+    fn format_generic_app(
+        &self,
+        balloon: &AppMessage,
+        bundle_id: &str,
+        _: &mut Vec<Attachment>,
+        indent: &str,
+    ) -> String {
+        let mut out_s = String::new();
+
+        self.add_line(
+            &mut out_s,
+            &format!(
+                "{} message:",
+                sanitize_markdown(balloon.app_name.unwrap_or(bundle_id))
+            ),
+            indent,
+        );
+
+        for part in [
+            balloon.title,
+            balloon.subtitle,
+            balloon.caption,
+            balloon.subcaption,
+            balloon.trailing_caption,
+            balloon.trailing_subcaption,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            self.add_line(&mut out_s, &sanitize_markdown(part), indent);
+        }
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+}
+
+impl<'a> TextEffectFormatter for Markdown<'a> {
+    fn format_mention(&self, text: &str, _: &str) -> String {
+        emphasize(text, "**", "**")
+    }
+
+    fn format_link(&self, text: &str, url: &str) -> String {
+        format!("[{text}]({})", link_target(url))
+    }
+
+    fn format_otp(&self, text: &str) -> String {
+        emphasize(text, "<u>", "</u>")
+    }
+
+    fn format_conversion(&self, text: &str, _: &Unit) -> String {
+        emphasize(text, "<u>", "</u>")
+    }
+
+    fn format_styles(&self, text: &str, styles: &[Style]) -> String {
+        let (prefix, suffix): (String, String) = styles.iter().rev().fold(
+            (String::new(), String::new()),
+            |(mut prefix, mut suffix), style| {
+                // Markdown has no underline syntax, so fall back to inline HTML
+                let (open, close) = match style {
+                    Style::Bold => ("**", "**"),
+                    Style::Italic => ("*", "*"),
+                    Style::Strikethrough => ("~~", "~~"),
+                    Style::Underline => ("<u>", "</u>"),
+                };
+                prefix.push_str(open);
+                suffix.insert_str(0, close);
+                (prefix, suffix)
+            },
+        );
+
+        emphasize(text, &prefix, &suffix)
+    }
+
+    fn format_animated(&self, text: &str, _: &Animation) -> String {
+        // There isn't really a way to represent animated text in Markdown
+        text.to_string()
+    }
+
+    fn format_detected(&self, text: &str, detector: &DataDetector) -> String {
+        match detector.link(text) {
+            Some(url) => self.format_link(text, &url),
+            None => text.to_string(),
+        }
+    }
+}
+
+impl<'a> Markdown<'a> {
+    /// List the members of a group chat and the changes to them, for the top of its file
+    fn format_roster(roster: &Roster) -> String {
+        let members: Vec<_> = roster
+            .members
+            .iter()
+            .map(|member| sanitize_markdown(member))
+            .collect();
+        let mut out_s = format!("**Participants:** {}\n\n", members.join(", "));
+        if !roster.changes.is_empty() {
+            out_s.push_str("**Membership changes:**\n\n");
+            for change in &roster.changes {
+                out_s.push_str(&format!("- {}\n", sanitize_markdown(change)));
+            }
+            out_s.push('\n');
+        }
+        out_s
+    }
+
+    /// Format the address in a URL message's text, for links without a readable preview
+    fn format_url_text(&self, message: &Message) -> Option<String> {
+        message
+            .text
+            .as_deref()
+            .map(|text| sanitize_markdown(text).to_string())
+    }
+
+    fn get_time(&self, message: &Message) -> String {
+        let mut date = format(&message.date(&self.config.offset));
+        let read_after = message.time_until_read(&self.config.offset);
+        if let Some(time) = read_after {
+            if !time.is_empty() {
+                let who = if message.is_from_me() {
+                    "them"
+                } else {
+                    self.config.options.custom_name.as_deref().unwrap_or("you")
+                };
+                date.push_str(&format!(" (Read by {who} after {time})"));
+            }
+        }
+        if let Some(receipts) = self.config.receipts(message) {
+            date.push_str(&format!(" ({receipts})"));
+        }
+        date
+    }
+
+    /// Add a block to the output, quoting each of its lines with `indent`
+    ///
+    /// Blocks are separated by an empty line at the same quote depth so Markdown renders them as distinct paragraphs.
+    fn add_line(&self, string: &mut String, part: &str, indent: &str) {
+        if !part.is_empty() {
+            if !string.is_empty() {
+                string.push_str(indent.trim_end());
+                string.push('\n');
+            }
+            for line in part.lines() {
+                if line.is_empty() {
+                    string.push_str(indent.trim_end());
+                } else {
+                    string.push_str(indent);
+                    string.push_str(line);
+                }
+                string.push('\n');
+            }
+        }
+    }
+}
+
+/// Wrap text in emphasis markers, keeping surrounding whitespace outside of them
+///
+/// Markdown does not treat delimiters next to whitespace as emphasis, i.e. `** bold**` is not bold.
+pub(super) fn emphasize(text: &str, open: &str, close: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_string();
+    }
+    let start = text.len() - text.trim_start().len();
+    let end = start + trimmed.len();
+    format!("{}{open}{trimmed}{close}{}", &text[..start], &text[end..])
+}
+
+/// Encode the characters that would end a Markdown link destination early
+fn link_target(path: &str) -> Cow<str> {
+    if path.contains([' ', '(', ')', '<', '>']) {
+        return Cow::Owned(
+            path.replace(' ', "%20")
+                .replace('(', "%28")
+                .replace(')', "%29")
+                .replace('<', "%3C")
+                .replace('>', "%3E"),
+        );
+    }
+    Cow::Borrowed(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env::set_var, path::PathBuf};
+
+    use crate::{
+        app::{
+            attachment_manager::AttachmentManager, chat_type::ChatType, roster::Roster,
+            theme::Theme,
+        },
+        exporters::{
+            exporter::Writer,
+            markdown::{emphasize, link_target},
+        },
+        Config, Exporter, Markdown, Options,
+    };
+    use imessage_database::{
+        tables::{
+            attachment::Attachment,
+            messages::Message,
+            table::{get_connection, ME},
+        },
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options() -> Options {
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path: PathBuf::from("/tmp"),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    pub(super) fn fake_attachment() -> Attachment {
+        Attachment {
+            rowid: 0,
+            filename: Some("a/b/c/d.jpg".to_string()),
+            uti: Some("public.png".to_string()),
+            mime_type: Some("image/png".to_string()),
+            transfer_name: Some("d.jpg".to_string()),
+            total_bytes: 100,
+            is_sticker: false,
+            hide_attachment: 0,
+            copied_path: None,
+            backup_root: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_add_line_no_indent() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        // Create sample data
+        let mut s = String::new();
+        exporter.add_line(&mut s, "hello world", "");
+        exporter.add_line(&mut s, "goodbye", "");
+
+        assert_eq!(s, "hello world\n\ngoodbye\n".to_string());
+    }
+
+    #[test]
+    fn can_add_line_quoted() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        // Create sample data
+        let mut s = String::new();
+        exporter.add_line(&mut s, "hello\nworld", "> ");
+        exporter.add_line(&mut s, "goodbye", "> ");
+
+        assert_eq!(s, "> hello\n> world\n>\n> goodbye\n".to_string());
+    }
+
+    #[test]
+    fn can_format_md_roster() {
+        let roster = Roster {
+            members: vec!["Alice".to_string(), "Bob_1".to_string(), "Me".to_string()],
+            changes: vec!["May 17, 2022  5:29:42 PM: Me added Bob_1".to_string()],
+        };
+
+        let actual = Markdown::format_roster(&roster);
+        let expected = "**Participants:** Alice, Bob\\_1, Me\n\n**Membership changes:**\n\n- May 17, 2022  5:29:42 PM: Me added Bob\\_1\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_from_me_normal() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Me** *May 17, 2022  5:29:42 PM*\n\nHello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_from_me_normal_deleted() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.deleted_from = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Me** *May 17, 2022  5:29:42 PM*\n\n*This message was deleted from the conversation!*\n\nHello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_from_them_normal() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.handle_id = Some(999999);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "> **Sample Contact** *May 17, 2022  5:29:42 PM*\n>\n> Hello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_from_them_multiline_escaped() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("# Hello\n*world*".to_string());
+        message.handle_id = Some(999999);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected =
+            "> **Sample Contact** *May 17, 2022  5:29:42 PM*\n>\n> \\# Hello  \n> \\*world\\*\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_shareplay() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.item_type = 6;
+        message.is_from_me = true;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Me** *May 17, 2022  5:29:42 PM*\n\nSharePlay Message  \nEnded\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_announcement() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.group_title = Some("Hello world".to_string());
+        message.is_from_me = true;
+
+        let actual = exporter.format_announcement(&message);
+        let expected =
+            "*May 17, 2022  5:29:42 PM* You renamed the conversation to **Hello world**\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_tapback_them() {
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = blank();
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some("fake_guid".to_string());
+        message.handle_id = Some(999999);
+
+        let actual = exporter.format_tapback(&message).unwrap();
+        let expected = "Loved by Sample Contact";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_attachment_image() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        let message = blank();
+        let mut attachment = fake_attachment();
+
+        let actual = exporter.format_attachment(&mut attachment, &message);
+
+        assert_eq!(actual, Ok("![d.jpg](a/b/c/d.jpg)".to_string()));
+    }
+
+    #[test]
+    fn can_format_md_attachment_link() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        let message = blank();
+        let mut attachment = fake_attachment();
+        attachment.filename = Some("a/b/c/my file.pdf".to_string());
+        attachment.transfer_name = Some("my file.pdf".to_string());
+        attachment.mime_type = Some("application/pdf".to_string());
+
+        let actual = exporter.format_attachment(&mut attachment, &message);
+
+        assert_eq!(actual, Ok("[my file.pdf](a/b/c/my%20file.pdf)".to_string()));
+    }
+
+    #[test]
+    fn can_encode_link_target() {
+        assert_eq!(link_target("a/b.png"), "a/b.png");
+        assert_eq!(link_target("a b/(c).png"), "a%20b/%28c%29.png");
+    }
+
+    #[test]
+    fn can_emphasize_with_whitespace() {
+        assert_eq!(emphasize(" bold ", "**", "**"), " **bold** ");
+        assert_eq!(emphasize("   ", "**", "**"), "   ");
+    }
+}
+
+#[cfg(test)]
+mod text_effect_tests {
+    use super::tests::{fake_config, fake_options};
+    use crate::{exporters::exporter::TextEffectFormatter, Exporter, Markdown};
+    use imessage_database::message_types::text_effects::{Animation, Style};
+
+    #[test]
+    fn can_format_md_mention() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.format_mention("Christopher", "+15558675309"),
+            "**Christopher**"
+        );
+    }
+
+    #[test]
+    fn can_format_md_link() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.format_link("site", "https://example.com"),
+            "[site](https://example.com)"
+        );
+    }
+
+    #[test]
+    fn can_format_md_styles() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.format_styles("text", &[Style::Bold, Style::Strikethrough]),
+            "~~**text**~~"
+        );
+        assert_eq!(
+            exporter.format_styles("text", &[Style::Underline]),
+            "<u>text</u>"
+        );
+    }
+
+    #[test]
+    fn can_format_md_animated() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        assert_eq!(exporter.format_animated("text", &Animation::Big), "text");
+    }
+}
@@ -0,0 +1,755 @@
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap, HashSet,
+    },
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use log::info;
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::{
+        exporter::Exporter,
+        html::append_extension,
+        ndjson::{object, string},
+    },
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::{Announcement, Tapback, Variant},
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::Message,
+        table::{Table, ME, ORPHANED, UNKNOWN, YOU},
+    },
+    util::dates::get_local_time,
+};
+
+/// Server name used to build room and user IDs, since conversations do not belong to a homeserver
+const SERVER_NAME: &str = "imessage.invalid";
+/// Characters allowed in the localpart of a generated user ID, other than lowercase letters and digits
+const LOCALPART_CHARS: &str = "._=-/";
+
+pub struct Matrix<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write events to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
+    /// Files that already contain at least one event, so the next one needs a separator
+    started: HashSet<String>,
+}
+
+impl<'a> Exporter<'a> for Matrix<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("json");
+        let file =
+            File::create(&orphaned).map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        let mut orphaned = BufWriter::new(file);
+        Matrix::write_to_file(&mut orphaned, &room_header(ORPHANED, ORPHANED))?;
+
+        Ok(Matrix {
+            config,
+            files: HashMap::new(),
+            orphaned,
+            started: HashSet::new(),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as matrix...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            for event in self.format_events(&msg)? {
+                self.write_event(&msg, &event)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        // Close the event list and room object in every file
+        for buf in self.files.values_mut() {
+            Matrix::write_to_file(buf, "\n]}\n")?;
+            buf.flush().map_err(RuntimeError::DiskError)?;
+        }
+        Matrix::write_to_file(&mut self.orphaned, "\n]}\n")?;
+        self.orphaned.flush().map_err(RuntimeError::DiskError)
+    }
+
+    /// Create a room file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, id)) => {
+                let filename = self.config.filename(chatroom);
+                return match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.options.export_path.clone();
+                        path.push(entry.key());
+                        append_extension(&mut path, "json");
+
+                        let file = File::create(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        let mut buf = BufWriter::new(file);
+                        Matrix::write_to_file(
+                            &mut buf,
+                            &room_header(&id.to_string(), chatroom.name()),
+                        )?;
+
+                        Ok(entry.insert(buf))
+                    }
+                };
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> Matrix<'a> {
+    /// Append an event to the room the message belongs to
+    fn write_event(&mut self, message: &Message, event: &str) -> Result<(), RuntimeError> {
+        let key = match self.config.conversation(message) {
+            Some((chatroom, _)) => self.config.filename(chatroom),
+            None => String::from(ORPHANED),
+        };
+        let separator = match self.started.insert(key) {
+            true => "\n",
+            false => ",\n",
+        };
+        let file = self.get_or_create_file(message)?;
+        Matrix::write_to_file(file, separator)?;
+        Matrix::write_to_file(file, event)
+    }
+
+    /// Build the Matrix events for a message: its text, each of its attachments, or the reaction it represents
+    fn format_events(&self, message: &Message) -> Result<Vec<String>, RuntimeError> {
+        let id = event_id(&message.guid);
+
+        // Tapbacks become reactions, which can only annotate a message once
+        if message.is_tapback() {
+            let key = match message.variant() {
+                Variant::Tapback(_, true, tapback) => reaction_key(&tapback),
+                _ => None,
+            };
+            let target = message.clean_associated_guid().map(|(_, guid)| guid);
+            return Ok(match (key, target) {
+                (Some(key), Some(target)) => vec![self.event(
+                    message,
+                    "m.reaction",
+                    &id,
+                    None,
+                    object(&[(
+                        "m.relates_to",
+                        object(&[
+                            ("rel_type", string("m.annotation")),
+                            ("event_id", string(&event_id(target))),
+                            ("key", string(&key)),
+                        ]),
+                    )]),
+                )],
+                _ => vec![],
+            });
+        }
+
+        if message.is_announcement() {
+            return Ok(vec![match message.get_announcement() {
+                Some(Announcement::NameChange(name)) => self.event(
+                    message,
+                    "m.room.name",
+                    &id,
+                    Some(""),
+                    object(&[("name", string(name))]),
+                ),
+                _ => self.event(
+                    message,
+                    "m.room.message",
+                    &id,
+                    None,
+                    object(&[
+                        ("msgtype", string("m.notice")),
+                        ("body", string(&self.format_announcement(message))),
+                    ]),
+                ),
+            }]);
+        }
+
+        // Replies point at the first event of the message they reply to
+        let reply = message.thread_originator_guid.as_deref().map(|originator| {
+            (
+                "m.relates_to",
+                object(&[(
+                    "m.in_reply_to",
+                    object(&[("event_id", string(&event_id(originator)))]),
+                )]),
+            )
+        });
+
+        let mut events = vec![];
+        if let Some(text) = self.config.message_text(message) {
+            // Attachments are sent as their own events
+            let body = text.replace('\u{FFFC}', "");
+            let body = body.trim();
+            if !body.is_empty() {
+                let mut content = vec![("msgtype", string("m.text")), ("body", string(body))];
+                content.extend(reply.clone());
+                events.push(self.event(message, "m.room.message", &id, None, object(&content)));
+            }
+        }
+
+        if message.has_attachments() {
+            let mut attachments = Attachment::from_message(&self.config.db, message)
+                .map_err(RuntimeError::DatabaseError)?;
+            for attachment in attachments.iter_mut() {
+                // The first event of a message keeps its ID so replies and reactions can find it
+                let part_id = match events.len() {
+                    0 => id.clone(),
+                    idx => format!("{id}-{idx}"),
+                };
+                let mut content = self.format_attachment(attachment, message);
+                content.extend(reply.clone());
+                events.push(self.event(
+                    message,
+                    "m.room.message",
+                    &part_id,
+                    None,
+                    object(&content),
+                ));
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Build the content fields of an attachment event, copying the attachment if requested
+    fn format_attachment(
+        &self,
+        attachment: &mut Attachment,
+        message: &Message,
+    ) -> Vec<(&'static str, String)> {
+        let copied = self.config.options.attachment_manager.handle_attachment(
+            message,
+            attachment,
+            self.config,
+        );
+
+        let msgtype = match attachment.mime_type() {
+            MediaType::Image(_) => "m.image",
+            MediaType::Video(_) => "m.video",
+            MediaType::Audio(_) => "m.audio",
+            _ => "m.file",
+        };
+        let mut info = vec![("size", attachment.total_bytes.to_string())];
+        if let Some(mime_type) = &attachment.mime_type {
+            info.push(("mimetype", string(mime_type)));
+        }
+
+        let mut content = vec![("msgtype", string(msgtype))];
+        // A transcribed audio message is captioned with its transcription, which clients show under the file
+        match message.audio_transcription() {
+            Some(transcription) => {
+                content.push(("body", string(transcription)));
+                content.push(("filename", string(attachment.filename())));
+            }
+            None => content.push(("body", string(attachment.filename()))),
+        }
+        content.push(("info", object(&info)));
+        if copied.is_some() {
+            content.push((
+                "url",
+                string(&self.config.message_attachment_path(attachment)),
+            ));
+        }
+        content
+    }
+
+    /// Format an announcement as a sentence
+    fn format_announcement(&self, message: &Message) -> String {
+        let mut who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+        match message.get_announcement() {
+            Some(Announcement::NameChange(name)) => {
+                format!("{who} renamed the conversation to {name}")
+            }
+            Some(Announcement::PhotoChange) => format!("{who} changed the group photo."),
+            Some(Announcement::PhotoRemoved) => format!("{who} removed the group photo."),
+            Some(Announcement::ParticipantAdded(handle)) => format!(
+                "{who} added {} to the conversation.",
+                self.config
+                    .who(Some(handle), false, &message.destination_caller_id)
+            ),
+            Some(Announcement::ParticipantRemoved(handle)) => format!(
+                "{who} removed {} from the conversation.",
+                self.config
+                    .who(Some(handle), false, &message.destination_caller_id)
+            ),
+            Some(Announcement::ParticipantLeft) => format!("{who} left the conversation."),
+            Some(Announcement::AudioMessageKept) => format!("{who} kept an audio message."),
+            Some(Announcement::UnknownEvent(num)) => {
+                format!("{who} performed unknown system event {num}.")
+            }
+            Some(Announcement::Unknown(num)) => format!("{who} performed unknown action {num}."),
+            Some(Announcement::FullyUnsent) => format!("{who} unsent a message!"),
+            None => String::from("Unable to format announcement!"),
+        }
+    }
+
+    /// Wrap event content with the fields every room event has
+    fn event(
+        &self,
+        message: &Message,
+        kind: &str,
+        event_id: &str,
+        state_key: Option<&str>,
+        content: String,
+    ) -> String {
+        let origin_server_ts = get_local_time(&message.date, &self.config.offset)
+            .map(|date| date.timestamp_millis())
+            .unwrap_or_default();
+
+        let mut fields = vec![
+            ("type", string(kind)),
+            ("event_id", string(event_id)),
+            ("sender", string(&self.user_id(message))),
+            ("origin_server_ts", origin_server_ts.to_string()),
+        ];
+        if let Some(state_key) = state_key {
+            fields.push(("state_key", string(state_key)));
+        }
+        fields.push(("content", content));
+        object(&fields)
+    }
+
+    /// Build the Matrix user ID of the message's sender
+    fn user_id(&self, message: &Message) -> String {
+        if message.is_from_me() {
+            let identifier = self
+                .config
+                .my_handle(&message.destination_caller_id)
+                .unwrap_or(ME);
+            return user_id(identifier);
+        }
+
+        let identifier = message
+            .handle_id
+            .and_then(|id| self.config.participants.get(&id))
+            .map_or(UNKNOWN, |id| id.as_str());
+        user_id(identifier)
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+/// Start a room object, leaving its event list open
+fn room_header(id: &str, name: &str) -> String {
+    let fields = object(&[
+        ("room_id", string(&format!("!{id}:{SERVER_NAME}"))),
+        ("name", string(name)),
+        ("events", String::from("[")),
+    ]);
+    // Drop the closing brace so events can be streamed into the list
+    fields[..fields.len() - 1].to_string()
+}
+
+/// Build a Matrix event ID from a message GUID
+fn event_id(guid: &str) -> String {
+    format!("${guid}")
+}
+
+/// Build a Matrix user ID from a handle, which may be a phone number or email address
+fn user_id(identifier: &str) -> String {
+    let identifier = identifier.to_lowercase();
+    let localpart: String = match identifier.contains('@') {
+        true => identifier
+            .chars()
+            .map(|c| if c == '@' { '=' } else { c })
+            .filter(|c| {
+                c.is_ascii_lowercase() || c.is_ascii_digit() || LOCALPART_CHARS.contains(*c)
+            })
+            .collect(),
+        // Phone numbers keep only their digits, so the same number is one user however it is formatted
+        false => identifier
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect(),
+    };
+    format!("@{localpart}:{SERVER_NAME}")
+}
+
+/// Get the emoji used as the key of a reaction, if the tapback has one
+fn reaction_key(tapback: &Tapback) -> Option<String> {
+    match tapback {
+        Tapback::Loved => Some(String::from("❤️")),
+        Tapback::Liked => Some(String::from("👍")),
+        Tapback::Disliked => Some(String::from("👎")),
+        Tapback::Laughed => Some(String::from("😂")),
+        Tapback::Emphasized => Some(String::from("‼️")),
+        Tapback::Questioned => Some(String::from("❓")),
+        Tapback::Emoji(emoji) => emoji.map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs::create_dir_all, path::PathBuf};
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        exporters::matrix::{room_header, user_id},
+        Config, Exporter, Matrix, Options,
+    };
+    use imessage_database::{
+        tables::{attachment::Attachment, messages::Message, table::get_connection},
+        util::{
+            dates::get_offset,
+            dirs::default_db_path,
+            platform::Platform,
+            query_context::QueryContext,
+            typedstream::models::{Archivable, Class, OutputData},
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options() -> Options {
+        let export_path = PathBuf::from("/tmp/matrix");
+        create_dir_all(&export_path).unwrap();
+
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path,
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Matrix::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_format_text_event() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Matrix::new(&config).unwrap();
+
+        let mut message = blank();
+        message.guid = "guid".to_string();
+        message.text = Some("Hello \"world\"".to_string());
+        message.is_from_me = true;
+
+        let events = exporter.format_events(&message).unwrap();
+        assert_eq!(
+            events,
+            vec![concat!(
+                "{\"type\":\"m.room.message\",\"event_id\":\"$guid\",",
+                "\"sender\":\"@me:imessage.invalid\",\"origin_server_ts\":978307200000,",
+                "\"content\":{\"msgtype\":\"m.text\",\"body\":\"Hello \\\"world\\\"\"}}"
+            )]
+        );
+    }
+
+    #[test]
+    fn can_format_reply_event() {
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "+1 (555) 555-5555".to_string());
+        let exporter = Matrix::new(&config).unwrap();
+
+        let mut message = blank();
+        message.handle_id = Some(999999);
+        message.text = Some("Reply".to_string());
+        message.thread_originator_guid = Some("original".to_string());
+
+        let events = exporter.format_events(&message).unwrap();
+        assert!(events[0].contains("\"sender\":\"@15555555555:imessage.invalid\""));
+        assert!(
+            events[0].contains("\"m.relates_to\":{\"m.in_reply_to\":{\"event_id\":\"$original\"}}")
+        );
+    }
+
+    #[test]
+    fn can_format_reaction_event() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Matrix::new(&config).unwrap();
+
+        let mut message = blank();
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid =
+            Some("p:0/F0B18A15-E248-46D3-8F4B-D6ED3F0A3A0B".to_string());
+
+        let events = exporter.format_events(&message).unwrap();
+        assert!(events[0].starts_with("{\"type\":\"m.reaction\""));
+        assert!(events[0].contains(concat!(
+            "\"m.relates_to\":{\"rel_type\":\"m.annotation\",",
+            "\"event_id\":\"$F0B18A15-E248-46D3-8F4B-D6ED3F0A3A0B\",\"key\":\"❤️\"}"
+        )));
+    }
+
+    #[test]
+    fn can_skip_removed_reaction() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Matrix::new(&config).unwrap();
+
+        let mut message = blank();
+        message.associated_message_type = Some(3000);
+        message.associated_message_guid = Some("p:0/target".to_string());
+
+        assert!(exporter.format_events(&message).unwrap().is_empty());
+    }
+
+    #[test]
+    fn can_format_name_change() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Matrix::new(&config).unwrap();
+
+        let mut message = blank();
+        message.item_type = 2;
+        message.group_title = Some("Book Club".to_string());
+
+        let events = exporter.format_events(&message).unwrap();
+        assert!(events[0].starts_with("{\"type\":\"m.room.name\""));
+        assert!(events[0].contains("\"state_key\":\"\",\"content\":{\"name\":\"Book Club\"}"));
+    }
+
+    #[test]
+    fn can_caption_transcribed_audio() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = Matrix::new(&config).unwrap();
+
+        let mut message = blank();
+        message.is_audio_message = true;
+        message.components = Some(
+            ["\u{FFFC}", "IMAudioTranscription", "On my way"]
+                .iter()
+                .map(|text| {
+                    Archivable::Object(
+                        Class {
+                            name: "NSString".into(),
+                            version: 1,
+                        },
+                        vec![OutputData::String((*text).into())],
+                    )
+                })
+                .collect(),
+        );
+        let mut attachment = Attachment {
+            rowid: 0,
+            filename: None,
+            uti: Some("com.apple.coreaudio-format".to_string()),
+            mime_type: Some("audio/x-caf".to_string()),
+            transfer_name: Some("Audio Message.caf".to_string()),
+            total_bytes: 100,
+            is_sticker: false,
+            hide_attachment: 0,
+            copied_path: None,
+            backup_root: None,
+        };
+
+        let content = exporter.format_attachment(&mut attachment, &message);
+        assert_eq!(
+            content[..3],
+            [
+                ("msgtype", "\"m.audio\"".to_string()),
+                ("body", "\"On my way\"".to_string()),
+                ("filename", "\"Audio Message.caf\"".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_build_user_id() {
+        assert_eq!(
+            user_id("+1 (555) 555-5555"),
+            "@15555555555:imessage.invalid"
+        );
+        assert_eq!(
+            user_id("Person@Example.com"),
+            "@person=example.com:imessage.invalid"
+        );
+    }
+
+    #[test]
+    fn can_build_room_header() {
+        assert_eq!(
+            room_header("1", "Book Club"),
+            "{\"room_id\":\"!1:imessage.invalid\",\"name\":\"Book Club\",\"events\":["
+        );
+    }
+}
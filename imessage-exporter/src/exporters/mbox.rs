@@ -0,0 +1,768 @@
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::{read, File},
+    io::{BufWriter, Write},
+};
+
+use log::info;
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::{exporter::Exporter, html::append_extension},
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{expressives::Expressive, variants::Announcement},
+    tables::{
+        attachment::Attachment,
+        messages::Message,
+        table::{Table, ME, ORPHANED, UNKNOWN, YOU},
+    },
+    util::dates::get_local_time,
+};
+
+/// Domain used to build email addresses for phone numbers and the database owner
+const ADDRESS_DOMAIN: &str = "imessage.invalid";
+/// Characters allowed in the local part of a generated address
+const ADDRESS_CHARS: &str = "+-._";
+/// Subject used for messages that do not belong to a conversation
+const ORPHANED_SUBJECT: &str = "Orphaned messages";
+/// Length of each line of base64-encoded attachment data
+const BASE64_LINE_LENGTH: usize = 76;
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub struct MBOX<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
+}
+
+impl<'a> Exporter<'a> for MBOX<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("mbox");
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        Ok(MBOX {
+            config,
+            files: HashMap::new(),
+            orphaned: BufWriter::new(file),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as mbox...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Tapbacks are listed in the email for the message they belong to
+            if !msg.is_tapback() {
+                let email = self.format_entry(&msg)?;
+                MBOX::write_to_file(self.get_or_create_file(&msg)?, &email)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                return match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.options.export_path.clone();
+                        path.push(entry.key());
+                        append_extension(&mut path, "mbox");
+
+                        let file = File::options()
+                            .append(true)
+                            .create(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        Ok(entry.insert(BufWriter::new(file)))
+                    }
+                };
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> MBOX<'a> {
+    /// Build an mbox entry for a message: a separator line followed by the message as an email
+    fn format_entry(&self, message: &Message) -> Result<String, RuntimeError> {
+        let date = get_local_time(&message.date, &self.config.offset);
+        let sender = address(
+            self.config,
+            message.handle_id,
+            message.is_from_me(),
+            message,
+        );
+        let email = format_email(self.config, message)?;
+
+        // The mbox separator line uses the `asctime` date format, and entries are separated by a blank line
+        Ok(format!(
+            "From {} {}\n{}\n",
+            sender.1,
+            date.as_ref()
+                .map(|d| d.format("%a %b %e %H:%M:%S %Y").to_string())
+                .unwrap_or_default(),
+            escape_from_lines(&email)
+        ))
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+/// Build an RFC 5322 email for a message, including its attachments
+pub(super) fn format_email(config: &Config, message: &Message) -> Result<String, RuntimeError> {
+    let date = get_local_time(&message.date, &config.offset);
+    let sender = address(config, message.handle_id, message.is_from_me(), message);
+
+    // Everyone in the conversation other than the sender receives the message
+    let mut recipients = vec![];
+    if !message.is_from_me() {
+        recipients.push(address(config, None, true, message));
+    }
+    if let Some(participants) = message
+        .chat_id
+        .or(message.deleted_from)
+        .and_then(|id| config.chatroom_participants.get(&id))
+    {
+        for handle_id in participants {
+            if Some(*handle_id) != message.handle_id || message.is_from_me() {
+                recipients.push(address(config, Some(*handle_id), false, message));
+            }
+        }
+    }
+
+    let subject = match (&message.subject, config.conversation(message)) {
+        (Some(subject), _) => subject.as_str(),
+        (None, Some((chatroom, _))) => chatroom.name(),
+        (None, None) => ORPHANED_SUBJECT,
+    };
+
+    let mut email = String::new();
+    email.push_str(&format!("From: {}\n", mailbox(&sender.0, &sender.1)));
+    if !recipients.is_empty() {
+        let to: Vec<String> = recipients
+            .iter()
+            .map(|(name, address)| mailbox(name, address))
+            .collect();
+        email.push_str(&format!("To: {}\n", to.join(",\n ")));
+    }
+    if let Ok(date) = &date {
+        email.push_str(&format!("Date: {}\n", date.to_rfc2822()));
+    }
+    email.push_str(&format!("Subject: {}\n", encode_word(subject)));
+    email.push_str(&format!("Message-ID: {}\n", message_id(&message.guid)));
+    if let Some(originator) = &message.thread_originator_guid {
+        let originator = message_id(originator);
+        email.push_str(&format!(
+            "In-Reply-To: {originator}\nReferences: {originator}\n"
+        ));
+    }
+    email.push_str("MIME-Version: 1.0\n");
+
+    let body = format_body(config, message)?;
+    let mut attachments = if message.has_attachments() {
+        Attachment::from_message(&config.db, message).map_err(RuntimeError::DatabaseError)?
+    } else {
+        vec![]
+    };
+
+    if attachments.is_empty() {
+        email.push_str("Content-Type: text/plain; charset=utf-8\n");
+        email.push_str("Content-Transfer-Encoding: 8bit\n\n");
+        email.push_str(&body);
+    } else {
+        // Message GUIDs are unique, so they never appear in the encoded parts
+        let boundary = format!("imessage-{}", message.guid);
+        email.push_str(&format!(
+            "Content-Type: multipart/mixed; boundary=\"{boundary}\"\n\n"
+        ));
+        email.push_str(&format!("--{boundary}\n"));
+        email.push_str("Content-Type: text/plain; charset=utf-8\n");
+        email.push_str("Content-Transfer-Encoding: 8bit\n\n");
+        email.push_str(&body);
+        for attachment in attachments.iter_mut() {
+            email.push_str(&format!("\n--{boundary}\n"));
+            email.push_str(&format_attachment(config, attachment, message));
+        }
+        email.push_str(&format!("\n--{boundary}--\n"));
+    }
+
+    Ok(email)
+}
+
+/// Build the plain text body of a message
+fn format_body(config: &Config, message: &Message) -> Result<String, RuntimeError> {
+    let mut body = String::new();
+
+    if message.is_announcement() {
+        let mut who = config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+        body.push_str(&match message.get_announcement() {
+            Some(Announcement::NameChange(name)) => {
+                format!("{who} renamed the conversation to {name}")
+            }
+            Some(Announcement::PhotoChange) => format!("{who} changed the group photo."),
+            Some(Announcement::PhotoRemoved) => format!("{who} removed the group photo."),
+            Some(Announcement::ParticipantAdded(handle)) => format!(
+                "{who} added {} to the conversation.",
+                config.who(Some(handle), false, &message.destination_caller_id)
+            ),
+            Some(Announcement::ParticipantRemoved(handle)) => format!(
+                "{who} removed {} from the conversation.",
+                config.who(Some(handle), false, &message.destination_caller_id)
+            ),
+            Some(Announcement::ParticipantLeft) => format!("{who} left the conversation."),
+            Some(Announcement::AudioMessageKept) => format!("{who} kept an audio message."),
+            Some(Announcement::UnknownEvent(num)) => {
+                format!("{who} performed unknown system event {num}.")
+            }
+            Some(Announcement::Unknown(num)) => {
+                format!("{who} performed unknown action {num}.")
+            }
+            Some(Announcement::FullyUnsent) => format!("{who} unsent a message!"),
+            None => String::from("Unable to format announcement!"),
+        });
+    } else if message.is_shareplay() {
+        body.push_str("SharePlay Message Ended");
+    } else if let Some(text) = config.message_text(message) {
+        // Attachments are sent as their own parts
+        body.push_str(text.replace('\u{FFFC}', "").trim());
+    }
+    body.push('\n');
+
+    if let Some(transcription) = message.audio_transcription() {
+        body.push_str(&format!("\nTranscription: {transcription}\n"));
+    }
+
+    let expressive = match message.get_expressive() {
+        Expressive::Screen(effect) => Some(format!("{effect:?}")),
+        Expressive::Bubble(effect) => Some(format!("{effect:?}")),
+        Expressive::Unknown(effect) => Some(effect.to_string()),
+        Expressive::None => None,
+    };
+    if let Some(expressive) = expressive {
+        body.push_str(&format!("\nSent with {expressive}\n"));
+    }
+
+    // List the tapbacks placed on the message
+    let tapbacks = config.tapback_lines(message);
+    if !tapbacks.is_empty() {
+        body.push_str(&format!("\nTapbacks:\n{}\n", tapbacks.join("\n")));
+    }
+
+    Ok(body)
+}
+
+/// Build a MIME part for an attachment, copying it if requested
+fn format_attachment(config: &Config, attachment: &mut Attachment, message: &Message) -> String {
+    config
+        .options
+        .attachment_manager
+        .handle_attachment(message, attachment, config);
+    let (data, name, mime_type) = read_attachment(config, attachment);
+
+    match data {
+        Some(data) => format!(
+            "Content-Type: {mime_type}; name=\"{0}\"\nContent-Disposition: attachment; filename=\"{0}\"\nContent-Transfer-Encoding: base64\n\n{1}",
+            encode_word(&name).replace('"', "'"),
+            base64(&data)
+        ),
+        None => format!(
+            "Content-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\nAttachment does not exist: {name}\n"
+        ),
+    }
+}
+
+/// Read the data of an attachment, along with its filename and MIME type
+///
+/// If the attachment was copied, the copy is read, since it may have been converted to a different format.
+pub(super) fn read_attachment(
+    config: &Config,
+    attachment: &Attachment,
+) -> (Option<Vec<u8>>, String, String) {
+    if !config.includes_attachment(attachment) {
+        return (None, attachment.filename().to_string(), String::new());
+    }
+    match &attachment.copied_path {
+        Some(path) => (
+            read(path).ok(),
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| attachment.filename().to_string()),
+            media_type(path.extension().and_then(|ext| ext.to_str()))
+                .or(attachment.mime_type.as_deref())
+                .unwrap_or("application/octet-stream")
+                .to_string(),
+        ),
+        None => (
+            attachment
+                .as_bytes(
+                    &config.options.platform,
+                    &config.options.db_path,
+                    config.options.attachment_root.as_deref(),
+                )
+                .ok()
+                .flatten(),
+            attachment.filename().to_string(),
+            attachment
+                .mime_type
+                .as_deref()
+                .unwrap_or("application/octet-stream")
+                .to_string(),
+        ),
+    }
+}
+
+/// Get the display name and email address for a participant
+fn address(
+    config: &Config,
+    handle_id: Option<i32>,
+    is_from_me: bool,
+    message: &Message,
+) -> (String, String) {
+    if is_from_me {
+        let name = config.options.custom_name.as_deref().unwrap_or(ME);
+        let identifier = config
+            .my_handle(&message.destination_caller_id)
+            .unwrap_or("me");
+        return (name.to_string(), email_address(identifier));
+    }
+
+    let identifier = handle_id
+        .and_then(|id| config.participants.get(&id))
+        .map_or(UNKNOWN, |id| id.as_str());
+    (identifier.to_string(), email_address(identifier))
+}
+
+/// Build an email address from a handle, which may be a phone number
+fn email_address(identifier: &str) -> String {
+    if identifier.contains('@') {
+        return identifier.replace(char::is_whitespace, "");
+    }
+    let local: String = identifier
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || ADDRESS_CHARS.contains(*c))
+        .collect();
+    format!("{local}@{ADDRESS_DOMAIN}")
+}
+
+/// Format a mailbox for an address header, i.e. `"Name" <address>`
+fn mailbox(name: &str, address: &str) -> String {
+    if name == address {
+        return format!("<{address}>");
+    }
+    let name = encode_word(name);
+    if name.starts_with("=?") {
+        format!("{name} <{address}>")
+    } else {
+        format!("\"{}\" <{address}>", name.replace(['"', '\\'], ""))
+    }
+}
+
+/// Build a globally unique message ID from a message GUID
+fn message_id(guid: &str) -> String {
+    format!("<{guid}@{ADDRESS_DOMAIN}>")
+}
+
+/// Encode header text that is not plain ASCII as an RFC 2047 encoded word
+pub(super) fn encode_word(text: &str) -> String {
+    if text.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+        return text.to_string();
+    }
+    format!("=?UTF-8?B?{}?=", base64(text.as_bytes()).replace('\n', ""))
+}
+
+/// Guess the media type of a file from its extension
+pub(super) fn media_type(extension: Option<&str>) -> Option<&'static str> {
+    match extension?.to_lowercase().as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "svg" => Some("image/svg+xml"),
+        "webp" => Some("image/webp"),
+        "heic" => Some("image/heic"),
+        "mp4" => Some("video/mp4"),
+        "mov" => Some("video/quicktime"),
+        "mp3" => Some("audio/mpeg"),
+        "m4a" => Some("audio/mp4"),
+        "caf" => Some("audio/x-caf"),
+        _ => None,
+    }
+}
+
+/// Quote lines that would otherwise be read as the start of a new entry, following the mboxrd convention
+fn escape_from_lines(body: &str) -> String {
+    body.split_inclusive('\n')
+        .map(|line| {
+            if line.trim_start_matches('>').starts_with("From ") {
+                format!(">{line}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Encode bytes as base64, wrapping lines for use in a MIME part
+pub(super) fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() * 4 / 3 + data.len() / 57 + 4);
+    for (idx, chunk) in data.chunks(3).enumerate() {
+        if idx > 0 && idx % (BASE64_LINE_LENGTH / 4) == 0 {
+            out.push('\n');
+        }
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = (u32::from(bytes[0]) << 16) | (u32::from(bytes[1]) << 8) | u32::from(bytes[2]);
+        for position in 0..4 {
+            if position <= chunk.len() {
+                out.push(BASE64_ALPHABET[(group >> (18 - 6 * position) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env::set_var, path::PathBuf};
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        exporters::mbox::{base64, email_address, encode_word, escape_from_lines, mailbox},
+        Config, Exporter, Options, MBOX,
+    };
+    use imessage_database::{
+        tables::{messages::Message, table::get_connection},
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options() -> Options {
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path: PathBuf::from("/tmp"),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = MBOX::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_format_email_from_me() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = MBOX::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.guid = "guid".to_string();
+        message.text = Some("Hello world\nFrom me".to_string());
+        message.is_from_me = true;
+        message.destination_caller_id = Some("+15558675309".to_string());
+
+        let actual = exporter.format_entry(&message).unwrap();
+        let expected = "From +15558675309@imessage.invalid Tue May 17 17:29:42 2022\nFrom: \"Me\" <+15558675309@imessage.invalid>\nDate: Tue, 17 May 2022 17:29:42 -0700\nSubject: Orphaned messages\nMessage-ID: <guid@imessage.invalid>\nMIME-Version: 1.0\nContent-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\nHello world\n>From me\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_email_from_me_custom_identity() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let mut options = fake_options();
+        options.custom_name = Some("Jane Doe".to_string());
+        options.me_handle = Some("jane@example.com".to_string());
+        let config = fake_config(options);
+        let exporter = MBOX::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.guid = "guid".to_string();
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.destination_caller_id = Some("+15558675309".to_string());
+
+        let actual = exporter.format_entry(&message).unwrap();
+        let expected = "From jane@example.com Tue May 17 17:29:42 2022\nFrom: \"Jane Doe\" <jane@example.com>\nDate: Tue, 17 May 2022 17:29:42 -0700\nSubject: Orphaned messages\nMessage-ID: <guid@imessage.invalid>\nMIME-Version: 1.0\nContent-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\nHello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_email_from_them() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "test@example.com".to_string());
+        let exporter = MBOX::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.guid = "guid".to_string();
+        message.text = Some("Hello world".to_string());
+        message.handle_id = Some(999999);
+        message.thread_originator_guid = Some("parent".to_string());
+
+        let actual = exporter.format_entry(&message).unwrap();
+        let expected = "From test@example.com Tue May 17 17:29:42 2022\nFrom: <test@example.com>\nTo: \"Me\" <me@imessage.invalid>\nDate: Tue, 17 May 2022 17:29:42 -0700\nSubject: Orphaned messages\nMessage-ID: <guid@imessage.invalid>\nIn-Reply-To: <parent@imessage.invalid>\nReferences: <parent@imessage.invalid>\nMIME-Version: 1.0\nContent-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\nHello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_encode_base64() {
+        assert_eq!(base64(b""), "\n");
+        assert_eq!(base64(b"f"), "Zg==\n");
+        assert_eq!(base64(b"fo"), "Zm8=\n");
+        assert_eq!(base64(b"foo"), "Zm9v\n");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy\n");
+    }
+
+    #[test]
+    fn can_wrap_base64() {
+        let encoded = base64(&[0; 60]);
+        let lines: Vec<&str> = encoded.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 76);
+        assert_eq!(lines[1], "AAAA");
+    }
+
+    #[test]
+    fn can_encode_word() {
+        assert_eq!(encode_word("Weekend Plans"), "Weekend Plans");
+        assert_eq!(encode_word("Café"), "=?UTF-8?B?Q2Fmw6k=?=");
+    }
+
+    #[test]
+    fn can_build_email_address() {
+        assert_eq!(email_address("test@example.com"), "test@example.com");
+        assert_eq!(
+            email_address("+1 (555) 867 5309"),
+            "+15558675309@imessage.invalid"
+        );
+    }
+
+    #[test]
+    fn can_build_mailbox() {
+        assert_eq!(mailbox("a@b.c", "a@b.c"), "<a@b.c>");
+        assert_eq!(mailbox("Me", "me@b.c"), "\"Me\" <me@b.c>");
+        assert_eq!(mailbox("Zoë", "z@b.c"), "=?UTF-8?B?Wm/Dqw==?= <z@b.c>");
+    }
+
+    #[test]
+    fn can_escape_from_lines() {
+        assert_eq!(
+            escape_from_lines("From here\n>From there\nNot From\n"),
+            ">From here\n>>From there\nNot From\n"
+        );
+    }
+}
@@ -0,0 +1,421 @@
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        BTreeSet, HashMap,
+    },
+    fs::{read, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use log::info;
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::{
+        exporter::{Exporter, Writer},
+        html::{append_extension, templates::Templates, HTML},
+        mbox::{base64, encode_word, media_type},
+    },
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        messages::Message,
+        table::{Table, ORPHANED},
+    },
+};
+
+/// Boundary between the parts of each archive
+const BOUNDARY: &str = "----=_imessage-exporter_MHTML";
+/// URL of the folder each archived page claims to be saved in, which relative attachment paths are resolved against
+const BASE_URL: &str = "file:///imessage-export/";
+/// Attributes that may point to an attachment on disk
+const RESOURCE_ATTRIBUTES: [&str; 2] = ["src=\"", "href=\""];
+
+/// Exports each conversation to an MHTML archive
+///
+/// Pages are rendered the same way as HTML exports and stored as the first part of a
+/// `multipart/related` document, followed by every attachment the page references, so
+/// browsers can open the conversation and its images from a single `.mht` file.
+pub struct MHTML<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Renders messages the same way as the HTML export
+    ///
+    /// The renderer's files are the archives being written, keyed by conversation.
+    pub html: HTML<'a>,
+    /// Map of resolved chatroom file location to the attachment paths its page references
+    resources: HashMap<String, BTreeSet<String>>,
+}
+
+impl<'a> Exporter<'a> for MHTML<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let templates = Templates::new(
+            config.options.template_dir.as_deref(),
+            &config.options.theme,
+        )?;
+
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("mht");
+        let file =
+            File::create(&orphaned).map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+        let mut orphaned = BufWriter::new(file);
+        MHTML::write_headers(&templates, &mut orphaned, ORPHANED)?;
+
+        Ok(MHTML {
+            config,
+            html: HTML {
+                config,
+                files: HashMap::new(),
+                orphaned,
+                pages: None,
+                site: None,
+                templates,
+                icons: HashMap::new(),
+            },
+            resources: HashMap::new(),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as mhtml...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self
+                    .html
+                    .wrap_message(&msg, self.html.format_announcement(&msg))?;
+                HTML::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+            }
+            // Message replies and tapbacks are rendered in context, so no need to render them separately
+            else if !msg.is_tapback() {
+                let message = self
+                    .html
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                let message = self.html.wrap_message(&msg, message)?;
+                self.add_resources(&msg, &message);
+                HTML::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        info!("Writing MHTML attachments...");
+        for (page, buf) in self.html.files.iter_mut() {
+            HTML::write_footers(&self.html.templates, buf, page)?;
+            MHTML::write_resources(self.config, buf, self.resources.get(page))?;
+        }
+        HTML::write_footers(&self.html.templates, &mut self.html.orphaned, ORPHANED)?;
+        MHTML::write_resources(
+            self.config,
+            &mut self.html.orphaned,
+            self.resources.get(ORPHANED),
+        )?;
+
+        Ok(())
+    }
+
+    /// Create an archive for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                match self.html.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.options.export_path.clone();
+                        path.push(entry.key());
+                        append_extension(&mut path, "mht");
+
+                        let file = File::create(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+                        let mut buf = BufWriter::new(file);
+                        MHTML::write_headers(&self.html.templates, &mut buf, entry.key())?;
+
+                        Ok(entry.insert(buf))
+                    }
+                }
+            }
+            None => Ok(&mut self.html.orphaned),
+        }
+    }
+}
+
+impl<'a> MHTML<'a> {
+    /// Remember the attachments a rendered message points to, so they can be stored in its archive
+    fn add_resources(&mut self, message: &Message, content: &str) {
+        let page = match self.config.conversation(message) {
+            Some((chatroom, _)) => self.config.filename(chatroom),
+            None => ORPHANED.to_string(),
+        };
+        let resources = self.resources.entry(page).or_default();
+        for path in local_paths(content) {
+            resources.insert(path.to_string());
+        }
+    }
+
+    /// Write the headers of an archive and the start of the page it contains
+    fn write_headers(
+        templates: &Templates,
+        file: &mut BufWriter<File>,
+        title: &str,
+    ) -> Result<(), RuntimeError> {
+        HTML::write_to_file(
+            file,
+            &format!(
+                "From: <Saved by imessage-exporter>\nSubject: {}\nMIME-Version: 1.0\nContent-Type: multipart/related; type=\"text/html\"; boundary=\"{BOUNDARY}\"\n\n--{BOUNDARY}\nContent-Type: text/html; charset=utf-8\nContent-Transfer-Encoding: 8bit\nContent-Location: {BASE_URL}{}.html\n\n",
+                encode_word(title),
+                encode_url(title)
+            ),
+        )?;
+        HTML::write_headers(templates, file, title)
+    }
+
+    /// Write a part for each attachment the page references, then close the archive
+    fn write_resources(
+        config: &Config,
+        file: &mut BufWriter<File>,
+        resources: Option<&BTreeSet<String>>,
+    ) -> Result<(), RuntimeError> {
+        for resource in resources.into_iter().flatten() {
+            // Relative paths point to attachments copied to the export directory
+            let (path, location) = match resource.starts_with('/') {
+                true => (
+                    PathBuf::from(resource),
+                    format!("file://{}", encode_url(resource)),
+                ),
+                false => (
+                    config.options.export_path.join(resource),
+                    format!("{BASE_URL}{}", encode_url(resource)),
+                ),
+            };
+            if let Ok(data) = read(&path) {
+                HTML::write_to_file(
+                    file,
+                    &format!(
+                        "\n--{BOUNDARY}\nContent-Type: {}\nContent-Transfer-Encoding: base64\nContent-Location: {location}\n\n{}",
+                        resource_type(&path),
+                        base64(&data)
+                    ),
+                )?;
+            }
+        }
+        HTML::write_to_file(file, &format!("\n--{BOUNDARY}--\n"))
+    }
+}
+
+/// Find the paths to files on disk that a rendered message links to or embeds
+fn local_paths(content: &str) -> Vec<&str> {
+    let mut paths = vec![];
+    for attribute in RESOURCE_ATTRIBUTES {
+        for (idx, _) in content.match_indices(attribute) {
+            let value = &content[idx + attribute.len()..];
+            let value = &value[..value.find('"').unwrap_or(value.len())];
+            // Skip anchors and anything with a URL scheme, like `https:` or `data:`
+            let has_scheme = value
+                .split('/')
+                .next()
+                .is_some_and(|first| first.contains(':'));
+            if !value.is_empty() && !value.starts_with('#') && !has_scheme {
+                paths.push(value);
+            }
+        }
+    }
+    paths
+}
+
+/// Get the MIME type of an attachment from its extension
+fn resource_type(path: &Path) -> &'static str {
+    media_type(path.extension().and_then(|ext| ext.to_str())).unwrap_or("application/octet-stream")
+}
+
+/// Percent-encode the characters a browser would encode when resolving a path in a page to a URL
+fn encode_url(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b' ' | b'"' | b'#' | b'<' | b'>' | b'?' | b'`' | b'{' | b'}' => {
+                out.push_str(&format!("%{byte:02X}"));
+            }
+            0x21..=0x7E => out.push(byte as char),
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        exporters::mhtml::{encode_url, local_paths},
+        Config, Exporter, Options, MHTML,
+    };
+    use imessage_database::{
+        tables::table::get_connection,
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn fake_options() -> Options {
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path: PathBuf::from("/tmp"),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = MHTML::new(&config).unwrap();
+        assert_eq!(exporter.html.files.len(), 0);
+    }
+
+    #[test]
+    fn can_find_local_paths() {
+        let content = "<img src=\"attachments/1/2.jpg\" loading=\"lazy\"><a href=\"https://example.com\">link</a><audio controls src=\"/Users/me/Library/Messages/Attachments/a.caf\"><a href=\"#r-1\">reply</a>";
+        assert_eq!(
+            local_paths(content),
+            vec![
+                "attachments/1/2.jpg",
+                "/Users/me/Library/Messages/Attachments/a.caf"
+            ]
+        );
+    }
+
+    #[test]
+    fn can_skip_data_uris() {
+        assert!(local_paths("<img src=\"data:image/png;base64,Zm9v\">").is_empty());
+    }
+
+    #[test]
+    fn can_encode_url() {
+        assert_eq!(
+            encode_url("attachments/Group Chat #1/d (1).jpg"),
+            "attachments/Group%20Chat%20%231/d%20(1).jpg"
+        );
+        assert_eq!(encode_url("Zoë"), "Zo%C3%AB");
+    }
+}
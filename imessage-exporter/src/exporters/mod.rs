@@ -1,3 +1,19 @@
+pub mod attachments;
+pub mod day_one;
+pub mod epub;
 pub mod exporter;
 pub mod html;
+pub mod latex;
+pub mod maildir;
+pub mod markdown;
+pub mod matrix;
+pub mod mbox;
+pub mod mhtml;
+pub mod ndjson;
+pub mod obsidian;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod pdf;
+pub mod sqlite;
 pub mod txt;
+pub mod whatsapp;
@@ -0,0 +1,505 @@
+use std::{
+    fs::File,
+    io::{stdout, BufWriter, Write},
+};
+
+use log::info;
+
+use crate::app::{
+    error::RuntimeError, progress::build_progress_bar_export, runtime::Config,
+    sanitizers::sanitize_json,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{
+        expressives::Expressive,
+        variants::{Tapback, Variant},
+    },
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::Message,
+        table::Table,
+    },
+    util::dates::get_local_time,
+};
+
+/// Name of the file messages are exported to when not streaming to stdout
+const STREAM_FILENAME: &str = "messages.ndjson";
+
+/// Exports messages as newline-delimited JSON
+///
+/// Each message is written as a single JSON object on its own line as soon as it is read
+/// from the database, so nothing is held in memory and the output can be piped straight into
+/// tools like `jq`. Tapbacks and sticker reactions are written as their own objects that
+/// reference the message they react to.
+pub struct NDJSON<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Where each line is written, either stdout or a file in the export directory
+    output: Box<dyn Write>,
+}
+
+impl<'a> NDJSON<'a> {
+    /// Open the output stream
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        // stdout is already line buffered, so each message is emitted as soon as it is written
+        let output: Box<dyn Write> = if config.options.writes_to_stdout() {
+            Box::new(stdout())
+        } else {
+            let mut path = config.options.export_path.clone();
+            path.push(STREAM_FILENAME);
+            Box::new(BufWriter::new(
+                File::create(path).map_err(RuntimeError::DiskError)?,
+            ))
+        };
+
+        Ok(NDJSON { config, output })
+    }
+
+    /// Write every message to the output stream as it is read
+    pub fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        if self.config.options.writes_to_stdout() {
+            info!("Exporting to stdout as ndjson...");
+        } else {
+            info!(
+                "Exporting to {} as ndjson...",
+                self.config.options.export_path.display()
+            );
+        }
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            let line = self.format_message(&msg)?;
+            writeln!(self.output, "{line}").map_err(RuntimeError::DiskError)?;
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        self.output.flush().map_err(RuntimeError::DiskError)
+    }
+
+    /// Build the JSON object for a single message
+    fn format_message(&self, message: &Message) -> Result<String, RuntimeError> {
+        let kind = match message.variant() {
+            Variant::Tapback(..) => "tapback",
+            Variant::Sticker(_) if message.is_tapback() => "sticker",
+            _ if message.is_announcement() => "announcement",
+            Variant::SharePlay => "shareplay",
+            _ => "message",
+        };
+        let sender_id = match message.is_from_me() {
+            true => None,
+            false => message.handle_id,
+        };
+        let conversation = self
+            .config
+            .conversation(message)
+            .map(|(chatroom, _)| self.config.filename(chatroom));
+        let expressive = match message.get_expressive() {
+            Expressive::Screen(effect) => Some(format!("{effect:?}")),
+            Expressive::Bubble(effect) => Some(format!("{effect:?}")),
+            Expressive::Unknown(effect) => Some(effect.to_string()),
+            Expressive::None => None,
+        };
+
+        let fields = [
+            ("id", message.rowid.to_string()),
+            ("guid", string(&message.guid)),
+            ("kind", string(kind)),
+            ("chat_id", number(message.chat_id.or(message.deleted_from))),
+            ("conversation", optional(conversation.as_deref())),
+            ("sender_id", number(sender_id)),
+            (
+                "sender",
+                string(self.config.who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )),
+            ),
+            ("is_from_me", message.is_from_me().to_string()),
+            ("service", optional(message.service.as_deref())),
+            ("date", self.timestamp(message.date)),
+            ("date_delivered", self.timestamp(message.date_delivered)),
+            ("date_read", self.timestamp(message.date_read)),
+            ("date_edited", self.timestamp(message.date_edited)),
+            ("text", optional(message.text.as_deref())),
+            ("subject", optional(message.subject.as_deref())),
+            ("app", optional(message.balloon_bundle_id.as_deref())),
+            ("expressive", optional(expressive.as_deref())),
+            (
+                "reply_to",
+                optional(message.thread_originator_guid.as_deref()),
+            ),
+            ("reaction", self.format_reaction(message)),
+            ("is_deleted", message.is_deleted().to_string()),
+            ("attachments", self.format_attachments(message)?),
+        ];
+
+        Ok(object(&fields))
+    }
+
+    /// Build the JSON object describing what a tapback or sticker reacts to, or `null`
+    fn format_reaction(&self, message: &Message) -> String {
+        if !message.is_tapback() {
+            return String::from("null");
+        }
+        let Some((part, target)) = message.clean_associated_guid() else {
+            return String::from("null");
+        };
+
+        let (kind, emoji, added) = match message.variant() {
+            Variant::Tapback(_, added, tapback) => {
+                let (kind, emoji) = match tapback {
+                    Tapback::Loved => ("loved", None),
+                    Tapback::Liked => ("liked", None),
+                    Tapback::Disliked => ("disliked", None),
+                    Tapback::Laughed => ("laughed", None),
+                    Tapback::Emphasized => ("emphasized", None),
+                    Tapback::Questioned => ("questioned", None),
+                    Tapback::Emoji(emoji) => ("emoji", emoji),
+                };
+                (kind, emoji, added)
+            }
+            _ => ("sticker", None, true),
+        };
+
+        object(&[
+            ("target", string(target)),
+            ("part", part.to_string()),
+            ("kind", string(kind)),
+            ("emoji", optional(emoji)),
+            ("added", added.to_string()),
+        ])
+    }
+
+    /// Build the JSON array of a message's attachments, copying them if requested
+    fn format_attachments(&self, message: &Message) -> Result<String, RuntimeError> {
+        if !message.has_attachments() {
+            return Ok(String::from("[]"));
+        }
+
+        let mut attachments = Attachment::from_message(&self.config.db, message)
+            .map_err(RuntimeError::DatabaseError)?;
+        let objects: Vec<String> = attachments
+            .iter_mut()
+            .map(|attachment| {
+                let path = self
+                    .config
+                    .options
+                    .attachment_manager
+                    .handle_attachment(message, attachment, self.config)
+                    .map(|_| self.config.message_attachment_path(attachment));
+                let transcription = match attachment.mime_type() {
+                    MediaType::Audio(_) => message.audio_transcription(),
+                    _ => None,
+                };
+                object(&[
+                    ("name", optional(attachment.transfer_name.as_deref())),
+                    ("mime_type", optional(attachment.mime_type.as_deref())),
+                    ("size", attachment.total_bytes.to_string()),
+                    ("is_sticker", attachment.is_sticker.to_string()),
+                    ("path", optional(path.as_deref())),
+                    ("transcription", optional(transcription)),
+                ])
+            })
+            .collect();
+
+        Ok(format!("[{}]", objects.join(",")))
+    }
+
+    /// Format a timestamp from the source database as a JSON string, or `null` if the event never happened
+    fn timestamp(&self, stamp: i64) -> String {
+        if stamp == 0 {
+            return String::from("null");
+        }
+        match get_local_time(&stamp, &self.config.offset) {
+            Ok(date) => string(&date.naive_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+            Err(_) => String::from("null"),
+        }
+    }
+}
+
+/// Join already-encoded values into a JSON object
+pub(super) fn object(fields: &[(&str, String)]) -> String {
+    let members: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("\"{key}\":{value}"))
+        .collect();
+    format!("{{{}}}", members.join(","))
+}
+
+/// Encode a JSON string
+pub(super) fn string(value: &str) -> String {
+    format!("\"{}\"", sanitize_json(value))
+}
+
+/// Encode a JSON string, or `null`
+pub(super) fn optional(value: Option<&str>) -> String {
+    value.map_or_else(|| String::from("null"), string)
+}
+
+/// Encode a JSON number, or `null`
+pub(super) fn number(value: Option<i32>) -> String {
+    value.map_or_else(|| String::from("null"), |n| n.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env::set_var, fs::create_dir_all, path::PathBuf};
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        Config, Options, NDJSON,
+    };
+    use imessage_database::{
+        tables::{messages::Message, table::get_connection},
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options() -> Options {
+        let export_path = PathBuf::from("/tmp/ndjson");
+        create_dir_all(&export_path).unwrap();
+
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path,
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = NDJSON::new(&config);
+        assert!(exporter.is_ok());
+    }
+
+    #[test]
+    fn can_format_message_from_me() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = NDJSON::new(&config).unwrap();
+
+        let mut message = blank();
+        message.rowid = 1;
+        message.guid = "guid".to_string();
+        message.text = Some("Hello \"world\"\nbye".to_string());
+        message.is_from_me = true;
+        message.date = 674526582885055488;
+
+        let expected = concat!(
+            "{\"id\":1,\"guid\":\"guid\",\"kind\":\"message\",\"chat_id\":null,",
+            "\"conversation\":null,\"sender_id\":null,\"sender\":\"Me\",\"is_from_me\":true,",
+            "\"service\":\"iMessage\",\"date\":\"2022-05-18T00:29:42Z\",\"date_delivered\":null,",
+            "\"date_read\":null,\"date_edited\":null,\"text\":\"Hello \\\"world\\\"\\nbye\",",
+            "\"subject\":null,\"app\":null,\"expressive\":null,\"reply_to\":null,",
+            "\"reaction\":null,\"is_deleted\":false,\"attachments\":[]}"
+        );
+        assert_eq!(exporter.format_message(&message).unwrap(), expected);
+    }
+
+    #[test]
+    fn can_format_message_from_them() {
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = NDJSON::new(&config).unwrap();
+
+        let mut message = blank();
+        message.handle_id = Some(999999);
+        message.thread_originator_guid = Some("original".to_string());
+
+        let actual = exporter.format_message(&message).unwrap();
+        assert!(actual.contains("\"sender_id\":999999,\"sender\":\"Sample Contact\""));
+        assert!(actual.contains("\"reply_to\":\"original\""));
+        assert!(!actual.contains('\n'));
+    }
+
+    #[test]
+    fn can_format_tapback() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = NDJSON::new(&config).unwrap();
+
+        let mut message = blank();
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid =
+            Some("p:1/FD2D6A61-4A8F-4D5E-9DB4-7C2E4B1E5F7A".to_string());
+
+        let actual = exporter.format_message(&message).unwrap();
+        assert!(actual.contains("\"kind\":\"tapback\""));
+        assert!(actual.contains(concat!(
+            "\"reaction\":{\"target\":\"FD2D6A61-4A8F-4D5E-9DB4-7C2E4B1E5F7A\",",
+            "\"part\":1,\"kind\":\"loved\",\"emoji\":null,\"added\":true}"
+        )));
+    }
+
+    #[test]
+    fn can_format_tapback_removed() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = NDJSON::new(&config).unwrap();
+
+        let mut message = blank();
+        message.associated_message_type = Some(3003);
+        message.associated_message_guid = Some("FD2D6A61-4A8F-4D5E-9DB4-7C2E4B1E5F7A".to_string());
+
+        let actual = exporter.format_message(&message).unwrap();
+        assert!(actual.contains("\"part\":0,\"kind\":\"laughed\",\"emoji\":null,\"added\":false"));
+    }
+}
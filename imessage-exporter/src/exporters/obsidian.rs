@@ -0,0 +1,431 @@
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        BTreeMap, BTreeSet, HashMap,
+    },
+    fs::{create_dir_all, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use log::info;
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::{
+        exporter::{Exporter, Writer},
+        html::append_extension,
+        markdown::Markdown,
+    },
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        messages::Message,
+        table::{Table, ORPHANED},
+    },
+};
+
+/// Folder the notes that list each contact's days are written to
+const CONTACTS_FOLDER: &str = "Contacts";
+/// Name of the day for messages whose date cannot be read
+const UNKNOWN_DAY: &str = "Unknown";
+
+pub struct Obsidian<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Renders messages the same way as the Markdown export
+    ///
+    /// The renderer's files are the notes for the day currently being exported, keyed by their
+    /// path in the vault. Messages are sorted by date, so notes are closed when the day changes.
+    pub markdown: Markdown<'a>,
+    /// The day currently being exported, as `YYYY-MM-DD`
+    day: Option<String>,
+    /// Map of day to the contacts with messages that day
+    days: BTreeMap<String, BTreeSet<String>>,
+    /// Map of contact to the days with messages to or from them
+    contacts: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl<'a> Exporter<'a> for Obsidian<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("md");
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        Ok(Obsidian {
+            config,
+            markdown: Markdown {
+                config,
+                files: HashMap::new(),
+                orphaned: BufWriter::new(file),
+            },
+            day: None,
+            days: BTreeMap::new(),
+            contacts: BTreeMap::new(),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as an Obsidian vault...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self.markdown.format_announcement(&msg);
+                Markdown::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+            }
+            // Message replies and tapbacks are rendered in context, so no need to render them separately
+            else if !msg.is_tapback() {
+                let message = self
+                    .markdown
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                Markdown::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        info!("Writing daily and contact notes...");
+        self.close_notes()?;
+        self.markdown
+            .orphaned
+            .flush()
+            .map_err(RuntimeError::DiskError)?;
+        self.write_index_notes()
+    }
+
+    /// Create a note for the given chat and day, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let contact = sanitize_wikilink(&self.config.filename(chatroom));
+                let day = self.day(message);
+
+                // Messages are sorted by date, so the previous day's notes are complete
+                if self.day.as_ref() != Some(&day) {
+                    self.close_notes()?;
+                    self.day = Some(day.clone());
+                }
+
+                self.days
+                    .entry(day.clone())
+                    .or_default()
+                    .insert(contact.clone());
+                self.contacts
+                    .entry(contact.clone())
+                    .or_default()
+                    .insert(day.clone());
+
+                return match self.markdown.files.entry(note_link(&day, &contact)) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let path = Obsidian::note_path(self.config, entry.key())?;
+
+                        // If the note already exists, don't write the links again
+                        // This can happen if multiple chats use the same group name
+                        let file_exists = path.exists();
+
+                        let file = File::options()
+                            .append(true)
+                            .create(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        let mut buf = BufWriter::new(file);
+
+                        // Link the note to its contact and day so they show up in the graph
+                        if !file_exists {
+                            let links = format!(
+                                "{} · {}\n\n",
+                                wikilink(&format!("{CONTACTS_FOLDER}/{contact}"), &contact),
+                                wikilink(&note_link(&day, &day), &day),
+                            );
+                            Markdown::write_to_file(&mut buf, &links)?;
+                        }
+
+                        Ok(entry.insert(buf))
+                    }
+                };
+            }
+            None => Ok(&mut self.markdown.orphaned),
+        }
+    }
+}
+
+impl<'a> Obsidian<'a> {
+    /// Get the day a message was sent, as `YYYY-MM-DD`
+    fn day(&self, message: &Message) -> String {
+        match message.date(&self.config.offset) {
+            Ok(date) => date.format("%Y-%m-%d").to_string(),
+            Err(_) => String::from(UNKNOWN_DAY),
+        }
+    }
+
+    /// Build the path to a note in the vault from its link, creating its folder
+    fn note_path(config: &Config, link: &str) -> Result<PathBuf, RuntimeError> {
+        let mut path = config.options.export_path.join(link);
+        if let Some(folder) = path.parent() {
+            create_dir_all(folder)
+                .map_err(|err| RuntimeError::CreateError(err, folder.to_path_buf()))?;
+        }
+        append_extension(&mut path, "md");
+        Ok(path)
+    }
+
+    /// Flush and close the notes for the day that was being exported
+    fn close_notes(&mut self) -> Result<(), RuntimeError> {
+        for buf in self.markdown.files.values_mut() {
+            buf.flush().map_err(RuntimeError::DiskError)?;
+        }
+        self.markdown.files.clear();
+        Ok(())
+    }
+
+    /// Write a note for each day that links to its contacts, and for each contact that links to its days
+    fn write_index_notes(&self) -> Result<(), RuntimeError> {
+        for (day, contacts) in &self.days {
+            let mut note = format!("# {day}\n\n");
+            for contact in contacts {
+                note.push_str(&format!(
+                    "- {}\n",
+                    wikilink(&note_link(day, contact), contact)
+                ));
+            }
+            Obsidian::write_note(self.config, &note_link(day, day), &note)?;
+        }
+
+        for (contact, days) in &self.contacts {
+            let mut note = format!("# {contact}\n\n");
+            for day in days {
+                note.push_str(&format!("- {}\n", wikilink(&note_link(day, contact), day)));
+            }
+            Obsidian::write_note(self.config, &format!("{CONTACTS_FOLDER}/{contact}"), &note)?;
+        }
+        Ok(())
+    }
+
+    /// Write the full contents of a note, replacing any existing note
+    fn write_note(config: &Config, link: &str, note: &str) -> Result<(), RuntimeError> {
+        let path = Obsidian::note_path(config, link)?;
+        std::fs::write(&path, note).map_err(|err| RuntimeError::CreateError(err, path))
+    }
+}
+
+/// Get the link to a note in a day's folder, i.e. `2022/05/17/Contact`
+fn note_link(day: &str, name: &str) -> String {
+    format!("{}/{name}", day.replace('-', "/"))
+}
+
+/// Build a wikilink to a note that displays `alias`
+fn wikilink(link: &str, alias: &str) -> String {
+    format!("[[{link}|{alias}]]")
+}
+
+/// Replace the characters that end a wikilink early, or that Obsidian does not allow in note names
+fn sanitize_wikilink(name: &str) -> String {
+    name.replace(['#', '^', '[', ']', '|'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs::read_to_string, path::PathBuf};
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        exporters::obsidian::{note_link, sanitize_wikilink, wikilink},
+        Config, Exporter, Obsidian, Options,
+    };
+    use imessage_database::{
+        tables::table::get_connection,
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    fn fake_options(export_path: &str) -> Options {
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path: PathBuf::from(export_path),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options("/tmp");
+        let config = fake_config(options);
+        let exporter = Obsidian::new(&config).unwrap();
+        assert_eq!(exporter.markdown.files.len(), 0);
+    }
+
+    #[test]
+    fn can_build_links() {
+        assert_eq!(note_link("2022-05-17", "Contact"), "2022/05/17/Contact");
+        assert_eq!(
+            wikilink("2022/05/17/Contact", "Contact"),
+            "[[2022/05/17/Contact|Contact]]"
+        );
+    }
+
+    #[test]
+    fn can_sanitize_wikilink() {
+        assert_eq!(
+            sanitize_wikilink("Book club #2 [old]"),
+            "Book club _2 _old_"
+        );
+    }
+
+    #[test]
+    fn can_write_index_notes() {
+        let path = "/tmp/obsidian_can_write_index_notes";
+        let _ = std::fs::remove_dir_all(path);
+        std::fs::create_dir_all(path).unwrap();
+
+        let options = fake_options(path);
+        let config = fake_config(options);
+        let mut exporter = Obsidian::new(&config).unwrap();
+        for (day, contact) in [("2022-05-17", "Person"), ("2022-05-18", "Person")] {
+            exporter
+                .days
+                .entry(day.to_string())
+                .or_default()
+                .insert(contact.to_string());
+            exporter
+                .contacts
+                .entry(contact.to_string())
+                .or_default()
+                .insert(day.to_string());
+        }
+        exporter.write_index_notes().unwrap();
+
+        assert_eq!(
+            read_to_string(format!("{path}/2022/05/17/2022-05-17.md")).unwrap(),
+            "# 2022-05-17\n\n- [[2022/05/17/Person|Person]]\n"
+        );
+        assert_eq!(
+            read_to_string(format!("{path}/Contacts/Person.md")).unwrap(),
+            "# Person\n\n- [[2022/05/17/Person|2022-05-17]]\n- [[2022/05/18/Person|2022-05-18]]\n"
+        );
+    }
+}
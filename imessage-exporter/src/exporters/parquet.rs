@@ -0,0 +1,602 @@
+use std::{fs::File, sync::Arc};
+
+use log::info;
+
+use parquet::{
+    basic::Compression,
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::parser::parse_message_type,
+};
+
+use crate::app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{
+        expressives::Expressive,
+        variants::{Tapback, Variant},
+    },
+    tables::{messages::Message, table::Table},
+    util::dates::get_local_time,
+};
+
+/// Name of the file messages are exported to
+const PARQUET_FILENAME: &str = "messages.parquet";
+/// Number of messages buffered before they are written as a row group
+const ROW_GROUP_SIZE: usize = 65_536;
+/// Columns in the exported file, in the order [`Parquet::row()`] builds them
+///
+/// IDs match the `ROWID`s in the source iMessage database. Tapbacks and stickers placed on
+/// other messages are stored as their own rows, with the `reaction_*` columns describing
+/// the message they react to.
+const SCHEMA: &str = "
+message message {
+    REQUIRED INT32 id;
+    REQUIRED BYTE_ARRAY guid (STRING);
+    REQUIRED BYTE_ARRAY kind (STRING);
+    OPTIONAL INT32 chat_id;
+    OPTIONAL BYTE_ARRAY conversation (STRING);
+    OPTIONAL INT32 sender_id;
+    REQUIRED BYTE_ARRAY sender (STRING);
+    REQUIRED BOOLEAN is_from_me;
+    OPTIONAL BYTE_ARRAY service (STRING);
+    OPTIONAL INT64 date (TIMESTAMP(MICROS, true));
+    OPTIONAL INT64 date_delivered (TIMESTAMP(MICROS, true));
+    OPTIONAL INT64 date_read (TIMESTAMP(MICROS, true));
+    OPTIONAL INT64 date_edited (TIMESTAMP(MICROS, true));
+    OPTIONAL BYTE_ARRAY text (STRING);
+    OPTIONAL BYTE_ARRAY subject (STRING);
+    OPTIONAL BYTE_ARRAY app (STRING);
+    OPTIONAL BYTE_ARRAY expressive (STRING);
+    OPTIONAL BYTE_ARRAY reply_to (STRING);
+    OPTIONAL BYTE_ARRAY reaction_target (STRING);
+    OPTIONAL INT32 reaction_part;
+    OPTIONAL BYTE_ARRAY reaction_kind (STRING);
+    OPTIONAL BYTE_ARRAY reaction_emoji (STRING);
+    OPTIONAL BOOLEAN reaction_added;
+    REQUIRED BOOLEAN is_deleted;
+    REQUIRED INT32 attachments;
+    OPTIONAL BYTE_ARRAY transcription (STRING);
+}
+";
+
+/// A single cell in the exported table, `None` when the value is null
+#[derive(Debug, PartialEq)]
+enum Value {
+    Int32(Option<i32>),
+    Int64(Option<i64>),
+    Boolean(Option<bool>),
+    Text(Option<String>),
+}
+
+impl Value {
+    fn is_some(&self) -> bool {
+        match self {
+            Value::Int32(value) => value.is_some(),
+            Value::Int64(value) => value.is_some(),
+            Value::Boolean(value) => value.is_some(),
+            Value::Text(value) => value.is_some(),
+        }
+    }
+
+    fn as_i32(&self) -> Option<i32> {
+        match self {
+            Value::Int32(value) => *value,
+            _ => None,
+        }
+    }
+
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int64(value) => *value,
+            _ => None,
+        }
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(value) => *value,
+            _ => None,
+        }
+    }
+
+    fn as_bytes(&self) -> Option<ByteArray> {
+        match self {
+            Value::Text(value) => value.as_deref().map(ByteArray::from),
+            _ => None,
+        }
+    }
+}
+
+/// Exports messages to a columnar Apache Parquet file
+///
+/// Like the SQLite exporter, this writes every conversation to a single file instead of a
+/// file per conversation, so it can be queried with tools like DuckDB or Spark. Messages
+/// are buffered and written in row groups of [`ROW_GROUP_SIZE`] rows.
+pub struct Parquet<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Writer for the exported file
+    writer: SerializedFileWriter<File>,
+    /// Values for each column in the row group being built
+    columns: Vec<Vec<Value>>,
+}
+
+impl<'a> Parquet<'a> {
+    /// Create the exported file
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut path = config.options.export_path.clone();
+        path.push(PARQUET_FILENAME);
+
+        let schema = Arc::new(parse_message_type(SCHEMA).map_err(RuntimeError::ParquetError)?);
+        let properties = Arc::new(
+            WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build(),
+        );
+        let file = File::create(path).map_err(RuntimeError::DiskError)?;
+        let writer = SerializedFileWriter::new(file, schema, properties)
+            .map_err(RuntimeError::ParquetError)?;
+
+        Ok(Parquet {
+            config,
+            writer,
+            columns: vec![],
+        })
+    }
+
+    /// Write every message to the file, one row group at a time
+    pub fn iter_messages(mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as parquet...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            self.push_row(self.row(&msg))?;
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        self.write_row_group()?;
+        self.writer
+            .close()
+            .map_err(RuntimeError::ParquetError)
+            .map(|_| ())
+    }
+
+    /// Build the cells for a single message, in schema order
+    fn row(&self, message: &Message) -> Vec<Value> {
+        let kind = match message.variant() {
+            Variant::Tapback(..) => "tapback",
+            Variant::Sticker(_) if message.is_tapback() => "sticker",
+            _ if message.is_announcement() => "announcement",
+            Variant::SharePlay => "shareplay",
+            _ => "message",
+        };
+        let sender_id = match message.is_from_me() {
+            true => None,
+            false => message.handle_id,
+        };
+        let conversation = self
+            .config
+            .conversation(message)
+            .map(|(chatroom, _)| self.config.filename(chatroom));
+        let expressive = match message.get_expressive() {
+            Expressive::Screen(effect) => Some(format!("{effect:?}")),
+            Expressive::Bubble(effect) => Some(format!("{effect:?}")),
+            Expressive::Unknown(effect) => Some(effect.to_string()),
+            Expressive::None => None,
+        };
+
+        // Tapbacks and stickers placed on other messages describe the message they react to
+        let mut reaction = (None, None, None, None, None);
+        if message.is_tapback() {
+            if let Some((part, target)) = message.clean_associated_guid() {
+                let (kind, emoji, added) = match message.variant() {
+                    Variant::Tapback(_, added, tapback) => {
+                        let (kind, emoji) = match tapback {
+                            Tapback::Loved => ("loved", None),
+                            Tapback::Liked => ("liked", None),
+                            Tapback::Disliked => ("disliked", None),
+                            Tapback::Laughed => ("laughed", None),
+                            Tapback::Emphasized => ("emphasized", None),
+                            Tapback::Questioned => ("questioned", None),
+                            Tapback::Emoji(emoji) => ("emoji", emoji),
+                        };
+                        (kind, emoji, added)
+                    }
+                    _ => ("sticker", None, true),
+                };
+                reaction = (
+                    Some(target.to_string()),
+                    i32::try_from(part).ok(),
+                    Some(kind.to_string()),
+                    emoji.map(str::to_string),
+                    Some(added),
+                );
+            }
+        }
+        let (reaction_target, reaction_part, reaction_kind, reaction_emoji, reaction_added) =
+            reaction;
+
+        vec![
+            Value::Int32(Some(message.rowid)),
+            Value::Text(Some(message.guid.clone())),
+            Value::Text(Some(kind.to_string())),
+            Value::Int32(message.chat_id.or(message.deleted_from)),
+            Value::Text(conversation),
+            Value::Int32(sender_id),
+            Value::Text(Some(
+                self.config
+                    .who(
+                        message.handle_id,
+                        message.is_from_me(),
+                        &message.destination_caller_id,
+                    )
+                    .to_string(),
+            )),
+            Value::Boolean(Some(message.is_from_me())),
+            Value::Text(message.service.clone()),
+            Value::Int64(self.timestamp(message.date)),
+            Value::Int64(self.timestamp(message.date_delivered)),
+            Value::Int64(self.timestamp(message.date_read)),
+            Value::Int64(self.timestamp(message.date_edited)),
+            Value::Text(message.text.clone()),
+            Value::Text(message.subject.clone()),
+            Value::Text(message.balloon_bundle_id.clone()),
+            Value::Text(expressive),
+            Value::Text(message.thread_originator_guid.clone()),
+            Value::Text(reaction_target),
+            Value::Int32(reaction_part),
+            Value::Text(reaction_kind),
+            Value::Text(reaction_emoji),
+            Value::Boolean(reaction_added),
+            Value::Boolean(Some(message.is_deleted())),
+            Value::Int32(Some(message.num_attachments)),
+            Value::Text(message.audio_transcription().map(str::to_string)),
+        ]
+    }
+
+    /// Buffer a row, writing the row group once it is full
+    fn push_row(&mut self, row: Vec<Value>) -> Result<(), RuntimeError> {
+        if self.columns.is_empty() {
+            self.columns = row.iter().map(|_| vec![]).collect();
+        }
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.push(value);
+        }
+
+        if self.columns[0].len() >= ROW_GROUP_SIZE {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered rows to the file as a row group
+    fn write_row_group(&mut self) -> Result<(), RuntimeError> {
+        if self.columns.first().map_or(true, Vec::is_empty) {
+            return Ok(());
+        }
+
+        let mut row_group = self
+            .writer
+            .next_row_group()
+            .map_err(RuntimeError::ParquetError)?;
+        let mut columns = self.columns.iter_mut();
+
+        while let Some(mut column_writer) = row_group
+            .next_column()
+            .map_err(RuntimeError::ParquetError)?
+        {
+            let Some(cells) = columns.next() else {
+                break;
+            };
+            let levels: Vec<i16> = cells.iter().map(|cell| cell.is_some() as i16).collect();
+
+            let written = match column_writer.untyped() {
+                ColumnWriter::Int32ColumnWriter(writer) => {
+                    let values: Vec<i32> = cells.iter().filter_map(Value::as_i32).collect();
+                    writer.write_batch(&values, Some(&levels), None)
+                }
+                ColumnWriter::Int64ColumnWriter(writer) => {
+                    let values: Vec<i64> = cells.iter().filter_map(Value::as_i64).collect();
+                    writer.write_batch(&values, Some(&levels), None)
+                }
+                ColumnWriter::BoolColumnWriter(writer) => {
+                    let values: Vec<bool> = cells.iter().filter_map(Value::as_bool).collect();
+                    writer.write_batch(&values, Some(&levels), None)
+                }
+                ColumnWriter::ByteArrayColumnWriter(writer) => {
+                    let values: Vec<ByteArray> = cells.iter().filter_map(Value::as_bytes).collect();
+                    writer.write_batch(&values, Some(&levels), None)
+                }
+                _ => unreachable!("the schema only uses INT32, INT64, BOOLEAN, and BYTE_ARRAY"),
+            };
+            written.map_err(RuntimeError::ParquetError)?;
+            column_writer.close().map_err(RuntimeError::ParquetError)?;
+            cells.clear();
+        }
+
+        row_group.close().map_err(RuntimeError::ParquetError)?;
+        Ok(())
+    }
+
+    /// Convert a timestamp from the source database to microseconds since the Unix epoch,
+    /// or `None` if the event never happened
+    fn timestamp(&self, stamp: i64) -> Option<i64> {
+        if stamp == 0 {
+            return None;
+        }
+        get_local_time(&stamp, &self.config.offset)
+            .ok()
+            .map(|date| date.timestamp_micros())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs::{create_dir_all, File},
+        path::PathBuf,
+    };
+
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    use super::Value;
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        Config, Options, Parquet,
+    };
+    use imessage_database::{
+        tables::{messages::Message, table::get_connection},
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options(export_path: &str) -> Options {
+        // Each test gets its own file
+        let export_path = PathBuf::from(export_path);
+        create_dir_all(&export_path).unwrap();
+
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path,
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options("/tmp/parquet_can_create");
+        let config = fake_config(options);
+        let exporter = Parquet::new(&config);
+        assert!(exporter.is_ok());
+    }
+
+    #[test]
+    fn can_format_timestamp() {
+        let options = fake_options("/tmp/parquet_can_format_timestamp");
+        let config = fake_config(options);
+        let exporter = Parquet::new(&config).unwrap();
+
+        assert_eq!(exporter.timestamp(0), None);
+        assert_eq!(
+            exporter.timestamp(674526582885055488),
+            Some(1652833782000000)
+        );
+    }
+
+    #[test]
+    fn can_build_row_tapback() {
+        let options = fake_options("/tmp/parquet_can_build_row_tapback");
+        let config = fake_config(options);
+        let exporter = Parquet::new(&config).unwrap();
+
+        let mut message = blank();
+        message.associated_message_type = Some(2001);
+        message.associated_message_guid =
+            Some("p:2/FD2D6A61-4A8F-4D5E-9DB4-7C2E4B1E5F7A".to_string());
+
+        let row = exporter.row(&message);
+        assert_eq!(row[2], Value::Text(Some("tapback".to_string())));
+        assert_eq!(
+            row[18..23],
+            [
+                Value::Text(Some("FD2D6A61-4A8F-4D5E-9DB4-7C2E4B1E5F7A".to_string())),
+                Value::Int32(Some(2)),
+                Value::Text(Some("liked".to_string())),
+                Value::Text(None),
+                Value::Boolean(Some(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_write_rows() {
+        let options = fake_options("/tmp/parquet_can_write_rows");
+        let config = fake_config(options);
+        let mut exporter = Parquet::new(&config).unwrap();
+
+        let mut message = blank();
+        message.rowid = 1;
+        message.text = Some("Hello world".to_string());
+        message.date = 674526582885055488;
+        exporter.push_row(exporter.row(&message)).unwrap();
+        message.rowid = 2;
+        message.text = None;
+        exporter.push_row(exporter.row(&message)).unwrap();
+        exporter.write_row_group().unwrap();
+        exporter.writer.close().unwrap();
+
+        let file = File::open("/tmp/parquet_can_write_rows/messages.parquet").unwrap();
+        let reader = SerializedFileReader::new(file).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 2);
+        assert_eq!(metadata.file_metadata().schema_descr().num_columns(), 26);
+
+        let texts: Vec<String> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                row.unwrap()
+                    .get_column_iter()
+                    .nth(13)
+                    .unwrap()
+                    .1
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(texts, vec!["\"Hello world\"", "null"]);
+    }
+}
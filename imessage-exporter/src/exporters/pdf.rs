@@ -0,0 +1,1471 @@
+mod document;
+
+use std::{
+    borrow::Cow,
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::{remove_file, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use log::info;
+
+use crate::{
+    app::{
+        attachment_manager::AttachmentManager, error::RuntimeError,
+        progress::build_progress_bar_export, runtime::Config,
+    },
+    exporters::{
+        exporter::{BalloonFormatter, Exporter, Writer},
+        html::append_extension,
+        pdf::document::Document,
+    },
+};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        app_store::AppStoreMessage,
+        collaboration::CollaborationMessage,
+        edited::{EditStatus, EditedMessage},
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        facetime::FaceTimeMessage,
+        handwriting::HandwrittenMessage,
+        icloud_photos::{ICloudPhotosKind, ICloudPhotosMessage},
+        music::MusicMessage,
+        placemark::PlacemarkMessage,
+        text_effects::TextEffect,
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, CustomBalloon, URLOverride, Variant},
+    },
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::{models::BubbleComponent, Message},
+        table::{Table, FITNESS_RECEIVER, ME, ORPHANED, YOU},
+    },
+    util::{
+        dates::{format, get_local_time, readable_diff, TIMESTAMP_FACTOR},
+        plist::parse_plist,
+    },
+};
+
+/// Marks a staged line that should be drawn as an image instead of text
+///
+/// This is the same object replacement character iMessage uses to denote attachment positions in message text.
+const IMAGE_MARKER: char = '\u{FFFC}';
+/// Extension for the plain text files messages are staged in before they are laid out
+const STAGING_EXTENSION: &str = "pdf.part";
+
+pub struct PDF<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to the staging files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    ///
+    /// PDF layout requires knowing the full conversation, so messages are staged as text
+    /// and rendered once all of the messages have been written.
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Map of resolved chatroom file location to the title printed on each page
+    pub titles: HashMap<String, String>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
+}
+
+impl<'a> Exporter<'a> for PDF<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let orphaned = PDF::staging_path(config, ORPHANED);
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        Ok(PDF {
+            config,
+            files: HashMap::new(),
+            titles: HashMap::new(),
+            orphaned: BufWriter::new(file),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as pdf...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self.format_announcement(&msg);
+                PDF::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+            }
+            // Message replies and tapbacks are rendered in context, so no need to render them separately
+            else if !msg.is_tapback() {
+                let message = self
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                PDF::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        info!("Rendering PDF documents...");
+        for buf in self.files.values_mut() {
+            buf.flush().map_err(RuntimeError::DiskError)?;
+        }
+        self.orphaned.flush().map_err(RuntimeError::DiskError)?;
+        for filename in self.files.keys() {
+            let title = self.titles.get(filename).map_or(filename.as_str(), |t| t);
+            self.render(filename, title)?;
+        }
+        self.render(ORPHANED, ORPHANED)?;
+
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                return match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let path = PDF::staging_path(self.config, entry.key());
+                        self.titles
+                            .insert(entry.key().to_string(), chatroom.name().to_string());
+
+                        let file = File::options()
+                            .append(true)
+                            .create(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        Ok(entry.insert(BufWriter::new(file)))
+                    }
+                };
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> Writer<'a> for PDF<'a> {
+    fn format_message(&self, message: &Message, indent_size: usize) -> Result<String, TableError> {
+        let indent = String::from_iter((0..indent_size).map(|_| " "));
+        // Data we want to write to a file
+        let mut formatted_message = String::new();
+
+        // Add message date
+        self.add_line(&mut formatted_message, &self.get_time(message), &indent);
+
+        // Add message sender
+        self.add_line(
+            &mut formatted_message,
+            self.config.who(
+                message.handle_id,
+                message.is_from_me(),
+                &message.destination_caller_id,
+            ),
+            &indent,
+        );
+
+        // If message was deleted, annotate it
+        if message.is_deleted() {
+            self.add_line(
+                &mut formatted_message,
+                "This message was deleted from the conversation!",
+                &indent,
+            );
+        }
+
+        // Useful message metadata
+        let message_parts = message.body();
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let mut replies = message.get_replies(&self.config.db)?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        // Render subject
+        if let Some(subject) = &message.subject {
+            self.add_line(&mut formatted_message, subject, &indent);
+        }
+
+        // Handle SharePlay
+        if message.is_shareplay() {
+            self.add_line(&mut formatted_message, self.format_shareplay(), &indent);
+        }
+
+        // Handle Shared Location
+        if message.started_sharing_location() || message.stopped_sharing_location() {
+            self.add_line(
+                &mut formatted_message,
+                self.format_shared_location(message),
+                &indent,
+            );
+        }
+
+        // Generate the message body from it's components
+        for (idx, message_part) in message_parts.iter().enumerate() {
+            match message_part {
+                // Fitness messages have a prefix that we need to replace with the opposite if who sent the message
+                BubbleComponent::Text(text_attrs) => {
+                    if let Some(text) = &message.text {
+                        // Render edited message content, if applicable
+                        if message.is_part_edited(idx) {
+                            if let Some(edited_parts) = &message.edited_parts {
+                                if let Some(edited) =
+                                    self.format_edited(message, edited_parts, idx, &indent)
+                                {
+                                    self.add_line(&mut formatted_message, &edited, &indent);
+                                };
+                            }
+                        } else {
+                            let mut formatted_text = String::with_capacity(text.len());
+
+                            for text_attr in text_attrs {
+                                if let Some(message_content) =
+                                    text.get(text_attr.start..text_attr.end)
+                                {
+                                    formatted_text.push_str(
+                                        &self.format_attributed(message_content, &text_attr.effect),
+                                    )
+                                }
+                            }
+
+                            // If we failed to parse any text above, use the original text
+                            if formatted_text.is_empty() {
+                                formatted_text.push_str(text);
+                            }
+
+                            if formatted_text.starts_with(FITNESS_RECEIVER) {
+                                self.add_line(
+                                    &mut formatted_message,
+                                    &formatted_text.replace(FITNESS_RECEIVER, YOU),
+                                    &indent,
+                                );
+                            } else {
+                                self.add_line(&mut formatted_message, &formatted_text, &indent);
+                            }
+                        }
+                    }
+                }
+                BubbleComponent::Attachment(_) => match attachments.get_mut(attachment_index) {
+                    Some(attachment) => {
+                        if attachment.is_sticker {
+                            let result = self.format_sticker(attachment, message);
+                            self.add_line(&mut formatted_message, &result, &indent);
+                        } else {
+                            match self.format_attachment(attachment, message) {
+                                Ok(result) => {
+                                    attachment_index += 1;
+                                    self.add_line(&mut formatted_message, &result, &indent);
+                                }
+                                Err(result) => {
+                                    self.add_line(&mut formatted_message, result, &indent);
+                                }
+                            }
+                            if let Some(transcription) = message.audio_transcription() {
+                                self.add_line(
+                                    &mut formatted_message,
+                                    &self.format_transcription(transcription),
+                                    &indent,
+                                );
+                            }
+                        }
+                    }
+                    // Attachment does not exist in attachments table
+                    None => self.add_line(&mut formatted_message, "Attachment missing!", &indent),
+                },
+                BubbleComponent::App => match self.format_app(message, &mut attachments, &indent) {
+                    // We use an empty indent here because `format_app` handles building the entire message
+                    Ok(ok_bubble) => self.add_line(&mut formatted_message, &ok_bubble, &indent),
+                    Err(why) => self.add_line(
+                        &mut formatted_message,
+                        &format!("Unable to format app message: {why}"),
+                        &indent,
+                    ),
+                },
+                BubbleComponent::Retracted => {
+                    if let Some(edited_parts) = &message.edited_parts {
+                        if let Some(edited) =
+                            self.format_edited(message, edited_parts, idx, &indent)
+                        {
+                            self.add_line(&mut formatted_message, &edited, &indent);
+                        };
+                    }
+                }
+            };
+
+            // Handle expressives
+            if message.expressive_send_style_id.is_some() {
+                self.add_line(
+                    &mut formatted_message,
+                    self.format_expressive(message),
+                    &indent,
+                );
+            }
+
+            // Handle Tapbacks
+            if let Some(tapbacks_map) = self.config.tapbacks.get(&message.guid) {
+                if let Some(tapbacks) = tapbacks_map.get(&idx) {
+                    let mut formatted_tapbacks = String::new();
+                    tapbacks
+                        .iter()
+                        .try_for_each(|tapbacks| -> Result<(), TableError> {
+                            let formatted = self.format_tapback(tapbacks)?;
+                            if !formatted.is_empty() {
+                                self.add_line(
+                                    &mut formatted_tapbacks,
+                                    &self.format_tapback(tapbacks)?,
+                                    &indent,
+                                );
+                            }
+                            Ok(())
+                        })?;
+
+                    if !formatted_tapbacks.is_empty() {
+                        self.add_line(&mut formatted_message, "Tapbacks:", &indent);
+                        self.add_line(&mut formatted_message, &formatted_tapbacks, &indent);
+                    }
+                }
+            }
+
+            // Handle Replies
+            if let Some(replies) = replies.get_mut(&idx) {
+                replies
+                    .iter_mut()
+                    .try_for_each(|reply| -> Result<(), TableError> {
+                        let _ = reply.generate_text(&self.config.db);
+                        if !reply.is_tapback() {
+                            self.add_line(
+                                &mut formatted_message,
+                                &self.format_message(reply, 4)?,
+                                &indent,
+                            );
+                        }
+                        Ok(())
+                    })?;
+            }
+        }
+
+        // Add a note if the message is a reply
+        if message.is_reply() && indent.is_empty() {
+            self.add_line(
+                &mut formatted_message,
+                "This message responded to an earlier message.",
+                &indent,
+            );
+        }
+
+        if indent.is_empty() {
+            // Add a newline for top-level messages
+            formatted_message.push('\n');
+        }
+
+        Ok(formatted_message)
+    }
+
+    fn format_attachment(
+        &self,
+        attachment: &'a mut Attachment,
+        message: &Message,
+    ) -> Result<String, &'a str> {
+        if let Some(removed) = self.config.removed_attachment(message, attachment) {
+            return Ok(removed.to_string());
+        }
+
+        // Copy the file, if requested
+        self.config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .ok_or(attachment.filename())?;
+
+        // Build a relative filepath from the fully qualified one on the `Attachment`
+        let path = self.config.message_attachment_path(attachment);
+
+        // Mark images so they get drawn inline when the document is rendered
+        Ok(match attachment.mime_type() {
+            MediaType::Image(_) => format!("{IMAGE_MARKER}{path}"),
+            _ => path,
+        })
+    }
+
+    fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
+        let who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        match self.format_attachment(sticker, message) {
+            Ok(path_to_sticker) => {
+                let sticker_effect = sticker.get_sticker_effect(
+                    &self.config.options.platform,
+                    &self.config.options.db_path,
+                    self.config.options.attachment_root.as_deref(),
+                );
+                if let Ok(Some(sticker_effect)) = sticker_effect {
+                    return format!("{sticker_effect} Sticker from {who}: {path_to_sticker}");
+                }
+                format!("Sticker from {who}: {path_to_sticker}")
+            }
+            Err(path) => format!("Sticker from {who}: {path}"),
+        }
+    }
+
+    fn format_app(
+        &self,
+        message: &'a Message,
+        attachments: &mut Vec<Attachment>,
+        indent: &str,
+    ) -> Result<String, PlistParseError> {
+        if let Variant::App(balloon) = message.variant() {
+            let mut app_bubble = String::new();
+
+            // Handwritten messages use a different payload type, so check that first
+            if message.is_handwriting() {
+                if let Some(payload) = message.raw_payload_data(&self.config.db) {
+                    return match HandwrittenMessage::from_payload(&payload) {
+                        Ok(bubble) => Ok(self.format_handwriting(message, &bubble, indent)),
+                        Err(why) => Err(PlistParseError::HandwritingError(why)),
+                    };
+                }
+            }
+
+            if message.is_digital_touch() {
+                let payload = message
+                    .raw_payload_data(&self.config.db)
+                    .unwrap_or_default();
+                return Ok(self.format_digital_touch(message, &payload, indent));
+            }
+
+            if let Some(payload) = message.payload_data(&self.config.db) {
+                // Handle URL messages separately since they are a special case
+                let res = if message.is_url() {
+                    let parsed = match parse_plist(&payload) {
+                        Ok(parsed) => parsed,
+                        Err(why) => return message.text.clone().ok_or(why),
+                    };
+                    let bubble = match URLMessage::get_url_message_override(&parsed) {
+                        Ok(bubble) => bubble,
+                        Err(why) => return message.text.clone().ok_or(why),
+                    };
+                    match bubble {
+                        URLOverride::Normal(balloon) => self.format_url(message, &balloon, indent),
+                        URLOverride::AppleMusic(balloon) => self.format_music(&balloon, indent),
+                        URLOverride::Collaboration(balloon) => {
+                            self.format_collaboration(&balloon, indent)
+                        }
+                        URLOverride::AppStore(balloon) => self.format_app_store(&balloon, indent),
+                        URLOverride::SharedPlacemark(balloon) => {
+                            self.format_placemark(&balloon, indent)
+                        }
+                        URLOverride::FaceTime(balloon) => self.format_facetime(&balloon, indent),
+                        URLOverride::ICloudPhotos(balloon) => {
+                            self.format_icloud_photos(&balloon, indent)
+                        }
+                    }
+                // Handwriting uses a different payload type than the rest of the branches
+                } else {
+                    // Handle the app case
+                    let parsed = parse_plist(&payload)?;
+                    match AppMessage::from_map(&parsed) {
+                        Ok(bubble) => match balloon {
+                            CustomBalloon::Application(bundle_id) => {
+                                self.format_generic_app(&bubble, bundle_id, attachments, indent)
+                            }
+                            CustomBalloon::ApplePay => self.format_apple_pay(&bubble, indent),
+                            CustomBalloon::Fitness => self.format_fitness(&bubble, indent),
+                            CustomBalloon::Slideshow => self.format_slideshow(&bubble, indent),
+                            CustomBalloon::CheckIn => self.format_check_in(&bubble, indent),
+                            CustomBalloon::FindMy => self.format_find_my(&bubble, indent),
+                            CustomBalloon::Handwriting => unreachable!(),
+                            CustomBalloon::DigitalTouch => unreachable!(),
+                            CustomBalloon::URL => unreachable!(),
+                        },
+                        Err(why) => return Err(why),
+                    }
+                };
+                app_bubble.push_str(&res);
+            } else {
+                // Sometimes, URL messages are missing their payloads
+                if message.is_url() {
+                    if let Some(text) = &message.text {
+                        return Ok(text.to_string());
+                    }
+                }
+                return Err(PlistParseError::NoPayload);
+            };
+            Ok(app_bubble)
+        } else {
+            Err(PlistParseError::WrongMessageType)
+        }
+    }
+
+    fn format_tapback(&self, msg: &Message) -> Result<String, TableError> {
+        match msg.variant() {
+            Variant::Tapback(_, added, tapback) => {
+                if !added {
+                    return Ok(String::new());
+                }
+                Ok(format!(
+                    "{} by {}",
+                    tapback,
+                    self.config
+                        .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id),
+                ))
+            }
+            Variant::Sticker(_) => {
+                let mut paths = Attachment::from_message(&self.config.db, msg)?;
+                let who =
+                    self.config
+                        .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+                // Sticker messages have only one attachment, the sticker image
+                Ok(if let Some(sticker) = paths.get_mut(0) {
+                    format!("{} from {who}", self.format_sticker(sticker, msg))
+                } else {
+                    format!("Sticker from {who} not found!")
+                })
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn format_expressive(&self, msg: &'a Message) -> &'a str {
+        match msg.get_expressive() {
+            Expressive::Screen(effect) => match effect {
+                ScreenEffect::Confetti => "Sent with Confetti",
+                ScreenEffect::Echo => "Sent with Echo",
+                ScreenEffect::Fireworks => "Sent with Fireworks",
+                ScreenEffect::Balloons => "Sent with Balloons",
+                ScreenEffect::Heart => "Sent with Heart",
+                ScreenEffect::Lasers => "Sent with Lasers",
+                ScreenEffect::ShootingStar => "Sent with Shooting Star",
+                ScreenEffect::Sparkles => "Sent with Sparkles",
+                ScreenEffect::Spotlight => "Sent with Spotlight",
+            },
+            Expressive::Bubble(effect) => match effect {
+                BubbleEffect::Slam => "Sent with Slam",
+                BubbleEffect::Loud => "Sent with Loud",
+                BubbleEffect::Gentle => "Sent with Gentle",
+                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
+            },
+            Expressive::Unknown(effect) => effect,
+            Expressive::None => "",
+        }
+    }
+
+    fn format_announcement(&self, msg: &'a Message) -> String {
+        let mut who = self
+            .config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+
+        let timestamp = format(&msg.date(&self.config.offset));
+
+        return match msg.get_announcement() {
+            Some(announcement) => match announcement {
+                Announcement::NameChange(name) => {
+                    format!("{timestamp} {who} renamed the conversation to {name}\n\n")
+                }
+                Announcement::PhotoChange => {
+                    format!("{timestamp} {who} changed the group photo.\n\n")
+                }
+                Announcement::PhotoRemoved => {
+                    format!("{timestamp} {who} removed the group photo.\n\n")
+                }
+                Announcement::ParticipantAdded(handle) => {
+                    let added = self
+                        .config
+                        .who(Some(handle), false, &msg.destination_caller_id);
+                    format!("{timestamp} {who} added {added} to the conversation.\n\n")
+                }
+                Announcement::ParticipantRemoved(handle) => {
+                    let removed = self
+                        .config
+                        .who(Some(handle), false, &msg.destination_caller_id);
+                    format!("{timestamp} {who} removed {removed} from the conversation.\n\n")
+                }
+                Announcement::ParticipantLeft => {
+                    format!("{timestamp} {who} left the conversation.\n\n")
+                }
+                Announcement::AudioMessageKept => {
+                    format!("{timestamp} {who} kept an audio message.\n\n")
+                }
+                Announcement::UnknownEvent(num) => {
+                    format!("{timestamp} {who} performed unknown system event {num}.\n\n")
+                }
+                Announcement::Unknown(num) => {
+                    format!("{timestamp} {who} performed unknown action {num}.\n\n")
+                }
+                Announcement::FullyUnsent => format!("{timestamp} {who} unsent a message!\n\n"),
+            },
+            None => String::from("Unable to format announcement!\n\n"),
+        };
+    }
+
+    fn format_shareplay(&self) -> &str {
+        "SharePlay Message\nEnded"
+    }
+
+    fn format_shared_location(&self, msg: &'a Message) -> &str {
+        // Handle Shared Location
+        if msg.started_sharing_location() {
+            return "Started sharing location!";
+        } else if msg.stopped_sharing_location() {
+            return "Stopped sharing location!";
+        }
+        "Shared location!"
+    }
+
+    fn format_transcription(&self, transcription: &str) -> String {
+        format!("Transcription: {transcription}")
+    }
+
+    fn format_edited(
+        &self,
+        msg: &'a Message,
+        edited_message: &'a EditedMessage,
+        message_part_idx: usize,
+        indent: &str,
+    ) -> Option<String> {
+        if let Some(edited_message_part) = edited_message.part(message_part_idx) {
+            let mut out_s = String::new();
+            let mut previous_timestamp: Option<&i64> = None;
+
+            match edited_message_part.status {
+                EditStatus::Edited => {
+                    for event in &edited_message_part.edit_history {
+                        match previous_timestamp {
+                            // Original message get an absolute timestamp
+                            None => {
+                                let parsed_timestamp =
+                                    format(&get_local_time(&event.date, &self.config.offset));
+                                out_s.push_str(&parsed_timestamp);
+                                out_s.push(' ');
+                            }
+                            // Subsequent edits get a relative timestamp
+                            Some(prev_timestamp) => {
+                                let end = get_local_time(&event.date, &self.config.offset);
+                                let start = get_local_time(prev_timestamp, &self.config.offset);
+                                if let Some(diff) = readable_diff(start, end) {
+                                    out_s.push_str(indent);
+                                    out_s.push_str("Edited ");
+                                    out_s.push_str(&diff);
+                                    out_s.push_str(" later: ");
+                                }
+                            }
+                        };
+
+                        // Update the previous timestamp for the next loop
+                        previous_timestamp = Some(&event.date);
+
+                        // Render the message text
+                        self.add_line(&mut out_s, &event.text, indent);
+                    }
+                }
+                EditStatus::Unsent => {
+                    let who = if msg.is_from_me() {
+                        self.config.options.custom_name.as_deref().unwrap_or(YOU)
+                    } else {
+                        "They"
+                    };
+
+                    match readable_diff(
+                        msg.date(&self.config.offset),
+                        msg.date_edited(&self.config.offset),
+                    ) {
+                        Some(diff) => {
+                            out_s.push_str(who);
+                            out_s.push_str(" unsent this message part ");
+                            out_s.push_str(&diff);
+                            out_s.push_str(" after sending!");
+                        }
+                        None => {
+                            out_s.push_str(who);
+                            out_s.push_str(" unsent this message part!");
+                        }
+                    }
+                }
+                EditStatus::Original => {
+                    return None;
+                }
+            }
+
+            return Some(out_s);
+        }
+        None
+    }
+
+    fn format_attributed(&'a self, msg: &'a str, _: &'a TextEffect) -> Cow<str> {
+        // There isn't really a way to represent formatted text in a plain text export
+        Cow::Borrowed(msg)
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+impl<'a> BalloonFormatter<&'a str> for PDF<'a> {
+    fn format_url(&self, msg: &Message, balloon: &URLMessage, indent: &str) -> String {
+        let mut out_s = String::new();
+
+        if let Some(url) = balloon.get_url() {
+            self.add_line(&mut out_s, url, indent);
+        } else if let Some(text) = &msg.text {
+            self.add_line(&mut out_s, text, indent);
+        }
+
+        if let Some(title) = balloon.title {
+            self.add_line(&mut out_s, title, indent);
+        }
+
+        if let Some(summary) = balloon.summary {
+            self.add_line(&mut out_s, summary, indent);
+        }
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_music(&self, balloon: &MusicMessage, indent: &str) -> String {
+        let mut out_s = String::new();
+
+        if let Some(track_name) = balloon.track_name {
+            self.add_line(&mut out_s, track_name, indent);
+        }
+
+        if let Some(album) = balloon.album {
+            self.add_line(&mut out_s, album, indent);
+        }
+
+        if let Some(artist) = balloon.artist {
+            self.add_line(&mut out_s, artist, indent);
+        }
+
+        if let Some(url) = balloon.url {
+            self.add_line(&mut out_s, url, indent);
+        }
+
+        out_s
+    }
+
+    fn format_collaboration(&self, balloon: &CollaborationMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+
+        if let Some(name) = balloon.app_name {
+            out_s.push_str(name);
+        } else if let Some(bundle_id) = balloon.bundle_id {
+            out_s.push_str(bundle_id);
+        }
+
+        if !out_s.is_empty() {
+            out_s.push_str(" message:\n");
+        }
+
+        if let Some(title) = balloon.title {
+            self.add_line(&mut out_s, title, indent);
+        }
+
+        if let Some(url) = balloon.get_url() {
+            self.add_line(&mut out_s, url, indent);
+        }
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_app_store(&self, balloon: &AppStoreMessage, indent: &'a str) -> String {
+        let mut out_s = String::from(indent);
+
+        if let Some(name) = balloon.app_name {
+            self.add_line(&mut out_s, name, indent);
+        }
+
+        if let Some(description) = balloon.description {
+            self.add_line(&mut out_s, description, indent);
+        }
+
+        if let Some(platform) = balloon.platform {
+            self.add_line(&mut out_s, platform, indent);
+        }
+
+        if let Some(genre) = balloon.genre {
+            self.add_line(&mut out_s, genre, indent);
+        }
+
+        if let Some(url) = balloon.url {
+            self.add_line(&mut out_s, url, indent);
+        }
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_facetime(&self, balloon: &FaceTimeMessage, indent: &'a str) -> String {
+        let mut out_s = String::from(indent);
+        out_s.push_str("FaceTime call link shared");
+        if let Some(title) = balloon.title {
+            out_s.push_str(": ");
+            out_s.push_str(title);
+        }
+        out_s.push('\n');
+        self.add_line(&mut out_s, balloon.url, indent);
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_icloud_photos(&self, balloon: &ICloudPhotosMessage, indent: &'a str) -> String {
+        let mut out_s = String::from(indent);
+        out_s.push_str(match balloon.kind {
+            ICloudPhotosKind::SharedAlbum => "Shared Album invitation",
+            ICloudPhotosKind::PhotoLink => "iCloud Photos link shared",
+        });
+        if let Some(title) = balloon.title {
+            out_s.push_str(": ");
+            out_s.push_str(title);
+        }
+        out_s.push('\n');
+        if let Some(summary) = balloon.summary {
+            self.add_line(&mut out_s, summary, indent);
+        }
+        self.add_line(&mut out_s, balloon.url, indent);
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_placemark(&self, balloon: &PlacemarkMessage, indent: &'a str) -> String {
+        let mut out_s = String::from(indent);
+
+        if let Some(name) = balloon.place_name {
+            self.add_line(&mut out_s, name, indent);
+        }
+
+        if let Some(url) = balloon.get_url() {
+            self.add_line(&mut out_s, url, indent);
+        }
+
+        if let Some(name) = balloon.placemark.name {
+            self.add_line(&mut out_s, name, indent);
+        }
+
+        if let Some(address) = balloon.placemark.address {
+            self.add_line(&mut out_s, address, indent);
+        }
+
+        if let Some(state) = balloon.placemark.state {
+            self.add_line(&mut out_s, state, indent);
+        }
+
+        if let Some(city) = balloon.placemark.city {
+            self.add_line(&mut out_s, city, indent);
+        }
+
+        if let Some(iso_country_code) = balloon.placemark.iso_country_code {
+            self.add_line(&mut out_s, iso_country_code, indent);
+        }
+
+        if let Some(postal_code) = balloon.placemark.postal_code {
+            self.add_line(&mut out_s, postal_code, indent);
+        }
+
+        if let Some(country) = balloon.placemark.country {
+            self.add_line(&mut out_s, country, indent);
+        }
+
+        if let Some(street) = balloon.placemark.street {
+            self.add_line(&mut out_s, street, indent);
+        }
+
+        if let Some(sub_administrative_area) = balloon.placemark.sub_administrative_area {
+            self.add_line(&mut out_s, sub_administrative_area, indent);
+        }
+
+        if let Some(sub_locality) = balloon.placemark.sub_locality {
+            self.add_line(&mut out_s, sub_locality, indent);
+        }
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_handwriting(
+        &self,
+        msg: &Message,
+        balloon: &HandwrittenMessage,
+        indent: &str,
+    ) -> String {
+        match self.config.options.attachment_manager {
+            AttachmentManager::Disabled => balloon
+                .render_ascii(40)
+                .replace("\n", &format!("{indent}\n")),
+            AttachmentManager::Compatible
+            | AttachmentManager::Efficient
+            | AttachmentManager::Link => self
+                .config
+                .options
+                .attachment_manager
+                .handle_handwriting(msg, balloon, self.config)
+                .map(|filepath| {
+                    self.config
+                        .relative_path(PathBuf::from(&filepath))
+                        .unwrap_or(filepath.display().to_string())
+                })
+                .map(|filepath| format!("{indent}{filepath}"))
+                .unwrap_or_else(|| {
+                    balloon
+                        .render_ascii(40)
+                        .replace("\n", &format!("{indent}\n"))
+                }),
+        }
+    }
+
+    fn format_digital_touch(&self, msg: &Message, payload: &[u8], indent: &str) -> String {
+        match self
+            .config
+            .options
+            .attachment_manager
+            .handle_digital_touch(msg, payload, self.config)
+        {
+            Some(filepath) => format!(
+                "{indent}Digital Touch message, strokes saved to {}",
+                self.config
+                    .relative_path(filepath.clone())
+                    .unwrap_or(filepath.display().to_string())
+            ),
+            None => format!("{indent}Digital Touch message"),
+        }
+    }
+
+    fn format_apple_pay(&self, balloon: &AppMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        if let Some(caption) = balloon.caption {
+            out_s.push_str(caption);
+            out_s.push_str(" transaction: ");
+        }
+
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str(ldtext);
+        } else {
+            out_s.push_str("unknown amount");
+        }
+
+        out_s
+    }
+
+    fn format_fitness(&self, balloon: &AppMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str(app_name);
+            out_s.push_str(" message: ");
+        }
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str(ldtext);
+        } else {
+            out_s.push_str("unknown workout");
+        }
+        out_s
+    }
+
+    fn format_slideshow(&self, balloon: &AppMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str("Photo album: ");
+            out_s.push_str(ldtext);
+        }
+
+        if let Some(url) = balloon.url {
+            out_s.push(' ');
+            out_s.push_str(url);
+        }
+
+        out_s
+    }
+
+    fn format_find_my(&self, balloon: &AppMessage, indent: &'a str) -> String {
+        let mut out_s = String::from(indent);
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str(app_name);
+            out_s.push_str(": ");
+        }
+
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push(' ');
+            out_s.push_str(ldtext);
+        }
+
+        out_s
+    }
+
+    fn format_check_in(&self, balloon: &AppMessage, indent: &'a str) -> String {
+        let mut out_s = String::from(indent);
+
+        out_s.push_str(balloon.caption.unwrap_or("Check In"));
+
+        let metadata: HashMap<&str, &str> = balloon.parse_query_string();
+
+        // Before manual check-in
+        if let Some(date_str) = metadata.get("estimatedEndTime") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            out_s.push_str("\nExpected at ");
+            out_s.push_str(&date_string);
+        }
+        // Expired check-in
+        else if let Some(date_str) = metadata.get("triggerTime") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            out_s.push_str("\nWas expected at ");
+            out_s.push_str(&date_string);
+        }
+        // Accepted check-in
+        else if let Some(date_str) = metadata.get("sendDate") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            out_s.push_str("\nChecked in at ");
+            out_s.push_str(&date_string);
+        }
+
+        out_s
+    }
+
+    fn format_generic_app(
+        &self,
+        balloon: &AppMessage,
+        bundle_id: &str,
+        _: &mut Vec<Attachment>,
+        indent: &str,
+    ) -> String {
+        let mut out_s = String::from(indent);
+
+        if let Some(name) = balloon.app_name {
+            out_s.push_str(name);
+        } else {
+            out_s.push_str(bundle_id);
+        }
+
+        if !out_s.is_empty() {
+            out_s.push_str(" message:\n");
+        }
+
+        if let Some(title) = balloon.title {
+            self.add_line(&mut out_s, title, indent);
+        }
+
+        if let Some(subtitle) = balloon.subtitle {
+            self.add_line(&mut out_s, subtitle, indent);
+        }
+
+        if let Some(caption) = balloon.caption {
+            self.add_line(&mut out_s, caption, indent);
+        }
+
+        if let Some(subcaption) = balloon.subcaption {
+            self.add_line(&mut out_s, subcaption, indent);
+        }
+
+        if let Some(trailing_caption) = balloon.trailing_caption {
+            self.add_line(&mut out_s, trailing_caption, indent);
+        }
+
+        if let Some(trailing_subcaption) = balloon.trailing_subcaption {
+            self.add_line(&mut out_s, trailing_subcaption, indent);
+        }
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+}
+
+impl<'a> PDF<'a> {
+    /// Get the path to the file a conversation is staged in
+    fn staging_path(config: &Config, filename: &str) -> PathBuf {
+        let mut path = config.options.export_path.clone();
+        path.push(filename);
+        append_extension(&mut path, STAGING_EXTENSION);
+        path
+    }
+
+    /// Lay out a staged conversation as a paginated PDF, then remove the staging file
+    fn render(&self, filename: &str, title: &str) -> Result<(), RuntimeError> {
+        let staging = PDF::staging_path(self.config, filename);
+        let mut path = self.config.options.export_path.clone();
+        path.push(filename);
+        append_extension(&mut path, "pdf");
+
+        let staged =
+            File::open(&staging).map_err(|err| RuntimeError::CreateError(err, staging.clone()))?;
+        let mut document =
+            Document::new(&path, title).map_err(|err| RuntimeError::CreateError(err, path))?;
+
+        for line in BufReader::new(staged).lines() {
+            let line = line.map_err(RuntimeError::DiskError)?;
+            let content = line.trim_start_matches(' ');
+            let indent = &line[..line.len() - content.len()];
+
+            if let Some(image) = content.strip_prefix(IMAGE_MARKER) {
+                // Copied attachments are referenced relative to the export directory
+                let mut image_path = PathBuf::from(image);
+                if image_path.is_relative() {
+                    image_path = self.config.options.export_path.join(image_path);
+                }
+
+                // Fall back to the file path if the image cannot be embedded
+                let drawn = document
+                    .add_image(&image_path, indent)
+                    .map_err(RuntimeError::DiskError)?;
+                if !drawn {
+                    document
+                        .add_line(&format!("{indent}{image}"))
+                        .map_err(RuntimeError::DiskError)?;
+                }
+            } else {
+                document.add_line(&line).map_err(RuntimeError::DiskError)?;
+            }
+        }
+
+        document.finish().map_err(RuntimeError::DiskError)?;
+        remove_file(&staging).map_err(RuntimeError::DiskError)
+    }
+
+    fn get_time(&self, message: &Message) -> String {
+        let mut date = format(&message.date(&self.config.offset));
+        let read_after = message.time_until_read(&self.config.offset);
+        if let Some(time) = read_after {
+            if !time.is_empty() {
+                let who = if message.is_from_me() {
+                    "them"
+                } else {
+                    self.config.options.custom_name.as_deref().unwrap_or("you")
+                };
+                date.push_str(&format!(" (Read by {who} after {time})"));
+            }
+        }
+        if let Some(receipts) = self.config.receipts(message) {
+            date.push_str(&format!(" ({receipts})"));
+        }
+        date
+    }
+
+    fn add_line(&self, string: &mut String, part: &str, indent: &str) {
+        if !part.is_empty() {
+            string.push_str(indent);
+            string.push_str(part);
+            string.push('\n');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        env::set_var,
+        fs::{read, write},
+        path::PathBuf,
+    };
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        exporters::{
+            exporter::Writer,
+            pdf::{IMAGE_MARKER, PDF},
+        },
+        Config, Exporter, Options,
+    };
+    use imessage_database::{
+        tables::{
+            attachment::Attachment,
+            messages::Message,
+            table::{get_connection, ME},
+        },
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options() -> Options {
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path: PathBuf::from("/tmp"),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    pub(super) fn fake_attachment() -> Attachment {
+        Attachment {
+            rowid: 0,
+            filename: Some("a/b/c/d.jpg".to_string()),
+            uti: Some("public.png".to_string()),
+            mime_type: Some("image/png".to_string()),
+            transfer_name: Some("d.jpg".to_string()),
+            total_bytes: 100,
+            is_sticker: false,
+            hide_attachment: 0,
+            copied_path: None,
+            backup_root: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = PDF::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_format_pdf_from_me_normal() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+        let exporter = PDF::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "May 17, 2022  5:29:42 PM\nMe\nHello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_pdf_attachment_image() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = PDF::new(&config).unwrap();
+
+        let message = blank();
+        let mut attachment = fake_attachment();
+
+        let actual = exporter.format_attachment(&mut attachment, &message);
+
+        assert_eq!(actual, Ok(format!("{IMAGE_MARKER}a/b/c/d.jpg")));
+    }
+
+    #[test]
+    fn can_format_pdf_attachment_file() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = PDF::new(&config).unwrap();
+
+        let message = blank();
+        let mut attachment = fake_attachment();
+        attachment.filename = Some("a/b/c/d.pdf".to_string());
+        attachment.mime_type = Some("application/pdf".to_string());
+
+        let actual = exporter.format_attachment(&mut attachment, &message);
+
+        assert_eq!(actual, Ok("a/b/c/d.pdf".to_string()));
+    }
+
+    #[test]
+    fn can_render_staged_file() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = PDF::new(&config).unwrap();
+
+        // Stage a conversation that references a missing image
+        let staging = PDF::staging_path(&config, "pdf_render_test");
+        write(
+            &staging,
+            format!("Hello world\n    {IMAGE_MARKER}/does/not/exist.png\n"),
+        )
+        .unwrap();
+
+        exporter.render("pdf_render_test", "Test Chat").unwrap();
+
+        let rendered = read("/tmp/pdf_render_test.pdf").unwrap();
+        let rendered = String::from_utf8_lossy(&rendered);
+        assert!(rendered.starts_with("%PDF-1.4"));
+        assert!(rendered.contains("(Hello world) Tj"));
+        assert!(rendered.contains("(/does/not/exist.png) Tj"));
+        assert!(!staging.exists());
+    }
+}
@@ -0,0 +1,595 @@
+/*!
+ A minimal PDF writer used to lay out exported conversations.
+
+ Documents are written incrementally: each page is flushed to disk as soon as it is full,
+ so large conversations never need to be held in memory. Text is set in the standard
+ Helvetica fonts, which every PDF reader is required to provide, and images are embedded
+ without re-encoding when they are JPEG or compatible PNG files.
+*/
+
+use std::{
+    collections::HashMap,
+    fs::{read, File},
+    io::{BufWriter, Result, Write},
+    path::{Path, PathBuf},
+};
+
+/// Width of a US Letter page, in points
+const PAGE_WIDTH: f32 = 612.;
+/// Height of a US Letter page, in points
+const PAGE_HEIGHT: f32 = 792.;
+/// Space between the edge of the page and the content
+const MARGIN: f32 = 54.;
+/// Size of message text
+const FONT_SIZE: f32 = 10.;
+/// Distance between lines of message text
+const LEADING: f32 = 13.;
+/// Size of the page header and footer text
+const CAPTION_SIZE: f32 = 8.;
+/// Tallest an embedded image may be drawn
+const MAX_IMAGE_HEIGHT: f32 = 288.;
+
+/// Object ID of the document catalog
+const CATALOG_ID: usize = 1;
+/// Object ID of the page tree
+const PAGES_ID: usize = 2;
+/// Object ID of the regular text font
+const REGULAR_FONT_ID: usize = 3;
+/// Object ID of the bold text font
+const BOLD_FONT_ID: usize = 4;
+
+/// Widths of the printable ASCII characters in Helvetica, in thousandths of the font size
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// An image that has been written to the document
+#[derive(Clone, Copy)]
+struct ImageObject {
+    id: usize,
+    width: u32,
+    height: u32,
+}
+
+/// Image data in a format PDF readers can decode natively
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct EmbeddableImage {
+    width: u32,
+    height: u32,
+    color_space: &'static str,
+    filter: &'static str,
+    decode_parms: Option<String>,
+    data: Vec<u8>,
+}
+
+pub(super) struct Document {
+    /// Buffered writer for the PDF file
+    file: BufWriter<File>,
+    /// Number of bytes written to the file so far
+    position: usize,
+    /// Byte offset of each object, indexed by object ID
+    offsets: Vec<usize>,
+    /// Object IDs of the finished pages
+    pages: Vec<usize>,
+    /// Content stream for the page currently being laid out
+    content: String,
+    /// Images drawn on the current page
+    page_images: Vec<usize>,
+    /// Images already embedded in the document, so they are only stored once
+    images: HashMap<PathBuf, Option<ImageObject>>,
+    /// Distance from the bottom of the page to the baseline of the next line
+    cursor: f32,
+    /// Title printed at the top of each page
+    title: String,
+}
+
+impl Document {
+    /// Create a new document at `path`
+    pub(super) fn new(path: &Path, title: &str) -> Result<Self> {
+        let mut document = Document {
+            file: BufWriter::new(File::create(path)?),
+            position: 0,
+            offsets: vec![0; BOLD_FONT_ID + 1],
+            pages: vec![],
+            content: String::new(),
+            page_images: vec![],
+            images: HashMap::new(),
+            cursor: 0.,
+            title: title.to_string(),
+        };
+
+        // The binary comment tells transfer tools that the file is not plain text
+        document.write(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n")?;
+        document.write_object(
+            REGULAR_FONT_ID,
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+        )?;
+        document.write_object(
+            BOLD_FONT_ID,
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>",
+        )?;
+        document.start_page();
+        Ok(document)
+    }
+
+    /// Lay out a line of text, wrapping it to the width of the page
+    ///
+    /// Leading spaces are kept as an indent for each wrapped line.
+    pub(super) fn add_line(&mut self, line: &str) -> Result<()> {
+        let text = line.trim_start_matches(' ');
+        let indent = text_width(&line[..line.len() - text.len()], FONT_SIZE);
+        let available = PAGE_WIDTH - 2. * MARGIN - indent;
+
+        if text.is_empty() {
+            return self.advance(LEADING);
+        }
+
+        for wrapped in wrap(text, available, FONT_SIZE) {
+            self.advance(LEADING)?;
+            self.content.push_str(&format!(
+                "BT /F1 {FONT_SIZE} Tf {:.2} {:.2} Td ({}) Tj ET\n",
+                MARGIN + indent,
+                self.cursor,
+                encode(&wrapped)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Draw an image scaled to fit the page, returning `false` if the file cannot be embedded
+    pub(super) fn add_image(&mut self, path: &Path, indent: &str) -> Result<bool> {
+        let image = match self.images.get(path) {
+            Some(cached) => *cached,
+            None => {
+                let embedded = match read(path).ok().and_then(|bytes| embeddable(&bytes)) {
+                    Some(image) => Some(self.write_image(image)?),
+                    None => None,
+                };
+                self.images.insert(path.to_path_buf(), embedded);
+                embedded
+            }
+        };
+
+        let Some(image) = image else {
+            return Ok(false);
+        };
+
+        // Scale the image down to fit inside the content area, but never scale it up
+        let indent = text_width(indent, FONT_SIZE);
+        let max_width = PAGE_WIDTH - 2. * MARGIN - indent;
+        let scale = (max_width / image.width as f32)
+            .min(MAX_IMAGE_HEIGHT / image.height as f32)
+            .min(1.);
+        let width = image.width as f32 * scale;
+        let height = image.height as f32 * scale;
+
+        self.advance(height + LEADING / 2.)?;
+        if !self.page_images.contains(&image.id) {
+            self.page_images.push(image.id);
+        }
+        self.content.push_str(&format!(
+            "q {width:.2} 0 0 {height:.2} {:.2} {:.2} cm /Im{} Do Q\n",
+            MARGIN + indent,
+            self.cursor,
+            image.id
+        ));
+        Ok(true)
+    }
+
+    /// Write the remaining page and the document structure, then close the file
+    pub(super) fn finish(mut self) -> Result<()> {
+        self.end_page()?;
+
+        let kids = self
+            .pages
+            .iter()
+            .map(|id| format!("{id} 0 R"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.write_object(
+            PAGES_ID,
+            &format!(
+                "<< /Type /Pages /Kids [{kids}] /Count {} >>",
+                self.pages.len()
+            ),
+        )?;
+        self.write_object(
+            CATALOG_ID,
+            &format!("<< /Type /Catalog /Pages {PAGES_ID} 0 R >>"),
+        )?;
+        let info_id = self.next_id();
+        self.write_object(
+            info_id,
+            &format!(
+                "<< /Title ({}) /Producer (imessage-exporter) >>",
+                encode(&self.title)
+            ),
+        )?;
+
+        // Cross-reference table that lets readers find each object
+        let xref_position = self.position;
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len());
+        self.offsets
+            .iter()
+            .skip(1)
+            .for_each(|offset| xref.push_str(&format!("{offset:010} 00000 n \n")));
+        xref.push_str(&format!(
+            "trailer\n<< /Size {} /Root {CATALOG_ID} 0 R /Info {info_id} 0 R >>\nstartxref\n{xref_position}\n%%EOF\n",
+            self.offsets.len()
+        ));
+        self.write(xref.as_bytes())?;
+        self.file.flush()
+    }
+
+    /// Move the cursor down, starting a new page if there is not enough room left
+    fn advance(&mut self, height: f32) -> Result<()> {
+        if self.cursor - height < MARGIN {
+            self.end_page()?;
+            self.start_page();
+        }
+        self.cursor -= height;
+        Ok(())
+    }
+
+    /// Reset the layout state and draw the page header and footer
+    fn start_page(&mut self) {
+        self.content.clear();
+        self.page_images.clear();
+        self.cursor = PAGE_HEIGHT - MARGIN;
+        self.content.push_str(&format!(
+            "BT /F2 {CAPTION_SIZE} Tf {MARGIN} {:.2} Td ({}) Tj ET\n",
+            PAGE_HEIGHT - MARGIN / 2.,
+            encode(&self.title)
+        ));
+        self.content.push_str(&format!(
+            "BT /F1 {CAPTION_SIZE} Tf {MARGIN} {:.2} Td (Page {}) Tj ET\n",
+            MARGIN / 2.,
+            self.pages.len() + 1
+        ));
+    }
+
+    /// Write the current page's content stream and page object
+    fn end_page(&mut self) -> Result<()> {
+        let content_id = self.next_id();
+        let content = std::mem::take(&mut self.content);
+        self.write_stream(content_id, "", content.as_bytes())?;
+
+        let images = self
+            .page_images
+            .iter()
+            .map(|id| format!("/Im{id} {id} 0 R"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let page_id = self.next_id();
+        self.write_object(
+            page_id,
+            &format!(
+                "<< /Type /Page /Parent {PAGES_ID} 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] /Resources << /Font << /F1 {REGULAR_FONT_ID} 0 R /F2 {BOLD_FONT_ID} 0 R >> /XObject << {images} >> >> /Contents {content_id} 0 R >>"
+            ),
+        )?;
+        self.pages.push(page_id);
+        Ok(())
+    }
+
+    fn write_image(&mut self, image: EmbeddableImage) -> Result<ImageObject> {
+        let id = self.next_id();
+        let mut dictionary = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} /BitsPerComponent 8 /Filter /{}",
+            image.width, image.height, image.color_space, image.filter
+        );
+        if let Some(parms) = &image.decode_parms {
+            dictionary.push_str(" /DecodeParms ");
+            dictionary.push_str(parms);
+        }
+        self.write_stream(id, &dictionary, &image.data)?;
+        Ok(ImageObject {
+            id,
+            width: image.width,
+            height: image.height,
+        })
+    }
+
+    /// Reserve the next object ID
+    fn next_id(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len() - 1
+    }
+
+    fn write_object(&mut self, id: usize, body: &str) -> Result<()> {
+        self.offsets[id] = self.position;
+        self.write(format!("{id} 0 obj\n{body}\nendobj\n").as_bytes())
+    }
+
+    fn write_stream(&mut self, id: usize, dictionary: &str, data: &[u8]) -> Result<()> {
+        self.offsets[id] = self.position;
+        self.write(
+            format!(
+                "{id} 0 obj\n<< {dictionary} /Length {} >>\nstream\n",
+                data.len()
+            )
+            .as_bytes(),
+        )?;
+        self.write(data)?;
+        self.write(b"\nendstream\nendobj\n")
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.position += bytes.len();
+        self.file.write_all(bytes)
+    }
+}
+
+/// Map a character to its code in `WinAnsiEncoding`, the encoding used by the standard fonts
+fn win_ansi(c: char) -> Option<u8> {
+    match c {
+        ' '..='~' => Some(c as u8),
+        '\u{A0}'..='\u{FF}' => Some(c as u8),
+        '€' => Some(0x80),
+        '‚' => Some(0x82),
+        'ƒ' => Some(0x83),
+        '„' => Some(0x84),
+        '…' => Some(0x85),
+        '†' => Some(0x86),
+        '‡' => Some(0x87),
+        'ˆ' => Some(0x88),
+        '‰' => Some(0x89),
+        'Š' => Some(0x8A),
+        '‹' => Some(0x8B),
+        'Œ' => Some(0x8C),
+        'Ž' => Some(0x8E),
+        '‘' => Some(0x91),
+        '’' => Some(0x92),
+        '“' => Some(0x93),
+        '”' => Some(0x94),
+        '•' => Some(0x95),
+        '–' => Some(0x96),
+        '—' => Some(0x97),
+        '˜' => Some(0x98),
+        '™' => Some(0x99),
+        'š' => Some(0x9A),
+        '›' => Some(0x9B),
+        'œ' => Some(0x9C),
+        'ž' => Some(0x9E),
+        'Ÿ' => Some(0x9F),
+        _ => None,
+    }
+}
+
+/// Encode text as the body of a PDF string literal
+///
+/// Characters the standard fonts cannot draw are replaced with `?`.
+pub(super) fn encode(text: &str) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match win_ansi(c).unwrap_or(b'?') {
+            b'(' => out_s.push_str("\\("),
+            b')' => out_s.push_str("\\)"),
+            b'\\' => out_s.push_str("\\\\"),
+            byte @ 0x20..=0x7E => out_s.push(byte as char),
+            byte => out_s.push_str(&format!("\\{byte:03o}")),
+        }
+    }
+    out_s
+}
+
+/// Approximate the width of a string set in Helvetica
+fn text_width(text: &str, size: f32) -> f32 {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' => HELVETICA_WIDTHS[c as usize - 32],
+            _ => 556,
+        } as f32)
+        .sum::<f32>()
+        * size
+        / 1000.
+}
+
+/// Split text into lines no wider than `width`, breaking between words where possible
+pub(super) fn wrap(text: &str, width: f32, size: f32) -> Vec<String> {
+    let mut lines = vec![];
+    let mut current = String::new();
+
+    for word in text.split(' ') {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{current} {word}")
+        };
+
+        if text_width(&candidate, size) <= width {
+            current = candidate;
+            continue;
+        }
+
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+
+        // Words wider than the page are broken between characters
+        for c in word.chars() {
+            current.push(c);
+            if text_width(&current, size) > width && current.chars().count() > 1 {
+                current.pop();
+                lines.push(std::mem::replace(&mut current, c.to_string()));
+            }
+        }
+    }
+    lines.push(current);
+    lines
+}
+
+/// Determine how an image can be stored in the document without decoding it
+pub(super) fn embeddable(bytes: &[u8]) -> Option<EmbeddableImage> {
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        return embeddable_jpeg(bytes);
+    }
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return embeddable_png(bytes);
+    }
+    None
+}
+
+/// JPEG data can be stored as-is, it just needs the dimensions from the frame header
+fn embeddable_jpeg(bytes: &[u8]) -> Option<EmbeddableImage> {
+    let mut idx = 2;
+    while idx + 9 < bytes.len() {
+        if bytes[idx] != 0xFF {
+            return None;
+        }
+        let marker = bytes[idx + 1];
+        let length = u16::from_be_bytes([bytes[idx + 2], bytes[idx + 3]]) as usize;
+
+        // Start of frame markers, excluding the Huffman and arithmetic coding tables
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            let height = u16::from_be_bytes([bytes[idx + 5], bytes[idx + 6]]) as u32;
+            let width = u16::from_be_bytes([bytes[idx + 7], bytes[idx + 8]]) as u32;
+            let color_space = match bytes[idx + 9] {
+                1 => "DeviceGray",
+                3 => "DeviceRGB",
+                4 => "DeviceCMYK",
+                _ => return None,
+            };
+            return Some(EmbeddableImage {
+                width,
+                height,
+                color_space,
+                filter: "DCTDecode",
+                decode_parms: None,
+                data: bytes.to_vec(),
+            });
+        }
+        idx += 2 + length;
+    }
+    None
+}
+
+/// PNG image data is a zlib stream that PDF readers can decode with the PNG predictors
+///
+/// Only 8-bit, non-interlaced images without an alpha channel can be stored this way.
+fn embeddable_png(bytes: &[u8]) -> Option<EmbeddableImage> {
+    let mut idx = 8;
+    let mut header = None;
+    let mut data = vec![];
+
+    while idx + 8 <= bytes.len() {
+        let length = u32::from_be_bytes(bytes[idx..idx + 4].try_into().ok()?) as usize;
+        let kind = &bytes[idx + 4..idx + 8];
+        let body = bytes.get(idx + 8..idx + 8 + length)?;
+        match kind {
+            b"IHDR" => header = Some(body),
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        // Skip the chunk's trailing checksum
+        idx += 12 + length;
+    }
+
+    let header = header.filter(|header| header.len() >= 13)?;
+    let width = u32::from_be_bytes(header[0..4].try_into().ok()?);
+    let height = u32::from_be_bytes(header[4..8].try_into().ok()?);
+    let (bit_depth, color_type, interlace) = (header[8], header[9], header[12]);
+
+    let (color_space, colors) = match color_type {
+        0 => ("DeviceGray", 1),
+        2 => ("DeviceRGB", 3),
+        _ => return None,
+    };
+    if bit_depth != 8 || interlace != 0 || data.is_empty() {
+        return None;
+    }
+
+    Some(EmbeddableImage {
+        width,
+        height,
+        color_space,
+        filter: "FlateDecode",
+        decode_parms: Some(format!(
+            "<< /Predictor 15 /Colors {colors} /BitsPerComponent 8 /Columns {width} >>"
+        )),
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::read, path::PathBuf};
+
+    use crate::exporters::pdf::document::{embeddable, encode, wrap, Document};
+
+    #[test]
+    fn can_encode_ascii() {
+        assert_eq!(encode("Hello world"), "Hello world");
+    }
+
+    #[test]
+    fn can_encode_special_chars() {
+        assert_eq!(encode("(a\\b)"), "\\(a\\\\b\\)");
+    }
+
+    #[test]
+    fn can_encode_win_ansi() {
+        assert_eq!(encode("café — ’"), "caf\\351 \\227 \\222");
+    }
+
+    #[test]
+    fn can_encode_unsupported() {
+        assert_eq!(encode("hi 🙂"), "hi ?");
+    }
+
+    #[test]
+    fn can_wrap_short() {
+        assert_eq!(wrap("Hello world", 500., 10.), vec!["Hello world"]);
+    }
+
+    #[test]
+    fn can_wrap_words() {
+        assert_eq!(
+            wrap("Hello world, how are you?", 65., 10.),
+            vec!["Hello world,", "how are you?"]
+        );
+    }
+
+    #[test]
+    fn can_wrap_long_word() {
+        assert_eq!(wrap("aaaaaaaaaa", 25., 10.), vec!["aaaa", "aaaa", "aa"]);
+    }
+
+    #[test]
+    fn can_get_jpeg_dimensions() {
+        // SOI, then a baseline frame header for a 3x2 RGB image
+        let bytes = [
+            0xFF, 0xD8, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x02, 0x00, 0x03, 0x03, 0x01, 0x22,
+            0x00,
+        ];
+        let image = embeddable(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.color_space, "DeviceRGB");
+        assert_eq!(image.filter, "DCTDecode");
+    }
+
+    #[test]
+    fn cant_embed_unknown() {
+        assert!(embeddable(b"GIF89a").is_none());
+        assert!(embeddable(&[]).is_none());
+    }
+
+    #[test]
+    fn can_write_document() {
+        let path = PathBuf::from("/tmp/can_write_document.pdf");
+        let mut document = Document::new(&path, "Test").unwrap();
+        document.add_line("Hello world").unwrap();
+        document.finish().unwrap();
+
+        let bytes = read(&path).unwrap();
+        let contents = String::from_utf8_lossy(&bytes);
+        assert!(contents.starts_with("%PDF-1.4"));
+        assert!(contents.contains("(Hello world) Tj"));
+        assert!(contents.contains("/Count 1"));
+        assert!(contents.ends_with("%%EOF\n"));
+    }
+}
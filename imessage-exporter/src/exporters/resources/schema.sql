@@ -0,0 +1,105 @@
+-- Schema for SQLite exports
+--
+-- IDs in the `handles`, `chats`, and `messages` tables match the `ROWID`s in the source
+-- iMessage database. Timestamps are ISO 8601 strings in UTC, i.e. `2022-05-17T20:29:42Z`,
+-- and are `NULL` when the event never happened.
+
+-- A phone number or email address that messages are sent from
+CREATE TABLE handles (
+    id INTEGER PRIMARY KEY,
+    -- Phone number or email address
+    identifier TEXT NOT NULL,
+    -- Handles that belong to the same person share a `person_id`
+    person_id INTEGER
+);
+
+-- A conversation thread
+CREATE TABLE chats (
+    id INTEGER PRIMARY KEY,
+    -- Phone number, email address, or group chat identifier
+    identifier TEXT NOT NULL,
+    -- iMessage, SMS, etc.
+    service TEXT,
+    -- Name given to the chat by its participants
+    display_name TEXT,
+    -- Chats with the same participants share a `conversation_id`, the same way
+    -- other export formats merge them into a single file
+    conversation_id INTEGER
+);
+
+-- The handles that participate in each chat
+CREATE TABLE chat_handles (
+    chat_id INTEGER NOT NULL REFERENCES chats (id),
+    handle_id INTEGER NOT NULL REFERENCES handles (id),
+    PRIMARY KEY (chat_id, handle_id)
+);
+
+-- A message, announcement, or SharePlay session
+CREATE TABLE messages (
+    id INTEGER PRIMARY KEY,
+    guid TEXT NOT NULL UNIQUE,
+    chat_id INTEGER REFERENCES chats (id),
+    -- `NULL` for messages sent by the database owner
+    sender_id INTEGER REFERENCES handles (id),
+    is_from_me INTEGER NOT NULL,
+    -- One of `message`, `announcement`, or `shareplay`
+    kind TEXT NOT NULL,
+    -- iMessage, SMS, etc.
+    service TEXT,
+    -- Message text, with attachments marked by U+FFFC OBJECT REPLACEMENT CHARACTER
+    text TEXT,
+    subject TEXT,
+    -- Bundle ID of the app that generated the message, if any
+    app TEXT,
+    -- Bubble or screen effect the message was sent with, i.e. `Confetti` or `InvisibleInk`
+    expressive TEXT,
+    -- The message this message is a threaded reply to
+    reply_to_id INTEGER REFERENCES messages (id),
+    sent_at TEXT,
+    delivered_at TEXT,
+    read_at TEXT,
+    edited_at TEXT,
+    -- 1 if the message was deleted but is still recoverable
+    is_deleted INTEGER NOT NULL
+);
+
+-- A file sent with a message
+CREATE TABLE attachments (
+    id INTEGER PRIMARY KEY,
+    message_id INTEGER NOT NULL REFERENCES messages (id),
+    -- Name of the file when it was sent
+    name TEXT,
+    mime_type TEXT,
+    uti TEXT,
+    size INTEGER NOT NULL,
+    is_sticker INTEGER NOT NULL,
+    -- Location of the file, relative to the export directory if it was copied;
+    -- `NULL` if the file is missing
+    path TEXT,
+    -- Apple's transcription of an audio message, if there is one
+    transcription TEXT
+);
+
+-- A tapback or sticker placed on a message
+CREATE TABLE reactions (
+    id INTEGER PRIMARY KEY,
+    message_id INTEGER NOT NULL REFERENCES messages (id),
+    -- Index of the part of the message the reaction applies to
+    part INTEGER NOT NULL,
+    -- `NULL` for reactions sent by the database owner
+    sender_id INTEGER REFERENCES handles (id),
+    is_from_me INTEGER NOT NULL,
+    -- One of `loved`, `liked`, `disliked`, `laughed`, `emphasized`, `questioned`, `emoji`, or `sticker`
+    kind TEXT NOT NULL,
+    -- The emoji used, for `emoji` reactions
+    emoji TEXT,
+    -- Location of the sticker image, for `sticker` reactions
+    sticker_path TEXT,
+    -- 1 if this event removed an earlier reaction
+    is_removal INTEGER NOT NULL,
+    sent_at TEXT
+);
+
+CREATE INDEX messages_chat ON messages (chat_id, sent_at);
+CREATE INDEX attachments_message ON attachments (message_id);
+CREATE INDEX reactions_message ON reactions (message_id);
@@ -0,0 +1,48 @@
+// Searches the messages in `SEARCH_INDEX`, which is loaded from `search_index.js`
+//
+// Each entry is `[link, conversation, sender, date, text]`. A message matches if its text
+// contains every word in the query, ignoring case.
+(function () {
+	const MAX_RESULTS = 200;
+	const input = document.getElementById("search");
+	const results = document.getElementById("results");
+	const haystacks = SEARCH_INDEX.map((entry) => entry[4].toLowerCase());
+
+	function render(entry) {
+		const [link, conversation, sender, date, text] = entry;
+		const item = document.createElement("li");
+		const anchor = document.createElement("a");
+		anchor.href = link;
+		anchor.textContent = conversation + " — " + date;
+		const from = document.createElement("span");
+		from.className = "sender";
+		from.textContent = sender + ": ";
+		const body = document.createElement("p");
+		body.appendChild(from);
+		body.appendChild(document.createTextNode(text));
+		item.appendChild(anchor);
+		item.appendChild(body);
+		return item;
+	}
+
+	input.addEventListener("input", function () {
+		results.replaceChildren();
+		const terms = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+		if (terms.length === 0) {
+			return;
+		}
+
+		let found = 0;
+		for (let i = 0; i < SEARCH_INDEX.length && found < MAX_RESULTS; i++) {
+			if (terms.every((term) => haystacks[i].includes(term))) {
+				results.appendChild(render(SEARCH_INDEX[i]));
+				found++;
+			}
+		}
+		if (found === 0) {
+			const item = document.createElement("li");
+			item.textContent = "No messages found";
+			results.appendChild(item);
+		}
+	});
+})();
@@ -0,0 +1,608 @@
+use std::collections::HashSet;
+
+use log::info;
+
+use rusqlite::{params, Connection};
+
+use crate::app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{
+        expressives::Expressive,
+        variants::{Tapback, Variant},
+    },
+    tables::{
+        attachment::{Attachment, MediaType},
+        handle::Handle,
+        messages::Message,
+        table::Table,
+    },
+    util::dates::get_local_time,
+};
+
+/// Name of the database messages are exported to
+const DATABASE_FILENAME: &str = "messages.sqlite";
+/// Tables and indexes in the exported database
+const SCHEMA: &str = include_str!("resources/schema.sql");
+
+/// Exports messages to a normalized `SQLite` database
+///
+/// Unlike the other exporters, which write a file per conversation, this writes every
+/// conversation to a single database described by `resources/schema.sql`.
+pub struct SQLite<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Connection to the exported database
+    pub db: Connection,
+    /// IDs of the handles written to the exported database
+    handles: HashSet<i32>,
+}
+
+impl<'a> SQLite<'a> {
+    /// Create the exported database and its tables
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut path = config.options.export_path.clone();
+        path.push(DATABASE_FILENAME);
+
+        let db = Connection::open(&path).map_err(RuntimeError::SqliteError)?;
+        db.execute_batch(SCHEMA)
+            .map_err(RuntimeError::SqliteError)?;
+        db.pragma_update(None, "foreign_keys", true)
+            .map_err(RuntimeError::SqliteError)?;
+
+        Ok(SQLite {
+            config,
+            db,
+            handles: HashSet::new(),
+        })
+    }
+
+    /// Write the handles and chats, then every message and its attachments and reactions
+    pub fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as sqlite...",
+            self.config.options.export_path.display()
+        );
+
+        // Write everything in a single transaction, which is much faster than one per row
+        self.db
+            .execute_batch("BEGIN")
+            .map_err(RuntimeError::SqliteError)?;
+
+        self.write_handles()?;
+        self.write_chats()?;
+
+        // Replies are linked to the messages they reply to once every message has been written
+        self.db
+            .execute_batch(
+                "CREATE TEMP TABLE source_threads (message_id INTEGER PRIMARY KEY, originator_guid TEXT NOT NULL)",
+            )
+            .map_err(RuntimeError::SqliteError)?;
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Tapbacks are written as reactions to the message they belong to
+            if !msg.is_tapback() {
+                self.write_message(&msg)?;
+                self.write_attachments(&msg)?;
+                self.write_reactions(&msg)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        // Link replies to the messages they reply to
+        self.db
+            .execute(
+                "UPDATE messages SET reply_to_id = (
+                    SELECT m.id FROM messages m JOIN source_threads t ON m.guid = t.originator_guid
+                    WHERE t.message_id = messages.id
+                )",
+                [],
+            )
+            .map_err(RuntimeError::SqliteError)?;
+        self.db
+            .execute_batch("COMMIT")
+            .map_err(RuntimeError::SqliteError)
+    }
+
+    /// Write every handle in the source database
+    fn write_handles(&mut self) -> Result<(), RuntimeError> {
+        let mut statement = Handle::get(&self.config.db).map_err(RuntimeError::DatabaseError)?;
+        let handles = statement
+            .query_map([], |row| Ok(Handle::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Handle(err)))?;
+
+        let mut insert = self
+            .db
+            .prepare("INSERT INTO handles (id, identifier, person_id) VALUES (?1, ?2, ?3)")
+            .map_err(RuntimeError::SqliteError)?;
+        for handle in handles {
+            let handle = Handle::extract(handle).map_err(RuntimeError::DatabaseError)?;
+            let person_id = self.config.real_participants.get(&handle.rowid);
+            insert
+                .execute(params![handle.rowid, handle.id, person_id])
+                .map_err(RuntimeError::SqliteError)?;
+            self.handles.insert(handle.rowid);
+        }
+        Ok(())
+    }
+
+    /// Write every chat in the source database and the handles that participate in it
+    fn write_chats(&self) -> Result<(), RuntimeError> {
+        let mut insert_chat = self
+            .db
+            .prepare("INSERT INTO chats (id, identifier, service, display_name, conversation_id) VALUES (?1, ?2, ?3, ?4, ?5)")
+            .map_err(RuntimeError::SqliteError)?;
+        let mut insert_participant = self
+            .db
+            .prepare("INSERT INTO chat_handles (chat_id, handle_id) VALUES (?1, ?2)")
+            .map_err(RuntimeError::SqliteError)?;
+
+        for (id, chat) in &self.config.chatrooms {
+            insert_chat
+                .execute(params![
+                    id,
+                    chat.chat_identifier,
+                    chat.service_name,
+                    chat.display_name(),
+                    self.config.real_chatrooms.get(id),
+                ])
+                .map_err(RuntimeError::SqliteError)?;
+
+            if let Some(participants) = self.config.chatroom_participants.get(id) {
+                for handle_id in participants.iter().filter(|h| self.handles.contains(h)) {
+                    insert_participant
+                        .execute(params![id, handle_id])
+                        .map_err(RuntimeError::SqliteError)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a single message
+    fn write_message(&self, message: &Message) -> Result<(), RuntimeError> {
+        let kind = if message.is_announcement() {
+            "announcement"
+        } else if message.is_shareplay() {
+            "shareplay"
+        } else {
+            "message"
+        };
+        let chat_id = message
+            .chat_id
+            .or(message.deleted_from)
+            .filter(|id| self.config.chatrooms.contains_key(id));
+        let expressive = match message.get_expressive() {
+            Expressive::Screen(effect) => Some(format!("{effect:?}")),
+            Expressive::Bubble(effect) => Some(format!("{effect:?}")),
+            Expressive::Unknown(effect) => Some(effect.to_string()),
+            Expressive::None => None,
+        };
+
+        self.db
+            .prepare_cached(
+                "INSERT INTO messages (
+                    id, guid, chat_id, sender_id, is_from_me, kind, service, text, subject, app,
+                    expressive, sent_at, delivered_at, read_at, edited_at, is_deleted
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            )
+            .and_then(|mut statement| {
+                statement.execute(params![
+                    message.rowid,
+                    message.guid,
+                    chat_id,
+                    self.sender(message),
+                    message.is_from_me(),
+                    kind,
+                    message.service,
+                    message.text,
+                    message.subject,
+                    message.balloon_bundle_id,
+                    expressive,
+                    self.timestamp(message.date),
+                    self.timestamp(message.date_delivered),
+                    self.timestamp(message.date_read),
+                    self.timestamp(message.date_edited),
+                    message.is_deleted(),
+                ])
+            })
+            .map_err(RuntimeError::SqliteError)?;
+
+        if let Some(originator) = &message.thread_originator_guid {
+            self.db
+                .prepare_cached(
+                    "INSERT INTO source_threads (message_id, originator_guid) VALUES (?1, ?2)",
+                )
+                .and_then(|mut statement| statement.execute(params![message.rowid, originator]))
+                .map_err(RuntimeError::SqliteError)?;
+        }
+        Ok(())
+    }
+
+    /// Write the attachments for a message, copying them if requested
+    fn write_attachments(&self, message: &Message) -> Result<(), RuntimeError> {
+        if !message.has_attachments() {
+            return Ok(());
+        }
+
+        let mut attachments = Attachment::from_message(&self.config.db, message)
+            .map_err(RuntimeError::DatabaseError)?;
+        for attachment in attachments.iter_mut() {
+            let path = self.attachment_path(message, attachment);
+            let transcription = match attachment.mime_type() {
+                MediaType::Audio(_) => message.audio_transcription(),
+                _ => None,
+            };
+            self.db
+                .prepare_cached(
+                    "INSERT INTO attachments (message_id, name, mime_type, uti, size, is_sticker, path, transcription)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                )
+                .and_then(|mut statement| {
+                    statement.execute(params![
+                        message.rowid,
+                        attachment.transfer_name,
+                        attachment.mime_type,
+                        attachment.uti,
+                        attachment.total_bytes,
+                        attachment.is_sticker,
+                        path,
+                        transcription,
+                    ])
+                })
+                .map_err(RuntimeError::SqliteError)?;
+        }
+        Ok(())
+    }
+
+    /// Write the tapbacks and stickers placed on a message
+    fn write_reactions(&self, message: &Message) -> Result<(), RuntimeError> {
+        let Some(reactions) = self.config.tapbacks.get(&message.guid) else {
+            return Ok(());
+        };
+
+        for reaction in reactions.values().flatten() {
+            let (part, kind, emoji, sticker_path, is_removal) = match reaction.variant() {
+                Variant::Tapback(part, added, tapback) => {
+                    let (kind, emoji) = match tapback {
+                        Tapback::Loved => ("loved", None),
+                        Tapback::Liked => ("liked", None),
+                        Tapback::Disliked => ("disliked", None),
+                        Tapback::Laughed => ("laughed", None),
+                        Tapback::Emphasized => ("emphasized", None),
+                        Tapback::Questioned => ("questioned", None),
+                        Tapback::Emoji(emoji) => ("emoji", emoji.map(str::to_string)),
+                    };
+                    (part, kind, emoji, None, !added)
+                }
+                Variant::Sticker(part) => {
+                    // Sticker messages have only one attachment, the sticker image
+                    let mut stickers = Attachment::from_message(&self.config.db, reaction)
+                        .map_err(RuntimeError::DatabaseError)?;
+                    let path = stickers
+                        .get_mut(0)
+                        .and_then(|sticker| self.attachment_path(reaction, sticker));
+                    (part, "sticker", None, path, false)
+                }
+                _ => continue,
+            };
+
+            self.db
+                .prepare_cached(
+                    "INSERT INTO reactions (message_id, part, sender_id, is_from_me, kind, emoji, sticker_path, is_removal, sent_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                )
+                .and_then(|mut statement| {
+                    statement.execute(params![
+                        message.rowid,
+                        part,
+                        self.sender(reaction),
+                        reaction.is_from_me(),
+                        kind,
+                        emoji,
+                        sticker_path,
+                        is_removal,
+                        self.timestamp(reaction.date),
+                    ])
+                })
+                .map_err(RuntimeError::SqliteError)?;
+        }
+        Ok(())
+    }
+
+    /// Copy an attachment if requested, returning where it can be found
+    fn attachment_path(&self, message: &Message, attachment: &mut Attachment) -> Option<String> {
+        self.config.options.attachment_manager.handle_attachment(
+            message,
+            attachment,
+            self.config,
+        )?;
+        Some(self.config.message_attachment_path(attachment))
+    }
+
+    /// Get the ID of the handle that sent a message, if it was not the database owner
+    fn sender(&self, message: &Message) -> Option<i32> {
+        if message.is_from_me() {
+            return None;
+        }
+        message.handle_id.filter(|id| self.handles.contains(id))
+    }
+
+    /// Format a timestamp from the source database, or `None` if the event never happened
+    fn timestamp(&self, stamp: i64) -> Option<String> {
+        if stamp == 0 {
+            return None;
+        }
+        get_local_time(&stamp, &self.config.offset)
+            .ok()
+            .map(|date| date.naive_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs::{create_dir_all, remove_file},
+        path::PathBuf,
+    };
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        Config, Options, SQLite,
+    };
+    use imessage_database::{
+        tables::{messages::Message, table::get_connection},
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options(export_path: &str) -> Options {
+        // Each test gets its own database
+        let export_path = PathBuf::from(export_path);
+        create_dir_all(&export_path).unwrap();
+        let _ = remove_file(export_path.join("messages.sqlite"));
+
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path,
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options("/tmp/sqlite_can_create");
+        let config = fake_config(options);
+        let exporter = SQLite::new(&config).unwrap();
+
+        let tables: Vec<String> = exporter
+            .db
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(
+            tables,
+            vec![
+                "attachments",
+                "chat_handles",
+                "chats",
+                "handles",
+                "messages",
+                "reactions"
+            ]
+        );
+    }
+
+    #[test]
+    fn can_format_timestamp() {
+        let options = fake_options("/tmp/sqlite_can_format_timestamp");
+        let config = fake_config(options);
+        let exporter = SQLite::new(&config).unwrap();
+
+        assert_eq!(exporter.timestamp(0), None);
+        assert_eq!(
+            exporter.timestamp(674526582885055488),
+            Some("2022-05-18T00:29:42Z".to_string())
+        );
+    }
+
+    #[test]
+    fn can_write_message() {
+        let options = fake_options("/tmp/sqlite_can_write_message");
+        let config = fake_config(options);
+        let exporter = SQLite::new(&config).unwrap();
+
+        let mut message = blank();
+        message.rowid = 1;
+        message.guid = "guid".to_string();
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.date = 674526582885055488;
+        message.expressive_send_style_id =
+            Some("com.apple.MobileSMS.expressivesend.impact".to_string());
+        exporter.write_message(&message).unwrap();
+
+        let row: (String, Option<i32>, bool, String, String, String, Option<String>) = exporter
+            .db
+            .query_row(
+                "SELECT text, sender_id, is_from_me, kind, sent_at, expressive, read_at FROM messages WHERE id = 1",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                        row.get(6)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            (
+                "Hello world".to_string(),
+                None,
+                true,
+                "message".to_string(),
+                "2022-05-18T00:29:42Z".to_string(),
+                "Slam".to_string(),
+                None
+            )
+        );
+    }
+}
@@ -9,26 +9,35 @@ use std::{
     path::PathBuf,
 };
 
+use log::info;
+
 use crate::{
     app::{
         attachment_manager::AttachmentManager, error::RuntimeError,
-        progress::build_progress_bar_export, runtime::Config,
+        progress::build_progress_bar_export, roster::Roster, runtime::Config,
+        txt_format::TxtFormat,
+    },
+    exporters::{
+        exporter::{BalloonFormatter, Exporter, TextEffectFormatter, Writer},
+        markdown::emphasize,
     },
-    exporters::exporter::{BalloonFormatter, Exporter, Writer},
 };
 
 use imessage_database::{
     error::{plist::PlistParseError, table::TableError},
     message_types::{
         app::AppMessage,
+        app_decoder::{summarize_app, AppSummary},
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
         edited::{EditStatus, EditedMessage},
         expressives::{BubbleEffect, Expressive, ScreenEffect},
+        facetime::FaceTimeMessage,
         handwriting::HandwrittenMessage,
+        icloud_photos::{ICloudPhotosKind, ICloudPhotosMessage},
         music::MusicMessage,
         placemark::PlacemarkMessage,
-        text_effects::TextEffect,
+        text_effects::{Animation, DataDetector, Style, TextEffect, Unit},
         url::URLMessage,
         variants::{Announcement, BalloonProvider, CustomBalloon, URLOverride, Variant},
     },
@@ -51,6 +60,8 @@ pub struct TXT<'a> {
     pub files: HashMap<String, BufWriter<File>>,
     /// Writer instance for orphaned messages
     pub orphaned: BufWriter<File>,
+    /// Layout of each message
+    format: TxtFormat,
 }
 
 impl<'a> Exporter<'a> for TXT<'a> {
@@ -65,16 +76,22 @@ impl<'a> Exporter<'a> for TXT<'a> {
             .open(&orphaned)
             .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
 
+        let format = match &config.options.txt_format {
+            Some(format) => TxtFormat::parse(format).map_err(RuntimeError::InvalidOptions)?,
+            None => TxtFormat::default(),
+        };
+
         Ok(TXT {
             config,
             files: HashMap::new(),
             orphaned: BufWriter::new(file),
+            format,
         })
     }
 
     fn iter_messages(&mut self) -> Result<(), RuntimeError> {
         // Tell the user what we are doing
-        eprintln!(
+        info!(
             "Exporting to {} as txt...",
             self.config.options.export_path.display()
         );
@@ -147,13 +164,24 @@ impl<'a> Exporter<'a> for TXT<'a> {
                         path.push(self.config.filename(chatroom));
                         path.set_extension("txt");
 
+                        // If the file already exists, don't write the participants again
+                        let file_exists = path.exists();
+
                         let file = File::options()
                             .append(true)
                             .create(true)
                             .open(&path)
                             .map_err(|err| RuntimeError::CreateError(err, path))?;
 
-                        Ok(entry.insert(BufWriter::new(file)))
+                        let mut buf = BufWriter::new(file);
+
+                        if !file_exists {
+                            if let Some(roster) = Roster::new(self.config, chatroom) {
+                                TXT::write_to_file(&mut buf, &TXT::format_roster(&roster))?;
+                            }
+                        }
+
+                        Ok(entry.insert(buf))
                     }
                 };
             }
@@ -165,23 +193,9 @@ impl<'a> Exporter<'a> for TXT<'a> {
 impl<'a> Writer<'a> for TXT<'a> {
     fn format_message(&self, message: &Message, indent_size: usize) -> Result<String, TableError> {
         let indent = String::from_iter((0..indent_size).map(|_| " "));
-        // Data we want to write to a file
+        // Data we want to write to a file, which is laid out by the format string once it is built
         let mut formatted_message = String::new();
 
-        // Add message date
-        self.add_line(&mut formatted_message, &self.get_time(message), &indent);
-
-        // Add message sender
-        self.add_line(
-            &mut formatted_message,
-            self.config.who(
-                message.handle_id,
-                message.is_from_me(),
-                &message.destination_caller_id,
-            ),
-            &indent,
-        );
-
         // If message was deleted, annotate it
         if message.is_deleted() {
             self.add_line(
@@ -278,6 +292,13 @@ impl<'a> Writer<'a> for TXT<'a> {
                                     self.add_line(&mut formatted_message, result, &indent);
                                 }
                             }
+                            if let Some(transcription) = message.audio_transcription() {
+                                self.add_line(
+                                    &mut formatted_message,
+                                    &self.format_transcription(transcription),
+                                    &indent,
+                                );
+                            }
                         }
                     }
                     // Attachment does not exist in attachments table
@@ -364,6 +385,18 @@ impl<'a> Writer<'a> for TXT<'a> {
             );
         }
 
+        // Add message date and sender
+        let mut formatted_message = self.format.render(
+            |date_format| self.get_time_as(message, date_format),
+            self.config.who(
+                message.handle_id,
+                message.is_from_me(),
+                &message.destination_caller_id,
+            ),
+            &formatted_message,
+            &indent,
+        );
+
         if indent.is_empty() {
             // Add a newline for top-level messages
             formatted_message.push('\n');
@@ -377,6 +410,10 @@ impl<'a> Writer<'a> for TXT<'a> {
         attachment: &'a mut Attachment,
         message: &Message,
     ) -> Result<String, &'a str> {
+        if let Some(removed) = self.config.removed_attachment(message, attachment) {
+            return Ok(removed.to_string());
+        }
+
         // Copy the file, if requested
         self.config
             .options
@@ -385,7 +422,18 @@ impl<'a> Writer<'a> for TXT<'a> {
             .ok_or(attachment.filename())?;
 
         // Build a relative filepath from the fully qualified one on the `Attachment`
-        Ok(self.config.message_attachment_path(attachment))
+        let path = self.config.message_attachment_path(attachment);
+
+        // Show who a shared contact card is for, since the file itself is not readable as text
+        if let Some(cards) = self.format_contact_cards(attachment) {
+            return Ok(format!("{cards}: {path}"));
+        }
+
+        // Label audio with its length
+        Ok(match self.format_audio_duration(attachment) {
+            Some(duration) => format!("Audio ({duration}): {path}"),
+            None => path,
+        })
     }
 
     fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
@@ -429,11 +477,24 @@ impl<'a> Writer<'a> for TXT<'a> {
                 }
             }
 
+            if message.is_digital_touch() {
+                let payload = message
+                    .raw_payload_data(&self.config.db)
+                    .unwrap_or_default();
+                return Ok(self.format_digital_touch(message, &payload, indent));
+            }
+
             if let Some(payload) = message.payload_data(&self.config.db) {
                 // Handle URL messages separately since they are a special case
                 let res = if message.is_url() {
-                    let parsed = parse_plist(&payload)?;
-                    let bubble = URLMessage::get_url_message_override(&parsed)?;
+                    let parsed = match parse_plist(&payload) {
+                        Ok(parsed) => parsed,
+                        Err(why) => return message.text.clone().ok_or(why),
+                    };
+                    let bubble = match URLMessage::get_url_message_override(&parsed) {
+                        Ok(bubble) => bubble,
+                        Err(why) => return message.text.clone().ok_or(why),
+                    };
                     match bubble {
                         URLOverride::Normal(balloon) => self.format_url(message, &balloon, indent),
                         URLOverride::AppleMusic(balloon) => self.format_music(&balloon, indent),
@@ -444,6 +505,10 @@ impl<'a> Writer<'a> for TXT<'a> {
                         URLOverride::SharedPlacemark(balloon) => {
                             self.format_placemark(&balloon, indent)
                         }
+                        URLOverride::FaceTime(balloon) => self.format_facetime(&balloon, indent),
+                        URLOverride::ICloudPhotos(balloon) => {
+                            self.format_icloud_photos(&balloon, indent)
+                        }
                     }
                 // Handwriting uses a different payload type than the rest of the branches
                 } else {
@@ -460,6 +525,7 @@ impl<'a> Writer<'a> for TXT<'a> {
                             CustomBalloon::CheckIn => self.format_check_in(&bubble, indent),
                             CustomBalloon::FindMy => self.format_find_my(&bubble, indent),
                             CustomBalloon::Handwriting => unreachable!(),
+                            CustomBalloon::DigitalTouch => unreachable!(),
                             CustomBalloon::URL => unreachable!(),
                         },
                         Err(why) => return Err(why),
@@ -543,7 +609,7 @@ impl<'a> Writer<'a> for TXT<'a> {
             who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
         }
 
-        let timestamp = format(&msg.date(&self.config.offset));
+        let timestamp = self.format_date(msg, self.format.date_format());
 
         return match msg.get_announcement() {
             Some(announcement) => match announcement {
@@ -553,6 +619,30 @@ impl<'a> Writer<'a> for TXT<'a> {
                 Announcement::PhotoChange => {
                     format!("{timestamp} {who} changed the group photo.\n\n")
                 }
+                Announcement::PhotoRemoved => {
+                    format!("{timestamp} {who} removed the group photo.\n\n")
+                }
+                Announcement::ParticipantAdded(handle) => {
+                    let added = self
+                        .config
+                        .who(Some(handle), false, &msg.destination_caller_id);
+                    format!("{timestamp} {who} added {added} to the conversation.\n\n")
+                }
+                Announcement::ParticipantRemoved(handle) => {
+                    let removed = self
+                        .config
+                        .who(Some(handle), false, &msg.destination_caller_id);
+                    format!("{timestamp} {who} removed {removed} from the conversation.\n\n")
+                }
+                Announcement::ParticipantLeft => {
+                    format!("{timestamp} {who} left the conversation.\n\n")
+                }
+                Announcement::AudioMessageKept => {
+                    format!("{timestamp} {who} kept an audio message.\n\n")
+                }
+                Announcement::UnknownEvent(num) => {
+                    format!("{timestamp} {who} performed unknown system event {num}.\n\n")
+                }
                 Announcement::Unknown(num) => {
                     format!("{timestamp} {who} performed unknown action {num}.\n\n")
                 }
@@ -576,6 +666,10 @@ impl<'a> Writer<'a> for TXT<'a> {
         "Shared location!"
     }
 
+    fn format_transcription(&self, transcription: &str) -> String {
+        format!("Transcription: {transcription}")
+    }
+
     fn format_edited(
         &self,
         msg: &'a Message,
@@ -651,9 +745,17 @@ impl<'a> Writer<'a> for TXT<'a> {
         None
     }
 
-    fn format_attributed(&'a self, msg: &'a str, _: &'a TextEffect) -> Cow<str> {
-        // There isn't really a way to represent formatted text in a plain text export
-        Cow::Borrowed(msg)
+    fn format_attributed(&'a self, msg: &'a str, attribute: &'a TextEffect) -> Cow<str> {
+        match attribute {
+            TextEffect::Default => Cow::Borrowed(msg),
+            TextEffect::Mention(mentioned) => Cow::Owned(self.format_mention(msg, mentioned)),
+            TextEffect::Link(url) => Cow::Owned(self.format_link(msg, url)),
+            TextEffect::OTP => Cow::Owned(self.format_otp(msg)),
+            TextEffect::Styles(styles) => Cow::Owned(self.format_styles(msg, styles)),
+            TextEffect::Animated(animation) => Cow::Owned(self.format_animated(msg, animation)),
+            TextEffect::Conversion(unit) => Cow::Owned(self.format_conversion(msg, unit)),
+            TextEffect::Detected(detector) => Cow::Owned(self.format_detected(msg, detector)),
+        }
     }
 
     fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
@@ -758,6 +860,40 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
         out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
     }
 
+    fn format_facetime(&self, balloon: &FaceTimeMessage, indent: &'a str) -> String {
+        let mut out_s = String::from(indent);
+        out_s.push_str("FaceTime call link shared");
+        if let Some(title) = balloon.title {
+            out_s.push_str(": ");
+            out_s.push_str(title);
+        }
+        out_s.push('\n');
+        self.add_line(&mut out_s, balloon.url, indent);
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
+    fn format_icloud_photos(&self, balloon: &ICloudPhotosMessage, indent: &'a str) -> String {
+        let mut out_s = String::from(indent);
+        out_s.push_str(match balloon.kind {
+            ICloudPhotosKind::SharedAlbum => "Shared Album invitation",
+            ICloudPhotosKind::PhotoLink => "iCloud Photos link shared",
+        });
+        if let Some(title) = balloon.title {
+            out_s.push_str(": ");
+            out_s.push_str(title);
+        }
+        out_s.push('\n');
+        if let Some(summary) = balloon.summary {
+            self.add_line(&mut out_s, summary, indent);
+        }
+        self.add_line(&mut out_s, balloon.url, indent);
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
     fn format_placemark(&self, balloon: &PlacemarkMessage, indent: &'a str) -> String {
         let mut out_s = String::from(indent);
 
@@ -769,6 +905,10 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
             self.add_line(&mut out_s, url, indent);
         }
 
+        if let Some((latitude, longitude)) = balloon.coordinates() {
+            self.add_line(&mut out_s, &format!("{latitude}, {longitude}"), indent);
+        }
+
         if let Some(name) = balloon.placemark.name {
             self.add_line(&mut out_s, name, indent);
         }
@@ -823,7 +963,9 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
             AttachmentManager::Disabled => balloon
                 .render_ascii(40)
                 .replace("\n", &format!("{indent}\n")),
-            AttachmentManager::Compatible | AttachmentManager::Efficient => self
+            AttachmentManager::Compatible
+            | AttachmentManager::Efficient
+            | AttachmentManager::Link => self
                 .config
                 .options
                 .attachment_manager
@@ -842,6 +984,23 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
         }
     }
 
+    fn format_digital_touch(&self, msg: &Message, payload: &[u8], indent: &str) -> String {
+        match self
+            .config
+            .options
+            .attachment_manager
+            .handle_digital_touch(msg, payload, self.config)
+        {
+            Some(filepath) => format!(
+                "{indent}Digital Touch message, strokes saved to {}",
+                self.config
+                    .relative_path(filepath.clone())
+                    .unwrap_or(filepath.display().to_string())
+            ),
+            None => format!("{indent}Digital Touch message"),
+        }
+    }
+
     fn format_apple_pay(&self, balloon: &AppMessage, indent: &str) -> String {
         let mut out_s = String::from(indent);
         if let Some(caption) = balloon.caption {
@@ -950,6 +1109,11 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
         _: &mut Vec<Attachment>,
         indent: &str,
     ) -> String {
+        // Apps we know how to read are described in a sentence instead of listing every field
+        if let Some(summary) = summarize_app(bundle_id, balloon) {
+            return self.format_app_summary(&summary, indent);
+        }
+
         let mut out_s = String::from(indent);
 
         if let Some(name) = balloon.app_name {
@@ -991,7 +1155,126 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
     }
 }
 
+impl<'a> TextEffectFormatter for TXT<'a> {
+    fn format_mention(&self, text: &str, mentioned: &str) -> String {
+        // Name the contact when the text of the mention does not
+        match self.config.mention_name(mentioned) {
+            name if name == text => format!("@{text}"),
+            name => format!("@{text} ({name})"),
+        }
+    }
+
+    fn format_link(&self, text: &str, _: &str) -> String {
+        text.to_string()
+    }
+
+    fn format_otp(&self, text: &str) -> String {
+        text.to_string()
+    }
+
+    fn format_conversion(&self, text: &str, _: &Unit) -> String {
+        text.to_string()
+    }
+
+    fn format_styles(&self, text: &str, styles: &[Style]) -> String {
+        // Plain text has no formatting, so mark styles the way people type them
+        let (prefix, suffix): (String, String) = styles.iter().rev().fold(
+            (String::new(), String::new()),
+            |(mut prefix, mut suffix), style| {
+                let (open, close) = match style {
+                    Style::Bold => ("**", "**"),
+                    Style::Italic => ("*", "*"),
+                    Style::Strikethrough => ("~~", "~~"),
+                    Style::Underline => ("_", "_"),
+                };
+                prefix.push_str(open);
+                suffix.insert_str(0, close);
+                (prefix, suffix)
+            },
+        );
+
+        emphasize(text, &prefix, &suffix)
+    }
+
+    fn format_animated(&self, text: &str, _: &Animation) -> String {
+        text.to_string()
+    }
+
+    fn format_detected(&self, text: &str, _: &DataDetector) -> String {
+        text.to_string()
+    }
+}
+
 impl<'a> TXT<'a> {
+    /// List the members of a group chat and the changes to them, for the top of its file
+    fn format_roster(roster: &Roster) -> String {
+        let mut out_s = format!("Participants: {}\n", roster.members.join(", "));
+        if !roster.changes.is_empty() {
+            out_s.push_str("Membership changes:\n");
+            for change in &roster.changes {
+                out_s.push_str(change);
+                out_s.push('\n');
+            }
+        }
+        out_s.push('\n');
+        out_s
+    }
+
+    /// Describe the contacts in a contact card attachment, if it holds any
+    fn format_contact_cards(&self, attachment: &Attachment) -> Option<String> {
+        let cards = attachment
+            .get_contact_cards(
+                &self.config.options.platform,
+                &self.config.options.db_path,
+                self.config.options.attachment_root.as_deref(),
+            )
+            .ok()?;
+        let described: Vec<String> = cards
+            .iter()
+            .filter_map(|card| {
+                let name = card.display_name()?;
+                let details = card.details();
+                Some(match details.is_empty() {
+                    true => name.to_string(),
+                    false => format!("{name} ({})", details.join(", ")),
+                })
+            })
+            .collect();
+        match described.is_empty() {
+            true => None,
+            false => Some(format!("Contact card for {}", described.join("; "))),
+        }
+    }
+
+    /// Get the length of an audio attachment, if it can be read
+    fn format_audio_duration(&self, attachment: &Attachment) -> Option<String> {
+        attachment
+            .get_audio_metadata(
+                &self.config.options.platform,
+                &self.config.options.db_path,
+                self.config.options.attachment_root.as_deref(),
+            )
+            .ok()
+            .flatten()
+            .map(|audio| audio.format_duration())
+    }
+
+    /// Format the date a message was sent, using a custom `strftime` format if one is provided
+    fn format_date(&self, message: &Message, date_format: Option<&str>) -> String {
+        match (message.date(&self.config.offset), date_format) {
+            (Ok(date), Some(date_format)) => date.format(date_format).to_string(),
+            (date, _) => format(&date),
+        }
+    }
+
+    /// Get the time for a message's date placeholder; custom date formats omit the read receipt
+    fn get_time_as(&self, message: &Message, date_format: Option<&str>) -> String {
+        match date_format {
+            Some(_) => self.format_date(message, date_format),
+            None => self.get_time(message),
+        }
+    }
+
     fn get_time(&self, message: &Message) -> String {
         let mut date = format(&message.date(&self.config.offset));
         let read_after = message.time_until_read(&self.config.offset);
@@ -1005,9 +1288,24 @@ impl<'a> TXT<'a> {
                 date.push_str(&format!(" (Read by {who} after {time})"));
             }
         }
+        if let Some(receipts) = self.config.receipts(message) {
+            date.push_str(&format!(" ({receipts})"));
+        }
         date
     }
 
+    /// Format the description of an app message, followed by its details
+    fn format_app_summary(&self, summary: &AppSummary, indent: &str) -> String {
+        let mut out_s = format!("{indent}{} message: {}\n", summary.app, summary.description);
+        summary
+            .details
+            .iter()
+            .for_each(|detail| self.add_line(&mut out_s, detail, indent));
+
+        // We want to keep the newlines between blocks, but the last one should be removed
+        out_s.strip_suffix('\n').unwrap_or(&out_s).to_string()
+    }
+
     fn add_line(&self, string: &mut String, part: &str, indent: &str) {
         if !part.is_empty() {
             string.push_str(indent);
@@ -1021,13 +1319,18 @@ impl<'a> TXT<'a> {
 mod tests {
     use std::{
         collections::HashMap,
-        env::{current_dir, set_var},
+        env::{current_dir, set_var, temp_dir},
+        fs::{create_dir_all, write},
         path::PathBuf,
     };
 
     use crate::{
-        app::attachment_manager::AttachmentManager, exporters::exporter::Writer, Config, Exporter,
-        Options, TXT,
+        app::{
+            attachment_manager::AttachmentManager, chat_type::ChatType, roster::Roster,
+            theme::Theme,
+        },
+        exporters::exporter::Writer,
+        Config, Exporter, Options, TXT,
     };
     use imessage_database::{
         tables::{
@@ -1068,6 +1371,8 @@ mod tests {
             thread_originator_guid: None,
             thread_originator_part: None,
             date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
             chat_id: None,
             associated_message_emoji: None,
             num_attachments: 0,
@@ -1092,6 +1397,39 @@ mod tests {
             use_caller_id: false,
             platform: Platform::macOS,
             ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
         }
     }
 
@@ -1103,11 +1441,23 @@ mod tests {
             chatroom_participants: HashMap::new(),
             participants: HashMap::new(),
             real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
             tapbacks: HashMap::new(),
             options,
             offset: get_offset(),
             db,
             converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
         }
     }
 
@@ -1122,6 +1472,7 @@ mod tests {
             is_sticker: false,
             hide_attachment: 0,
             copied_path: None,
+            backup_root: None,
         }
     }
 
@@ -1230,6 +1581,31 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_txt_from_me_expressive() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+        message.expressive_send_style_id =
+            Some("com.apple.MobileSMS.expressivesend.invisibleink".to_string());
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "May 17, 2022  5:29:42 PM\nMe\nHello world\nSent with Invisible Ink\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_txt_from_me_normal_deleted() {
         // Set timezone to PST for consistent Local time
@@ -1388,6 +1764,132 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_txt_custom_format() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let mut options = fake_options();
+        options.txt_format = Some("[{date:%Y-%m-%d %H:%M:%S}] <{sender}> {text}".to_string());
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "[2022-05-17 17:29:42] <Me> Hello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_txt_announcement_custom_format() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let mut options = fake_options();
+        options.txt_format = Some("{date:%Y-%m-%d %H:%M:%S}\t{sender}\t{text}".to_string());
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.group_title = Some("Hello world".to_string());
+        message.is_from_me = true;
+
+        let actual = exporter.format_announcement(&message);
+        let expected = "2022-05-17 17:29:42 You renamed the conversation to Hello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_txt_announcement_participant_removed() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config.participants.insert(1, "Alice".to_string());
+        config.participants.insert(2, "Bob".to_string());
+
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.handle_id = Some(1);
+        message.item_type = 1;
+        message.group_action_type = 1;
+        message.other_handle = 2;
+
+        let actual = exporter.format_announcement(&message);
+        let expected = "May 17, 2022  5:29:42 PM Alice removed Bob from the conversation.\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_txt_roster() {
+        let roster = Roster {
+            members: vec!["Alice".to_string(), "Bob".to_string(), "Me".to_string()],
+            changes: vec!["May 17, 2022  5:29:42 PM: Me added Bob".to_string()],
+        };
+
+        let actual = TXT::format_roster(&roster);
+        let expected = "Participants: Alice, Bob, Me\nMembership changes:\nMay 17, 2022  5:29:42 PM: Me added Bob\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_txt_roster_no_changes() {
+        let roster = Roster {
+            members: vec!["Alice".to_string(), "Me".to_string()],
+            changes: vec![],
+        };
+
+        let actual = TXT::format_roster(&roster);
+        let expected = "Participants: Alice, Me\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_txt_announcement_audio_message_kept() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config.participants.insert(1, "Alice".to_string());
+
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.handle_id = Some(1);
+        message.item_type = 5;
+
+        let actual = exporter.format_announcement(&message);
+        let expected = "May 17, 2022  5:29:42 PM Alice kept an audio message.\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_txt_announcement() {
         // Set timezone to PST for consistent Local time
@@ -1652,6 +2154,76 @@ mod tests {
         assert_eq!(actual, "a/b/c/d.jpg");
     }
 
+    #[test]
+    fn can_format_txt_attachment_contact_card() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let message = blank();
+
+        let dir = temp_dir().join("imessage-exporter-txt-contact-card");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("Jane Doe.vcf");
+        write(
+            &path,
+            "BEGIN:VCARD\nFN:Jane Doe\nTEL:+15558675309\nEMAIL:jane@example.com\nEND:VCARD\n",
+        )
+        .unwrap();
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("text/vcard".to_string());
+        attachment.filename = Some(path.display().to_string());
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(
+            actual,
+            format!(
+                "Contact card for Jane Doe (+15558675309, jane@example.com): {}",
+                path.display()
+            )
+        );
+    }
+
+    #[test]
+    fn can_format_txt_attachment_audio_duration() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let message = blank();
+
+        // An `M4A` file that is 65 seconds long at a timescale of 1 kHz
+        let mut mvhd = vec![0; 12];
+        mvhd.extend_from_slice(&1000u32.to_be_bytes());
+        mvhd.extend_from_slice(&65000u32.to_be_bytes());
+        let mut data = 16u32.to_be_bytes().to_vec();
+        data.extend_from_slice(b"ftypM4A \0\0\0\0");
+        data.extend_from_slice(&(mvhd.len() as u32 + 16).to_be_bytes());
+        data.extend_from_slice(b"moov");
+        data.extend_from_slice(&(mvhd.len() as u32 + 8).to_be_bytes());
+        data.extend_from_slice(b"mvhd");
+        data.extend_from_slice(&mvhd);
+
+        let dir = temp_dir().join("imessage-exporter-txt-audio");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("Audio Message.m4a");
+        write(&path, data).unwrap();
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("audio/mp4".to_string());
+        attachment.filename = Some(path.display().to_string());
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(actual, format!("Audio (1:05): {}", path.display()));
+    }
+
     #[test]
     fn can_format_txt_attachment_macos_invalid() {
         // Create exporter
@@ -1669,6 +2241,59 @@ mod tests {
         assert_eq!(actual, Err("d.jpg"));
     }
 
+    #[test]
+    fn can_format_txt_attachment_expired_audio() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = blank();
+        message.is_audio_message = true;
+        message.expire_state = 1;
+
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("audio/x-caf".to_string());
+        attachment.filename = None;
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(actual, "Expired audio message");
+    }
+
+    #[test]
+    fn can_format_txt_transcription() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let actual = exporter.format_transcription("Running ten minutes late");
+
+        assert_eq!(actual, "Transcription: Running ten minutes late");
+    }
+
+    #[test]
+    fn can_format_txt_attachment_hidden() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let message = blank();
+
+        let mut attachment = fake_attachment();
+        attachment.hide_attachment = 1;
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(actual, "Deleted attachment");
+    }
+
     #[test]
     fn can_format_txt_attachment_ios() {
         // Create exporter
@@ -1759,6 +2384,8 @@ mod balloon_format_tests {
         app::AppMessage,
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
+        facetime::FaceTimeMessage,
+        icloud_photos::{ICloudPhotosKind, ICloudPhotosMessage},
         music::MusicMessage,
         placemark::{Placemark, PlacemarkMessage},
         url::URLMessage,
@@ -1936,6 +2563,58 @@ mod balloon_format_tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn can_format_txt_facetime() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let balloon = FaceTimeMessage {
+            url: "https://facetime.apple.com/join#v=1&p=AbCdEf",
+            title: Some("Book Club"),
+        };
+
+        let expected = exporter.format_facetime(&balloon, "");
+        let actual =
+            "FaceTime call link shared: Book Club\nhttps://facetime.apple.com/join#v=1&p=AbCdEf";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_format_txt_icloud_photos() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let balloon = ICloudPhotosMessage {
+            kind: ICloudPhotosKind::SharedAlbum,
+            url: "https://www.icloud.com/sharedalbum/#B0aGWZuqDGKvJx",
+            title: Some("Ski Trip"),
+            summary: Some("12 Photos"),
+        };
+
+        let expected = exporter.format_icloud_photos(&balloon, "");
+        let actual = "Shared Album invitation: Ski Trip\n12 Photos\nhttps://www.icloud.com/sharedalbum/#B0aGWZuqDGKvJx";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_format_txt_digital_touch() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let expected = exporter.format_digital_touch(&blank(), &[], "");
+        let actual = "Digital Touch message";
+
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn can_format_txt_check_in_timer() {
         // Set timezone to PST for consistent Local time
@@ -2101,6 +2780,37 @@ mod balloon_format_tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn can_format_txt_known_app() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let balloon = AppMessage {
+            image: None,
+            url: None,
+            title: None,
+            subtitle: None,
+            caption: Some("Your move."),
+            subcaption: None,
+            trailing_caption: None,
+            trailing_subcaption: None,
+            app_name: Some("GamePigeon"),
+            ldtext: Some("Dots & Boxes"),
+        };
+
+        let expected = exporter.format_generic_app(
+            &balloon,
+            "com.gamerdelights.gamepigeon.ext.MessagesExtension",
+            &mut vec![],
+            "  ",
+        );
+        let actual = "  GamePigeon message: Game of Dots & Boxes\n  Your move.";
+
+        assert_eq!(expected, actual);
+    }
 }
 
 #[cfg(test)]
@@ -2116,7 +2826,7 @@ mod edited_tests {
     use crate::{exporters::exporter::Writer, Exporter, TXT};
     use imessage_database::{
         message_types::edited::{EditStatus, EditedMessage, EditedMessagePart},
-        util::typedstream::parser::TypedStreamReader,
+        util::typedstream::{models::Archivable, parser::TypedStreamReader},
     };
 
     #[test]
@@ -2169,7 +2879,10 @@ mod edited_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "May 17, 2022  5:29:42 PM\nMe\nFrom arbitrary byte stream:\r\nAttachment missing!\nTo native Rust data structures:\r\nYou unsent this message part 1 hour, 49 seconds after sending!\n\n";
@@ -2206,7 +2919,10 @@ mod edited_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_message(&message, 0).unwrap();
         let expected = "May 17, 2022  5:29:42 PM\nMe\nFrom arbitrary byte stream:\r\nAttachment missing!\nTo native Rust data structures:\r\n\n";
@@ -2248,7 +2964,10 @@ mod edited_tests {
         file.read_to_end(&mut bytes).unwrap();
 
         let mut parser = TypedStreamReader::from(&bytes);
-        message.components = parser.parse().ok();
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
 
         let actual = exporter.format_announcement(&message);
         let expected = "May 17, 2022  5:29:42 PM You unsent a message!\n\n";
@@ -2256,3 +2975,128 @@ mod edited_tests {
         assert_eq!(actual, expected);
     }
 }
+
+#[cfg(test)]
+mod text_effect_tests {
+    use std::{
+        env::{current_dir, set_var},
+        fs::File,
+        io::Read,
+    };
+
+    use super::tests::{blank, fake_config, fake_options};
+
+    use crate::{
+        exporters::exporter::{TextEffectFormatter, Writer},
+        Exporter, TXT,
+    };
+    use imessage_database::{
+        message_types::text_effects::Style,
+        util::typedstream::{models::Archivable, parser::TypedStreamReader},
+    };
+
+    #[test]
+    fn can_format_txt_mention_end_to_end() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Test Dad ".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let typedstream_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/typedstream/Mention");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "May 17, 2022  5:29:42 PM\nMe\nTest @Dad (+15558675309) \n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_txt_mention_resolved() {
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(10, "+15558675309 dad@example.com".to_string());
+        let exporter = TXT::new(&config).unwrap();
+
+        let actual = exporter.format_mention("Dad", "+15558675309");
+        let expected = "@Dad (+15558675309)";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_txt_text_styles_end_to_end() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Bold underline italic strikethrough all four".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let typedstream_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/typedstream/TextStyles");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        message.components = parser
+            .parse()
+            .ok()
+            .map(|items| items.into_iter().map(Archivable::into_owned).collect());
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "May 17, 2022  5:29:42 PM\nMe\n**Bold** _underline_ *italic* ~~strikethrough~~ all *_~~**four**~~_*\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_txt_styles_whitespace() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let actual = exporter.format_styles(" Bold ", &[Style::Bold, Style::Strikethrough]);
+        let expected = " ~~**Bold**~~ ";
+
+        assert_eq!(actual, expected);
+    }
+}
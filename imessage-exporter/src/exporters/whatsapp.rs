@@ -0,0 +1,542 @@
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::{create_dir_all, rename, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::exporter::Exporter,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::Announcement,
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::Message,
+        table::{Table, ME, ORPHANED, YOU},
+    },
+};
+
+/// Name of the transcript written to each conversation's folder
+const CHAT_FILENAME: &str = "_chat.txt";
+/// Left-to-right mark that WhatsApp places before attachment markers and system messages
+const LTR_MARK: char = '\u{200E}';
+/// Date format WhatsApp uses at the start of each line
+const DATE_FORMAT: &str = "%d/%m/%Y, %H:%M:%S";
+
+/// Exports conversations in the layout of WhatsApp's "Export Chat" feature
+///
+/// Each conversation is written to `_chat.txt` in its own folder. Copied attachments are moved
+/// into that folder and named the way WhatsApp names media, so tools that read WhatsApp exports
+/// can find them from their `<attached: ...>` markers.
+pub struct WhatsApp<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
+}
+
+impl<'a> Exporter<'a> for WhatsApp<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let orphaned = WhatsApp::chat_path(config, ORPHANED)?;
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        Ok(WhatsApp {
+            config,
+            files: HashMap::new(),
+            orphaned: BufWriter::new(file),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as WhatsApp chats...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // WhatsApp does not include reactions in its exports
+            if !msg.is_tapback() {
+                let lines = match msg.is_announcement() {
+                    true => self.format_announcement(&msg),
+                    false => self.format_message(&msg)?,
+                };
+                WhatsApp::write_to_file(self.get_or_create_file(&msg)?, &lines)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        for buf in self.files.values_mut() {
+            buf.flush().map_err(RuntimeError::DiskError)?;
+        }
+        self.orphaned.flush().map_err(RuntimeError::DiskError)
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                return match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let path = WhatsApp::chat_path(self.config, entry.key())?;
+
+                        let file = File::options()
+                            .append(true)
+                            .create(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        Ok(entry.insert(BufWriter::new(file)))
+                    }
+                };
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> WhatsApp<'a> {
+    /// Build the path to a conversation's transcript, creating its folder
+    fn chat_path(config: &Config, filename: &str) -> Result<PathBuf, RuntimeError> {
+        let folder = config.options.export_path.join(filename);
+        create_dir_all(&folder).map_err(|err| RuntimeError::CreateError(err, folder.clone()))?;
+        Ok(folder.join(CHAT_FILENAME))
+    }
+
+    /// Get the folder a message's transcript is written to
+    fn chat_folder(&self, message: &Message) -> PathBuf {
+        let filename = match self.config.conversation(message) {
+            Some((chatroom, _)) => self.config.filename(chatroom),
+            None => String::from(ORPHANED),
+        };
+        self.config.options.export_path.join(filename)
+    }
+
+    /// Format the prefix of a line, i.e. `[17/05/2022, 17:29:42] Sender: `
+    fn line_prefix(&self, message: &Message, who: &str) -> String {
+        let date = message
+            .date(&self.config.offset)
+            .map(|date| date.format(DATE_FORMAT).to_string())
+            .unwrap_or_default();
+        format!("[{date}] {who}: ")
+    }
+
+    /// Format a message as a line of text followed by a line for each attachment
+    fn format_message(&self, message: &Message) -> Result<String, RuntimeError> {
+        let who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        let prefix = self.line_prefix(message, who);
+        let mut lines = String::new();
+
+        if let Some(text) = self.config.message_text(message) {
+            // Attachments get their own lines
+            let text = text.replace('\u{FFFC}', "");
+            let text = text.trim();
+            if !text.is_empty() {
+                lines.push_str(&prefix);
+                lines.push_str(text);
+                if message.is_edited() {
+                    lines.push_str(&format!(" {LTR_MARK}<This message was edited>"));
+                }
+                lines.push('\n');
+            }
+        }
+
+        if message.has_attachments() {
+            let mut attachments = Attachment::from_message(&self.config.db, message)
+                .map_err(RuntimeError::DatabaseError)?;
+            for attachment in attachments.iter_mut() {
+                let name = self.format_attachment(attachment, message);
+                lines.push_str(&format!("{prefix}{LTR_MARK}<attached: {name}>\n"));
+            }
+            // Lines without a prefix continue the message above them
+            if let Some(transcription) = message.audio_transcription() {
+                lines.push_str(&format!("Transcription: {transcription}\n"));
+            }
+        }
+
+        Ok(lines)
+    }
+
+    /// Get the name of an attachment, moving it into the conversation's folder if it was copied
+    fn format_attachment(&self, attachment: &mut Attachment, message: &Message) -> String {
+        let copied = self.config.options.attachment_manager.handle_attachment(
+            message,
+            attachment,
+            self.config,
+        );
+
+        match (copied, &attachment.copied_path) {
+            (Some(()), Some(from)) => {
+                let name = media_filename(attachment, message, self.config, from);
+                let to = self.chat_folder(message).join(&name);
+                match rename(from, &to) {
+                    Ok(()) => {
+                        attachment.copied_path = Some(to);
+                        name
+                    }
+                    Err(why) => {
+                        warn!("Unable to move {from:?} to {to:?}: {why}");
+                        attachment.filename().to_string()
+                    }
+                }
+            }
+            _ => attachment.filename().to_string(),
+        }
+    }
+
+    /// Format an announcement as a system message from the conversation
+    fn format_announcement(&self, message: &Message) -> String {
+        let mut who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+
+        let sentence = match message.get_announcement() {
+            Some(Announcement::NameChange(name)) => {
+                format!("{who} changed the group name to \u{201C}{name}\u{201D}")
+            }
+            Some(Announcement::PhotoChange) => format!("{who} changed this group's icon"),
+            Some(Announcement::PhotoRemoved) => format!("{who} deleted this group's icon"),
+            Some(Announcement::ParticipantAdded(handle)) => format!(
+                "{who} added {}",
+                self.config
+                    .who(Some(handle), false, &message.destination_caller_id)
+            ),
+            Some(Announcement::ParticipantRemoved(handle)) => format!(
+                "{who} removed {}",
+                self.config
+                    .who(Some(handle), false, &message.destination_caller_id)
+            ),
+            Some(Announcement::ParticipantLeft) => format!("{who} left"),
+            Some(Announcement::AudioMessageKept) => format!("{who} kept an audio message"),
+            Some(Announcement::UnknownEvent(num)) => {
+                format!("{who} performed unknown system event {num}")
+            }
+            Some(Announcement::Unknown(num)) => format!("{who} performed unknown action {num}"),
+            Some(Announcement::FullyUnsent) => format!("{who} deleted a message"),
+            None => String::from("Unable to format announcement!"),
+        };
+        let chat = match self.config.conversation(message) {
+            Some((chatroom, _)) => chatroom.name(),
+            None => ORPHANED,
+        };
+        format!("{}{LTR_MARK}{sentence}\n", self.line_prefix(message, chat))
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+/// Name a copied attachment the way WhatsApp names media, i.e. `00000012-PHOTO-2022-05-17-17-29-42.jpg`
+///
+/// Other files keep their original name after the number, i.e. `00000013-Report.pdf`.
+fn media_filename(
+    attachment: &Attachment,
+    message: &Message,
+    config: &Config,
+    copy: &Path,
+) -> String {
+    let kind = match attachment.mime_type() {
+        MediaType::Image(_) => Some("PHOTO"),
+        MediaType::Video(_) => Some("VIDEO"),
+        MediaType::Audio(_) => Some("AUDIO"),
+        _ => None,
+    };
+
+    match kind {
+        Some(kind) => {
+            let date = message
+                .date(&config.offset)
+                .map(|date| date.format("%Y-%m-%d-%H-%M-%S").to_string())
+                .unwrap_or_default();
+            // Copies may have been converted to a different format
+            let extension = copy
+                .extension()
+                .map(|ext| format!(".{}", ext.to_string_lossy()))
+                .unwrap_or_default();
+            format!("{:08}-{kind}-{date}{extension}", attachment.rowid)
+        }
+        None => format!(
+            "{:08}-{}",
+            attachment.rowid,
+            attachment.filename().replace('/', "_")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env::set_var, fs::create_dir_all, path::PathBuf};
+
+    use crate::{
+        app::{attachment_manager::AttachmentManager, chat_type::ChatType, theme::Theme},
+        Config, Exporter, Options, WhatsApp,
+    };
+    use imessage_database::{
+        tables::{messages::Message, table::get_connection},
+        util::{
+            dates::get_offset, dirs::default_db_path, platform::Platform,
+            query_context::QueryContext,
+        },
+    };
+
+    pub(super) fn blank() -> Message {
+        Message {
+            rowid: i32::default(),
+            guid: String::default(),
+            text: None,
+            service: Some("iMessage".to_string()),
+            handle_id: Some(i32::default()),
+            destination_caller_id: None,
+            subject: None,
+            date: i64::default(),
+            date_read: i64::default(),
+            date_delivered: i64::default(),
+            is_from_me: false,
+            is_read: false,
+            item_type: 0,
+            other_handle: 0,
+            share_status: false,
+            share_direction: false,
+            group_title: None,
+            group_action_type: 0,
+            associated_message_guid: None,
+            associated_message_type: Some(i32::default()),
+            balloon_bundle_id: None,
+            expressive_send_style_id: None,
+            thread_originator_guid: None,
+            thread_originator_part: None,
+            date_edited: 0,
+            is_audio_message: false,
+            expire_state: 0,
+            chat_id: None,
+            associated_message_emoji: None,
+            num_attachments: 0,
+            deleted_from: None,
+            num_replies: 0,
+            components: None,
+            edited_parts: None,
+        }
+    }
+
+    pub(super) fn fake_options() -> Options {
+        let export_path = PathBuf::from("/tmp/whatsapp");
+        create_dir_all(&export_path).unwrap();
+
+        Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::Disabled,
+            diagnostic: false,
+            export_type: None,
+            export_path,
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            ignore_disk_space: false,
+            site: false,
+            template_dir: None,
+            txt_format: None,
+            paginate: None,
+            theme: Theme::default(),
+            single_file: false,
+            conversations: vec![],
+            excluded: vec![],
+            chat_type: ChatType::default(),
+            skipped_attachment_types: vec![],
+            skip_short_codes: false,
+            known_contacts: None,
+            pinning_file: None,
+            dedupe_attachments: false,
+            thumbnails: false,
+            max_attachment_size: None,
+            strip_metadata: false,
+            attachment_manifest: false,
+            missing_media_report: false,
+            attachment_name: None,
+            verify: false,
+            receipts: false,
+            contacts_file: None,
+            names_file: None,
+            merge_identities: false,
+            merge_services: false,
+            stats: false,
+            country_code: None,
+            me_handle: None,
+            me_avatar: None,
+            pick: false,
+            backup_password: None,
+            merge_databases: vec![],
+        }
+    }
+
+    pub(super) fn fake_config(options: Options) -> Config {
+        let db = get_connection(&options.get_db_path()).unwrap();
+        Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            contact_names: HashMap::new(),
+            contact_avatars: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: get_offset(),
+            db,
+            converter: None,
+            deduplicator: None,
+            media_converter: None,
+            document_converter: None,
+            manifest: None,
+            missing_media: None,
+            attachment_namer: None,
+            verifier: None,
+            #[cfg(feature = "encrypted-backups")]
+            decrypted_backup: None,
+            merged_database: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = WhatsApp::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+        assert!(PathBuf::from("/tmp/whatsapp/orphaned/_chat.txt").exists());
+    }
+
+    #[test]
+    fn can_format_message_from_me() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = WhatsApp::new(&config).unwrap();
+
+        let mut message = blank();
+        message.text = Some("Hello world\nSecond line".to_string());
+        message.is_from_me = true;
+        message.date = 674526582885055488;
+
+        assert_eq!(
+            exporter.format_message(&message).unwrap(),
+            "[17/05/2022, 17:29:42] Me: Hello world\nSecond line\n"
+        );
+    }
+
+    #[test]
+    fn can_format_message_from_them() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = WhatsApp::new(&config).unwrap();
+
+        let mut message = blank();
+        message.handle_id = Some(999999);
+        message.text = Some("Hi".to_string());
+        message.date = 674526582885055488;
+        message.date_edited = 674526582885055488;
+
+        assert_eq!(
+            exporter.format_message(&message).unwrap(),
+            "[17/05/2022, 17:29:42] Sample Contact: Hi \u{200E}<This message was edited>\n"
+        );
+    }
+
+    #[test]
+    fn can_format_announcement() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = WhatsApp::new(&config).unwrap();
+
+        let mut message = blank();
+        message.is_from_me = true;
+        message.date = 674526582885055488;
+        message.group_title = Some("Book Club".to_string());
+
+        assert_eq!(
+            exporter.format_announcement(&message),
+            "[17/05/2022, 17:29:42] orphaned: \u{200E}You changed the group name to \u{201C}Book Club\u{201D}\n"
+        );
+    }
+}
@@ -3,16 +3,35 @@
 mod app;
 mod exporters;
 
-pub use exporters::{exporter::Exporter, html::HTML, txt::TXT};
+pub use exporters::{
+    attachments::Attachments, day_one::DayOne, epub::EPUB, exporter::Exporter, html::HTML,
+    latex::LaTeX, maildir::Maildir, markdown::Markdown, matrix::Matrix, mbox::MBOX, mhtml::MHTML,
+    ndjson::NDJSON, obsidian::Obsidian, pdf::PDF, sqlite::SQLite, txt::TXT, whatsapp::WhatsApp,
+};
+
+#[cfg(feature = "parquet")]
+pub use exporters::parquet::Parquet;
 
 use app::{
-    options::{from_command_line, Options},
+    debug, list, logger,
+    options::{from_command_line, Options, COMMAND_DEBUG, COMMAND_LIST},
     runtime::Config,
 };
 
 fn main() {
     // Get args from command line
     let args = from_command_line();
+    // Start logging before building the options, which can emit warnings
+    logger::init(&args);
+
+    // Debugging tools read the database directly, so they do not need export options
+    if let Some((COMMAND_DEBUG, debug_args)) = args.subcommand() {
+        if let Err(why) = debug::run(debug_args) {
+            eprintln!("Unable to debug: {why}");
+        }
+        return;
+    }
+
     // Create application options
     let options = Options::from_args(&args);
 
@@ -22,11 +41,18 @@ fn main() {
     } else {
         match options {
             Ok(options) => match Config::new(options) {
-                Ok(app) => {
-                    if let Err(why) = app.start() {
-                        eprintln!("Unable to export: {why}");
+                Ok(app) => match args.subcommand() {
+                    Some((COMMAND_LIST, list_args)) => {
+                        if let Err(why) = list::run(&app, list_args) {
+                            eprintln!("Unable to list conversations: {why}");
+                        }
                     }
-                }
+                    _ => {
+                        if let Err(why) = app.start() {
+                            eprintln!("Unable to export: {why}");
+                        }
+                    }
+                },
                 Err(why) => {
                     eprintln!("Invalid configuration: {why}");
                 }